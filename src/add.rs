@@ -4,12 +4,16 @@ use std::path::PathBuf;
 use serde_json::{json, Value};
 
 fn mcphub_dir() -> PathBuf {
-    dirs::home_dir().unwrap_or_default().join(".McpHub")
+    crate::config::hub_dir().unwrap_or_default()
 }
 
 pub async fn run() {
     println!("McpHub — Add Server");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if let Some(path) = crate::config::dedicated_config_path().filter(|p| crate::config::is_toml(p)) {
+        println!("Servers are configured in {}; add a [servers.<name>] table there.", path.display());
+        return;
+    }
 
     print!("Server name (e.g. github): ");
     io::stdout().flush().unwrap();
@@ -73,6 +77,6 @@ pub async fn run() {
     }
     fs::write(&path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
     
-    println!("✓ Added '{}' to ~/.McpHub/config.json", name);
+    println!("✓ Added '{}' to {}", name, path.display());
     println!("Run 'McpHub generate' to rebuild cache if needed.");
 }
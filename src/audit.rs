@@ -0,0 +1,263 @@
+/// Append-only audit trail of `tools/call`s, one JSON object per line.
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+/// Argument keys redacted by default (matched case-insensitively, as substrings).
+const DEFAULT_REDACT_KEYS: &[&str] = &["password", "secret", "token", "apikey", "api_key", "authorization", "cookie"];
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    /// Defaults to `~/.McpHub/audit.log`, next to `mcphub.log`.
+    pub path: Option<PathBuf>,
+    /// Arguments whose key contains one of these are logged as `[REDACTED]`.
+    pub redact_keys: Vec<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            redact_keys: DEFAULT_REDACT_KEYS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+}
+
+/// How an audited call ended.
+pub enum Outcome {
+    Ok,
+    /// A JSON-RPC error (with its message) or an `isError` result.
+    Error(Option<String>),
+    Cancelled,
+}
+
+pub struct AuditEntry<'a> {
+    pub session: &'a str,
+    /// None for the hub's own tools and names that match no server.
+    pub server: Option<&'a str>,
+    pub tool: &'a str,
+    pub arguments: &'a Value,
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    redact_keys: Vec<String>,
+    /// Lines for the writer thread, started on the first entry.
+    writer: Mutex<Option<(mpsc::Sender<String>, std::thread::JoinHandle<()>)>>,
+}
+
+impl AuditLog {
+    pub fn new(config: &AuditConfig) -> Self {
+        let path = config.enabled.then(|| {
+            config.path.clone().unwrap_or_else(|| crate::config::hub_dir().unwrap_or_default().join("audit.log"))
+        });
+        Self {
+            path,
+            redact_keys: config.redact_keys.iter().map(|k| k.to_lowercase()).collect(),
+            writer: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Queue `entry` for appending. The file is written on a thread of its
+    /// own, so a slow disk never holds up a call; write failures are logged
+    /// and otherwise ignored so auditing never fails one.
+    pub fn record(&self, entry: AuditEntry) {
+        let Some(path) = &self.path else { return };
+        let line = self.line(&entry);
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let (lines, _) = writer.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            let path = path.clone();
+            (tx, std::thread::spawn(move || write_lines(&path, rx)))
+        });
+        let _ = lines.send(line);
+    }
+
+    /// Wait for queued entries to be written. Later entries start a new
+    /// writer.
+    pub fn flush(&self) {
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((lines, thread)) = writer {
+            drop(lines);
+            let _ = thread.join();
+        }
+    }
+
+    fn line(&self, entry: &AuditEntry) -> String {
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut line = serde_json::json!({
+            "ts": ts,
+            "session": entry.session,
+            "server": entry.server,
+            "tool": entry.tool,
+            "arguments": self.redact(entry.arguments),
+            "duration_ms": entry.duration.as_millis() as u64,
+        });
+        match &entry.outcome {
+            Outcome::Ok => line["outcome"] = "ok".into(),
+            Outcome::Cancelled => line["outcome"] = "cancelled".into(),
+            Outcome::Error(message) => {
+                line["outcome"] = "error".into();
+                if let Some(message) = message {
+                    line["error"] = message.as_str().into();
+                }
+            }
+        }
+        line.to_string()
+    }
+
+    fn redact(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(key, v)| {
+                    let lower = key.to_lowercase();
+                    if self.redact_keys.iter().any(|k| lower.contains(k.as_str())) {
+                        (key.clone(), Value::from(REDACTED))
+                    } else {
+                        (key.clone(), self.redact(v))
+                    }
+                })
+                .collect(),
+            Value::Array(items) => items.iter().map(|v| self.redact(v)).collect(),
+            v => v.clone(),
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Writer thread: append each line, opening the file on the first one (and
+/// again after a failed write). Ends when the log is flushed or dropped.
+fn write_lines(path: &Path, lines: mpsc::Receiver<String>) {
+    let mut file: Option<File> = None;
+    for line in lines {
+        if file.is_none() {
+            match open_log(path) {
+                Ok(f) => file = Some(f),
+                Err(e) => {
+                    tracing::warn!("Cannot open audit log {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+        }
+        if let Some(f) = file.as_mut() {
+            if let Err(e) = writeln!(f, "{}", line) {
+                tracing::warn!("Failed to write audit log: {}", e);
+                file = None;
+            }
+        }
+    }
+}
+
+/// Arguments can hold anything a client sends, so only the owner may read
+/// the log.
+fn open_log(path: &Path) -> std::io::Result<File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Parse `settings.audit`.
+pub fn parse_audit(value: &Value) -> Result<AuditConfig, String> {
+    let mut config = AuditConfig::default();
+    if let Some(v) = value.get("enabled") {
+        config.enabled = v.as_bool().ok_or("enabled must be true or false")?;
+    }
+    if let Some(v) = value.get("path") {
+        let path = v.as_str().filter(|p| !p.is_empty()).ok_or("path must be a file path")?;
+        config.path = Some(PathBuf::from(path));
+    }
+    if let Some(v) = value.get("redactKeys") {
+        config.redact_keys = v
+            .as_array()
+            .and_then(|keys| keys.iter().map(|k| k.as_str().map(String::from)).collect::<Option<Vec<_>>>())
+            .ok_or("redactKeys must be a list of argument names")?;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_appended_with_secrets_redacted() {
+        let path = std::env::temp_dir().join(format!("mcphub-audit-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = parse_audit(&serde_json::json!({"enabled": true, "path": path.to_str().unwrap()})).unwrap();
+        let log = AuditLog::new(&config);
+        let arguments = serde_json::json!({"repo": "a/b", "githubToken": "ghp_x", "headers": [{"Authorization": "Bearer y"}]});
+        log.record(AuditEntry {
+            session: "s1",
+            server: Some("github"),
+            tool: "create_issue",
+            arguments: &arguments,
+            duration: Duration::from_millis(12),
+            outcome: Outcome::Ok,
+        });
+        log.record(AuditEntry {
+            session: "s1",
+            server: None,
+            tool: "nope",
+            arguments: &Value::Null,
+            duration: Duration::ZERO,
+            outcome: Outcome::Error(Some("Unknown tool: nope".into())),
+        });
+
+        log.flush();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<Value> = written.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["server"], "github");
+        assert_eq!(lines[0]["duration_ms"], 12);
+        assert_eq!(lines[0]["outcome"], "ok");
+        assert_eq!(lines[0]["arguments"], serde_json::json!({"repo": "a/b", "githubToken": REDACTED, "headers": [{"Authorization": REDACTED}]}));
+        assert!(!written.contains("ghp_x"));
+        assert_eq!(lines[1]["server"], Value::Null);
+        assert_eq!(lines[1]["error"], "Unknown tool: nope");
+    }
+
+    #[test]
+    fn test_disabled_log_is_inert() {
+        assert!(!AuditLog::new(&AuditConfig::default()).enabled());
+        let config = parse_audit(&serde_json::json!({"enabled": false})).unwrap();
+        assert!(!AuditLog::new(&config).enabled());
+        assert!(parse_audit(&serde_json::json!({"redactKeys": "token"})).is_err());
+    }
+}
@@ -1,18 +1,16 @@
 use crate::config::auto_detect;
 use std::time::Instant;
-use std::sync::Arc;
-use std::collections::HashMap;
-use tokio::sync::Mutex;
 
 pub async fn run() {
     let config = auto_detect();
     println!("{:<15} | {:<10} | {:<10} | {:<8} | {:<8}", "Server", "Start", "Ping", "Tools", "RAM");
     println!("{:-<15}-|-{:-<10}-|-{:-<10}-|-{:-<8}-|-{:-<8}", "", "", "", "", "");
 
-    let manager = std::sync::Arc::new(crate::child::ChildManager::new(
-        config.servers.clone(),
-        300_000,
-    ));
+    let manager = std::sync::Arc::new(
+        crate::child::ChildManager::new(config.servers.clone(), 300_000)
+            .with_timeouts(config.init_timeout_secs, config.call_timeout_secs)
+            .with_init_retries(config.init_retries, config.init_retry_backoff_ms),
+    );
 
     let mut names: Vec<_> = config.servers.keys().cloned().collect();
     names.sort();
@@ -0,0 +1,268 @@
+/// Per-backend circuit breaker.
+/// After `failure_threshold` consecutive failures within `window`, the circuit
+/// opens and calls fail fast for `cooldown`. Then one probe call is let
+/// through (half-open): success closes the circuit, failure reopens it.
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakerConfig {
+    /// Consecutive failures that open the circuit (0 disables the breaker).
+    pub failure_threshold: u32,
+    /// Failures further apart than this don't count as one streak.
+    pub window: Duration,
+    /// How long the circuit stays open before a probe is allowed.
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Thresholds set in a `circuitBreaker` object; unset ones keep the base value.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BreakerOverride {
+    pub failure_threshold: Option<u32>,
+    pub window: Option<Duration>,
+    pub cooldown: Option<Duration>,
+}
+
+impl BreakerOverride {
+    /// Parse `{"failureThreshold", "windowSecs", "cooldownSecs"}`.
+    pub fn parse(value: &Value) -> Self {
+        let secs = |key: &str| value.get(key).and_then(|v| v.as_u64()).map(Duration::from_secs);
+        Self {
+            failure_threshold: value.get("failureThreshold").and_then(|v| v.as_u64()).map(|t| t as u32),
+            window: secs("windowSecs"),
+            cooldown: secs("cooldownSecs"),
+        }
+    }
+
+    pub fn apply(&self, base: BreakerConfig) -> BreakerConfig {
+        BreakerConfig {
+            failure_threshold: self.failure_threshold.unwrap_or(base.failure_threshold),
+            window: self.window.unwrap_or(base.window),
+            cooldown: self.cooldown.unwrap_or(base.cooldown),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// Snapshot for /api/servers and /api/metrics.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    /// Times the circuit has opened since startup.
+    pub opened_count: u64,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: BreakerState,
+    consecutive_failures: u32,
+    streak_started: Option<Instant>,
+    opened_at: Option<Instant>,
+    /// A half-open probe is in flight; other calls keep failing fast.
+    probing: bool,
+    opened_count: u64,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            streak_started: None,
+            opened_at: None,
+            probing: false,
+            opened_count: 0,
+        }
+    }
+
+    /// Take new thresholds (after a config reload) without losing the state.
+    pub fn set_config(&mut self, config: BreakerConfig) {
+        self.config = config;
+    }
+
+    /// Open or probing: calls fail fast.
+    pub fn is_tripped(&self) -> bool {
+        self.config.failure_threshold > 0 && self.state != BreakerState::Closed
+    }
+
+    /// Whether a call may go to the backend now. Err carries the seconds
+    /// left until the next probe.
+    pub fn allow(&mut self, now: Instant) -> Result<(), u64> {
+        if self.config.failure_threshold == 0 {
+            return Ok(());
+        }
+        match self.state {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open => {
+                let opened_at = self.opened_at.unwrap_or(now);
+                let elapsed = now.saturating_duration_since(opened_at);
+                if elapsed >= self.config.cooldown {
+                    self.state = BreakerState::HalfOpen;
+                    self.probing = true;
+                    Ok(())
+                } else {
+                    Err((self.config.cooldown - elapsed).as_secs().max(1))
+                }
+            }
+            BreakerState::HalfOpen if !self.probing => {
+                self.probing = true;
+                Ok(())
+            }
+            BreakerState::HalfOpen => Err(1),
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.streak_started = None;
+        self.probing = false;
+    }
+
+    /// Record an outcome that says nothing about backend health. Frees the
+    /// half-open probe slot so the next call can probe instead.
+    pub fn record_neutral(&mut self) {
+        self.probing = false;
+    }
+
+    /// Record a failed call. Returns true if this opened the circuit.
+    pub fn record_failure(&mut self, now: Instant) -> bool {
+        if self.config.failure_threshold == 0 {
+            return false;
+        }
+        if self.state == BreakerState::HalfOpen {
+            self.open(now);
+            return true;
+        }
+        let in_window = self
+            .streak_started
+            .is_some_and(|start| now.saturating_duration_since(start) <= self.config.window);
+        if !in_window {
+            self.streak_started = Some(now);
+            self.consecutive_failures = 0;
+        }
+        self.consecutive_failures += 1;
+        if self.state == BreakerState::Closed && self.consecutive_failures >= self.config.failure_threshold {
+            self.open(now);
+            return true;
+        }
+        false
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.state = BreakerState::Open;
+        self.opened_at = Some(now);
+        self.probing = false;
+        self.opened_count += 1;
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        BreakerStatus {
+            state: self.state,
+            consecutive_failures: self.consecutive_failures,
+            opened_count: self.opened_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig {
+            failure_threshold: 3,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(10),
+        })
+    }
+
+    #[test]
+    fn test_opens_after_threshold_then_closes_on_probe_success() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        assert!(!b.record_failure(t0));
+        assert!(!b.record_failure(t0));
+        assert!(b.record_failure(t0));
+        assert_eq!(b.status().state, BreakerState::Open);
+
+        // Fails fast during the cool-down
+        assert_eq!(b.allow(t0 + Duration::from_secs(4)), Err(6));
+
+        // Half-open: one probe goes through, concurrent calls still fail fast
+        let t1 = t0 + Duration::from_secs(10);
+        assert!(b.allow(t1).is_ok());
+        assert_eq!(b.status().state, BreakerState::HalfOpen);
+        assert!(b.allow(t1).is_err());
+
+        b.record_success();
+        assert_eq!(b.status().state, BreakerState::Closed);
+        assert!(b.allow(t1).is_ok());
+        assert_eq!(b.status().opened_count, 1);
+    }
+
+    #[test]
+    fn test_failed_probe_reopens() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        for _ in 0..3 {
+            b.record_failure(t0);
+        }
+        let t1 = t0 + Duration::from_secs(10);
+        assert!(b.allow(t1).is_ok());
+        assert!(b.record_failure(t1));
+        assert_eq!(b.status().state, BreakerState::Open);
+        assert!(b.allow(t1 + Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_failures_outside_window_dont_accumulate() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        b.record_failure(t0);
+        b.record_failure(t0);
+        // Third failure arrives after the window: starts a new streak
+        assert!(!b.record_failure(t0 + Duration::from_secs(61)));
+        assert_eq!(b.status().consecutive_failures, 1);
+    }
+
+    #[test]
+    fn test_override_keeps_unset_thresholds() {
+        let o = BreakerOverride::parse(&serde_json::json!({"failureThreshold": 2, "cooldownSecs": 5}));
+        let config = o.apply(BreakerConfig::default());
+        assert_eq!(config.failure_threshold, 2);
+        assert_eq!(config.window, Duration::from_secs(60));
+        assert_eq!(config.cooldown, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_success_resets_streak() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        b.record_failure(t0);
+        b.record_failure(t0);
+        b.record_success();
+        assert!(!b.record_failure(t0));
+        assert_eq!(b.status().state, BreakerState::Closed);
+    }
+}
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use crate::protocol::ToolDef;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::protocol::{PromptDef, ResourceDef, ToolDef};
 
 #[derive(Serialize, Deserialize)]
 pub struct SchemaCache {
@@ -10,73 +13,1295 @@ pub struct SchemaCache {
     pub servers: HashMap<String, Vec<ToolDef>>,
     #[serde(default)]
     pub errors: HashMap<String, String>,
+    /// Unix time (secs) each server entry was last written. Missing for caches
+    /// written by older versions.
+    #[serde(default)]
+    pub updated_at: HashMap<String, u64>,
+    /// Initialize capabilities reported by each server at generate time.
+    #[serde(default)]
+    pub capabilities: HashMap<String, serde_json::Value>,
+    /// Prompt definitions (with argument schemas) of servers that have prompts.
+    #[serde(default)]
+    pub prompts: HashMap<String, Vec<PromptDef>>,
+    /// Resources listed by servers that have resources.
+    #[serde(default)]
+    pub resources: HashMap<String, Vec<ResourceDef>>,
+    /// `servers_checksum` of `servers` when written; missing for caches
+    /// written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// Layout of the file (`CACHE_FORMAT` when written); 0 for caches from
+    /// before it was recorded, which are format 1.
+    #[serde(default)]
+    pub format: u32,
+}
+
+impl SchemaCache {
+    fn empty() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            servers: HashMap::new(),
+            errors: HashMap::new(),
+            updated_at: HashMap::new(),
+            capabilities: HashMap::new(),
+            prompts: HashMap::new(),
+            resources: HashMap::new(),
+            checksum: None,
+            format: CACHE_FORMAT,
+        }
+    }
+}
+
+/// Layout of the cache file this version writes. Bump it with an upgrader
+/// in `UPGRADES` when fields are renamed or restructured; fields that are
+/// only added don't need one.
+pub const CACHE_FORMAT: u32 = 2;
+
+/// `UPGRADES[i]` turns the raw JSON of format `i + 1` into format `i + 2`.
+const UPGRADES: &[fn(&mut serde_json::Map<String, serde_json::Value>)] = &[upgrade_v1];
+
+/// Format 1 had only `version`, `servers` and `errors`: give the maps added
+/// since an empty value, so every entry counts as never timestamped.
+fn upgrade_v1(raw: &mut serde_json::Map<String, serde_json::Value>) {
+    for key in ["errors", "updated_at", "capabilities", "prompts", "resources"] {
+        raw.entry(key).or_insert_with(|| serde_json::json!({}));
+    }
+}
+
+/// Run the upgraders from the stored format up to `CACHE_FORMAT`. The
+/// format field itself is left as stored, so callers can tell the file
+/// needs rewriting; a newer format is left alone for serde to read what
+/// it knows.
+fn upgrade(raw: &mut serde_json::Map<String, serde_json::Value>) {
+    let stored = raw.get("format").and_then(|f| f.as_u64()).unwrap_or(1).max(1) as usize;
+    for upgrader in UPGRADES.iter().skip(stored - 1) {
+        upgrader(raw);
+    }
+}
+
+/// Numeric parts of a version, for ordering: "0.10.2" > "0.9.12".
+fn version_parts(version: &str) -> Vec<u64> {
+    version.split(['.', '-', '+']).map_while(|part| part.parse().ok()).collect()
+}
+
+/// Whether `cache` should be rewritten in this version's layout: written in
+/// an older format, or by an older McpHub than `expected`. Caches from
+/// newer versions are read but left as they are on disk.
+fn is_outdated(cache: &SchemaCache, expected: &str) -> bool {
+    let format = cache.format.max(1);
+    format < CACHE_FORMAT || (format == CACHE_FORMAT && version_parts(&cache.version) < version_parts(expected))
+}
+
+/// FNV-1a 64 over the canonical (key-sorted) JSON of `servers`. Catches
+/// corruption on disk, nothing more.
+fn servers_checksum(servers: &HashMap<String, Vec<ToolDef>>) -> String {
+    let canonical = serde_json::to_value(servers).map(|v| v.to_string()).unwrap_or_default();
+    let hash = canonical.bytes().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+/// How a cache written by a different McpHub version is treated.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VersionPolicy {
+    /// Refuse mismatched caches instead of migrating them.
+    pub strict: bool,
+    /// Version the cache must carry; defaults to this build's version.
+    pub pinned: Option<String>,
+}
+
+impl VersionPolicy {
+    fn expected(&self) -> &str {
+        self.pinned.as_deref().unwrap_or(env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// Accept `cache` if it carries the expected version. Otherwise strict mode
+/// refuses it and lenient mode migrates it.
+pub fn check_version(cache: SchemaCache, policy: &VersionPolicy) -> Result<SchemaCache, String> {
+    let expected = policy.expected();
+    if cache.version == expected {
+        return Ok(cache);
+    }
+    if policy.strict {
+        return Err(format!(
+            "Schema cache was written by McpHub {}, expected {} (strict version check). Run 'McpHub generate' to rebuild it.",
+            cache.version, expected
+        ));
+    }
+    Ok(migrate(cache, expected))
+}
+
+/// Bring a cache from another version up to `target`. Fields added since
+/// are filled by their serde defaults on load; entries are kept as they are.
+fn migrate(mut cache: SchemaCache, target: &str) -> SchemaCache {
+    if cache.format > CACHE_FORMAT || version_parts(&cache.version) > version_parts(target) {
+        tracing::warn!(
+            "Schema cache was written by a newer McpHub ({}, format {}); using the parts {} understands",
+            cache.version,
+            cache.format,
+            target
+        );
+    } else {
+        tracing::info!("Migrated schema cache from {} to {}", cache.version, target);
+    }
+    cache.version = target.to_string();
+    cache
+}
+
+/// `load_cache` with the version policy applied; a refused cache is
+/// reported and treated as missing.
+pub fn load_cache_checked(policy: &VersionPolicy) -> Option<SchemaCache> {
+    let cache = load_cache()?;
+    let outdated = is_outdated(&cache, policy.expected());
+    match check_version(cache, policy) {
+        Ok(cache) => {
+            // Written back so the upgrade runs once, not at every load
+            if outdated {
+                let version = cache.version.clone();
+                if let Err(e) = update_cache(|stored| stored.version = version) {
+                    tracing::warn!("Could not rewrite the migrated schema cache: {}", e);
+                }
+            }
+            Some(cache)
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            None
+        }
+    }
+}
+
+/// `load_cache_checked` without the entries written more than `max_age`
+/// ago, as if they had never been fetched. Also returns their names so
+/// they can be fetched again.
+pub fn load_cache_fresh(policy: &VersionPolicy, max_age: Duration) -> Option<(SchemaCache, Vec<String>)> {
+    let mut cache = load_cache_checked(policy)?;
+    let stale = drop_stale(&mut cache, max_age, now_secs());
+    if !stale.is_empty() {
+        tracing::info!("Schema cache entries older than {}s: {}", max_age.as_secs(), stale.join(", "));
+    }
+    Some((cache, stale))
+}
+
+/// Remove server entries written more than `max_age` before `now`. Entries
+/// without a timestamp (older caches) count as stale. Returns their names.
+fn drop_stale(cache: &mut SchemaCache, max_age: Duration, now: u64) -> Vec<String> {
+    let mut stale: Vec<String> = cache
+        .servers
+        .keys()
+        .filter(|name| cache.updated_at.get(*name).is_none_or(|&ts| now.saturating_sub(ts) > max_age.as_secs()))
+        .cloned()
+        .collect();
+    stale.sort();
+    for name in &stale {
+        cache.servers.remove(name);
+        cache.capabilities.remove(name);
+        cache.prompts.remove(name);
+        cache.resources.remove(name);
+    }
+    stale
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 pub fn cache_path() -> Option<PathBuf> {
-    let home = dirs::home_dir()?;
-    Some(home.join(".McpHub").join("schema-cache.json"))
+    Some(crate::config::hub_dir()?.join("schema-cache.json"))
+}
+
+/// Where the gzipped cache (`cache.format: "gzip"`) lives.
+pub fn cache_path_gz() -> Option<PathBuf> {
+    cache_path().map(|path| gz_path(&path))
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    path.with_extension("json.gz")
+}
+
+/// The cache file there is, gzipped or not: the gzipped one if both exist.
+pub fn cache_file() -> Option<PathBuf> {
+    let gz = cache_path_gz()?;
+    if gz.exists() { Some(gz) } else { cache_path() }
+}
+
+fn existing_file(path: &Path) -> PathBuf {
+    let gz = gz_path(path);
+    if gz.exists() { gz } else { path.to_path_buf() }
+}
+
+/// Write the cache gzipped (`cache.format`); it's read in either format.
+static GZIP: AtomicBool = AtomicBool::new(false);
+
+pub fn set_gzip(gzip: bool) {
+    GZIP.store(gzip, Ordering::Relaxed);
+}
+
+/// Advisory lock on the cache's sibling `.lock` file, released on drop.
+/// Writers hold it exclusively across their read-modify-write, so
+/// concurrent processes (a daemon and a manual `repair`) don't drop each
+/// other's entries; readers hold it shared.
+struct CacheLock(fs::File);
+
+impl CacheLock {
+    /// None (after a warning) if the lock file can't be opened or locked;
+    /// the caller goes on unlocked rather than fail.
+    fn acquire(cache: &Path, shared: bool) -> Option<Self> {
+        let path = cache.with_extension("json.lock");
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let locked = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .and_then(|file| {
+                if shared { file.lock_shared()? } else { file.lock()? }
+                Ok(file)
+            });
+        match locked {
+            Ok(file) => Some(Self(file)),
+            Err(e) => {
+                tracing::warn!("Could not lock {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
 }
 
 pub fn load_cache() -> Option<SchemaCache> {
-    let path = cache_path()?;
-    if !path.exists() { return None; }
-    let content = fs::read_to_string(&path).ok()?;
-    let cache: SchemaCache = serde_json::from_str(&content).ok()?;
+    let cache = read_cache()?;
     let total_tools: usize = cache.servers.values().map(|v| v.len()).sum();
-    eprintln!("[McpHub][INFO] Loaded cache: {} servers, {} tools", cache.servers.len(), total_tools);
+    tracing::info!("Loaded cache: {} servers, {} tools", cache.servers.len(), total_tools);
     Some(cache)
 }
 
-pub fn save_cache(servers: &HashMap<String, Vec<ToolDef>>) {
-    save_cache_with_errors(servers, &HashMap::new());
+/// `load_cache` without the log line, for frequent readers. Only what is
+/// on disk: a running hub reads through its `CacheWriter`.
+pub fn read_cache() -> Option<SchemaCache> {
+    read_locked(&cache_path()?)
 }
 
-pub fn save_cache_with_errors(servers: &HashMap<String, Vec<ToolDef>>, errors: &HashMap<String, String>) {
-    let cache = SchemaCache {
-        version: env!("CARGO_PKG_VERSION").to_string(),
-        servers: servers.clone(),
-        errors: errors.clone(),
+/// The cache at `path` under the shared lock, if there is one.
+fn read_locked(path: &Path) -> Option<SchemaCache> {
+    if !existing_file(path).exists() {
+        return None;
+    }
+    let _lock = CacheLock::acquire(path, true);
+    read_cache_at(path)
+}
+
+/// Read the cache whose plain path is `path`, from its gzipped file if
+/// there is one.
+fn read_cache_at(path: &Path) -> Option<SchemaCache> {
+    let file = existing_file(path);
+    let bytes = fs::read(&file).ok()?;
+    let content = if file == path {
+        String::from_utf8(bytes).ok()?
+    } else {
+        match crate::deflate::gunzip(&bytes, usize::MAX).map(String::from_utf8) {
+            Ok(Ok(content)) => content,
+            _ => {
+                tracing::warn!("Schema cache {} is not valid gzip, ignoring it", file.display());
+                return None;
+            }
+        }
     };
-    if let Some(path) = cache_path() {
-        if let Some(parent) = path.parent() {
-            let _ = fs::create_dir_all(parent);
+    let parsed = serde_json::from_str::<serde_json::Value>(&content).and_then(|mut raw| {
+        if let Some(raw) = raw.as_object_mut() {
+            upgrade(raw);
+        }
+        serde_json::from_value::<SchemaCache>(raw)
+    });
+    let cache = match parsed {
+        Ok(cache) => cache,
+        Err(e) => return recover_cache(&content, &e.to_string()),
+    };
+    match &cache.checksum {
+        Some(stored) if *stored != servers_checksum(&cache.servers) => {
+            tracing::warn!("Schema cache checksum mismatch, ignoring it; run 'McpHub generate' to rebuild it");
+            None
+        }
+        _ => Some(cache),
+    }
+}
+
+/// Best-effort reading of a cache that doesn't deserialize as a whole: each
+/// server entry is read on its own, and unreadable ones are logged and
+/// recorded in `errors` so they can be repaired. None if it isn't JSON.
+fn recover_cache(content: &str, error: &str) -> Option<SchemaCache> {
+    let Ok(serde_json::Value::Object(mut raw)) = serde_json::from_str::<serde_json::Value>(content) else {
+        tracing::warn!("Schema cache is unreadable, ignoring it: {}", error);
+        return None;
+    };
+    upgrade(&mut raw);
+    fn field<T: serde::de::DeserializeOwned + Default>(raw: &mut serde_json::Map<String, serde_json::Value>, key: &str) -> T {
+        raw.remove(key).and_then(|v| serde_json::from_value(v).ok()).unwrap_or_default()
+    }
+    fn entries<T: serde::de::DeserializeOwned>(
+        raw: &mut serde_json::Map<String, serde_json::Value>,
+        key: &str,
+        dropped: &mut Vec<(String, String)>,
+    ) -> HashMap<String, T> {
+        let Some(serde_json::Value::Object(map)) = raw.remove(key) else { return HashMap::new() };
+        map.into_iter()
+            .filter_map(|(name, value)| match serde_json::from_value(value) {
+                Ok(entry) => Some((name, entry)),
+                Err(e) => {
+                    dropped.push((name, format!("unreadable {} entry in schema cache: {}", key, e)));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    let mut dropped = Vec::new();
+    let mut cache = SchemaCache {
+        version: raw.get("version").and_then(|v| v.as_str()).unwrap_or("unknown").to_string(),
+        servers: entries(&mut raw, "servers", &mut dropped),
+        errors: field(&mut raw, "errors"),
+        updated_at: field(&mut raw, "updated_at"),
+        capabilities: field(&mut raw, "capabilities"),
+        prompts: entries(&mut raw, "prompts", &mut dropped),
+        resources: entries(&mut raw, "resources", &mut dropped),
+        checksum: None,
+        format: field(&mut raw, "format"),
+    };
+    dropped.sort();
+    for (name, reason) in dropped {
+        tracing::warn!("Schema cache: dropped {} ({}); run 'McpHub generate' to fetch it again", name, reason);
+        cache.servers.remove(&name);
+        cache.errors.insert(name, reason);
+    }
+    Some(cache)
+}
+
+/// Mask secrets in the error messages the cache keeps, which can quote
+/// failed requests. Tool definitions are stored as the servers sent them,
+/// since clients are served from them.
+fn redact_cache(cache: &mut SchemaCache) {
+    for error in cache.errors.values_mut() {
+        *error = crate::redact::redact(error);
+    }
+}
+
+/// Write the cache atomically: write a temp file next to it, then rename over.
+/// Readers (hot-reload watcher, dashboard) never see a half-written file.
+/// Callers hold the exclusive `CacheLock`. Sets the checksum and format.
+fn write_cache_at(path: &Path, cache: &mut SchemaCache) -> Result<(), String> {
+    write_cache_as(path, cache, GZIP.load(Ordering::Relaxed))
+}
+
+/// `write_cache_at` in the given format; gzip is compressed into the temp file.
+fn write_cache_as(path: &Path, cache: &mut SchemaCache, gzip: bool) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    redact_cache(cache);
+    cache.checksum = Some(servers_checksum(&cache.servers));
+    cache.format = CACHE_FORMAT;
+    let json = serde_json::to_string_pretty(cache).map_err(|e| e.to_string())?;
+    // One format on disk at a time: the other file would shadow or outlive this one
+    let (target, other, bytes) = match gzip {
+        true => (gz_path(path), path.to_path_buf(), crate::deflate::gzip(json.as_bytes())),
+        false => (path.to_path_buf(), gz_path(path), json.into_bytes()),
+    };
+    let tmp = target.with_extension(format!("{}.tmp", target.extension().unwrap_or_default().to_string_lossy()));
+    fs::write(&tmp, bytes).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, &target).map_err(|e| format!("Failed to replace {}: {}", target.display(), e))?;
+    match fs::remove_file(&other) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove {}: {}", other.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+/// Apply `update` to the cache at `path` (empty if missing) and write it
+/// back, all under the exclusive lock.
+fn update_cache_at(path: &Path, update: impl FnOnce(&mut SchemaCache)) -> Result<(), String> {
+    let _lock = CacheLock::acquire(path, false);
+    let mut cache = read_cache_at(path).unwrap_or_else(SchemaCache::empty);
+    update(&mut cache);
+    write_cache_at(path, &mut cache)
+}
+
+/// `update_cache_at` on the cache file.
+fn update_cache(update: impl FnOnce(&mut SchemaCache)) -> Result<(), String> {
+    update_cache_at(&cache_path().ok_or("Cannot find home directory")?, update)
+}
+
+/// A server entry written at runtime but not yet on disk.
+#[derive(Clone)]
+struct PendingEntry {
+    name: String,
+    /// Its tools, or why they couldn't be fetched.
+    result: Result<Vec<ToolDef>, String>,
+    at: u64,
+}
+
+fn apply_entry(cache: &mut SchemaCache, entry: &PendingEntry) {
+    match &entry.result {
+        Ok(tools) => {
+            cache.servers.insert(entry.name.clone(), tools.clone());
+            cache.errors.remove(&entry.name);
+        }
+        Err(error) => {
+            cache.errors.insert(entry.name.clone(), error.clone());
+            cache.servers.remove(&entry.name);
+        }
+    }
+    cache.updated_at.insert(entry.name.clone(), entry.at);
+}
+
+const FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// Runtime writes to the schema cache (repairs and errors), owned by
+/// whoever makes them. They come in bursts: preloading, a restart loop, a
+/// refresh of many servers. They are kept in memory, seen by `read` at
+/// once, and written with one read-modify-write of the file by a thread of
+/// their own, `FLUSH_DELAY` after the first of them. Whatever is left is
+/// written on `flush` and when the writer is dropped.
+pub struct CacheWriter {
+    path: Option<PathBuf>,
+    pending: Arc<Mutex<Vec<PendingEntry>>>,
+    /// Serializes flushes, so an entry isn't written twice.
+    flushing: Arc<Mutex<()>>,
+    /// Wakes the flush thread, started on the first entry.
+    flusher: Mutex<Option<(mpsc::Sender<()>, std::thread::JoinHandle<()>)>>,
+}
+
+impl CacheWriter {
+    pub fn new() -> Self {
+        Self::at(cache_path())
+    }
+
+    fn at(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            pending: Arc::new(Mutex::new(Vec::new())),
+            flushing: Arc::new(Mutex::new(())),
+            flusher: Mutex::new(None),
+        }
+    }
+
+    /// Update the cache for a single server (repair). Merged into the file
+    /// at the next flush.
+    pub fn repair_server(&self, name: &str, tools: Vec<ToolDef>) {
+        self.queue(PendingEntry { name: name.to_string(), result: Ok(tools), at: now_secs() });
+    }
+
+    /// Store an error for a server in the cache, at the next flush.
+    pub fn set_server_error(&self, name: &str, error: &str) {
+        self.queue(PendingEntry { name: name.to_string(), result: Err(error.to_string()), at: now_secs() });
+    }
+
+    /// The cache on disk with the entries not written yet applied.
+    pub fn read(&self) -> Option<SchemaCache> {
+        let on_disk = self.path.as_deref().and_then(read_locked);
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if pending.is_empty() {
+            return on_disk;
+        }
+        let mut cache = on_disk.unwrap_or_else(SchemaCache::empty);
+        for entry in &pending {
+            apply_entry(&mut cache, entry);
+        }
+        Some(cache)
+    }
+
+    /// Write pending entries to disk now, e.g. before exiting.
+    pub fn flush(&self) {
+        flush_pending(self.path.as_deref(), &self.pending, &self.flushing);
+    }
+
+    fn queue(&self, entry: PendingEntry) {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).push(entry);
+        let mut flusher = self.flusher.lock().unwrap_or_else(|e| e.into_inner());
+        let (wake, _) = flusher.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            let (path, pending, flushing) = (self.path.clone(), self.pending.clone(), self.flushing.clone());
+            (tx, std::thread::spawn(move || run_flusher(path, pending, flushing, rx)))
+        });
+        let _ = wake.send(());
+    }
+}
+
+impl Default for CacheWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        let flusher = self.flusher.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((wake, thread)) = flusher {
+            drop(wake);
+            let _ = thread.join();
+        }
+        self.flush();
+    }
+}
+
+/// Flush thread: after a wake-up, wait `FLUSH_DELAY` for the rest of the
+/// burst, then write it. A last flush runs when the writer goes away.
+fn run_flusher(
+    path: Option<PathBuf>,
+    pending: Arc<Mutex<Vec<PendingEntry>>>,
+    flushing: Arc<Mutex<()>>,
+    wake: mpsc::Receiver<()>,
+) {
+    while wake.recv().is_ok() {
+        let due = Instant::now() + FLUSH_DELAY;
+        let mut open = true;
+        while let Some(left) = due.checked_duration_since(Instant::now()) {
+            if let Err(mpsc::RecvTimeoutError::Disconnected) = wake.recv_timeout(left) {
+                open = false;
+                break;
+            }
         }
-        if let Ok(json) = serde_json::to_string_pretty(&cache) {
-            let _ = fs::write(&path, json);
-            let total_tools: usize = servers.values().map(|v| v.len()).sum();
-            eprintln!("[McpHub][INFO] Saved cache: {} servers, {} tools, {} errors", servers.len(), total_tools, errors.len());
+        flush_pending(path.as_deref(), &pending, &flushing);
+        if !open {
+            return;
         }
     }
 }
 
-/// Update cache for a single server (repair). Merges into existing cache.
-pub fn repair_server_cache(name: &str, tools: Vec<ToolDef>) {
-    let mut cache = load_cache().unwrap_or_else(|| SchemaCache {
-        version: env!("CARGO_PKG_VERSION").to_string(),
-        servers: HashMap::new(),
-        errors: HashMap::new(),
+/// Write `pending` into the cache at `path` and drop what was written.
+fn flush_pending(path: Option<&Path>, pending: &Mutex<Vec<PendingEntry>>, flushing: &Mutex<()>) {
+    let _flushing = flushing.lock().unwrap_or_else(|e| e.into_inner());
+    let entries = pending.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if entries.is_empty() {
+        return;
+    }
+    let Some(path) = path else { return };
+    let written = update_cache_at(path, |cache| {
+        for entry in &entries {
+            if let Ok(tools) = &entry.result {
+                log_schema_changes(cache, &HashMap::from([(entry.name.clone(), tools.clone())]));
+            }
+            apply_entry(cache, entry);
+        }
     });
-    cache.servers.insert(name.to_string(), tools);
-    cache.errors.remove(name);
-    if let Some(path) = cache_path() {
-        if let Ok(json) = serde_json::to_string_pretty(&cache) {
-            let _ = fs::write(&path, json);
+    match written {
+        Ok(()) => {
+            pending.lock().unwrap_or_else(|e| e.into_inner()).drain(..entries.len());
         }
+        Err(e) => tracing::warn!("Could not write the schema cache: {}", e),
     }
 }
 
-/// Store an error for a server in cache
-pub fn set_server_error(name: &str, error: &str) {
-    let mut cache = load_cache().unwrap_or_else(|| SchemaCache {
-        version: env!("CARGO_PKG_VERSION").to_string(),
-        servers: HashMap::new(),
-        errors: HashMap::new(),
+/// Complete a generated cache whose server entries were written as each
+/// server was discovered: store capabilities, prompts and resources, and
+/// drop the servers not in `names`.
+pub fn complete_generated_cache(
+    names: &[String],
+    capabilities: &HashMap<String, serde_json::Value>,
+    prompts: &HashMap<String, Vec<PromptDef>>,
+    resources: &HashMap<String, Vec<ResourceDef>>,
+) {
+    let mut counts = (0, 0, 0);
+    let saved = update_cache(|cache| {
+        complete_generated(cache, names, capabilities, prompts, resources);
+        counts = (cache.servers.len(), cache.servers.values().map(Vec::len).sum(), cache.errors.len());
     });
-    cache.errors.insert(name.to_string(), error.to_string());
-    cache.servers.remove(name);
-    if let Some(path) = cache_path() {
-        if let Ok(json) = serde_json::to_string_pretty(&cache) {
-            let _ = fs::write(&path, json);
+    if saved.is_ok() {
+        tracing::info!("Saved cache: {} servers, {} tools, {} errors", counts.0, counts.1, counts.2);
+    }
+}
+
+fn complete_generated(
+    cache: &mut SchemaCache,
+    names: &[String],
+    capabilities: &HashMap<String, serde_json::Value>,
+    prompts: &HashMap<String, Vec<PromptDef>>,
+    resources: &HashMap<String, Vec<ResourceDef>>,
+) {
+    cache.servers.retain(|name, _| names.contains(name));
+    cache.errors.retain(|name, _| names.contains(name));
+    cache.updated_at.retain(|name, _| names.contains(name));
+    cache.version = env!("CARGO_PKG_VERSION").to_string();
+    cache.capabilities = capabilities.clone();
+    cache.prompts = prompts.clone();
+    cache.resources = resources.clone();
+}
+
+/// Validate a server's `prompts/list` result. Invalid definitions are
+/// logged and left out rather than failing the whole list.
+pub fn parse_prompt_list(server: &str, result: &serde_json::Value) -> Vec<PromptDef> {
+    let entries = result.get("prompts").and_then(|v| v.as_array()).map(Vec::as_slice).unwrap_or_default();
+    entries
+        .iter()
+        .filter_map(|entry| match PromptDef::from_value(entry) {
+            Ok(prompt) => Some(prompt),
+            Err(e) => {
+                tracing::warn!("{}: skipping invalid prompt: {}", server, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Read a server's `resources/list` result, leaving out entries without a uri.
+pub fn parse_resource_list(server: &str, result: &serde_json::Value) -> Vec<ResourceDef> {
+    let entries = result.get("resources").and_then(|v| v.as_array()).map(Vec::as_slice).unwrap_or_default();
+    entries
+        .iter()
+        .filter_map(|entry| match serde_json::from_value::<ResourceDef>(entry.clone()) {
+            Ok(resource) if !resource.uri.is_empty() => Some(resource),
+            Ok(_) => {
+                tracing::warn!("{}: skipping resource without a uri", server);
+                None
+            }
+            Err(e) => {
+                tracing::warn!("{}: skipping invalid resource: {}", server, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// What changed in one server's tool list between two introspections.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ToolDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Tools whose description or input schema changed.
+    pub modified: Vec<String>,
+}
+
+impl ToolDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub fn diff_tools(old: &[ToolDef], new: &[ToolDef]) -> ToolDiff {
+    let old_by_name: HashMap<&str, &ToolDef> = old.iter().map(|t| (t.name.as_str(), t)).collect();
+    let new_by_name: HashMap<&str, &ToolDef> = new.iter().map(|t| (t.name.as_str(), t)).collect();
+
+    let mut diff = ToolDiff::default();
+    for (name, tool) in &new_by_name {
+        match old_by_name.get(name) {
+            None => diff.added.push(name.to_string()),
+            Some(prev)
+                if prev.description != tool.description
+                    || prev.input_schema != tool.input_schema
+                    || prev.annotations != tool.annotations =>
+            {
+                diff.modified.push(name.to_string())
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = old_by_name.keys().filter(|n| !new_by_name.contains_key(*n)).map(|n| n.to_string()).collect();
+    diff.added.sort();
+    diff.removed.sort();
+    diff.modified.sort();
+    diff
+}
+
+/// Structured `tools_changed` event for one server.
+pub fn schema_change_event(server: &str, diff: &ToolDiff) -> serde_json::Value {
+    serde_json::json!({
+        "event": "tools_changed",
+        "server": server,
+        "added": diff.added,
+        "removed": diff.removed,
+        "modified": diff.modified,
+    })
+}
+
+/// Log a `tools_changed` event for every server whose freshly introspected
+/// tools differ from the cached ones. Servers new to the cache are skipped.
+fn log_schema_changes(previous: &SchemaCache, servers: &HashMap<String, Vec<ToolDef>>) {
+    let mut names: Vec<&String> = servers.keys().collect();
+    names.sort();
+    for name in names {
+        let Some(old) = previous.servers.get(name) else { continue };
+        let diff = diff_tools(old, &servers[name]);
+        if !diff.is_empty() {
+            tracing::info!(tag = "SCHEMA", "{}", schema_change_event(name, &diff));
+        }
+    }
+}
+
+/// Remove entries for servers that are no longer configured and, if `max_age_secs`
+/// is set, entries last written longer ago than that. Entries without a timestamp
+/// are only pruned when orphaned. Returns `(server, reason)` for each pruned entry.
+pub fn prune(
+    cache: &mut SchemaCache,
+    configured: &HashSet<String>,
+    max_age_secs: Option<u64>,
+    now: u64,
+) -> Vec<(String, String)> {
+    let mut names: Vec<String> = cache.servers.keys().chain(cache.errors.keys()).cloned().collect();
+    names.sort();
+    names.dedup();
+
+    let mut pruned = Vec::new();
+    for name in names {
+        let reason = if !configured.contains(&name) {
+            Some("not in config".to_string())
+        } else {
+            match (max_age_secs, cache.updated_at.get(&name)) {
+                (Some(max_age), Some(&ts)) if now.saturating_sub(ts) > max_age => {
+                    Some(format!("older than {}s", max_age))
+                }
+                _ => None,
+            }
+        };
+        if let Some(reason) = reason {
+            remove_server(cache, &name);
+            pruned.push((name, reason));
+        }
+    }
+    pruned
+}
+
+/// Drop everything cached for `name`. False if it had no entry.
+fn remove_server(cache: &mut SchemaCache, name: &str) -> bool {
+    let found = cache.servers.remove(name).is_some() | cache.errors.remove(name).is_some();
+    cache.updated_at.remove(name);
+    cache.capabilities.remove(name);
+    cache.prompts.remove(name);
+    cache.resources.remove(name);
+    found
+}
+
+/// `McpHub cache prune [--max-age-days N]`
+pub fn run_prune(max_age_days: Option<u64>) {
+    let configured: HashSet<String> = crate::config::auto_detect().servers.keys().cloned().collect();
+    let Some(path) = cache_path() else { return };
+    let _lock = CacheLock::acquire(&path, false);
+    let mut cache = match read_cache_at(&path) {
+        Some(c) => c,
+        None => {
+            println!("No cache found. Run 'McpHub generate' first.");
+            return;
+        }
+    };
+    let pruned = prune(&mut cache, &configured, max_age_days.map(|d| d * 86_400), now_secs());
+
+    if pruned.is_empty() {
+        println!("Nothing to prune.");
+        return;
+    }
+    for (name, reason) in &pruned {
+        println!("  pruned {} ({})", name, reason);
+    }
+    match write_cache_at(&path, &mut cache) {
+        Ok(()) => println!("Pruned {} entries, {} servers remain.", pruned.len(), cache.servers.len()),
+        Err(e) => eprintln!("Failed to save cache: {}", e),
+    }
+}
+
+/// `McpHub cache show [server]`
+pub fn run_show(server: Option<&str>) {
+    let cache = match load_cache() {
+        Some(c) => c,
+        None => {
+            println!("No cache found. Run 'McpHub generate' first.");
+            return;
+        }
+    };
+
+    let mut names: Vec<&String> = cache.servers.keys().collect();
+    names.sort();
+
+    match server {
+        None => {
+            for name in names {
+                let tools = &cache.servers[name];
+                let tool_names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+                println!("{} ({} tools): {}", name, tools.len(), tool_names.join(", "));
+            }
+            let mut errors: Vec<_> = cache.errors.iter().collect();
+            errors.sort();
+            for (name, error) in errors {
+                println!("{} (error): {}", name, error);
+            }
+        }
+        Some(wanted) => {
+            let found = names.into_iter().find(|n| n.eq_ignore_ascii_case(wanted));
+            let Some(name) = found else {
+                match cache.errors.iter().find(|(n, _)| n.eq_ignore_ascii_case(wanted)) {
+                    Some((name, error)) => println!("{} (error): {}", name, error),
+                    None => println!("Server '{}' not in cache.", wanted),
+                }
+                return;
+            };
+            for tool in &cache.servers[name] {
+                println!("── {}", tool.name);
+                if !tool.description.is_empty() {
+                    println!("{}", tool.description);
+                }
+                println!("{}\n", serde_json::to_string_pretty(&tool.input_schema).unwrap_or_default());
+            }
+        }
+    }
+}
+
+/// `12s`, `5m`, `3h`, `2d`.
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3_600 => format!("{}m", secs / 60),
+        3_600..86_400 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// One line per cached server: tool count or stored error, and age.
+fn inspect_lines(cache: &SchemaCache, now: u64) -> Vec<String> {
+    let mut names: Vec<&String> = cache.servers.keys().chain(cache.errors.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut line = match (cache.servers.get(name), cache.errors.get(name)) {
+                (Some(tools), None) => format!("{}: {} tools", name, tools.len()),
+                (Some(tools), Some(error)) => format!("{}: {} tools, error: {}", name, tools.len(), error),
+                (None, error) => format!("{}: error: {}", name, error.map(String::as_str).unwrap_or_default()),
+            };
+            if let Some(&fetched) = cache.updated_at.get(name) {
+                line.push_str(&format!(" (fetched {} ago)", format_age(now.saturating_sub(fetched))));
+            }
+            line
+        })
+        .collect()
+}
+
+/// `McpHub cache inspect`. Err if there is no readable cache.
+pub fn run_inspect() -> Result<(), String> {
+    let path = cache_file().ok_or("Cannot find home directory")?;
+    if !path.exists() {
+        return Err(format!("No cache at {}. Run 'McpHub generate' first.", path.display()));
+    }
+    let cache = load_cache().ok_or_else(|| format!("Cannot read {}", path.display()))?;
+    let total_tools: usize = cache.servers.values().map(|v| v.len()).sum();
+    println!("{} (v{}): {} servers, {} tools", path.display(), cache.version, cache.servers.len(), total_tools);
+    for line in inspect_lines(&cache, now_secs()) {
+        println!("  {}", line);
+    }
+    Ok(())
+}
+
+/// Remove `server`'s entry from the cache at `path`, or the whole file.
+/// Returns what was done.
+fn clear_at(path: &Path, server: Option<&str>) -> Result<String, String> {
+    let _lock = CacheLock::acquire(path, false);
+    let file = existing_file(path);
+    let Some(mut cache) = file.exists().then(|| read_cache_at(path)).flatten() else {
+        return match server {
+            None if file.exists() => {
+                fs::remove_file(&file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+                Ok(format!("Removed unreadable {}", file.display()))
+            }
+            _ => Ok("No cache found.".into()),
+        };
+    };
+    match server {
+        None => {
+            fs::remove_file(&file).map_err(|e| format!("Failed to remove {}: {}", file.display(), e))?;
+            Ok(format!("Removed {} ({} servers)", file.display(), cache.servers.len() + cache.errors.len()))
+        }
+        Some(wanted) => {
+            let names: Vec<String> = cache.servers.keys().chain(cache.errors.keys()).cloned().collect();
+            let Some(name) = names.into_iter().find(|n| n.eq_ignore_ascii_case(wanted)) else {
+                return Ok(format!("Server '{}' not in cache.", wanted));
+            };
+            remove_server(&mut cache, &name);
+            write_cache_at(path, &mut cache)?;
+            Ok(format!("Removed {} from the cache.", name))
+        }
+    }
+}
+
+/// `McpHub cache clear [server]`
+pub fn run_clear(server: Option<&str>) {
+    let result = cache_path().ok_or("Cannot find home directory".to_string()).and_then(|path| clear_at(&path, server));
+    match result {
+        Ok(message) => println!("{}", message),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str) -> ToolDef {
+        ToolDef { name: name.into(), description: String::new(), input_schema: serde_json::json!({}), annotations: None }
+    }
+
+    #[test]
+    fn test_prune_removes_orphaned_keeps_current() {
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("github".into(), vec![tool("create_issue")]);
+        cache.servers.insert("old".into(), vec![tool("x")]);
+        cache.errors.insert("broken-old".into(), "spawn failed".into());
+
+        let configured: HashSet<String> = ["github".to_string()].into_iter().collect();
+        let pruned = prune(&mut cache, &configured, None, 1_000);
+
+        let names: Vec<&str> = pruned.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["broken-old", "old"]);
+        assert!(cache.servers.contains_key("github"));
+        assert!(!cache.servers.contains_key("old"));
+        assert!(cache.errors.is_empty());
+    }
+
+    #[test]
+    fn test_added_tool_produces_event() {
+        let old = vec![tool("create_issue")];
+        let new = vec![tool("create_issue"), tool("close_issue")];
+        let diff = diff_tools(&old, &new);
+        assert_eq!(diff.added, vec!["close_issue"]);
+        assert!(diff.removed.is_empty() && diff.modified.is_empty());
+
+        let event = schema_change_event("github", &diff);
+        assert_eq!(event["event"], "tools_changed");
+        assert_eq!(event["server"], "github");
+        assert_eq!(event["added"], serde_json::json!(["close_issue"]));
+    }
+
+    #[test]
+    fn test_diff_removed_and_modified() {
+        let old = vec![tool("a"), tool("b")];
+        let mut changed = tool("a");
+        changed.input_schema = serde_json::json!({"type": "object"});
+        let diff = diff_tools(&old, &[changed]);
+        assert_eq!(diff.removed, vec!["b"]);
+        assert_eq!(diff.modified, vec!["a"]);
+        assert!(diff_tools(&old, &old).is_empty());
+
+        // A tool that becomes destructive is a change clients need to see
+        let mut hinted = tool("a");
+        hinted.annotations = Some(serde_json::json!({"destructiveHint": true}));
+        assert_eq!(diff_tools(&old, &[hinted, tool("b")]).modified, vec!["a"]);
+    }
+
+    #[test]
+    fn test_version_mismatch_strict_rejects_lenient_migrates() {
+        let mut cache = SchemaCache::empty();
+        cache.version = "0.0.1".into();
+        cache.servers.insert("a".into(), vec![tool("t")]);
+
+        let strict = VersionPolicy { strict: true, pinned: None };
+        let err = check_version(cache, &strict).err().unwrap();
+        assert!(err.contains("0.0.1") && err.contains(env!("CARGO_PKG_VERSION")), "{}", err);
+
+        let mut cache = SchemaCache::empty();
+        cache.version = "0.0.1".into();
+        cache.servers.insert("a".into(), vec![tool("t")]);
+        let migrated = check_version(cache, &VersionPolicy::default()).unwrap();
+        assert_eq!(migrated.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(migrated.servers["a"].len(), 1);
+
+        // A pinned version is accepted in strict mode even if this build differs
+        let mut cache = SchemaCache::empty();
+        cache.version = "0.0.1".into();
+        let pinned = VersionPolicy { strict: true, pinned: Some("0.0.1".into()) };
+        assert!(check_version(cache, &pinned).is_ok());
+    }
+
+    #[test]
+    fn test_old_format_upgraded_newer_read_as_is() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-cache-format-{}", std::process::id()));
+        let path = dir.join("schema-cache.json");
+        fs::create_dir_all(&dir).unwrap();
+
+        // Format 1: no timestamps, capabilities, prompts or resources
+        fs::write(&path, r#"{"version":"0.1.0","servers":{"github":[{"name":"a","inputSchema":{}}]}}"#).unwrap();
+        let cache = read_cache_at(&path).unwrap();
+        assert_eq!((cache.format, cache.servers["github"].len()), (0, 1));
+        assert!(cache.updated_at.is_empty() && cache.resources.is_empty());
+        assert!(is_outdated(&cache, env!("CARGO_PKG_VERSION")));
+        update_cache_at(&path, |c| c.version = env!("CARGO_PKG_VERSION").into()).unwrap();
+        let raw: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["format"], CACHE_FORMAT);
+        assert!(!is_outdated(&read_cache_at(&path).unwrap(), env!("CARGO_PKG_VERSION")));
+
+        // A newer McpHub's cache: unknown fields ignored, not rewritten
+        fs::write(&path, r#"{"version":"99.0.0","format":7,"servers":{"fs":[{"name":"read","inputSchema":{}}]},"shards":[1]}"#).unwrap();
+        let cache = read_cache_at(&path).unwrap();
+        assert_eq!((cache.format, cache.servers["fs"][0].name.as_str()), (7, "read"));
+        assert!(!is_outdated(&cache, env!("CARGO_PKG_VERSION")));
+        assert!(version_parts("0.10.2") > version_parts("0.9.12"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_invalid_prompts_are_skipped() {
+        let result = serde_json::json!({"prompts": [
+            {"name": "ok", "arguments": [{"name": "text", "required": true}]},
+            {"name": "dup", "arguments": [{"name": "x"}, {"name": "x"}]},
+            {"description": "no name"}
+        ]});
+        let prompts = parse_prompt_list("srv", &result);
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].name, "ok");
+        assert!(prompts[0].arguments[0].required);
+    }
+
+    #[test]
+    fn test_resources_parsed_and_optional_in_old_caches() {
+        let result = serde_json::json!({"resources": [
+            {"uri": "file:///notes.md", "name": "notes", "mimeType": "text/markdown", "size": 42},
+            {"name": "no uri"}
+        ]});
+        let resources = parse_resource_list("srv", &result);
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].mime_type.as_deref(), Some("text/markdown"));
+        // Fields McpHub doesn't model survive the round trip
+        assert_eq!(serde_json::to_value(&resources[0]).unwrap()["size"], 42);
+
+        let old: SchemaCache = serde_json::from_str(r#"{"version": "0.1.0", "servers": {}}"#).unwrap();
+        assert!(old.resources.is_empty());
+    }
+
+    #[test]
+    fn test_prune_max_age() {
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("fresh".into(), vec![tool("a")]);
+        cache.servers.insert("stale".into(), vec![tool("b")]);
+        cache.servers.insert("legacy".into(), vec![tool("c")]);
+        cache.updated_at.insert("fresh".into(), 900);
+        cache.updated_at.insert("stale".into(), 100);
+
+        let configured: HashSet<String> = ["fresh", "stale", "legacy"].iter().map(|s| s.to_string()).collect();
+        let pruned = prune(&mut cache, &configured, Some(500), 1_000);
+
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].0, "stale");
+        assert!(cache.servers.contains_key("fresh"));
+        assert!(cache.servers.contains_key("legacy"));
+    }
+
+    #[test]
+    fn test_concurrent_updates_keep_every_entry() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-cache-lock-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("schema-cache.json");
+
+        let writers: Vec<_> = ["repair", "error"]
+            .into_iter()
+            .map(|kind| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..20 {
+                        update_cache_at(&path, |cache| match kind {
+                            "repair" => { cache.servers.insert(format!("repaired{}", i), vec![tool("t")]); }
+                            _ => { cache.errors.insert(format!("failed{}", i), "exited".into()); }
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let cache = read_cache_at(&path).unwrap();
+        assert_eq!((cache.servers.len(), cache.errors.len()), (20, 20));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_corrupt_entry_dropped_others_kept() {
+        let content = r#"{
+            "version": "1.0.0",
+            "servers": {
+                "good": [{ "name": "a", "inputSchema": {} }],
+                "bad": [{ "description": "no name" }],
+                "worse": "not a list"
+            },
+            "updated_at": { "good": 100 }
+        }"#;
+        let e = serde_json::from_str::<SchemaCache>(content).err().unwrap();
+        let cache = recover_cache(content, &e.to_string()).unwrap();
+        assert_eq!(cache.version, "1.0.0");
+        assert_eq!(cache.servers.keys().collect::<Vec<_>>(), ["good"]);
+        assert_eq!(cache.updated_at["good"], 100);
+        assert!(cache.errors["bad"].contains("missing field `name`"), "{}", cache.errors["bad"]);
+        assert!(cache.errors.contains_key("worse"));
+
+        assert!(recover_cache("{\"servers\": {", "eof").is_none());
+    }
+
+    #[test]
+    fn test_checksum_mismatch_rejects_cache() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-cache-checksum-{}", std::process::id()));
+        let path = dir.join("schema-cache.json");
+        update_cache_at(&path, |cache| {
+            cache.servers.insert("github".into(), vec![tool("create_issue")]);
+        })
+        .unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(read_cache_at(&path).unwrap().checksum.is_some());
+
+        // Still valid JSON, but not what was written
+        fs::write(&path, written.replace("create_issue", "delete_repo!")).unwrap();
+        assert!(read_cache_at(&path).is_none());
+
+        // Caches from before checksums are trusted
+        fs::write(&path, r#"{"version":"1.0.0","servers":{"github":[{"name":"a","inputSchema":{}}]}}"#).unwrap();
+        assert_eq!(read_cache_at(&path).unwrap().servers["github"][0].name, "a");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_secrets_redacted_before_writing() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-cache-redact-{}", std::process::id()));
+        let path = dir.join("schema-cache.json");
+        update_cache_at(&path, |cache| {
+            cache.errors.insert("openai".into(), "401: invalid key sk-abcdefghijklmnop1234".into());
+            let mut search = tool("search");
+            search.input_schema = serde_json::json!({"properties": {"token": {"examples": ["ghp_0123456789abcdefghijABCD"]}}});
+            cache.servers.insert("github".into(), vec![search]);
+        })
+        .unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(!written.contains("sk-abcdefghijklmnop1234"), "{}", written);
+        let cache = read_cache_at(&path).unwrap();
+        assert_eq!(cache.errors["openai"], "401: invalid key sk-[REDACTED]");
+        // Tool schemas are what clients get, so they are kept as sent
+        assert_eq!(cache.servers["github"][0].input_schema["properties"]["token"]["examples"][0], "ghp_0123456789abcdefghijABCD");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pending_entries_apply_in_order() {
+        let entry = |name: &str, result: Result<Vec<ToolDef>, String>, at| PendingEntry { name: name.into(), result, at };
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("fs".into(), vec![tool("read")]);
+        for pending in [
+            entry("github", Err("spawn failed".into()), 10),
+            entry("github", Ok(vec![tool("create_issue")]), 20),
+            entry("fs", Err("crashed".into()), 30),
+        ] {
+            apply_entry(&mut cache, &pending);
+        }
+        assert_eq!(cache.servers.keys().collect::<Vec<_>>(), ["github"]);
+        assert_eq!(cache.errors.keys().collect::<Vec<_>>(), ["fs"]);
+        assert_eq!((cache.updated_at["github"], cache.updated_at["fs"]), (20, 30));
+    }
+
+    #[test]
+    fn test_writer_reads_pending_entries_and_writes_them_when_dropped() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-cache-writer-{}", std::process::id()));
+        let path = dir.join("schema-cache.json");
+        let writer = CacheWriter::at(Some(path.clone()));
+        writer.repair_server("github", vec![tool("create_issue")]);
+        writer.set_server_error("fs", "spawn failed");
+
+        let read = writer.read().unwrap();
+        assert_eq!((read.servers["github"][0].name.as_str(), read.errors["fs"].as_str()), ("create_issue", "spawn failed"));
+
+        drop(writer);
+        let written = read_cache_at(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(written.servers["github"][0].name, "create_issue");
+        assert_eq!(written.errors["fs"], "spawn failed");
+    }
+
+    #[test]
+    fn test_generated_cache_completed_around_discovered_entries() {
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("github".into(), vec![tool("create_issue")]);
+        cache.errors.insert("fs".into(), "spawn failed".into());
+        cache.servers.insert("removed".into(), vec![tool("old")]);
+        cache.updated_at.insert("removed".into(), 1);
+        let names = vec!["github".to_string(), "fs".to_string()];
+        let capabilities = HashMap::from([("github".to_string(), serde_json::json!({"tools": {}}))]);
+        complete_generated(&mut cache, &names, &capabilities, &HashMap::new(), &HashMap::new());
+
+        assert_eq!(cache.servers.keys().collect::<Vec<_>>(), ["github"]);
+        assert_eq!(cache.errors["fs"], "spawn failed");
+        assert!(!cache.updated_at.contains_key("removed"));
+        assert_eq!(cache.capabilities, capabilities);
+    }
+
+    #[test]
+    fn test_gzip_format_replaces_plain_and_back() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-cache-gzip-{}", std::process::id()));
+        let path = dir.join("schema-cache.json");
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("github".into(), vec![tool("create_issue")]);
+        write_cache_as(&path, &mut cache, false).unwrap();
+
+        write_cache_as(&path, &mut cache, true).unwrap();
+        let gz = gz_path(&path);
+        assert!(gz.exists() && !path.exists() && !dir.join("schema-cache.json.gz.tmp").exists());
+        assert!(fs::read(&gz).unwrap().starts_with(&[0x1f, 0x8b]));
+        assert_eq!(read_cache_at(&path).unwrap().servers["github"][0].name, "create_issue");
+
+        // Gzip wins over a plain file next to it
+        fs::write(&path, r#"{"version":"1.0.0","servers":{}}"#).unwrap();
+        assert_eq!(read_cache_at(&path).unwrap().servers.len(), 1);
+
+        write_cache_as(&path, &mut cache, false).unwrap();
+        assert!(path.exists() && !gz.exists());
+        assert_eq!(read_cache_at(&path).unwrap().servers.len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_stale_entries_dropped_fresh_kept() {
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("fresh".into(), vec![tool("a")]);
+        cache.servers.insert("stale".into(), vec![tool("b")]);
+        cache.servers.insert("legacy".into(), vec![tool("c")]);
+        cache.prompts.insert("stale".into(), Vec::new());
+        cache.updated_at.insert("fresh".into(), 90_000);
+        cache.updated_at.insert("stale".into(), 1_000);
+
+        let stale = drop_stale(&mut cache, Duration::from_secs(24 * 3600), 100_000);
+        assert_eq!(stale, ["legacy", "stale"]);
+        assert_eq!(cache.servers.keys().collect::<Vec<_>>(), ["fresh"]);
+        assert!(cache.prompts.is_empty());
+    }
+
+    #[test]
+    fn test_inspect_and_clear() {
+        let mut cache = SchemaCache::empty();
+        cache.servers.insert("github".into(), vec![tool("a"), tool("b")]);
+        cache.updated_at.insert("github".into(), 10_000 - 7_200);
+        cache.servers.insert("fs".into(), vec![tool("read")]);
+        cache.errors.insert("broken".into(), "spawn failed".into());
+        cache.updated_at.insert("broken".into(), 10_000 - 30);
+        assert_eq!(
+            inspect_lines(&cache, 10_000),
+            vec!["broken: error: spawn failed (fetched 30s ago)", "fs: 1 tools", "github: 2 tools (fetched 2h ago)"]
+        );
+
+        let dir = std::env::temp_dir().join(format!("mcphub-cache-clear-{}", std::process::id()));
+        let path = dir.join("schema-cache.json");
+        update_cache_at(&path, |c| *c = cache).unwrap();
+        assert_eq!(clear_at(&path, Some("GitHub")).unwrap(), "Removed github from the cache.");
+        let left = read_cache_at(&path).unwrap();
+        assert!(!left.servers.contains_key("github") && !left.updated_at.contains_key("github"));
+        assert!(left.servers.contains_key("fs") && left.errors.contains_key("broken"));
+        assert_eq!(clear_at(&path, Some("nope")).unwrap(), "Server 'nope' not in cache.");
+
+        assert!(clear_at(&path, None).unwrap().starts_with("Removed"));
+        assert!(!path.exists());
+        assert_eq!(clear_at(&path, None).unwrap(), "No cache found.");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,38 +1,396 @@
-/// Child process manager: spawn MCP servers, communicate over stdio, manage lifecycle.
-use std::collections::HashMap;
+/// Child process manager: spawn MCP servers (or connect to remote ones), communicate over stdio, manage lifecycle.
+use std::collections::{HashMap, HashSet};
 use std::process::Stdio;
 use std::sync::Arc;
-use std::time::Instant;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex, OwnedSemaphorePermit, Semaphore};
 
+use crate::breaker::{BreakerConfig, BreakerStatus, CircuitBreaker};
 pub use crate::config::ServerConfig;
+use crate::config::{Balance, WhenBusy};
+use crate::error::{Failure, Kind};
+use crate::framing::{MessageReader, DEFAULT_MAX_MESSAGE_BYTES};
+use crate::stderr::{self, StderrTail};
 use crate::protocol::ToolDef;
+use crate::tool_filter::ToolFilter;
+use crate::tool_overrides::ToolOverrides;
 
+/// What serves an instance: a spawned process, or a connection to a
+/// remote server (`url`).
 #[derive(Debug)]
+enum Upstream {
+    Process(Child),
+    Remote(crate::remote::Connection),
+}
+
+impl Upstream {
+    async fn kill(&mut self) {
+        match self {
+            Upstream::Process(child) => {
+                // The process leads its own group on Unix (see `spawn`)
+                #[cfg(unix)]
+                signal_group(child, libc::SIGKILL);
+                let _ = child.kill().await;
+            }
+            Upstream::Remote(connection) => connection.close(),
+        }
+    }
+
+    /// Why the upstream is gone, once it is.
+    fn gone(&mut self) -> Option<String> {
+        match self {
+            Upstream::Process(child) => match child.try_wait() {
+                Ok(Some(status)) => Some(format!("Process {}", describe_exit(status))),
+                Err(e) => Some(format!("Process check failed: {}", e)),
+                Ok(None) => None,
+            },
+            Upstream::Remote(connection) => connection.closed_reason().map(|r| format!("Connection lost: {}", r)),
+        }
+    }
+}
+
+/// A process's stdin, or the messages POSTed to a remote server.
+type Input = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+/// A process's stdout, or the events of a remote server.
+type Output = MessageReader<BufReader<Box<dyn AsyncRead + Send + Sync + Unpin>>>;
+
 struct ChildProcess {
-    child: Child,
-    stdin: tokio::process::ChildStdin,
-    stdout_lines: Arc<Mutex<tokio::io::Lines<BufReader<tokio::process::ChildStdout>>>>,
+    upstream: Upstream,
+    /// Taken at shutdown to close the server's input.
+    stdin: Option<Input>,
+    stdout: Arc<Mutex<Output>>,
     next_id: u64,
     tools: Vec<ToolDef>,
     last_used: Instant,
     server_name: String,
     protocol_version: String,
+    call_timeout: Duration,
+    /// `capabilities` from the server's initialize result.
+    capabilities: serde_json::Value,
+    /// Backend notifications, tagged with the server name.
+    notify: broadcast::Sender<(String, serde_json::Value)>,
+    /// Position in the server's pool.
+    instance: usize,
+    /// Resource updates for uris pinned elsewhere are dropped.
+    pins: Pins,
+    /// Recent stderr lines, for when the process dies.
+    stderr_tail: StderrTail,
+    /// Where requests from the backend to a client go, once someone handles them.
+    client_requests: ClientRequests,
+    /// Session of the call in flight; the backend's requests go to it.
+    caller: Option<String>,
+}
+
+type ClientRequests = Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<ClientRequest>>>>;
+
+/// Per-call options from the client's request.
+#[derive(Debug, Clone, Default)]
+pub struct CallContext {
+    /// `_meta.progressToken`; the backend may send `notifications/progress` for it.
+    pub progress_token: Option<serde_json::Value>,
+    /// Turns true when the client cancels the call.
+    pub cancelled: Option<watch::Receiver<bool>>,
+    /// Client session the call came from, which gets the backend's
+    /// requests (`sampling/createMessage`) made while it runs.
+    pub session: Option<String>,
+    /// Servers the session is limited to (`?servers=`); None for all.
+    pub scope: Option<crate::proxy::Scope>,
+}
+
+/// A request a backend sent to the client side (`sampling/createMessage`).
+/// `reply` takes the client's JSON-RPC response; dropping it answers with
+/// an error.
+#[derive(Debug)]
+pub struct ClientRequest {
+    pub server: String,
+    /// Session whose call caused the request; None outside a session's call.
+    pub session: Option<String>,
+    pub request: serde_json::Value,
+    pub reply: oneshot::Sender<serde_json::Value>,
 }
 
 struct ServerPool {
     procs: Vec<Arc<Mutex<ChildProcess>>>,
+    /// Per-instance liveness, cleared by failed health checks and connection errors.
+    up: Vec<AtomicBool>,
+    /// Per-instance requests waiting for or holding the instance.
+    busy: Vec<AtomicUsize>,
     next_idx: AtomicUsize,
+    balance: Balance,
+}
+
+impl ServerPool {
+    /// An instance that is up, chosen by the pool's balance.
+    fn pick(&self) -> Option<usize> {
+        pick_instance(self.balance, &self.next_idx, &self.up, &self.busy)
+    }
+
+    fn health(&self) -> GroupHealth {
+        let up = self.up.iter().filter(|u| u.load(Ordering::Relaxed)).count();
+        GroupHealth::new(up, self.procs.len())
+    }
+}
+
+fn pick_instance(balance: Balance, next_idx: &AtomicUsize, up: &[AtomicBool], busy: &[AtomicUsize]) -> Option<usize> {
+    let n = up.len();
+    match balance {
+        Balance::RoundRobin => (0..n)
+            .map(|_| next_idx.fetch_add(1, Ordering::Relaxed) % n)
+            .find(|&idx| up[idx].load(Ordering::Relaxed)),
+        // Ties go round-robin, so idle instances still share the calls
+        Balance::LeastInFlight => {
+            let start = next_idx.fetch_add(1, Ordering::Relaxed);
+            (0..n)
+                .map(|i| (start + i) % n)
+                .filter(|&idx| up[idx].load(Ordering::Relaxed))
+                .min_by_key(|&idx| busy[idx].load(Ordering::Relaxed))
+        }
+    }
+}
+
+/// Counts a request against an instance while it lives.
+struct BusyGuard<'a>(&'a AtomicUsize);
+
+impl<'a> BusyGuard<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Aggregate state of a server's instance pool.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupState {
+    /// Every instance is up.
+    Healthy,
+    /// Some instances are down; calls go to the rest.
+    Degraded,
+    /// No instance is up.
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct GroupHealth {
+    pub state: GroupState,
+    pub up: usize,
+    pub total: usize,
+}
+
+impl GroupHealth {
+    fn new(up: usize, total: usize) -> Self {
+        let state = if up == 0 {
+            GroupState::Failed
+        } else if up < total {
+            GroupState::Degraded
+        } else {
+            GroupState::Healthy
+        };
+        Self { state, up, total }
+    }
+}
+
+/// Result of an on-demand start, shared by every caller waiting on it.
+type Activation = watch::Receiver<Option<Result<Vec<ToolDef>, Failure>>>;
+
+/// Starts in progress with the tasks running them, keyed by server name.
+type Starts = Arc<Mutex<HashMap<String, (Activation, tokio::task::JoinHandle<()>)>>>;
+
+/// How long a backend gets to answer a `ping`.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Call accounting for one server, so a reload can drain in-flight calls.
+#[derive(Debug, Default, Clone, Copy)]
+struct CallState {
+    active: usize,
+    /// A reload is waiting for `active` to reach zero; new calls are rejected.
+    draining: bool,
+    /// The grace period elapsed; remaining calls must give up.
+    aborted: bool,
+}
+
+type InFlight = Arc<watch::Sender<CallState>>;
+
+/// How a tool call was served, for diagnostics.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CallTrace {
+    /// The server was not running and had to be started for this call.
+    pub cold_start: bool,
+    /// The first attempt hit a connection error and the call was retried on a restarted server.
+    pub retried: bool,
+    /// Time spent waiting on the backend's answer, excluding starts and
+    /// waiting for a free instance. None if no request was sent.
+    pub upstream: Option<Duration>,
+}
+
+impl CallTrace {
+    fn add_upstream(&mut self, elapsed: Duration) {
+        self.upstream = Some(self.upstream.unwrap_or_default() + elapsed);
+    }
+}
+
+/// Where a resource subscription lives.
+#[derive(Debug, Clone, PartialEq)]
+enum Pin {
+    /// Held by this pool instance; reads of the uri go there.
+    Instance(usize),
+    /// The holding instance went away; reads fail until the client
+    /// subscribes again.
+    Lost(String),
 }
 
+/// Servers in shutdown waves: each in a wave before the servers it depends
+/// on, the reverse of bringing dependencies up first. The servers of a wave
+/// can stop together. Servers in a dependency cycle make up the last wave.
+fn shutdown_waves(configs: &HashMap<String, ServerConfig>) -> Vec<Vec<String>> {
+    let mut left: Vec<&String> = configs.keys().collect();
+    left.sort();
+    let mut waves = Vec::new();
+    loop {
+        // Servers nothing left depends on
+        let (free, held): (Vec<&String>, Vec<&String>) = left.iter().partition(|name| {
+            !left.iter().any(|other| other != *name && configs[*other].depends_on.contains(name))
+        });
+        if free.is_empty() {
+            break;
+        }
+        waves.push(free.into_iter().cloned().collect());
+        left = held;
+    }
+    if !left.is_empty() {
+        waves.push(left.into_iter().cloned().collect());
+    }
+    waves
+}
+
+/// How long a backend gets to exit after SIGTERM before it is killed.
+const TERM_GRACE: Duration = Duration::from_secs(2);
+
+/// Send `signal` to a backend's process group. Unreaped, so the id is
+/// still ours.
+#[cfg(unix)]
+fn signal_group(child: &tokio::process::Child, signal: libc::c_int) {
+    if let Some(pid) = child.id() {
+        // SAFETY: plain kill(2); the negative pid addresses the group
+        unsafe { libc::kill(-(pid as libc::pid_t), signal) };
+    }
+}
+
+/// The MCP stdio shutdown sequence: close the process's stdin and wait up
+/// to `grace` for it to exit, then SIGTERM it (Unix) and wait TERM_GRACE,
+/// then kill it. On Unix the signals go to its whole process group.
+/// True if it exited before being killed.
+async fn exit_gracefully(proc: &mut ChildProcess, grace: Duration) -> bool {
+    proc.stdin = None;
+    let child = match &mut proc.upstream {
+        Upstream::Process(child) => child,
+        Upstream::Remote(connection) => {
+            connection.close();
+            return true;
+        }
+    };
+    if matches!(tokio::time::timeout(grace, child.wait()).await, Ok(Ok(_))) {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        signal_group(child, libc::SIGTERM);
+        if matches!(tokio::time::timeout(TERM_GRACE, child.wait()).await, Ok(Ok(_))) {
+            return true;
+        }
+        signal_group(child, libc::SIGKILL);
+    }
+    let _ = child.kill().await;
+    false
+}
+
+/// Resource subscriptions by (server, backend uri).
+type Pins = Arc<std::sync::Mutex<HashMap<(String, String), Pin>>>;
+
+/// Call slots of servers with a concurrency limit, with the limit they
+/// were made for.
+type CallSlots = Arc<std::sync::Mutex<HashMap<String, (usize, Arc<Semaphore>)>>>;
+
+/// Restarts per server since startup.
+type Restarts = Arc<std::sync::Mutex<HashMap<String, RestartStats>>>;
+
+/// How often a server has been brought back since startup, for
+/// `/api/servers` and `/metrics`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct RestartStats {
+    /// Whole-server restarts: after a crash, by the health monitor or a reload.
+    pub restarts: u64,
+    /// Restarts that failed to bring the server back.
+    pub failed_restarts: u64,
+    /// Single pool instances respawned after going down.
+    pub respawns: u64,
+    /// Unix time (secs) of the last restart or respawn.
+    pub last_restart_at: Option<u64>,
+}
+
+/// Held for the duration of a tool call.
+struct CallGuard(InFlight);
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|s| s.active -= 1);
+    }
+}
+
+#[derive(Clone)]
 pub struct ChildManager {
     configs: Arc<Mutex<HashMap<String, ServerConfig>>>,
     pools: Arc<Mutex<HashMap<String, Arc<ServerPool>>>>,
+    /// Starts in progress, keyed by server name; concurrent starts share one.
+    activations: Starts,
+    /// Client `capabilities.experimental`, forwarded in each backend's initialize.
+    client_experimental: Arc<Mutex<Option<serde_json::Value>>>,
+    /// In-flight tool calls per server.
+    inflight: Arc<Mutex<HashMap<String, InFlight>>>,
+    breakers: Arc<Mutex<HashMap<String, CircuitBreaker>>>,
+    breaker_config: BreakerConfig,
+    /// Default `maxConcurrent` of servers without one; None = unlimited.
+    max_concurrent: Option<usize>,
+    call_slots: CallSlots,
+    restarts: Restarts,
+    /// Notifications read from any backend's stdout.
+    notifications: broadcast::Sender<(String, serde_json::Value)>,
+    /// Servers whose last instance died, with the reason.
+    crashes: broadcast::Sender<(String, String)>,
+    /// Where server state goes in the schema cache; servers are marked failed
+    /// when their stdout closes.
+    cache: Option<Arc<crate::cache::CacheWriter>>,
     idle_timeout_ms: u64,
+    init_timeout: Duration,
+    /// Extra attempts at a start whose handshake failed or timed out.
+    init_retries: u32,
+    /// Wait before the first retry; doubles on each one after.
+    init_retry_backoff: Duration,
+    call_timeout: Duration,
+    activation_timeout: Duration,
+    /// Cap on one message read from a backend's stdout.
+    max_message_bytes: usize,
+    /// Stderr lines per second forwarded to the log from each instance.
+    stderr_max_lines_per_sec: u32,
+    /// Keep a subscribed uri on the instance holding the subscription.
+    resource_affinity: bool,
+    pins: Pins,
+    /// Offer `sampling` to backends and pass their requests to a client.
+    forward_sampling: bool,
+    client_requests: ClientRequests,
+    /// Tools hidden from the hub, dropped from every tools/list answer.
+    tool_filter: Arc<Mutex<ToolFilter>>,
+    tool_overrides: Arc<Mutex<ToolOverrides>>,
 }
 
 impl ChildManager {
@@ -40,32 +398,180 @@ impl ChildManager {
         Self {
             configs: Arc::new(Mutex::new(configs)),
             pools: Arc::new(Mutex::new(HashMap::new())),
+            activations: Arc::new(Mutex::new(HashMap::new())),
+            client_experimental: Arc::new(Mutex::new(None)),
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+            breakers: Arc::new(Mutex::new(HashMap::new())),
+            breaker_config: BreakerConfig::default(),
+            max_concurrent: None,
+            call_slots: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            restarts: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            notifications: broadcast::channel(NOTIFICATION_BUFFER).0,
+            crashes: broadcast::channel(NOTIFICATION_BUFFER).0,
+            cache: None,
             idle_timeout_ms,
+            init_timeout: Duration::from_secs(DEFAULT_INIT_TIMEOUT_SECS),
+            init_retries: 2,
+            init_retry_backoff: Duration::from_millis(500),
+            call_timeout: Duration::from_secs(DEFAULT_CALL_TIMEOUT_SECS),
+            activation_timeout: Duration::from_secs(DEFAULT_ACTIVATION_TIMEOUT_SECS),
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            stderr_max_lines_per_sec: stderr::DEFAULT_MAX_LINES_PER_SEC,
+            resource_affinity: true,
+            pins: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            forward_sampling: true,
+            client_requests: Arc::new(std::sync::Mutex::new(None)),
+            tool_filter: Arc::new(Mutex::new(ToolFilter::default())),
+            tool_overrides: Arc::new(Mutex::new(ToolOverrides::default())),
         }
     }
 
-    pub async fn update_configs(&self, new_configs: HashMap<String, ServerConfig>) {
-        let mut current_configs = self.configs.lock().await;
-        
-        let mut to_stop = Vec::new();
-        for (name, old_cfg) in current_configs.iter() {
-            if let Some(new_cfg) = new_configs.get(name) {
-                if old_cfg != new_cfg {
-                    to_stop.push(name.clone());
-                }
-            } else {
-                to_stop.push(name.clone());
-            }
+    /// How long a call to a stopped server waits for it to start.
+    /// The start keeps going in the background if this elapses.
+    pub fn with_activation_timeout(mut self, activation_timeout_secs: u64) -> Self {
+        self.activation_timeout = Duration::from_secs(activation_timeout_secs);
+        self
+    }
+
+    /// Thresholds for the per-server circuit breaker on tool calls.
+    pub fn with_circuit_breaker(mut self, config: BreakerConfig) -> Self {
+        self.breaker_config = config;
+        self
+    }
+
+    /// Default cap on each server's `tools/call`s in flight (0 = none);
+    /// a server's `maxConcurrent` takes precedence.
+    pub fn with_max_concurrent(mut self, max_calls: usize) -> Self {
+        self.max_concurrent = (max_calls > 0).then_some(max_calls);
+        self
+    }
+
+    /// Record backends whose stdout closes as failed in the schema cache
+    /// (cleared again once the server restarts).
+    pub fn with_failure_recording(mut self, cache: Arc<crate::cache::CacheWriter>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Where this manager writes server state, if it records it.
+    pub fn cache_writer(&self) -> Option<&Arc<crate::cache::CacheWriter>> {
+        self.cache.as_ref()
+    }
+
+    /// Set the global initialize and call timeouts.
+    /// Per-server `initTimeout` / `callTimeout` take precedence over these.
+    pub fn with_timeouts(mut self, init_timeout_secs: u64, call_timeout_secs: u64) -> Self {
+        self.init_timeout = Duration::from_secs(init_timeout_secs);
+        self.call_timeout = Duration::from_secs(call_timeout_secs);
+        self
+    }
+
+    /// Retry a failed start `retries` times, waiting `backoff_ms` before the
+    /// first retry and twice as long before each next one.
+    pub fn with_init_retries(mut self, retries: u32, backoff_ms: u64) -> Self {
+        self.init_retries = retries;
+        self.init_retry_backoff = Duration::from_millis(backoff_ms);
+        self
+    }
+
+    /// Largest message a backend may write on stdout; a longer one is
+    /// dropped and reading resumes at the next line.
+    pub fn with_max_message_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_message_bytes = max_bytes;
+        self
+    }
+
+    /// Cap on backend stderr lines logged per second per instance; the
+    /// excess is summarized.
+    pub fn with_stderr_limit(mut self, max_lines_per_sec: u32) -> Self {
+        self.stderr_max_lines_per_sec = max_lines_per_sec;
+        self
+    }
+
+    /// Route `resources/read` and `resources/unsubscribe` of a subscribed
+    /// uri to the pool instance that took the subscription (on by default).
+    pub fn with_resource_affinity(mut self, enabled: bool) -> Self {
+        self.resource_affinity = enabled;
+        self
+    }
+
+    /// Declare the `sampling` capability to servers started afterwards and
+    /// forward their `sampling/createMessage` requests (on by default).
+    pub fn with_sampling(mut self, enabled: bool) -> Self {
+        self.forward_sampling = enabled;
+        self
+    }
+
+    /// Hide the tools `filter` doesn't allow from the tool lists servers return.
+    pub fn with_tool_filter(mut self, filter: ToolFilter) -> Self {
+        self.tool_filter = Arc::new(Mutex::new(filter));
+        self
+    }
+
+    /// Edit descriptions and names in the tool lists servers return.
+    pub fn with_tool_overrides(mut self, overrides: ToolOverrides) -> Self {
+        self.tool_overrides = Arc::new(Mutex::new(overrides));
+        self
+    }
+
+    /// Receive backend requests for a client. Until this is called (and
+    /// with sampling off) backends get an error instead. Each call replaces
+    /// the previous receiver.
+    pub fn take_client_requests(&self) -> Option<mpsc::UnboundedReceiver<ClientRequest>> {
+        if !self.forward_sampling {
+            return None;
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.client_requests.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+        Some(rx)
+    }
+
+    /// Apply a new set of server configs. Servers that were removed or need a
+    /// restart are drained first: new calls to them are refused while calls
+    /// already running get up to `grace` to finish, then they are stopped.
+    pub async fn update_configs(&self, new_configs: HashMap<String, ServerConfig>, grace: Duration) {
+        let to_stop: Vec<String> = self.configs.lock().await.iter()
+            .filter(|(name, old_cfg)| new_configs.get(*name).is_none_or(|new_cfg| old_cfg.needs_restart(new_cfg)))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let deadline = tokio::time::Instant::now() + grace;
+        let mut drains = tokio::task::JoinSet::new();
+        for name in to_stop.clone() {
+            let manager = self.clone();
+            drains.spawn(async move { manager.drain_until(&name, deadline).await });
+        }
+        while drains.join_next().await.is_some() {}
+        for name in &to_stop {
+            self.stop_server(name).await;
         }
 
-        for name in to_stop {
-            self.stop_server(&name).await;
+        // Calls admitted after the drain must see the new config
+        *self.configs.lock().await = new_configs;
+        for name in &to_stop {
+            self.end_drain(name).await;
         }
+    }
+
+    /// Remember the client's experimental capabilities. Servers started after
+    /// this receive them in `initialize`; already-running servers are unaffected.
+    pub async fn set_client_experimental(&self, experimental: Option<serde_json::Value>) {
+        *self.client_experimental.lock().await = experimental;
+    }
+
+    /// Replace the tool filter, e.g. on config reload. Running servers keep
+    /// their tool lists until restarted or refreshed.
+    pub async fn set_tool_filter(&self, filter: ToolFilter) {
+        *self.tool_filter.lock().await = filter;
+    }
 
-        *current_configs = new_configs;
+    /// Replace the tool overrides; like the filter, they reach running
+    /// servers' tool lists on restart or refresh.
+    pub async fn set_tool_overrides(&self, overrides: ToolOverrides) {
+        *self.tool_overrides.lock().await = overrides;
     }
 
-    async fn resolve_name(&self, name: &str) -> Option<String> {
+    pub async fn resolve_name(&self, name: &str) -> Option<String> {
         let configs = self.configs.lock().await;
         if configs.contains_key(name) {
             return Some(name.to_string());
@@ -86,135 +592,250 @@ impl ChildManager {
         None
     }
 
-    pub async fn start_server(&self, name: &str) -> Result<Vec<ToolDef>, String> {
+    pub async fn start_server(&self, name: &str) -> Result<Vec<ToolDef>, Failure> {
         let name_resolved = self.resolve_name(name).await
-            .ok_or_else(|| format!("Unknown server: {}", name))?;
+            .ok_or_else(|| Failure::unknown_server(name))?;
         let name = name_resolved.as_str();
 
-        {
-            let pools = self.pools.lock().await;
-            if let Some(pool) = pools.get(name) {
+        loop {
+            let running = self.pools.lock().await.get(name).cloned();
+            if let Some(pool) = running {
                 let mut proc = pool.procs[0].lock().await;
                 proc.last_used = Instant::now();
                 return Ok(proc.tools.clone());
             }
+            if let Some(mut start) = self.join_start(name).await {
+                return match start.wait_for(|r| r.is_some()).await {
+                    Ok(result) => result.clone().unwrap_or_else(|| Ok(Vec::new())),
+                    Err(_) => Err(Failure::new(Kind::Unavailable, format!("Server '{}' failed to start", name))),
+                };
+            }
         }
+    }
+
+    /// The start of `name` in progress, or a new one; None if it's already
+    /// running. The start runs in its own task, so concurrent callers (on
+    /// demand calls, preload, reloads) share it instead of each spawning a
+    /// pool, and a caller that stops waiting doesn't abort it.
+    async fn join_start(&self, name: &str) -> Option<Activation> {
+        let mut activations = self.activations.lock().await;
+        if let Some((start, _)) = activations.get(name) {
+            return Some(start.clone());
+        }
+        // A finished start registers its pool before leaving `activations`
+        if self.pools.lock().await.contains_key(name) {
+            return None;
+        }
+        let (tx, rx) = watch::channel(None);
+        let manager = self.clone();
+        let server = name.to_string();
+        let task = tokio::spawn(async move {
+            let result = manager.start_with_retries(&server).await;
+            manager.activations.lock().await.remove(&server);
+            let _ = tx.send(Some(result));
+        });
+        activations.insert(name.to_string(), (rx.clone(), task));
+        Some(rx)
+    }
 
-        const MAX_RETRIES: u32 = 3;
-        const BACKOFF_MS: [u64; 3] = [500, 1000, 2000];
-        let mut last_error = String::new();
+    async fn start_with_retries(&self, name: &str) -> Result<Vec<ToolDef>, Failure> {
+        let attempts = self.init_retries + 1;
+        let mut last_error = Failure::from(String::new());
 
-        for attempt in 0..MAX_RETRIES {
+        for attempt in 0..attempts {
             if attempt > 0 {
-                let delay = BACKOFF_MS.get(attempt as usize - 1).copied().unwrap_or(2000);
-                eprintln!("[McpHub][RETRY] {} attempt {}/{} (backoff {}ms)", name, attempt + 1, MAX_RETRIES, delay);
-                tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                let delay = self.init_retry_backoff.saturating_mul(1 << (attempt - 1).min(16));
+                tracing::info!(tag = "RETRY", "{} attempt {}/{} (backoff {}ms)", name, attempt + 1, attempts, delay.as_millis());
+                tokio::time::sleep(delay).await;
             }
 
             match self.try_start_pool(name).await {
-                Ok(tools) => return Ok(tools),
+                Ok(tools) => {
+                    if attempt > 0 {
+                        tracing::info!(tag = "RETRY", "{} started on attempt {}/{}", name, attempt + 1, attempts);
+                    }
+                    return Ok(tools);
+                }
                 Err(e) => {
                     last_error = e;
-                    if attempt < MAX_RETRIES - 1 {
-                        eprintln!("[McpHub][WARN] {} failed: {} — retrying...", name, last_error);
+                    if attempt + 1 < attempts {
+                        tracing::warn!("{} failed: {} — retrying...", name, last_error);
                     }
                 }
             }
         }
 
-        Err(format!("{} (after {} attempts)", last_error, MAX_RETRIES))
+        Err(Failure::new(last_error.kind, format!("{} (after {} attempts)", last_error, attempts)))
     }
 
-    async fn try_start_pool(&self, name: &str) -> Result<Vec<ToolDef>, String> {
+    async fn try_start_pool(&self, name: &str) -> Result<Vec<ToolDef>, Failure> {
         let config = {
             let configs = self.configs.lock().await;
-            configs.get(name).ok_or_else(|| format!("Unknown server: {}", name))?.clone()
+            configs.get(name).ok_or_else(|| Failure::unknown_server(name))?.clone()
         };
 
+        config.validate_template().map_err(|e| format!("Invalid config for {}: {}", name, e))?;
         let pool_size = config.pool.max(1);
         let mut procs = Vec::new();
         let mut first_tools = Vec::new();
-
         for i in 0..pool_size {
-            let start = Instant::now();
-            if pool_size > 1 {
-                eprintln!("[McpHub][INFO] Starting server: {} (instance {}/{})", name, i + 1, pool_size);
-            } else {
-                eprintln!("[McpHub][INFO] Starting server: {}", name);
+            let proc = self.spawn_instance(name, &config, i).await?;
+            if i == 0 {
+                first_tools = proc.tools.clone();
             }
+            procs.push(Arc::new(Mutex::new(proc)));
+        }
 
-            let mut cmd = Command::new(&config.command);
-            cmd.args(&config.args)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null());
+        let pool = Arc::new(ServerPool {
+            up: procs.iter().map(|_| AtomicBool::new(true)).collect(),
+            busy: procs.iter().map(|_| AtomicUsize::new(0)).collect(),
+            procs,
+            next_idx: AtomicUsize::new(0),
+            balance: config.balance,
+        });
 
-            for (k, v) in &config.env {
-                cmd.env(k, v);
-            }
+        let mut pools = self.pools.lock().await;
+        pools.insert(name.to_string(), pool);
 
-            let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn {}: {}", name, e))?;
-            let stdin = child.stdin.take().ok_or("No stdin")?;
-            let stdout = child.stdout.take().ok_or("No stdout")?;
+        Ok(first_tools)
+    }
 
-            let reader = BufReader::new(stdout);
-            let lines = Arc::new(Mutex::new(reader.lines()));
+    /// Spawn a pool instance's process: its stdin, stdout and stderr tail.
+    fn spawn_process(
+        &self,
+        name: &str,
+        instance: &crate::config::InstanceCommand,
+    ) -> Result<(Upstream, Input, Box<dyn AsyncRead + Send + Sync + Unpin>, StderrTail), String> {
+        let mut cmd = Command::new(&instance.command);
+        cmd.args(&instance.args)
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Own process group, so shutdown reaches what a launcher (npx, sh -c) spawned
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let env = crate::config::resolve_env(&instance.env).map_err(|e| format!("Invalid config for {}: {}", name, e))?;
+        for (k, v) in &env {
+            cmd.env(k, v);
+        }
 
-            let mut proc = ChildProcess {
-                child,
-                stdin,
-                stdout_lines: lines,
-                next_id: 1,
-                tools: Vec::new(),
-                last_used: Instant::now(),
-                server_name: name.to_string(),
-                protocol_version: "2024-11-05".to_string(),
-            };
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn {}: {}", name, e))?;
+        let stdin = child.stdin.take().ok_or("No stdin")?;
+        let stdout = child.stdout.take().ok_or("No stdout")?;
+        let stderr_tail = StderrTail::default();
+        if let Some(err) = child.stderr.take() {
+            let (server, tail, max) = (name.to_string(), stderr_tail.clone(), self.stderr_max_lines_per_sec);
+            tokio::spawn(async move {
+                stderr::forward(err, max, tail, |line| tracing::info!(source = server.as_str(), tag = "STDERR", "{}", line)).await;
+            });
+        }
 
-            let init_result = send_request(
-                &mut proc,
-                "initialize",
-                serde_json::json!({
-                    "protocolVersion": "2024-11-05",
-                    "capabilities": {},
-                    "clientInfo": { "name": "McpHub", "version": "4.0.0" }
-                }),
-            )
-            .await?;
-
-            if let Some(pv) = init_result.get("protocolVersion").and_then(|v| v.as_str()) {
-                proc.protocol_version = pv.to_string();
-                if i == 0 {
-                    eprintln!("[McpHub][INFO] Server '{}' negotiated protocol: {}", name, pv);
+        Ok((Upstream::Process(child), Box::new(stdin), Box::new(stdout), stderr_tail))
+    }
+
+    /// Spawn (or connect) and initialize instance `i` of a server's pool.
+    async fn spawn_instance(&self, name: &str, config: &ServerConfig, i: usize) -> Result<ChildProcess, Failure> {
+        let pool_size = config.pool.max(1);
+        let init_timeout = config.init_timeout_secs.map(Duration::from_secs).unwrap_or(self.init_timeout);
+        let call_timeout = config.call_timeout_secs.map(Duration::from_secs).unwrap_or(self.call_timeout);
+
+        let mut client_capabilities = serde_json::json!({});
+        if let Some(exp) = self.client_experimental.lock().await.clone() {
+            client_capabilities["experimental"] = exp;
+        }
+        // Only offered once a client router takes the requests (HTTP transports)
+        if self.forward_sampling && self.client_requests.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+            client_capabilities["sampling"] = serde_json::json!({});
+        }
+
+        let start = Instant::now();
+        let (upstream, stdin, stdout, stderr_tail) = match &config.url {
+            Some(url) => {
+                tracing::info!("Connecting to server: {} ({})", name, url);
+                let headers = crate::config::resolve_headers(&config.headers)
+                    .map_err(|e| format!("Invalid config for {}: {}", name, e))?;
+                let connect = crate::remote::connect(url, headers.into_iter().collect(), self.max_message_bytes, call_timeout);
+                let (connection, input, output) = tokio::time::timeout(init_timeout, connect)
+                    .await
+                    .map_err(|_| Failure::new(Kind::Timeout, format!("{}: {} did not connect within {:.1}s", TIMEOUT_ERROR, url, init_timeout.as_secs_f64())))??;
+                let (input, output): (Input, Box<dyn AsyncRead + Send + Sync + Unpin>) = (Box::new(input), Box::new(output));
+                (Upstream::Remote(connection), input, output, StderrTail::default())
+            }
+            None => {
+                let instance = config.expand_for_instance(i);
+                if pool_size > 1 {
+                    tracing::info!(
+                        "Starting server: {} (instance {}/{}): {} {}",
+                        name, i + 1, pool_size, instance.command, instance.args.join(" ")
+                    );
+                } else {
+                    tracing::info!("Starting server: {}", name);
                 }
+                self.spawn_process(name, &instance)?
             }
+        };
+        let reader = MessageReader::new(BufReader::new(stdout), self.max_message_bytes, name);
+
+        let mut proc = ChildProcess {
+            upstream,
+            stdin: Some(stdin),
+            stdout: Arc::new(Mutex::new(reader)),
+            next_id: 1,
+            tools: Vec::new(),
+            last_used: Instant::now(),
+            server_name: name.to_string(),
+            protocol_version: "2024-11-05".to_string(),
+            call_timeout,
+            capabilities: serde_json::json!({}),
+            notify: self.notifications.clone(),
+            instance: i,
+            pins: self.pins.clone(),
+            stderr_tail,
+            client_requests: self.client_requests.clone(),
+            caller: None,
+        };
 
-            send_notification(&mut proc, "notifications/initialized", serde_json::json!({})).await?;
-            let tools_result = send_request(&mut proc, "tools/list", serde_json::json!({})).await?;
-            let tools: Vec<ToolDef> = tools_result
-                .get("tools")
-                .and_then(|v| serde_json::from_value(v.clone()).ok())
-                .unwrap_or_default();
-
+        let init_result = send_request_timeout(
+            &mut proc,
+            "initialize",
+            serde_json::json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": client_capabilities,
+                "clientInfo": { "name": "McpHub", "version": "4.0.0" }
+            }),
+            init_timeout,
+        )
+        .await?;
+
+        if let Some(pv) = init_result.get("protocolVersion").and_then(|v| v.as_str()) {
+            proc.protocol_version = pv.to_string();
             if i == 0 {
-                let elapsed = start.elapsed();
-                eprintln!("[McpHub][INFO] Server '{}' ready: {} tools in {:.0}ms", name, tools.len(), elapsed.as_secs_f64() * 1000.0);
-                first_tools = tools.clone();
+                tracing::info!("Server '{}' negotiated protocol: {}", name, pv);
             }
-
-            proc.tools = tools;
-            procs.push(Arc::new(Mutex::new(proc)));
         }
 
-        let pool = Arc::new(ServerPool {
-            procs,
-            next_idx: AtomicUsize::new(0),
-        });
+        if let Some(caps) = init_result.get("capabilities") {
+            proc.capabilities = caps.clone();
+        }
 
-        let mut pools = self.pools.lock().await;
-        pools.insert(name.to_string(), pool);
+        send_notification(&mut proc, "notifications/initialized", serde_json::json!({})).await?;
+        let tools_result = send_request_timeout(&mut proc, "tools/list", serde_json::json!({}), init_timeout).await?;
+        let tools: Vec<ToolDef> = tools_result
+            .get("tools")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let tools = self.tool_filter.lock().await.apply(name, tools);
+        let tools = self.tool_overrides.lock().await.apply(name, tools);
+
+        if i == 0 {
+            let elapsed = start.elapsed();
+            tracing::info!("Server '{}' ready: {} tools in {:.0}ms", name, tools.len(), elapsed.as_secs_f64() * 1000.0);
+        }
 
-        Ok(first_tools)
+        proc.tools = tools;
+        Ok(proc)
     }
 
     pub async fn call_method(
@@ -222,97 +843,639 @@ impl ChildManager {
         server_name: &str,
         method: &str,
         arguments: serde_json::Value,
-    ) -> Result<serde_json::Value, String> {
+    ) -> Result<serde_json::Value, Failure> {
         let resolved = self.resolve_name(server_name).await
-            .ok_or_else(|| format!("Unknown server: {}", server_name))?;
+            .ok_or_else(|| Failure::unknown_server(server_name))?;
+        let server_name = resolved.as_str();
+
+        if !self.is_running(server_name).await {
+            return Err(Failure::not_running(server_name));
+        }
+
+        let pool = {
+            let pools = self.pools.lock().await;
+            pools.get(server_name).cloned().ok_or_else(|| Failure::not_running(server_name))?
+        };
+
+        if method == "resources/read" {
+            if let Some(idx) = self.pinned_instance(server_name, &arguments)? {
+                return self.send_pinned(server_name, &pool, idx, method, arguments).await;
+            }
+        }
+
+        let idx = pool.pick().ok_or_else(|| Failure::no_healthy_instances(server_name))?;
+        let result = self.send_to_instance(server_name, &pool, idx, method, arguments.clone()).await;
+
+        match result {
+            Err(e) if is_connection_error(&e) => {
+                tracing::warn!("Connection error on '{}': {}. Retrying...", server_name, e);
+                // Route around a dead instance if the rest of the group is up
+                self.mark_instance_down(server_name, &pool, idx);
+                if let Some(other) = pool.pick() {
+                    return self.send_to_instance(server_name, &pool, other, method, arguments).await;
+                }
+                self.restart_server(server_name).await?;
+                
+                let pool = {
+                    let pools = self.pools.lock().await;
+                    pools.get(server_name).cloned().ok_or_else(|| Failure::not_running(server_name))?
+                };
+
+                let idx = pool.pick().ok_or_else(|| Failure::no_healthy_instances(server_name))?;
+                self.send_to_instance(server_name, &pool, idx, method, arguments).await
+            }
+            other => other,
+        }
+    }
+
+    /// Forward `resources/subscribe` / `resources/unsubscribe`. With resource
+    /// affinity the instance taking a subscription is remembered, so reads
+    /// and the unsubscribe of that uri reach the same instance.
+    pub async fn call_resource_subscription(
+        &self,
+        server_name: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Failure> {
+        if !self.resource_affinity {
+            return self.call_method(server_name, method, params).await;
+        }
+        let resolved = self.resolve_name(server_name).await
+            .ok_or_else(|| Failure::unknown_server(server_name))?;
+        let server_name = resolved.as_str();
+        let uri = params.get("uri").and_then(|u| u.as_str()).unwrap_or_default();
+        let key = (server_name.to_string(), uri.to_string());
+        let pinned = self.pins.lock().unwrap().get(&key).cloned();
+
+        if method == "resources/unsubscribe" {
+            self.pins.lock().unwrap().remove(&key);
+            return match pinned {
+                Some(Pin::Instance(idx)) => {
+                    let pool = self.running_pool(server_name).await?;
+                    self.send_to_instance(server_name, &pool, idx, method, params).await
+                }
+                // The instance is gone and the subscription with it
+                Some(Pin::Lost(_)) => Ok(serde_json::json!({})),
+                None => self.call_method(server_name, method, params).await,
+            };
+        }
+
+        let pool = self.running_pool(server_name).await?;
+        let idx = match pinned {
+            Some(Pin::Instance(idx)) => idx,
+            _ => pool.pick().ok_or_else(|| Failure::no_healthy_instances(server_name))?,
+        };
+        let result = self.send_to_instance(server_name, &pool, idx, method, params).await;
+        match &result {
+            Ok(_) => {
+                self.pins.lock().unwrap().insert(key, Pin::Instance(idx));
+            }
+            Err(e) if is_connection_error(e) => self.mark_instance_down(server_name, &pool, idx),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Instance holding the subscription to `params.uri`, if any.
+    /// Err once that instance is gone.
+    fn pinned_instance(&self, server_name: &str, params: &serde_json::Value) -> Result<Option<usize>, Failure> {
+        if !self.resource_affinity {
+            return Ok(None);
+        }
+        let Some(uri) = params.get("uri").and_then(|u| u.as_str()) else { return Ok(None) };
+        match self.pins.lock().unwrap().get(&(server_name.to_string(), uri.to_string())) {
+            None => Ok(None),
+            Some(Pin::Instance(idx)) => Ok(Some(*idx)),
+            Some(Pin::Lost(reason)) => Err(Failure::from(format!(
+                "Subscription to '{}' on '{}' was lost ({}); subscribe again with resources/subscribe",
+                uri, server_name, reason
+            ))),
+        }
+    }
+
+    /// Send to the instance a subscription is pinned to. No failover: another
+    /// instance doesn't hold the subscription.
+    async fn send_pinned(
+        &self,
+        server_name: &str,
+        pool: &ServerPool,
+        idx: usize,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Failure> {
+        match self.send_to_instance(server_name, pool, idx, method, params.clone()).await {
+            Err(e) if is_connection_error(&e) => {
+                self.mark_instance_down(server_name, pool, idx);
+                self.pinned_instance(server_name, &params).and(Err(e))
+            }
+            other => other,
+        }
+    }
+
+    /// Mark the subscriptions held by instance `idx` of `name` (every
+    /// instance if None) lost. Subscribers get `notifications/resources/updated`
+    /// for each uri, so their next read reports the loss.
+    fn lose_pins(&self, name: &str, idx: Option<usize>, reason: &str) {
+        let lost: Vec<String> = {
+            let mut pins = self.pins.lock().unwrap();
+            pins.iter_mut()
+                .filter(|((server, _), pin)| server == name && matches!(pin, Pin::Instance(i) if idx.is_none_or(|idx| idx == *i)))
+                .map(|((_, uri), pin)| {
+                    *pin = Pin::Lost(reason.to_string());
+                    uri.clone()
+                })
+                .collect()
+        };
+        for uri in lost {
+            tracing::warn!("Server '{}': subscription to {} lost ({})", name, uri, reason);
+            let update = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/resources/updated",
+                "params": { "uri": uri },
+            });
+            let _ = self.notifications.send((name.to_string(), update));
+        }
+    }
+
+    /// Ask a running server for its tools again, e.g. when it refused one
+    /// the cache lists. The schema cache is updated if failures are recorded.
+    pub async fn refresh_tools(&self, name: &str) -> Result<Vec<ToolDef>, Failure> {
+        let result = self.call_method(name, "tools/list", serde_json::json!({})).await?;
+        let tools: Vec<ToolDef> = result
+            .get("tools")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let tools = self.tool_filter.lock().await.apply(name, tools);
+        let tools = self.tool_overrides.lock().await.apply(name, tools);
+        if let Some(cache) = &self.cache {
+            cache.repair_server(name, tools.clone());
+        }
+        Ok(tools)
+    }
+
+    /// Send a hub-originated notification to clients, as if from `server`.
+    pub fn notify_clients(&self, server: &str, notification: serde_json::Value) {
+        let _ = self.notifications.send((server.to_string(), notification));
+    }
+
+    async fn running_pool(&self, name: &str) -> Result<Arc<ServerPool>, Failure> {
+        let pools = self.pools.lock().await;
+        pools.get(name).cloned().ok_or_else(|| Failure::not_running(name))
+    }
+
+    pub async fn call_tool(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<serde_json::Value, Failure> {
+        self.call_tool_traced(server_name, tool_name, arguments, CallContext::default()).await.0
+    }
+
+    /// `call_tool`, also reporting how the call was served.
+    pub async fn call_tool_traced(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+        context: CallContext,
+    ) -> (Result<serde_json::Value, Failure>, CallTrace) {
+        let resolved = match self.resolve_name(server_name).await {
+            Some(name) => name,
+            None => return (Err(Failure::unknown_server(server_name)), CallTrace::default()),
+        };
         let server_name = resolved.as_str();
 
+        let guard = match self.begin_call(server_name).await {
+            Ok(guard) => guard,
+            Err(e) => return (Err(e), CallTrace::default()),
+        };
+
+        let breaker_config = match self.configs.lock().await.get(server_name) {
+            Some(config) => config.circuit_breaker.apply(self.breaker_config),
+            None => self.breaker_config,
+        };
+        let allowed = {
+            let mut breakers = self.breakers.lock().await;
+            let breaker = breakers
+                .entry(server_name.to_string())
+                .or_insert_with(|| CircuitBreaker::new(breaker_config));
+            breaker.set_config(breaker_config);
+            breaker.allow(Instant::now())
+        };
+        if let Err(retry_in) = allowed {
+            return (
+                Err(Failure::new(Kind::Unavailable, format!(
+                    "Circuit open for '{}': too many consecutive failures, next attempt in {}s",
+                    server_name, retry_in
+                ))),
+                CallTrace::default(),
+            );
+        }
+        // Held until the call is done; a busy server isn't a failing one
+        let _slot = match self.call_slot(server_name).await {
+            Ok(slot) => slot,
+            Err(e) => return (Err(e), CallTrace::default()),
+        };
+
+        let mut aborted = guard.0.subscribe();
+        let mut trace = CallTrace::default();
+        let result = tokio::select! {
+            result = self.call_tool_inner(server_name, tool_name, arguments, context, &mut trace) => result,
+            _ = aborted.wait_for(|s| s.aborted) => Err(Failure::new(Kind::Aborted, format!(
+                "Call to '{}' on '{}' aborted: server was reloaded or stopped before the call finished",
+                tool_name, server_name
+            ))),
+        };
+        self.record_breaker_outcome(server_name, &result).await;
+        (result, trace)
+    }
+
+    /// Feed a call outcome to the server's breaker. Errors the backend itself
+    /// answered (MCP errors) count as success; errors unrelated to backend
+    /// health (still starting, reload) count as neither.
+    /// Clients are told the tool list changed when the circuit opens or
+    /// closes, since `tools/list` flags the tools of tripped servers.
+    async fn record_breaker_outcome(&self, server_name: &str, result: &Result<serde_json::Value, Failure>) {
+        let mut breakers = self.breakers.lock().await;
+        let Some(breaker) = breakers.get_mut(server_name) else { return };
+        let was_tripped = breaker.is_tripped();
+        match result {
+            Ok(_) => breaker.record_success(),
+            Err(e) if e.kind == Kind::Answered => breaker.record_success(),
+            Err(e) if matches!(e.kind, Kind::Starting | Kind::Aborted | Kind::Cancelled) => breaker.record_neutral(),
+            Err(_) => {
+                if breaker.record_failure(Instant::now()) {
+                    tracing::warn!(
+                        "Circuit opened for '{}' after {} consecutive failures",
+                        server_name,
+                        breaker.status().consecutive_failures
+                    );
+                }
+            }
+        }
+        if breaker.is_tripped() != was_tripped {
+            drop(breakers);
+            self.notify_clients(server_name, serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" }));
+        }
+    }
+
+    /// Servers whose circuit is open or probing.
+    pub async fn tripped_servers(&self) -> HashSet<String> {
+        let breakers = self.breakers.lock().await;
+        breakers.iter().filter(|(_, b)| b.is_tripped()).map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Circuit breaker state of every server that has been called.
+    pub async fn breaker_states(&self) -> HashMap<String, BreakerStatus> {
+        let breakers = self.breakers.lock().await;
+        breakers.iter().map(|(name, b)| (name.clone(), b.status())).collect()
+    }
+
+    /// Restart counts of every server that has been restarted or respawned.
+    pub fn restart_stats(&self) -> HashMap<String, RestartStats> {
+        self.restarts.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn note_restart(&self, name: &str, update: impl FnOnce(&mut RestartStats)) {
+        let mut restarts = self.restarts.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = restarts.entry(name.to_string()).or_default();
+        update(stats);
+        stats.last_restart_at = Some(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs());
+    }
+
+    /// Take one of the server's call slots when it has a concurrency limit:
+    /// waiting up to its call timeout for one to free up, or failing at once
+    /// with `whenBusy: reject`. None for a server without a limit.
+    async fn call_slot(&self, server_name: &str) -> Result<Option<OwnedSemaphorePermit>, Failure> {
+        let (limit, when_busy, timeout) = match self.configs.lock().await.get(server_name) {
+            Some(config) => (
+                config.concurrency.max.or(self.max_concurrent),
+                config.concurrency.when_busy,
+                config.call_timeout_secs.map(Duration::from_secs).unwrap_or(self.call_timeout),
+            ),
+            None => return Ok(None),
+        };
+        let Some(limit) = limit else { return Ok(None) };
+        let slots = {
+            let mut call_slots = self.call_slots.lock().unwrap_or_else(|e| e.into_inner());
+            let entry = call_slots.entry(server_name.to_string()).or_insert_with(|| (limit, Arc::new(Semaphore::new(limit))));
+            // A changed limit counts from the next call; calls under the old one finish as they are
+            if entry.0 != limit {
+                *entry = (limit, Arc::new(Semaphore::new(limit)));
+            }
+            entry.1.clone()
+        };
+        let busy = format!("Server '{}' is busy: {} call(s) already in flight (maxConcurrent)", server_name, limit);
+        match when_busy {
+            WhenBusy::Reject => slots.try_acquire_owned().map(Some).map_err(|_| Failure::from(busy)),
+            WhenBusy::Wait => match tokio::time::timeout(timeout, slots.acquire_owned()).await {
+                Ok(Ok(permit)) => Ok(Some(permit)),
+                _ => Err(Failure::from(format!("{}, no slot freed up within {}s", busy, timeout.as_secs()))),
+            },
+        }
+    }
+
+    async fn call_tool_inner(
+        &self,
+        server_name: &str,
+        tool_name: &str,
+        arguments: serde_json::Value,
+        context: CallContext,
+        trace: &mut CallTrace,
+    ) -> Result<serde_json::Value, Failure> {
+        let upstream = self.tool_overrides.lock().await.upstream_name(server_name, tool_name).map(String::from)
+            .ok_or_else(|| format!("Tool '{}' of server '{}' is renamed by toolOverrides", tool_name, server_name))?;
         if !self.is_running(server_name).await {
-            return Err(format!("Server not running: {}", server_name));
+            trace.cold_start = true;
+            self.activate(server_name).await?;
+        }
+
+        let pool = {
+            let pools = self.pools.lock().await;
+            pools.get(server_name).cloned().ok_or_else(|| Failure::not_running(server_name))?
+        };
+
+        let idx = pool.pick().ok_or_else(|| Failure::no_healthy_instances(server_name))?;
+        let mut params = serde_json::json!({ "name": upstream, "arguments": arguments });
+        if let Some(token) = context.progress_token {
+            params["_meta"] = serde_json::json!({ "progressToken": token });
         }
+        let (cancelled, caller) = (context.cancelled, context.session.as_deref());
+        let (result, elapsed) = self.send_call_timed(server_name, &pool, idx, "tools/call", params.clone(), cancelled.clone(), caller).await;
+        trace.add_upstream(elapsed);
+
+        match result {
+            Err(e) if is_connection_error(&e) => {
+                tracing::warn!("Connection error on '{}': {}. Retrying...", server_name, e);
+                // Route around a dead instance if the rest of the group is up
+                trace.retried = true;
+                self.mark_instance_down(server_name, &pool, idx);
+                if let Some(other) = pool.pick() {
+                    let (result, elapsed) = self.send_call_timed(server_name, &pool, other, "tools/call", params, cancelled, caller).await;
+                    trace.add_upstream(elapsed);
+                    return result;
+                }
+                self.restart_server(server_name).await?;
+                
+                let pool = {
+                    let pools = self.pools.lock().await;
+                    pools.get(server_name).cloned().ok_or_else(|| Failure::not_running(server_name))?
+                };
+
+                let idx = pool.pick().ok_or_else(|| Failure::no_healthy_instances(server_name))?;
+                let (result, elapsed) = self.send_call_timed(server_name, &pool, idx, "tools/call", params, cancelled, caller).await;
+                trace.add_upstream(elapsed);
+                result
+            }
+            other => other,
+        }
+    }
+
+    /// Register an in-flight call, or refuse it while the server is draining.
+    async fn begin_call(&self, name: &str) -> Result<CallGuard, Failure> {
+        let state = self.inflight.lock().await
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(watch::Sender::new(CallState::default())))
+            .clone();
+        let admitted = state.send_if_modified(|s| {
+            if s.draining {
+                return false;
+            }
+            s.active += 1;
+            true
+        });
+        if admitted {
+            Ok(CallGuard(state))
+        } else {
+            Err(Failure::new(Kind::Unavailable, format!("Server '{}' is draining (reloading or stopping), try again shortly", name)))
+        }
+    }
+
+    /// Stop accepting calls to `name` and wait up to `grace` for in-flight ones
+    /// to finish. Stragglers are then aborted. Returns how many were aborted.
+    /// Call `end_drain` once the server has been stopped or restarted.
+    async fn drain(&self, name: &str, grace: Duration) -> usize {
+        self.drain_until(name, tokio::time::Instant::now() + grace).await
+    }
+
+    /// `drain`, with stragglers aborted at `deadline`.
+    async fn drain_until(&self, name: &str, deadline: tokio::time::Instant) -> usize {
+        let state = self.inflight.lock().await
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(watch::Sender::new(CallState::default())))
+            .clone();
+        state.send_modify(|s| s.draining = true);
+
+        let mut rx = state.subscribe();
+        if tokio::time::timeout_at(deadline, rx.wait_for(|s| s.active == 0)).await.is_ok() {
+            return 0;
+        }
+        let stragglers = state.borrow().active;
+        tracing::warn!("Server '{}': {} call(s) still running after the grace period, aborting", name, stragglers);
+        state.send_modify(|s| s.aborted = true);
+        let _ = rx.wait_for(|s| s.active == 0).await;
+        stragglers
+    }
+
+    /// Accept calls to `name` again after a drain.
+    async fn end_drain(&self, name: &str) {
+        self.inflight.lock().await.remove(name);
+    }
+
+    /// Restart a server without cutting off in-flight calls: new calls are
+    /// rejected while those already running get up to `grace` to finish.
+    pub async fn reload_server(&self, name: &str, grace: Duration) -> Result<usize, Failure> {
+        let name = self.resolve_name(name).await
+            .ok_or_else(|| Failure::unknown_server(name))?;
+        self.drain(&name, grace).await;
+        let result = self.restart_server(&name).await;
+        self.end_drain(&name).await;
+        result
+    }
+
+    /// Stop a server after draining its in-flight calls (see `reload_server`).
+    pub async fn stop_server_gracefully(&self, name: &str, grace: Duration) -> Result<(), String> {
+        let name = self.resolve_name(name).await
+            .ok_or_else(|| Failure::unknown_server(name))?;
+        self.drain(&name, grace).await;
+        self.stop_server(&name).await;
+        self.end_drain(&name).await;
+        Ok(())
+    }
+
+    /// Start a stopped server on demand, waiting up to the activation timeout.
+    /// Concurrent callers share one start. If the timeout elapses the start
+    /// continues in the background and a "starting, try again" error is returned.
+    async fn activate(&self, name: &str) -> Result<(), Failure> {
+        let Some(mut rx) = self.join_start(name).await else { return Ok(()) };
+
+        let waited = tokio::time::timeout(self.activation_timeout, async {
+            rx.wait_for(|r| r.is_some()).await.map(|r| r.clone())
+        })
+        .await;
+        match waited {
+            Ok(Ok(result)) => result.map_or(Ok(()), |r| r.map(|_| ())),
+            Ok(Err(_)) => Err(Failure::new(Kind::Unavailable, format!("Server '{}' failed to start", name))),
+            Err(_) => Err(Failure::new(Kind::Starting, format!(
+                "Server '{}' is starting (not ready after {:.0}s), try again shortly",
+                name,
+                self.activation_timeout.as_secs_f64()
+            ))),
+        }
+    }
+
+    /// Take one instance out of rotation and log the group's new state.
+    fn mark_instance_down(&self, name: &str, pool: &ServerPool, idx: usize) {
+        if pool.up[idx].swap(false, Ordering::Relaxed) {
+            let health = pool.health();
+            tracing::info!(
+                tag = "HEALTH", "Server '{}' instance {}/{} down, group {:?} ({}/{} up)",
+                name, idx + 1, pool.procs.len(), health.state, health.up, health.total
+            );
+            self.lose_pins(name, Some(idx), &format!("instance {}/{} went down", idx + 1, pool.procs.len()));
+        }
+    }
+
+    /// Send a request to one instance of a pool. A closed stdout is handled
+    /// here (see `handle_backend_closed`) so every caller deals with it.
+    async fn send_to_instance(
+        &self,
+        name: &str,
+        pool: &ServerPool,
+        idx: usize,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Failure> {
+        self.send_call_to_instance(name, pool, idx, method, params, None).await
+    }
 
-        let pool = {
-            let pools = self.pools.lock().await;
-            pools.get(server_name).cloned().ok_or_else(|| format!("Server not running: {}", server_name))?
-        };
+    /// `send_to_instance` that gives up once `cancelled` turns true.
+    async fn send_call_to_instance(
+        &self,
+        name: &str,
+        pool: &ServerPool,
+        idx: usize,
+        method: &str,
+        params: serde_json::Value,
+        cancelled: Option<watch::Receiver<bool>>,
+    ) -> Result<serde_json::Value, Failure> {
+        self.send_call_timed(name, pool, idx, method, params, cancelled, None).await.0
+    }
 
-        let idx = pool.next_idx.fetch_add(1, Ordering::Relaxed) % pool.procs.len();
-        let result = {
+    /// `send_call_to_instance`, also returning the time from acquiring the
+    /// instance to its answer. Backend requests made meanwhile go to `caller`.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_call_timed(
+        &self,
+        name: &str,
+        pool: &ServerPool,
+        idx: usize,
+        method: &str,
+        params: serde_json::Value,
+        cancelled: Option<watch::Receiver<bool>>,
+        caller: Option<&str>,
+    ) -> (Result<serde_json::Value, Failure>, Duration) {
+        let (result, elapsed) = {
+            let _busy = BusyGuard::new(&pool.busy[idx]);
             let mut proc = pool.procs[idx].lock().await;
             proc.last_used = Instant::now();
-            send_request(&mut proc, method, arguments.clone()).await
+            proc.caller = caller.map(String::from);
+            let sent = Instant::now();
+            let result = match cancelled {
+                Some(cancelled) => send_request_cancellable(&mut proc, method, params, cancelled).await,
+                None => send_request(&mut proc, method, params).await,
+            };
+            proc.caller = None;
+            (result, sent.elapsed())
         };
-
-        match result {
-            Err(e) if is_connection_error(&e) => {
-                eprintln!("[McpHub][WARN] Connection error on '{}': {}. Retrying...", server_name, e);
-                self.restart_server(server_name).await?;
-                
-                let pool = {
-                    let pools = self.pools.lock().await;
-                    pools.get(server_name).cloned().ok_or_else(|| format!("Server not running: {}", server_name))?
-                };
-
-                let idx = pool.next_idx.fetch_add(1, Ordering::Relaxed) % pool.procs.len();
-                let mut proc = pool.procs[idx].lock().await;
-                proc.last_used = Instant::now();
-                send_request(&mut proc, method, arguments).await
+        if let Err(e) = &result {
+            if e.message.starts_with(CLOSED_ERROR) {
+                self.handle_backend_closed(name, pool, idx, &e.message).await;
+            } else if e.kind == Kind::Timeout {
+                // Likely hung: out of rotation until the health monitor revives it
+                self.mark_instance_down(name, pool, idx);
             }
-            other => other,
         }
+        (result, elapsed)
     }
 
-    pub async fn call_tool(
-        &self,
-        server_name: &str,
-        tool_name: &str,
-        arguments: serde_json::Value,
-    ) -> Result<serde_json::Value, String> {
-        let resolved = self.resolve_name(server_name).await
-            .ok_or_else(|| format!("Unknown server: {}", server_name))?;
-        let server_name = resolved.as_str();
-
-        if !self.is_running(server_name).await {
-            self.start_server(server_name).await?;
+    /// An instance's stdout hit EOF. Kill it, so a process that closed stdout
+    /// but kept running doesn't linger, and take it out of rotation. Once no
+    /// instance is left, the server is marked failed in the cache.
+    async fn handle_backend_closed(&self, name: &str, pool: &ServerPool, idx: usize, error: &str) {
+        {
+            let mut proc = pool.procs[idx].lock().await;
+            proc.upstream.kill().await;
+        }
+        self.mark_instance_down(name, pool, idx);
+        if pool.pick().is_none() {
+            if let Some(cache) = &self.cache {
+                cache.set_server_error(name, error);
+            }
+            let _ = self.crashes.send((name.to_string(), error.to_string()));
         }
+    }
 
-        let pool = {
-            let pools = self.pools.lock().await;
-            pools.get(server_name).cloned().ok_or_else(|| format!("Server not running: {}", server_name))?
-        };
+    /// Health of every running server's instance group.
+    pub async fn group_health(&self) -> HashMap<String, GroupHealth> {
+        let pools = self.pools.lock().await;
+        pools.iter().map(|(name, pool)| (name.clone(), pool.health())).collect()
+    }
 
-        let idx = pool.next_idx.fetch_add(1, Ordering::Relaxed) % pool.procs.len();
-        let result = {
-            let mut proc = pool.procs[idx].lock().await;
-            proc.last_used = Instant::now();
-            send_request(
-                &mut proc,
-                "tools/call",
-                serde_json::json!({ "name": tool_name, "arguments": arguments.clone() }),
-            ).await
+    /// Respawn instances that health checks or connection errors took out of
+    /// a group that is still running. Returns how many came back.
+    pub async fn revive_instances(&self) -> usize {
+        let degraded: Vec<(String, Arc<ServerPool>)> = {
+            let pools = self.pools.lock().await;
+            pools.iter()
+                .filter(|(_, pool)| pool.health().state == GroupState::Degraded)
+                .map(|(name, pool)| (name.clone(), pool.clone()))
+                .collect()
         };
 
-        match result {
-            Err(e) if is_connection_error(&e) => {
-                eprintln!("[McpHub][WARN] Connection error on '{}': {}. Retrying...", server_name, e);
-                self.restart_server(server_name).await?;
-                
-                let pool = {
-                    let pools = self.pools.lock().await;
-                    pools.get(server_name).cloned().ok_or_else(|| format!("Server not running: {}", server_name))?
-                };
-
-                let idx = pool.next_idx.fetch_add(1, Ordering::Relaxed) % pool.procs.len();
-                let mut proc = pool.procs[idx].lock().await;
-                proc.last_used = Instant::now();
-                send_request(
-                    &mut proc,
-                    "tools/call",
-                    serde_json::json!({ "name": tool_name, "arguments": arguments }),
-                ).await
+        let mut revived = 0;
+        for (name, pool) in degraded {
+            let config = match self.configs.lock().await.get(&name).cloned() {
+                Some(c) => c,
+                None => continue,
+            };
+            for idx in 0..pool.procs.len() {
+                if pool.up[idx].load(Ordering::Relaxed) {
+                    continue;
+                }
+                match self.spawn_instance(&name, &config, idx).await {
+                    Ok(fresh) => {
+                        let mut proc = pool.procs[idx].lock().await;
+                        proc.upstream.kill().await;
+                        *proc = fresh;
+                        pool.up[idx].store(true, Ordering::Relaxed);
+                        revived += 1;
+                        self.note_restart(&name, |s| s.respawns += 1);
+                        let health = pool.health();
+                        tracing::info!(
+                            tag = "HEALTH", "Server '{}' instance {}/{} back up, group {:?}",
+                            name, idx + 1, pool.procs.len(), health.state
+                        );
+                    }
+                    Err(e) => tracing::warn!("Server '{}' instance {} respawn failed: {}", name, idx + 1, e),
+                }
             }
-            other => other,
         }
+        revived
+    }
+
+    /// Receive notifications backends send between responses. Backend stdout
+    /// is only read while a request is in flight, so a notification is seen
+    /// on that server's next request or health ping.
+    pub fn subscribe_notifications(&self) -> broadcast::Receiver<(String, serde_json::Value)> {
+        self.notifications.subscribe()
+    }
+
+    /// Learn about servers whose every instance died (stdout closed) as
+    /// soon as a request finds out, rather than at the next health check.
+    pub fn subscribe_crashes(&self) -> broadcast::Receiver<(String, String)> {
+        self.crashes.subscribe()
     }
 
     pub async fn is_running(&self, name: &str) -> bool {
@@ -320,29 +1483,123 @@ impl ChildManager {
         pools.contains_key(name)
     }
 
-    #[allow(dead_code)]
     pub async fn stop_server(&self, name: &str) {
         let mut pools = self.pools.lock().await;
         if let Some(pool) = pools.remove(name) {
             for proc_arc in &pool.procs {
                 let mut proc = proc_arc.lock().await;
-                let _ = proc.child.kill().await;
+                proc.upstream.kill().await;
+            }
+            tracing::info!("Stopped server: {}", name);
+            self.lose_pins(name, None, "server was stopped");
+        }
+    }
+
+    /// Stop every server for good, each before the servers it depends on.
+    /// A server gets `grace` to exit once its stdin is closed and is killed
+    /// after that.
+    pub async fn shutdown(&self, grace: Duration) {
+        // A start still in progress holds a process no pool knows about;
+        // aborting it drops (and so kills) the process
+        let starts: Vec<_> = self.activations.lock().await.drain().collect();
+        for (name, (_, task)) in starts {
+            task.abort();
+            let _ = task.await;
+            tracing::info!("Aborted start of server: {}", name);
+        }
+
+        // Calls get the grace period to finish; stragglers are aborted so
+        // they release their processes
+        let deadline = tokio::time::Instant::now() + grace;
+        let names: Vec<String> = self.pools.lock().await.keys().cloned().collect();
+        let mut drains = tokio::task::JoinSet::new();
+        for name in names {
+            let manager = self.clone();
+            drains.spawn(async move { manager.drain_until(&name, deadline).await });
+        }
+        while drains.join_next().await.is_some() {}
+
+        let mut waves = shutdown_waves(&*self.configs.lock().await);
+        let mut pools: HashMap<String, Arc<ServerPool>> = self.pools.lock().await.drain().collect();
+        // Servers removed from the config meanwhile have no dependents
+        let rest: Vec<String> = pools.keys().filter(|n| !waves.iter().flatten().any(|w| w == *n)).cloned().collect();
+        match waves.first_mut() {
+            Some(first) => first.extend(rest),
+            None => waves.push(rest),
+        }
+        // A wave's instances get their grace period side by side
+        for wave in waves {
+            let mut stops = tokio::task::JoinSet::new();
+            for name in wave {
+                let Some(pool) = pools.remove(&name) else { continue };
+                for proc_arc in pool.procs.clone() {
+                    let name = name.clone();
+                    stops.spawn(async move {
+                        let mut proc = proc_arc.lock().await;
+                        (name, exit_gracefully(&mut proc, grace).await)
+                    });
+                }
+            }
+            // Server -> (instances killed, instances)
+            let mut stopped: HashMap<String, (usize, usize)> = HashMap::new();
+            while let Some(done) = stops.join_next().await {
+                match done {
+                    Ok((name, clean)) => {
+                        let counts = stopped.entry(name).or_default();
+                        counts.0 += !clean as usize;
+                        counts.1 += 1;
+                    }
+                    Err(e) => tracing::error!("Stopping a server instance panicked: {}", e),
+                }
+            }
+            let mut stopped: Vec<_> = stopped.into_iter().collect();
+            stopped.sort();
+            for (name, (killed, total)) in stopped {
+                if killed == 0 {
+                    tracing::info!("Stopped server: {} (exited cleanly)", name);
+                } else {
+                    tracing::warn!(
+                        "Stopped server: {} ({} of {} instance(s) killed after {}s grace period)",
+                        name, killed, total, grace.as_secs()
+                    );
+                }
             }
-            eprintln!("[McpHub][INFO] Stopped server: {}", name);
         }
     }
 
+    /// Kill every server at once, without a grace period.
     pub async fn stop_all(&self) {
-        let mut pools = self.pools.lock().await;
-        for (name, pool) in pools.drain() {
-            for proc_arc in &pool.procs {
-                let mut proc = proc_arc.lock().await;
-                let _ = proc.child.kill().await;
+        let pools: Vec<(String, Arc<ServerPool>)> = self.pools.lock().await.drain().collect();
+        let mut kills = tokio::task::JoinSet::new();
+        for (name, pool) in pools {
+            kills.spawn(async move {
+                for proc_arc in &pool.procs {
+                    proc_arc.lock().await.upstream.kill().await;
+                }
+                tracing::info!("Stopped server: {}", name);
+            });
+        }
+        while let Some(done) = kills.join_next().await {
+            if let Err(e) = done {
+                tracing::error!("Stopping a server panicked: {}", e);
             }
-            eprintln!("[McpHub][INFO] Stopped server: {}", name);
         }
     }
 
+    /// Initialize capabilities reported by each running server.
+    pub async fn server_capabilities(&self) -> HashMap<String, serde_json::Value> {
+        let pools: Vec<(String, Arc<ServerPool>)> = {
+            let pools = self.pools.lock().await;
+            pools.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        };
+        let mut result = HashMap::new();
+        for (name, pool) in pools {
+            let proc = pool.procs[0].lock().await;
+            result.insert(name, proc.capabilities.clone());
+        }
+        result
+    }
+
     pub async fn server_names(&self) -> Vec<String> {
         let configs = self.configs.lock().await;
         configs.keys().cloned().collect()
@@ -352,7 +1609,7 @@ impl ChildManager {
         &self,
         method: &str,
         params: serde_json::Value,
-    ) -> Vec<(String, Result<serde_json::Value, String>)> {
+    ) -> Vec<(String, Result<serde_json::Value, Failure>)> {
         let running_servers: Vec<String> = {
             let pools = self.pools.lock().await;
             pools.keys().cloned().collect()
@@ -365,13 +1622,17 @@ impl ChildManager {
                 pools.get(&name).cloned()
             };
             if let Some(pool) = pool_opt {
-                let idx = pool.next_idx.fetch_add(1, Ordering::Relaxed) % pool.procs.len();
-                let mut proc = pool.procs[idx].lock().await;
-                proc.last_used = Instant::now();
-                let res = send_request(&mut proc, method, params.clone()).await;
+                let res = match pool.pick() {
+                    Some(idx) => {
+                        let mut proc = pool.procs[idx].lock().await;
+                        proc.last_used = Instant::now();
+                        send_request(&mut proc, method, params.clone()).await
+                    }
+                    None => Err(Failure::no_healthy_instances(&name)),
+                };
                 results.push((name, res));
             } else {
-                results.push((name, Err("Server stopped".into())));
+                results.push((name, Err(Failure::new(Kind::Unavailable, "Server stopped"))));
             }
         }
         
@@ -386,7 +1647,7 @@ impl ChildManager {
     ) -> Result<(), String> {
         let pool = {
             let pools = self.pools.lock().await;
-            pools.get(server_name).cloned().ok_or_else(|| format!("Server not running: {}", server_name))?
+            pools.get(server_name).cloned().ok_or_else(|| Failure::not_running(server_name))?
         };
 
         // Forward to all instances in the pool to ensure it hits the right one
@@ -422,10 +1683,39 @@ impl ChildManager {
             if let Some(pool) = pools.remove(&name) {
                 for proc_arc in &pool.procs {
                     let mut proc = proc_arc.lock().await;
-                    let _ = proc.child.kill().await;
+                    proc.upstream.kill().await;
                 }
-                eprintln!("[McpHub][INFO] Idle-stopped server: {}", name);
+                tracing::info!("Idle-stopped server: {}", name);
+            }
+        }
+    }
+
+    /// Send `ping` to one instance of a running server and return how long it
+    /// took to answer. The outcome feeds the server's circuit breaker like a
+    /// tool call, and an instance whose connection fails is taken out of
+    /// rotation. A stopped server is not started.
+    pub async fn ping_server(&self, name: &str) -> Result<Duration, Failure> {
+        let resolved = self.resolve_name(name).await
+            .ok_or_else(|| Failure::unknown_server(name))?;
+        let name = resolved.as_str();
+        let pool = self.pools.lock().await.get(name).cloned()
+            .ok_or_else(|| Failure::not_running(name))?;
+        let idx = pool.pick().ok_or_else(|| Failure::no_healthy_instances(name))?;
+
+        let started = Instant::now();
+        let result = match tokio::time::timeout(PING_TIMEOUT, self.send_to_instance(name, &pool, idx, "ping", serde_json::json!({}))).await {
+            Ok(Err(e)) if is_connection_error(&e) => {
+                self.mark_instance_down(name, &pool, idx);
+                Err(e)
             }
+            Ok(result) => result,
+            Err(_) => Err(Failure::new(Kind::Timeout, format!("Ping to '{}' timed out after {}s", name, PING_TIMEOUT.as_secs()))),
+        };
+        self.record_breaker_outcome(name, &result).await;
+        // A backend that answers with an error (e.g. no `ping` method) is still responsive
+        match result {
+            Err(e) if e.kind != Kind::Answered => Err(e),
+            _ => Ok(started.elapsed()),
         }
     }
 
@@ -440,53 +1730,42 @@ impl ChildManager {
                 None => continue,
             };
 
-            let mut pool_dead = false;
-            let mut reason = String::new();
-
-            for proc_arc in &pool.procs {
-                let mut proc = proc_arc.lock().await;
-                
-                match proc.child.try_wait() {
-                    Ok(Some(status)) => {
-                        pool_dead = true;
-                        reason = format!("Process exited: {}", status);
-                        break;
-                    }
-                    Ok(None) => {} 
-                    Err(e) => {
-                        pool_dead = true;
-                        reason = format!("Process check failed: {}", e);
-                        break;
-                    }
+            // Check each instance that is still in rotation; a failed one is
+            // killed and taken out. The group is only dead once all are out.
+            let mut reason = "All instances down".to_string();
+            for (idx, proc_arc) in pool.procs.iter().enumerate() {
+                if !pool.up[idx].load(Ordering::Relaxed) {
+                    continue;
                 }
-
-                let ping_timeout = std::time::Duration::from_secs(5);
-                let ping_result = tokio::time::timeout(
-                    ping_timeout,
-                    send_request_inner(&mut proc, "ping", serde_json::json!({})),
-                ).await;
-
-                match ping_result {
-                    Ok(Ok(_)) => {} 
-                    Ok(Err(e)) => {
-                        pool_dead = true;
-                        reason = format!("Ping error: {}", e);
-                        break;
-                    }
-                    Err(_) => {
-                        pool_dead = true;
-                        reason = "Ping timeout (5s)".to_string();
-                        break;
+                let mut proc = proc_arc.lock().await;
+                let failure = match proc.upstream.gone() {
+                    Some(reason) => Some(reason),
+                    None => {
+                        let ping_result = tokio::time::timeout(
+                            PING_TIMEOUT,
+                            send_request_inner(&mut proc, "ping", serde_json::json!({})),
+                        ).await;
+                        match ping_result {
+                            Ok(Ok(_)) => None,
+                            Ok(Err(e)) => Some(format!("Ping error: {}", e)),
+                            Err(_) => Some(format!("Ping timeout ({}s)", PING_TIMEOUT.as_secs())),
+                        }
                     }
+                };
+                if let Some(failure) = failure {
+                    proc.upstream.kill().await;
+                    self.mark_instance_down(&name, &pool, idx);
+                    reason = failure;
                 }
             }
+            let pool_dead = pool.health().state == GroupState::Failed;
 
             if pool_dead {
                 dead_servers.push((name.clone(), reason));
                 if let Some(pool) = pools.remove(&name) {
                     for proc_arc in &pool.procs {
                         let mut proc = proc_arc.lock().await;
-                        let _ = proc.child.kill().await;
+                        proc.upstream.kill().await;
                     }
                 }
             }
@@ -495,37 +1774,173 @@ impl ChildManager {
         dead_servers
     }
 
-    pub async fn restart_server(&self, name: &str) -> Result<usize, String> {
+    pub async fn restart_server(&self, name: &str) -> Result<usize, Failure> {
         {
             let mut pools = self.pools.lock().await;
             if let Some(pool) = pools.remove(name) {
                 for proc_arc in &pool.procs {
                     let mut proc = proc_arc.lock().await;
-                    let _ = proc.child.kill().await;
+                    proc.upstream.kill().await;
                 }
             }
         }
+        self.lose_pins(name, None, "server was restarted");
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-        let tools = self.start_server(name).await?;
-        Ok(tools.len())
+        let started = self.start_server(name).await;
+        self.note_restart(name, |s| {
+            s.restarts += 1;
+            s.failed_restarts += started.is_err() as u64;
+        });
+        let tools = match started {
+            Ok(tools) => tools,
+            Err(e) => {
+                if let Some(cache) = &self.cache {
+                    cache.set_server_error(name, &e.message);
+                }
+                return Err(e);
+            }
+        };
+        let count = tools.len();
+        if let Some(cache) = &self.cache {
+            // Back up: clears a failure recorded when its stdout closed
+            cache.repair_server(name, tools);
+        }
+        Ok(count)
+    }
+}
+
+/// Log a backend's `notifications/message` at the matching level, tagged
+/// with the level it sent.
+fn log_server_message(server: &str, level: &str, data: &str) {
+    let tag = level.to_uppercase();
+    let tag = tag.as_str();
+    match level {
+        "debug" => tracing::debug!(source = server, tag, "{}", data),
+        "info" | "notice" => tracing::info!(source = server, tag, "{}", data),
+        "warning" => tracing::warn!(source = server, tag, "{}", data),
+        _ => tracing::error!(source = server, tag, "{}", data),
+    }
+}
+
+/// Whether a resource update comes from the instance the uri is pinned to
+/// (or from any instance for uris nobody pinned).
+fn holds_subscription(proc: &ChildProcess, update: &serde_json::Value) -> bool {
+    let Some(uri) = update.pointer("/params/uri").and_then(|u| u.as_str()) else { return true };
+    match proc.pins.lock().unwrap().get(&(proc.server_name.clone(), uri.to_string())) {
+        None => true,
+        Some(Pin::Instance(idx)) => *idx == proc.instance,
+        Some(Pin::Lost(_)) => false,
+    }
+}
+
+fn is_connection_error(e: &Failure) -> bool {
+    e.kind == Kind::Disconnected
+}
+
+/// Prefix of the error for a backend whose stdout hit EOF.
+const CLOSED_ERROR: &str = "Server closed connection";
+/// Prefix of the error for a request the backend didn't answer in time.
+const TIMEOUT_ERROR: &str = "Upstream timed out";
+
+/// "exited cleanly", "crashed (exit code 3)", ...
+fn describe_exit(status: std::process::ExitStatus) -> String {
+    if status.success() {
+        return "exited cleanly".into();
+    }
+    if let Some(code) = status.code() {
+        return format!("crashed (exit code {})", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("crashed (signal {})", signal);
+        }
+    }
+    format!("crashed ({})", status)
+}
+
+/// Why a backend's stdout closed, from its exit status if it has one.
+async fn describe_closed(upstream: &mut Upstream, stderr_tail: &StderrTail) -> String {
+    let child = match upstream {
+        Upstream::Process(child) => child,
+        Upstream::Remote(connection) => {
+            let reason = connection.closed_reason().unwrap_or_else(|| "event stream closed".into());
+            return format!("{}: {}", CLOSED_ERROR, reason);
+        }
+    };
+    let last_words = stderr_tail.last().map(|l| format!(" (last stderr: {})", l)).unwrap_or_default();
+    // The exit status can trail the EOF slightly
+    for _ in 0..10 {
+        if let Ok(Some(status)) = child.try_wait() {
+            return format!("{}: process {}{}", CLOSED_ERROR, describe_exit(status), last_words);
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
     }
+    format!("{}: stdout closed but the process is still running{}", CLOSED_ERROR, last_words)
 }
 
-fn is_connection_error(e: &str) -> bool {
-    e.contains("Write error") || e.contains("Flush error") || e.contains("Read error") || e.contains("Server closed connection")
+/// A failed write to or read from an instance.
+fn disconnected(what: &str, e: impl std::fmt::Display) -> Failure {
+    Failure::new(Kind::Disconnected, format!("{}: {}", what, e))
 }
 
-const REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Backend notifications buffered for slow subscribers before they lag.
+const NOTIFICATION_BUFFER: usize = 256;
+/// Default startup handshake timeout: servers can be slow to boot (npx, uvx).
+const DEFAULT_INIT_TIMEOUT_SECS: u64 = 60;
+/// Default per-call timeout: calls should be responsive.
+const DEFAULT_CALL_TIMEOUT_SECS: u64 = 30;
+/// Default wait for an on-demand start before telling the caller to retry.
+const DEFAULT_ACTIVATION_TIMEOUT_SECS: u64 = 60;
 
+/// Send a request using the process's call timeout.
 async fn send_request(
     proc: &mut ChildProcess,
     method: &str,
     params: serde_json::Value,
-) -> Result<serde_json::Value, String> {
-    let timeout = std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS);
+) -> Result<serde_json::Value, Failure> {
+    let timeout = proc.call_timeout;
+    send_request_timeout(proc, method, params, timeout).await
+}
+
+/// `send_request` that stops waiting when `cancelled` turns true, and
+/// tells the backend to stop working on the request.
+async fn send_request_cancellable(
+    proc: &mut ChildProcess,
+    method: &str,
+    params: serde_json::Value,
+    mut cancelled: watch::Receiver<bool>,
+) -> Result<serde_json::Value, Failure> {
+    // The id send_request_inner is about to use
+    let id = proc.next_id;
+    let outcome = tokio::select! {
+        result = send_request(proc, method, params) => Some(result),
+        Ok(_) = cancelled.wait_for(|c| *c) => None,
+    };
+    if let Some(result) = outcome {
+        return result;
+    }
+    tracing::info!("{} request {} to '{}' cancelled by the client", method, id, proc.server_name);
+    let _ = send_notification(proc, "notifications/cancelled", serde_json::json!({ "requestId": id, "reason": "Cancelled by the client" })).await;
+    Err(Failure::new(Kind::Cancelled, format!("{} cancelled by the client", method)))
+}
+
+async fn send_request_timeout(
+    proc: &mut ChildProcess,
+    method: &str,
+    params: serde_json::Value,
+    timeout: Duration,
+) -> Result<serde_json::Value, Failure> {
+    // The id send_request_inner is about to use
+    let id = proc.next_id;
     match tokio::time::timeout(timeout, send_request_inner(proc, method, params)).await {
         Ok(result) => result,
-        Err(_) => Err(format!("Timeout: server did not respond within {}s", REQUEST_TIMEOUT_SECS)),
+        Err(_) => {
+            // Don't leave the backend working on an answer nobody waits for
+            let _ = send_notification(proc, "notifications/cancelled", serde_json::json!({ "requestId": id, "reason": "Request timed out" })).await;
+            Err(Failure::new(Kind::Timeout, format!("{}: server did not respond to {} within {:.1}s", TIMEOUT_ERROR, method, timeout.as_secs_f64())))
+        }
     }
 }
 
@@ -533,7 +1948,7 @@ async fn send_request_inner(
     proc: &mut ChildProcess,
     method: &str,
     params: serde_json::Value,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, Failure> {
     let id = proc.next_id;
     proc.next_id += 1;
 
@@ -547,44 +1962,55 @@ async fn send_request_inner(
     let mut msg = serde_json::to_string(&request).map_err(|e| e.to_string())?;
     msg.push('\n');
 
-    proc.stdin
+    let stdin = proc.stdin.as_mut().ok_or_else(|| disconnected("Write error", "stdin is closed"))?;
+    stdin
         .write_all(msg.as_bytes())
         .await
-        .map_err(|e| format!("Write error: {}", e))?;
-    proc.stdin
+        .map_err(|e| disconnected("Write error", e))?;
+    stdin
         .flush()
         .await
-        .map_err(|e| format!("Flush error: {}", e))?;
+        .map_err(|e| disconnected("Flush error", e))?;
 
-    let mut lines = proc.stdout_lines.lock().await;
+    let stdout = proc.stdout.clone();
+    let mut stdout = stdout.lock().await;
+    // Backend requests being answered; reading goes on meanwhile, and any
+    // still waiting when the call ends are dropped with it
+    let mut answers = tokio::task::JoinSet::new();
     loop {
-        let line = lines
-            .next_line()
-            .await
-            .map_err(|e| format!("Read error: {}", e))?
-            .ok_or("Server closed connection")?;
+        let message = tokio::select! {
+            message = stdout.next_message() => message,
+            Some(Ok(response)) = answers.join_next() => {
+                write_message(proc, &response).await?;
+                continue;
+            }
+        };
+        let parsed = match message.map_err(|e| disconnected("Read error", e))? {
+            Some(parsed) => parsed,
+            None => return Err(Failure::new(Kind::Disconnected, describe_closed(&mut proc.upstream, &proc.stderr_tail).await)),
+        };
 
-        let line = line.trim().to_string();
-        if line.is_empty() {
+        if parsed.get("id").is_some() && parsed.get("method").is_some() {
+            answers.spawn(answer_backend_request(proc, parsed));
             continue;
         }
 
-        let parsed: serde_json::Value = match serde_json::from_str(&line) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-
         if parsed.get("id").is_none() {
             if let Some(method) = parsed.get("method").and_then(|v| v.as_str()) {
                 if method == "notifications/message" {
                     if let Some(params) = parsed.get("params") {
                         if let Some(level) = params.get("level").and_then(|v| v.as_str()) {
                             if let Some(data) = params.get("data").and_then(|v| v.as_str()) {
-                                eprintln!("[McpHub][{}][{}] {}", proc.server_name, level.to_uppercase(), data);
+                                log_server_message(&proc.server_name, level, data);
                             }
                         }
                     }
                 }
+                if method == "notifications/resources/updated" && !holds_subscription(proc, &parsed) {
+                    continue;
+                }
+                // No receivers is fine: nobody is subscribed
+                let _ = proc.notify.send((proc.server_name.clone(), parsed));
             }
             continue;
         }
@@ -592,7 +2018,7 @@ async fn send_request_inner(
         if let Some(resp_id) = parsed.get("id") {
             if resp_id.as_u64() == Some(id) {
                 if let Some(error) = parsed.get("error") {
-                    return Err(format!("MCP error: {}", error));
+                    return Err(Failure::new(Kind::Answered, format!("{}{}", crate::error::UPSTREAM_PREFIX, error)));
                 }
                 return Ok(parsed.get("result").cloned().unwrap_or(serde_json::Value::Null));
             }
@@ -604,24 +2030,619 @@ async fn send_notification(
     proc: &mut ChildProcess,
     method: &str,
     params: serde_json::Value,
-) -> Result<(), String> {
+) -> Result<(), Failure> {
     let notification = serde_json::json!({
         "jsonrpc": "2.0",
         "method": method,
         "params": params,
     });
+    write_message(proc, &notification).await
+}
+
+/// Answer a request the backend sent us while a request of ours was in
+/// flight: `sampling/createMessage` goes to the calling session's client,
+/// anything else is refused. The answer doesn't borrow `proc`, so the
+/// caller keeps reading the backend while it waits.
+fn answer_backend_request(proc: &ChildProcess, request: serde_json::Value) -> impl std::future::Future<Output = serde_json::Value> + Send + 'static {
+    let id = request["id"].clone();
+    let method = request.get("method").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    let handler = match method.as_str() {
+        "sampling/createMessage" => proc.client_requests.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        _ => None,
+    };
+    let (server, session) = (proc.server_name.clone(), proc.caller.clone());
+    async move {
+        let reply = match handler {
+            Some(handler) => {
+                let (reply, answer) = oneshot::channel();
+                let sent = handler.send(ClientRequest { server, session, request, reply });
+                match sent {
+                    Ok(()) => answer.await.ok(),
+                    Err(_) => None,
+                }
+            }
+            None => None,
+        };
+        let mut response = reply.unwrap_or_else(|| {
+            serde_json::json!({ "error": { "code": crate::error::METHOD_NOT_FOUND, "message": format!("Method not supported: {}", method) } })
+        });
+        response["jsonrpc"] = serde_json::json!("2.0");
+        response["id"] = id;
+        response
+    }
+}
 
-    let mut msg = serde_json::to_string(&notification).map_err(|e| e.to_string())?;
+async fn write_message(proc: &mut ChildProcess, message: &serde_json::Value) -> Result<(), Failure> {
+    let mut msg = serde_json::to_string(message).map_err(|e| e.to_string())?;
     msg.push('\n');
 
-    proc.stdin
+    let stdin = proc.stdin.as_mut().ok_or_else(|| disconnected("Write error", "stdin is closed"))?;
+    stdin
         .write_all(msg.as_bytes())
         .await
-        .map_err(|e| format!("Write error: {}", e))?;
-    proc.stdin
+        .map_err(|e| disconnected("Write error", e))?;
+    stdin
         .flush()
         .await
-        .map_err(|e| format!("Flush error: {}", e))?;
+        .map_err(|e| disconnected("Flush error", e))?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Mock MCP server: sleeps `init_delay` before answering initialize,
+    /// answers tools/list immediately, then sleeps `call_delay` before each call.
+    fn mock_server(init_delay: &str, call_delay: &str) -> ServerConfig {
+        let script = format!(
+            r#"read l; sleep {init}; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05"}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{{"name":"echo"}}]}}}}'
+id=3
+while read l; do sleep {call}; echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{}}}}"; id=$((id+1)); done"#,
+            init = init_delay,
+            call = call_delay,
+        );
+        ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script],
+            ..Default::default()
+        }
+    }
+
+    fn manager(server: ServerConfig, init: Duration, call: Duration) -> ChildManager {
+        let mut configs = HashMap::new();
+        configs.insert("mock".to_string(), server);
+        let mut m = ChildManager::new(configs, 60_000);
+        m.init_timeout = init;
+        m.call_timeout = call;
+        m
+    }
+
+    #[tokio::test]
+    async fn test_slow_init_uses_init_timeout() {
+        // Slow to boot but fast to respond: a generous init timeout and a
+        // tight call timeout both succeed.
+        let m = manager(mock_server("0.5", "0"), Duration::from_secs(5), Duration::from_millis(200));
+        let tools = m.try_start_pool("mock").await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert!(m.call_tool("mock", "echo", serde_json::json!({})).await.is_ok());
+        m.stop_all().await;
+
+        // The same server fails if the init timeout is tighter than its boot time,
+        // even though the call timeout is generous.
+        let m = manager(mock_server("0.5", "0"), Duration::from_millis(200), Duration::from_secs(5));
+        let err = m.try_start_pool("mock").await.unwrap_err().message;
+        assert!(err.starts_with(TIMEOUT_ERROR) && err.contains("initialize"), "{}", err);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_slow_first_start_is_retried() {
+        // Too slow for the init timeout on its first launch only, like a
+        // server building an index the first time it runs
+        let flag = std::env::temp_dir().join(format!("mcphub_first_start_{}", std::process::id()));
+        let _ = std::fs::remove_file(&flag);
+        let delay = format!("$(test -e {0} && echo 0 || (touch {0}; echo 1))", flag.display());
+        let m = manager(mock_server(&delay, "0"), Duration::from_millis(300), Duration::from_secs(5)).with_init_retries(0, 10);
+        assert!(m.start_with_retries("mock").await.unwrap_err().message.ends_with("(after 1 attempts)"));
+        m.stop_all().await;
+
+        let _ = std::fs::remove_file(&flag);
+        let m = manager(mock_server(&delay, "0"), Duration::from_millis(300), Duration::from_secs(5)).with_init_retries(2, 10);
+        assert_eq!(m.start_with_retries("mock").await.unwrap().len(), 1);
+        m.stop_all().await;
+        let _ = std::fs::remove_file(&flag);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_queues_or_rejects() {
+        let server = ServerConfig {
+            concurrency: crate::config::Concurrency { max: Some(1), when_busy: WhenBusy::Reject },
+            ..mock_server("0", "0.3")
+        };
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+        let (a, b) = tokio::join!(m.call_tool("mock", "echo", serde_json::json!({})), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            m.call_tool("mock", "echo", serde_json::json!({})).await
+        });
+        assert!(a.is_ok());
+        assert!(b.unwrap_err().message.contains("is busy: 1 call(s) already in flight"));
+        // Rejections don't trip the circuit breaker
+        assert_eq!(m.breaker_states().await["mock"].consecutive_failures, 0);
+
+        // Waiting, the second call runs once the first is done
+        m.configs.lock().await.get_mut("mock").unwrap().concurrency.when_busy = WhenBusy::Wait;
+        let started = Instant::now();
+        let (a, b) = tokio::join!(
+            m.call_tool("mock", "echo", serde_json::json!({})),
+            m.call_tool("mock", "echo", serde_json::json!({})),
+        );
+        assert!(a.is_ok() && b.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(600));
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_slow_call_uses_call_timeout() {
+        let m = manager(mock_server("0", "0.5"), Duration::from_secs(5), Duration::from_millis(200));
+        m.try_start_pool("mock").await.unwrap();
+        let failure = m.call_method("mock", "tools/call", serde_json::json!({})).await.unwrap_err();
+        let err = failure.message;
+        assert!(failure.kind == Kind::Timeout && err.starts_with(TIMEOUT_ERROR) && err.contains("tools/call"), "{}", err);
+        // The server is marked unhealthy
+        assert_eq!(m.group_health().await["mock"].state, GroupState::Failed);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_dead_server_reported_as_crash() {
+        // Exits on its first request after the handshake
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}'
+read l; exit 3"#;
+        let server = ServerConfig { command: "sh".into(), args: vec!["-c".into(), script.into()], ..mock_server("0", "0") };
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+        let mut crashes = m.subscribe_crashes();
+        m.try_start_pool("mock").await.unwrap();
+
+        let failure = m.call_method("mock", "resources/list", serde_json::json!({})).await.unwrap_err();
+        let err = failure.message;
+        assert!(failure.kind == Kind::Disconnected && err.starts_with(CLOSED_ERROR), "{}", err);
+        let (name, reason) = crashes.try_recv().unwrap();
+        assert_eq!(name, "mock");
+        assert!(reason.contains("exit code 3"), "{}", reason);
+        assert_eq!(m.restart_server("mock").await, Ok(0));
+        assert_eq!(m.group_health().await["mock"].state, GroupState::Healthy);
+        // Once by the failed call, once here
+        let stats = &m.restart_stats()["mock"];
+        assert_eq!((stats.restarts, stats.failed_restarts, stats.respawns), (2, 0, 0));
+        assert!(stats.last_restart_at.is_some());
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_starts_spawn_one_process() {
+        let spawns = std::env::temp_dir().join(format!("mcphub-spawns-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&spawns);
+        let mut server = mock_server("0.3", "0");
+        server.args[1] = format!("echo up >> {}\n{}", spawns.display(), server.args[1]);
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5)).with_activation_timeout(5);
+
+        let (a, b, call) = tokio::join!(
+            m.start_server("mock"),
+            m.start_server("mock"),
+            m.call_tool("mock", "echo", serde_json::json!({})),
+        );
+        assert_eq!(a.unwrap().len(), 1);
+        assert_eq!(b.unwrap().len(), 1);
+        assert!(call.is_ok());
+        let spawned = std::fs::read_to_string(&spawns).unwrap_or_default().lines().count();
+        let _ = std::fs::remove_file(&spawns);
+        assert_eq!(spawned, 1);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_cold_call_waits_for_activation() {
+        let m = manager(mock_server("0.5", "0"), Duration::from_secs(5), Duration::from_secs(5));
+        let m = m.with_activation_timeout(5);
+        assert!(m.call_tool("mock", "echo", serde_json::json!({})).await.is_ok());
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_cold_call_past_activation_timeout() {
+        let mut m = manager(mock_server("0.5", "0"), Duration::from_secs(5), Duration::from_secs(5));
+        m.activation_timeout = Duration::from_millis(100);
+        let err = m.call_tool("mock", "echo", serde_json::json!({})).await.unwrap_err().message;
+        assert!(err.contains("is starting"), "{}", err);
+
+        // The start carried on in the background; a retry succeeds once it is up.
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(m.call_tool("mock", "echo", serde_json::json!({})).await.is_ok());
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_experimental_capabilities_round_trip() {
+        // The mock echoes the raw initialize request back under experimental.echo
+        let script = r#"read l; echo "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"capabilities\":{\"experimental\":{\"echo\":$l}}}}"
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}'
+cat > /dev/null"#;
+        let mut server = mock_server("0", "0");
+        server.args = vec!["-c".into(), script.into()];
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+
+        m.set_client_experimental(Some(serde_json::json!({"x-client": {"on": true}}))).await;
+        m.try_start_pool("mock").await.unwrap();
+
+        let caps = m.server_capabilities().await;
+        let backend = &caps["mock"];
+        // Client -> backend: forwarded in the initialize params
+        assert_eq!(
+            backend["experimental"]["echo"]["params"]["capabilities"]["experimental"],
+            serde_json::json!({"x-client": {"on": true}})
+        );
+        // Backend -> client: merged into the hub's advertised capabilities
+        let hub = crate::protocol::Capabilities::from_backends([backend]);
+        assert!(hub.experimental.unwrap().get("echo").is_some());
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_inflight_call_completes_across_reload() {
+        let m = manager(mock_server("0", "0.5"), Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+
+        let caller = m.clone();
+        let call = tokio::spawn(async move { caller.call_tool("mock", "echo", serde_json::json!({})).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let reloader = m.clone();
+        let reload = tokio::spawn(async move { reloader.reload_server("mock", Duration::from_secs(5)).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // New calls are refused while the reload drains
+        let err = m.call_tool("mock", "echo", serde_json::json!({})).await.unwrap_err().message;
+        assert!(err.contains("is draining"), "{}", err);
+
+        assert!(call.await.unwrap().is_ok());
+        assert_eq!(reload.await.unwrap().unwrap(), 1);
+        assert!(m.call_tool("mock", "echo", serde_json::json!({})).await.is_ok());
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_ping_server_feeds_health_and_breaker() {
+        let m = manager(mock_server("0", "0"), Duration::from_secs(5), Duration::from_secs(5));
+        assert_eq!(m.ping_server("mock").await.unwrap_err().message, "Server not running: mock");
+        m.try_start_pool("mock").await.unwrap();
+        assert!(m.call_tool("mock", "echo", serde_json::json!({})).await.is_ok());
+        assert!(m.ping_server("mock").await.is_ok());
+
+        let pool = m.pools.lock().await["mock"].clone();
+        pool.procs[0].lock().await.upstream.kill().await;
+        assert!(m.ping_server("mock").await.is_err());
+        assert_eq!(m.group_health().await["mock"].state, GroupState::Failed);
+        assert_eq!(m.breaker_states().await["mock"].consecutive_failures, 1);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_drains_removed_server() {
+        let m = manager(mock_server("0", "0.5"), Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+
+        let caller = m.clone();
+        let call = tokio::spawn(async move { caller.call_tool("mock", "echo", serde_json::json!({})).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let updater = m.clone();
+        let update = tokio::spawn(async move { updater.update_configs(HashMap::new(), Duration::from_secs(5)).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let err = m.call_tool("mock", "echo", serde_json::json!({})).await.unwrap_err().message;
+        assert!(err.contains("is draining"), "{}", err);
+
+        // The running call finishes before the server is stopped
+        assert!(call.await.unwrap().is_ok());
+        update.await.unwrap();
+        assert!(m.group_health().await.is_empty());
+        assert!(m.resolve_name("mock").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reload_aborts_calls_past_grace_period() {
+        let m = manager(mock_server("0", "2"), Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+
+        let caller = m.clone();
+        let call = tokio::spawn(async move { caller.call_tool("mock", "echo", serde_json::json!({})).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        m.reload_server("mock", Duration::from_millis(200)).await.unwrap();
+        let err = call.await.unwrap().unwrap_err().message;
+        assert!(err.contains("aborted"), "{}", err);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_one_instance_down_degrades_group() {
+        let mut server = mock_server("0", "0");
+        server.pool = 2;
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+        assert_eq!(m.group_health().await["mock"].state, GroupState::Healthy);
+
+        let pool = m.pools.lock().await["mock"].clone();
+        pool.procs[0].lock().await.upstream.kill().await;
+
+        // Not reported dead: the group is still serving
+        assert!(m.health_check().await.is_empty());
+        let health = m.group_health().await["mock"];
+        assert_eq!(health, GroupHealth { state: GroupState::Degraded, up: 1, total: 2 });
+
+        // Every call lands on the surviving instance
+        for _ in 0..3 {
+            assert!(m.call_tool("mock", "echo", serde_json::json!({})).await.is_ok());
+        }
+
+        assert_eq!(m.revive_instances().await, 1);
+        assert_eq!(m.group_health().await["mock"].state, GroupState::Healthy);
+        assert_eq!(m.restart_stats()["mock"].respawns, 1);
+        m.stop_all().await;
+    }
+
+    #[test]
+    fn test_least_in_flight_picks_idlest_up_instance() {
+        let next_idx = AtomicUsize::new(0);
+        let up: Vec<AtomicBool> = (0..3).map(|_| AtomicBool::new(true)).collect();
+        let busy: Vec<AtomicUsize> = [2, 0, 1].into_iter().map(AtomicUsize::new).collect();
+        assert_eq!(pick_instance(Balance::LeastInFlight, &next_idx, &up, &busy), Some(1));
+
+        up[1].store(false, Ordering::Relaxed);
+        assert_eq!(pick_instance(Balance::LeastInFlight, &next_idx, &up, &busy), Some(2));
+
+        // Idle instances take turns
+        up[1].store(true, Ordering::Relaxed);
+        busy.iter().for_each(|b| b.store(0, Ordering::Relaxed));
+        let picks: Vec<_> = (0..3).map(|_| pick_instance(Balance::LeastInFlight, &next_idx, &up, &busy).unwrap()).collect();
+        assert_eq!(picks.iter().collect::<std::collections::HashSet<_>>().len(), 3);
+
+        up.iter().for_each(|u| u.store(false, Ordering::Relaxed));
+        assert_eq!(pick_instance(Balance::LeastInFlight, &next_idx, &up, &busy), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_resource_sticks_to_its_instance() {
+        // Answers every request with its instance, after an update for file:///a
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}'
+id=3
+while read l; do
+echo '{"jsonrpc":"2.0","method":"notifications/resources/updated","params":{"uri":"file:///a"}}'
+echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"instance\":\"$1\"}}"; id=$((id+1))
+done"#;
+        let mut server = mock_server("0", "0");
+        server.args = vec!["-c".into(), script.into(), "mock".into(), "{instance}".into()];
+        server.pool = 3;
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+        let mut updates = m.subscribe_notifications();
+        let uri = serde_json::json!({ "uri": "file:///a" });
+
+        let sub = m.call_resource_subscription("mock", "resources/subscribe", uri.clone()).await.unwrap();
+        let holder = sub["instance"].clone();
+        for _ in 0..4 {
+            assert_eq!(m.call_method("mock", "resources/read", uri.clone()).await.unwrap()["instance"], holder);
+        }
+
+        // Other instances' updates for the uri aren't forwarded
+        while updates.try_recv().is_ok() {}
+        for _ in 0..3 {
+            m.call_method("mock", "resources/list", serde_json::json!({})).await.unwrap();
+        }
+        assert_eq!(std::iter::from_fn(|| updates.try_recv().ok()).count(), 1);
+
+        // The holder dies: reads fail until the client subscribes again
+        let idx: usize = holder.as_str().unwrap().parse().unwrap();
+        let pool = m.pools.lock().await["mock"].clone();
+        pool.procs[idx].lock().await.upstream.kill().await;
+        let err = m.call_method("mock", "resources/read", uri.clone()).await.unwrap_err().message;
+        assert!(err.contains("was lost") && err.contains("subscribe again"), "{}", err);
+        let nudge = updates.recv().await.unwrap();
+        assert_eq!(nudge.1["params"]["uri"], "file:///a");
+
+        let sub = m.call_resource_subscription("mock", "resources/subscribe", uri.clone()).await.unwrap();
+        assert_ne!(sub["instance"], holder);
+        assert_eq!(m.call_method("mock", "resources/read", uri.clone()).await.unwrap()["instance"], sub["instance"]);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_templated_group_spawns_distinct_instances() {
+        // Reports its first argument back in the initialize capabilities
+        let script = r#"read l; echo "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"capabilities\":{\"experimental\":{\"arg\":\"$1\"}}}}"
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}'
+cat > /dev/null"#;
+        let mut server = mock_server("0", "0");
+        server.args = vec!["-c".into(), script.into(), "mock".into(), "--port={port}/{instance}".into()];
+        server.pool = 3;
+        server.base_port = Some(9000);
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+        m.try_start_pool("mock").await.unwrap();
+
+        let pool = m.pools.lock().await["mock"].clone();
+        let mut args = Vec::new();
+        for proc in &pool.procs {
+            args.push(proc.lock().await.capabilities["experimental"]["arg"].clone());
+        }
+        assert_eq!(args, vec!["--port=9000/0", "--port=9001/1", "--port=9002/2"]);
+        m.stop_all().await;
+
+        // {port} without basePort is rejected before anything is spawned
+        let mut server = mock_server("0", "0");
+        server.args.push("{port}".into());
+        let m = manager(server, Duration::from_secs(5), Duration::from_secs(5));
+        let err = m.try_start_pool("mock").await.unwrap_err().message;
+        assert!(err.contains("basePort"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_per_server_timeout_overrides_global() {
+        let mut server = mock_server("0.5", "0");
+        server.init_timeout_secs = Some(5);
+        let m = manager(server, Duration::from_millis(200), Duration::from_secs(5));
+        assert!(m.try_start_pool("mock").await.is_ok());
+        m.stop_all().await;
+    }
+
+    /// Completes the handshake, then closes stdout on the first call and
+    /// runs `then` (e.g. `exit 3` or `sleep 30`).
+    fn closing_server(then: &str) -> ServerConfig {
+        let script = format!(
+            r#"read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05"}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{{"name":"echo"}}]}}}}'
+read l; exec 1>&-; {then}"#,
+            then = then,
+        );
+        ServerConfig { args: vec!["-c".into(), script], ..mock_server("0", "0") }
+    }
+
+    #[tokio::test]
+    async fn test_stdout_eof_fails_call_with_exit_status() {
+        let m = manager(closing_server("exit 3"), Duration::from_secs(5), Duration::from_secs(30));
+        m.start_server("mock").await.unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(10), m.call_tool("mock", "echo", serde_json::json!({})))
+            .await
+            .expect("call hung after the backend closed stdout");
+        let err = res.unwrap_err().message;
+        assert!(err.contains("Server closed connection") && err.contains("exit code 3"), "{}", err);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_stdout_eof_without_exit_kills_instance() {
+        let m = manager(closing_server("sleep 30"), Duration::from_secs(5), Duration::from_secs(30));
+        m.start_server("mock").await.unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(10), m.call_tool("mock", "echo", serde_json::json!({})))
+            .await
+            .expect("call hung after the backend closed stdout");
+        let err = res.unwrap_err().message;
+        assert!(err.contains("still running"), "{}", err);
+        // The stale instance was killed and taken out of rotation
+        assert_eq!(m.group_health().await["mock"].state, GroupState::Failed);
+        m.stop_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_dependents_first() {
+        let log = std::env::temp_dir().join(format!("mcphub-shutdown-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        // Records its name once stdin is closed
+        let server = |name: &str, depends_on: &[&str]| {
+            let script = format!(
+                r#"read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05"}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[]}}}}'
+while read l; do :; done; sleep 0.2; echo {name} >> {log}"#,
+                name = name,
+                log = log.display(),
+            );
+            ServerConfig {
+                args: vec!["-c".into(), script],
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                ..mock_server("0", "0")
+            }
+        };
+        let mut configs = HashMap::new();
+        configs.insert("gateway".to_string(), server("gateway", &[]));
+        configs.insert("app".to_string(), server("app", &["gateway"]));
+        configs.insert("worker".to_string(), server("worker", &["app"]));
+        assert_eq!(shutdown_waves(&configs), [["worker"], ["app"], ["gateway"]]);
+
+        let m = ChildManager::new(configs, 60_000);
+        for name in ["gateway", "app", "worker"] {
+            m.start_server(name).await.unwrap();
+        }
+        m.shutdown(Duration::from_secs(5)).await;
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "worker\napp\ngateway\n");
+        assert!(!m.is_running("gateway").await);
+        let _ = std::fs::remove_file(&log);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_independent_servers_together() {
+        // Each takes a second to exit once stdin is closed
+        let slow = || {
+            let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}'
+while read l; do :; done; sleep 1"#;
+            ServerConfig { args: vec!["-c".into(), script.into()], ..mock_server("0", "0") }
+        };
+        let configs = HashMap::from([("a".to_string(), slow()), ("b".to_string(), slow())]);
+        assert_eq!(shutdown_waves(&configs), [["a", "b"]]);
+        let m = ChildManager::new(configs, 60_000);
+        for name in ["a", "b"] {
+            m.start_server(name).await.unwrap();
+        }
+        let started = Instant::now();
+        m.shutdown(Duration::from_secs(5)).await;
+        assert!(started.elapsed() < Duration::from_millis(1800), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_stop_all_kills_process_group() {
+        let pid_file = std::env::temp_dir().join(format!("mcphub-group-{}.pid", std::process::id()));
+        let script = format!(
+            r#"sleep 30 & echo $! > {pid}
+read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05"}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[]}}}}'
+while read l; do :; done"#,
+            pid = pid_file.display(),
+        );
+        let m = manager(ServerConfig { args: vec!["-c".into(), script], ..mock_server("0", "0") }, Duration::from_secs(5), Duration::from_secs(5));
+        m.start_server("mock").await.unwrap();
+        let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        let _ = std::fs::remove_file(&pid_file);
+        m.stop_all().await;
+
+        // Gone, or a zombie waiting for init to reap it
+        let stat = std::path::Path::new("/proc").join(&pid).join("stat");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while std::fs::read_to_string(&stat).is_ok_and(|s| !s.contains(") Z ")) {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("the server's own child outlived it");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_aborts_stuck_calls() {
+        let m = manager(mock_server("0", "30"), Duration::from_secs(5), Duration::from_secs(60));
+        m.try_start_pool("mock").await.unwrap();
+
+        let caller = m.clone();
+        let call = tokio::spawn(async move { caller.call_tool("mock", "echo", serde_json::json!({})).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        tokio::time::timeout(Duration::from_secs(10), m.shutdown(Duration::from_millis(200)))
+            .await
+            .expect("shutdown waited on the stuck call");
+        let err = call.await.unwrap().unwrap_err().message;
+        assert!(err.contains("aborted"), "{}", err);
+        assert!(!m.is_running("mock").await);
+    }
+}
@@ -0,0 +1,144 @@
+/// Single-flight for the list requests clients send in bursts (`tools/list`,
+/// `prompts/list`, `resources/list`): while one is being built, identical
+/// requests wait for it instead of asking the backends again, and the
+/// finished list is reused for `listCacheMs`. Lists are forgotten when a
+/// `list_changed` notification goes out, so a client refetching after one
+/// gets the new list.
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::sync::watch;
+
+enum Flight {
+    /// Being built; the builder sends the list when done.
+    Running(watch::Receiver<Option<Value>>),
+    Done { value: Value, at: Instant },
+}
+
+#[derive(Default)]
+pub struct Coalescer {
+    flights: Mutex<HashMap<String, Flight>>,
+}
+
+/// What a caller does about a key.
+enum Role {
+    Hit(Value),
+    Wait(watch::Receiver<Option<Value>>),
+    Build(watch::Sender<Option<Value>>),
+}
+
+impl Coalescer {
+    fn role(&self, key: &str, ttl: Duration) -> Role {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        match flights.get(key) {
+            Some(Flight::Done { value, at }) if at.elapsed() < ttl => return Role::Hit(value.clone()),
+            // A closed channel means the builder was dropped midway
+            Some(Flight::Running(rx)) if rx.has_changed().is_ok() => return Role::Wait(rx.clone()),
+            _ => {}
+        }
+        let (tx, rx) = watch::channel(None);
+        flights.insert(key.to_string(), Flight::Running(rx));
+        Role::Build(tx)
+    }
+
+    /// The list for `key`: reused if built less than `ttl` ago, awaited if
+    /// being built, built with `build` otherwise.
+    pub async fn run<F, Fut>(&self, key: &str, ttl: Duration, build: F) -> Value
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Value>,
+    {
+        loop {
+            match self.role(key, ttl) {
+                Role::Hit(value) => return value,
+                Role::Wait(mut rx) => {
+                    if let Ok(value) = rx.wait_for(|v| v.is_some()).await {
+                        return value.clone().unwrap_or_default();
+                    }
+                }
+                Role::Build(tx) => {
+                    let value = build().await;
+                    let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+                    // Unless invalidated meanwhile, which leaves nothing to reuse
+                    if matches!(flights.get(key), Some(Flight::Running(rx)) if rx.same_channel(&tx.subscribe())) {
+                        flights.insert(key.to_string(), Flight::Done { value: value.clone(), at: Instant::now() });
+                    }
+                    tx.send_replace(Some(value.clone()));
+                    return value;
+                }
+            }
+        }
+    }
+
+    /// Forget finished lists. Requests already waiting still get theirs.
+    pub fn invalidate(&self) {
+        self.flights.lock().unwrap_or_else(|e| e.into_inner()).retain(|_, flight| matches!(flight, Flight::Running(_)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_one_build() {
+        let coalescer = Arc::new(Coalescer::default());
+        let builds = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let (coalescer, builds) = (coalescer.clone(), builds.clone());
+                tokio::spawn(async move {
+                    coalescer
+                        .run("tools/list", Duration::ZERO, || async {
+                            builds.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            json!(["a"])
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), json!(["a"]));
+        }
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_finished_list_reused_until_ttl_or_invalidated() {
+        let coalescer = Coalescer::default();
+        let builds = AtomicUsize::new(0);
+        let build = || async { json!(builds.fetch_add(1, Ordering::SeqCst)) };
+        let ttl = Duration::from_secs(60);
+        assert_eq!(coalescer.run("prompts/list", ttl, build).await, json!(0));
+        assert_eq!(coalescer.run("prompts/list", ttl, build).await, json!(0));
+        // Another list, or no reuse at all, builds again
+        assert_eq!(coalescer.run("resources/list", ttl, build).await, json!(1));
+        assert_eq!(coalescer.run("prompts/list", Duration::ZERO, build).await, json!(2));
+        coalescer.invalidate();
+        assert_eq!(coalescer.run("resources/list", ttl, build).await, json!(3));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_builder_hands_over() {
+        let coalescer = Arc::new(Coalescer::default());
+        let slow = {
+            let coalescer = coalescer.clone();
+            tokio::spawn(async move { coalescer.run("tools/list", Duration::ZERO, std::future::pending).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let waiter = {
+            let coalescer = coalescer.clone();
+            tokio::spawn(async move { coalescer.run("tools/list", Duration::ZERO, || async { json!("fresh") }).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        slow.abort();
+        assert_eq!(waiter.await.unwrap(), json!("fresh"));
+    }
+}
@@ -1,14 +1,250 @@
 use serde_json::Value;
+
+use crate::breaker::{BreakerConfig, BreakerOverride};
+use crate::cache::VersionPolicy;
+use crate::framing::DEFAULT_MAX_MESSAGE_BYTES;
+use crate::http::DEFAULT_MAX_HEADERS;
+use crate::audit::{parse_audit, AuditConfig};
+use crate::limits::{parse_result_limits, ResultLimits};
+use crate::protocol::LogLevel;
+use crate::record::{parse_record, RecordConfig};
+use crate::result_cache::{parse_result_cache, ResultCacheConfig};
+use crate::sse::{KeepaliveConfig, RateLimitConfig, ReaperConfig};
+use crate::tool_filter::{parse_tool_filter, ToolFilter};
+use crate::tool_overrides::{parse_tool_overrides, ToolOverrides};
+use crate::transform::{parse_transforms, Transform};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ServerConfig {
     pub command: String,
     pub args: Vec<String>,
     pub env: HashMap<String, String>,
+    /// Remote server (HTTP+SSE) to connect to instead of running `command`.
+    pub url: Option<String>,
+    /// Sent with every request to `url`; values may use `${VAR}` references.
+    pub headers: HashMap<String, String>,
     pub pool: usize,
+    /// How calls are spread over the pool's instances.
+    pub balance: Balance,
+    /// Per-server override of `ProxyConfig::init_timeout_secs`.
+    pub init_timeout_secs: Option<u64>,
+    /// Per-server override of `ProxyConfig::call_timeout_secs`.
+    pub call_timeout_secs: Option<u64>,
+    /// First port of a pool; `{port}` expands to `base_port + instance`.
+    pub base_port: Option<u16>,
+    /// Higher-priority servers' tools come first in `tools/list`.
+    pub priority: i32,
+    /// Servers this one uses; it is shut down before them.
+    pub depends_on: Vec<String>,
+    /// Per-server override of `ProxyConfig::circuit_breaker`.
+    pub circuit_breaker: BreakerOverride,
+    /// Cap on this server's `tools/call`s in flight.
+    pub concurrency: Concurrency,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Balance {
+    /// Each call goes to the next instance that is up.
+    #[default]
+    RoundRobin,
+    /// Each call goes to the up instance with the fewest requests in flight.
+    LeastInFlight,
+}
+
+/// `maxConcurrent` / `whenBusy` of a server entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Concurrency {
+    /// Calls in flight at once; None falls back to `maxConcurrentCalls`.
+    pub max: Option<usize>,
+    pub when_busy: WhenBusy,
+}
+
+/// What a call does when its server already has its maximum in flight.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WhenBusy {
+    /// Queue for a slot, failing if none frees up within the call timeout.
+    #[default]
+    Wait,
+    /// Fail at once with a busy error.
+    Reject,
+}
+
+/// Command line and environment of one pool instance after templating.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            url: None,
+            headers: HashMap::new(),
+            pool: 1,
+            balance: Balance::default(),
+            init_timeout_secs: None,
+            call_timeout_secs: None,
+            base_port: None,
+            priority: 0,
+            depends_on: Vec::new(),
+            circuit_breaker: BreakerOverride::default(),
+            concurrency: Concurrency::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    fn uses_placeholder(&self, placeholder: &str) -> bool {
+        self.command.contains(placeholder)
+            || self.args.iter().any(|a| a.contains(placeholder))
+            || self.env.values().any(|v| v.contains(placeholder))
+    }
+
+    /// Whether switching to `other` needs the server restarted: anything but
+    /// the listing priority, the shutdown order, breaker thresholds or the
+    /// concurrency limit changed.
+    pub fn needs_restart(&self, other: &ServerConfig) -> bool {
+        ServerConfig {
+            priority: other.priority,
+            depends_on: other.depends_on.clone(),
+            circuit_breaker: other.circuit_breaker,
+            concurrency: other.concurrency,
+            ..self.clone()
+        } != *other
+    }
+
+    /// Check the `{instance}` / `{port}` templates against the pool size.
+    pub fn validate_template(&self) -> Result<(), String> {
+        if self.pool == 0 {
+            return Err("pool must be at least 1".into());
+        }
+        if self.uses_placeholder("{port}") {
+            let base = self.base_port.ok_or("{port} is used but basePort is not set")?;
+            if base as usize + self.pool - 1 > u16::MAX as usize {
+                return Err(format!("basePort {} + pool {} exceeds the highest port", base, self.pool));
+            }
+        }
+        Ok(())
+    }
+
+    /// Command line for pool instance `instance` (0-based), with `{instance}`
+    /// and `{port}` substituted in the command, args and env values.
+    pub fn expand_for_instance(&self, instance: usize) -> InstanceCommand {
+        let port = self.base_port.map(|p| (p as usize + instance).to_string()).unwrap_or_default();
+        let expand = |s: &str| s.replace("{instance}", &instance.to_string()).replace("{port}", &port);
+        InstanceCommand {
+            command: expand(&self.command),
+            args: self.args.iter().map(|a| expand(a)).collect(),
+            env: self.env.iter().map(|(k, v)| (k.clone(), expand(v))).collect(),
+        }
+    }
+}
+
+/// Resolve `${VAR}` (host environment) and `${secret:NAME}`
+/// (`~/.McpHub/secrets.json`) references in server env values. The config
+/// keeps the references, so resolved values only reach the spawned process.
+/// Errors name the missing reference, never a value.
+pub fn resolve_env(env: &HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+    resolve_references(env, "env")
+}
+
+/// `resolve_env` for the headers sent to a remote server. A line break in a
+/// name or resolved value would start a header (or body) of its own, so it
+/// is an error.
+pub fn resolve_headers(headers: &HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+    let resolved = resolve_references(headers, "header")?;
+    for (name, value) in &resolved {
+        if name.is_empty() || name.contains([':', ' ', '\t', '\r', '\n']) {
+            return Err(format!("header '{}': invalid name", name.escape_debug()));
+        }
+        if value.contains(['\r', '\n']) {
+            return Err(format!("header {}: value contains a line break", name));
+        }
+    }
+    Ok(resolved)
+}
+
+fn resolve_references(values: &HashMap<String, String>, kind: &str) -> Result<HashMap<String, String>, String> {
+    let mut secrets: Option<HashMap<String, String>> = None;
+    values.iter()
+        .map(|(key, value)| {
+            let resolved = interpolate(value, |reference| match reference.strip_prefix("secret:") {
+                Some(name) => secrets.get_or_insert_with(load_secrets).get(name).cloned(),
+                None => std::env::var(reference).ok(),
+            })
+            .map_err(|missing| format!("{} {}: {} is not set", kind, key, missing))?;
+            Ok((key.clone(), resolved))
+        })
+        .collect()
+}
+
+/// Replace each `${reference}` in `value` with `lookup(reference)`; the
+/// first unresolved reference is the error. An unclosed `${` is kept as is.
+fn interpolate(value: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else { break };
+        let reference = &rest[start + 2..start + 2 + len];
+        out.push_str(&rest[..start]);
+        out.push_str(&lookup(reference).ok_or_else(|| format!("${{{}}}", reference))?);
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Variable that moves McpHub's data directory.
+pub const HOME_ENV: &str = "MCPHUB_HOME";
+
+/// Where McpHub keeps its config, token, cache, logs and other state:
+/// `$MCPHUB_HOME`; else `~/.McpHub` if it exists; else
+/// `$XDG_STATE_HOME/mcphub` if that is set; else `~/.McpHub`. None without
+/// a home directory.
+pub fn hub_dir() -> Option<PathBuf> {
+    // Tests never touch the user's cache, token or logs
+    if cfg!(test) {
+        return Some(std::env::temp_dir().join(format!("mcphub-test-home-{}", std::process::id())));
+    }
+    resolve_hub_dir(|key| std::env::var(key).ok(), dirs::home_dir())
+}
+
+fn resolve_hub_dir(var: impl Fn(&str) -> Option<String>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let set = |key| var(key).filter(|v| !v.is_empty()).map(PathBuf::from);
+    if let Some(dir) = set(HOME_ENV) {
+        return Some(dir);
+    }
+    let default = home.map(|home| home.join(".McpHub"));
+    // An existing ~/.McpHub stays put
+    match (set("XDG_STATE_HOME"), &default) {
+        (Some(state), Some(default)) if !default.exists() => Some(state.join("mcphub")),
+        (Some(state), None) => Some(state.join("mcphub")),
+        _ => default,
+    }
+}
+
+/// Secrets for `${secret:NAME}`: a JSON object of strings, read at each start.
+fn load_secrets() -> HashMap<String, String> {
+    let Some(path) = hub_dir().map(|dir| dir.join("secrets.json")) else {
+        return HashMap::new();
+    };
+    let Ok(content) = fs::read_to_string(&path) else { return HashMap::new() };
+    match serde_json::from_str(&content) {
+        Ok(secrets) => secrets,
+        Err(e) => {
+            tracing::error!("Invalid {}, expected an object of strings: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,6 +253,50 @@ pub enum Mode {
     Passthrough,
 }
 
+/// How passthrough tools are named in `tools/list` and `tools/call`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolNames {
+    /// Prefix each tool with its server's name.
+    pub prefix: bool,
+    pub separator: String,
+}
+
+impl Default for ToolNames {
+    fn default() -> Self {
+        Self { prefix: true, separator: "__".into() }
+    }
+}
+
+impl ToolNames {
+    /// The name clients see for `tool` of `server`.
+    pub fn join(&self, server: &str, tool: &str) -> String {
+        if self.prefix {
+            format!("{}{}{}", server, self.separator, tool)
+        } else {
+            tool.to_string()
+        }
+    }
+}
+
+/// What to do when an SSE client connects again with a client id that
+/// already has a live session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DuplicateClients {
+    /// Keep both sessions.
+    Allow,
+    /// Close the older session.
+    Supersede,
+}
+
+/// What to do with a request from a client that hasn't sent `initialize`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreInitialize {
+    /// Answer with a "not initialized" error.
+    Strict,
+    /// Initialize the session with default client info and serve the call.
+    Lenient,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Preload {
     All,
@@ -28,12 +308,152 @@ pub enum Preload {
 pub struct ProxyConfig {
     pub servers: HashMap<String, ServerConfig>,
     pub mode: Mode,
+    pub tool_names: ToolNames,
+    /// Servers started at boot; none by default (lazy start).
     pub preload: Preload,
     pub idle_timeout_ms: u64,
+    /// Startup handshake timeout (initialize + initial tools/list).
+    pub init_timeout_secs: u64,
+    /// Extra attempts at a server start whose handshake failed or timed out.
+    pub init_retries: u32,
+    /// Wait before the first start retry; doubles on each one after.
+    pub init_retry_backoff_ms: u64,
+    /// Timeout for every request after startup.
+    pub call_timeout_secs: u64,
+    /// Default cap on each server's `tools/call`s in flight; 0 = unlimited.
+    pub max_concurrent_calls: usize,
+    /// How long a call to a stopped server waits for it to start on demand.
+    pub activation_timeout_secs: u64,
+    /// Pause between preloaded server starts.
     pub preload_delay_ms: u64,
+    /// Preloaded servers starting at once.
+    pub preload_concurrency: usize,
+    /// How long a built `tools/list` / `prompts/list` / `resources/list` is reused.
+    pub list_cache_ms: u64,
     pub health_check_interval_secs: u64,
     pub health_auto_restart: bool,
     pub health_notifications: bool,
+    pub auth_auto_generate_token: bool,
+    /// Require `Authorization: Bearer <token>` on /sse and /message.
+    pub auth_required: bool,
+    /// Concurrent SSE sessions; further connects get 503.
+    pub sse_max_sessions: usize,
+    /// Per-session cap on bytes queued for a slow SSE client (None = count-only).
+    pub sse_max_buffered_bytes: Option<usize>,
+    /// Largest request body (a POST to /message, /mcp or the API) accepted.
+    pub sse_max_body_bytes: usize,
+    /// Largest data of one SSE event (None = no limit).
+    pub sse_max_event_bytes: Option<usize>,
+    /// Pending events per SSE session.
+    pub sse_channel_capacity: usize,
+    /// Queue size a session that keeps filling its queue may grow to; at
+    /// most `sse_channel_capacity` means fixed-size queues.
+    pub sse_max_channel_capacity: usize,
+    /// How long a response waits for room in a full SSE session queue
+    /// before /message answers 503.
+    pub sse_send_timeout_ms: u64,
+    pub sse_keepalive: KeepaliveConfig,
+    pub sse_reaper: ReaperConfig,
+    /// Keep session metadata in `~/.McpHub/sessions.json` so clients can
+    /// resume their session after a restart.
+    pub sse_persist_sessions: bool,
+    /// Keep responses finishing while a session's stream is down for replay
+    /// on resume; otherwise drop them.
+    pub sse_buffer_late_responses: bool,
+    /// Backend log notifications forwarded per SSE session per second (0 = no cap).
+    pub sse_log_rate: u32,
+    /// Least severe backend log level ever forwarded to SSE clients.
+    pub sse_log_floor: LogLevel,
+    /// Browser origins that get CORS headers; empty sends none.
+    pub cors_allowed_origins: Vec<String>,
+    /// Policy for a second SSE connect carrying the same client id.
+    pub sse_duplicate_clients: DuplicateClients,
+    /// Send responses as `event: response` and notifications as
+    /// `event: notification` instead of `event: message` for both.
+    pub sse_split_events: bool,
+    /// Tools (`server__tool`, or a whole server) whose results go to SSE
+    /// clients as chunk events ahead of the response.
+    pub sse_stream_results: Vec<String>,
+    /// Per-session message and per-IP connect limits; 429 past them.
+    pub sse_rate_limit: RateLimitConfig,
+    /// Pass `capabilities.experimental` between clients and backends.
+    pub forward_experimental: bool,
+    /// Handling of requests sent before `initialize`.
+    pub pre_initialize: PreInitialize,
+    /// Answer calls to unknown tools with suggestions instead of a bare error.
+    pub unknown_tool_fallback: bool,
+    /// Forward `completion/complete` to backends (otherwise no suggestions).
+    pub forward_completions: bool,
+    /// Offer `sampling` to backends and pass `sampling/createMessage` to a
+    /// client session that supports it.
+    pub forward_sampling: bool,
+    /// How long a per-server reload or stop waits for in-flight calls.
+    pub reload_grace_secs: u64,
+    /// Seconds each backend gets to exit after its stdin is closed at
+    /// shutdown before it is killed.
+    pub shutdown_grace_secs: u64,
+    /// Attach diagnostic `_meta.mcphub` to tool call results (non-spec, debugging only).
+    pub debug_meta: bool,
+    /// Check `tools/call` arguments against the tool's cached input schema
+    /// and answer -32602 instead of forwarding a call that doesn't match.
+    pub validate_arguments: bool,
+    /// Per-server circuit breaker thresholds for tool calls.
+    pub circuit_breaker: BreakerConfig,
+    /// Extra attempts to bind the HTTP port when it is still in use.
+    pub bind_retries: u32,
+    /// Delay before the first bind retry; doubles on each attempt.
+    pub bind_retry_delay_ms: u64,
+    /// Declarative rewrites of forwarded requests and responses.
+    pub transforms: Vec<Transform>,
+    /// Handling of a schema cache written by another McpHub version.
+    pub cache_version: VersionPolicy,
+    /// Cached schemas older than this are dropped at startup and fetched again.
+    pub cache_max_age: Option<std::time::Duration>,
+    /// Secret prefixes masked in the schema cache, beyond the built-in ones.
+    pub cache_redact_patterns: Vec<String>,
+    /// Write the schema cache gzipped (`cache.format: "gzip"`).
+    pub cache_gzip: bool,
+    /// Re-list a server's tools when it refuses a cached tool as unknown.
+    pub refresh_stale_tools: bool,
+    /// Request methods answered with an error instead of being handled.
+    pub blocked_methods: Vec<String>,
+    /// What to do with a tool result over the client's `maxResultBytes`.
+    pub oversized_results: ResultLimits,
+    /// Which tools' results are cached, and for how long.
+    pub result_cache: ResultCacheConfig,
+    /// Which upstream tools each server exposes.
+    pub tool_filter: ToolFilter,
+    /// Per-server description edits and renames of upstream tools.
+    pub tool_overrides: ToolOverrides,
+    /// Audit log of tool calls.
+    pub audit: AuditConfig,
+    /// Session recordings for `McpHub replay`.
+    pub record: RecordConfig,
+    /// Send reads of a subscribed resource to the pool instance holding
+    /// the subscription.
+    pub resource_affinity: bool,
+    /// Largest JSON-RPC message accepted on a stdio stream (client stdin
+    /// or a backend's stdout).
+    pub stdio_max_message_bytes: usize,
+    /// Backend stderr lines logged per second per instance before the rest
+    /// is summarized.
+    pub stdio_max_stderr_lines_per_sec: u32,
+    /// Size-based rotation of the daemon's log file.
+    pub log_rotation: crate::logging::Rotation,
+    /// Offer permessage-deflate to WebSocket clients that ask for it.
+    pub ws_compression: bool,
+    /// Outgoing WebSocket messages shorter than this are sent uncompressed.
+    pub ws_compression_threshold: usize,
+    /// Requests with more header lines than this get 400.
+    pub http_max_headers: usize,
+    /// Gzip JSON response bodies for clients that accept it.
+    pub http_compression: bool,
+    /// Response bodies shorter than this are sent uncompressed.
+    pub http_compression_threshold: usize,
+    /// `serve` gives up after this many starts within
+    /// `crash_loop_window_secs` that didn't stay up. 0 never gives up.
+    pub crash_loop_max_starts: u32,
+    pub crash_loop_window_secs: u64,
 }
 
 impl Default for ProxyConfig {
@@ -41,12 +461,79 @@ impl Default for ProxyConfig {
         Self {
             servers: HashMap::new(),
             mode: Mode::Discover,
-            preload: Preload::All,
+            tool_names: ToolNames::default(),
+            preload: Preload::None,
             idle_timeout_ms: 5 * 60 * 1000,
+            init_timeout_secs: 60,
+            init_retries: 2,
+            init_retry_backoff_ms: 500,
+            call_timeout_secs: 30,
+            max_concurrent_calls: 0,
+            activation_timeout_secs: 60,
             preload_delay_ms: 200,
+            preload_concurrency: 4,
+            list_cache_ms: 1000,
             health_check_interval_secs: 30,
             health_auto_restart: true,
             health_notifications: true,
+            auth_auto_generate_token: true,
+            auth_required: true,
+            sse_max_sessions: 256,
+            sse_max_buffered_bytes: None,
+            sse_max_body_bytes: crate::http::DEFAULT_MAX_BODY_BYTES,
+            sse_max_event_bytes: Some(crate::framing::DEFAULT_MAX_MESSAGE_BYTES),
+            sse_channel_capacity: 64,
+            sse_max_channel_capacity: 0,
+            sse_send_timeout_ms: 5000,
+            sse_keepalive: KeepaliveConfig::default(),
+            sse_reaper: ReaperConfig::default(),
+            sse_persist_sessions: false,
+            sse_buffer_late_responses: true,
+            sse_log_rate: 20,
+            sse_log_floor: LogLevel::Debug,
+            cors_allowed_origins: ["http://localhost", "http://127.0.0.1", "http://[::1]"]
+                .map(String::from)
+                .to_vec(),
+            sse_duplicate_clients: DuplicateClients::Allow,
+            sse_split_events: false,
+            sse_stream_results: Vec::new(),
+            sse_rate_limit: RateLimitConfig::default(),
+            forward_experimental: true,
+            pre_initialize: PreInitialize::Lenient,
+            unknown_tool_fallback: false,
+            forward_completions: true,
+            forward_sampling: true,
+            reload_grace_secs: 30,
+            shutdown_grace_secs: 5,
+            debug_meta: false,
+            validate_arguments: false,
+            circuit_breaker: BreakerConfig::default(),
+            bind_retries: 5,
+            bind_retry_delay_ms: 250,
+            transforms: Vec::new(),
+            cache_version: VersionPolicy::default(),
+            cache_max_age: None,
+            cache_redact_patterns: Vec::new(),
+            cache_gzip: false,
+            refresh_stale_tools: true,
+            blocked_methods: Vec::new(),
+            oversized_results: ResultLimits::default(),
+            result_cache: ResultCacheConfig::default(),
+            tool_filter: ToolFilter::default(),
+            tool_overrides: ToolOverrides::default(),
+            audit: AuditConfig::default(),
+            record: RecordConfig::default(),
+            resource_affinity: true,
+            stdio_max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            stdio_max_stderr_lines_per_sec: crate::stderr::DEFAULT_MAX_LINES_PER_SEC,
+            log_rotation: crate::logging::Rotation::DEFAULT,
+            ws_compression: true,
+            ws_compression_threshold: 1024,
+            http_max_headers: DEFAULT_MAX_HEADERS,
+            http_compression: true,
+            http_compression_threshold: 1024,
+            crash_loop_max_starts: 5,
+            crash_loop_window_secs: 60,
         }
     }
 }
@@ -66,10 +553,17 @@ fn is_self(name: &str, config: &Value) -> bool {
             return true;
         }
     }
+    // Pointed at the hub's own endpoint, as clients using McpHub are
+    if let Some(url) = config.get("url").and_then(|v| v.as_str()) {
+        let port = DEFAULT_BIND_ADDR.rsplit_once(':').map_or("", |(_, port)| port);
+        if ["localhost", "127.0.0.1", "[::1]"].iter().any(|host| url.contains(&format!("://{}:{}/", host, port))) {
+            return true;
+        }
+    }
     false
 }
 
-fn parse_servers(json: &Value) -> HashMap<String, ServerConfig> {
+pub fn parse_servers(json: &Value) -> HashMap<String, ServerConfig> {
     let mut result = HashMap::new();
     let servers_obj = json.get("mcpServers").or_else(|| json.get("servers")).unwrap_or(json);
     let servers = match servers_obj.as_object() { Some(m) => m, None => return result };
@@ -77,59 +571,609 @@ fn parse_servers(json: &Value) -> HashMap<String, ServerConfig> {
     for (name, config) in servers {
         if name.starts_with('_') { continue; }
         if is_self(name, config) {
-            eprintln!("[McpHub][INFO] Skipped self: {}", name);
+            tracing::info!("Skipped self: {}", name);
             continue;
         }
         if config.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false) {
-            eprintln!("[McpHub][INFO] Skipped disabled: {}", name);
+            tracing::info!("Skipped disabled: {}", name);
+            continue;
+        }
+        if let Some(e) = server_entry_error(config) {
+            tracing::error!("Skipped server '{}': {}", name, e);
             continue;
         }
-        if let Some(cmd) = config.get("command").and_then(|v| v.as_str()) {
+        let command = config.get("command").and_then(|v| v.as_str());
+        let url = config.get("url").and_then(|v| v.as_str());
+        if command.is_some() || url.is_some() {
             let args: Vec<String> = config.get("args").and_then(|v| v.as_array())
                 .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
                 .unwrap_or_default();
             let env: HashMap<String, String> = config.get("env").and_then(|v| v.as_object())
                 .map(|obj| obj.iter().filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string()))).collect())
                 .unwrap_or_default();
+            let headers: HashMap<String, String> = config.get("headers").and_then(|v| v.as_object())
+                .map(|obj| obj.iter().filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string()))).collect())
+                .unwrap_or_default();
             let pool = config.get("pool").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
-            result.insert(name.clone(), ServerConfig { command: cmd.to_string(), args, env, pool });
+            let balance = match config.get("balance").and_then(|v| v.as_str()) {
+                Some("least-in-flight") => Balance::LeastInFlight,
+                Some("round-robin") | None => Balance::RoundRobin,
+                Some(other) => {
+                    tracing::warn!("Server '{}': unknown balance '{}', using round-robin", name, other);
+                    Balance::RoundRobin
+                }
+            };
+            let init_timeout_secs = config.get("initTimeout").and_then(|v| v.as_u64());
+            let call_timeout_secs = config.get("callTimeout").and_then(|v| v.as_u64());
+            let base_port = config.get("basePort").and_then(|v| v.as_u64()).and_then(|p| u16::try_from(p).ok());
+            let priority = config.get("priority").and_then(|v| v.as_i64()).unwrap_or(0).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+            let depends_on: Vec<String> = config.get("dependsOn").and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+                .unwrap_or_default();
+            let circuit_breaker = config.get("circuitBreaker").map(BreakerOverride::parse).unwrap_or_default();
+            let when_busy = match config.get("whenBusy").and_then(|v| v.as_str()) {
+                Some("reject") => WhenBusy::Reject,
+                Some("wait") | None => WhenBusy::Wait,
+                Some(other) => {
+                    tracing::warn!("Server '{}': unknown whenBusy '{}', using wait", name, other);
+                    WhenBusy::Wait
+                }
+            };
+            let concurrency = Concurrency {
+                max: config.get("maxConcurrent").and_then(|v| v.as_u64()).map(|n| n as usize),
+                when_busy,
+            };
+            result.insert(name.clone(), ServerConfig {
+                command: command.unwrap_or_default().to_string(),
+                args,
+                env,
+                url: url.map(String::from),
+                headers,
+                pool,
+                balance,
+                init_timeout_secs,
+                call_timeout_secs,
+                base_port,
+                priority,
+                depends_on,
+                circuit_breaker,
+                concurrency,
+            });
         }
     }
     result
 }
 
+/// What is wrong with a server entry whose known keys have the wrong
+/// type; None for a usable entry. Entries with neither a command nor a
+/// url aren't checked.
+pub fn server_entry_error(config: &Value) -> Option<String> {
+    let obj = match config.as_object() {
+        Some(obj) => obj,
+        None => return Some("expected a table of server options".into()),
+    };
+    match (obj.get("command"), obj.get("url")) {
+        (None, None) => return None,
+        (Some(_), Some(_)) => return Some("set either command or url, not both".into()),
+        _ => {}
+    }
+    fn strings(v: &Value) -> bool {
+        v.as_array().is_some_and(|a| a.iter().all(Value::is_string))
+    }
+    type Check = (&'static str, fn(&Value) -> bool, &'static str);
+    let checks: [Check; 12] = [
+        ("command", |v| v.as_str().is_some_and(|c| !c.is_empty()), "a program name"),
+        ("args", strings, "a list of strings"),
+        ("env", |v| v.as_object().is_some_and(|e| e.values().all(Value::is_string)), "a table of strings"),
+        ("url", |v| v.as_str().is_some_and(|u| crate::remote::Target::parse(u).is_ok()), "an http:// url"),
+        ("headers", |v| v.as_object().is_some_and(|h| h.values().all(Value::is_string)), "a table of strings"),
+        ("disabled", Value::is_boolean, "true or false"),
+        ("pool", |v| v.as_u64().is_some_and(|n| n >= 1), "a positive integer"),
+        ("initTimeout", Value::is_u64, "a number of seconds"),
+        ("callTimeout", Value::is_u64, "a number of seconds"),
+        ("priority", Value::is_i64, "an integer"),
+        ("dependsOn", strings, "a list of server names"),
+        ("maxConcurrent", |v| v.as_u64().is_some_and(|n| n >= 1), "a positive integer"),
+    ];
+    checks
+        .iter()
+        .find(|(key, valid, _)| obj.get(*key).is_some_and(|v| !valid(v)))
+        .map(|(key, _, expected)| format!("{} must be {}", key, expected))
+}
+
+/// Malformed server entries of a config file, as `name: problem`.
+pub fn server_errors(json: &Value) -> Vec<String> {
+    let servers = json.get("mcpServers").or_else(|| json.get("servers")).and_then(|v| v.as_object());
+    let mut errors: Vec<String> = servers
+        .into_iter()
+        .flatten()
+        .filter(|(name, _)| !name.starts_with('_'))
+        .filter_map(|(name, config)| server_entry_error(config).map(|e| format!("{}: {}", name, e)))
+        .collect();
+    errors.sort();
+    errors
+}
+
+/// Both dedicated config files; config.toml is used when it exists.
+fn dedicated_config_files() -> Vec<PathBuf> {
+    hub_dir()
+        .map(|dir| vec![dir.join("config.toml"), dir.join("config.json")])
+        .unwrap_or_default()
+}
+
+/// Path of the dedicated McpHub config file: `~/.McpHub/config.toml` if it
+/// exists, else `~/.McpHub/config.json`.
+pub fn dedicated_config_path() -> Option<PathBuf> {
+    let files = dedicated_config_files();
+    files.iter().find(|p| p.exists()).or(files.last()).cloned()
+}
+
+pub fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// `path:line:column: message`, then the lines up to the error and a caret
+/// under its position.
+fn syntax_report(path: &Path, content: &str, line: usize, column: usize, message: &str) -> String {
+    let mut report = format!("{}:{}:{}: {}", path.display(), line, column, message);
+    let lines: Vec<&str> = content.lines().collect();
+    let first = line.saturating_sub(2).max(1);
+    for n in first..=line.min(lines.len()) {
+        report.push_str(&format!("\n{:>5} | {}", n, lines[n - 1]));
+    }
+    if line >= 1 && line <= lines.len() {
+        report.push_str(&format!("\n      | {}^", " ".repeat(column.saturating_sub(1))));
+    }
+    report
+}
+
+/// Parse config JSON, reporting syntax errors as `path:line:column: message`
+/// followed by the offending lines and a caret under the error position.
+pub fn parse_config_json(path: &Path, content: &str) -> Result<Value, String> {
+    serde_json::from_str(content).map_err(|e| {
+        let full = e.to_string();
+        let message = full.rsplit_once(" at line ").map(|(m, _)| m).unwrap_or(&full);
+        syntax_report(path, content, e.line(), e.column(), message)
+    })
+}
+
+/// Parse a TOML config into the same shape as config.json: tables become
+/// objects, and dates become strings. Errors are reported like JSON ones.
+pub fn parse_config_toml(path: &Path, content: &str) -> Result<Value, String> {
+    let doc: toml_edit::DocumentMut = content.parse().map_err(|e: toml_edit::TomlError| {
+        let offset = e.span().map_or(0, |span| span.start).min(content.len());
+        let before = &content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        syntax_report(path, content, line, column, e.message())
+    })?;
+    Ok(toml_table(doc.as_table()))
+}
+
+fn toml_table(table: &toml_edit::Table) -> Value {
+    Value::Object(table.iter().map(|(key, item)| (key.to_string(), toml_item(item))).collect())
+}
+
+fn toml_item(item: &toml_edit::Item) -> Value {
+    match item {
+        toml_edit::Item::None => Value::Null,
+        toml_edit::Item::Value(value) => toml_value(value),
+        toml_edit::Item::Table(table) => toml_table(table),
+        toml_edit::Item::ArrayOfTables(tables) => tables.iter().map(toml_table).collect(),
+    }
+}
+
+fn toml_value(value: &toml_edit::Value) -> Value {
+    use toml_edit::Value as T;
+    match value {
+        T::String(s) => Value::from(s.value().as_str()),
+        T::Integer(n) => Value::from(*n.value()),
+        T::Float(f) => Value::from(*f.value()),
+        T::Boolean(b) => Value::from(*b.value()),
+        T::Datetime(d) => Value::from(d.value().to_string()),
+        T::Array(items) => items.iter().map(toml_value).collect(),
+        T::InlineTable(table) => Value::Object(table.iter().map(|(key, v)| (key.to_string(), toml_value(v))).collect()),
+    }
+}
+
+/// Read and parse a config file, JSON or (by extension) TOML.
+pub fn read_config_file(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if is_toml(path) {
+        parse_config_toml(path, &content)
+    } else {
+        parse_config_json(path, &content)
+    }
+}
+
+/// The dedicated config file and its `settings` block, when `auto_detect`
+/// uses it (it has servers).
+pub fn dedicated_settings() -> Option<(PathBuf, Value)> {
+    let path = dedicated_config_path().filter(|p| p.exists())?;
+    let json = read_config_file(&path).ok()?;
+    if parse_servers(&json).is_empty() {
+        return None;
+    }
+    Some((path, json.get("settings").cloned().unwrap_or_default()))
+}
+
+/// The file each server `auto_detect` finds is read from.
+pub fn server_sources() -> HashMap<String, PathBuf> {
+    if let Some((path, _)) = dedicated_settings() {
+        let servers = read_config_file(&path).map(|json| parse_servers(&json)).unwrap_or_default();
+        return servers.into_keys().map(|name| (name, path.clone())).collect();
+    }
+    let mut sources = HashMap::new();
+    for path in get_config_paths() {
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        let Ok(json) = serde_json::from_str::<Value>(&content) else { continue };
+        // Later files win, as in auto_detect
+        sources.extend(parse_servers(&json).into_keys().map(|name| (name, path.clone())));
+    }
+    sources
+}
+
 fn load_dedicated_config() -> Option<ProxyConfig> {
-    let home = dirs::home_dir()?;
-    let path = home.join(".McpHub").join("config.json");
+    let path = dedicated_config_path()?;
     if !path.exists() { return None; }
-    let content = fs::read_to_string(&path).ok()?;
-    let json: Value = serde_json::from_str(&content).ok()?;
+    let json = match read_config_file(&path) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!("Invalid config, ignoring it:\n{}", e);
+            return None;
+        }
+    };
     let servers = parse_servers(&json);
     if servers.is_empty() { return None; }
-    eprintln!("[McpHub][INFO] Loaded {} servers from {}", servers.len(), path.display());
+    tracing::info!("Loaded {} servers from {}", servers.len(), path.display());
 
     let mut config = ProxyConfig { servers, ..Default::default() };
     if let Some(settings) = json.get("settings") {
-        if let Some(mode) = settings.get("mode").and_then(|v| v.as_str()) {
-            config.mode = match mode { "passthrough" => Mode::Passthrough, _ => Mode::Discover };
+        parse_settings(settings, &mut config);
+    }
+    Some(config)
+}
+
+/// Apply the `settings` block of config.json on top of `config`.
+fn parse_settings(settings: &Value, config: &mut ProxyConfig) {
+    if let Some(mode) = settings.get("mode").and_then(|v| v.as_str()) {
+        config.mode = match mode { "passthrough" => Mode::Passthrough, _ => Mode::Discover };
+    }
+    if let Some(prefix) = settings.pointer("/toolNames/prefix").and_then(|v| v.as_bool()) {
+        config.tool_names.prefix = prefix;
+    }
+    match settings.pointer("/toolNames/separator").and_then(|v| v.as_str()) {
+        Some("") => tracing::error!("toolNames.separator can't be empty, keeping '{}'", config.tool_names.separator),
+        Some(separator) => config.tool_names.separator = separator.to_string(),
+        None => {}
+    }
+    // Servers started at boot; the others start on their first call
+    match settings.get("preload") {
+        Some(Value::String(s)) if s == "all" => config.preload = Preload::All,
+        Some(Value::String(s)) if s == "none" => config.preload = Preload::None,
+        Some(Value::Array(names)) if names.iter().all(|n| n.is_string()) => {
+            config.preload = Preload::Some(names.iter().filter_map(|n| n.as_str()).map(String::from).collect());
         }
-        if let Some(timeout) = settings.get("idleTimeout").and_then(|v| v.as_u64()) {
-            config.idle_timeout_ms = timeout * 1000;
+        Some(other) => tracing::error!("Invalid preload {}: expected \"all\", \"none\" or a list of servers", other),
+        None => {}
+    }
+    if let Some(delay) = settings.get("preloadDelayMs").and_then(|v| v.as_u64()) {
+        config.preload_delay_ms = delay;
+    }
+    if let Some(n) = settings.get("preloadConcurrency").and_then(|v| v.as_u64()) {
+        config.preload_concurrency = (n as usize).max(1);
+    }
+    if let Some(ms) = settings.get("listCacheMs").and_then(|v| v.as_u64()) {
+        config.list_cache_ms = ms;
+    }
+    if let Some(timeout) = settings.get("idleTimeout").and_then(|v| v.as_u64()) {
+        config.idle_timeout_ms = timeout * 1000;
+    }
+    if let Some(timeout) = settings.get("initTimeout").and_then(|v| v.as_u64()) {
+        config.init_timeout_secs = timeout;
+    }
+    if let Some(retries) = settings.get("initRetries").and_then(|v| v.as_u64()) {
+        config.init_retries = retries.min(u32::MAX as u64) as u32;
+    }
+    if let Some(backoff) = settings.get("initRetryBackoffMs").and_then(|v| v.as_u64()) {
+        config.init_retry_backoff_ms = backoff;
+    }
+    if let Some(timeout) = settings.get("callTimeout").and_then(|v| v.as_u64()) {
+        config.call_timeout_secs = timeout;
+    }
+    if let Some(max) = settings.get("maxConcurrentCalls").and_then(|v| v.as_u64()) {
+        config.max_concurrent_calls = max as usize;
+    }
+    if let Some(forward) = settings.get("forwardExperimental").and_then(|v| v.as_bool()) {
+        config.forward_experimental = forward;
+    }
+    if let Some(policy) = settings.get("preInitialize").and_then(|v| v.as_str()) {
+        match policy {
+            "strict" => config.pre_initialize = PreInitialize::Strict,
+            "lenient" => config.pre_initialize = PreInitialize::Lenient,
+            _ => tracing::warn!("Ignoring unknown preInitialize '{}'", policy),
         }
-        // Health monitor settings
-        if let Some(health) = settings.get("health") {
-            if let Some(interval) = health.get("checkInterval").and_then(|v| v.as_u64()) {
-                config.health_check_interval_secs = interval;
+    }
+    if let Some(fallback) = settings.get("unknownToolFallback").and_then(|v| v.as_bool()) {
+        config.unknown_tool_fallback = fallback;
+    }
+    if let Some(forward) = settings.get("forwardCompletions").and_then(|v| v.as_bool()) {
+        config.forward_completions = forward;
+    }
+    if let Some(forward) = settings.get("forwardSampling").and_then(|v| v.as_bool()) {
+        config.forward_sampling = forward;
+    }
+    if let Some(debug_meta) = settings.get("debugMeta").and_then(|v| v.as_bool()) {
+        config.debug_meta = debug_meta;
+    }
+    if let Some(validate) = settings.get("validateArguments").and_then(|v| v.as_bool()) {
+        config.validate_arguments = validate;
+    }
+    if let Some(grace) = settings.get("reloadGracePeriod").and_then(|v| v.as_u64()) {
+        config.reload_grace_secs = grace;
+    }
+    if let Some(grace) = settings.get("shutdownGracePeriod").and_then(|v| v.as_u64()) {
+        config.shutdown_grace_secs = grace;
+    }
+    if let Some(timeout) = settings.get("activationTimeout").and_then(|v| v.as_u64()) {
+        config.activation_timeout_secs = timeout;
+    }
+    if let Some(refresh) = settings.get("refreshStaleTools").and_then(|v| v.as_bool()) {
+        config.refresh_stale_tools = refresh;
+    }
+    if let Some(methods) = settings.get("blockedMethods").and_then(|v| v.as_array()) {
+        config.blocked_methods = methods.iter().filter_map(|m| m.as_str()).map(String::from).collect();
+    }
+    if let Some(affinity) = settings.get("resourceAffinity").and_then(|v| v.as_bool()) {
+        config.resource_affinity = affinity;
+    }
+    // Health monitor settings
+    if let Some(health) = settings.get("health") {
+        if let Some(interval) = health.get("checkInterval").and_then(|v| v.as_u64()) {
+            config.health_check_interval_secs = interval;
+        }
+        if let Some(restart) = health.get("autoRestart").and_then(|v| v.as_bool()) {
+            config.health_auto_restart = restart;
+        }
+        if let Some(notify) = health.get("notifications").and_then(|v| v.as_bool()) {
+            config.health_notifications = notify;
+        }
+    }
+    // SSE transport settings
+    if let Some(sse) = settings.get("sse") {
+        if let Some(max) = sse.get("maxSessions").and_then(|v| v.as_u64()) {
+            config.sse_max_sessions = max.max(1) as usize;
+        }
+        if let Some(bytes) = sse.get("maxBufferedBytes").and_then(|v| v.as_u64()) {
+            config.sse_max_buffered_bytes = if bytes == 0 { None } else { Some(bytes as usize) };
+        }
+        if let Some(bytes) = sse.get("maxBodyBytes").and_then(|v| v.as_u64()) {
+            config.sse_max_body_bytes = (bytes as usize).max(1024);
+        }
+        if let Some(bytes) = sse.get("maxEventBytes").and_then(|v| v.as_u64()) {
+            config.sse_max_event_bytes = if bytes == 0 { None } else { Some(bytes as usize) };
+        }
+        if let Some(capacity) = sse.get("channelCapacity").and_then(|v| v.as_u64()) {
+            config.sse_channel_capacity = capacity.max(1) as usize;
+        }
+        if let Some(capacity) = sse.get("maxChannelCapacity").and_then(|v| v.as_u64()) {
+            config.sse_max_channel_capacity = capacity as usize;
+        }
+        if let Some(ms) = sse.get("sendTimeoutMs").and_then(|v| v.as_u64()) {
+            config.sse_send_timeout_ms = ms;
+        }
+        if let Some(logs) = sse.get("logs") {
+            if let Some(rate) = logs.get("maxPerSecond").and_then(|v| v.as_u64()) {
+                config.sse_log_rate = rate.min(u32::MAX as u64) as u32;
             }
-            if let Some(restart) = health.get("autoRestart").and_then(|v| v.as_bool()) {
-                config.health_auto_restart = restart;
+            if let Some(level) = logs.get("minLevel").and_then(|v| v.as_str()) {
+                match LogLevel::parse(level) {
+                    Some(level) => config.sse_log_floor = level,
+                    None => tracing::warn!("Ignoring unknown sse.logs.minLevel '{}'", level),
+                }
+            }
+        }
+        if let Some(keepalive) = sse.get("keepalive") {
+            let secs = |key: &str| keepalive.get(key).and_then(|v| v.as_u64()).map(std::time::Duration::from_secs);
+            if let Some(interval) = secs("intervalSecs").filter(|d| !d.is_zero()) {
+                config.sse_keepalive.interval = interval;
             }
-            if let Some(notify) = health.get("notifications").and_then(|v| v.as_bool()) {
-                config.health_notifications = notify;
+            if let Some(idle_after) = secs("idleAfterSecs") {
+                config.sse_keepalive.idle_after = idle_after;
             }
+            if let Some(idle_interval) = secs("idleIntervalSecs").filter(|d| !d.is_zero()) {
+                config.sse_keepalive.idle_interval = idle_interval;
+            }
+        }
+        if let Some(limit) = sse.get("rateLimit") {
+            let rate = |key: &str| limit.get(key).and_then(|v| v.as_f64()).filter(|r| r.is_finite() && *r >= 0.0);
+            let burst = |key: &str| limit.get(key).and_then(|v| v.as_u64()).map(|b| b.clamp(1, u32::MAX as u64) as u32);
+            if let Some(per_sec) = rate("messagesPerSecond") {
+                config.sse_rate_limit.messages_per_sec = per_sec;
+            }
+            if let Some(burst) = burst("messageBurst") {
+                config.sse_rate_limit.message_burst = burst;
+            }
+            if let Some(per_sec) = rate("connectsPerSecond") {
+                config.sse_rate_limit.connects_per_sec = per_sec;
+            }
+            if let Some(burst) = burst("connectBurst") {
+                config.sse_rate_limit.connect_burst = burst;
+            }
+        }
+        let secs = |key: &str| sse.get(key).and_then(|v| v.as_u64()).filter(|&s| s > 0).map(std::time::Duration::from_secs);
+        if let Some(timeout) = secs("sessionTimeoutSecs") {
+            config.sse_reaper.session_timeout = timeout;
+        }
+        if let Some(interval) = secs("reaperIntervalSecs") {
+            config.sse_reaper.interval = interval;
+        }
+        // 0 lifts the limit
+        if let Some(lifetime) = sse.get("maxSessionLifetimeSecs").and_then(|v| v.as_u64()) {
+            config.sse_reaper.max_lifetime = (lifetime > 0).then(|| std::time::Duration::from_secs(lifetime));
+        }
+        if let Some(persist) = sse.get("persistSessions").and_then(|v| v.as_bool()) {
+            config.sse_persist_sessions = persist;
+        }
+        if let Some(buffer) = sse.get("bufferLateResponses").and_then(|v| v.as_bool()) {
+            config.sse_buffer_late_responses = buffer;
+        }
+        if let Some(policy) = sse.get("duplicateClients").and_then(|v| v.as_str()) {
+            config.sse_duplicate_clients = match policy {
+                "supersede" => DuplicateClients::Supersede,
+                _ => DuplicateClients::Allow,
+            };
+        }
+        if let Some(events) = sse.get("eventTypes").and_then(|v| v.as_str()) {
+            config.sse_split_events = events == "split";
+        }
+        if let Some(tools) = sse.get("streamResults").and_then(|v| v.as_array()) {
+            config.sse_stream_results = tools.iter().filter_map(|t| t.as_str()).map(String::from).collect();
+        }
+    }
+    // Circuit breaker settings
+    if let Some(breaker) = settings.get("circuitBreaker") {
+        config.circuit_breaker = BreakerOverride::parse(breaker).apply(config.circuit_breaker);
+    }
+    // Request/response transforms: all or nothing, a partial set could
+    // rewrite calls in ways the author didn't intend
+    if let Some(transforms) = settings.get("transforms") {
+        match parse_transforms(transforms) {
+            Ok(parsed) => config.transforms = parsed,
+            Err(e) => tracing::error!("Invalid transforms, ignoring them: {}", e),
+        }
+    }
+    // Tool results over the client's declared maximum
+    if let Some(limits) = settings.get("oversizedResults") {
+        match parse_result_limits(limits) {
+            Ok(parsed) => config.oversized_results = parsed,
+            Err(e) => tracing::error!("Invalid oversizedResults, ignoring it: {}", e),
+        }
+    }
+    // Per-tool result cache
+    if let Some(cache) = settings.get("resultCache") {
+        match parse_result_cache(cache) {
+            Ok(parsed) => config.result_cache = parsed,
+            Err(e) => tracing::error!("Invalid resultCache, ignoring it: {}", e),
+        }
+    }
+    if let Some(filter) = settings.get("toolFilter") {
+        match parse_tool_filter(filter) {
+            Ok(parsed) => config.tool_filter = parsed,
+            Err(e) => tracing::error!("Invalid toolFilter, ignoring it: {}", e),
+        }
+    }
+    if let Some(overrides) = settings.get("toolOverrides") {
+        match parse_tool_overrides(overrides) {
+            Ok(parsed) => {
+                for server in parsed.servers.keys().filter(|s| !config.servers.contains_key(*s)) {
+                    tracing::warn!("toolOverrides: no server named '{}'", server);
+                }
+                config.tool_overrides = parsed;
+            }
+            Err(e) => tracing::error!("Invalid toolOverrides, ignoring it: {}", e),
+        }
+    }
+    if let Some(audit) = settings.get("audit") {
+        match parse_audit(audit) {
+            Ok(parsed) => config.audit = parsed,
+            Err(e) => tracing::error!("Invalid audit settings, ignoring them: {}", e),
+        }
+    }
+    if let Some(record) = settings.get("record") {
+        match parse_record(record) {
+            Ok(parsed) => config.record = parsed,
+            Err(e) => tracing::error!("Invalid record settings, ignoring them: {}", e),
+        }
+    }
+    // Schema cache version check
+    if let Some(cache) = settings.get("cache") {
+        if let Some(check) = cache.get("versionCheck").and_then(|v| v.as_str()) {
+            config.cache_version.strict = check == "strict";
+        }
+        if let Some(pinned) = cache.get("pinVersion").and_then(|v| v.as_str()) {
+            config.cache_version.pinned = Some(pinned.to_string());
+        }
+        if let Some(hours) = cache.get("maxAgeHours").and_then(|v| v.as_u64()) {
+            config.cache_max_age = (hours > 0).then(|| std::time::Duration::from_secs(hours * 3600));
+        }
+        if let Some(patterns) = cache.get("redactPatterns").and_then(|v| v.as_array()) {
+            config.cache_redact_patterns = patterns.iter().filter_map(|p| p.as_str().map(String::from)).collect();
+        }
+        match cache.get("format").and_then(|v| v.as_str()) {
+            Some("gzip") => config.cache_gzip = true,
+            Some("json") => config.cache_gzip = false,
+            Some(other) => tracing::error!("Invalid cache.format {}: expected \"json\" or \"gzip\"", other),
+            None => {}
+        }
+    }
+    // stdio framing
+    if let Some(bytes) = settings.pointer("/stdio/maxMessageBytes").and_then(|v| v.as_u64()) {
+        config.stdio_max_message_bytes = (bytes as usize).max(1024);
+    }
+    if let Some(lines) = settings.pointer("/stdio/maxStderrLinesPerSec").and_then(|v| v.as_u64()) {
+        config.stdio_max_stderr_lines_per_sec = lines.clamp(1, u32::MAX as u64) as u32;
+    }
+    // Log file rotation
+    if let Some(mb) = settings.pointer("/log/maxSizeMb").and_then(|v| v.as_u64()) {
+        config.log_rotation.max_bytes = mb.saturating_mul(1024 * 1024);
+    }
+    if let Some(keep) = settings.pointer("/log/keep").and_then(|v| v.as_u64()) {
+        config.log_rotation.keep = keep as usize;
+    }
+    // WebSocket compression
+    if let Some(compression) = settings.pointer("/ws/compression") {
+        if let Some(enabled) = compression.get("enabled").and_then(|v| v.as_bool()) {
+            config.ws_compression = enabled;
+        }
+        if let Some(bytes) = compression.get("thresholdBytes").and_then(|v| v.as_u64()) {
+            config.ws_compression_threshold = bytes as usize;
+        }
+    }
+    // HTTP request parsing
+    if let Some(max) = settings.pointer("/http/maxHeaders").and_then(|v| v.as_u64()) {
+        config.http_max_headers = (max as usize).max(16);
+    }
+    if let Some(compression) = settings.pointer("/http/compression") {
+        if let Some(enabled) = compression.get("enabled").and_then(|v| v.as_bool()) {
+            config.http_compression = enabled;
+        }
+        if let Some(bytes) = compression.get("thresholdBytes").and_then(|v| v.as_u64()) {
+            config.http_compression_threshold = bytes as usize;
+        }
+    }
+    // Crash-loop guard of `serve`
+    if let Some(crash_loop) = settings.get("crashLoop") {
+        if let Some(max) = crash_loop.get("maxStarts").and_then(|v| v.as_u64()) {
+            config.crash_loop_max_starts = max.min(u32::MAX as u64) as u32;
+        }
+        if let Some(secs) = crash_loop.get("windowSecs").and_then(|v| v.as_u64()) {
+            config.crash_loop_window_secs = secs.max(1);
+        }
+    }
+    // CORS allowlist
+    if let Some(origins) = settings.pointer("/cors/allowedOrigins").and_then(|v| v.as_array()) {
+        config.cors_allowed_origins = origins.iter().filter_map(|o| o.as_str()).map(String::from).collect();
+    }
+    // Port binding settings
+    if let Some(bind) = settings.get("bind") {
+        if let Some(retries) = bind.get("retries").and_then(|v| v.as_u64()) {
+            config.bind_retries = retries as u32;
+        }
+        if let Some(delay) = bind.get("retryDelayMs").and_then(|v| v.as_u64()) {
+            config.bind_retry_delay_ms = delay;
+        }
+    }
+    // Auth settings
+    if let Some(auth) = settings.get("auth") {
+        if let Some(auto) = auth.get("autoGenerateToken").and_then(|v| v.as_bool()) {
+            config.auth_auto_generate_token = auto;
+        }
+        if let Some(required) = auth.get("required").and_then(|v| v.as_bool()) {
+            config.auth_required = required;
         }
     }
-    Some(config)
 }
 
 fn get_config_paths() -> Vec<PathBuf> {
@@ -148,11 +1192,26 @@ fn get_config_paths() -> Vec<PathBuf> {
     paths
 }
 
+/// Every file `auto_detect` may read servers from.
+pub fn config_sources() -> Vec<PathBuf> {
+    dedicated_config_files().into_iter().chain(get_config_paths()).collect()
+}
+
+/// `auto_detect` for a hot reload. A dedicated config that exists but
+/// doesn't parse is an error here instead of falling back to the other
+/// clients' configs, so a half-saved edit doesn't stop every server.
+pub fn reload() -> Result<ProxyConfig, String> {
+    if let Some(path) = dedicated_config_path().filter(|p| p.exists()) {
+        read_config_file(&path)?;
+    }
+    Ok(auto_detect())
+}
+
 pub fn auto_detect() -> ProxyConfig {
     if let Some(config) = load_dedicated_config() {
         let mode_str = match config.mode { Mode::Discover => "discover", Mode::Passthrough => "passthrough" };
-        eprintln!("[McpHub][INFO] Using dedicated config: {} servers, mode={}", config.servers.len(), mode_str);
-        return apply_env_overrides(config);
+        tracing::info!("Using dedicated config: {} servers, mode={}", config.servers.len(), mode_str);
+        return apply_env_overrides(config, |key| std::env::var(key).ok());
     }
 
     let mut config = ProxyConfig::default();
@@ -162,7 +1221,7 @@ pub fn auto_detect() -> ProxyConfig {
                 if let Ok(json) = serde_json::from_str::<Value>(&content) {
                     let servers = parse_servers(&json);
                     if !servers.is_empty() {
-                        eprintln!("[McpHub][INFO] Found {} servers in {}", servers.len(), path.display());
+                        tracing::info!("Found {} servers in {}", servers.len(), path.display());
                         config.servers.extend(servers);
                     }
                 }
@@ -171,28 +1230,309 @@ pub fn auto_detect() -> ProxyConfig {
     }
 
     if config.servers.is_empty() {
-        eprintln!("[McpHub][WARN] No MCP servers found.");
+        tracing::warn!("No MCP servers found.");
     } else {
-        eprintln!("[McpHub][INFO] Total: {} servers detected", config.servers.len());
+        tracing::info!("Total: {} servers detected", config.servers.len());
+    }
+    apply_env_overrides(config, |key| std::env::var(key).ok())
+}
+
+/// How an env var's text becomes a settings value.
+#[derive(Clone, Copy)]
+enum EnvValue {
+    /// Taken verbatim.
+    Text,
+    /// Parsed as JSON (numbers, booleans, objects); anything else is text.
+    Json,
+    /// Comma-separated strings.
+    List,
+}
+
+/// Settings that can be set from the environment, by their path in
+/// `settings`. The variable name is derived with `env_var_name`.
+const ENV_SETTINGS: &[(&str, EnvValue)] = &[
+    ("mode", EnvValue::Text),
+    ("toolNames.prefix", EnvValue::Json),
+    ("toolNames.separator", EnvValue::Text),
+    ("preload", EnvValue::Json),
+    ("preloadDelayMs", EnvValue::Json),
+    ("preloadConcurrency", EnvValue::Json),
+    ("listCacheMs", EnvValue::Json),
+    ("idleTimeout", EnvValue::Json),
+    ("initTimeout", EnvValue::Json),
+    ("initRetries", EnvValue::Json),
+    ("initRetryBackoffMs", EnvValue::Json),
+    ("callTimeout", EnvValue::Json),
+    ("maxConcurrentCalls", EnvValue::Json),
+    ("activationTimeout", EnvValue::Json),
+    ("reloadGracePeriod", EnvValue::Json),
+    ("shutdownGracePeriod", EnvValue::Json),
+    ("forwardExperimental", EnvValue::Json),
+    ("preInitialize", EnvValue::Text),
+    ("unknownToolFallback", EnvValue::Json),
+    ("forwardCompletions", EnvValue::Json),
+    ("forwardSampling", EnvValue::Json),
+    ("debugMeta", EnvValue::Json),
+    ("validateArguments", EnvValue::Json),
+    ("refreshStaleTools", EnvValue::Json),
+    ("blockedMethods", EnvValue::List),
+    ("resourceAffinity", EnvValue::Json),
+    ("health.checkInterval", EnvValue::Json),
+    ("health.autoRestart", EnvValue::Json),
+    ("health.notifications", EnvValue::Json),
+    ("sse.maxSessions", EnvValue::Json),
+    ("sse.maxBufferedBytes", EnvValue::Json),
+    ("sse.maxBodyBytes", EnvValue::Json),
+    ("sse.maxEventBytes", EnvValue::Json),
+    ("sse.channelCapacity", EnvValue::Json),
+    ("sse.maxChannelCapacity", EnvValue::Json),
+    ("sse.sendTimeoutMs", EnvValue::Json),
+    ("sse.logs.maxPerSecond", EnvValue::Json),
+    ("sse.logs.minLevel", EnvValue::Text),
+    ("sse.keepalive.intervalSecs", EnvValue::Json),
+    ("sse.keepalive.idleAfterSecs", EnvValue::Json),
+    ("sse.keepalive.idleIntervalSecs", EnvValue::Json),
+    ("sse.rateLimit.messagesPerSecond", EnvValue::Json),
+    ("sse.rateLimit.messageBurst", EnvValue::Json),
+    ("sse.rateLimit.connectsPerSecond", EnvValue::Json),
+    ("sse.rateLimit.connectBurst", EnvValue::Json),
+    ("sse.sessionTimeoutSecs", EnvValue::Json),
+    ("sse.reaperIntervalSecs", EnvValue::Json),
+    ("sse.maxSessionLifetimeSecs", EnvValue::Json),
+    ("sse.persistSessions", EnvValue::Json),
+    ("sse.bufferLateResponses", EnvValue::Json),
+    ("sse.duplicateClients", EnvValue::Text),
+    ("sse.eventTypes", EnvValue::Text),
+    ("sse.streamResults", EnvValue::List),
+    ("circuitBreaker.failureThreshold", EnvValue::Json),
+    ("circuitBreaker.windowSecs", EnvValue::Json),
+    ("circuitBreaker.cooldownSecs", EnvValue::Json),
+    ("transforms", EnvValue::Json),
+    ("oversizedResults", EnvValue::Json),
+    ("resultCache", EnvValue::Json),
+    ("toolFilter", EnvValue::Json),
+    ("toolOverrides", EnvValue::Json),
+    ("audit.enabled", EnvValue::Json),
+    ("audit.path", EnvValue::Text),
+    ("audit.redactKeys", EnvValue::List),
+    ("record.enabled", EnvValue::Json),
+    ("record.dir", EnvValue::Text),
+    ("cache.versionCheck", EnvValue::Text),
+    ("cache.pinVersion", EnvValue::Text),
+    ("cache.maxAgeHours", EnvValue::Json),
+    ("cache.redactPatterns", EnvValue::List),
+    ("cache.format", EnvValue::Text),
+    ("stdio.maxMessageBytes", EnvValue::Json),
+    ("stdio.maxStderrLinesPerSec", EnvValue::Json),
+    ("log.maxSizeMb", EnvValue::Json),
+    ("log.keep", EnvValue::Json),
+    ("ws.compression.enabled", EnvValue::Json),
+    ("ws.compression.thresholdBytes", EnvValue::Json),
+    ("http.maxHeaders", EnvValue::Json),
+    ("http.compression.enabled", EnvValue::Json),
+    ("http.compression.thresholdBytes", EnvValue::Json),
+    ("crashLoop.maxStarts", EnvValue::Json),
+    ("crashLoop.windowSecs", EnvValue::Json),
+    ("cors.allowedOrigins", EnvValue::List),
+    ("bind.retries", EnvValue::Json),
+    ("bind.retryDelayMs", EnvValue::Json),
+    ("auth.autoGenerateToken", EnvValue::Json),
+    ("auth.required", EnvValue::Json),
+];
+
+/// `MCPHUB_*` variable overriding the setting at `path`, e.g.
+/// `sse.maxSessions` -> `MCPHUB_SSE_MAX_SESSIONS`.
+pub fn env_var_name(path: &str) -> String {
+    let mut name = String::from("MCPHUB");
+    for segment in path.split('.') {
+        name.push('_');
+        for c in segment.chars() {
+            if c.is_ascii_uppercase() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+    }
+    name
+}
+
+/// Settings block built from `MCPHUB_*` variables, or None when none are set.
+fn env_settings(env: &impl Fn(&str) -> Option<String>) -> Option<Value> {
+    let mut settings = serde_json::json!({});
+    let mut applied = Vec::new();
+    for &(path, kind) in ENV_SETTINGS {
+        let name = env_var_name(path);
+        let Some(raw) = env(&name) else { continue };
+        let value = match kind {
+            EnvValue::Text => Value::String(raw),
+            EnvValue::Json => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+            EnvValue::List => raw.split(',').map(str::trim).filter(|s| !s.is_empty()).map(Value::from).collect(),
+        };
+        let mut target = &mut settings;
+        let mut keys = path.split('.').peekable();
+        while let Some(key) = keys.next() {
+            if keys.peek().is_none() {
+                target[key] = value;
+                break;
+            }
+            if target.get(key).is_none() {
+                target[key] = serde_json::json!({});
+            }
+            target = &mut target[key];
+        }
+        applied.push(name);
     }
-    apply_env_overrides(config)
+    if applied.is_empty() {
+        return None;
+    }
+    tracing::info!("Settings from environment: {}", applied.join(", "));
+    Some(settings)
 }
 
-fn apply_env_overrides(mut config: ProxyConfig) -> ProxyConfig {
-    if let Ok(mode) = std::env::var("MCP_ON_DEMAND_MODE") {
+/// Apply environment variables (read through `env`) over the config. They
+/// go through `parse_settings`, so they are checked like file values.
+fn apply_env_overrides(mut config: ProxyConfig, env: impl Fn(&str) -> Option<String>) -> ProxyConfig {
+    if let Some(mode) = env("MCP_ON_DEMAND_MODE") {
         config.mode = match mode.as_str() { "passthrough" => Mode::Passthrough, _ => Mode::Discover };
     }
-    if let Ok(preload) = std::env::var("MCP_ON_DEMAND_PRELOAD") {
+    if let Some(preload) = env("MCP_ON_DEMAND_PRELOAD") {
         config.preload = match preload.as_str() { "none" => Preload::None, _ => Preload::All };
     }
+    if let Some(settings) = env_settings(&env) {
+        parse_settings(&settings, &mut config);
+    }
+    // Session timing, in seconds, for networks that need other values
+    let secs = |key: &str| {
+        let raw = env(key)?;
+        match raw.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => Some(std::time::Duration::from_secs(secs)),
+            _ => {
+                tracing::warn!("Ignoring {}='{}': expected a positive number of seconds", key, raw);
+                None
+            }
+        }
+    };
+    if let Some(timeout) = secs("MCPHUB_SESSION_TIMEOUT") {
+        config.sse_reaper.session_timeout = timeout;
+    }
+    if let Some(interval) = secs("MCPHUB_REAPER_INTERVAL") {
+        config.sse_reaper.interval = interval;
+    }
+    if let Some(interval) = secs("MCPHUB_KEEPALIVE_INTERVAL") {
+        config.sse_keepalive.interval = interval;
+    }
     config
 }
 
+/// Address of the HTTP server (dashboard + SSE) when nothing else is set.
+pub const DEFAULT_BIND_ADDR: &str = "127.0.0.1:24680";
+
+/// Address the HTTP server binds: the `--bind` flag if given, else the
+/// `MCPHUB_BIND` env var, else DEFAULT_BIND_ADDR. The `--port` flag, or
+/// without either flag `MCPHUB_PORT`, replaces the port.
+pub fn resolve_bind_addr(flag: Option<&str>, port_flag: Option<&str>) -> Result<SocketAddr, String> {
+    let env = std::env::var("MCPHUB_BIND").ok();
+    let port = std::env::var("MCPHUB_PORT").ok();
+    parse_bind_addr(flag, port_flag, env.as_deref(), port.as_deref())
+}
+
+/// Where `resolve_bind_addr` takes the address and the port from, e.g.
+/// `MCPHUB_BIND` or `default, port from --port`.
+pub fn bind_addr_source(flag: Option<&str>, port_flag: Option<&str>) -> String {
+    let env = std::env::var("MCPHUB_BIND").ok();
+    let port = std::env::var("MCPHUB_PORT").ok();
+    let ((_, source), port) = bind_sources(flag, port_flag, env.as_deref(), port.as_deref());
+    match port {
+        Some((_, port)) => format!("{}, port from {}", source, port),
+        None => source.to_string(),
+    }
+}
+
+type BindSources<'a> = ((&'a str, &'static str), Option<(&'a str, &'static str)>);
+
+/// The address and (if replaced) port to use, each with where it came from.
+fn bind_sources<'a>(flag: Option<&'a str>, port_flag: Option<&'a str>, env: Option<&'a str>, port: Option<&'a str>) -> BindSources<'a> {
+    let addr = match (flag, env) {
+        (Some(flag), _) => (flag, "--bind"),
+        (None, Some(env)) if !env.is_empty() => (env, "MCPHUB_BIND"),
+        _ => (DEFAULT_BIND_ADDR, "default"),
+    };
+    let port = match (port_flag, flag, port.filter(|p| !p.is_empty())) {
+        (Some(port), _, _) => Some((port, "--port")),
+        (None, None, Some(port)) => Some((port, "MCPHUB_PORT")),
+        _ => None,
+    };
+    (addr, port)
+}
+
+fn parse_bind_addr(flag: Option<&str>, port_flag: Option<&str>, env: Option<&str>, port: Option<&str>) -> Result<SocketAddr, String> {
+    let ((raw, source), port) = bind_sources(flag, port_flag, env, port);
+    let mut addr: SocketAddr = raw
+        .parse()
+        .map_err(|_| format!("Invalid {} address '{}': expected IP:PORT, e.g. 0.0.0.0:24680", source, raw))?;
+    if let Some((port, source)) = port {
+        let port = port.trim().parse().map_err(|_| format!("Invalid {} '{}': expected 1-65535", source, port))?;
+        addr.set_port(port);
+    }
+    Ok(addr)
+}
+
+/// Address local clients connect to for a server bound to `addr`. A
+/// wildcard bind is reached over loopback.
+pub fn client_addr(addr: SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    SocketAddr::new(ip, addr.port())
+}
+
+/// Second address to listen on so a loopback or wildcard bind answers over
+/// both IPv4 and IPv6 (clients resolve `localhost` to either). A `[::]` bind
+/// needs none: its socket is dual-stack.
+pub fn companion_addr(addr: SocketAddr) -> Option<SocketAddr> {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_loopback() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        IpAddr::V6(ip) if ip.is_loopback() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, addr.port()))
+}
+
+/// Base URL clients use to reach a server bound to `addr`: `localhost`
+/// when both address families are served, so either resolution works.
+pub fn client_base_url(addr: SocketAddr) -> String {
+    let client = client_addr(addr);
+    if client.ip().is_loopback() && (companion_addr(addr).is_some() || addr.ip().is_unspecified()) {
+        format!("http://localhost:{}", client.port())
+    } else {
+        format!("http://{}", client)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_hub_dir_resolution() {
+        let home = std::env::temp_dir().join(format!("mcphub_home_{}", std::process::id()));
+        let vars = |pairs: &'static [(&'static str, &'static str)]| move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string());
+
+        assert_eq!(resolve_hub_dir(vars(&[]), Some(home.clone())), Some(home.join(".McpHub")));
+        assert_eq!(resolve_hub_dir(vars(&[("MCPHUB_HOME", "/srv/mcphub"), ("XDG_STATE_HOME", "/state")]), Some(home.clone())), Some(PathBuf::from("/srv/mcphub")));
+        assert_eq!(resolve_hub_dir(vars(&[("MCPHUB_HOME", ""), ("XDG_STATE_HOME", "/state")]), Some(home.clone())), Some(PathBuf::from("/state/mcphub")));
+        assert_eq!(resolve_hub_dir(vars(&[]), None), None);
+
+        // An existing ~/.McpHub wins over XDG
+        fs::create_dir_all(home.join(".McpHub")).unwrap();
+        assert_eq!(resolve_hub_dir(vars(&[("XDG_STATE_HOME", "/state")]), Some(home.clone())), Some(home.join(".McpHub")));
+        let _ = fs::remove_dir_all(&home);
+    }
+
     #[test]
     fn test_is_self() {
         assert!(is_self("McpHub", &json!({})));
@@ -200,6 +1540,108 @@ mod tests {
         assert!(!is_self("github", &json!({})));
         assert!(is_self("test", &json!({"command": "/path/to/mcphub"})));
         assert!(is_self("test", &json!({"command": "node", "args": ["mcphub"]})));
+        assert!(is_self("hub", &json!({"url": "http://localhost:24680/sse"})));
+        assert!(!is_self("docs", &json!({"url": "http://localhost:8080/sse"})));
+    }
+
+    #[test]
+    fn test_bind_addr_precedence_and_validation() {
+        assert_eq!(parse_bind_addr(None, None, None, None).unwrap().to_string(), DEFAULT_BIND_ADDR);
+        assert_eq!(parse_bind_addr(None, None, Some(""), None).unwrap().to_string(), DEFAULT_BIND_ADDR);
+        assert_eq!(parse_bind_addr(None, None, Some("0.0.0.0:9000"), None).unwrap().port(), 9000);
+        assert_eq!(parse_bind_addr(Some("127.0.0.1:9001"), None, Some("0.0.0.0:9000"), None).unwrap().port(), 9001);
+        assert_eq!(parse_bind_addr(None, None, None, Some("9002")).unwrap().to_string(), "127.0.0.1:9002");
+        assert_eq!(parse_bind_addr(Some("127.0.0.1:9001"), None, None, Some("9002")).unwrap().port(), 9001);
+
+        let err = parse_bind_addr(None, None, Some("localhost"), None).unwrap_err();
+        assert!(err.contains("MCPHUB_BIND") && err.contains("'localhost'"), "{}", err);
+        assert!(parse_bind_addr(None, None, None, Some("70000")).unwrap_err().contains("MCPHUB_PORT"));
+        // --port beats both the env var and the port of --bind
+        assert_eq!(parse_bind_addr(Some("0.0.0.0:9001"), Some("9003"), None, Some("9002")).unwrap().to_string(), "0.0.0.0:9003");
+        assert_eq!(parse_bind_addr(None, Some("9003"), Some("0.0.0.0:9000"), Some("9002")).unwrap().to_string(), "0.0.0.0:9003");
+        assert!(parse_bind_addr(None, Some("http"), None, None).unwrap_err().contains("--port"));
+
+        // Loopback and wildcard binds serve both families, reached as localhost
+        assert_eq!(client_base_url("0.0.0.0:9000".parse().unwrap()), "http://localhost:9000");
+        assert_eq!(client_base_url("[::]:9000".parse().unwrap()), "http://localhost:9000");
+        assert_eq!(client_base_url("127.0.0.1:9000".parse().unwrap()), "http://localhost:9000");
+        assert_eq!(client_base_url("10.0.0.5:9000".parse().unwrap()), "http://10.0.0.5:9000");
+        assert_eq!(client_addr("0.0.0.0:9000".parse().unwrap()).to_string(), "127.0.0.1:9000");
+    }
+
+    #[test]
+    fn test_companion_addr_covers_other_family() {
+        let companion = |addr: &str| companion_addr(addr.parse().unwrap()).map(|a| a.to_string());
+        assert_eq!(companion("127.0.0.1:24680").as_deref(), Some("[::1]:24680"));
+        assert_eq!(companion("[::1]:24680").as_deref(), Some("127.0.0.1:24680"));
+        assert_eq!(companion("0.0.0.0:24680").as_deref(), Some("[::]:24680"));
+        assert_eq!(companion("[::]:24680"), None);
+        assert_eq!(companion("10.0.0.5:24680"), None);
+    }
+
+    #[test]
+    fn test_config_error_names_location() {
+        let broken = "{\n  \"servers\": {\n    \"github\": { \"command\": \"npx\" }\n    \"slack\": {}\n  }\n}\n";
+        let err = parse_config_json(Path::new("/tmp/config.json"), broken).unwrap_err();
+        assert!(err.starts_with("/tmp/config.json:4:5: expected"), "{}", err);
+        assert!(err.contains("    4 |     \"slack\": {}"), "{}", err);
+        assert!(err.ends_with("\n      |     ^"), "{}", err);
+
+        assert!(parse_config_json(Path::new("ok.json"), "{}").is_ok());
+    }
+
+    #[test]
+    fn test_toml_config_matches_json_shape() {
+        let toml = r#"
+[settings]
+mode = "passthrough"
+
+[servers.github]
+command = "npx"
+args = ["-y", "server-github"]
+callTimeout = 60
+env = { GITHUB_TOKEN = "t" }
+
+[servers.broken]
+command = "x"
+args = "not-a-list"
+
+[settings.toolFilter.github]
+allow = ["search_*"]
+"#;
+        let json = parse_config_toml(Path::new("config.toml"), toml).unwrap();
+        assert_eq!(json["settings"]["toolFilter"]["github"]["allow"][0], "search_*");
+        let servers = parse_servers(&json);
+        assert_eq!(servers.keys().collect::<Vec<_>>(), ["github"]);
+        assert_eq!(servers["github"].args, ["-y", "server-github"]);
+        assert_eq!(servers["github"].call_timeout_secs, Some(60));
+        assert_eq!(servers["github"].env["GITHUB_TOKEN"], "t");
+        assert_eq!(server_errors(&json), ["broken: args must be a list of strings"]);
+
+        let err = parse_config_toml(Path::new("/tmp/config.toml"), "[servers.a]\ncommand = npx\n").unwrap_err();
+        assert!(err.starts_with("/tmp/config.toml:2:11: "), "{}", err);
+        assert!(err.ends_with("    2 | command = npx\n      |           ^"), "{}", err);
+    }
+
+    #[test]
+    fn test_env_interpolation() {
+        let lookup = |reference: &str| match reference {
+            "HOME" => Some("/home/me".to_string()),
+            "secret:github" => Some("ghp_x".to_string()),
+            _ => None,
+        };
+        assert_eq!(interpolate("${HOME}/.cache", lookup).unwrap(), "/home/me/.cache");
+        assert_eq!(interpolate("token ${secret:github}", lookup).unwrap(), "token ghp_x");
+        assert_eq!(interpolate("plain ${unclosed", lookup).unwrap(), "plain ${unclosed");
+        assert_eq!(interpolate("${NOPE}", lookup).unwrap_err(), "${NOPE}");
+
+        let env = HashMap::from([("KEY".to_string(), "${MCPHUB_TEST_SURELY_UNSET}".to_string())]);
+        assert_eq!(resolve_env(&env).unwrap_err(), "env KEY: ${MCPHUB_TEST_SURELY_UNSET} is not set");
+
+        let headers = HashMap::from([("X-Api-Key".to_string(), "k\r\nX-Admin: 1".to_string())]);
+        assert_eq!(resolve_headers(&headers).unwrap_err(), "header X-Api-Key: value contains a line break");
+        assert!(resolve_headers(&HashMap::from([("X-Bad: y".to_string(), "v".to_string())])).is_err());
+        assert!(resolve_headers(&HashMap::from([("X-Api-Key".to_string(), "k".to_string())])).is_ok());
     }
 
     #[test]
@@ -234,6 +1676,45 @@ mod tests {
         assert_eq!(github.command, "npx");
         assert_eq!(github.args.len(), 2);
         assert_eq!(github.env.get("GITHUB_TOKEN").unwrap(), "123");
+        assert_eq!(github.init_timeout_secs, None);
+        assert_eq!(github.call_timeout_secs, None);
+    }
+
+    #[test]
+    fn test_parse_servers_timeouts() {
+        let json = json!({
+            "mcpServers": {
+                "slow": { "command": "slow-server", "initTimeout": 120, "callTimeout": 10, "priority": 5, "dependsOn": ["gateway"] }
+            }
+        });
+        let servers = parse_servers(&json);
+        assert_eq!(servers["slow"].init_timeout_secs, Some(120));
+        assert_eq!(servers["slow"].call_timeout_secs, Some(10));
+        assert_eq!(servers["slow"].priority, 5);
+        assert_eq!(servers["slow"].depends_on, ["gateway"]);
+        // Only the listing order changes: no restart needed
+        let reordered = ServerConfig { priority: 0, ..servers["slow"].clone() };
+        assert!(!servers["slow"].needs_restart(&reordered));
+        assert!(servers["slow"].needs_restart(&ServerConfig { pool: 2, ..reordered }));
+    }
+
+    #[test]
+    fn test_parse_remote_servers() {
+        let json = json!({
+            "mcpServers": {
+                "docs": { "url": "http://docs.internal:8080/sse", "headers": { "Authorization": "Bearer ${DOCS_TOKEN}" } },
+                "secure": { "url": "https://docs.example.com/sse" },
+                "both": { "command": "docs-mcp", "url": "http://docs.internal:8080/sse" }
+            }
+        });
+        let servers = parse_servers(&json);
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers["docs"].url.as_deref(), Some("http://docs.internal:8080/sse"));
+        assert_eq!(servers["docs"].headers["Authorization"], "Bearer ${DOCS_TOKEN}");
+        assert_eq!(server_errors(&json), [
+            "both: set either command or url, not both",
+            "secure: url must be an http:// url",
+        ]);
     }
 
     #[test]
@@ -242,4 +1723,85 @@ mod tests {
         let servers = parse_servers(&json);
         assert!(servers.is_empty());
     }
+
+    #[test]
+    fn test_parse_settings() {
+        let mut config = ProxyConfig::default();
+        assert!(config.auth_auto_generate_token);
+
+        parse_settings(&json!({
+            "mode": "passthrough",
+            "idleTimeout": 60,
+            "initTimeout": 90,
+            "initRetries": 4,
+            "callTimeout": 15,
+            "preload": ["github"],
+            "preloadConcurrency": 0,
+            "blockedMethods": ["completion/complete"],
+            "health": { "autoRestart": false },
+            "auth": { "autoGenerateToken": false }
+        }), &mut config);
+
+        assert_eq!(config.mode, Mode::Passthrough);
+        assert_eq!(config.idle_timeout_ms, 60_000);
+        assert_eq!(config.init_timeout_secs, 90);
+        assert_eq!((config.init_retries, config.init_retry_backoff_ms), (4, 500));
+        assert_eq!(config.call_timeout_secs, 15);
+        assert_eq!(config.preload, Preload::Some(vec!["github".into()]));
+        assert_eq!(config.preload_concurrency, 1);
+        assert_eq!(config.blocked_methods, ["completion/complete"]);
+        assert!(!config.health_auto_restart);
+        assert!(config.health_notifications);
+        assert!(!config.auth_auto_generate_token);
+    }
+
+    #[test]
+    fn test_env_overrides_session_timing() {
+        use std::time::Duration;
+        let mut config = ProxyConfig::default();
+        parse_settings(&json!({ "sse": { "sessionTimeoutSecs": 600, "reaperIntervalSecs": 30 } }), &mut config);
+        assert_eq!(config.sse_reaper.session_timeout, Duration::from_secs(600));
+
+        let env: HashMap<&str, &str> =
+            [("MCPHUB_SESSION_TIMEOUT", "120"), ("MCPHUB_KEEPALIVE_INTERVAL", "5"), ("MCPHUB_REAPER_INTERVAL", "soon")]
+                .into_iter()
+                .collect();
+        let config = apply_env_overrides(config, |k| env.get(k).map(|v| v.to_string()));
+        assert_eq!(config.sse_reaper.session_timeout, Duration::from_secs(120));
+        assert_eq!(config.sse_keepalive.interval, Duration::from_secs(5));
+        // Invalid values leave the file setting in place
+        assert_eq!(config.sse_reaper.interval, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_env_setting_wins_over_file() {
+        let mut config = ProxyConfig::default();
+        parse_settings(&json!({
+            "callTimeout": 15,
+            "sse": { "maxSessions": 10, "logs": { "minLevel": "info" } },
+            "cache": { "pinVersion": "1.0" }
+        }), &mut config);
+
+        let env: HashMap<&str, &str> = [
+            ("MCPHUB_SSE_MAX_SESSIONS", "3"),
+            ("MCPHUB_SSE_LOGS_MIN_LEVEL", "warning"),
+            ("MCPHUB_CACHE_PIN_VERSION", "1.2"),
+            ("MCPHUB_CORS_ALLOWED_ORIGINS", "https://a.example, https://b.example"),
+            ("MCPHUB_AUTH_REQUIRED", "false"),
+            ("MCPHUB_CALL_TIMEOUT", "soon"),
+        ]
+        .into_iter()
+        .collect();
+        let config = apply_env_overrides(config, |k| env.get(k).map(|v| v.to_string()));
+        assert_eq!(config.sse_max_sessions, 3);
+        assert_eq!(config.sse_log_floor, LogLevel::Warning);
+        assert_eq!(config.cache_version.pinned.as_deref(), Some("1.2"));
+        assert_eq!(config.cors_allowed_origins, ["https://a.example", "https://b.example"]);
+        assert!(!config.auth_required);
+        // Rejected like the same value in the file would be
+        assert_eq!(config.call_timeout_secs, 15);
+
+        assert_eq!(env_var_name("sse.logs.minLevel"), "MCPHUB_SSE_LOGS_MIN_LEVEL");
+        assert_eq!(env_var_name("idleTimeout"), "MCPHUB_IDLE_TIMEOUT");
+    }
 }
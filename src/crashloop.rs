@@ -0,0 +1,84 @@
+/// Crash-loop guard for `serve`. Each start is recorded in
+/// `~/.McpHub/starts.json`, and the record is cleared once the server has
+/// stayed up for `crashLoop.windowSecs` or stops cleanly. A start that finds
+/// `crashLoop.maxStarts` recent starts that never got that far logs it and
+/// exits with `EXIT_CRASH_LOOP` instead of failing the same way again.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Exit code of a start that gave up (`EX_CONFIG`); the systemd unit
+/// doesn't restart on it.
+pub const EXIT_CRASH_LOOP: i32 = 78;
+
+fn starts_path() -> Option<PathBuf> {
+    crate::config::hub_dir().map(|dir| dir.join("starts.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Record a start at `now`. Err with the number of failed starts if
+/// `max_starts` of them (0 = never) fall within `window_secs`; the record is
+/// then cleared, so a start after fixing the cause gets a fresh count.
+fn record_start_at(path: &Path, now: u64, max_starts: u32, window_secs: u64) -> Result<(), usize> {
+    let starts: Vec<u64> = fs::read_to_string(path).ok().and_then(|text| serde_json::from_str(&text).ok()).unwrap_or_default();
+    let mut recent: Vec<u64> = starts.into_iter().filter(|t| now.saturating_sub(*t) < window_secs).collect();
+    if max_starts > 0 && recent.len() >= max_starts as usize {
+        let _ = fs::remove_file(path);
+        return Err(recent.len());
+    }
+    recent.push(now);
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Err(e) = fs::write(path, serde_json::to_string(&recent).unwrap_or_default()) {
+        tracing::warn!("Could not record start in {}: {}", path.display(), e);
+    }
+    Ok(())
+}
+
+/// Record this start; Err with the number of recent failed starts if it
+/// should give up.
+pub fn record_start(max_starts: u32, window_secs: u64) -> Result<(), usize> {
+    match starts_path() {
+        Some(path) => record_start_at(&path, now(), max_starts, window_secs),
+        None => Ok(()),
+    }
+}
+
+/// Forget recorded starts: this one stayed up or stopped cleanly.
+pub fn clear() {
+    if let Some(path) = starts_path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gives_up_after_max_starts_in_window() {
+        let dir = std::env::temp_dir().join(format!("mcphub_crashloop_{}", std::process::id()));
+        let path = dir.join("starts.json");
+        let _ = fs::remove_file(&path);
+
+        for t in [100, 110, 120] {
+            assert_eq!(record_start_at(&path, t, 3, 60), Ok(()));
+        }
+        assert_eq!(record_start_at(&path, 130, 3, 60), Err(3));
+        // Giving up starts the count over
+        assert!(!path.exists());
+        assert_eq!(record_start_at(&path, 131, 3, 60), Ok(()));
+
+        // Starts older than the window don't count, and 0 never gives up
+        for t in [140, 150] {
+            assert_eq!(record_start_at(&path, t, 3, 60), Ok(()));
+        }
+        assert_eq!(record_start_at(&path, 195, 3, 60), Ok(()));
+        assert_eq!(record_start_at(&path, 196, 0, 60), Ok(()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,58 +1,179 @@
 //! Embedded web dashboard for McpHub.
-//! Serves HTML + JSON API on http://127.0.0.1:24680
+//! Serves HTML + JSON API on http://127.0.0.1:24680 (or the `--bind` address)
 //! Zero external dependencies — uses tokio::net::TcpListener directly.
 
+use crate::breaker::BreakerStatus;
+use crate::child::{GroupHealth, GroupState, RestartStats};
+use crate::http::{gzip_response, parse_request, with_headers, HttpRequest, RequestError};
 use crate::proxy::ProxyServer;
-use crate::sse::{extract_session_id, SseManager};
+use crate::sse::{extract_session_id, ConnectInfo, SseManager};
+use crate::streamable::StreamableManager;
+use crate::ws::WsManager;
 use serde_json::{json, Value};
 use std::fs;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpListener;
 
 // ─── Config I/O ──────────────────────────────────────────────
 
 fn config_dir() -> PathBuf {
-    dirs::home_dir().unwrap_or_default().join(".McpHub")
+    crate::config::hub_dir().unwrap_or_default()
 }
 
 fn config_path() -> PathBuf {
-    config_dir().join("config.json")
+    crate::config::dedicated_config_path().unwrap_or_else(|| config_dir().join("config.json"))
 }
 
-fn cache_path() -> PathBuf {
-    config_dir().join("schema-cache.json")
+fn auth_token_path() -> PathBuf {
+    config_dir().join("auth-token")
 }
 
-pub fn get_auth_token() -> String {
-    let path = config_dir().join("auth-token");
-    if let Ok(token) = fs::read_to_string(&path) {
+/// Variables that supply the token instead of the file, in precedence order.
+const TOKEN_ENV_VARS: [&str; 2] = ["MCPHUB_AUTH_TOKEN", "MCPHUB_TOKEN"];
+
+/// Get the auth token, generating and persisting one if the file is missing.
+/// Precedence: `MCPHUB_AUTH_TOKEN`, then `MCPHUB_TOKEN` (empty values are
+/// ignored), then `~/.McpHub/auth-token`. With a variable set the file is
+/// neither read nor created.
+pub fn get_auth_token(bind_addr: SocketAddr) -> String {
+    load_auth_token(true, bind_addr).unwrap_or_default()
+}
+
+/// The token variable in effect and its value, if any.
+fn env_auth_token() -> Option<(&'static str, String)> {
+    token_from_vars(|var| std::env::var(var).ok())
+}
+
+fn token_from_vars(lookup: impl Fn(&str) -> Option<String>) -> Option<(&'static str, String)> {
+    TOKEN_ENV_VARS.iter().find_map(|&var| {
+        let token = lookup(var)?.trim().to_string();
+        (!token.is_empty()).then_some((var, token))
+    })
+}
+
+/// Where the auth token comes from, without the token: the variable
+/// holding it, or the token file. None while neither has one.
+pub fn auth_token_source() -> Option<String> {
+    if let Some((var, _)) = env_auth_token() {
+        return Some(format!("env {}", var));
+    }
+    let path = auth_token_path();
+    fs::read_to_string(&path)
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+        .map(|_| format!("file {}", path.display()))
+}
+
+/// Load the auth token with `get_auth_token`'s precedence. If the file is
+/// missing and `auto_generate` is set, a new token is created and
+/// persisted; the log says where and how to print the client config (for
+/// `bind_addr`), never the token itself. Otherwise returns Err.
+pub fn load_auth_token(auto_generate: bool, bind_addr: SocketAddr) -> Result<String, String> {
+    if let Some((_, token)) = env_auth_token() {
+        return Ok(token);
+    }
+    let path = auth_token_path();
+    let (token, created) = load_or_create_token(&path, auto_generate)?;
+    if created {
+        tracing::info!(tag = "AUTH", "No auth token found, generated a new one at {}", path.display());
+        let show = if bind_addr.to_string() == crate::config::DEFAULT_BIND_ADDR {
+            "McpHub token show".to_string()
+        } else {
+            format!("McpHub token show --bind {}", bind_addr)
+        };
+        tracing::info!(tag = "AUTH", "Update your client config: '{}' prints it", show);
+    }
+    Ok(token)
+}
+
+/// Token of a daemon started by the service manager, which doesn't see
+/// this shell's variables: always the file's. `install` prints this one.
+pub fn service_auth_token() -> String {
+    if let Some((var, _)) = env_auth_token() {
+        tracing::warn!(
+            tag = "AUTH", "{} is set here, but the auto-started service won't inherit it and uses {} instead",
+            var,
+            auth_token_path().display()
+        );
+    }
+    let path = auth_token_path();
+    match load_or_create_token(&path, true) {
+        Ok((token, created)) => {
+            if created {
+                tracing::info!(tag = "AUTH", "No auth token found, generated a new one at {}", path.display());
+            }
+            token
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            String::new()
+        }
+    }
+}
+
+/// Returns the token and whether it was freshly generated.
+fn load_or_create_token(path: &Path, auto_generate: bool) -> Result<(String, bool), String> {
+    if let Ok(token) = fs::read_to_string(path) {
         let token = token.trim().to_string();
         if !token.is_empty() {
-            return token;
+            return Ok((token, false));
         }
     }
-    
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
-    let token = format!("mcphub_{:x}", nanos);
+
+    if !auto_generate {
+        return Err(format!(
+            "Auth token missing at {} and settings.auth.autoGenerateToken is false",
+            path.display()
+        ));
+    }
+    Ok((write_new_token(path)?, true))
+}
+
+/// Replace the persisted token with a fresh one. Clients using the old
+/// token are refused from then on, and a running hub closes their SSE
+/// sessions.
+pub fn rotate_auth_token() -> Result<String, String> {
+    if let Some((var, _)) = env_auth_token() {
+        return Err(format!("{} is set and overrides the token file; change it there instead", var));
+    }
+    write_new_token(&auth_token_path())
+}
+
+/// Write a fresh random token. It goes to a file created owner-only and
+/// renamed into place, so it is never readable by others, even briefly.
+fn write_new_token(path: &Path) -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Failed to generate an auth token: {}", e))?;
+    let token = format!("mcphub_{}", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>());
     if let Some(parent) = path.parent() {
         let _ = fs::create_dir_all(parent);
     }
-    
-    let _ = fs::write(&path, &token);
-    
+
+    let tmp = path.with_extension("tmp");
+    let _ = fs::remove_file(&tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
     #[cfg(unix)]
     {
-        use std::os::unix::fs::PermissionsExt;
-        if let Ok(mut perms) = fs::metadata(&path).map(|m| m.permissions()) {
-            perms.set_mode(0o600);
-            let _ = fs::set_permissions(&path, perms);
-        }
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
-    
-    token
+    let written = options.open(&tmp).and_then(|mut file| {
+        use std::io::Write;
+        file.write_all(token.as_bytes())?;
+        file.sync_all()
+    });
+    written
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            format!("Failed to write auth token to {}: {}", path.display(), e)
+        })?;
+
+    Ok(token)
 }
 
 fn binary_path() -> PathBuf {
@@ -71,91 +192,75 @@ fn read_config() -> Value {
     if !path.exists() {
         return json!({"mcpServers": {}, "settings": {"mode": "discover", "idleTimeout": 300}});
     }
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_else(|| json!({"mcpServers": {}, "settings": {}}))
+    crate::config::read_config_file(&path).unwrap_or_else(|_| json!({"mcpServers": {}, "settings": {}}))
 }
 
-fn save_config(config: &Value) -> bool {
+/// Write the dashboard's edit to config.json. A config.toml is the user's
+/// to edit, so changes to it are refused rather than rewritten as JSON.
+fn save_config(config: &Value) -> Result<(), Vec<u8>> {
     let path = config_path();
+    if crate::config::is_toml(&path) {
+        return Err(json_err(409, &format!("The config is {}; edit it directly", path.display())));
+    }
     if let Some(parent) = path.parent() {
         let _ = fs::create_dir_all(parent);
     }
     serde_json::to_string_pretty(config)
         .ok()
         .and_then(|json| fs::write(&path, json).ok())
-        .is_some()
+        .ok_or_else(|| json_err(500, "Failed to save config"))
 }
 
-fn read_cache() -> Option<Value> {
-    let path = cache_path();
-    if !path.exists() {
-        return None;
+/// The running hub's view of the schema cache, which includes server state
+/// it hasn't written yet; the file alone in dashboard-only mode.
+fn read_cache(proxy: Option<&ProxyServer>) -> Option<crate::cache::SchemaCache> {
+    match proxy {
+        Some(proxy) => proxy.read_cache(),
+        None => crate::cache::read_cache(),
     }
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
 }
 
 // ─── HTTP Parsing ────────────────────────────────────────────
 
-struct HttpRequest {
-    method: String,
-    path: String,
-    headers: std::collections::HashMap<String, String>,
-    body: String,
-}
-
-fn parse_request(raw: &str) -> Option<HttpRequest> {
-    let mut lines = raw.lines();
-    let first = lines.next()?;
-    let parts: Vec<&str> = first.split_whitespace().collect();
-    if parts.len() < 2 {
-        return None;
-    }
-    let method = parts[0].to_string();
-    let path = parts[1].to_string();
-
-    let mut headers = std::collections::HashMap::new();
-    let mut content_length: usize = 0;
-    for line in raw.lines().skip(1) {
-        if line.is_empty() || line == "\r" {
-            break;
-        }
-        if let Some((k, v)) = line.split_once(':') {
-            let key = k.trim().to_lowercase();
-            let val = v.trim().to_string();
-            if key == "content-length" {
-                content_length = val.parse().unwrap_or(0);
-            }
-            headers.insert(key, val);
-        }
-    }
-
-    let body = if content_length > 0 {
-        if let Some(idx) = raw.find("\r\n\r\n") {
-            raw[idx + 4..].to_string()
-        } else if let Some(idx) = raw.find("\n\n") {
-            raw[idx + 2..].to_string()
-        } else {
-            String::new()
-        }
-    } else {
-        String::new()
-    };
-
-    Some(HttpRequest { method, path, headers, body })
-}
-
 fn http_response(status: u16, status_text: &str, content_type: &str, body: &str) -> Vec<u8> {
     format!(
-        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, PUT, DELETE, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n{}",
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
         status, status_text, content_type, body.len(), body
     )
     .into_bytes()
 }
 
+/// CORS headers for a request from `origin`: the origin echoed back if the
+/// allowlist permits it, nothing otherwise. Preflights also get the allowed
+/// methods and headers.
+pub fn cors_headers(allowed: &[String], origin: Option<&str>, preflight: bool) -> String {
+    let Some(origin) = origin.filter(|o| origin_allowed(allowed, o)) else {
+        return String::new();
+    };
+    let mut headers = format!("Access-Control-Allow-Origin: {}\r\nVary: Origin\r\n", origin);
+    if preflight {
+        headers.push_str(
+            "Access-Control-Allow-Methods: GET, HEAD, POST, PUT, DELETE, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type, Authorization, Last-Event-ID, X-McpHub-Client-Id, X-McpHub-Pin, Mcp-Session-Id, Mcp-Protocol-Version\r\n\
+             Access-Control-Max-Age: 86400\r\n",
+        );
+    }
+    headers
+}
+
+/// An entry matches the origin exactly; one without a port matches that
+/// scheme and host on any port; `*` matches everything.
+pub fn origin_allowed(allowed: &[String], origin: &str) -> bool {
+    allowed.iter().any(|entry| {
+        entry == "*"
+            || entry == origin
+            || origin
+                .strip_prefix(entry.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+                .is_some_and(|port| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
 fn json_ok(data: Value) -> Vec<u8> {
     http_response(200, "OK", "application/json", &data.to_string())
 }
@@ -171,57 +276,56 @@ fn json_err(status: u16, msg: &str) -> Vec<u8> {
 
 // ─── API Handlers ────────────────────────────────────────────
 
-fn handle_get_servers() -> Vec<u8> {
+/// Configured servers with their cached tools (full `ToolDef`s under
+/// `toolDefs`) and stored error. `health` and `breakers` hold the live
+/// instance group health and circuit breaker state per server (empty in
+/// dashboard-only mode). `only` narrows the list to one server.
+fn handle_get_servers(
+    cache: Option<crate::cache::SchemaCache>,
+    health: &std::collections::HashMap<String, GroupHealth>,
+    breakers: &std::collections::HashMap<String, BreakerStatus>,
+    restarts: &std::collections::HashMap<String, RestartStats>,
+    only: Option<&str>,
+) -> Vec<u8> {
     let config = read_config();
-    let cache = read_cache();
     let servers_obj = config
         .get("mcpServers")
         .or_else(|| config.get("servers"))
         .and_then(|v| v.as_object())
         .cloned()
         .unwrap_or_default();
-    let cached_servers = cache
-        .as_ref()
-        .and_then(|c| c.get("servers"))
-        .and_then(|v| v.as_object())
-        .cloned()
-        .unwrap_or_default();
-    let cached_errors = cache
-        .as_ref()
-        .and_then(|c| c.get("errors"))
-        .and_then(|v| v.as_object())
-        .cloned()
-        .unwrap_or_default();
 
     let mut result: Vec<Value> = Vec::new();
-    let mut names: Vec<String> = servers_obj.keys().cloned().collect();
+    let mut names: Vec<String> = servers_obj.keys().filter(|n| only.is_none_or(|o| o == n.as_str())).cloned().collect();
     names.sort();
+    if let (Some(name), true) = (only, names.is_empty()) {
+        return json_err(404, &format!("Unknown server: {}", name));
+    }
 
     for name in &names {
         let srv = &servers_obj[name];
-        let cached = cached_servers.get(name);
-        let tools: Vec<String> = cached
-            .and_then(|c| c.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|t| t.get("name").and_then(|n| n.as_str()).map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
+        let cached = cache.as_ref().and_then(|c| c.servers.get(name));
+        let tools: Vec<String> = cached.map(|defs| defs.iter().map(|t| t.name.clone()).collect()).unwrap_or_default();
         let tool_count = tools.len();
 
-        let error_msg = cached_errors.get(name).and_then(|v| v.as_str()).unwrap_or("");
+        let error_msg = cache.as_ref().and_then(|c| c.errors.get(name)).map(String::as_str).unwrap_or("");
 
         result.push(json!({
             "name": name,
             "command": srv.get("command").and_then(|v| v.as_str()).unwrap_or(""),
             "args": srv.get("args").unwrap_or(&json!([])),
             "env": srv.get("env").unwrap_or(&json!({})),
+            "url": srv.get("url"),
             "disabled": srv.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false),
             "tools": tool_count,
             "toolNames": tools,
+            "toolDefs": cached.cloned().unwrap_or_default(),
+            "updatedAt": cache.as_ref().and_then(|c| c.updated_at.get(name)),
             "status": if cached.is_some() { "cached" } else if !error_msg.is_empty() { "error" } else { "uncached" },
-            "error": error_msg
+            "error": error_msg,
+            "health": health.get(name),
+            "breaker": breakers.get(name),
+            "restarts": restarts.get(name).cloned().unwrap_or_default()
         }));
     }
 
@@ -231,6 +335,7 @@ fn handle_get_servers() -> Vec<u8> {
         "servers": result,
         "settings": config.get("settings").unwrap_or(&json!({})),
         "totalTools": total_tools,
+        "totalRestarts": restarts.values().map(|r| r.restarts).sum::<u64>(),
         "cacheExists": cache.is_some()
     }))
 }
@@ -272,10 +377,9 @@ fn handle_add_server(body: &str) -> Vec<u8> {
         "env": env
     });
 
-    if save_config(&config) {
-        json_ok(json!({"ok": true, "message": "Server added"}))
-    } else {
-        json_err(500, "Failed to save config")
+    match save_config(&config) {
+        Ok(()) => json_ok(json!({"ok": true, "message": "Server added"})),
+        Err(resp) => resp,
     }
 }
 
@@ -325,10 +429,9 @@ fn handle_update_server(name: &str, body: &str) -> Vec<u8> {
         srv["env"] = env.clone();
     }
 
-    if save_config(&config) {
-        json_ok(json!({"ok": true}))
-    } else {
-        json_err(500, "Failed to save config")
+    match save_config(&config) {
+        Ok(()) => json_ok(json!({"ok": true})),
+        Err(resp) => resp,
     }
 }
 
@@ -342,41 +445,87 @@ fn handle_delete_server(name: &str) -> Vec<u8> {
     if servers.remove(name).is_none() {
         return json_err(404, "Server not found");
     }
-    if save_config(&config) {
-        json_ok(json!({"ok": true}))
-    } else {
-        json_err(500, "Failed to save config")
+    match save_config(&config) {
+        Ok(()) => json_ok(json!({"ok": true})),
+        Err(resp) => resp,
     }
 }
 
-fn handle_toggle_server(name: &str, body: &str) -> Vec<u8> {
-    let data: Value = match serde_json::from_str(body) {
+/// POST /api/servers/{name}/toggle with `{"disabled": bool}`: the
+/// dashboard's switch for `/disable` and `/enable`, token included.
+async fn handle_toggle_server(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>, name: &str) -> Vec<u8> {
+    let data: Value = match serde_json::from_str(&req.body) {
         Ok(v) => v,
         Err(_) => return json_err(400, "Invalid JSON"),
     };
     let disabled = data.get("disabled").and_then(|v| v.as_bool()).unwrap_or(false);
+    handle_set_disabled(req, proxy, sse, name, disabled).await
+}
+
+/// POST /api/servers/{name}/disable and /enable. The flag is saved in the
+/// config (so it survives a restart) and applied to the running hub right
+/// away: a disabled server is stopped and its tools leave `tools/list`.
+/// Needs the Bearer token when the hub is serving.
+async fn handle_set_disabled(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>, name: &str, disabled: bool) -> Vec<u8> {
+    if let Err(resp) = check_token(req, sse.as_deref()) {
+        return resp;
+    }
+    if let Err(resp) = save_disabled(name, disabled) {
+        return resp;
+    }
+    if let Some(proxy) = proxy {
+        if let Err(e) = proxy.reload_config().await {
+            return json_err(500, &format!("Saved, but the config did not reload: {}", e));
+        }
+    }
+    tracing::info!("Server {} {}", name, if disabled { "disabled" } else { "enabled" });
+    json_ok(json!({"ok": true, "disabled": disabled}))
+}
+
+/// 401 unless the request carries the hub's Bearer token. Dashboard-only
+/// mode has no token to check against.
+fn check_token(req: &HttpRequest, sse: Option<&SseManager>) -> Result<(), Vec<u8>> {
+    match sse {
+        Some(sse) if !sse.is_authorized(req.headers.get("authorization").map(|a| a.as_str())) => Err(crate::sse::unauthorized()),
+        _ => Ok(()),
+    }
+}
 
+fn save_disabled(name: &str, disabled: bool) -> Result<(), Vec<u8>> {
     let mut config = read_config();
+    set_disabled(&mut config, name, disabled).map_err(|e| json_err(404, e))?;
+    save_config(&config)
+}
+
+fn set_disabled(config: &mut Value, name: &str, disabled: bool) -> Result<(), &'static str> {
     let key = if config.get("servers").and_then(|v| v.as_object()).is_some() { "servers" } else { "mcpServers" };
-    let servers = match config.get_mut(key).and_then(|v| v.as_object_mut()) {
-        Some(s) => s,
-        None => return json_err(404, "No servers configured"),
-    };
-    let server = match servers.get_mut(name) {
-        Some(s) => s,
-        None => return json_err(404, "Server not found"),
+    let servers = config.get_mut(key).and_then(|v| v.as_object_mut()).ok_or("No servers configured")?;
+    let server = servers.get_mut(name).and_then(|s| s.as_object_mut()).ok_or("Server not found")?;
+    if disabled {
+        server.insert("disabled".to_string(), json!(true));
+    } else {
+        server.remove("disabled");
+    }
+    Ok(())
+}
+
+/// Restart (`reload`) or stop one running server, draining in-flight calls
+/// first, or `ping` it. Needs the Bearer token.
+async fn handle_server_lifecycle(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>, name: &str, action: &str) -> Vec<u8> {
+    let (Some(proxy), Some(sse)) = (proxy, sse) else {
+        return json_err(503, "Proxy not running");
     };
-    if let Some(obj) = server.as_object_mut() {
-        if disabled {
-            obj.insert("disabled".to_string(), json!(true));
-        } else {
-            obj.remove("disabled");
-        }
+    if let Err(resp) = check_token(req, Some(&sse)) {
+        return resp;
     }
-    if save_config(&config) {
-        json_ok(json!({"ok": true, "disabled": disabled}))
-    } else {
-        json_err(500, "Failed to save config")
+    let result = match action {
+        "reload" => proxy.reload_server(name).await.map(|tools| json!({"ok": true, "tools": tools})),
+        "ping" => proxy.ping_server(name).await.map(|took| json!({"ok": true, "latencyMs": took.as_millis() as u64})),
+        _ => proxy.stop_server(name).await.map(|_| json!({"ok": true})),
+    };
+    match result {
+        Ok(body) => json_ok(body),
+        Err(e) => json_err(500, &e),
     }
 }
 
@@ -389,18 +538,316 @@ fn handle_get_settings() -> Vec<u8> {
     json_ok(settings)
 }
 
+/// Readiness: 503 if any running server's instance group has failed.
+async fn handle_readyz(proxy: Option<Arc<ProxyServer>>) -> Vec<u8> {
+    let proxy = match proxy {
+        Some(p) => p,
+        None => return json_err(503, "Proxy not running"),
+    };
+    let health = proxy.group_health().await;
+    let ready = health.values().all(|h| h.state != GroupState::Failed);
+    probe_response(ready, json!({"ready": ready, "servers": health}))
+}
+
+/// 200 with `body` if the probe passes, else 503 with it.
+fn probe_response(ok: bool, body: Value) -> Vec<u8> {
+    if ok {
+        json_ok(body)
+    } else {
+        http_response(503, "Service Unavailable", "application/json", &body.to_string())
+    }
+}
+
+/// Why each configured server can't serve tools: its cache entry records
+/// an error, or all instances of its group are down. Servers not listed are
+/// healthy.
+fn unhealthy_servers<'a>(
+    servers: impl Iterator<Item = &'a String>,
+    errors: &std::collections::HashMap<String, String>,
+    health: &std::collections::HashMap<String, GroupHealth>,
+) -> (usize, serde_json::Map<String, Value>) {
+    let mut total = 0;
+    let mut unhealthy = serde_json::Map::new();
+    for name in servers {
+        total += 1;
+        if let Some(error) = errors.get(name) {
+            unhealthy.insert(name.clone(), json!(error));
+        } else if health.get(name).is_some_and(|h| h.state == GroupState::Failed) {
+            unhealthy.insert(name.clone(), json!("no instance up"));
+        }
+    }
+    (total, unhealthy)
+}
+
+/// 200 while at least one upstream server is healthy, else 503 listing the
+/// unhealthy ones. For load balancers. Their errors can carry paths and
+/// upstream messages, so only a request with the token gets them; the
+/// rest see the names.
+async fn handle_health(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<&SseManager>) -> Vec<u8> {
+    let proxy = match proxy {
+        Some(p) => p,
+        None => return json_err(503, "Proxy not running"),
+    };
+    let errors = proxy.read_cache().map(|c| c.errors).unwrap_or_default();
+    let health = proxy.group_health().await;
+    let config = proxy.config().await;
+    let (total, unhealthy) = unhealthy_servers(config.servers.keys(), &errors, &health);
+    let healthy = total > unhealthy.len();
+    let unhealthy = match check_token(req, sse) {
+        Ok(()) => Value::Object(unhealthy),
+        Err(_) => json!(unhealthy.keys().collect::<Vec<_>>()),
+    };
+    probe_response(healthy, json!({"healthy": healthy, "unhealthy": unhealthy}))
+}
+
+/// 200 once startup discovery has finished, 503 before.
+fn handle_ready(proxy: Option<Arc<ProxyServer>>) -> Vec<u8> {
+    match proxy {
+        Some(p) => probe_response(p.discovered(), json!({"ready": p.discovered()})),
+        None => json_err(503, "Proxy not running"),
+    }
+}
+
+/// Version and binary of this process, for `McpHub upgrade-check`.
+fn handle_info() -> Vec<u8> {
+    json_ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "pid": std::process::id(),
+        "binary": binary_path().display().to_string(),
+    }))
+}
+
+/// GET `path` from a running McpHub and parse the JSON body, whatever the
+/// status. None if nothing answers at `addr`.
+pub fn daemon_get(addr: SocketAddr, path: &str) -> Option<Value> {
+    let (_, body) = daemon_request(addr, path, None)?;
+    serde_json::from_str(&body).ok()
+}
+
+/// GET `path` from whatever listens at `addr`, with `token` as the Bearer
+/// token if given. The status code and body, or None if nothing answers.
+pub fn daemon_request(addr: SocketAddr, path: &str, token: Option<&str>) -> Option<(u16, String)> {
+    use std::io::{Read, Write};
+    let addr = crate::config::client_addr(addr);
+    let mut stream = std::net::TcpStream::connect_timeout(&addr, std::time::Duration::from_millis(500)).ok()?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(2))).ok()?;
+    let auth = token.map(|t| format!("Authorization: Bearer {}\r\n", t)).unwrap_or_default();
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n", path, addr, auth);
+    stream.write_all(request.as_bytes()).ok()?;
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).ok()?;
+    let (head, body) = raw.split_once("\r\n\r\n")?;
+    let status = head.split_whitespace().nth(1)?.parse().ok()?;
+    Some((status, body.to_string()))
+}
+
 async fn handle_get_metrics(proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>) -> Vec<u8> {
     if let Some(p) = proxy {
         let mut m = p.metrics.lock().await;
-        if let Some(s) = sse {
+        if let Some(s) = &sse {
             m.active_sse_sessions = s.session_count().await;
+            m.max_sse_sessions = s.max_sessions();
         }
-        json_ok(json!(*m))
+        let mut body = json!(*m);
+        body["breakers"] = json!(p.breaker_states().await);
+        body["result_cache"] = json!(p.tool_cache_stats().await);
+        if let Some(s) = sse {
+            body["sessions"] = json!(s.stats().await);
+        }
+        json_ok(body)
     } else {
         json_err(503, "Metrics not available in dashboard-only mode")
     }
 }
 
+/// Most matches `/api/tools/search` returns without a `limit`.
+const TOOL_SEARCH_LIMIT: usize = 20;
+
+/// GET /api/tools/search?q=...: cached tools ranked by a fuzzy match of
+/// `q` against their names and descriptions, optionally on one `server`
+/// and capped at `limit`.
+fn handle_search_tools(path: &str, cache: Option<crate::cache::SchemaCache>) -> Vec<u8> {
+    let query = crate::http::query_param(path, "q").unwrap_or_default();
+    if query.trim().is_empty() {
+        return json_err(400, "Missing query: ?q=...");
+    }
+    let limit = match crate::http::query_param(path, "limit").map(|l| l.parse::<usize>()) {
+        None => TOOL_SEARCH_LIMIT,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return json_err(400, "limit must be a non-negative integer"),
+    };
+    let Some(cache) = cache else {
+        return json_err(404, "No schema cache. Run 'McpHub generate' first.");
+    };
+    let server = crate::http::query_param(path, "server");
+    let matches: Vec<Value> = rank_tools(&cache.servers, &query, server.as_deref(), limit)
+        .into_iter()
+        .map(|(score, server, tool)| json!({ "server": server, "score": (score * 100.0).round() / 100.0, "tool": tool }))
+        .collect();
+    json_ok(json!({ "query": query, "matches": matches }))
+}
+
+/// Tools matching `query` by `fuzzy_score`, best first (ties by server and
+/// name), at most `limit` of them.
+fn rank_tools<'a>(
+    servers: &'a std::collections::HashMap<String, Vec<crate::protocol::ToolDef>>,
+    query: &str,
+    only: Option<&str>,
+    limit: usize,
+) -> Vec<(f64, &'a str, &'a crate::protocol::ToolDef)> {
+    let mut ranked: Vec<(f64, &str, &crate::protocol::ToolDef)> = servers
+        .iter()
+        .filter(|(server, _)| only.is_none_or(|o| o == server.as_str()))
+        .flat_map(|(server, tools)| tools.iter().map(move |tool| (server.as_str(), tool)))
+        .map(|(server, tool)| (crate::search::fuzzy_score(query, &tool.name, &tool.description), server, tool))
+        .filter(|(score, _, _)| *score > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| (a.1, &a.2.name).cmp(&(b.1, &b.2.name))));
+    ranked.truncate(limit);
+    ranked
+}
+
+/// Session, throughput and per-tool counters for scraping: JSON, or
+/// Prometheus text (plus server up gauges) with `?format=prometheus` or
+/// `Accept: text/plain`.
+/// Needs the Bearer token.
+async fn handle_sse_metrics(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>) -> Vec<u8> {
+    let Some(sse) = sse else {
+        return json_err(503, "Metrics not available in dashboard-only mode");
+    };
+    if !sse.is_authorized(req.headers.get("authorization").map(|a| a.as_str())) {
+        return crate::sse::unauthorized();
+    }
+    let metrics = sse.metrics().await;
+    let prometheus = crate::http::query_param(&req.path, "format").as_deref() == Some("prometheus")
+        || req.headers.get("accept").is_some_and(|a| a.contains("text/plain"));
+    let tools = match &proxy {
+        Some(p) => p.metrics.lock().await.tools.clone(),
+        None => Default::default(),
+    };
+    if prometheus {
+        let servers = match &proxy {
+            Some(p) => {
+                let names: Vec<String> = p.config().await.servers.into_keys().collect();
+                crate::proxy::servers_prometheus(&names, &p.group_health().await, &p.restart_stats(), &p.breaker_states().await)
+            }
+            None => String::new(),
+        };
+        let body = metrics.to_prometheus() + &crate::proxy::tools_prometheus(&tools) + &servers;
+        http_response(200, "OK", "text/plain; version=0.0.4", &body)
+    } else {
+        let mut body = json!(metrics);
+        body["tools"] = json!(tools);
+        if let Some(p) = &proxy {
+            let restarts = p.restart_stats();
+            body["server_restarts_total"] = json!(restarts.values().map(|r| r.restarts).sum::<u64>());
+            body["server_restarts"] = json!(restarts);
+        }
+        body["tool_latency_bucket_bounds_ms"] = json!(crate::proxy::LATENCY_BUCKETS_MS);
+        json_ok(body)
+    }
+}
+
+/// Snapshot behind the `/dashboard` status page: configured servers with
+/// their live health and cached error, open sessions, and per-tool calls.
+/// Needs the Bearer token.
+async fn handle_status(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>) -> Vec<u8> {
+    let (Some(proxy), Some(sse)) = (proxy, sse) else {
+        return json_err(503, "Status not available in dashboard-only mode");
+    };
+    if !sse.is_authorized(req.headers.get("authorization").map(|a| a.as_str())) {
+        return crate::sse::unauthorized();
+    }
+    let mut names: Vec<String> = proxy.config().await.servers.keys().cloned().collect();
+    names.sort();
+    let tools = proxy.metrics.lock().await.tools.clone();
+    json_ok(status_body(
+        &names,
+        proxy.read_cache().and_then(|cache| serde_json::to_value(cache).ok()).as_ref(),
+        &proxy.group_health().await,
+        &proxy.breaker_states().await,
+        json!(sse.sessions().await),
+        &tools,
+    ))
+}
+
+fn status_body(
+    names: &[String],
+    cache: Option<&Value>,
+    health: &std::collections::HashMap<String, GroupHealth>,
+    breakers: &std::collections::HashMap<String, BreakerStatus>,
+    sessions: Value,
+    tools: &std::collections::HashMap<String, std::collections::HashMap<String, crate::proxy::ToolMetrics>>,
+) -> Value {
+    let cached = |key: &str, name: &str| cache.and_then(|c| c.get(key)).and_then(|m| m.get(name)).cloned();
+    let servers: Vec<Value> = names
+        .iter()
+        .map(|name| {
+            let tool_count = cached("servers", name).and_then(|t| t.as_array().map(|a| a.len()));
+            let error = cached("errors", name).and_then(|e| e.as_str().map(String::from));
+            let health = health.get(name);
+            let breaker = breakers.get(name);
+            let tripped = breaker.is_some_and(|b| b.state != crate::breaker::BreakerState::Closed);
+            // Live state first; a stopped server is judged by its cache entry
+            let state = match (health.map(|h| h.state), tripped, &error, tool_count) {
+                (_, true, _, _) => "circuit open",
+                (Some(GroupState::Healthy), ..) => "healthy",
+                (Some(GroupState::Degraded), ..) => "degraded",
+                (Some(GroupState::Failed), ..) => "failed",
+                (None, _, Some(_), _) => "error",
+                (None, _, None, Some(_)) => "stopped",
+                (None, _, None, None) => "unknown",
+            };
+            json!({
+                "name": name,
+                "state": state,
+                "health": health,
+                "breaker": breaker,
+                "tools": tool_count,
+                "error": error,
+            })
+        })
+        .collect();
+    let mut tool_rows: Vec<Value> = tools
+        .iter()
+        .flat_map(|(server, tools)| {
+            tools.iter().map(move |(tool, m)| {
+                json!({
+                    "server": server,
+                    "tool": tool,
+                    "calls": m.call_count,
+                    "errors": m.error_count,
+                    "p50_ms": m.p50_ms,
+                    "p95_ms": m.p95_ms,
+                })
+            })
+        })
+        .collect();
+    tool_rows.sort_by(|a, b| b["calls"].as_u64().cmp(&a["calls"].as_u64()));
+    json!({ "servers": servers, "sessions": sessions, "tools": tool_rows })
+}
+
+/// Drop cached tool results: all of them, or those of the body's
+/// `{"tool": "server__tool"}` (or a server name). Needs the Bearer token.
+async fn handle_invalidate_tool_results(req: &HttpRequest, proxy: Option<Arc<ProxyServer>>, sse: Option<Arc<SseManager>>) -> Vec<u8> {
+    let (Some(proxy), Some(sse)) = (proxy, sse) else {
+        return json_err(503, "Proxy not running");
+    };
+    if !sse.is_authorized(req.headers.get("authorization").map(|a| a.as_str())) {
+        return crate::sse::unauthorized();
+    }
+    let tool = if req.body.trim().is_empty() {
+        None
+    } else {
+        match serde_json::from_str::<Value>(&req.body) {
+            Ok(v) => v.get("tool").and_then(|t| t.as_str()).map(String::from),
+            Err(_) => return json_err(400, "Invalid JSON"),
+        }
+    };
+    let removed = proxy.invalidate_tool_results(tool.as_deref()).await;
+    json_ok(json!({"ok": true, "invalidated": removed}))
+}
+
 fn handle_update_settings(body: &str) -> Vec<u8> {
     let data: Value = match serde_json::from_str(body) {
         Ok(v) => v,
@@ -416,10 +863,9 @@ fn handle_update_settings(body: &str) -> Vec<u8> {
     } else {
         config["settings"] = data;
     }
-    if save_config(&config) {
-        json_ok(json!({"ok": true, "settings": config["settings"]}))
-    } else {
-        json_err(500, "Failed to save config")
+    match save_config(&config) {
+        Ok(()) => json_ok(json!({"ok": true, "settings": config["settings"]})),
+        Err(resp) => resp,
     }
 }
 
@@ -499,6 +945,15 @@ async fn handle_repair_server(name: &str) -> Vec<u8> {
     }
 
     let srv = &servers[name];
+    if let Some(url) = srv.get("url").and_then(|v| v.as_str()) {
+        return json_ok(json!({
+            "ok": false,
+            "step": "remote",
+            "error": format!("'{}' is a remote server ({}); repair only checks local commands", name, url),
+            "suggestion": "Check that the url is reachable and the headers are right",
+            "auto_fixable": false
+        }));
+    }
     let command = srv.get("command").and_then(|v| v.as_str()).unwrap_or("");
     let args: Vec<String> = srv.get("args")
         .and_then(|v| v.as_array())
@@ -554,12 +1009,22 @@ async fn handle_repair_server(name: &str) -> Vec<u8> {
         cmd.arg(arg);
     }
     
-    // Add env vars
-    if let Some(env_obj) = srv.get("env").and_then(|v| v.as_object()) {
-        for (k, v) in env_obj {
-            if let Some(val) = v.as_str() {
-                cmd.env(k, val);
-            }
+    // Add env vars, with ${VAR} / ${secret:NAME} references resolved
+    let env: std::collections::HashMap<String, String> = srv.get("env").and_then(|v| v.as_object())
+        .map(|env| env.iter().filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string()))).collect())
+        .unwrap_or_default();
+    match crate::config::resolve_env(&env) {
+        Ok(env) => {
+            cmd.envs(env);
+        }
+        Err(e) => {
+            return json_ok(json!({
+                "ok": false,
+                "step": "env",
+                "error": e,
+                "suggestion": "Set the variable, or add the secret to ~/.McpHub/secrets.json",
+                "auto_fixable": false
+            }));
         }
     }
     
@@ -755,29 +1220,82 @@ async fn route(
 ) -> Vec<u8> {
     let path = req.path.split('?').next().unwrap_or(&req.path);
 
-    if req.method == "OPTIONS" {
-        return http_response(204, "No Content", "text/plain", "");
-    }
-
     match (&req.method[..], path) {
         ("GET", "/") => http_response(200, "OK", "text/html; charset=utf-8", DASHBOARD_HTML),
-        ("GET", "/api/servers") => handle_get_servers(),
+        // The page holds no data; it asks for the token and polls /api/status
+        ("GET", "/dashboard") => http_response(200, "OK", "text/html; charset=utf-8", STATUS_HTML),
+        ("GET", "/api/status") => handle_status(req, proxy, sse).await,
+        // Full tool definitions: token required, like the rest of the API
+        ("GET", "/api/servers") => {
+            if let Err(resp) = check_token(req, sse.as_deref()) {
+                return resp;
+            }
+            let (health, breakers, restarts) = match &proxy {
+                Some(p) => (p.group_health().await, p.breaker_states().await, p.restart_stats()),
+                None => Default::default(),
+            };
+            handle_get_servers(read_cache(proxy.as_deref()), &health, &breakers, &restarts, crate::http::query_param(&req.path, "server").as_deref())
+        }
+        ("GET", "/api/tools/search") => match check_token(req, sse.as_deref()) {
+            Err(resp) => resp,
+            Ok(()) => handle_search_tools(&req.path, read_cache(proxy.as_deref())),
+        },
+        ("GET", "/readyz") => handle_readyz(proxy).await,
+        ("GET", "/health") => handle_health(req, proxy, sse.as_deref()).await,
+        ("GET", "/ready") => handle_ready(proxy),
+        ("GET", "/info") => handle_info(),
         ("POST", "/api/servers") => handle_add_server(&req.body),
         ("GET", "/api/settings") => handle_get_settings(),
         ("GET", "/api/metrics") => handle_get_metrics(proxy, sse).await,
+        ("GET", "/metrics") => handle_sse_metrics(req, proxy, sse).await,
+        ("GET", "/api/sessions") => match (check_token(req, sse.as_deref()), sse) {
+            (Err(resp), _) => resp,
+            (Ok(()), Some(s)) => json_ok(json!({ "sessions": s.sessions().await })),
+            (Ok(()), None) => json_err(503, "SSE not available in dashboard-only mode"),
+        },
         ("PUT", "/api/settings") => handle_update_settings(&req.body),
         ("POST", "/api/generate") => handle_generate().await,
+        ("POST", "/cache/tools/invalidate") => handle_invalidate_tool_results(req, proxy, sse).await,
         _ => {
-            if path.starts_with("/api/servers/") {
-                let rest = &path["/api/servers/".len()..];
-                if rest.ends_with("/toggle") {
-                    let name = &rest[..rest.len() - "/toggle".len()];
-                    let decoded = urldecode(name);
-                    handle_toggle_server(&decoded, &req.body)
-                } else if rest.ends_with("/repair") {
-                    let name = &rest[..rest.len() - "/repair".len()];
+            if let Some(rest) = path.strip_prefix("/api/sessions/") {
+                let pin = match (&req.method[..], rest.rsplit_once('/')) {
+                    ("POST", Some((id, "pin"))) => Some((id, true)),
+                    ("POST", Some((id, "unpin"))) => Some((id, false)),
+                    _ => None,
+                };
+                if let (Some(_), Err(resp)) = (pin, check_token(req, sse.as_deref())) {
+                    return resp;
+                }
+                match (pin, sse) {
+                    (Some((id, pinned)), Some(s)) => {
+                        if s.set_pinned(&urldecode(id), pinned).await {
+                            json_ok(json!({ "ok": true, "pinned": pinned }))
+                        } else {
+                            json_err(404, "Session not found")
+                        }
+                    }
+                    (Some(_), None) => json_err(503, "SSE not available in dashboard-only mode"),
+                    (None, _) => json_err(404, "Not found"),
+                }
+            } else if let Some(rest) = path.strip_prefix("/api/servers/") {
+                if let Some(name) = rest.strip_suffix("/toggle") {
+                    handle_toggle_server(req, proxy, sse, &urldecode(name)).await
+                } else if let (Some(name), "POST") = (rest.strip_suffix("/disable"), &req.method[..]) {
+                    handle_set_disabled(req, proxy, sse, &urldecode(name), true).await
+                } else if let (Some(name), "POST") = (rest.strip_suffix("/enable"), &req.method[..]) {
+                    handle_set_disabled(req, proxy, sse, &urldecode(name), false).await
+                } else if let Some(name) = rest.strip_suffix("/repair") {
                     let decoded = urldecode(name);
                     handle_repair_server(&decoded).await
+                } else if let Some(name) = rest.strip_suffix("/reload") {
+                    let decoded = urldecode(name);
+                    handle_server_lifecycle(req, proxy, sse, &decoded, "reload").await
+                } else if let Some(name) = rest.strip_suffix("/stop") {
+                    let decoded = urldecode(name);
+                    handle_server_lifecycle(req, proxy, sse, &decoded, "stop").await
+                } else if let Some(name) = rest.strip_suffix("/ping") {
+                    let decoded = urldecode(name);
+                    handle_server_lifecycle(req, proxy, sse, &decoded, "ping").await
                 } else {
                     let decoded = urldecode(rest);
                     match &req.method[..] {
@@ -794,176 +1312,363 @@ async fn route(
 }
 
 fn urldecode(s: &str) -> String {
-    let mut result = String::new();
-    let mut chars = s.chars();
-    while let Some(c) = chars.next() {
-        if c == '%' {
-            let hex: String = chars.by_ref().take(2).collect();
-            if let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                result.push(byte as char);
-            }
-        } else if c == '+' {
-            result.push(' ');
-        } else {
-            result.push(c);
-        }
-    }
-    result
+    crate::http::percent_decode(s)
 }
 
 // ─── Server Entry Point ─────────────────────────────────────
 
 /// Start dashboard only (no SSE, no proxy). For `McpHub dashboard` command.
-pub async fn start_dashboard() {
-    start_http(None, None, true).await;
+/// Returns only if the server can't start.
+pub async fn start_dashboard(bind_addr: SocketAddr) -> Result<(), String> {
+    start_http(None, None, None, None, true, bind_addr).await
+}
+
+/// Start full server: dashboard + SSE, Streamable HTTP and WebSocket transports. For `McpHub serve` and default mode.
+/// Returns only if the server can't start.
+pub async fn start_server(proxy: Arc<ProxyServer>, bind_addr: SocketAddr) -> Result<(), String> {
+    let config = proxy.config().await;
+    let sse = Arc::new(SseManager::new(&config).with_bind_addr(bind_addr));
+    let streamable = Arc::new(StreamableManager::new(&config).with_bind_addr(bind_addr));
+    let ws = Arc::new(WsManager::new(&config).with_bind_addr(bind_addr));
+    sse.start_router(proxy.clone());
+    let mut shutdown = proxy.subscribe_shutdown();
+    let closing = sse.clone();
+    tokio::spawn(async move {
+        if shutdown.wait_for(|started| *started).await.is_ok() {
+            closing.shutdown().await;
+        }
+    });
+    start_http(Some(proxy), Some(sse), Some(streamable), Some(ws), false, bind_addr).await
+}
+
+/// Longest wait between two bind attempts.
+const MAX_BIND_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Bind the listener, retrying while the port is still in use (e.g. held by
+/// the previous instance right after a restart). The delay doubles per
+/// attempt, capped at MAX_BIND_RETRY_DELAY. Other errors fail immediately.
+async fn bind_with_retry(addr: SocketAddr, retries: u32, delay: std::time::Duration) -> std::io::Result<TcpListener> {
+    let mut delay = delay;
+    let mut attempt = 0;
+    loop {
+        // A `[::]` bind is dual-stack; other IPv6 sockets stay IPv6-only
+        match bind_reuseaddr(addr, !addr.ip().is_unspecified()) {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "{} in use, retrying bind in {}ms ({}/{})",
+                    addr, delay.as_millis(), attempt, retries
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_BIND_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
 }
 
-/// Start full server: dashboard + SSE transport. For `McpHub serve` and default mode.
-pub async fn start_server(proxy: Arc<ProxyServer>) {
-    start_http(Some(proxy), Some(Arc::new(SseManager::new())), false).await;
+/// Bind with SO_REUSEADDR so a socket of the previous instance lingering in
+/// TIME_WAIT doesn't block the port. An active listener still does. Not set
+/// on Windows, where it would let two processes share the port. `only_v6`
+/// (IPv6 only) leaves IPv4 to a separate listener on the same port.
+fn bind_reuseaddr(addr: SocketAddr, only_v6: bool) -> std::io::Result<TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(only_v6)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
 }
 
 async fn start_http(
     proxy: Option<Arc<ProxyServer>>,
     sse: Option<Arc<SseManager>>,
+    streamable: Option<Arc<StreamableManager>>,
+    ws: Option<Arc<WsManager>>,
     open_browser: bool,
-) {
-    let addr = "127.0.0.1:24680";
-    let listener = match TcpListener::bind(addr).await {
+    addr: SocketAddr,
+) -> Result<(), String> {
+    // Resolve the auth token up front so a missing token is reported (or
+    // generated) at startup rather than on the first client request.
+    if let Some(p) = &proxy {
+        let config = p.config().await;
+        if config.auth_required {
+            if let Err(e) = load_auth_token(config.auth_auto_generate_token, addr) {
+                return Err(format!(
+                    "{}. Refusing to serve without an auth token. Create the file or enable autoGenerateToken.",
+                    e
+                ));
+            }
+        }
+    }
+
+    let config = match &proxy {
+        Some(p) => p.config().await,
+        None => crate::config::ProxyConfig::default(),
+    };
+    let retry_delay = std::time::Duration::from_millis(config.bind_retry_delay_ms);
+    let allowed_origins: Arc<[String]> = config.cors_allowed_origins.clone().into();
+    let listener = match bind_with_retry(addr, config.bind_retries, retry_delay).await {
         Ok(l) => l,
-        Err(e) => {
-            eprintln!("[McpHub] Failed to bind {}: {}", addr, e);
-            eprintln!("[McpHub] Is another instance running?");
-            return;
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            return Err(format!(
+                "Failed to bind {}: {}. Is another instance running? Pick another address with --bind or MCPHUB_BIND.",
+                addr, e
+            ));
         }
+        Err(e) => return Err(format!("Failed to bind {}: {}", addr, e)),
     };
+    // The other address family, best effort: IPv6 may be disabled
+    let companion = crate::config::companion_addr(addr).and_then(|other| match bind_reuseaddr(other, true) {
+        Ok(l) => Some(l),
+        Err(e) => {
+            tracing::warn!("Not listening on {} as well: {}", other, e);
+            None
+        }
+    });
 
+    let url = crate::config::client_base_url(addr);
     if proxy.is_some() {
-        eprintln!("[McpHub][HTTP] Server ready on http://{}", addr);
-        eprintln!("[McpHub][SSE]  Cursor endpoint: http://{}/sse", addr);
+        match companion.as_ref().and_then(|l| l.local_addr().ok()) {
+            Some(other) => tracing::info!(tag = "HTTP", "Server ready on http://{} and http://{}", addr, other),
+            None => tracing::info!(tag = "HTTP", "Server ready on http://{}", addr),
+        }
+        tracing::info!(tag = "SSE", " Cursor endpoint: {}/sse", url);
+        tracing::info!(tag = "HTTP", "Streamable HTTP endpoint: {}/mcp", url);
+        tracing::info!(tag = "WS", "  WebSocket endpoint: {}/ws", url.replacen("http", "ws", 1));
     } else {
-        eprintln!("[dashboard] Running on http://{}", addr);
+        eprintln!("[dashboard] Running on {}", url);
     }
 
     if open_browser {
         #[cfg(target_os = "macos")]
         let _ = std::process::Command::new("open")
-            .arg(format!("http://{}", addr))
+            .arg(&url)
             .spawn();
         #[cfg(target_os = "linux")]
         let _ = std::process::Command::new("xdg-open")
-            .arg(format!("http://{}", addr))
+            .arg(&url)
             .spawn();
         #[cfg(target_os = "windows")]
         let _ = std::process::Command::new("cmd")
-            .args(["/c", "start", &format!("http://{}", addr)])
+            .args(["/c", "start", &url])
             .spawn();
     }
 
+    let handlers = Handlers {
+        proxy,
+        sse,
+        streamable,
+        ws,
+        allowed_origins,
+        max_headers: config.http_max_headers,
+        max_body_bytes: config.sse_max_body_bytes,
+        gzip_threshold: config.http_compression.then_some(config.http_compression_threshold),
+    };
+    if let Some(companion) = companion {
+        tokio::spawn(accept_loop(companion, handlers.clone()));
+    }
+    accept_loop(listener, handlers).await;
+    Ok(())
+}
+
+/// Everything a connection is served with.
+#[derive(Clone)]
+struct Handlers {
+    proxy: Option<Arc<ProxyServer>>,
+    sse: Option<Arc<SseManager>>,
+    streamable: Option<Arc<StreamableManager>>,
+    ws: Option<Arc<WsManager>>,
+    allowed_origins: Arc<[String]>,
+    max_headers: usize,
+    max_body_bytes: usize,
+    /// Size from which response bodies are gzipped; None when compression is off.
+    gzip_threshold: Option<usize>,
+}
+
+async fn accept_loop(listener: TcpListener, handlers: Handlers) {
     loop {
-        let (stream, _) = match listener.accept().await {
+        let (stream, peer) = match listener.accept().await {
             Ok(conn) => conn,
             Err(_) => continue,
         };
-
-        let proxy_clone = proxy.clone();
-        let sse_clone = sse.clone();
-
+        let h = handlers.clone();
         tokio::spawn(async move {
-            handle_connection(stream, proxy_clone, sse_clone).await;
+            handle_connection(stream, peer, h).await;
         });
     }
 }
 
-async fn handle_connection(
-    mut stream: tokio::net::TcpStream,
-    proxy: Option<Arc<ProxyServer>>,
-    sse: Option<Arc<SseManager>>,
-) {
-    // Add CORS OPTIONS handler
-    let mut buf = vec![0u8; 65536];
-    let mut total_read = match tokio::time::timeout(
-        std::time::Duration::from_secs(10),
-        stream.read(&mut buf),
-    ).await {
-        Ok(Ok(n)) if n > 0 => n,
-        _ => return, // Timeout or read error: drop connection
-    };
+/// Methods some route answers, for `Allow` on OPTIONS.
+const ALLOWED_METHODS: &str = "GET, HEAD, POST, PUT, DELETE, OPTIONS";
 
-    // Check if it's an OPTIONS request early
-    if total_read >= 7 && &buf[..7] == b"OPTIONS" {
-        let resp = b"HTTP/1.1 204 No Content\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type, Authorization\r\nAccess-Control-Max-Age: 86400\r\nContent-Length: 0\r\n\r\n";
-        let _ = stream.write_all(resp).await;
-        return;
+fn event_stream_headers(cors: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/event-stream\r\n\
+         Cache-Control: no-cache\r\n\
+         Connection: keep-alive\r\n\
+         {}\r\n",
+        cors
+    )
+}
+
+/// `response` without its body, for HEAD. Content-Length still gives the
+/// size GET would send.
+fn head_only(mut response: Vec<u8>) -> Vec<u8> {
+    if let Some(end) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+        response.truncate(end + 4);
     }
+    response
+}
 
-    // Find end of headers
-    let mut body_offset = 0;
-    for i in 0..total_read.saturating_sub(3) {
-        if &buf[i..i+4] == b"\r\n\r\n" {
-            body_offset = i + 4;
-            break;
-        } else if i < total_read.saturating_sub(1) && &buf[i..i+2] == b"\n\n" {
-            body_offset = i + 2;
-            break;
-        }
+/// The file `/logs/stream` tails: the one this process logs to (`serve
+/// --log-path`), or 404 when it logs to stdout or stderr.
+fn log_file() -> Result<PathBuf, Vec<u8>> {
+    match crate::logging::target() {
+        crate::logging::Target::File(path) => Ok(path),
+        crate::logging::Target::Stdout => Err(json_err(404, "Not logging to a file: this hub logs to stdout")),
+        crate::logging::Target::Stderr => Err(json_err(404, "Not logging to a file: this hub logs to stderr (start it with --log-path)")),
     }
+}
 
-    if body_offset > 0 {
-        // Find Content-Length
-        let headers_str = String::from_utf8_lossy(&buf[..body_offset]);
-        let mut content_length: usize = 0;
-        for line in headers_str.lines() {
-            let lower = line.to_lowercase();
-            if let Some(val) = lower.strip_prefix("content-length:") {
-                content_length = val.trim().parse().unwrap_or(0);
-                break;
-            }
+/// HEAD: the headers GET would answer with. Streams (SSE, the log tail)
+/// answer with their headers once the token checks out, and the
+/// WebSocket and Streamable HTTP endpoints with the error a GET that
+/// can't be served gets.
+async fn head_response(
+    req: &HttpRequest,
+    path: &str,
+    proxy: Option<Arc<ProxyServer>>,
+    sse: Option<Arc<SseManager>>,
+    cors: &str,
+) -> Vec<u8> {
+    let auth = req.headers.get("authorization").map(|s| s.as_str());
+    let response = match path {
+        "/logs/stream" => match (&sse, log_file()) {
+            (Some(sse_mgr), _) if !sse_mgr.is_authorized(auth) => crate::sse::unauthorized(),
+            (Some(_), Ok(_)) => event_stream_headers(cors).into_bytes(),
+            (Some(_), Err(resp)) => resp,
+            (None, _) => json_err(503, "SSE not available in dashboard-only mode"),
+        },
+        "/sse" => match &sse {
+            Some(sse_mgr) if sse_mgr.is_authorized(auth) => event_stream_headers(cors).into_bytes(),
+            Some(_) => crate::sse::unauthorized(),
+            None => json_err(503, "SSE not available in dashboard-only mode"),
+        },
+        "/ws" => json_err(400, "Expected a WebSocket upgrade"),
+        "/mcp" => with_headers(json_err(405, "Use POST or DELETE"), "Allow: POST, DELETE\r\n"),
+        _ => {
+            let get = HttpRequest { method: "GET".to_string(), path: req.path.clone(), headers: req.headers.clone(), body: String::new() };
+            route(&get, proxy, sse).await
         }
+    };
+    head_only(with_headers(response, cors))
+}
 
-        // Read the rest of the body if needed
-        let target_size = body_offset + content_length;
-        if target_size > buf.len() {
-            buf.resize(target_size, 0);
-        }
-        while total_read < target_size {
-            let n = match tokio::time::timeout(
-                std::time::Duration::from_secs(10),
-                stream.read(&mut buf[total_read..]),
-            ).await {
-                Ok(Ok(n)) if n > 0 => n,
-                _ => return, // Timeout or read error
+async fn handle_connection(mut stream: tokio::net::TcpStream, peer: SocketAddr, handlers: Handlers) {
+    let Handlers { proxy, sse, streamable, ws, allowed_origins, max_headers, max_body_bytes, gzip_threshold } = handlers;
+    let req = match parse_request(&mut stream, std::time::Duration::from_secs(10), max_headers, max_body_bytes).await {
+        Ok(r) => r,
+        Err(e) => {
+            let response = match &e {
+                RequestError::TooLarge(message) => {
+                    http_response(413, "Payload Too Large", "application/json", &json!({"error": message}).to_string())
+                }
+                RequestError::Invalid(message) => json_err(400, &format!("Bad request: {}", message)),
             };
-            total_read += n;
+            // Best effort: the client may already be gone
+            let _ = stream.write_all(&response).await;
+            let _ = stream.shutdown().await;
+            return;
         }
-    }
-
-    let raw = String::from_utf8_lossy(&buf[..total_read]).to_string();
-
-    let req = match parse_request(&raw) {
-        Some(r) => r,
-        None => return,
     };
 
     let path = req.path.split('?').next().unwrap_or(&req.path).to_string();
+    let origin = req.headers.get("origin").map(|s| s.as_str());
 
-    let expected_auth = format!("Bearer {}", get_auth_token());
+    // CORS preflight: only permitted origins get Access-Control-Allow-*,
+    // so the browser blocks the actual request for everyone else.
+    if req.method == "OPTIONS" {
+        let resp = format!(
+            "HTTP/1.1 204 No Content\r\nAllow: {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+            ALLOWED_METHODS,
+            cors_headers(&allowed_origins, origin, true)
+        );
+        let _ = stream.write_all(resp.as_bytes()).await;
+        let _ = stream.shutdown().await;
+        return;
+    }
+    let cors = cors_headers(&allowed_origins, origin, false);
 
-    // SSE endpoint: long-lived connection, don't close
-    if path == "/sse" && req.method == "GET" {
-        let auth = req.headers.get("authorization").map(|s| s.as_str()).unwrap_or("");
-        if auth != expected_auth {
-            let resp = json_err(401, "Unauthorized");
-            let _ = stream.write_all(&resp).await;
+    if req.method == "HEAD" {
+        let _ = stream.write_all(&head_response(&req, &path, proxy, sse, &cors).await).await;
+        let _ = stream.shutdown().await;
+        return;
+    }
+
+    // WebSocket endpoint: the WS manager checks the upgrade, origin and token.
+    if path == "/ws" && req.method == "GET" {
+        if let (Some(proxy_ref), Some(ws_mgr)) = (&proxy, &ws) {
+            ws_mgr.handle_connect(stream, &req, proxy_ref.clone()).await;
+        } else {
+            let _ = stream.write_all(&json_err(503, "WebSocket not available in dashboard-only mode")).await;
             let _ = stream.shutdown().await;
-            return;
         }
+        return;
+    }
 
+    // Streamable HTTP endpoint: the manager checks origin and token, and
+    // writes (or streams) the response itself.
+    if path == "/mcp" {
+        if let (Some(proxy_ref), Some(streamable_mgr)) = (&proxy, &streamable) {
+            streamable_mgr.handle(stream, &req, proxy_ref.clone()).await;
+        } else {
+            let _ = stream.write_all(&with_headers(json_err(503, "Streamable HTTP not available in dashboard-only mode"), &cors)).await;
+            let _ = stream.shutdown().await;
+        }
+        return;
+    }
+
+    // SSE endpoint: long-lived connection, don't close.
+    // The SSE manager checks the Bearer token on both SSE endpoints.
+    if path == "/sse" && req.method == "GET" {
         if let Some(sse_mgr) = &sse {
-            sse_mgr.handle_connect(stream).await;
+            // A session limited to some servers; names are checked up front,
+            // once the token is (handle_connect turns away the rest)
+            let authorized = sse_mgr.is_authorized(req.headers.get("authorization").map(|a| a.as_str()));
+            let scope = match (crate::http::query_param(&req.path, "servers"), &proxy) {
+                (Some(servers), Some(proxy_ref)) if authorized => match proxy_ref.parse_scope(&servers).await {
+                    Ok(scope) => Some(scope),
+                    Err(e) => {
+                        let _ = stream.write_all(&with_headers(json_err(400, &e), &cors)).await;
+                        let _ = stream.shutdown().await;
+                        return;
+                    }
+                },
+                _ => None,
+            };
+            let info = ConnectInfo {
+                profile: crate::quirks::detect(&req.headers),
+                host: req.headers.get("host").cloned(),
+                client_id: req.headers.get("x-mcphub-client-id").cloned(),
+                authorization: req.headers.get("authorization").cloned(),
+                resume_session: extract_session_id(&req.path),
+                last_event_id: req.headers.get("last-event-id").and_then(|id| id.trim().parse().ok()),
+                origin: origin.map(String::from),
+                pinned: req.headers.get("x-mcphub-pin").is_some_and(|v| v.trim().eq_ignore_ascii_case("true")),
+                peer: Some(peer.ip()),
+                scope,
+            };
+            sse_mgr.handle_connect(stream, info).await;
             return; // Connection handled, don't close
         } else {
-            let resp = json_err(503, "SSE not available in dashboard-only mode");
+            let resp = with_headers(json_err(503, "SSE not available in dashboard-only mode"), &cors);
             let _ = stream.write_all(&resp).await;
             let _ = stream.shutdown().await;
             return;
@@ -972,81 +1677,294 @@ async fn handle_connection(
 
     // Message endpoint: process JSON-RPC via SSE session
     if path == "/message" && req.method == "POST" {
-        let auth = req.headers.get("authorization").map(|s| s.as_str()).unwrap_or("");
-        if auth != expected_auth {
-            let resp = json_err(401, "Unauthorized");
-            let _ = stream.write_all(&resp).await;
-            let _ = stream.shutdown().await;
-            return;
-        }
-
         let response = if let (Some(proxy_ref), Some(sse_mgr)) = (&proxy, &sse) {
-            if let Some(session_id) = extract_session_id(&req.path) {
-                sse_mgr.handle_message(&session_id, &req.body, proxy_ref).await
-            } else {
-                json_err(400, "Missing sessionId parameter")
+            let auth = req.headers.get("authorization").map(|s| s.as_str());
+            match extract_session_id(&req.path) {
+                Some(session_id) => sse_mgr.handle_message(&session_id, &req.body, auth, proxy_ref).await,
+                // Notifications can do without a session
+                None => sse_mgr.handle_sessionless(&req.body, auth, proxy_ref).await,
             }
         } else {
             json_err(503, "SSE not available in dashboard-only mode")
         };
-        let _ = stream.write_all(&response).await;
+        let _ = stream.write_all(&gzip_response(with_headers(response, &cors), &req, gzip_threshold)).await;
         let _ = stream.shutdown().await;
         return;
     }
 
-    if path == "/api/logs-stream" && req.method == "GET" {
-        let headers = "HTTP/1.1 200 OK\r\n\
-             Content-Type: text/event-stream\r\n\
-             Cache-Control: no-cache\r\n\
-             Connection: keep-alive\r\n\
-             Access-Control-Allow-Origin: *\r\n\
-             \r\n";
-        if stream.write_all(headers.as_bytes()).await.is_err() {
-            return;
-        }
-        
-        let log_path = config_dir().join("mcphub.log");
-        if !log_path.exists() {
-            let _ = stream.write_all(b"event: message\ndata: {\"error\": \"Log file not found\"}\n\n").await;
-            return;
-        }
-        
-        if let Ok(file) = std::fs::File::open(&log_path) {
-            use std::io::{BufRead, Seek, SeekFrom};
-            let mut reader = std::io::BufReader::new(file);
-            let mut pos = reader.seek(SeekFrom::End(0)).unwrap_or(0);
-            
-            loop {
-                let mut line = String::new();
-                match reader.read_line(&mut line) {
-                    Ok(0) => {
-                        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-                        if reader.seek(SeekFrom::Start(pos)).is_err() { break; }
+    // Authenticated tail of the file this process logs to; the SSE manager
+    // checks the token
+    if path == "/logs/stream" && req.method == "GET" {
+        match &sse {
+            Some(sse_mgr) => {
+                let backlog = crate::http::query_param(&req.path, "lines").and_then(|n| n.parse().ok()).unwrap_or(0);
+                let auth = req.headers.get("authorization").map(|s| s.as_str());
+                match log_file() {
+                    Ok(file) => sse_mgr.stream_log(stream, auth, origin, &file, backlog).await,
+                    Err(resp) => {
+                        let resp = if sse_mgr.is_authorized(auth) { resp } else { crate::sse::unauthorized() };
+                        let _ = stream.write_all(&with_headers(resp, &cors)).await;
+                        let _ = stream.shutdown().await;
                     }
-                    Ok(len) => {
-                        pos += len as u64;
-                        let line_trim = line.trim();
-                        if !line_trim.is_empty() {
-                            let json_msg = serde_json::json!({ "line": line_trim });
-                            let event = format!("event: message\ndata: {}\n\n", json_msg.to_string());
-                            if stream.write_all(event.as_bytes()).await.is_err() {
-                                break;
-                            }
-                        }
-                    }
-                    Err(_) => break,
                 }
             }
+            None => {
+                let _ = stream.write_all(&with_headers(json_err(503, "Log stream not available in dashboard-only mode"), &cors)).await;
+                let _ = stream.shutdown().await;
+            }
         }
         return;
     }
 
     // Normal dashboard routes
     let response = route(&req, proxy, sse).await;
-    let _ = stream.write_all(&response).await;
+    let _ = stream.write_all(&gzip_response(with_headers(response, &cors), &req, gzip_threshold)).await;
     let _ = stream.shutdown().await;
 }
 
 // ─── Embedded HTML ───────────────────────────────────────────
 
 const DASHBOARD_HTML: &str = include_str!("../static/dashboard.html");
+const STATUS_HTML: &str = include_str!("../static/status.html");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_token_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("auth-token")
+    }
+
+    #[test]
+    fn test_rank_tools_filters_and_caps() {
+        let tool = |name: &str, description: &str| crate::protocol::ToolDef {
+            name: name.into(),
+            description: description.into(),
+            input_schema: json!({}),
+            annotations: None,
+        };
+        let servers = std::collections::HashMap::from([
+            ("github".to_string(), vec![tool("search_code", "Search code"), tool("create_issue", "Open an issue")]),
+            ("files".to_string(), vec![tool("grep", "Search file contents"), tool("read_file", "Read a file")]),
+        ]);
+        let names = |ranked: Vec<(f64, &str, &crate::protocol::ToolDef)>| {
+            ranked.into_iter().map(|(_, server, tool)| format!("{}/{}", server, tool.name)).collect::<Vec<_>>()
+        };
+        assert_eq!(names(rank_tools(&servers, "search", None, 10)), ["github/search_code", "files/grep"]);
+        assert_eq!(names(rank_tools(&servers, "search", Some("files"), 10)), ["files/grep"]);
+        assert_eq!(names(rank_tools(&servers, "search", None, 1)), ["github/search_code"]);
+        assert!(rank_tools(&servers, "deploy", None, 10).is_empty());
+    }
+
+    #[test]
+    fn test_missing_token_is_generated_and_persisted() {
+        let path = temp_token_path("token-gen");
+        assert!(!path.exists());
+
+        let (token, created) = load_or_create_token(&path, true).unwrap();
+        assert!(created);
+        assert!(token.starts_with("mcphub_"));
+        assert_eq!(token.len(), "mcphub_".len() + 64);
+        assert_eq!(fs::read_to_string(&path).unwrap(), token);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // Second load reuses the persisted token
+        let (again, created) = load_or_create_token(&path, true).unwrap();
+        assert!(!created);
+        assert_eq!(again, token);
+
+        // Rotation replaces it in place
+        assert_ne!(write_new_token(&path).unwrap(), token);
+        assert!(!path.with_extension("tmp").exists());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_token_variable_precedence() {
+        let vars = |set: &'static [(&'static str, &'static str)]| {
+            move |var: &str| set.iter().find(|(k, _)| *k == var).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(token_from_vars(vars(&[])), None);
+        assert_eq!(token_from_vars(vars(&[("MCPHUB_TOKEN", " t2 ")])), Some(("MCPHUB_TOKEN", "t2".into())));
+        assert_eq!(
+            token_from_vars(vars(&[("MCPHUB_TOKEN", "t2"), ("MCPHUB_AUTH_TOKEN", "t1")])),
+            Some(("MCPHUB_AUTH_TOKEN", "t1".into()))
+        );
+        // An empty variable doesn't shadow the next one
+        assert_eq!(
+            token_from_vars(vars(&[("MCPHUB_AUTH_TOKEN", ""), ("MCPHUB_TOKEN", "t2")])),
+            Some(("MCPHUB_TOKEN", "t2".into()))
+        );
+    }
+
+    #[test]
+    fn test_status_reports_live_and_cached_server_state() {
+        let names: Vec<String> = ["db", "fs", "github", "new"].iter().map(|n| n.to_string()).collect();
+        let cache = json!({
+            "servers": { "fs": [{"name": "read"}, {"name": "write"}], "github": [{"name": "search"}] },
+            "errors": { "db": "spawn failed: no such file" },
+        });
+        let health = std::collections::HashMap::from([(
+            "github".to_string(),
+            GroupHealth { state: GroupState::Degraded, up: 1, total: 2 },
+        )]);
+        let body = status_body(&names, Some(&cache), &health, &Default::default(), json!([]), &Default::default());
+        let state = |i: usize| body["servers"][i]["state"].as_str().unwrap().to_string();
+        assert_eq!(state(0), "error");
+        assert_eq!(body["servers"][0]["error"], "spawn failed: no such file");
+        assert_eq!(state(1), "stopped");
+        assert_eq!(body["servers"][1]["tools"], 2);
+        assert_eq!(state(2), "degraded");
+        assert_eq!(state(3), "unknown");
+    }
+
+    #[test]
+    fn test_disable_flag_round_trips() {
+        let mut config = json!({"mcpServers": {"github": {"command": "gh-mcp"}}});
+        set_disabled(&mut config, "github", true).unwrap();
+        assert_eq!(config["mcpServers"]["github"]["disabled"], true);
+        set_disabled(&mut config, "github", false).unwrap();
+        assert_eq!(config["mcpServers"]["github"], json!({"command": "gh-mcp"}));
+        assert_eq!(set_disabled(&mut config, "nope", true), Err("Server not found"));
+    }
+
+    #[test]
+    fn test_missing_token_hard_fail() {
+        let path = temp_token_path("token-fail");
+        assert!(load_or_create_token(&path, false).is_err());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_bind_retries_until_port_is_released() {
+        let held = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = held.local_addr().unwrap();
+        let no_retry = bind_with_retry(addr, 0, std::time::Duration::from_millis(10)).await;
+        assert_eq!(no_retry.unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            drop(held);
+        });
+        let listener = bind_with_retry(addr, 10, std::time::Duration::from_millis(20)).await.unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+    }
+
+    #[test]
+    fn test_unhealthy_servers_from_cache_errors_and_failed_groups() {
+        let servers = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let errors = [("a".to_string(), "spawn failed".to_string()), ("gone".to_string(), "x".to_string())].into();
+        let health = [("b".to_string(), GroupHealth { state: GroupState::Failed, up: 0, total: 1 })].into();
+        let (total, unhealthy) = unhealthy_servers(servers.iter(), &errors, &health);
+        assert_eq!(total, 3);
+        assert_eq!(Value::Object(unhealthy), json!({"a": "spawn failed", "b": "no instance up"}));
+
+        let (total, unhealthy) = unhealthy_servers(servers[..2].iter(), &errors, &health);
+        assert_eq!((total, unhealthy.len()), (2, 2));
+    }
+
+    #[test]
+    fn test_cors_echoes_only_allowed_origins() {
+        let allowed = crate::config::ProxyConfig::default().cors_allowed_origins;
+        let headers = cors_headers(&allowed, Some("http://localhost:5173"), false);
+        assert!(headers.starts_with("Access-Control-Allow-Origin: http://localhost:5173\r\n"), "{}", headers);
+        assert!(!headers.contains("Allow-Methods"));
+        assert!(cors_headers(&allowed, Some("http://127.0.0.1"), true).contains("Allow-Methods"));
+
+        assert_eq!(cors_headers(&allowed, Some("https://evil.example"), true), "");
+        assert_eq!(cors_headers(&allowed, Some("http://localhost.evil.example"), false), "");
+        assert_eq!(cors_headers(&allowed, None, false), "");
+        assert_eq!(cors_headers(&[], Some("http://localhost"), false), "");
+
+        let resp = with_headers(json_ok(json!({})), &headers);
+        let text = String::from_utf8(resp).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\nAccess-Control-Allow-Origin: http://localhost:5173\r\n"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_head_answers_get_headers_without_body() {
+        let req = HttpRequest { method: "HEAD".into(), path: "/info".into(), headers: Default::default(), body: String::new() };
+        let text = String::from_utf8(head_response(&req, "/info", None, None, "").await).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\n"), "{}", text);
+        assert!(text.ends_with("\r\n\r\n"), "{}", text);
+        let length: usize = text.lines().find_map(|l| l.strip_prefix("Content-Length: ")).unwrap().parse().unwrap();
+        assert!(length > 0);
+
+        let text = String::from_utf8(head_response(&req, "/sse", None, None, "").await).unwrap();
+        assert!(text.starts_with("HTTP/1.1 503 "), "{}", text);
+        assert!(text.ends_with("\r\n\r\n"), "{}", text);
+
+        // The log is only streamed with the token, from /logs/stream
+        let config = crate::config::ProxyConfig { auth_required: true, ..Default::default() };
+        let sse = Arc::new(SseManager::new(&config));
+        let text = String::from_utf8(head_response(&req, "/logs/stream", None, Some(sse.clone()), "").await).unwrap();
+        assert!(text.starts_with("HTTP/1.1 401 "), "{}", text);
+        // Tests log to stderr: there is no file to tail
+        let open = Arc::new(SseManager::new(&crate::config::ProxyConfig { auth_required: false, ..Default::default() }));
+        let text = String::from_utf8(head_response(&req, "/logs/stream", None, Some(open), "").await).unwrap();
+        assert!(text.starts_with("HTTP/1.1 404 "), "{}", text);
+        let req = HttpRequest { method: "HEAD".into(), path: "/api/logs-stream".into(), headers: Default::default(), body: String::new() };
+        let text = String::from_utf8(head_response(&req, "/api/logs-stream", None, Some(sse), "").await).unwrap();
+        assert!(text.starts_with("HTTP/1.1 404 "), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_server_actions_need_the_token() {
+        let config = crate::config::ProxyConfig { auth_required: true, ..Default::default() };
+        let proxy = Arc::new(ProxyServer::new(config.clone()));
+        let sse = Arc::new(SseManager::new(&config));
+        for action in ["disable", "enable", "toggle", "reload", "stop", "ping"] {
+            let body = if action == "toggle" { r#"{"disabled": true}"# } else { "" };
+            let req = HttpRequest { method: "POST".into(), path: format!("/api/servers/github/{}", action), headers: Default::default(), body: body.into() };
+            let text = String::from_utf8(route(&req, Some(proxy.clone()), Some(sse.clone())).await).unwrap();
+            assert!(text.starts_with("HTTP/1.1 401 "), "{}: {}", action, text);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_lists_only_names_without_the_token() {
+        let config = crate::config::ProxyConfig { auth_required: true, ..Default::default() };
+        let proxy = Arc::new(ProxyServer::new(config.clone()));
+        let sse = SseManager::new(&config);
+        let req = HttpRequest { method: "GET".into(), path: "/health".into(), headers: Default::default(), body: String::new() };
+        let text = String::from_utf8(handle_health(&req, Some(proxy.clone()), Some(&sse)).await).unwrap();
+        assert!(text.starts_with("HTTP/1.1 503 "), "{}", text);
+        assert!(text.ends_with(r#"{"healthy":false,"unhealthy":[]}"#), "{}", text);
+
+        // Without the SSE manager there is no token to check against
+        let text = String::from_utf8(handle_health(&req, Some(proxy), None).await).unwrap();
+        assert!(text.ends_with(r#"{"healthy":false,"unhealthy":{}}"#), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_session_routes_need_the_token() {
+        let config = crate::config::ProxyConfig { auth_required: true, ..Default::default() };
+        let proxy = Arc::new(ProxyServer::new(config.clone()));
+        let sse = Arc::new(SseManager::new(&config));
+        for (method, path) in [("GET", "/api/sessions"), ("POST", "/api/sessions/s-1/pin"), ("POST", "/api/sessions/s-1/unpin")] {
+            let req = HttpRequest { method: method.into(), path: path.into(), headers: Default::default(), body: String::new() };
+            let text = String::from_utf8(route(&req, Some(proxy.clone()), Some(sse.clone())).await).unwrap();
+            assert!(text.starts_with("HTTP/1.1 401 "), "{} {}: {}", method, path, text);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_listings_need_the_token() {
+        let config = crate::config::ProxyConfig { auth_required: true, ..Default::default() };
+        let proxy = Arc::new(ProxyServer::new(config.clone()));
+        let sse = Arc::new(SseManager::new(&config));
+        for path in ["/api/servers", "/api/tools/search?q=issue"] {
+            let req = HttpRequest { method: "GET".into(), path: path.into(), headers: Default::default(), body: String::new() };
+            let text = String::from_utf8(route(&req, Some(proxy.clone()), Some(sse.clone())).await).unwrap();
+            assert!(text.starts_with("HTTP/1.1 401 "), "{}: {}", path, text);
+        }
+    }
+
+}
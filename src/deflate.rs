@@ -0,0 +1,444 @@
+//! Raw DEFLATE (RFC 1951) for WebSocket `permessage-deflate`, and its gzip
+//! wrapping (RFC 1952) for HTTP response bodies.
+//! Compression is LZ77 with fixed Huffman codes: far from zlib's best, but
+//! JSON results shrink several times over. Decompression handles every
+//! block type, so whatever a client's zlib sends can be read.
+
+/// Shortest and longest back-reference.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates tried per position before settling for the best so far.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order of the code length code lengths in a dynamic block header.
+const CLEN_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Compress `data` as one fixed-Huffman block followed by an empty stored
+/// block (a sync flush), so the output ends in `00 00 ff ff`. Back-references
+/// reach at most `2^window_bits` bytes.
+pub fn compress(data: &[u8], window_bits: u8) -> Vec<u8> {
+    let window = 1usize << window_bits.clamp(8, 15);
+    let mut out = BitWriter::default();
+    out.bits(0, 1); // not final
+    out.bits(1, 2); // fixed Huffman
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let hash = |i: usize| {
+        let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+    };
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(i)];
+            let max = MAX_MATCH.min(data.len() - i);
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || i - candidate > window {
+                    break;
+                }
+                let len = data[candidate..].iter().zip(&data[i..i + max]).take_while(|(a, b)| a == b).count();
+                if len > best_len {
+                    (best_len, best_dist) = (len, i - candidate);
+                    if len == max {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+            }
+        }
+        if best_len >= MIN_MATCH {
+            out.length(best_len);
+            out.distance(best_dist);
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            out.literal(data[i] as u16);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    out.literal(256); // end of block
+    out.bits(0, 3); // empty stored block, not final
+    let mut bytes = out.finish();
+    bytes.extend_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+    bytes
+}
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Write `count` bits of `value`, least significant first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.acc |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, most significant bit first.
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    /// Fixed-Huffman literal/length symbol.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn length(&mut self, len: usize) {
+        let idx = LENGTH_BASE.iter().rposition(|&base| base as usize <= len).expect("len >= 3");
+        self.literal(257 + idx as u16);
+        self.bits((len - LENGTH_BASE[idx] as usize) as u32, LENGTH_EXTRA[idx] as u32);
+    }
+
+    fn distance(&mut self, dist: usize) {
+        let idx = DIST_BASE.iter().rposition(|&base| base as usize <= dist).expect("dist >= 1");
+        self.code(idx as u32, 5);
+        self.bits((dist - DIST_BASE[idx] as usize) as u32, DIST_EXTRA[idx] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// `data` as a gzip member: `compress` with the full window, closed by an
+/// empty final block, then the CRC-32 and length of `data`.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate, no flags, no mtime, no extra flags, unknown OS
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend(compress(data, 15));
+    out.extend([0x03, 0x00]);
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+/// The data of a gzip member, such as `gzip` or the gzip tool writes:
+/// header fields are skipped, and the CRC-32 and length are checked.
+pub fn gunzip(data: &[u8], max_len: usize) -> Result<Vec<u8>, String> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 8] {
+        return Err("not gzip data".into());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or("truncated gzip header")?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            pos += data.get(pos..).and_then(|rest| rest.iter().position(|&b| b == 0)).ok_or("truncated gzip header")? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    let trailer = data.len() - 8;
+    let out = decompress(data.get(pos..trailer).ok_or("truncated gzip header")?, max_len)?;
+    let expected = |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    if crc32(&out) != expected(trailer) || out.len() as u32 != expected(trailer + 4) {
+        return Err("gzip checksum mismatch".into());
+    }
+    Ok(out)
+}
+
+/// CRC-32 (IEEE, reflected), bit by bit: bodies are small enough.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Decompress raw DEFLATE data, stopping after the final block or, for a
+/// sync-flushed stream without one, at the end of the input. Fails on
+/// malformed data or once the output would exceed `max_len`.
+pub fn decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>, String> {
+    let mut input = BitReader { data, pos: 0 };
+    let mut out = Vec::new();
+    loop {
+        if input.exhausted() {
+            return Ok(out);
+        }
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let len = input.bits(16)?;
+                if input.bits(16)? != !len & 0xFFFF {
+                    return Err("stored block length mismatch".into());
+                }
+                let start = input.pos / 8;
+                let block = data.get(start..start + len as usize).ok_or("truncated stored block")?;
+                if out.len() + block.len() > max_len {
+                    return Err(format!("decompressed data over {} bytes", max_len));
+                }
+                out.extend_from_slice(block);
+                input.pos += len as usize * 8;
+            }
+            1 => {
+                let mut lengths = [0u8; 288 + 30];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                lengths[288..].fill(5);
+                let (literals, distances) = (Huffman::new(&lengths[..288]), Huffman::new(&lengths[288..]));
+                inflate_block(&mut input, &mut out, &literals, &distances, max_len)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut input)?;
+                inflate_block(&mut input, &mut out, &literals, &distances, max_len)?;
+            }
+            _ => return Err("invalid block type".into()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    /// No complete block can start here: past the last byte, or in its
+    /// padding (a block needs at least 10 bits).
+    fn exhausted(&self) -> bool {
+        self.pos.div_ceil(8) >= self.data.len()
+    }
+
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = self.data.get(self.pos / 8).ok_or("unexpected end of compressed data")?;
+        let bit = (byte >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    /// `count` bits, least significant first.
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+/// Canonical Huffman decoding table: symbol counts per code length and the
+/// symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, input: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= input.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".into())
+    }
+}
+
+fn read_dynamic_codes(input: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let clen_count = input.bits(4)? as usize + 4;
+    let mut clen_lengths = [0u8; 19];
+    for &i in &CLEN_ORDER[..clen_count] {
+        clen_lengths[i] = input.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clen_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match clen.decode(input)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => (*lengths.last().ok_or("repeat with no previous length")?, input.bits(2)? + 3),
+            17 => (0, input.bits(3)? + 3),
+            _ => (0, input.bits(7)? + 11),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err("code lengths overrun".into());
+    }
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(
+    input: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    max_len: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(input)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let idx = symbol - 257;
+                let len = LENGTH_BASE[idx] as usize + input.bits(LENGTH_EXTRA[idx] as u32)? as usize;
+                let code = distances.decode(input)? as usize;
+                if code >= 30 {
+                    return Err("invalid distance code".into());
+                }
+                let dist = DIST_BASE[code] as usize + input.bits(DIST_EXTRA[code] as u32)? as usize;
+                if dist > out.len() {
+                    return Err("distance before start of data".into());
+                }
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+            _ => return Err("invalid length code".into()),
+        }
+        if out.len() > max_len {
+            return Err(format!("decompressed data over {} bytes", max_len));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_shrinks_repetitive_json() {
+        let rows: Vec<String> = (0..400).map(|i| format!("{{\"id\":{},\"name\":\"row {}\",\"ok\":true}}", i, i % 7)).collect();
+        let data = format!("[{}]", rows.join(","));
+        let compressed = compress(data.as_bytes(), 15);
+        assert!(compressed.len() * 4 < data.len(), "{} -> {}", data.len(), compressed.len());
+        assert!(compressed.ends_with(&[0x00, 0x00, 0xff, 0xff]));
+        assert_eq!(decompress(&compressed, usize::MAX).unwrap(), data.as_bytes());
+
+        // A small window still round-trips
+        assert_eq!(decompress(&compress(data.as_bytes(), 9), usize::MAX).unwrap(), data.as_bytes());
+        assert_eq!(decompress(&compress(b"", 15), 0).unwrap(), b"");
+        assert!(decompress(&compressed, 100).is_err());
+    }
+
+    #[test]
+    fn test_gzip_member() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let data = b"{\"content\":[{\"type\":\"text\",\"text\":\"aaaaaaaaaaaaaaaaaaaaaaaa\"}]}";
+        let member = gzip(data);
+        assert_eq!(&member[..3], &[0x1f, 0x8b, 8]);
+        assert_eq!(gunzip(&member, usize::MAX).unwrap(), data);
+
+        // The gzip tool's output for "hello\n", with a file name
+        let tool = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, b'h', b'i', 0x00, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+            0xe7, 0x02, 0x00, 0x20, 0x30, 0x3a, 0x36, 0x06, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(gunzip(&tool, usize::MAX).unwrap(), b"hello\n");
+        let mut corrupt = member.clone();
+        let last = corrupt.len() - 5;
+        corrupt[last] ^= 1;
+        assert!(gunzip(&corrupt, usize::MAX).is_err());
+        assert!(gunzip(data, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_decompresses_zlib_dynamic_block() {
+        // zlib raw deflate (level 9, sync flush) of a tools/list-like result
+        let zlib = [
+            0x6c, 0xce, 0xbd, 0x0e, 0x40, 0x30, 0x00, 0x45, 0xe1, 0x77, 0xb9, 0x73, 0x23, 0xea, 0x5f, 0x5f, 0x45, 0x44,
+            0x84, 0x0e, 0xa4, 0x54, 0x94, 0xa9, 0xe9, 0xbb, 0xab, 0x51, 0xae, 0xf5, 0xcb, 0x19, 0x8e, 0xc7, 0xea, 0xec,
+            0x7e, 0x1e, 0x13, 0x14, 0xb2, 0x24, 0x85, 0xc0, 0x32, 0x43, 0x49, 0x81, 0x53, 0xbb, 0xdb, 0x5c, 0x50, 0x1e,
+            0x97, 0xb5, 0xc6, 0x41, 0x75, 0x1e, 0xfb, 0xb8, 0xe9, 0xd8, 0xbd, 0x30, 0xa4, 0x08, 0xe2, 0x2b, 0x92, 0x24,
+            0x23, 0xc9, 0x49, 0x0a, 0x92, 0x92, 0xa4, 0x22, 0xa9, 0x49, 0x1a, 0x92, 0x96, 0x0f, 0x7f, 0xa6, 0xe3, 0x75,
+            0x1f, 0xc2, 0x03, 0x00, 0x00, 0xff, 0xff,
+        ];
+        let tools: Vec<String> = (0..12).map(|i| format!("{{\"name\":\"tool_{}\"}}", i)).collect();
+        let text = format!("{{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{{\"tools\":[{}]}}}}", tools.join(","));
+        assert_eq!(decompress(&zlib, usize::MAX).unwrap(), text.as_bytes());
+    }
+}
@@ -1,78 +1,329 @@
-use std::path::PathBuf;
-use std::process::Command;
-use std::net::TcpStream;
-use crate::config::auto_detect;
+use std::path::{Path, PathBuf};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::config::{auto_detect, ProxyConfig};
+
+/// How many servers are launched at once.
+const LAUNCH_JOBS: usize = 4;
 
 fn mcphub_dir() -> PathBuf {
-    dirs::home_dir().unwrap_or_default().join(".McpHub")
+    crate::config::hub_dir().unwrap_or_default()
+}
+
+/// Outcome of one check, with a hint on how to fix it.
+#[derive(Debug, PartialEq)]
+enum Check {
+    Pass(String),
+    /// Worth a look, but McpHub can run.
+    Warn(String, String),
+    /// McpHub won't work until this is fixed.
+    Fail(String, String),
 }
 
-pub fn run() {
+impl Check {
+    fn print(&self, indent: &str, label: &str) {
+        let (mark, text, hint) = match self {
+            Check::Pass(text) => ("✓", text, None),
+            Check::Warn(text, hint) => ("!", text, Some(hint)),
+            Check::Fail(text, hint) => ("✗", text, Some(hint)),
+        };
+        println!("{}{} {:<10}{}", indent, mark, format!("{}:", label), text);
+        if let Some(hint) = hint.filter(|h| !h.is_empty()) {
+            println!("{}    → {}", indent, hint);
+        }
+    }
+}
+
+/// Run every check, printing one line each. Exits 1 if any failed. With
+/// `launch`, each local server is started and handshaken with.
+pub async fn run(launch: bool) {
     println!("McpHub Doctor 🩺");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
-    // 1. Binary info
     let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("McpHub"));
-    println!("✓ Binary: {} (v{})", exe.display(), env!("CARGO_PKG_VERSION"));
+    let config = auto_detect();
+    let mut checks = vec![
+        ("Binary", Check::Pass(format!("{} (v{})", exe.display(), env!("CARGO_PKG_VERSION")))),
+        ("Data dir", check_data_dir(&mcphub_dir())),
+        ("Config", check_config(&config)),
+        ("Token", check_token(&config)),
+    ];
+    checks.extend(check_listener(&config));
+    checks.push(("Cache", check_cache(&config)));
+    for (label, check) in &checks {
+        check.print("", label);
+    }
 
-    // 2. Config
-    let config_path = mcphub_dir().join("config.json");
-    if config_path.exists() {
-        println!("✓ Config: {} (Valid JSON)", config_path.display());
-    } else {
-        println!("✗ Config: Not found at {}", config_path.display());
+    let mut names: Vec<String> = config.servers.keys().cloned().collect();
+    names.sort();
+    println!("\nServers ({} total):", names.len());
+    let servers = check_servers(&config, &names, launch).await;
+    for (name, check) in &servers {
+        check.print("  ", name);
     }
 
-    // 3. Cache
-    let cache_path = mcphub_dir().join("schema-cache.json");
-    if cache_path.exists() {
-        let meta = std::fs::metadata(&cache_path).unwrap();
-        let modified = meta.modified().unwrap();
-        let age = std::time::SystemTime::now().duration_since(modified).unwrap().as_secs();
-        let size_kb = meta.len() / 1024;
-        println!("✓ Cache:  {} ({} KB, updated {}s ago)", cache_path.display(), size_kb, age);
+    let mut total_size = 0;
+    if let Ok(entries) = std::fs::read_dir(mcphub_dir()) {
+        for entry in entries.flatten() {
+            if let Ok(meta) = entry.metadata() {
+                total_size += meta.len();
+            }
+        }
+    }
+    println!("\nDisk Usage: {} uses {} MB", mcphub_dir().display(), total_size / 1024 / 1024);
+
+    let failed = checks.iter().map(|(_, c)| c).chain(servers.iter().map(|(_, c)| c)).filter(|c| matches!(c, Check::Fail(..))).count();
+    if failed > 0 {
+        println!("\n{} check(s) failed", failed);
+        std::process::exit(1);
+    }
+    println!("\nAll critical checks passed");
+}
+
+/// The data directory exists (or can be created) and takes a write.
+fn check_data_dir(dir: &Path) -> Check {
+    let hint = format!("Fix the permissions of {} or point {} at a writable directory", dir.display(), crate::config::HOME_ENV);
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Check::Fail(format!("{} can't be created: {}", dir.display(), e), hint);
+    }
+    let probe = dir.join(".doctor-probe");
+    match std::fs::write(&probe, b"ok") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::Pass(format!("{} (writable)", dir.display()))
+        }
+        Err(e) => Check::Fail(format!("{} is not writable: {}", dir.display(), e), hint),
+    }
+}
+
+fn check_config(config: &ProxyConfig) -> Check {
+    let config_path = crate::config::dedicated_config_path().unwrap_or_else(|| mcphub_dir().join("config.json"));
+    if config_path.exists() {
+        return match crate::config::read_config_file(&config_path) {
+            Ok(json) => match crate::config::server_errors(&json).first() {
+                Some(e) => Check::Fail(format!("{}: server {}", config_path.display(), e), "Run 'McpHub validate' for every error".into()),
+                None => Check::Pass(format!("{} (valid)", config_path.display())),
+            },
+            Err(e) => Check::Fail(e, "Run 'McpHub validate' to see where it breaks".into()),
+        };
+    }
+    if config.servers.is_empty() {
+        Check::Fail(
+            format!("No servers: {} not found and no client config lists any", config_path.display()),
+            "Run 'McpHub add' or create the file".into(),
+        )
     } else {
-        println!("✗ Cache:  Not found. Run 'McpHub generate'");
+        Check::Warn(
+            format!("{} not found, using servers from client configs", config_path.display()),
+            String::new(),
+        )
     }
+}
 
-    // 4. Daemon & Port
-    match TcpStream::connect("127.0.0.1:24680") {
-        Ok(_) => println!("✓ Daemon: Running on port 24680"),
-        Err(_) => println!("! Daemon: Not running on port 24680 (or port is blocked)"),
+fn check_token(config: &ProxyConfig) -> Check {
+    if !config.auth_required {
+        return Check::Warn("Auth is off (auth.required = false)".into(), "Any local process can use the hub".into());
+    }
+    match crate::dashboard::auth_token_source() {
+        Some(source) => Check::Pass(format!("set ({})", source)),
+        None if config.auth_auto_generate_token => {
+            Check::Warn("No token yet; the daemon creates one when it starts".into(), "Run 'McpHub token show' to create it now".into())
+        }
+        None => Check::Fail(
+            "No token, and auth.autoGenerateToken is off".into(),
+            "Set MCPHUB_AUTH_TOKEN, or run 'McpHub token rotate' to write one".into(),
+        ),
     }
+}
 
-    // 5. Servers check
-    let config = auto_detect();
-    println!("\nServers ({} total):", config.servers.len());
-    
-    for (name, srv) in &config.servers {
-        print!("  {} ... ", name);
-        
-        // Check command exists
-        let output = Command::new("which").arg(&srv.command).output();
-        let cmd_exists = output.map(|o| o.status.success()).unwrap_or(false);
-        
-        if cmd_exists {
-            print!("✓ Command '{}' found", srv.command);
-        } else {
-            print!("✗ Command '{}' NOT FOUND", srv.command);
+/// The listener address is free, or held by a running McpHub that accepts
+/// this machine's token.
+fn check_listener(config: &ProxyConfig) -> Vec<(&'static str, Check)> {
+    let addr = match crate::config::resolve_bind_addr(None, None) {
+        Ok(addr) => addr,
+        Err(e) => return vec![("Port", Check::Fail(e, "Fix MCPHUB_BIND / MCPHUB_PORT".into()))],
+    };
+    let info = crate::dashboard::daemon_get(addr, "/info");
+    match info.as_ref().and_then(|i| i["version"].as_str()) {
+        Some(version) => {
+            let daemon = Check::Pass(format!(
+                "McpHub v{} (pid {}) is listening on {}",
+                version,
+                info.as_ref().and_then(|i| i["pid"].as_u64()).unwrap_or(0),
+                addr
+            ));
+            vec![("Daemon", daemon), ("Auth", check_daemon_token(config, addr))]
         }
+        None if TcpStream::connect_timeout(&crate::config::client_addr(addr), Duration::from_millis(500)).is_ok() => vec![(
+            "Port",
+            Check::Fail(
+                format!("{} is in use by something other than McpHub", addr),
+                "Stop that program, or run McpHub on another port (--port / MCPHUB_PORT)".into(),
+            ),
+        )],
+        None => match TcpListener::bind(addr) {
+            Ok(_) => vec![("Port", Check::Pass(format!("{} is free (daemon not running)", addr)))],
+            Err(e) => vec![("Port", Check::Fail(format!("{} can't be bound: {}", addr, e), "Pick another address with --bind / MCPHUB_BIND".into()))],
+        },
+    }
+}
 
-        // Check env vars presence (just warning if some common ones seem missing)
-        if srv.env.is_empty() && srv.command.contains("github") {
-            print!(" (! No env vars, github might need GITHUB_TOKEN)");
+/// The running daemon accepts the token clients on this machine would send.
+fn check_daemon_token(config: &ProxyConfig, addr: SocketAddr) -> Check {
+    if !config.auth_required {
+        return Check::Pass("not needed".into());
+    }
+    let Ok(token) = crate::dashboard::load_auth_token(false, addr) else {
+        return Check::Warn("No local token to check against the daemon".into(), String::new());
+    };
+    match crate::dashboard::daemon_request(addr, "/metrics", Some(&token)) {
+        Some((401, _)) => Check::Fail(
+            "The daemon rejects the local token".into(),
+            "It was started with another token (MCPHUB_AUTH_TOKEN?); restart it, then update clients with 'McpHub token show'".into(),
+        ),
+        Some(_) => Check::Pass("accepted by the daemon".into()),
+        None => Check::Warn("The daemon didn't answer".into(), String::new()),
+    }
+}
+
+/// The schema cache parses and covers every configured server.
+fn check_cache(config: &ProxyConfig) -> Check {
+    let Some(cache_path) = crate::cache::cache_file().filter(|p| p.exists()) else {
+        return Check::Warn("Not found; tools are listed once servers start".into(), "Run 'McpHub generate'".into());
+    };
+    let Some(cache) = crate::cache::read_cache() else {
+        return Check::Fail(format!("{} doesn't parse", cache_path.display()), "Run 'McpHub generate' to rebuild it".into());
+    };
+    let age = std::fs::metadata(&cache_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|m| m.elapsed().ok())
+        .map_or(0, |age| age.as_secs());
+    let mut missing: Vec<&String> = config.servers.keys().filter(|name| !cache.servers.contains_key(*name)).collect();
+    missing.sort();
+    let mut errors: Vec<&String> = cache.errors.keys().filter(|name| config.servers.contains_key(*name)).collect();
+    errors.sort();
+    let summary = format!("{} ({} servers, updated {}s ago)", cache_path.display(), cache.servers.len(), age);
+    if !missing.is_empty() {
+        Check::Warn(format!("{}; missing {}", summary, join(&missing)), "Run 'McpHub generate'".into())
+    } else if !errors.is_empty() {
+        Check::Warn(format!("{}; last start failed for {}", summary, join(&errors)), "See 'McpHub cache inspect'".into())
+    } else {
+        Check::Pass(summary)
+    }
+}
+
+fn join(names: &[&String]) -> String {
+    names.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Each local server's command resolves and, with `launch`, completes the
+/// MCP handshake. Remote servers are listed as is.
+async fn check_servers(config: &ProxyConfig, names: &[String], launch: bool) -> Vec<(String, Check)> {
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let mut checks: Vec<(String, Option<Check>)> = names
+        .iter()
+        .map(|name| {
+            let srv = &config.servers[name];
+            let check = match &srv.url {
+                Some(url) => Some(Check::Pass(format!("remote server at {}", url))),
+                None => match resolve_command(&srv.command, &path_var) {
+                    None => Some(Check::Fail(
+                        format!("command '{}' not found", srv.command),
+                        "Install it, or give its absolute path in the config".into(),
+                    )),
+                    Some(_) if launch => None,
+                    Some(path) => Some(Check::Pass(format!("command found at {}", path.display()))),
+                },
+            };
+            (name.clone(), check)
+        })
+        .collect();
+
+    let to_launch: Vec<String> = checks.iter().filter(|(_, c)| c.is_none()).map(|(n, _)| n.clone()).collect();
+    if !to_launch.is_empty() {
+        let launched = launch_servers(config, to_launch).await;
+        for (name, check) in checks.iter_mut().filter(|(_, c)| c.is_none()) {
+            *check = launched.iter().find(|(n, _)| n == name).map(|(_, result)| match result {
+                Ok((tools, took)) => Check::Pass(format!("started, {} tools ({:.1}s)", tools, took.as_secs_f64())),
+                Err(e) => Check::Fail(
+                    format!("failed to start: {}", e),
+                    format!("Run '{}' by hand to see its error", config.servers[name.as_str()].command),
+                ),
+            });
         }
-        println!();
     }
+    checks.into_iter().filter_map(|(name, check)| Some((name, check?))).collect()
+}
 
-    // 6. Disk usage
-    let mut total_size = 0;
-    if let Ok(entries) = std::fs::read_dir(mcphub_dir()) {
-        for entry in entries.flatten() {
-            if let Ok(meta) = entry.metadata() {
-                total_size += meta.len();
-            }
+type Launched = Vec<(String, Result<(usize, Duration), String>)>;
+
+/// Start `names`, LAUNCH_JOBS at a time, and stop them again. The schema
+/// cache is left alone.
+async fn launch_servers(config: &ProxyConfig, names: Vec<String>) -> Launched {
+    let manager = Arc::new(
+        crate::child::ChildManager::new(config.servers.clone(), config.idle_timeout_ms)
+            .with_timeouts(config.init_timeout_secs, config.call_timeout_secs)
+            .with_init_retries(config.init_retries, config.init_retry_backoff_ms)
+            .with_tool_filter(config.tool_filter.clone())
+            .with_tool_overrides(config.tool_overrides.clone()),
+    );
+    let workers = Arc::new(tokio::sync::Semaphore::new(LAUNCH_JOBS));
+    let mut starts = tokio::task::JoinSet::new();
+    for name in names {
+        let (manager, workers) = (manager.clone(), workers.clone());
+        starts.spawn(async move {
+            let _worker = workers.acquire_owned().await;
+            let begun = std::time::Instant::now();
+            let result = manager.start_server(&name).await.map(|tools| (tools.len(), begun.elapsed())).map_err(String::from);
+            (name, result)
+        });
+    }
+    let mut launched = Vec::new();
+    while let Some(started) = starts.join_next().await {
+        if let Ok(started) = started {
+            launched.push(started);
         }
     }
-    println!("\nDisk Usage: ~/.McpHub/ uses {} MB", total_size / 1024 / 1024);
+    manager.stop_all().await;
+    launched
+}
+
+/// Where `command` runs from: itself if it is a path, else the first
+/// match on `path_var` (trying the PATHEXT extensions on Windows).
+fn resolve_command(command: &str, path_var: &std::ffi::OsStr) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.CMD;.BAT".into()).split(';').map(str::to_lowercase).collect()
+    } else {
+        Vec::new()
+    };
+    let candidates = |base: PathBuf| {
+        std::iter::once(base.clone()).chain(extensions.iter().map(move |ext| base.with_extension(ext.trim_start_matches('.'))))
+    };
+    let is_file = |path: &PathBuf| path.is_file();
+    if command.contains(['/', '\\']) {
+        return candidates(PathBuf::from(command)).find(is_file);
+    }
+    std::env::split_paths(path_var).flat_map(|dir| candidates(dir.join(command))).find(is_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_command_searches_path() {
+        let dir = std::env::temp_dir().join(format!("mcphub-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("some-mcp-server");
+        std::fs::write(&tool, "").unwrap();
+        let path_var = std::env::join_paths([dir.join("missing"), dir.clone()]).unwrap();
+
+        assert_eq!(resolve_command("some-mcp-server", &path_var), Some(tool.clone()));
+        assert_eq!(resolve_command(tool.to_str().unwrap(), &path_var), Some(tool.clone()));
+        assert_eq!(resolve_command("no-such-server", &path_var), None);
+        assert!(matches!(check_data_dir(&dir), Check::Pass(_)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
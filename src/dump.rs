@@ -0,0 +1,345 @@
+/// `McpHub config dump`: the configuration McpHub would run with, after
+/// defaults, the config file, `MCPHUB_*` variables and flags, each value
+/// with where it came from. Secrets (the auth token, server env and header
+/// values) are never printed, only whether they are set; secret-looking
+/// args and url credentials are masked.
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+use crate::config::{self, Mode, PreInitialize, Preload, ProxyConfig};
+
+/// Variables that set a setting directly, checked before its `MCPHUB_*`
+/// name; `apply_env_overrides` applies them last.
+const DIRECT_ENV: [(&str, &str); 3] = [
+    ("sse.sessionTimeoutSecs", "MCPHUB_SESSION_TIMEOUT"),
+    ("sse.reaperIntervalSecs", "MCPHUB_REAPER_INTERVAL"),
+    ("sse.keepalive.intervalSecs", "MCPHUB_KEEPALIVE_INTERVAL"),
+];
+
+/// Legacy variables, overridden by the `MCPHUB_*` ones.
+const LEGACY_ENV: [(&str, &str); 2] = [("mode", "MCP_ON_DEMAND_MODE"), ("preload", "MCP_ON_DEMAND_PRELOAD")];
+
+pub fn run(bind_flag: Option<&str>, port_flag: Option<&str>, toml: bool) {
+    let config = config::auto_detect();
+    let env = |key: &str| std::env::var(key).ok().filter(|v| !v.is_empty());
+    let file = config::dedicated_settings();
+    let dump = effective(&config, file.as_ref().map(|(path, settings)| (path.as_path(), settings)), &env, bind_flag, port_flag);
+    if toml {
+        print!("{}", to_toml(&dump));
+    } else {
+        println!("{}", serde_json::to_string_pretty(&dump).unwrap_or_default());
+    }
+}
+
+fn effective(
+    config: &ProxyConfig,
+    file: Option<(&Path, &Value)>,
+    env: &impl Fn(&str) -> Option<String>,
+    bind_flag: Option<&str>,
+    port_flag: Option<&str>,
+) -> Value {
+    let data_dir = config::hub_dir();
+    let data_dir_source = if env(config::HOME_ENV).is_some() {
+        format!("env {}", config::HOME_ENV)
+    } else if env("XDG_STATE_HOME").is_some_and(|state| data_dir.as_deref() == Some(&Path::new(&state).join("mcphub"))) {
+        "env XDG_STATE_HOME".to_string()
+    } else {
+        "default".to_string()
+    };
+    let bind = match config::resolve_bind_addr(bind_flag, port_flag) {
+        Ok(addr) => json!({ "value": addr.to_string(), "source": config::bind_addr_source(bind_flag, port_flag) }),
+        Err(e) => json!({ "error": e }),
+    };
+    let token_source = crate::dashboard::auth_token_source();
+
+    let mut settings = Map::new();
+    for (path, value) in settings_values(config) {
+        settings.insert(path.to_string(), json!({ "value": value, "source": setting_source(path, file, env) }));
+    }
+
+    let sources = config::server_sources();
+    let mut names: Vec<&String> = config.servers.keys().collect();
+    names.sort();
+    let mut servers = Map::new();
+    for name in names {
+        let mut server = server_value(&config.servers[name]);
+        server["source"] = json!(sources.get(name).map(|p| p.display().to_string()));
+        servers.insert(name.clone(), server);
+    }
+
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "dataDir": { "value": data_dir.map(|d| d.display().to_string()), "source": data_dir_source },
+        "configFile": file.map(|(path, _)| path.display().to_string()),
+        "bind": bind,
+        "auth": {
+            "required": config.auth_required,
+            "tokenSet": token_source.is_some(),
+            "tokenSource": token_source,
+        },
+        "settings": settings,
+        "servers": servers,
+    })
+}
+
+/// Where the setting at `path` got its value: a variable, the config file,
+/// or the default.
+fn setting_source(path: &str, file: Option<(&Path, &Value)>, env: &impl Fn(&str) -> Option<String>) -> String {
+    let direct = DIRECT_ENV.iter().find(|(p, _)| *p == path).map(|(_, var)| var.to_string());
+    let legacy = LEGACY_ENV.iter().find(|(p, _)| *p == path).map(|(_, var)| var.to_string());
+    let vars = direct.into_iter().chain([config::env_var_name(path)]).chain(legacy);
+    if let Some(var) = vars.into_iter().find(|var| env(var).is_some()) {
+        return format!("env {}", var);
+    }
+    match file {
+        Some((file, settings)) if settings.pointer(&format!("/{}", path.replace('.', "/"))).is_some() => {
+            format!("file {}", file.display())
+        }
+        _ => "default".to_string(),
+    }
+}
+
+/// The scalar settings in effect, by their config path. Structured blocks
+/// (`transforms`, `toolFilter`, `resultCache`, `oversizedResults`) are left out.
+fn settings_values(c: &ProxyConfig) -> Vec<(&'static str, Value)> {
+    vec![
+        ("mode", json!(match c.mode { Mode::Discover => "discover", Mode::Passthrough => "passthrough" })),
+        ("toolNames.prefix", json!(c.tool_names.prefix)),
+        ("toolNames.separator", json!(c.tool_names.separator)),
+        ("preload", match &c.preload { Preload::All => json!(true), Preload::None => json!(false), Preload::Some(names) => json!(names) }),
+        ("preloadDelayMs", json!(c.preload_delay_ms)),
+        ("preloadConcurrency", json!(c.preload_concurrency)),
+        ("listCacheMs", json!(c.list_cache_ms)),
+        ("idleTimeout", json!(c.idle_timeout_ms / 1000)),
+        ("initTimeout", json!(c.init_timeout_secs)),
+        ("initRetries", json!(c.init_retries)),
+        ("initRetryBackoffMs", json!(c.init_retry_backoff_ms)),
+        ("callTimeout", json!(c.call_timeout_secs)),
+        ("maxConcurrentCalls", json!(c.max_concurrent_calls)),
+        ("activationTimeout", json!(c.activation_timeout_secs)),
+        ("reloadGracePeriod", json!(c.reload_grace_secs)),
+        ("shutdownGracePeriod", json!(c.shutdown_grace_secs)),
+        ("forwardExperimental", json!(c.forward_experimental)),
+        ("preInitialize", json!(match c.pre_initialize { PreInitialize::Strict => "strict", PreInitialize::Lenient => "lenient" })),
+        ("unknownToolFallback", json!(c.unknown_tool_fallback)),
+        ("forwardCompletions", json!(c.forward_completions)),
+        ("forwardSampling", json!(c.forward_sampling)),
+        ("debugMeta", json!(c.debug_meta)),
+        ("validateArguments", json!(c.validate_arguments)),
+        ("refreshStaleTools", json!(c.refresh_stale_tools)),
+        ("blockedMethods", json!(c.blocked_methods)),
+        ("resourceAffinity", json!(c.resource_affinity)),
+        ("health.checkInterval", json!(c.health_check_interval_secs)),
+        ("health.autoRestart", json!(c.health_auto_restart)),
+        ("health.notifications", json!(c.health_notifications)),
+        ("sse.maxSessions", json!(c.sse_max_sessions)),
+        ("sse.maxBufferedBytes", json!(c.sse_max_buffered_bytes.unwrap_or(0))),
+        ("sse.maxBodyBytes", json!(c.sse_max_body_bytes)),
+        ("sse.maxEventBytes", json!(c.sse_max_event_bytes.unwrap_or(0))),
+        ("sse.channelCapacity", json!(c.sse_channel_capacity)),
+        ("sse.maxChannelCapacity", json!(c.sse_max_channel_capacity.max(c.sse_channel_capacity))),
+        ("sse.sendTimeoutMs", json!(c.sse_send_timeout_ms)),
+        ("sse.logs.maxPerSecond", json!(c.sse_log_rate)),
+        ("sse.logs.minLevel", json!(c.sse_log_floor)),
+        ("sse.keepalive.intervalSecs", json!(c.sse_keepalive.interval.as_secs())),
+        ("sse.keepalive.idleAfterSecs", json!(c.sse_keepalive.idle_after.as_secs())),
+        ("sse.keepalive.idleIntervalSecs", json!(c.sse_keepalive.idle_interval.as_secs())),
+        ("sse.rateLimit.messagesPerSecond", json!(c.sse_rate_limit.messages_per_sec)),
+        ("sse.rateLimit.messageBurst", json!(c.sse_rate_limit.message_burst)),
+        ("sse.rateLimit.connectsPerSecond", json!(c.sse_rate_limit.connects_per_sec)),
+        ("sse.rateLimit.connectBurst", json!(c.sse_rate_limit.connect_burst)),
+        ("sse.sessionTimeoutSecs", json!(c.sse_reaper.session_timeout.as_secs())),
+        ("sse.reaperIntervalSecs", json!(c.sse_reaper.interval.as_secs())),
+        ("sse.maxSessionLifetimeSecs", json!(c.sse_reaper.max_lifetime.map_or(0, |d| d.as_secs()))),
+        ("sse.persistSessions", json!(c.sse_persist_sessions)),
+        ("sse.bufferLateResponses", json!(c.sse_buffer_late_responses)),
+        ("sse.duplicateClients", json!(match c.sse_duplicate_clients { config::DuplicateClients::Allow => "allow", config::DuplicateClients::Supersede => "supersede" })),
+        ("sse.eventTypes", json!(if c.sse_split_events { "split" } else { "message" })),
+        ("sse.streamResults", json!(c.sse_stream_results)),
+        ("circuitBreaker.failureThreshold", json!(c.circuit_breaker.failure_threshold)),
+        ("circuitBreaker.windowSecs", json!(c.circuit_breaker.window.as_secs())),
+        ("circuitBreaker.cooldownSecs", json!(c.circuit_breaker.cooldown.as_secs())),
+        ("audit.enabled", json!(c.audit.enabled)),
+        ("audit.path", json!(c.audit.path.as_ref().map(|p| p.display().to_string()))),
+        ("audit.redactKeys", json!(c.audit.redact_keys)),
+        ("record.enabled", json!(c.record.enabled)),
+        ("record.dir", json!(c.record.dir.as_ref().map(|p| p.display().to_string()))),
+        ("cache.versionCheck", json!(if c.cache_version.strict { "strict" } else { "migrate" })),
+        ("cache.pinVersion", json!(c.cache_version.pinned)),
+        ("cache.maxAgeHours", json!(c.cache_max_age.map_or(0, |age| age.as_secs() / 3600))),
+        ("cache.redactPatterns", json!(c.cache_redact_patterns)),
+        ("cache.format", json!(if c.cache_gzip { "gzip" } else { "json" })),
+        ("stdio.maxMessageBytes", json!(c.stdio_max_message_bytes)),
+        ("stdio.maxStderrLinesPerSec", json!(c.stdio_max_stderr_lines_per_sec)),
+        ("log.maxSizeMb", json!(c.log_rotation.max_bytes / (1024 * 1024))),
+        ("log.keep", json!(c.log_rotation.keep)),
+        ("ws.compression.enabled", json!(c.ws_compression)),
+        ("ws.compression.thresholdBytes", json!(c.ws_compression_threshold)),
+        ("http.maxHeaders", json!(c.http_max_headers)),
+        ("http.compression.enabled", json!(c.http_compression)),
+        ("http.compression.thresholdBytes", json!(c.http_compression_threshold)),
+        ("crashLoop.maxStarts", json!(c.crash_loop_max_starts)),
+        ("crashLoop.windowSecs", json!(c.crash_loop_window_secs)),
+        ("cors.allowedOrigins", json!(c.cors_allowed_origins)),
+        ("bind.retries", json!(c.bind_retries)),
+        ("bind.retryDelayMs", json!(c.bind_retry_delay_ms)),
+        ("auth.autoGenerateToken", json!(c.auth_auto_generate_token)),
+        ("auth.required", json!(c.auth_required)),
+    ]
+}
+
+/// A server entry as it will run. Env and header values may hold secrets,
+/// so only their names are shown.
+fn server_value(server: &config::ServerConfig) -> Value {
+    let names = |map: &std::collections::HashMap<String, String>| {
+        let mut names: Vec<&String> = map.keys().collect();
+        names.sort();
+        names.into_iter().map(|name| (name.clone(), json!("[set]"))).collect::<Map<String, Value>>()
+    };
+    json!({
+        "command": server.command,
+        "args": redact_args(&server.args),
+        "url": server.url.as_deref().map(redact_url),
+        "env": names(&server.env),
+        "headers": names(&server.headers),
+        "pool": server.pool,
+        "balance": match server.balance { config::Balance::RoundRobin => "round-robin", config::Balance::LeastInFlight => "least-in-flight" },
+        "initTimeout": server.init_timeout_secs,
+        "callTimeout": server.call_timeout_secs,
+        "basePort": server.base_port,
+        "priority": server.priority,
+        "dependsOn": server.depends_on,
+        "maxConcurrent": server.concurrency.max,
+        "whenBusy": match server.concurrency.when_busy { config::WhenBusy::Wait => "wait", config::WhenBusy::Reject => "reject" },
+    })
+}
+
+/// Words in a flag name that make the argument after it (or after its `=`)
+/// a secret.
+const SECRET_FLAG_WORDS: &[&str] = &["token", "key", "secret", "password", "passwd", "auth", "credential"];
+
+/// `args` with the values of secret-looking flags (`--api-key X`,
+/// `--token=X`) masked, and known secret formats masked anywhere.
+fn redact_args(args: &[String]) -> Vec<String> {
+    let is_secret_flag = |flag: &str| {
+        let name = flag.trim_start_matches('-').to_ascii_lowercase();
+        flag.starts_with('-') && SECRET_FLAG_WORDS.iter().any(|word| name.contains(word))
+    };
+    let mut out = Vec::with_capacity(args.len());
+    let mut after_flag = false;
+    for arg in args {
+        let masked = if after_flag && !arg.starts_with('-') {
+            crate::redact::MASK.to_string()
+        } else {
+            match arg.split_once('=') {
+                Some((flag, _)) if is_secret_flag(flag) => format!("{}={}", flag, crate::redact::MASK),
+                _ => crate::redact::redact(arg),
+            }
+        };
+        after_flag = !arg.contains('=') && is_secret_flag(arg);
+        out.push(masked);
+    }
+    out
+}
+
+/// `url` without its credentials: user info and query are masked.
+fn redact_url(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, _)) => (rest, format!("?{}", crate::redact::MASK)),
+        None => (rest, String::new()),
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    let rest = match rest[..authority_end].rsplit_once('@') {
+        Some((_, host)) => format!("{}@{}{}", crate::redact::MASK, host, &rest[authority_end..]),
+        None => rest.to_string(),
+    };
+    if scheme.is_empty() { rest + &query } else { format!("{}://{}{}", scheme, rest, query) }
+}
+
+/// The dump as a TOML document. Nulls (unset values) are left out, as TOML
+/// has none.
+fn to_toml(value: &Value) -> String {
+    let mut doc = toml_edit::DocumentMut::new();
+    if let Value::Object(map) = value {
+        for (key, value) in map {
+            if let Some(item) = toml_item(value) {
+                doc.insert(key, item);
+            }
+        }
+    }
+    doc.to_string()
+}
+
+fn toml_item(value: &Value) -> Option<toml_edit::Item> {
+    match value {
+        Value::Object(map) => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in map {
+                if let Some(item) = toml_item(value) {
+                    table.insert(key, item);
+                }
+            }
+            Some(toml_edit::Item::Table(table))
+        }
+        other => toml_value(other).map(toml_edit::Item::Value),
+    }
+}
+
+fn toml_value(value: &Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64()?.into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => toml_edit::Value::Array(items.iter().filter_map(toml_value).collect()),
+        Value::Object(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                if let Some(value) = toml_value(value) {
+                    table.insert(key, value);
+                }
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_and_secrets() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        let settings = json!({ "initTimeout": 90, "sse": { "maxSessions": 10 } });
+        let file = Some((Path::new("/etc/mcphub/config.json"), &settings));
+        let env = vars(&[("MCPHUB_SSE_MAX_SESSIONS", "20"), ("MCPHUB_SESSION_TIMEOUT", "60"), ("MCP_ON_DEMAND_MODE", "passthrough")]);
+
+        assert_eq!(setting_source("initTimeout", file, &env), "file /etc/mcphub/config.json");
+        assert_eq!(setting_source("sse.maxSessions", file, &env), "env MCPHUB_SSE_MAX_SESSIONS");
+        assert_eq!(setting_source("sse.sessionTimeoutSecs", file, &env), "env MCPHUB_SESSION_TIMEOUT");
+        assert_eq!(setting_source("mode", file, &env), "env MCP_ON_DEMAND_MODE");
+        assert_eq!(setting_source("callTimeout", file, &env), "default");
+        assert_eq!(setting_source("callTimeout", None, &env), "default");
+
+        let server = config::ServerConfig {
+            env: [("GITHUB_TOKEN".to_string(), "ghp_secret".to_string())].into(),
+            ..config::parse_servers(&json!({ "mcpServers": { "github": { "command": "gh-mcp" } } }))["github"].clone()
+        };
+        let text = to_toml(&json!({ "servers": { "github": server_value(&server) } }));
+        assert!(text.contains("GITHUB_TOKEN = \"[set]\"") && !text.contains("ghp_secret"), "{}", text);
+        assert!(text.contains("[servers.github]") && text.contains("command = \"gh-mcp\""), "{}", text);
+        assert!(!text.contains("url"), "{}", text);
+
+        let args: Vec<String> = ["-y", "server", "--api-key", "abc123", "--token=xyz", "--port", "80", "--verbose"].map(String::from).into();
+        assert_eq!(redact_args(&args), ["-y", "server", "--api-key", "[REDACTED]", "--token=[REDACTED]", "--port", "80", "--verbose"]);
+        assert_eq!(redact_url("http://user:pw@docs.internal:8080/sse?key=abc"), "http://[REDACTED]@docs.internal:8080/sse?[REDACTED]");
+        assert_eq!(redact_url("http://docs.internal/sse"), "http://docs.internal/sse");
+    }
+}
@@ -0,0 +1,175 @@
+/// JSON-RPC error codes McpHub answers with, and the mapping from a failed
+/// backend request to one of them. The standard codes are JSON-RPC 2.0's;
+/// -32000 to -32099 are left to the implementation and used for backend
+/// and hub failures. Failures McpHub classifies carry `data.reason`.
+use serde_json::{json, Value};
+
+use crate::protocol::JsonRpcResponse;
+
+/// The message isn't JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// JSON, but not a JSON-RPC request.
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// A backend failed the request for a reason not covered below.
+pub const SERVER_ERROR: i64 = -32000;
+/// A backend didn't answer in time.
+pub const TIMEOUT: i64 = -32001;
+/// MCP's code for a resource that doesn't exist.
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+/// The backend can't take requests: crashed, not running, restarting,
+/// circuit open, or the hub is shutting down.
+pub const UNAVAILABLE: i64 = -32003;
+/// A request before `initialize` with strict `preInitialize`.
+pub const NOT_INITIALIZED: i64 = -32004;
+
+/// Start of the error text of a backend that answered with a JSON-RPC error.
+pub const UPSTREAM_PREFIX: &str = "MCP error: ";
+
+/// What went wrong with a backend request. Set where the failure happens,
+/// it picks the code a client gets and how the hub reacts (failover,
+/// circuit breaker).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// The backend answered with a JSON-RPC error.
+    Answered,
+    /// The backend didn't answer in time.
+    Timeout,
+    /// The connection to the instance failed or its stdout closed.
+    Disconnected,
+    /// The server can't take requests: not running, no instance up,
+    /// circuit open, failed to start or draining.
+    Unavailable,
+    /// An on-demand start is still going.
+    Starting,
+    /// Cut short by a reload or stop of the server.
+    Aborted,
+    /// The client cancelled the request.
+    Cancelled,
+    UnknownServer,
+    /// Anything else.
+    Other,
+}
+
+/// A failed backend request: its kind and the text for logs and clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub kind: Kind,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: Kind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+
+    pub fn unknown_server(name: &str) -> Self {
+        Self::new(Kind::UnknownServer, format!("Unknown server: {}", name))
+    }
+
+    pub fn not_running(name: &str) -> Self {
+        Self::new(Kind::Unavailable, format!("Server not running: {}", name))
+    }
+
+    pub fn no_healthy_instances(name: &str) -> Self {
+        Self::new(Kind::Unavailable, format!("Server '{}': no healthy instances", name))
+    }
+
+    /// The backend's JSON-RPC error, for `Answered` failures.
+    pub fn answer(&self) -> Option<Value> {
+        match self.kind {
+            Kind::Answered => self.message.strip_prefix(UPSTREAM_PREFIX).and_then(|e| serde_json::from_str(e).ok()),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Errors from helpers that don't classify theirs.
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Self::new(Kind::Other, message)
+    }
+}
+
+impl From<&str> for Failure {
+    fn from(message: &str) -> Self {
+        Self::new(Kind::Other, message)
+    }
+}
+
+impl From<Failure> for String {
+    fn from(failure: Failure) -> Self {
+        failure.message
+    }
+}
+
+/// The JSON-RPC error for a backend request that failed with `failure`.
+/// A backend's own JSON-RPC error is passed on with its code, message and
+/// data; the hub's failures get a code for their kind.
+pub fn upstream(id: Option<Value>, failure: Failure) -> JsonRpcResponse {
+    if let Some(answered) = failure.answer() {
+        if let Some(code) = answered.get("code").and_then(|c| c.as_i64()) {
+            let message = answered.get("message").and_then(|m| m.as_str()).unwrap_or(&failure.message).to_string();
+            return JsonRpcResponse::error_with_data(id, code, message, answered.get("data").cloned());
+        }
+    }
+    let (code, reason) = match failure.kind {
+        Kind::Timeout => (TIMEOUT, "timeout"),
+        Kind::Disconnected | Kind::Unavailable | Kind::Starting | Kind::Aborted => (UNAVAILABLE, "unavailable"),
+        Kind::UnknownServer => (INVALID_PARAMS, "unknown_server"),
+        Kind::Answered | Kind::Cancelled | Kind::Other => (SERVER_ERROR, "upstream_error"),
+    };
+    JsonRpcResponse::error_with_data(id, code, failure.message, Some(json!({ "reason": reason })))
+}
+
+/// A JSON-RPC error response as text, for an HTTP body.
+pub fn body(id: Option<Value>, code: i64, message: String) -> String {
+    serde_json::to_string(&JsonRpcResponse::error(id, code, message)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_error_keeps_its_code() {
+        let error = format!("{}{}", UPSTREAM_PREFIX, json!({"code": -32602, "message": "Unknown tool: gone", "data": {"tool": "gone"}}));
+        let err = upstream(Some(json!(1)), Failure::new(Kind::Answered, error)).error.unwrap();
+        assert_eq!((err.code, err.message.as_str()), (INVALID_PARAMS, "Unknown tool: gone"));
+        assert_eq!(err.data, Some(json!({"tool": "gone"})));
+    }
+
+    #[test]
+    fn test_hub_failures_are_classified() {
+        let cases = [
+            (Kind::Timeout, "Upstream timed out: server did not respond to tools/call within 30.0s", TIMEOUT, "timeout"),
+            (Kind::Disconnected, "Server closed connection: process crashed (exit code 3)", UNAVAILABLE, "unavailable"),
+            (Kind::Unavailable, "Circuit open for 'gh': too many consecutive failures, next attempt in 20s", UNAVAILABLE, "unavailable"),
+            (Kind::UnknownServer, "Unknown server: nope", INVALID_PARAMS, "unknown_server"),
+            (Kind::Cancelled, "tools/call cancelled by the client", SERVER_ERROR, "upstream_error"),
+            // The text alone doesn't decide
+            (Kind::Other, "Failed to spawn gh: timed out", SERVER_ERROR, "upstream_error"),
+        ];
+        for (kind, error, code, reason) in cases {
+            let err = upstream(None, Failure::new(kind, error)).error.unwrap();
+            assert_eq!((err.code, err.message.as_str()), (code, error));
+            assert_eq!(err.data, Some(json!({ "reason": reason })));
+        }
+    }
+
+    #[test]
+    fn test_body_has_null_id() {
+        assert_eq!(
+            body(None, PARSE_ERROR, "Parse error".into()),
+            r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Parse error"}}"#
+        );
+    }
+}
@@ -2,16 +2,20 @@ use serde_json::Value;
 use std::io::Write;
 
 pub fn run_export() {
-    let path = dirs::home_dir().unwrap_or_default().join(".McpHub").join("config.json");
+    let path = crate::config::dedicated_config_path().unwrap_or_default();
     if let Ok(content) = std::fs::read_to_string(&path) {
         println!("{}", content);
     } else {
-        eprintln!("Failed to read config.json");
+        eprintln!("Failed to read {}", path.display());
     }
 }
 
 pub fn run_import(file: &str) {
-    let dest = dirs::home_dir().unwrap_or_default().join(".McpHub").join("config.json");
+    let dest = crate::config::hub_dir().unwrap_or_default().join("config.json");
+    if let Some(path) = crate::config::dedicated_config_path().filter(|p| crate::config::is_toml(p)) {
+        eprintln!("Servers are configured in {}; copy them there instead.", path.display());
+        return;
+    }
     if let Ok(content) = std::fs::read_to_string(file) {
         if let Ok(mut json) = serde_json::from_str::<Value>(&content) {
             
@@ -49,7 +53,7 @@ pub fn run_import(file: &str) {
             if std::fs::write(&dest, serde_json::to_string_pretty(&json).unwrap()).is_ok() {
                 println!("Imported successfully. Run 'McpHub generate' to rebuild cache.");
             } else {
-                eprintln!("Failed to write to ~/.McpHub/config.json");
+                eprintln!("Failed to write to {}", dest.display());
             }
         } else {
             eprintln!("Invalid JSON in {}", file);
@@ -0,0 +1,125 @@
+//! Newline-delimited JSON-RPC framing for the stdio transports.
+//! A message is handed on only once it is complete: a line that stops
+//! mid-object is held until the rest arrives, and a JSON value spread over
+//! several lines is joined. Pending bytes are capped so a writer that
+//! never finishes a message can't grow the buffer without bound.
+
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Default cap on one message in bytes.
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct MessageReader<R> {
+    reader: R,
+    /// Bytes of the message being assembled.
+    pending: Vec<u8>,
+    /// Set after an oversized message was dropped: skip to the next newline.
+    discarding: bool,
+    max_bytes: usize,
+    /// Who is writing, for log lines.
+    label: String,
+}
+
+impl<R: AsyncBufRead + Unpin> MessageReader<R> {
+    pub fn new(reader: R, max_bytes: usize, label: &str) -> Self {
+        Self { reader, pending: Vec::new(), discarding: false, max_bytes, label: label.to_string() }
+    }
+
+    /// Next complete JSON value. Lines that aren't JSON (banners, stray
+    /// prints) are skipped. Ok(None) at end of stream.
+    pub async fn next_message(&mut self) -> std::io::Result<Option<Value>> {
+        loop {
+            let buf = self.reader.fill_buf().await?;
+            if buf.is_empty() {
+                if !self.pending.iter().all(u8::is_ascii_whitespace) {
+                    tracing::warn!(
+                        "{}: stream ended inside a message, discarding {} bytes",
+                        self.label,
+                        self.pending.len()
+                    );
+                }
+                self.pending.clear();
+                return Ok(None);
+            }
+            let (take, line_done) = match buf.iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (buf.len(), false),
+            };
+            if self.discarding {
+                self.reader.consume(take);
+                self.discarding = !line_done;
+                continue;
+            }
+            if self.pending.len() + take > self.max_bytes {
+                self.reader.consume(take);
+                self.reset();
+                self.discarding = !line_done;
+                continue;
+            }
+            self.pending.extend_from_slice(&buf[..take]);
+            self.reader.consume(take);
+            if !line_done {
+                continue;
+            }
+
+            if self.pending.iter().all(u8::is_ascii_whitespace) {
+                self.pending.clear();
+                continue;
+            }
+            match serde_json::from_slice::<Value>(&self.pending) {
+                Ok(value) => {
+                    self.pending.clear();
+                    return Ok(Some(value));
+                }
+                // The value continues on the next line
+                Err(e) if e.is_eof() => {}
+                Err(_) => self.pending.clear(),
+            }
+        }
+    }
+
+    /// Drop the message being assembled and the rest of its line.
+    fn reset(&mut self) {
+        tracing::warn!(
+            "{}: message exceeds {} bytes without completing, discarding it",
+            self.label, self.max_bytes
+        );
+        self.pending = Vec::new();
+        self.discarding = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncWriteExt, BufReader};
+
+    #[tokio::test]
+    async fn test_message_written_in_partial_chunks_is_reassembled() {
+        let (mut writer, read_half) = tokio::io::duplex(64);
+        let mut reader = MessageReader::new(BufReader::new(read_half), 1024, "test");
+        let sender = tokio::spawn(async move {
+            for part in ["{\"jsonrpc\":\"2.0\",", "\"id\":1,\"res", "ult\":{\"a\":[1,\n", "2]}}\n", "not json\n{\"id\":2}\n"] {
+                writer.write_all(part.as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let first = reader.next_message().await.unwrap().unwrap();
+        assert_eq!(first, serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": { "a": [1, 2] } }));
+        let second = reader.next_message().await.unwrap().unwrap();
+        assert_eq!(second["id"], 2);
+        sender.await.unwrap();
+        assert!(reader.next_message().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message_is_dropped_and_stream_recovers() {
+        let input = format!("{{\"data\":\"{}\"}}\n{{\"id\":3}}\n", "x".repeat(200));
+        let mut reader = MessageReader::new(BufReader::with_capacity(16, input.as_bytes()), 64, "test");
+        assert_eq!(reader.next_message().await.unwrap().unwrap()["id"], 3);
+        assert!(reader.next_message().await.unwrap().is_none());
+    }
+}
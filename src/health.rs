@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::child::ChildManager;
+use crate::child::{ChildManager, GroupState};
 
 const MAX_RESTART_ATTEMPTS: u32 = 3;
 const RESTART_BACKOFF_BASE_MS: u64 = 2000;
@@ -32,28 +32,41 @@ impl HealthMonitor {
 
     /// Run the health monitor loop. Call this as a spawned task.
     pub async fn run(&self) {
-        eprintln!(
-            "[McpHub][HEALTH] Monitor started: interval={}s, auto_restart={}",
+        tracing::info!(
+            tag = "HEALTH", "Monitor started: interval={}s, auto_restart={}",
             self.check_interval.as_secs(),
             self.auto_restart
         );
 
+        let mut crashes = self.manager.subscribe_crashes();
+        let mut checks = tokio::time::interval_at(tokio::time::Instant::now() + self.check_interval, self.check_interval);
         loop {
-            tokio::time::sleep(self.check_interval).await;
-            self.check_cycle().await;
+            tokio::select! {
+                _ = checks.tick() => self.check_cycle().await,
+                // Restart a crashed server now rather than at the next check
+                Ok((name, reason)) = crashes.recv(), if self.auto_restart => {
+                    tracing::info!(tag = "HEALTH", "Server '{}' crashed: {}", name, reason);
+                    self.try_restart(&name, &reason).await;
+                }
+            }
         }
     }
 
     async fn check_cycle(&self) {
         let dead = self.manager.health_check().await;
 
+        // Bring back single instances of load-balanced groups that are still serving
+        if self.auto_restart {
+            self.manager.revive_instances().await;
+        }
+
         if dead.is_empty() {
             return;
         }
 
         for (name, reason) in &dead {
-            eprintln!(
-                "[McpHub][HEALTH] Server '{}' is DOWN: {}",
+            tracing::warn!(
+                tag = "HEALTH", "Server '{}' is DOWN: {}",
                 name, reason
             );
 
@@ -70,8 +83,8 @@ impl HealthMonitor {
         let count = attempts.entry(name.to_string()).or_insert(0);
 
         if *count >= MAX_RESTART_ATTEMPTS {
-            eprintln!(
-                "[McpHub][HEALTH] Server '{}' failed {} restart attempts. Giving up.",
+            tracing::warn!(
+                tag = "HEALTH", "Server '{}' failed {} restart attempts. Giving up.",
                 name, MAX_RESTART_ATTEMPTS
             );
             self.notify_down(name, &format!("{} (failed {} restarts)", reason, count), false);
@@ -84,16 +97,23 @@ impl HealthMonitor {
 
         // Exponential backoff
         let backoff = Duration::from_millis(RESTART_BACKOFF_BASE_MS * (1 << (attempt - 1)));
-        eprintln!(
-            "[McpHub][HEALTH] Restarting '{}' (attempt {}/{}, backoff {:?})...",
+        tracing::info!(
+            tag = "HEALTH", "Restarting '{}' (attempt {}/{}, backoff {:?})...",
             name, attempt, MAX_RESTART_ATTEMPTS, backoff
         );
         tokio::time::sleep(backoff).await;
 
+        // A call that hit the crash may have restarted it meanwhile
+        if self.manager.group_health().await.get(name).is_some_and(|h| h.state != GroupState::Failed) {
+            tracing::info!(tag = "HEALTH", "Server '{}' is already back up", name);
+            self.restart_attempts.lock().await.remove(name);
+            return;
+        }
+
         match self.manager.restart_server(name).await {
             Ok(tool_count) => {
-                eprintln!(
-                    "[McpHub][HEALTH] Server '{}' restarted OK ({} tools)",
+                tracing::info!(
+                    tag = "HEALTH", "Server '{}' restarted OK ({} tools)",
                     name, tool_count
                 );
                 self.notify_restarted(name, tool_count);
@@ -102,11 +122,11 @@ impl HealthMonitor {
                 attempts.remove(name);
             }
             Err(e) => {
-                eprintln!(
-                    "[McpHub][HEALTH] Restart '{}' FAILED: {}",
+                tracing::warn!(
+                    tag = "HEALTH", "Restart '{}' FAILED: {}",
                     name, e
                 );
-                let mut attempts = self.restart_attempts.lock().await;
+                let attempts = self.restart_attempts.lock().await;
                 let count = attempts.get(name).copied().unwrap_or(0);
                 if count >= MAX_RESTART_ATTEMPTS {
                     self.notify_down(name, &format!("{} (all restarts failed)", reason), false);
@@ -133,7 +153,7 @@ impl HealthMonitor {
 /// - Linux: D-Bus / libnotify via notify-rust
 fn send_notification(title: &str, body: &str) {
     // Always log to stderr (visible in Cursor MCP output)
-    eprintln!("[McpHub][ALERT] {}: {}", title, body);
+    tracing::warn!(tag = "ALERT", "{}: {}", title, body);
 
     #[cfg(target_os = "macos")]
     {
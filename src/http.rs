@@ -0,0 +1,444 @@
+//! Minimal HTTP/1.1 request reading for the dashboard and SSE endpoints.
+//! A request is only handed on once its head and its whole body have
+//! arrived, however many TCP reads that takes. Bodies are framed by
+//! `Content-Length` or chunked transfer encoding.
+//!
+//! Repeated headers are joined with `, ` as HTTP lists are, except those
+//! that must appear once (`SINGLE_HEADERS`): a request repeating one is
+//! refused, as is one with both `Content-Length` and chunked encoding,
+//! since front proxies may frame such a body differently.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Largest request line plus headers accepted.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+/// Default largest request body accepted (`sse.maxBodyBytes`): room for a
+/// `tools/call` with inline file content, not for a body meant to exhaust
+/// memory.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+/// Default cap on the number of header lines.
+pub const DEFAULT_MAX_HEADERS: usize = 100;
+/// Headers a request may carry at most once.
+const SINGLE_HEADERS: &[&str] = &["authorization", "content-length", "host", "mcp-session-id", "sec-websocket-key"];
+
+/// Why no request could be read.
+#[derive(Debug, PartialEq)]
+pub enum RequestError {
+    /// The body is over the limit; answered with 413.
+    TooLarge(String),
+    /// Closed early, timed out or malformed; answered with 400.
+    Invalid(String),
+}
+
+impl From<String> for RequestError {
+    fn from(message: String) -> Self {
+        RequestError::Invalid(message)
+    }
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::TooLarge(message) | RequestError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+fn too_large(max_body_bytes: usize) -> RequestError {
+    RequestError::TooLarge(format!("request body exceeds {} bytes", max_body_bytes))
+}
+
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Request target including the query string, as sent.
+    pub path: String,
+    /// Keys are lowercase.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Read one request from `stream`. Each read waits at most `timeout`.
+/// A body over `max_body_bytes` is refused from its `Content-Length`
+/// before reading it, or as soon as its chunks add up to more.
+pub async fn parse_request<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    timeout: Duration,
+    max_headers: usize,
+    max_body_bytes: usize,
+) -> Result<HttpRequest, RequestError> {
+    let mut buf = Vec::with_capacity(8192);
+    let (head_len, body_start) = loop {
+        if let Some(end) = find_head_end(&buf) {
+            break end;
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err(format!("request head exceeds {} bytes", MAX_HEAD_BYTES).into());
+        }
+        read_more(stream, &mut buf, timeout).await?;
+    };
+    let mut req = parse_head(&String::from_utf8_lossy(&buf[..head_len]), max_headers)?;
+
+    // GET and HEAD carry no body: ignore framing such as a stray
+    // `Content-Length` some clients send on GET /sse.
+    if req.method == "GET" || req.method == "HEAD" {
+        return Ok(req);
+    }
+    let rest = buf.split_off(body_start);
+    let chunked = req
+        .headers
+        .get("transfer-encoding")
+        .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"));
+    if chunked && req.headers.contains_key("content-length") {
+        return Err(RequestError::Invalid("both Content-Length and Transfer-Encoding: chunked".into()));
+    }
+    let body = if chunked {
+        read_chunked(stream, rest, timeout, max_body_bytes).await?
+    } else {
+        let length = match req.headers.get("content-length") {
+            Some(v) => v.parse::<usize>().map_err(|_| format!("invalid Content-Length '{}'", v))?,
+            None => 0,
+        };
+        if length > max_body_bytes {
+            return Err(too_large(max_body_bytes));
+        }
+        read_sized(stream, rest, length, timeout).await?
+    };
+    req.body = String::from_utf8_lossy(&body).into_owned();
+    Ok(req)
+}
+
+/// Request line and headers; the body is left empty.
+fn parse_head(head: &str, max_headers: usize) -> Result<HttpRequest, String> {
+    let mut lines = head.lines();
+    let mut parts = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".into());
+    };
+    let mut headers: HashMap<String, String> = HashMap::new();
+    for (count, (name, value)) in lines.filter_map(|line| line.split_once(':')).enumerate() {
+        if count == max_headers {
+            return Err(format!("more than {} headers", max_headers));
+        }
+        let (name, value) = (name.trim().to_lowercase(), value.trim());
+        match headers.get_mut(&name) {
+            Some(_) if SINGLE_HEADERS.contains(&name.as_str()) => {
+                return Err(format!("duplicate {} header", name));
+            }
+            Some(existing) => {
+                existing.push_str(", ");
+                existing.push_str(value);
+            }
+            None => {
+                headers.insert(name, value.to_string());
+            }
+        }
+    }
+    Ok(HttpRequest { method: method.to_string(), path: path.to_string(), headers, body: String::new() })
+}
+
+/// Length of the head and offset of the body. Bare `\n\n` is tolerated.
+fn find_head_end(buf: &[u8]) -> Option<(usize, usize)> {
+    if let Some(i) = find(buf, b"\r\n\r\n") {
+        return Some((i, i + 4));
+    }
+    find(buf, b"\n\n").map(|i| (i, i + 2))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+async fn read_more<R: AsyncRead + Unpin>(stream: &mut R, buf: &mut Vec<u8>, timeout: Duration) -> Result<(), String> {
+    let mut chunk = [0u8; 16 * 1024];
+    match tokio::time::timeout(timeout, stream.read(&mut chunk)).await {
+        Ok(Ok(0)) => Err("connection closed mid-request".into()),
+        Ok(Ok(n)) => {
+            buf.extend_from_slice(&chunk[..n]);
+            Ok(())
+        }
+        Ok(Err(e)) => Err(format!("read failed: {}", e)),
+        Err(_) => Err("timed out reading request".into()),
+    }
+}
+
+async fn read_sized<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    mut body: Vec<u8>,
+    length: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    while body.len() < length {
+        read_more(stream, &mut body, timeout).await?;
+    }
+    body.truncate(length);
+    Ok(body)
+}
+
+/// Decode a chunked body. `buf` holds what was read past the head.
+async fn read_chunked<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    mut buf: Vec<u8>,
+    timeout: Duration,
+    max_body_bytes: usize,
+) -> Result<Vec<u8>, RequestError> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        let size_end = find_line_end(stream, &mut buf, pos, timeout).await?;
+        let size_line = String::from_utf8_lossy(&buf[pos..size_end]);
+        // Chunk extensions (`;name=value`) are ignored
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| format!("invalid chunk size '{}'", size_hex))?;
+        pos = size_end + 2;
+        if size == 0 {
+            // Skip trailers up to the empty line that ends the body
+            loop {
+                let end = find_line_end(stream, &mut buf, pos, timeout).await?;
+                if end == pos {
+                    return Ok(body);
+                }
+                pos = end + 2;
+            }
+        }
+        if body.len().saturating_add(size) > max_body_bytes {
+            return Err(too_large(max_body_bytes));
+        }
+        while buf.len() < pos + size + 2 {
+            read_more(stream, &mut buf, timeout).await?;
+        }
+        body.extend_from_slice(&buf[pos..pos + size]);
+        pos += size + 2;
+    }
+}
+
+/// Offset of the next `\r\n` at or after `from`, reading more as needed.
+async fn find_line_end<R: AsyncRead + Unpin>(stream: &mut R, buf: &mut Vec<u8>, from: usize, timeout: Duration) -> Result<usize, String> {
+    loop {
+        if let Some(i) = find(&buf[from..], b"\r\n") {
+            return Ok(from + i);
+        }
+        if buf.len() - from > MAX_HEAD_BYTES {
+            return Err("chunk header too long".into());
+        }
+        read_more(stream, buf, timeout).await?;
+    }
+}
+
+/// Decoded value of query parameter `name` in a request target.
+pub fn query_param(path: &str, name: &str) -> Option<String> {
+    let query = path.split_once('?')?.1;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (percent_decode(key) == name).then(|| percent_decode(value))
+    })
+}
+
+/// Decode `%XX` escapes and `+` (as a space). Invalid escapes are kept
+/// as they are; the decoded bytes are read as UTF-8.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 3;
+                    continue;
+                }
+                None => out.push(b'%'),
+            },
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Whether `Accept-Encoding` allows gzip: listed, or covered by `*`,
+/// with a nonzero `q`.
+pub fn accepts_gzip(headers: &HashMap<String, String>) -> bool {
+    let Some(accept) = headers.get("accept-encoding") else {
+        return false;
+    };
+    let weight = |name: &str| {
+        accept.split(',').find_map(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            if !params.next().is_some_and(|c| c.eq_ignore_ascii_case(name)) {
+                return None;
+            }
+            Some(params.find_map(|p| p.strip_prefix("q=")).and_then(|q| q.trim().parse::<f64>().ok()).unwrap_or(1.0))
+        })
+    };
+    weight("gzip").or_else(|| weight("*")).is_some_and(|q| q > 0.0)
+}
+
+/// Insert `headers` right after the status line of a complete response.
+pub fn with_headers(response: Vec<u8>, headers: &str) -> Vec<u8> {
+    if headers.is_empty() {
+        return response;
+    }
+    match response.windows(2).position(|w| w == b"\r\n") {
+        Some(end) => [&response[..end + 2], headers.as_bytes(), &response[end + 2..]].concat(),
+        None => response,
+    }
+}
+
+/// `response`, a complete HTTP response, with its body gzipped if `req`
+/// accepts gzip and the body is at least `threshold` bytes (None: never).
+/// Event streams and bodies that already have an encoding are left alone.
+pub fn gzip_response(response: Vec<u8>, req: &HttpRequest, threshold: Option<usize>) -> Vec<u8> {
+    let Some(end) = find(&response, b"\r\n\r\n") else {
+        return response;
+    };
+    let body = &response[end + 4..];
+    if threshold.is_none_or(|min| body.len() < min) || !accepts_gzip(&req.headers) {
+        return response;
+    }
+    let mut head = String::new();
+    for line in String::from_utf8_lossy(&response[..end]).split("\r\n") {
+        let name = line.split(':').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match name.as_str() {
+            "content-encoding" => return response,
+            "content-type" if line.contains("text/event-stream") => return response,
+            "content-length" => continue,
+            _ => head.push_str(&format!("{}\r\n", line)),
+        }
+    }
+    let compressed = crate::deflate::gzip(body);
+    head.push_str(&format!("Content-Encoding: gzip\r\nVary: Accept-Encoding\r\nContent-Length: {}\r\n\r\n", compressed.len()));
+    let mut out = head.into_bytes();
+    out.extend(compressed);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    async fn parse(raw: &str) -> Result<HttpRequest, String> {
+        parse_request(&mut raw.as_bytes(), Duration::from_secs(1), DEFAULT_MAX_HEADERS, DEFAULT_MAX_BODY_BYTES).await.map_err(|e| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_get_with_zero_content_length_has_no_body() {
+        let raw = "GET /sse HTTP/1.1\r\nHost: 127.0.0.1:24680\r\nContent-Length: 0\r\nAccept: text/event-stream\r\n\r\n\r\n";
+        let req = parse(raw).await.unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/sse");
+        assert_eq!(req.headers.get("accept").map(String::as_str), Some("text/event-stream"));
+        assert!(req.body.is_empty());
+
+        // A nonzero length on GET is ignored too
+        let raw = "GET /sse HTTP/1.1\r\nContent-Length: 2\r\n\r\n";
+        assert!(parse(raw).await.unwrap().body.is_empty());
+
+        let raw = "POST /message?sessionId=a HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(parse(raw).await.unwrap().body, "{}");
+    }
+
+    #[tokio::test]
+    async fn test_body_split_across_reads_is_assembled() {
+        let body = format!("{{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{{\"data\":\"{}\"}}}}", "x".repeat(100_000));
+        let raw = format!("POST /message?sessionId=s HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let sender = tokio::spawn(async move {
+            // Head split mid-header, body in several writes
+            for part in raw.as_bytes().chunks(7000) {
+                client.write_all(part).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            client
+        });
+        let req = parse_request(&mut server, Duration::from_secs(5), DEFAULT_MAX_HEADERS, DEFAULT_MAX_BODY_BYTES).await.unwrap();
+        assert_eq!(req.body, body);
+        sender.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chunked_body_is_decoded() {
+        let raw = "POST /message HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\n{\"a\"\r\n4\r\n:1}\n\r\n0\r\nX-Trailer: t\r\n\r\n";
+        assert_eq!(parse(raw).await.unwrap().body, "{\"a\":1}\n");
+
+        let truncated = "POST /message HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}";
+        assert!(parse(truncated).await.unwrap_err().contains("closed"));
+        assert!(parse("POST /m HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_too_large() {
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        let raw = "POST /message HTTP/1.1\r\nContent-Length: 2048\r\n\r\n";
+        client.write_all(raw.as_bytes()).await.unwrap();
+        let err = parse_request(&mut server, Duration::from_secs(5), DEFAULT_MAX_HEADERS, 1024).await.unwrap_err();
+        assert!(matches!(err, RequestError::TooLarge(_)));
+
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        let chunk = "x".repeat(600);
+        let raw = format!("POST /message HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n258\r\n{chunk}\r\n258\r\n{chunk}\r\n0\r\n\r\n");
+        client.write_all(raw.as_bytes()).await.unwrap();
+        let err = parse_request(&mut server, Duration::from_secs(5), DEFAULT_MAX_HEADERS, 1024).await.unwrap_err();
+        assert!(matches!(err, RequestError::TooLarge(_)));
+    }
+
+    #[tokio::test]
+    async fn test_header_abuse_is_rejected() {
+        let raw = "POST /message HTTP/1.1\r\nContent-Length: 2\r\ncontent-length: 40\r\n\r\n{}";
+        assert_eq!(parse(raw).await.unwrap_err(), "duplicate content-length header");
+        let raw = "GET /sse HTTP/1.1\r\nAuthorization: Bearer a\r\nAuthorization: Bearer b\r\n\r\n";
+        assert_eq!(parse(raw).await.unwrap_err(), "duplicate authorization header");
+        let raw = "POST /m HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        assert!(parse(raw).await.unwrap_err().contains("both Content-Length"));
+
+        let many: String = (0..DEFAULT_MAX_HEADERS + 1).map(|i| format!("X-H{}: v\r\n", i)).collect();
+        let raw = format!("GET /sse HTTP/1.1\r\n{}\r\n", many);
+        assert_eq!(parse(&raw).await.unwrap_err(), format!("more than {} headers", DEFAULT_MAX_HEADERS));
+        let raw = format!("GET /sse HTTP/1.1\r\n{}\r\n", &many[many.find("X-H1:").unwrap()..]);
+        assert!(parse(&raw).await.is_ok());
+
+        // Other repeated headers are joined as a list
+        let raw = "GET /ws HTTP/1.1\r\nSec-WebSocket-Extensions: a\r\nSec-WebSocket-Extensions: b; x=1\r\n\r\n";
+        assert_eq!(parse(raw).await.unwrap().headers["sec-websocket-extensions"], "a, b; x=1");
+    }
+
+    #[test]
+    fn test_gzip_only_when_accepted_and_large() {
+        let request = |accept: &str| HttpRequest {
+            method: "POST".into(),
+            path: "/mcp".into(),
+            headers: HashMap::from([("accept-encoding".to_string(), accept.to_string())]),
+            body: String::new(),
+        };
+        let body = format!("{{\"text\":\"{}\"}}", "x".repeat(4000));
+        let response = crate::sse::http_response(200, "OK", &body);
+
+        let gzipped = gzip_response(response.clone(), &request("br, gzip;q=0.8"), Some(1024));
+        let end = find(&gzipped, b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&gzipped[..end]).into_owned();
+        let member = &gzipped[end + 4..];
+        assert!(head.contains("Content-Encoding: gzip\r\n"), "{}", head);
+        assert!(head.contains(&format!("Content-Length: {}", member.len())), "{}", head);
+        let inflated = crate::deflate::decompress(&member[10..member.len() - 8], usize::MAX).unwrap();
+        assert_eq!(inflated, body.as_bytes());
+
+        assert_eq!(gzip_response(response.clone(), &request("gzip;q=0, *"), Some(1024)), response);
+        assert_eq!(gzip_response(response.clone(), &request("identity"), Some(1024)), response);
+        assert_eq!(gzip_response(response.clone(), &request("gzip"), Some(body.len() + 1)), response);
+        assert_eq!(gzip_response(response.clone(), &request("gzip"), None), response);
+    }
+
+    #[test]
+    fn test_query_param_is_percent_decoded() {
+        assert_eq!(query_param("/message?sessionId=a%2Bb%3D&x=1", "sessionId").as_deref(), Some("a+b="));
+        assert_eq!(query_param("/message?x=1&sessionId=abc", "sessionId").as_deref(), Some("abc"));
+        assert_eq!(query_param("/message?sessionIdx=1", "sessionId"), None);
+        assert_eq!(query_param("/message", "sessionId"), None);
+        assert_eq!(percent_decode("caf%C3%A9+%zz"), "café %zz");
+    }
+}
@@ -1,21 +1,446 @@
 /// Cross-platform auto-start management.
 /// `McpHub install`   — register McpHub to start at login
 /// `McpHub uninstall` — remove auto-start
-
+/// `McpHub status`    — reports the entry through `service_status`
+use crate::config::{client_base_url, DEFAULT_BIND_ADDR};
+use serde_json::json;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
+#[cfg(target_os = "macos")]
 const LABEL: &str = "com.soflution.mcphub";
 
-pub fn install() {
+/// An MCP client whose config `install` can print or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Client {
+    Cursor,
+    Claude,
+    VsCode,
+}
+
+impl Client {
+    pub const ALL: [Client; 3] = [Client::Cursor, Client::Claude, Client::VsCode];
+
+    /// `--client` value: `cursor`, `claude`, `vscode` or `all`, or several
+    /// of them comma-separated.
+    pub fn parse_list(value: &str) -> Result<Vec<Client>, String> {
+        let mut clients = Vec::new();
+        for name in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let parsed: &[Client] = match name.to_ascii_lowercase().as_str() {
+                "cursor" => &[Client::Cursor],
+                "claude" | "claude-desktop" => &[Client::Claude],
+                "vscode" | "vs-code" | "code" => &[Client::VsCode],
+                "all" => &Client::ALL,
+                _ => return Err(format!("Unknown client '{}': expected cursor, claude, vscode or all", name)),
+            };
+            for client in parsed {
+                if !clients.contains(client) {
+                    clients.push(*client);
+                }
+            }
+        }
+        if clients.is_empty() {
+            return Err("--client needs cursor, claude, vscode or all".to_string());
+        }
+        Ok(clients)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Client::Cursor => "Cursor",
+            Client::Claude => "Claude Desktop",
+            Client::VsCode => "VS Code",
+        }
+    }
+
+    /// The client's user-level config file.
+    pub fn config_path(self) -> Option<PathBuf> {
+        match self {
+            Client::Cursor => dirs::home_dir().map(|home| home.join(".cursor").join("mcp.json")),
+            Client::Claude => dirs::config_dir().map(|dir| dir.join("Claude").join("claude_desktop_config.json")),
+            Client::VsCode => dirs::config_dir().map(|dir| dir.join("Code").join("User").join("mcp.json")),
+        }
+    }
+
+    /// Key of the server map in the config file.
+    fn servers_key(self) -> &'static str {
+        match self {
+            Client::VsCode => "servers",
+            Client::Cursor | Client::Claude => "mcpServers",
+        }
+    }
+
+    /// The `McpHub` server entry. Claude Desktop only launches stdio
+    /// servers, so it reaches the SSE endpoint through `mcp-remote`.
+    fn entry(self, token: &str, bind_addr: SocketAddr) -> serde_json::Value {
+        let url = format!("{}/sse", client_base_url(bind_addr));
+        let bearer = format!("Bearer {}", token);
+        match self {
+            Client::Cursor => json!({"url": url, "headers": {"Authorization": bearer}}),
+            Client::VsCode => json!({"type": "sse", "url": url, "headers": {"Authorization": bearer}}),
+            Client::Claude => json!({
+                "command": "npx",
+                "args": ["-y", "mcp-remote", url, "--header", "Authorization:${AUTH_HEADER}"],
+                "env": {"AUTH_HEADER": bearer},
+            }),
+        }
+    }
+}
+
+/// `path` with the home directory shown as `~`.
+fn display_path(path: &std::path::Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(PathBuf::from)) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Config snippet for `client` pointing at the SSE endpoint of a server
+/// bound to `bind_addr`, with the given token.
+pub fn client_config_snippet(token: &str, bind_addr: SocketAddr, client: Client) -> String {
+    let config = json!({ client.servers_key(): { SERVER_NAME: client.entry(token, bind_addr) } });
+    let path = client.config_path().map_or_else(|| "config file".to_string(), |p| display_path(&p));
+    let body = serde_json::to_string_pretty(&config).unwrap_or_default();
+    let indented: Vec<String> = body.lines().map(|line| format!("  {}", line)).collect();
+    format!("  {} config ({}):\n{}", client.label(), path, indented.join("\n"))
+}
+
+/// Name of the hub's entry in client configs.
+const SERVER_NAME: &str = "McpHub";
+
+/// `existing` config file text with the `McpHub` entry for `client` set,
+/// every other key and server kept.
+fn merge_client_entry(existing: Option<&str>, client: Client, token: &str, bind_addr: SocketAddr) -> Result<String, String> {
+    let mut config = match existing.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(text) => serde_json::from_str(text).map_err(|e| format!("not valid JSON ({}); left unchanged", e))?,
+        None => json!({}),
+    };
+    let Some(root) = config.as_object_mut() else {
+        return Err("not a JSON object; left unchanged".to_string());
+    };
+    let servers = root.entry(client.servers_key()).or_insert_with(|| json!({}));
+    let Some(servers) = servers.as_object_mut() else {
+        return Err(format!("\"{}\" is not an object; left unchanged", client.servers_key()));
+    };
+    servers.insert(SERVER_NAME.to_string(), client.entry(token, bind_addr));
+    serde_json::to_string_pretty(&config).map_err(|e| e.to_string())
+}
+
+/// Add (or update) the `McpHub` entry in each client's config file. A
+/// client whose config directory doesn't exist isn't installed and is
+/// skipped.
+pub fn write_client_configs(token: &str, bind_addr: SocketAddr, clients: &[Client]) {
+    for &client in clients {
+        let Some(path) = client.config_path() else { continue };
+        if !path.parent().is_some_and(|dir| dir.is_dir()) {
+            println!("- {}: not found, skipped ({})", client.label(), display_path(&path));
+            continue;
+        }
+        let existing = fs::read_to_string(&path).ok();
+        let result = merge_client_entry(existing.as_deref(), client, token, bind_addr)
+            .and_then(|text| fs::write(&path, text + "\n").map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => println!("✓ {}: added McpHub to {}", client.label(), display_path(&path)),
+            Err(e) => eprintln!("✗ {}: {}: {}", client.label(), display_path(&path), e),
+        }
+    }
+}
+
+/// Stands in for the token when printing with `--redact-token`.
+pub const TOKEN_PLACEHOLDER: &str = "<your-token>";
+
+const PLAIN_TOKEN_WARNING: &str =
+    "Warning: stdout is not a terminal; the auth token below is printed in plain text. Use --redact-token to hide it.";
+
+/// Text to print for the clients' configs, and a warning to show first when
+/// the live token is headed somewhere other than a terminal (logs, CI, a
+/// pipe).
+fn render_client_config(
+    token: &str,
+    bind_addr: SocketAddr,
+    redact: bool,
+    stdout_is_tty: bool,
+    clients: &[Client],
+) -> (Option<&'static str>, String) {
+    let render = |token: &str| clients.iter().map(|&client| client_config_snippet(token, bind_addr, client)).collect::<Vec<_>>().join("\n\n");
+    if redact {
+        return (None, render(TOKEN_PLACEHOLDER));
+    }
+    let warning = if stdout_is_tty { None } else { Some(PLAIN_TOKEN_WARNING) };
+    (warning, render(token))
+}
+
+/// Print the clients' configs, redacted if asked. The warning goes to stderr.
+pub fn print_client_config(token: &str, bind_addr: SocketAddr, redact: bool, clients: &[Client]) {
+    use std::io::IsTerminal;
+    let (warning, text) = render_client_config(token, bind_addr, redact, std::io::stdout().is_terminal(), clients);
+    if let Some(warning) = warning {
+        eprintln!("{}", warning);
+    }
+    println!("{}", text);
+}
+
+/// Arguments of the auto-started `serve` command. A non-default address is
+/// passed along so the daemon binds where the printed config points, and
+/// the log options so it logs where the service manager expects.
+fn serve_args(bind_addr: SocketAddr, options: &InstallOptions) -> Vec<String> {
+    let mut args = vec!["serve".to_string()];
+    if bind_addr.to_string() != DEFAULT_BIND_ADDR {
+        args.push("--bind".to_string());
+        args.push(bind_addr.to_string());
+    }
+    if options.log_stdout {
+        args.push("--log-stdout".to_string());
+    } else if let Some(path) = &options.log_path {
+        args.push("--log-path".to_string());
+        args.push(path.display().to_string());
+    }
+    args
+}
+
+/// Written to `~/.McpHub/config.json` by `install` when there is no config
+/// yet. JSON rather than TOML so the dashboard can add and edit servers in
+/// it. Until a server is listed, servers are read from the clients' configs.
+const DEFAULT_CONFIG_JSON: &str = r#"{
+  "mcpServers": {},
+  "settings": {
+    "mode": "discover",
+    "idleTimeout": 300
+  }
+}
+"#;
+
+/// The data directory to hand the daemon in `MCPHUB_HOME`, when it isn't
+/// `~/.McpHub`: a service doesn't see the variables of this shell.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn hub_dir_override() -> Option<String> {
+    let dir = crate::config::hub_dir()?;
+    let default = dirs::home_dir().map(|home| home.join(".McpHub"));
+    (default.as_ref() != Some(&dir)).then(|| dir.display().to_string())
+}
+
+/// Write the default config.json if neither config file exists.
+fn scaffold_config() {
+    let Some(dir) = crate::config::hub_dir() else { return };
+    if dir.join("config.json").exists() || dir.join("config.toml").exists() {
+        return;
+    }
+    let path = dir.join("config.json");
+    let _ = fs::create_dir_all(&dir);
+    match fs::write(&path, DEFAULT_CONFIG_JSON) {
+        Ok(()) => println!("✓ Created {}; list your servers there", path.display()),
+        Err(e) => eprintln!("✗ Could not create {}: {}", path.display(), e),
+    }
+}
+
+/// What `--dry-run` would do: file contents go to stdout as they would be
+/// written (so the output can be redirected to a file), paths and commands
+/// to stderr.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn print_dry_run(files: &[(&std::path::Path, &str)], commands: &[Vec<String>]) {
+    for (path, content) in files {
+        eprintln!("Would write {}:", path.display());
+        println!("{}", content);
+    }
+    for command in commands {
+        eprintln!("Would run: {}", command.join(" "));
+    }
+}
+
+/// How an existing registration compares with the one `install` is about
+/// to write.
+#[derive(Debug, PartialEq)]
+enum Registration {
+    New,
+    Unchanged,
+    /// What differs, one line each.
+    Changed(Vec<String>),
+}
+
+/// Compare the registered command line and file (or value) with the new
+/// ones. A binary path that no longer exists, e.g. after moving McpHub, is
+/// called out.
+fn compare_registration(old: Option<(Vec<String>, String)>, command: &[String], text: &str) -> Registration {
+    let Some((old_command, old_text)) = old else { return Registration::New };
+    let mut changes = Vec::new();
+    let old_binary = old_command.first().map_or("", String::as_str);
+    if old_binary != command[0] {
+        let stale = if std::path::Path::new(old_binary).exists() { "" } else { " (no longer exists)" };
+        changes.push(format!("binary: {}{} -> {}", old_binary, stale, command[0]));
+    }
+    if old_command.get(1..).unwrap_or_default() != &command[1..] {
+        changes.push(format!("arguments: '{}' -> '{}'", old_command.get(1..).unwrap_or_default().join(" "), command[1..].join(" ")));
+    }
+    if changes.is_empty() && old_text.trim() != text.trim() {
+        changes.push("other settings (log path, restart policy)".to_string());
+    }
+    if changes.is_empty() {
+        Registration::Unchanged
+    } else {
+        Registration::Changed(changes)
+    }
+}
+
+/// Print what an update of the registration changes; to stderr with
+/// `--dry-run`, whose stdout is the file itself.
+fn print_changes(changes: &[String], dry_run: bool) {
+    let lines = std::iter::once("Updating the existing registration:".to_string()).chain(changes.iter().map(|c| format!("  ~ {}", c)));
+    for line in lines {
+        if dry_run {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// `ProgramArguments` of a LaunchAgent plist.
+#[cfg(any(target_os = "macos", test))]
+fn plist_command(plist: &str) -> Vec<String> {
+    let Some(start) = plist.find("<key>ProgramArguments</key>") else { return Vec::new() };
+    let array = &plist[start..];
+    let array = &array[..array.find("</array>").unwrap_or(array.len())];
+    array
+        .split("<string>")
+        .skip(1)
+        .filter_map(|piece| piece.split_once("</string>").map(|(value, _)| xml_unescape(value)))
+        .collect()
+}
+
+/// Escape text for a plist `<string>`.
+#[cfg(any(target_os = "macos", test))]
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// A systemd `Environment="NAME=value"` line: `%` would start a specifier,
+/// and quotes and backslashes would end or escape the quoted value.
+#[cfg(any(target_os = "linux", test))]
+fn systemd_environment(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%");
+    format!("Environment=\"{}={}\"\n", name, value)
+}
+
+/// `ExecStart` of a systemd unit, split on whitespace as written by `install`.
+#[cfg(any(target_os = "linux", test))]
+fn unit_command(unit: &str) -> Vec<String> {
+    unit.lines()
+        .find_map(|line| line.strip_prefix("ExecStart="))
+        .map(|command| command.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// A Windows command line as `install` writes it: `"binary" args...`.
+#[cfg(any(target_os = "windows", test))]
+fn split_command_line(command: &str) -> Vec<String> {
+    let command = command.trim();
+    let (binary, rest) = match command.strip_prefix('"').and_then(|c| c.split_once('"')) {
+        Some((binary, rest)) => (binary, rest),
+        None => command.split_once(' ').unwrap_or((command, "")),
+    };
+    std::iter::once(binary.to_string()).chain(rest.split_whitespace().map(String::from)).collect()
+}
+
+/// Flags of `McpHub install`.
+#[derive(Debug, Default, Clone)]
+pub struct InstallOptions {
+    /// Print a placeholder instead of the token in the client config.
+    pub redact_token: bool,
+    /// Restart a service that is already running, on the binary just installed.
+    pub force: bool,
+    /// Only print what would be written and run.
+    pub dry_run: bool,
+    /// Windows: register a service started at boot instead of a Run key.
+    pub service: bool,
+    /// Log file instead of `~/.McpHub/mcphub.log`.
+    pub log_path: Option<PathBuf>,
+    /// Log to stdout, for a supervisor that collects it (journald, a
+    /// container runtime).
+    pub log_stdout: bool,
+    /// Clients whose config is printed; Cursor's when empty.
+    pub clients: Vec<Client>,
+    /// Also add the config to each client's config file.
+    pub write_config: bool,
+}
+
+/// How long `install` waits for the daemon it started to answer.
+const VERIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// Log lines shown when it doesn't.
+const VERIFY_LOG_LINES: usize = 10;
+
+/// The service manager accepting the entry doesn't mean McpHub runs: wait
+/// for it to answer on `bind_addr`, and if it never does (bad config, port
+/// taken, crash loop) show the end of `log`, or point at the journal when
+/// there is no file.
+fn verify_started(bind_addr: SocketAddr, log: Option<&std::path::Path>) {
+    let deadline = std::time::Instant::now() + VERIFY_TIMEOUT;
+    loop {
+        if let Some(info) = crate::dashboard::daemon_get(bind_addr, "/info") {
+            let pid = info["pid"].as_u64().map(|pid| format!(" (pid {})", pid)).unwrap_or_default();
+            println!("✓ McpHub is serving on {}{}", client_base_url(bind_addr), pid);
+            return;
+        }
+        if std::time::Instant::now() >= deadline {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+    eprintln!("✗ McpHub is not answering on {} after {}s", client_base_url(bind_addr), VERIFY_TIMEOUT.as_secs());
+    let Some(log) = log else {
+        eprintln!("  See: journalctl --user -u mcphub -n {}", VERIFY_LOG_LINES);
+        return;
+    };
+    match crate::logs::tail(log, VERIFY_LOG_LINES) {
+        Ok((lines, _)) if !lines.is_empty() => {
+            eprintln!("  Last lines of {}:", log.display());
+            for line in lines {
+                eprintln!("    {}", line);
+            }
+        }
+        _ => eprintln!("  Nothing logged to {} yet", log.display()),
+    }
+}
+
+/// Print the client configs asked for, and write them with `--write-config`.
+fn report_client_config(token: &str, bind_addr: SocketAddr, options: &InstallOptions) {
+    let clients = if options.clients.is_empty() { &[Client::Cursor][..] } else { &options.clients };
+    print_client_config(token, bind_addr, options.redact_token, clients);
+    if options.write_config {
+        println!();
+        write_client_configs(token, bind_addr, clients);
+    }
+}
+
+/// Register auto-start.
+pub fn install(options: InstallOptions, bind_addr: SocketAddr) {
+    let InstallOptions { force, dry_run, .. } = options;
+    #[cfg(not(target_os = "windows"))]
+    if options.service {
+        eprintln!("! --service only applies on Windows; installing the usual auto-start entry");
+    }
+    if !dry_run {
+        scaffold_config();
+    }
     let binary = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("McpHub"));
     let binary_str = binary.display().to_string();
+    // The daemon writes (and rotates) its log file itself
+    let mut daemon = options.clone();
+    if !daemon.log_stdout {
+        daemon.log_path.get_or_insert_with(crate::logs::default_path);
+    }
+    let serve_args = serve_args(bind_addr, &daemon);
 
     #[cfg(target_os = "macos")]
     {
         let home = dirs::home_dir().expect("Cannot find home directory");
         let plist_dir = home.join("Library/LaunchAgents");
-        let _ = fs::create_dir_all(&plist_dir);
+        let log_path = options.log_path.clone().unwrap_or_else(crate::logs::default_path);
         let plist_path = plist_dir.join(format!("{}.plist", LABEL));
 
         let plist = format!(
@@ -28,28 +453,71 @@ r#"<?xml version="1.0" encoding="UTF-8"?>
     <key>ProgramArguments</key>
     <array>
         <string>{binary}</string>
-        <string>serve</string>
+{args}
     </array>
-    <key>RunAtLoad</key>
+{env}    <key>RunAtLoad</key>
     <true/>
     <key>KeepAlive</key>
     <true/>
     <key>StandardErrorPath</key>
-    <string>{home}/.McpHub/mcphub.log</string>
+    <string>{log}</string>
     <key>StandardOutPath</key>
-    <string>/dev/null</string>
+    <string>{out}</string>
     <key>ProcessType</key>
     <string>Background</string>
 </dict>
 </plist>"#,
             label = LABEL,
-            binary = binary_str,
-            home = home.display(),
+            binary = xml_escape(&binary_str),
+            args = serve_args
+                .iter()
+                .map(|a| format!("        <string>{}</string>", xml_escape(a)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            env = hub_dir_override()
+                .map(|dir| format!("    <key>EnvironmentVariables</key>\n    <dict>\n        <key>{}</key>\n        <string>{}</string>\n    </dict>\n", crate::config::HOME_ENV, xml_escape(&dir)))
+                .unwrap_or_default(),
+            // Not the log file itself: the daemon rotates that one
+            log = xml_escape(&crate::logs::stderr_path(&log_path).display().to_string()),
+            // launchd has no collector of its own: stdout goes to the log file
+            out = if options.log_stdout { xml_escape(&log_path.display().to_string()) } else { "/dev/null".to_string() },
         );
 
-        fs::write(&plist_path, &plist).expect("Failed to write LaunchAgent plist");
+        let command: Vec<String> = std::iter::once(binary_str.clone()).chain(serve_args.iter().cloned()).collect();
+        let existing = fs::read_to_string(&plist_path).ok().map(|text| (plist_command(&text), text));
+        let registration = compare_registration(existing, &command, &plist);
+        let running = service_status() == Service::Installed { running: Some(true) };
+        if let Registration::Changed(changes) = &registration {
+            print_changes(changes, dry_run);
+        }
+        // An unchanged agent that is running is left alone
+        let unchanged = registration == Registration::Unchanged && !force;
+        if unchanged && running {
+            if dry_run {
+                eprintln!("{} is up to date and loaded; nothing would change", plist_path.display());
+                return;
+            }
+            let token = crate::dashboard::service_auth_token();
+            println!("✓ McpHub is already installed as LaunchAgent and up to date");
+            println!("  Plist: {}", plist_path.display());
+            println!();
+            report_client_config(&token, bind_addr, &options);
+            return;
+        }
+
+        let plist_arg = plist_path.display().to_string();
+        if dry_run {
+            let launchctl = |action: &str| ["launchctl", action, &plist_arg].map(String::from).to_vec();
+            print_dry_run(&[(&plist_path, &plist)], &[launchctl("unload"), launchctl("load")]);
+            return;
+        }
+        if !unchanged {
+            let _ = fs::create_dir_all(&plist_dir);
+            fs::write(&plist_path, &plist).expect("Failed to write LaunchAgent plist");
+        }
 
-        // Load it
+        // Load it. Unloading first restarts a running agent, so `force`
+        // needs nothing extra here.
         let _ = std::process::Command::new("launchctl")
             .args(["unload", &plist_path.display().to_string()])
             .output();
@@ -59,21 +527,14 @@ r#"<?xml version="1.0" encoding="UTF-8"?>
             .expect("Failed to run launchctl");
 
         if output.status.success() {
-            let token = crate::dashboard::get_auth_token();
+            let token = crate::dashboard::service_auth_token();
             println!("✓ McpHub installed as LaunchAgent");
             println!("  Plist: {}", plist_path.display());
-            println!("  Log:   ~/.McpHub/mcphub.log");
+            println!("  Log:   {}", log_path.display());
             println!("  McpHub will start automatically at login.");
+            verify_started(bind_addr, Some(&log_path));
             println!();
-            println!("  Cursor config (~/.cursor/mcp.json):");
-            println!("  {{");
-            println!("    \"mcpServers\": {{");
-            println!("      \"McpHub\": {{");
-            println!("        \"url\": \"http://127.0.0.1:24680/sse\",");
-            println!("        \"headers\": {{\"Authorization\": \"Bearer {}\"}}", token);
-            println!("      }}");
-            println!("    }}");
-            println!("  }}");
+            report_client_config(&token, bind_addr, &options);
         } else {
             eprintln!("✗ launchctl load failed: {}", String::from_utf8_lossy(&output.stderr));
         }
@@ -83,51 +544,104 @@ r#"<?xml version="1.0" encoding="UTF-8"?>
     {
         let home = dirs::home_dir().expect("Cannot find home directory");
         let service_dir = home.join(".config/systemd/user");
-        let _ = fs::create_dir_all(&service_dir);
+        let log_path = options.log_path.clone().unwrap_or_else(crate::logs::default_path);
         let service_path = service_dir.join("mcphub.service");
 
         let service = format!(
 r#"[Unit]
 Description=McpHub MCP Proxy Server
 After=network.target
+StartLimitIntervalSec=300
+StartLimitBurst=10
 
 [Service]
 Type=simple
-ExecStart={binary} serve
+ExecStart={binary} {args}
 Restart=always
 RestartSec=5
-StandardError=append:{home}/.McpHub/mcphub.log
+RestartPreventExitStatus={give_up}
+{env}{log}
 
 [Install]
 WantedBy=default.target"#,
             binary = binary_str,
-            home = home.display(),
+            args = serve_args.join(" "),
+            give_up = crate::crashloop::EXIT_CRASH_LOOP,
+            env = hub_dir_override().map(|dir| systemd_environment(crate::config::HOME_ENV, &dir)).unwrap_or_default(),
+            // stdout and stderr go to the journal by default
+            log = if options.log_stdout { "StandardOutput=journal".to_string() } else { format!("StandardError=append:{}", crate::logs::stderr_path(&log_path).display()) },
         );
 
-        fs::write(&service_path, &service).expect("Failed to write systemd unit");
+        let command: Vec<String> = std::iter::once(binary_str.clone()).chain(serve_args.iter().cloned()).collect();
+        let existing = fs::read_to_string(&service_path).ok().map(|text| (unit_command(&text), text));
+        let registration = compare_registration(existing, &command, &service);
+        if let Registration::Changed(changes) = &registration {
+            print_changes(changes, dry_run);
+        }
+        let unchanged = registration == Registration::Unchanged;
 
-        let _ = std::process::Command::new("systemctl")
-            .args(["--user", "daemon-reload"])
-            .output();
+        if dry_run {
+            let systemctl = |args: &[&str]| ["systemctl", "--user"].iter().chain(args).map(|a| a.to_string()).collect();
+            let mut commands = vec![systemctl(&["enable", "--now", "mcphub"])];
+            if !unchanged {
+                commands.insert(0, systemctl(&["daemon-reload"]));
+            }
+            if force {
+                commands.push(systemctl(&["restart", "mcphub"]));
+            }
+            if unchanged {
+                eprintln!("{} is up to date; it would not be rewritten", service_path.display());
+                print_dry_run(&[], &commands);
+            } else {
+                print_dry_run(&[(&service_path, &service)], &commands);
+            }
+            return;
+        }
+        let was_running = service_status() == Service::Installed { running: Some(true) };
+        if !unchanged {
+            let _ = fs::create_dir_all(&service_dir);
+            fs::write(&service_path, &service).expect("Failed to write systemd unit");
+            let _ = std::process::Command::new("systemctl")
+                .args(["--user", "daemon-reload"])
+                .output();
+        }
         let output = std::process::Command::new("systemctl")
             .args(["--user", "enable", "--now", "mcphub"])
             .output()
             .expect("Failed to run systemctl");
 
+        if output.status.success() && force {
+            // `enable --now` leaves a running service alone
+            let restarted = std::process::Command::new("systemctl")
+                .args(["--user", "restart", "mcphub"])
+                .status()
+                .is_ok_and(|s| s.success());
+            if restarted {
+                println!("✓ Restarted the running service on the new binary");
+            } else {
+                eprintln!("! Could not restart mcphub; run: systemctl --user restart mcphub");
+            }
+        }
+
         if output.status.success() {
-            let token = crate::dashboard::get_auth_token();
-            println!("✓ McpHub installed as systemd user service");
+            let token = crate::dashboard::service_auth_token();
+            if unchanged {
+                println!("✓ McpHub is already installed as systemd user service and up to date");
+            } else {
+                println!("✓ McpHub installed as systemd user service");
+            }
             println!("  Unit: {}", service_path.display());
+            if !unchanged && was_running && !force {
+                println!("  The running service keeps the old settings until restarted (--force).");
+            }
+            if options.log_stdout {
+                println!("  Log:  journalctl --user -u mcphub");
+            } else {
+                println!("  Log:  {}", log_path.display());
+            }
+            verify_started(bind_addr, (!options.log_stdout).then_some(log_path.as_path()));
             println!();
-            println!("  Cursor config (~/.cursor/mcp.json):");
-            println!("  {{");
-            println!("    \"mcpServers\": {{");
-            println!("      \"McpHub\": {{");
-            println!("        \"url\": \"http://127.0.0.1:24680/sse\",");
-            println!("        \"headers\": {{\"Authorization\": \"Bearer {}\"}}", token);
-            println!("      }}");
-            println!("    }}");
-            println!("  }}");
+            report_client_config(&token, bind_addr, &options);
         } else {
             eprintln!("✗ systemctl enable failed: {}", String::from_utf8_lossy(&output.stderr));
         }
@@ -135,39 +649,67 @@ WantedBy=default.target"#,
 
     #[cfg(target_os = "windows")]
     {
+        // An installed service stays the mechanism, e.g. when `update` reinstalls
+        if options.service || windows_service_state().is_some() {
+            install_windows_service(&binary_str, bind_addr, &options);
+            return;
+        }
         // Windows: add to registry Run key
-        let key_path = r"Software\Microsoft\Windows\CurrentVersion\Run";
+        let command = format!("\"{}\" {}", binary_str, serve_args.join(" "));
+        let reg_args = ["add", RUN_KEY, "/v", "McpHub", "/t", "REG_SZ", "/d", &command, "/f"];
+        let existing = run_key_command().map(|old| (split_command_line(&old), old));
+        match compare_registration(existing, &split_command_line(&command), &command) {
+            Registration::Changed(changes) => print_changes(&changes, dry_run),
+            Registration::Unchanged if dry_run => {
+                eprintln!("The Run key is up to date; nothing would change");
+                return;
+            }
+            Registration::Unchanged => {
+                let token = crate::dashboard::service_auth_token();
+                println!("✓ McpHub is already in the Windows startup registry and up to date");
+                println!();
+                report_client_config(&token, bind_addr, &options);
+                return;
+            }
+            Registration::New => {}
+        }
+        if dry_run {
+            // The value written is the command line itself
+            println!("{}", command);
+            eprintln!("Would run: reg {}", reg_args.join(" "));
+            return;
+        }
         let output = std::process::Command::new("reg")
-            .args(["add", &format!("HKCU\\{}", key_path), "/v", "McpHub", "/t", "REG_SZ", "/d", &format!("\"{}\" serve", binary_str), "/f"])
+            .args(reg_args)
             .output()
             .expect("Failed to run reg");
 
         if output.status.success() {
-            let token = crate::dashboard::get_auth_token();
+            let token = crate::dashboard::service_auth_token();
             println!("✓ McpHub installed in Windows startup registry");
+            if force {
+                println!("  A running McpHub keeps the old binary until it is restarted.");
+            }
             println!();
-            println!("  Cursor config (~/.cursor/mcp.json):");
-            println!("  {{");
-            println!("    \"mcpServers\": {{");
-            println!("      \"McpHub\": {{");
-            println!("        \"url\": \"http://127.0.0.1:24680/sse\",");
-            println!("        \"headers\": {{\"Authorization\": \"Bearer {}\"}}", token);
-            println!("      }}");
-            println!("    }}");
-            println!("  }}");
+            report_client_config(&token, bind_addr, &options);
         } else {
             eprintln!("✗ Registry write failed: {}", String::from_utf8_lossy(&output.stderr));
         }
     }
 }
 
-pub fn uninstall() {
+/// Remove auto-start, and with `purge` the data directory too. With
+/// `dry_run`, only print what would be removed and run.
+pub fn uninstall(dry_run: bool, purge: bool) {
     #[cfg(target_os = "macos")]
     {
         let home = dirs::home_dir().expect("Cannot find home directory");
         let plist_path = home.join("Library/LaunchAgents").join(format!("{}.plist", LABEL));
 
-        if plist_path.exists() {
+        if plist_path.exists() && dry_run {
+            eprintln!("Would run: launchctl unload {}", plist_path.display());
+            eprintln!("Would remove {}", plist_path.display());
+        } else if plist_path.exists() {
             let _ = std::process::Command::new("launchctl")
                 .args(["unload", &plist_path.display().to_string()])
                 .output();
@@ -183,7 +725,11 @@ pub fn uninstall() {
         let home = dirs::home_dir().expect("Cannot find home directory");
         let service_path = home.join(".config/systemd/user/mcphub.service");
 
-        if service_path.exists() {
+        if service_path.exists() && dry_run {
+            eprintln!("Would run: systemctl --user disable --now mcphub");
+            eprintln!("Would remove {}", service_path.display());
+            eprintln!("Would run: systemctl --user daemon-reload");
+        } else if service_path.exists() {
             let _ = std::process::Command::new("systemctl")
                 .args(["--user", "disable", "--now", "mcphub"])
                 .output();
@@ -199,10 +745,497 @@ pub fn uninstall() {
 
     #[cfg(target_os = "windows")]
     {
-        let key_path = r"Software\Microsoft\Windows\CurrentVersion\Run";
-        let _ = std::process::Command::new("reg")
-            .args(["delete", &format!("HKCU\\{}", key_path), "/v", "McpHub", "/f"])
-            .output();
-        println!("✓ McpHub removed from Windows startup");
+        // Whichever was installed: the service, the Run key, or both
+        if windows_service_state().is_some() {
+            if dry_run {
+                eprintln!("Would run: sc stop {}", SERVICE_NAME);
+                eprintln!("Would run: sc delete {}", SERVICE_NAME);
+            } else {
+                stop_windows_service();
+                match sc(&["delete", SERVICE_NAME]) {
+                    Ok(o) if o.status.success() => println!("✓ McpHub Windows service removed"),
+                    Ok(o) => eprintln!(
+                        "✗ sc delete failed: {} (needs an elevated prompt)",
+                        String::from_utf8_lossy(&o.stdout).trim()
+                    ),
+                    Err(e) => eprintln!("✗ Failed to run sc: {}", e),
+                }
+            }
+        }
+        if run_key_installed() {
+            let reg_args = ["delete", RUN_KEY, "/v", "McpHub", "/f"];
+            if dry_run {
+                eprintln!("Would run: reg {}", reg_args.join(" "));
+            } else {
+                let _ = std::process::Command::new("reg").args(reg_args).output();
+                println!("✓ McpHub removed from Windows startup");
+            }
+        }
+    }
+
+    if purge {
+        purge_data(dry_run);
+    }
+}
+
+/// Directories `uninstall --purge` cleans: `~/.McpHub` (config, token,
+/// schema cache, logs) and on Windows the service's copy.
+fn data_dirs() -> Vec<PathBuf> {
+    let dirs = crate::config::hub_dir();
+    #[cfg(target_os = "windows")]
+    let dirs = dirs.into_iter().chain(Some(service_hub_dir()));
+    dirs.into_iter().collect()
+}
+
+/// Files McpHub writes to its data directory. `--purge` deletes only these,
+/// so a `MCPHUB_HOME` pointing at a shared directory keeps everything else.
+const DATA_FILES: &[&str] = &[
+    "config.json", "config.toml", "secrets.json", "auth-token", "auth-token.tmp", "sessions.json", "sessions.json.tmp", "starts.json",
+    "schema-cache.json", "schema-cache.json.gz", "schema-cache.json.lock", "schema-cache.json.tmp", "schema-cache.json.gz.tmp",
+    "audit.log", "mcphub.log", "mcphub.stderr.log",
+];
+
+/// Subdirectories of the data directory McpHub fills, with the extension of
+/// the files it puts there.
+const DATA_SUBDIRS: &[(&str, &str)] = &[("recordings", "jsonl")];
+
+/// McpHub's own files under `dir`, relative to it and sorted: the known
+/// names, rotated logs (`mcphub.log.1`, ...) and the subdirectories' files.
+fn owned_files(dir: &std::path::Path) -> Vec<PathBuf> {
+    let is_rotated = |name: &str| {
+        ["mcphub.log.", "audit.log."].iter().any(|prefix| {
+            name.strip_prefix(prefix).is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+    };
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| DATA_FILES.contains(&name.as_str()) || is_rotated(name))
+        .map(PathBuf::from)
+        .collect();
+    for (subdir, extension) in DATA_SUBDIRS {
+        for entry in fs::read_dir(dir.join(subdir)).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == *extension) {
+                files.push(PathBuf::from(subdir).join(entry.file_name()));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Delete McpHub's files from the data directories, listing each one, then
+/// the directories themselves if nothing else is left in them.
+fn purge_data(dry_run: bool) {
+    for dir in data_dirs() {
+        if !dir.exists() {
+            println!("{} is already gone", dir.display());
+            continue;
+        }
+        let files = owned_files(&dir);
+        if dry_run {
+            eprintln!("Would remove from {}:", dir.display());
+            for file in &files {
+                eprintln!("  {}", file.display());
+            }
+            continue;
+        }
+        println!("Removing from {}:", dir.display());
+        for file in &files {
+            match fs::remove_file(dir.join(file)) {
+                Ok(()) => println!("  ✓ {}", file.display()),
+                Err(e) => eprintln!("  ✗ {}: {}", file.display(), e),
+            }
+        }
+        for (subdir, _) in DATA_SUBDIRS {
+            let _ = fs::remove_dir(dir.join(subdir));
+        }
+        // Fails, and keeps the directory, if anything else is in it
+        match fs::remove_dir(&dir) {
+            Ok(()) => println!("✓ Removed {}", dir.display()),
+            Err(_) => println!("Kept {}: it holds files McpHub didn't create", dir.display()),
+        }
+    }
+}
+
+/// Auto-start registration as the native service manager reports it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Service {
+    NotInstalled,
+    /// Registered; `running` is None where the service manager can't tell
+    /// (the Windows Run key only starts McpHub at login).
+    Installed { running: Option<bool> },
+}
+
+/// Ask launchctl, systemctl or the registry whether McpHub is installed
+/// and running.
+pub fn service_status() -> Service {
+    #[cfg(target_os = "macos")]
+    {
+        let home = dirs::home_dir().expect("Cannot find home directory");
+        if !home.join("Library/LaunchAgents").join(format!("{}.plist", LABEL)).exists() {
+            return Service::NotInstalled;
+        }
+        // `launchctl list <label>` prints `"PID" = n;` while the agent runs
+        let running = std::process::Command::new("launchctl")
+            .args(["list", LABEL])
+            .output()
+            .ok()
+            .map(|o| o.status.success() && String::from_utf8_lossy(&o.stdout).contains("\"PID\""));
+        Service::Installed { running }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let home = dirs::home_dir().expect("Cannot find home directory");
+        if !home.join(".config/systemd/user/mcphub.service").exists() {
+            return Service::NotInstalled;
+        }
+        let running = std::process::Command::new("systemctl")
+            .args(["--user", "is-active", "mcphub"])
+            .output()
+            .ok()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim() == "active");
+        Service::Installed { running }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        match windows_service_state() {
+            Some(state) => Service::Installed { running: Some(state == "RUNNING") },
+            None if run_key_installed() => Service::Installed { running: None },
+            None => Service::NotInstalled,
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    {
+        Service::NotInstalled
+    }
+}
+
+// ─── Windows service ─────────────────────────────────────────
+
+/// Value under this key starts McpHub at login (the default on Windows).
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+#[cfg(target_os = "windows")]
+use crate::winservice::SERVICE_NAME;
+
+/// Files the service needs from the installing user's `~/.McpHub`.
+#[cfg(target_os = "windows")]
+const SERVICE_FILES: [&str; 6] = ["config.json", "config.toml", "secrets.json", "auth-token", "schema-cache.json", "schema-cache.json.gz"];
+
+#[cfg(target_os = "windows")]
+fn sc(args: &[&str]) -> std::io::Result<std::process::Output> {
+    std::process::Command::new("sc").args(args).output()
+}
+
+#[cfg(target_os = "windows")]
+fn run_key_installed() -> bool {
+    std::process::Command::new("reg")
+        .args(["query", RUN_KEY, "/v", "McpHub"])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Command line of the Run key entry, if there is one.
+#[cfg(target_os = "windows")]
+fn run_key_command() -> Option<String> {
+    let output = std::process::Command::new("reg")
+        .args(["query", RUN_KEY, "/v", "McpHub"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().find_map(|l| l.split_once("REG_SZ")).map(|(_, value)| value.trim().to_string())
+}
+
+/// `binPath` of the McpHub service, if there is one.
+#[cfg(target_os = "windows")]
+fn windows_service_command() -> Option<String> {
+    let output = sc(&["qc", SERVICE_NAME]).ok().filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .find_map(|l| l.trim_start().strip_prefix("BINARY_PATH_NAME"))
+        .and_then(|l| l.split_once(':'))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// State of the McpHub service as `sc query` names it (`RUNNING`,
+/// `STOPPED`, ...), or None if there is no such service.
+#[cfg(target_os = "windows")]
+fn windows_service_state() -> Option<String> {
+    let output = sc(&["query", SERVICE_NAME]).ok().filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let state = stdout.lines().find(|l| l.trim_start().starts_with("STATE"))?;
+    state.split_whitespace().last().map(String::from)
+}
+
+/// Stop the service and wait up to 30s for it to finish shutting down.
+#[cfg(target_os = "windows")]
+fn stop_windows_service() {
+    let _ = sc(&["stop", SERVICE_NAME]);
+    for _ in 0..60 {
+        if windows_service_state().is_none_or(|state| state == "STOPPED") {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
+
+/// `~/.McpHub` of LocalSystem, the account the service runs as.
+#[cfg(target_os = "windows")]
+fn service_hub_dir() -> PathBuf {
+    let root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string());
+    PathBuf::from(root).join(r"System32\config\systemprofile\.McpHub")
+}
+
+/// Register (or update) the McpHub Windows service: started at boot as
+/// LocalSystem and restarted 5s after a crash, like the systemd unit. The
+/// user's config, token and cache are copied to LocalSystem's home, where
+/// the service reads them. Needs an elevated prompt.
+#[cfg(target_os = "windows")]
+fn install_windows_service(binary: &str, bind_addr: SocketAddr, options: &InstallOptions) {
+    if options.log_stdout {
+        eprintln!("! A service has no stdout; --log-stdout is ignored, use --log-path");
+    }
+    let mut args = serve_args(bind_addr, &InstallOptions { log_stdout: false, ..options.clone() });
+    args[0] = "service".to_string();
+    let bin_path = format!("\"{}\" {}", binary, args.join(" "));
+    let state = windows_service_state();
+    let existing = windows_service_command().map(|old| (split_command_line(&old), old));
+    if let Registration::Changed(changes) = compare_registration(existing, &split_command_line(&bin_path), &bin_path) {
+        print_changes(&changes, options.dry_run);
+    }
+    let running = state.as_deref() == Some("RUNNING");
+    let register = if state.is_some() { "config" } else { "create" };
+    let mut commands: Vec<Vec<&str>> = vec![
+        vec![register, SERVICE_NAME, "binPath=", &bin_path, "start=", "auto", "DisplayName=", "McpHub MCP Proxy Server"],
+        vec!["failure", SERVICE_NAME, "reset=", "86400", "actions=", "restart/5000/restart/5000/restart/5000"],
+    ];
+    if !running || options.force {
+        commands.push(vec!["start", SERVICE_NAME]);
+    }
+    let home = crate::config::hub_dir().expect("Cannot find home directory");
+    let copies: Vec<(PathBuf, PathBuf)> = SERVICE_FILES
+        .iter()
+        .map(|file| (home.join(file), service_hub_dir().join(file)))
+        .filter(|(from, _)| from.exists())
+        .collect();
+    let run_key = ["delete", RUN_KEY, "/v", "McpHub", "/f"];
+
+    if options.dry_run {
+        for (from, to) in &copies {
+            eprintln!("Would copy {} to {}", from.display(), to.display());
+        }
+        if running && options.force {
+            eprintln!("Would run: sc stop {}", SERVICE_NAME);
+        }
+        for command in &commands {
+            eprintln!("Would run: sc {}", command.join(" "));
+        }
+        eprintln!("Would run: reg {}", run_key.join(" "));
+        return;
+    }
+
+    // Created first, so the copy carries the token the printed config uses
+    let token = crate::dashboard::service_auth_token();
+    let _ = fs::create_dir_all(service_hub_dir());
+    for (from, to) in &copies {
+        if let Err(e) = fs::copy(from, to) {
+            eprintln!("✗ Could not copy {} to {}: {}", from.display(), to.display(), e);
+            eprintln!("  Installing the service needs an elevated (Administrator) prompt.");
+            return;
+        }
+    }
+    if running && options.force {
+        stop_windows_service();
+    }
+    for command in &commands {
+        let output = match sc(command) {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("✗ Failed to run sc: {}", e);
+                return;
+            }
+        };
+        // sc reports errors on stdout
+        if !output.status.success() {
+            eprintln!("✗ sc {} failed: {}", command[0], String::from_utf8_lossy(&output.stdout).trim());
+            eprintln!("  Installing the service needs an elevated (Administrator) prompt.");
+            return;
+        }
+    }
+    // A Run key entry left behind would start a second McpHub at login
+    let _ = std::process::Command::new("reg").args(run_key).output();
+
+    println!("✓ McpHub installed as Windows service '{}'", SERVICE_NAME);
+    println!("  Starts at boot as LocalSystem and restarts after a failure.");
+    println!("  Config: {} (copied from {})", service_hub_dir().display(), home.display());
+    let log_path = options.log_path.clone().unwrap_or_else(|| service_hub_dir().join("mcphub.log"));
+    println!("  Log:    {}", log_path.display());
+    if running && !options.force {
+        println!("  The running service keeps the old binary until restarted (--force).");
+    }
+    verify_started(bind_addr, Some(&log_path));
+    println!();
+    report_client_config(&token, bind_addr, options);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_addr() -> SocketAddr {
+        DEFAULT_BIND_ADDR.parse().unwrap()
+    }
+
+    #[test]
+    fn test_default_config_parses() {
+        let json = crate::config::parse_config_json(std::path::Path::new("config.json"), DEFAULT_CONFIG_JSON).unwrap();
+        assert_eq!(json["settings"]["mode"], "discover");
+        assert!(crate::config::parse_servers(&json).is_empty());
+    }
+
+    #[test]
+    fn test_redact_replaces_token() {
+        let (warning, text) = render_client_config("secret-123", default_addr(), true, false, &[Client::Cursor]);
+        assert!(warning.is_none());
+        assert!(!text.contains("secret-123"));
+        assert!(text.contains("Bearer <your-token>"));
+    }
+
+    #[test]
+    fn test_plain_token_warns_when_not_tty() {
+        let (warning, text) = render_client_config("secret-123", default_addr(), false, false, &[Client::Cursor]);
+        assert_eq!(warning, Some(PLAIN_TOKEN_WARNING));
+        assert!(text.contains("Bearer secret-123"));
+
+        let (warning, _) = render_client_config("secret-123", default_addr(), false, true, &[Client::Cursor]);
+        assert!(warning.is_none());
+    }
+
+    #[test]
+    fn test_custom_bind_reflected_in_config_and_serve_args() {
+        let addr: SocketAddr = "0.0.0.0:9000".parse().unwrap();
+        let (_, text) = render_client_config("t", addr, false, true, &[Client::Cursor]);
+        assert!(text.contains(r#""url": "http://localhost:9000/sse""#), "{}", text);
+        let options = InstallOptions::default();
+        assert_eq!(serve_args(addr, &options), ["serve", "--bind", "0.0.0.0:9000"]);
+        assert_eq!(serve_args(default_addr(), &options), ["serve"]);
+    }
+
+    #[test]
+    fn test_log_options_passed_to_serve() {
+        let options = InstallOptions { log_path: Some(PathBuf::from("/var/log/mcphub.log")), ..Default::default() };
+        assert_eq!(serve_args(default_addr(), &options), ["serve", "--log-path", "/var/log/mcphub.log"]);
+        // stdout wins over a path
+        let options = InstallOptions { log_stdout: true, ..options };
+        assert_eq!(serve_args(default_addr(), &options), ["serve", "--log-stdout"]);
+    }
+
+    #[test]
+    fn test_client_list_parsing() {
+        assert_eq!(Client::parse_list("claude").unwrap(), [Client::Claude]);
+        assert_eq!(Client::parse_list("vscode,cursor").unwrap(), [Client::VsCode, Client::Cursor]);
+        assert_eq!(Client::parse_list("all").unwrap(), Client::ALL);
+        assert!(Client::parse_list("emacs").is_err());
+        assert!(Client::parse_list("").is_err());
+    }
+
+    #[test]
+    fn test_each_client_gets_its_own_shape() {
+        let (_, text) = render_client_config("t", default_addr(), false, true, &Client::ALL);
+        assert!(text.contains("Cursor config") && text.contains("Claude Desktop config") && text.contains("VS Code config"));
+
+        let vscode = Client::VsCode.entry("t", default_addr());
+        assert_eq!(vscode["type"], "sse");
+        assert_eq!(vscode["headers"]["Authorization"], "Bearer t");
+        let claude = Client::Claude.entry("t", default_addr());
+        assert_eq!(claude["command"], "npx");
+        assert_eq!(claude["args"][2], "http://localhost:24680/sse");
+        assert_eq!(claude["env"]["AUTH_HEADER"], "Bearer t");
+    }
+
+    #[test]
+    fn test_merge_keeps_existing_servers() {
+        let existing = r#"{"theme": "dark", "servers": {"github": {"command": "gh"}}}"#;
+        let merged: serde_json::Value =
+            serde_json::from_str(&merge_client_entry(Some(existing), Client::VsCode, "t", default_addr()).unwrap()).unwrap();
+        assert_eq!(merged["theme"], "dark");
+        assert_eq!(merged["servers"]["github"]["command"], "gh");
+        assert_eq!(merged["servers"]["McpHub"]["url"], "http://localhost:24680/sse");
+
+        let created: serde_json::Value = serde_json::from_str(&merge_client_entry(None, Client::Cursor, "t", default_addr()).unwrap()).unwrap();
+        assert_eq!(created["mcpServers"]["McpHub"]["headers"]["Authorization"], "Bearer t");
+
+        assert!(merge_client_entry(Some("{ // comment"), Client::Cursor, "t", default_addr()).is_err());
+        assert!(merge_client_entry(Some(r#"{"mcpServers": []}"#), Client::Cursor, "t", default_addr()).is_err());
+    }
+
+    #[test]
+    fn test_registered_command_lines() {
+        let plist = "<key>ProgramArguments</key>\n<array>\n<string>/bin/McpHub</string>\n<string>serve</string>\n</array>\n<key>StandardErrorPath</key>\n<string>/tmp/x</string>";
+        assert_eq!(plist_command(plist), ["/bin/McpHub", "serve"]);
+        assert_eq!(unit_command("[Service]\nExecStart=/bin/McpHub serve --port 9000\n"), ["/bin/McpHub", "serve", "--port", "9000"]);
+        assert_eq!(split_command_line(r#""C:\Program Files\McpHub.exe" service"#), [r"C:\Program Files\McpHub.exe", "service"]);
+    }
+
+    #[test]
+    fn test_compare_registration() {
+        let command = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let new = command(&["/opt/McpHub", "serve"]);
+        assert_eq!(compare_registration(None, &new, "unit"), Registration::New);
+        assert_eq!(compare_registration(Some((new.clone(), "unit\n".to_string())), &new, "unit"), Registration::Unchanged);
+        assert_eq!(
+            compare_registration(Some((new.clone(), "old unit".to_string())), &new, "unit"),
+            Registration::Changed(vec!["other settings (log path, restart policy)".to_string()])
+        );
+        let Registration::Changed(changes) = compare_registration(Some((command(&["/gone/McpHub", "serve", "--port", "1"]), String::new())), &new, "unit") else {
+            panic!("expected changes");
+        };
+        assert_eq!(changes, ["binary: /gone/McpHub (no longer exists) -> /opt/McpHub", "arguments: 'serve --port 1' -> 'serve'"]);
+    }
+
+    #[test]
+    fn test_owned_files_skip_everything_else() {
+        let dir = std::env::temp_dir().join(format!("mcphub-purge-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("recordings")).unwrap();
+        fs::create_dir_all(dir.join("Documents")).unwrap();
+        for name in ["auth-token", "mcphub.log.2", "mcphub.log.old", ".bashrc", "Documents/config.json", "recordings/s-1.jsonl", "recordings/notes.txt"] {
+            fs::write(dir.join(name), "x").unwrap();
+        }
+        let expected: Vec<PathBuf> = ["auth-token", "mcphub.log.2"].iter().map(PathBuf::from).chain(Some(PathBuf::from("recordings").join("s-1.jsonl"))).collect();
+        assert_eq!(owned_files(&dir), expected);
+        assert!(owned_files(&dir.join("missing")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_service_files_escape_their_values() {
+        assert_eq!(systemd_environment("MCPHUB_HOME", r#"/srv/a "b" 100%\x"#), "Environment=\"MCPHUB_HOME=/srv/a \\\"b\\\" 100%%\\\\x\"\n");
+        let path = "/Users/a&b/<McpHub>";
+        let plist = format!("<key>ProgramArguments</key>\n<array>\n<string>{}</string>\n</array>", xml_escape(path));
+        assert!(plist.contains("/Users/a&amp;b/&lt;McpHub&gt;"));
+        assert_eq!(plist_command(&plist), [path]);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_service_copies_are_purged() {
+        // Whichever cache file the user has goes to the service, and purge
+        // removes it from there again
+        assert!(SERVICE_FILES.contains(&"schema-cache.json.gz"));
+        for file in SERVICE_FILES {
+            assert!(DATA_FILES.contains(&file), "{}", file);
+        }
     }
 }
@@ -0,0 +1,135 @@
+/// Hooks run around every request the proxy dispatches, for logic that
+/// doesn't belong in McpHub itself: extra logging, rewriting arguments,
+/// blocking or mocking calls. Register them with
+/// `ProxyServer::with_interceptor`; they run in registration order.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::error;
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse};
+
+/// What a hook returns; boxed so interceptors can be held as trait objects.
+pub type Hook<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub trait RequestInterceptor: Send + Sync {
+    /// Before dispatch, with the request free to rewrite. `Some` answers it
+    /// as is: later interceptors and the proxy never see it.
+    fn before<'a>(&'a self, _req: &'a mut JsonRpcRequest) -> Hook<'a, Option<JsonRpcResponse>> {
+        Box::pin(async { None })
+    }
+
+    /// On the response, before it goes to the client. Not called for
+    /// notifications, which have none.
+    fn after<'a>(&'a self, _resp: &'a mut JsonRpcResponse) -> Hook<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// Answers requests for the methods of `settings.blockedMethods` with a
+/// method-not-found error. Notifications get through: they can't be
+/// answered.
+pub struct BlockMethods(pub Vec<String>);
+
+impl RequestInterceptor for BlockMethods {
+    fn before<'a>(&'a self, req: &'a mut JsonRpcRequest) -> Hook<'a, Option<JsonRpcResponse>> {
+        Box::pin(async move {
+            (req.id.is_some() && self.0.contains(&req.method)).then(|| {
+                JsonRpcResponse::error(req.id.clone(), error::METHOD_NOT_FOUND, format!("Method blocked by blockedMethods: {}", req.method))
+            })
+        })
+    }
+}
+
+/// Run `dispatch` on `req` inside `interceptors`: `before` hooks in order
+/// until one answers, then the `after` hooks of those that didn't answer,
+/// innermost first.
+pub async fn around<F, Fut>(interceptors: &[Arc<dyn RequestInterceptor>], mut req: JsonRpcRequest, dispatch: F) -> Option<JsonRpcResponse>
+where
+    F: FnOnce(JsonRpcRequest) -> Fut,
+    Fut: Future<Output = Option<JsonRpcResponse>>,
+{
+    let mut passed = 0;
+    let mut response = None;
+    for interceptor in interceptors {
+        if let Some(answer) = interceptor.before(&mut req).await {
+            response = Some(answer);
+            break;
+        }
+        passed += 1;
+    }
+    if passed == interceptors.len() {
+        response = dispatch(req).await;
+    }
+    if let Some(resp) = response.as_mut() {
+        for interceptor in interceptors[..passed].iter().rev() {
+            interceptor.after(resp).await;
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records the hooks it sees and blocks one method.
+    struct Recorder {
+        name: &'static str,
+        blocks: &'static str,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RequestInterceptor for Recorder {
+        fn before<'a>(&'a self, req: &'a mut JsonRpcRequest) -> Hook<'a, Option<JsonRpcResponse>> {
+            Box::pin(async move {
+                self.seen.lock().unwrap().push(format!("{} before {}", self.name, req.method));
+                req.params["by"] = json!(self.name);
+                (req.method == self.blocks).then(|| JsonRpcResponse::error(req.id.clone(), error::INVALID_REQUEST, format!("blocked by {}", self.name)))
+            })
+        }
+
+        fn after<'a>(&'a self, _resp: &'a mut JsonRpcResponse) -> Hook<'a, ()> {
+            Box::pin(async move { self.seen.lock().unwrap().push(format!("{} after", self.name)) })
+        }
+    }
+
+    fn request(method: &str) -> JsonRpcRequest {
+        JsonRpcRequest { jsonrpc: "2.0".into(), id: Some(json!(1)), method: method.into(), params: json!({}) }
+    }
+
+    #[tokio::test]
+    async fn test_hooks_wrap_dispatch_in_order() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let interceptors: Vec<Arc<dyn RequestInterceptor>> = vec![
+            Arc::new(Recorder { name: "outer", blocks: "tools/call", seen: seen.clone() }),
+            Arc::new(Recorder { name: "inner", blocks: "prompts/get", seen: seen.clone() }),
+        ];
+        let dispatch = |req: JsonRpcRequest| async move { Some(JsonRpcResponse::success(req.id, req.params)) };
+
+        let resp = around(&interceptors, request("tools/list"), dispatch).await.unwrap();
+        // The request reaches dispatch as the last interceptor left it
+        assert_eq!(resp.result, Some(json!({"by": "inner"})));
+        assert_eq!(*seen.lock().unwrap(), ["outer before tools/list", "inner before tools/list", "inner after", "outer after"]);
+
+        seen.lock().unwrap().clear();
+        let resp = around(&interceptors, request("prompts/get"), dispatch).await.unwrap();
+        assert_eq!(resp.error.unwrap().message, "blocked by inner");
+        assert_eq!(*seen.lock().unwrap(), ["outer before prompts/get", "inner before prompts/get", "outer after"]);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_methods_answered_without_dispatch() {
+        let interceptors: Vec<Arc<dyn RequestInterceptor>> = vec![Arc::new(BlockMethods(vec!["prompts/get".into()]))];
+        let dispatch = |req: JsonRpcRequest| async move { Some(JsonRpcResponse::success(req.id, json!({}))) };
+
+        let resp = around(&interceptors, request("prompts/get"), dispatch).await.unwrap();
+        assert_eq!(resp.error.unwrap().code, error::METHOD_NOT_FOUND);
+        assert!(around(&interceptors, request("prompts/list"), dispatch).await.unwrap().result.is_some());
+        // A notification isn't answered
+        let notification = JsonRpcRequest { id: None, ..request("prompts/get") };
+        assert!(around(&interceptors, notification, dispatch).await.unwrap().result.is_some());
+    }
+}
@@ -0,0 +1,193 @@
+/// Handling of tool results larger than the client asked for. A client
+/// declares its limit per call in `_meta["mcphub/maxResultBytes"]`; what
+/// happens to a bigger result is configured globally and per tool.
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// `_meta` key a client sets on `tools/call` to cap the result size.
+pub const MAX_RESULT_META: &str = "mcphub/maxResultBytes";
+/// Uri of a full result kept under the `reference` policy.
+pub const RESULT_URI_PREFIX: &str = "mcphub://results/";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultPolicy {
+    /// Return the result as is.
+    Passthrough,
+    /// Cut the content to fit and say so in a trailing text item.
+    Truncate,
+    /// Like truncate, and keep the full result readable as a resource.
+    Reference,
+    /// Fail the call.
+    Error,
+}
+
+impl ResultPolicy {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "passthrough" => Some(Self::Passthrough),
+            "truncate" => Some(Self::Truncate),
+            "reference" => Some(Self::Reference),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultLimits {
+    pub policy: ResultPolicy,
+    /// Overrides keyed by `server__tool` or by server name.
+    pub tools: HashMap<String, ResultPolicy>,
+}
+
+impl Default for ResultLimits {
+    fn default() -> Self {
+        Self { policy: ResultPolicy::Passthrough, tools: HashMap::new() }
+    }
+}
+
+impl ResultLimits {
+    /// Policy for a tool: its own override, then its server's, then the default.
+    pub fn policy_for(&self, server: &str, tool: &str) -> ResultPolicy {
+        self.tools
+            .get(&format!("{}__{}", server, tool))
+            .or_else(|| self.tools.get(server))
+            .copied()
+            .unwrap_or(self.policy)
+    }
+}
+
+/// Parse `settings.oversizedResults`.
+pub fn parse_result_limits(value: &Value) -> Result<ResultLimits, String> {
+    let policy = |v: &Value, key: &str| -> Result<ResultPolicy, String> {
+        v.as_str().and_then(ResultPolicy::parse).ok_or(format!(
+            "{}: expected passthrough, truncate, reference or error",
+            key
+        ))
+    };
+    let mut limits = ResultLimits::default();
+    if let Some(p) = value.get("policy") {
+        limits.policy = policy(p, "policy")?;
+    }
+    if let Some(tools) = value.get("tools") {
+        let tools = tools.as_object().ok_or("tools must be an object")?;
+        for (name, p) in tools {
+            limits.tools.insert(name.clone(), policy(p, &format!("tools.{}", name))?);
+        }
+    }
+    Ok(limits)
+}
+
+/// The client's limit from a `tools/call`'s params, if it set one.
+pub fn requested_max(params: &Value) -> Option<usize> {
+    params.get("_meta")?.get(MAX_RESULT_META)?.as_u64().map(|n| n as usize)
+}
+
+pub fn size_of(value: &Value) -> usize {
+    serde_json::to_string(value).map(|s| s.len()).unwrap_or(0)
+}
+
+/// Shrink `result` to about `max` serialized bytes: `structuredContent` is
+/// dropped, content items are kept while they fit, the text item that
+/// crosses the limit is cut and later items are dropped. `marker` is
+/// appended as a final text item and counts towards the limit.
+pub fn truncate(result: &mut Value, max: usize, marker: &str) {
+    let Some(obj) = result.as_object_mut() else { return };
+    obj.remove("structuredContent");
+    let content = match obj.remove("content") {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    };
+    let marker = json!({ "type": "text", "text": marker });
+    let fixed = size_of(&Value::Object(obj.clone())) + ",\"content\":[]".len() + size_of(&marker) + 1;
+    let mut budget = max.saturating_sub(fixed);
+
+    let mut kept = Vec::new();
+    for mut item in content {
+        let size = size_of(&item) + 1;
+        if size <= budget {
+            budget -= size;
+            kept.push(item);
+            continue;
+        }
+        if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
+            let overhead = size - escaped_len(text);
+            if budget > overhead {
+                let cut = cut_to_escaped_len(text, budget - overhead).to_string();
+                item["text"] = json!(cut);
+                kept.push(item);
+            }
+        }
+        break;
+    }
+    kept.push(marker);
+    obj.insert("content".into(), Value::Array(kept));
+}
+
+/// Length of `c` inside a JSON string.
+fn escaped_char_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+        c if (c as u32) < 0x20 => 6,
+        c => c.len_utf8(),
+    }
+}
+
+fn escaped_len(s: &str) -> usize {
+    s.chars().map(escaped_char_len).sum()
+}
+
+/// Longest prefix of `s` whose JSON-escaped form fits in `max` bytes.
+fn cut_to_escaped_len(s: &str, max: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in s.char_indices() {
+        used += escaped_char_len(c);
+        if used > max {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oversized_result_is_truncated_to_the_client_max() {
+        let mut result = json!({
+            "content": [
+                { "type": "text", "text": "header\n" },
+                { "type": "text", "text": "é\"x".repeat(5000) },
+                { "type": "image", "data": "AAAA", "mimeType": "image/png" }
+            ],
+            "structuredContent": { "rows": [1, 2, 3] }
+        });
+        truncate(&mut result, 500, "[McpHub: truncated]");
+        assert!(size_of(&result) <= 500, "{}", size_of(&result));
+        let content = result["content"].as_array().unwrap();
+        assert_eq!(content.len(), 3);
+        assert_eq!(content[0]["text"], "header\n");
+        assert!(content[1]["text"].as_str().unwrap().starts_with("é\"xé"));
+        assert_eq!(content[2]["text"], "[McpHub: truncated]");
+        assert!(result.get("structuredContent").is_none());
+
+        let params = json!({ "name": "t", "_meta": { "mcphub/maxResultBytes": 500 } });
+        assert_eq!(requested_max(&params), Some(500));
+        assert_eq!(requested_max(&json!({ "name": "t" })), None);
+    }
+
+    #[test]
+    fn test_per_tool_policy_overrides_default() {
+        let limits = parse_result_limits(&json!({
+            "policy": "truncate",
+            "tools": { "github__search_code": "reference", "fs": "error" }
+        }))
+        .unwrap();
+        assert_eq!(limits.policy_for("github", "search_code"), ResultPolicy::Reference);
+        assert_eq!(limits.policy_for("github", "list_issues"), ResultPolicy::Truncate);
+        assert_eq!(limits.policy_for("fs", "read"), ResultPolicy::Error);
+        assert_eq!(ResultLimits::default().policy_for("fs", "read"), ResultPolicy::Passthrough);
+        assert!(parse_result_limits(&json!({ "policy": "drop" })).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+/// The hub's log output: a small `tracing` subscriber that writes
+/// `[McpHub][TAG] message key=value` lines to stderr, stdout or a file.
+/// `TAG` is the event's `tag` field, or its level; a `source` field (a
+/// backend's name) is shown as a second bracket. Fields of the spans the
+/// event runs in (session, tool call) follow the message. `MCPHUB_LOG`
+/// sets the levels shown.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+pub const LOG_ENV: &str = "MCPHUB_LOG";
+
+/// Levels shown, from comma-separated `level` and `module=level`
+/// directives (e.g. `warn,sse=debug`). The longest matching module wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self { default: LevelFilter::INFO, modules: Vec::new() }
+    }
+}
+
+impl Filter {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (module, level) = match directive.split_once('=') {
+                Some((module, level)) => (Some(module.trim()), level.trim()),
+                None => (None, directive),
+            };
+            let level = LevelFilter::from_str(level).map_err(|_| format!("invalid level '{}' in '{}'", level, directive))?;
+            match module {
+                Some(module) => filter.modules.push((module_name(module).to_string(), level)),
+                None => filter.default = level,
+            }
+        }
+        filter.modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok(filter)
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let target = module_name(target);
+        self.modules
+            .iter()
+            .find(|(module, _)| target.strip_prefix(module.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with("::")))
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, LevelFilter::max)
+    }
+}
+
+/// `McpHub::sse` -> `sse`, so directives can leave out the crate name.
+fn module_name(target: &str) -> &str {
+    target.strip_prefix(concat!(env!("CARGO_CRATE_NAME"), "::")).unwrap_or(target)
+}
+
+/// Where log lines are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Stderr,
+    Stdout,
+    /// Appended to this file.
+    File(PathBuf),
+}
+
+/// Install the subscriber, with the filter from `MCPHUB_LOG` (default `info`).
+pub fn init() {
+    init_to(Target::Stderr);
+}
+
+static TARGET: OnceLock<Target> = OnceLock::new();
+
+/// Where this process logs: what `init_to` set up, stderr before it runs.
+pub fn target() -> Target {
+    TARGET.get().cloned().unwrap_or(Target::Stderr)
+}
+
+/// `init` writing to `target`. A file is rotated by size (see `Rotation`);
+/// one that can't be opened falls back to stderr.
+pub fn init_to(target: Target) {
+    match target {
+        Target::Stderr => {
+            let _ = TARGET.set(Target::Stderr);
+            install(|line| eprintln!("{}", line))
+        }
+        Target::Stdout => {
+            let _ = TARGET.set(Target::Stdout);
+            install(|line| println!("{}", line))
+        }
+        Target::File(path) => match LogFile::open(path.clone()) {
+            Ok(file) => {
+                let _ = TARGET.set(Target::File(path));
+                let file = Mutex::new(file);
+                install(move |line| file.lock().unwrap_or_else(|e| e.into_inner()).write_line(line, rotation()))
+            }
+            Err(e) => {
+                init();
+                tracing::warn!("Could not open log file {}: {}; logging to stderr", path.display(), e);
+            }
+        },
+    }
+}
+
+/// Size-based rotation of the log file: past `max_bytes` it is renamed to
+/// `.1` (older generations shift up, `keep` are kept) and a new file started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// 0 never rotates.
+    pub max_bytes: u64,
+    pub keep: usize,
+}
+
+impl Rotation {
+    pub const DEFAULT: Rotation = Rotation { max_bytes: 10 * 1024 * 1024, keep: 5 };
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static ROTATION: Mutex<Rotation> = Mutex::new(Rotation::DEFAULT);
+
+fn rotation() -> Rotation {
+    *ROTATION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Rotation of a file target, from the `log` settings; applies to lines
+/// written from now on.
+pub fn set_rotation(rotation: Rotation) {
+    *ROTATION.lock().unwrap_or_else(|e| e.into_inner()) = rotation;
+}
+
+/// A log file the hub appends to and rotates.
+struct LogFile {
+    path: PathBuf,
+    file: std::fs::File,
+    len: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { path, file, len })
+    }
+
+    fn write_line(&mut self, line: &str, rotation: Rotation) {
+        use std::io::Write as _;
+        if rotation.max_bytes > 0 && self.len >= rotation.max_bytes {
+            self.rotate(rotation.keep);
+        }
+        if writeln!(self.file, "{}", line).is_ok() {
+            self.len += line.len() as u64 + 1;
+        }
+    }
+
+    /// `path.{keep-1}` -> `path.{keep}`, ..., `path` -> `path.1`, then a
+    /// fresh `path`. On failure, writing carries on in the current file.
+    fn rotate(&mut self, keep: usize) {
+        let generation = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        let _ = std::fs::remove_file(generation(keep.max(1)));
+        for n in (1..keep).rev() {
+            let _ = std::fs::rename(generation(n), generation(n + 1));
+        }
+        let moved = if keep == 0 { std::fs::remove_file(&self.path) } else { std::fs::rename(&self.path, generation(1)) };
+        if moved.is_err() {
+            return;
+        }
+        if let Ok(file) = Self::open(self.path.clone()) {
+            *self = file;
+        }
+    }
+}
+
+fn install(write: impl Fn(&str) + Send + Sync + 'static) {
+    let spec = std::env::var(LOG_ENV).ok();
+    let (filter, invalid) = match spec.as_deref().map(Filter::parse) {
+        Some(Ok(filter)) => (filter, None),
+        Some(Err(e)) => (Filter::default(), Some(e)),
+        None => (Filter::default(), None),
+    };
+    let subscriber = HubSubscriber::new(filter, write);
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        if let Some(e) = invalid {
+            tracing::warn!("Ignoring {}: {}", LOG_ENV, e);
+        }
+    }
+}
+
+thread_local! {
+    /// Spans entered on this thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+struct SpanData {
+    /// ` key=value` pairs, ready to append to a line.
+    fields: String,
+    refs: usize,
+}
+
+pub struct HubSubscriber {
+    filter: Filter,
+    write: Box<dyn Fn(&str) + Send + Sync>,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+}
+
+impl HubSubscriber {
+    pub fn new(filter: Filter, write: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self { filter, write: Box::new(write), next_id: AtomicU64::new(1), spans: Mutex::new(HashMap::new()) }
+    }
+
+    fn spans(&self) -> std::sync::MutexGuard<'_, HashMap<u64, SpanData>> {
+        self.spans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    tag: Option<String>,
+    source: Option<String>,
+    rest: String,
+}
+
+impl Fields {
+    fn add(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = value,
+            "tag" => self.tag = Some(value),
+            "source" => self.source = Some(value),
+            name => {
+                let _ = write!(self.rest, " {}={}", name, value);
+            }
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.add(field, format!("{:?}", value));
+    }
+}
+
+impl Subscriber for HubSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.max_level())
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.spans().insert(id, SpanData { fields: fields.rest, refs: 1 });
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(data) = self.spans().get_mut(&span.into_u64()) {
+            data.fields.push_str(&fields.rest);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut line = String::from("[McpHub]");
+        if let Some(source) = &fields.source {
+            let _ = write!(line, "[{}]", source);
+        }
+        let tag = fields.tag.as_deref().unwrap_or(event.metadata().level().as_str());
+        let _ = write!(line, "[{}] {}{}", tag, fields.message, fields.rest);
+        ENTERED.with(|entered| {
+            let spans = self.spans();
+            for id in entered.borrow().iter() {
+                if let Some(data) = spans.get(id) {
+                    line.push_str(&data.fields);
+                }
+            }
+        });
+        (self.write)(&line);
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(pos) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(pos);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self.spans().get_mut(&span.into_u64()) {
+            data.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans();
+        let Some(data) = spans.get_mut(&span.into_u64()) else { return false };
+        data.refs -= 1;
+        if data.refs > 0 {
+            return false;
+        }
+        spans.remove(&span.into_u64());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_filter_directives() {
+        let filter = Filter::parse("warn, sse=debug,McpHub::sse::reaper=off").unwrap();
+        assert_eq!(filter.level_for("McpHub::proxy"), LevelFilter::WARN);
+        assert_eq!(filter.level_for("McpHub::sse"), LevelFilter::DEBUG);
+        assert_eq!(filter.level_for("McpHub::sse::reaper"), LevelFilter::OFF);
+        assert_eq!(filter.level_for("McpHub::sse_extra"), LevelFilter::WARN);
+        assert_eq!(filter.max_level(), LevelFilter::DEBUG);
+        assert_eq!(Filter::parse("").unwrap(), Filter::default());
+        assert!(Filter::parse("sse=loud").is_err());
+    }
+
+    #[test]
+    fn test_lines_keep_the_prefix_and_carry_span_fields() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let filter = Filter::parse("info").unwrap();
+        let subscriber = HubSubscriber::new(filter, move |line| sink.lock().unwrap().push(line.to_string()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("Plain {}", 1);
+            tracing::debug!("hidden");
+            let session = tracing::info_span!("session", session = "s1");
+            let _session = session.enter();
+            let call = tracing::info_span!("call", tool = "github__search");
+            let _call = call.enter();
+            tracing::info!(tag = "SSE", elapsed_ms = 3, "Done");
+            tracing::info!(source = "github", tag = "STDERR", "boot");
+        });
+        assert_eq!(*lines.lock().unwrap(), [
+            "[McpHub][WARN] Plain 1",
+            "[McpHub][SSE] Done elapsed_ms=3 session=s1 tool=github__search",
+            "[McpHub][github][STDERR] boot session=s1 tool=github__search",
+        ]);
+    }
+
+    #[test]
+    fn test_log_file_rotates_by_size_keeping_generations() {
+        let dir = std::env::temp_dir().join(format!("mcphub-log-rotate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("mcphub.log");
+        let rotation = Rotation { max_bytes: 10, keep: 2 };
+        let mut file = LogFile::open(path.clone()).unwrap();
+        for line in ["first line", "second line", "third line", "fourth line"] {
+            file.write_line(line, rotation);
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("mcphub.log"), "fourth line\n");
+        assert_eq!(read("mcphub.log.1"), "third line\n");
+        assert_eq!(read("mcphub.log.2"), "second line\n");
+        assert!(!dir.join("mcphub.log.3").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
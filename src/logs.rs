@@ -1,10 +1,70 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::Duration;
 
-pub fn run(server_filter: Option<&str>, level_filter: Option<&str>) {
-    let log_path = dirs::home_dir().unwrap_or_default().join(".McpHub/mcphub.log");
+/// Bytes read per step when scanning a log backwards.
+const TAIL_CHUNK: u64 = 8192;
+
+/// The last `n` complete lines of the file at `path`, and the offset just
+/// past them, where following new lines starts.
+pub fn tail(path: &Path, n: usize) -> std::io::Result<(Vec<String>, u64)> {
+    let mut file = File::open(path)?;
+    let mut start = file.metadata()?.len();
+    let mut buf: Vec<u8> = Vec::new();
+    // One newline more than needed, so the first line kept is complete
+    while start > 0 && buf.iter().filter(|&&b| b == b'\n').count() <= n {
+        let chunk = TAIL_CHUNK.min(start);
+        start -= chunk;
+        file.seek(SeekFrom::Start(start))?;
+        let mut piece = vec![0; chunk as usize];
+        file.read_exact(&mut piece)?;
+        piece.extend_from_slice(&buf);
+        buf = piece;
+    }
+    let end = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let mut lines: Vec<String> = String::from_utf8_lossy(&buf[..end]).lines().map(String::from).collect();
+    lines.drain(..lines.len().saturating_sub(n));
+    Ok((lines, start + end as u64))
+}
+
+/// Complete lines written after `*pos`, advancing it past them. A file
+/// shorter than `*pos` was truncated or rotated and is read from the start.
+pub fn read_new(path: &Path, pos: &mut u64) -> std::io::Result<Vec<String>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len < *pos {
+        *pos = 0;
+    }
+    file.seek(SeekFrom::Start(*pos))?;
+    let mut buf = Vec::new();
+    file.take(len - *pos).read_to_end(&mut buf)?;
+    let Some(end) = buf.iter().rposition(|&b| b == b'\n') else { return Ok(Vec::new()) };
+    *pos += end as u64 + 1;
+    Ok(String::from_utf8_lossy(&buf[..end]).lines().map(String::from).collect())
+}
+
+/// Where the installed daemon logs unless `install --log-path` says otherwise.
+pub fn default_path() -> PathBuf {
+    crate::config::hub_dir().unwrap_or_default().join("mcphub.log")
+}
+
+/// Where the service manager appends the daemon's own stderr (panics,
+/// anything printed before logging starts): next to `log`, as
+/// `mcphub.stderr.log` for `mcphub.log`. Not `log` itself, which the daemon
+/// rotates from under the supervisor's handle.
+pub fn stderr_path(log: &Path) -> PathBuf {
+    let stem = log.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match log.extension() {
+        Some(ext) => format!("{}.stderr.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.stderr", stem),
+    };
+    log.with_file_name(name)
+}
+
+pub fn run(server_filter: Option<&str>, level_filter: Option<&str>, path: Option<PathBuf>) {
+    let log_path = path.unwrap_or_else(default_path);
     if !log_path.exists() {
         eprintln!("Log file not found at {}", log_path.display());
         return;
@@ -60,4 +120,38 @@ pub fn run(server_filter: Option<&str>, level_filter: Option<&str>) {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_stderr_path_sits_next_to_the_log() {
+        assert_eq!(stderr_path(Path::new("/var/log/mcphub.log")), Path::new("/var/log/mcphub.stderr.log"));
+        assert_eq!(stderr_path(Path::new("/var/log/hub")), Path::new("/var/log/hub.stderr"));
+    }
+
+    #[test]
+    fn test_tail_then_follow() {
+        let path = std::env::temp_dir().join(format!("mcphub-tail-{}.log", std::process::id()));
+        let long = "x".repeat(TAIL_CHUNK as usize);
+        std::fs::write(&path, format!("one\n{}\nthree\nfour\npart", long)).unwrap();
+
+        let (lines, mut pos) = tail(&path, 2).unwrap();
+        assert_eq!(lines, ["three", "four"]);
+        let (lines, _) = tail(&path, 10).unwrap();
+        assert_eq!(lines, ["one", long.as_str(), "three", "four"]);
+
+        // The partial line is sent once it is complete
+        assert!(read_new(&path, &mut pos).unwrap().is_empty());
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"ial\nfive\n").unwrap();
+        assert_eq!(read_new(&path, &mut pos).unwrap(), ["partial", "five"]);
+
+        // Truncated (rotated): start over
+        std::fs::write(&path, "fresh\n").unwrap();
+        assert_eq!(read_new(&path, &mut pos).unwrap(), ["fresh"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,19 +1,45 @@
 mod add;
+mod audit;
 mod benchmark;
+mod breaker;
 mod cache;
 pub mod child;
+mod coalesce;
 mod config;
+mod crashloop;
 mod dashboard;
+mod deflate;
 mod doctor;
+mod dump;
+mod error;
 mod export;
+mod framing;
 mod health;
+mod http;
 mod install;
+mod intercept;
+mod limits;
+mod logging;
 mod logs;
 mod protocol;
 mod proxy;
+mod quirks;
+mod record;
+mod redact;
+mod remote;
+mod result_cache;
+mod schema;
 mod search;
 mod sse;
+mod stderr;
+mod streamable;
+mod tool_filter;
+mod tool_overrides;
+mod transform;
 mod update;
+#[cfg(windows)]
+mod winservice;
+mod ws;
 
 use config::auto_detect;
 use proxy::ProxyServer;
@@ -28,19 +54,62 @@ McpHub v{VERSION} — Fastest MCP proxy with BM25 tool discovery
 
 USAGE:
   McpHub              Start proxy (stdio + HTTP server on :24680)
+                      (--bind IP:PORT or MCPHUB_BIND sets the HTTP address
+                      for this, serve, dashboard, install and token show;
+                      --port PORT changes just the port)
   McpHub serve        Start HTTP-only server (SSE transport, no stdio)
-  McpHub generate     Start all servers, index tools, save cache
+                      (--log-path FILE logs to a file, --log-stdout to stdout)
+  McpHub stdio        Start stdio-only proxy (no HTTP server), for clients
+                      that launch McpHub as a child MCP server
+  McpHub generate [--jobs N]
+                      Start all servers (N at a time, default 4), index
+                      tools, save cache
+  McpHub warm [--jobs N]
+                      Same, with a one-line summary (for ExecStartPre=)
   McpHub dashboard    Open web dashboard on http://127.0.0.1:24680
   McpHub install      Register McpHub to auto-start at login
-  McpHub uninstall    Remove auto-start registration
-  McpHub status       Show detected servers, cache, and health config
-  McpHub doctor       Run full diagnostic of the installation
+                      (--redact-token prints the client config without the token,
+                      --force restarts a running service on the new binary,
+                      --dry-run prints the files and commands without applying,
+                      --service registers a Windows service started at boot,
+                      --log-path FILE / --log-stdout choose where it logs,
+                      --client cursor|claude|vscode|all picks the config printed,
+                      --write-config adds it to the clients' config files)
+  McpHub upgrade-check
+                      Warn if the running instance is older than the binary on disk
+  McpHub uninstall    Remove auto-start registration (--dry-run to preview,
+                      --purge also deletes ~/.McpHub: config, token, cache, logs)
+  McpHub token show [--redact-token] [--client NAMES] [--write-config]
+                      Print the client config with the auth token
+  McpHub token rotate [--redact-token] [--client NAMES] [--write-config]
+                      Replace the auth token and print the new client config
+  McpHub status [--bind ADDR]
+                      Show auto-start and daemon state, servers and cache
+  McpHub doctor [--no-launch]
+                      Check the installation (data dir, token, port, cache)
+                      and start each server; exits 1 if a check fails
+  McpHub validate [file]
+                      Check config.json/config.toml syntax and server entries
   McpHub logs         Tail daemon logs in real time
   McpHub add          Interactively add a new server
   McpHub benchmark    Measure start and ping times for servers
   McpHub export       Export configuration to stdout
+  McpHub config dump [--toml] [--bind ADDR] [--port N]
+                      Print the effective settings and where each came from
   McpHub import       Import configuration from a file
+  McpHub replay <file> [--realtime] [--mock]
+                      Send a session recording through the proxy again and
+                      report changed responses (--mock answers a stdio client
+                      from the recording instead)
   McpHub search "q"   Test BM25 search
+  McpHub cache show [server]
+                      Print cached tools (all servers, or one in detail)
+  McpHub cache inspect
+                      Print each cached server's tool count, error and age
+  McpHub cache clear [server]
+                      Remove one server from the cache, or the whole cache
+  McpHub cache prune [--max-age-days N]
+                      Drop cache entries for removed servers (and stale ones)
   McpHub update       Self-update to the latest version on GitHub
   McpHub version      Show version
   McpHub help         Show this help
@@ -50,13 +119,18 @@ TRANSPORT MODES:
     Cursor config: {{"mcpServers": {{"McpHub": {{"command": "/path/to/McpHub"}}}}}}
     Starts stdio proxy AND HTTP server on :24680 (dashboard + SSE)
 
+  Stdio only:
+    Cursor config: {{"mcpServers": {{"McpHub": {{"command": "/path/to/McpHub", "args": ["stdio"]}}}}}}
+    Newline-delimited JSON-RPC on stdin/stdout; no port, so it runs next
+    to an installed 'McpHub serve'.
+
   Serve (HTTP only, recommended):
     Cursor config: {{"mcpServers": {{"McpHub": {{"url": "http://127.0.0.1:24680/sse", "headers": {{"Authorization": "Bearer <token>"}}}}}}}}
     Run 'McpHub install' to auto-start, then configure Cursor with URL and token.
     Survives Cursor restarts. Single process for everything.
 
 FIRST TIME SETUP:
-  1. Configure servers in ~/.McpHub/config.json
+  1. Configure servers in ~/.McpHub/config.json (or config.toml)
   2. Run: McpHub generate    (one-time, ~60s)
   3. Run: McpHub install     (auto-start at login, prints auth token)
   4. Configure Cursor with URL and auth token
@@ -65,7 +139,13 @@ FIRST TIME SETUP:
     );
 }
 
-fn cmd_status() {
+/// `status` exit codes: the daemon answers and no running group has failed;
+/// it is down or a group failed; auto-start isn't installed and nothing answers.
+const STATUS_HEALTHY: i32 = 0;
+const STATUS_DOWN: i32 = 1;
+const STATUS_NOT_INSTALLED: i32 = 2;
+
+fn cmd_status(bind_addr: std::net::SocketAddr) {
     let config = auto_detect();
     println!("McpHub v{}", VERSION);
     println!("Mode: {:?}", config.mode);
@@ -76,87 +156,311 @@ fn cmd_status() {
         config.health_notifications,
     );
 
+    let service = install::service_status();
+    match service {
+        install::Service::NotInstalled => println!("Service: not installed (run 'McpHub install')"),
+        install::Service::Installed { running: Some(true) } => println!("Service: installed, running"),
+        install::Service::Installed { running: Some(false) } => println!("Service: installed, NOT running"),
+        install::Service::Installed { running: None } => println!("Service: installed"),
+    }
+
+    // The daemon itself, whatever started it
+    let base_url = config::client_base_url(bind_addr);
+    let info = dashboard::daemon_get(bind_addr, "/info");
+    match &info {
+        Some(info) => println!(
+            "Daemon: running at {} (v{}, pid {})",
+            base_url,
+            info["version"].as_str().unwrap_or("?"),
+            info["pid"].as_u64().unwrap_or(0),
+        ),
+        None => println!("Daemon: not reachable at {}", base_url),
+    }
+
     // Cache info
     if let Some(cached) = cache::load_cache() {
         let total_tools: usize = cached.servers.values().map(|v: &Vec<crate::protocol::ToolDef>| v.len()).sum::<usize>();
-        println!("Cache: {} servers, {} tools (v{})", cached.servers.len(), total_tools, cached.version);
+        println!(
+            "Cache: {} servers, {} tools, {} errors (v{})",
+            cached.servers.len(), total_tools, cached.errors.len(), cached.version,
+        );
+        let mut errors: Vec<_> = cached.errors.iter().collect();
+        errors.sort();
+        for (name, error) in errors {
+            println!("  ✗ {}: {}", name, error);
+        }
     } else {
         println!("Cache: NOT FOUND — run 'McpHub generate' first");
     }
 
+    // Live instance group health, if the daemon is running
+    let readyz = info.as_ref().and_then(|_| dashboard::daemon_get(bind_addr, "/readyz"));
+    let health = readyz.as_ref().and_then(|r| r.get("servers")?.as_object());
+
     println!();
     let mut names: Vec<_> = config.servers.keys().collect();
     names.sort();
     for name in names {
         let s = &config.servers[name];
-        let args = s.args.join(" ");
-        println!("  {} → {} {}", name, s.command, args);
+        let target = match &s.url {
+            Some(url) => url.clone(),
+            None => format!("{} {}", s.command, s.args.join(" ")),
+        };
+        match health.and_then(|h| h.get(name)) {
+            Some(h) => println!(
+                "  {} → {}  [{} {}/{}]",
+                name, target,
+                h["state"].as_str().unwrap_or("?"),
+                h["up"].as_u64().unwrap_or(0),
+                h["total"].as_u64().unwrap_or(0),
+            ),
+            None => println!("  {} → {}", name, target),
+        }
     }
+
+    let ready = readyz.as_ref().is_some_and(|r| r["ready"] == true);
+    let code = match (info.is_some(), service) {
+        (true, _) if ready => STATUS_HEALTHY,
+        (false, install::Service::NotInstalled) => STATUS_NOT_INSTALLED,
+        _ => STATUS_DOWN,
+    };
+    std::process::exit(code);
 }
 
-async fn cmd_generate() {
+async fn cmd_generate(jobs: usize) {
+    let Some((server_tools, server_errors)) = rebuild_cache("Generating", jobs).await else { return };
+
+    // Build index to verify
+    let mut all_tools: Vec<IndexedTool> = Vec::new();
+    for (name, tools) in &server_tools {
+        for tool in tools {
+            all_tools.push(IndexedTool {
+                name: format!("{}__{}", name, tool.name),
+                original_name: tool.name.clone(),
+                server_name: name.clone(),
+                description: tool.description.clone(),
+                tool_def: tool.clone(),
+            });
+        }
+    }
+    let mut engine = SearchEngine::new();
+    engine.build_index(all_tools);
+
+    eprintln!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    eprintln!("Done: {} OK, {} failed, {} total tools", server_tools.len(), server_errors.len(), engine.tool_count());
+    eprintln!("Cache saved to {}", cache::cache_file().map(|p| p.display().to_string()).unwrap_or_default());
+    eprintln!("Proxy will now start instantly from cache.");
+}
+
+/// Shared by generate and warm: start every configured server (up to
+/// `jobs` at a time), write their tools, errors, capabilities, prompts and
+/// resources to the schema cache, then stop them. None if no server is
+/// configured.
+async fn rebuild_cache(
+    verb: &str,
+    jobs: usize,
+) -> Option<(
+    std::collections::HashMap<String, Vec<protocol::ToolDef>>,
+    std::collections::HashMap<String, String>,
+)> {
     let config = auto_detect();
+    redact::set_extra_patterns(&config.cache_redact_patterns);
+    cache::set_gzip(config.cache_gzip);
     if config.servers.is_empty() {
-        eprintln!("No servers found. Add servers to ~/.McpHub/config.json");
-        return;
+        eprintln!("No servers found. Add servers to {}", config::hub_dir().unwrap_or_default().join("config.json").display());
+        return None;
     }
+    let mut names: Vec<String> = config.servers.keys().cloned().collect();
+    names.sort();
+    eprintln!("{} cache for {} servers, {} at a time...\n", verb, names.len(), jobs);
 
-    let total = config.servers.len();
-    eprintln!("Generating cache for {} servers...\n", total);
-
-    let manager = std::sync::Arc::new(child::ChildManager::new(
-        config.servers.clone(),
-        config.idle_timeout_ms,
-    ));
+    let manager = std::sync::Arc::new(
+        child::ChildManager::new(config.servers.clone(), config.idle_timeout_ms)
+            .with_timeouts(config.init_timeout_secs, config.call_timeout_secs)
+            .with_init_retries(config.init_retries, config.init_retry_backoff_ms)
+            .with_tool_filter(config.tool_filter.clone())
+            .with_tool_overrides(config.tool_overrides.clone()),
+    );
+    let discovered = discover_servers(&manager, &names, jobs).await;
 
-    let mut server_tools: std::collections::HashMap<String, Vec<protocol::ToolDef>> = std::collections::HashMap::new();
-    let mut server_errors: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-    let mut all_tools: Vec<IndexedTool> = Vec::new();
-    let mut ok = 0;
-    let mut fail = 0;
+    let (capabilities, server_prompts) = fetch_prompts(&manager, &names).await;
+    let server_resources = fetch_resources(&manager, &names, &capabilities).await;
+    cache::complete_generated_cache(&names, &capabilities, &server_prompts, &server_resources);
+    manager.stop_all().await;
+    Some(discovered)
+}
 
-    let mut names: Vec<String> = config.servers.keys().cloned().collect();
-    names.sort();
+/// Start `names` (up to `jobs` at a time) and collect their tools, or why
+/// they failed. Each attempt at a server's handshake gets its `initTimeout`,
+/// then `initRetries` more after a backoff, so a hanging one only holds its
+/// own slot, for all of them. Results go to the schema cache as they
+/// arrive, written by the time this returns, and a summary is printed at
+/// the end.
+async fn discover_servers(
+    manager: &std::sync::Arc<child::ChildManager>,
+    names: &[String],
+    jobs: usize,
+) -> (
+    std::collections::HashMap<String, Vec<protocol::ToolDef>>,
+    std::collections::HashMap<String, String>,
+) {
+    let started = std::time::Instant::now();
+    let workers = std::sync::Arc::new(tokio::sync::Semaphore::new(jobs));
+    let cache = cache::CacheWriter::new();
+    let mut fetches = tokio::task::JoinSet::new();
+    let mut fetching = std::collections::HashMap::new();
+    for name in names.iter().cloned() {
+        let (manager, workers) = (manager.clone(), workers.clone());
+        let task = fetches.spawn({
+            let name = name.clone();
+            async move {
+                let _worker = workers.acquire_owned().await;
+                let begun = std::time::Instant::now();
+                let result = manager.start_server(&name).await;
+                (name, result, begun.elapsed())
+            }
+        });
+        fetching.insert(task.id(), name);
+    }
 
-    for (i, name) in names.iter().enumerate() {
-        eprint!("[{}/{}] {} ... ", i + 1, total, name);
-        match manager.start_server(name).await {
+    let mut server_tools = std::collections::HashMap::new();
+    let mut server_errors = std::collections::HashMap::new();
+    while let Some(fetched) = fetches.join_next().await {
+        let (name, result, took) = match fetched {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                let name = fetching.remove(&e.id()).unwrap_or_default();
+                eprintln!("  {} ... FAILED: {}", name, e);
+                cache.set_server_error(&name, &e.to_string());
+                server_errors.insert(name, e.to_string());
+                continue;
+            }
+        };
+        match result {
             Ok(tools) => {
-                eprintln!("{} tools ✓", tools.len());
-                server_tools.insert(name.clone(), tools.clone());
-                for tool in tools {
-                    all_tools.push(IndexedTool {
-                        name: format!("{}__{}", name, tool.name),
-                        original_name: tool.name.clone(),
-                        server_name: name.clone(),
-                        description: tool.description.clone(),
-                        tool_def: tool,
-                    });
-                }
-                ok += 1;
+                eprintln!("  {} ... {} tools ✓ ({:.1}s)", name, tools.len(), took.as_secs_f64());
+                cache.repair_server(&name, tools.clone());
+                server_tools.insert(name, tools);
             }
             Err(e) => {
-                eprintln!("FAILED: {}", e);
-                server_errors.insert(name.clone(), e);
-                fail += 1;
+                eprintln!("  {} ... FAILED: {}", name, e);
+                cache.set_server_error(&name, &e.message);
+                server_errors.insert(name, e.message);
             }
         }
     }
 
-    // Build index to verify
-    let mut engine = SearchEngine::new();
-    engine.build_index(all_tools);
+    let mut failed: Vec<&String> = server_errors.keys().collect();
+    failed.sort();
+    eprintln!(
+        "Discovery took {:.1}s: {} ok, {} failed{}",
+        started.elapsed().as_secs_f64(),
+        server_tools.len(),
+        failed.len(),
+        if failed.is_empty() { String::new() } else { format!(" ({})", failed.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")) },
+    );
+    (server_tools, server_errors)
+}
 
-    // Save cache with errors
-    cache::save_cache_with_errors(&server_tools, &server_errors);
+/// Capabilities of the started servers, and the prompt definitions of
+/// those that advertise prompts.
+async fn fetch_prompts(
+    manager: &child::ChildManager,
+    names: &[String],
+) -> (
+    std::collections::HashMap<String, serde_json::Value>,
+    std::collections::HashMap<String, Vec<protocol::PromptDef>>,
+) {
+    let capabilities = manager.server_capabilities().await;
+    let mut server_prompts = std::collections::HashMap::new();
+    for name in names {
+        if capabilities.get(name).and_then(|c| c.get("prompts")).is_none() {
+            continue;
+        }
+        match manager.call_method(name, "prompts/list", serde_json::json!({})).await {
+            Ok(result) => {
+                server_prompts.insert(name.clone(), cache::parse_prompt_list(name, &result));
+            }
+            Err(e) => tracing::warn!("{}: prompts/list failed: {}", name, e),
+        }
+    }
+    (capabilities, server_prompts)
+}
 
-    // Stop all servers
-    manager.stop_all().await;
+/// Resources of the started servers that advertise resources.
+async fn fetch_resources(
+    manager: &child::ChildManager,
+    names: &[String],
+    capabilities: &std::collections::HashMap<String, serde_json::Value>,
+) -> std::collections::HashMap<String, Vec<protocol::ResourceDef>> {
+    let mut server_resources = std::collections::HashMap::new();
+    for name in names {
+        if capabilities.get(name).and_then(|c| c.get("resources")).is_none() {
+            continue;
+        }
+        match manager.call_method(name, "resources/list", serde_json::json!({})).await {
+            Ok(result) => {
+                server_resources.insert(name.clone(), cache::parse_resource_list(name, &result));
+            }
+            Err(e) => tracing::warn!("{}: resources/list failed: {}", name, e),
+        }
+    }
+    server_resources
+}
 
-    eprintln!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    eprintln!("Done: {} OK, {} failed, {} total tools", ok, fail, engine.tool_count());
-    eprintln!("Cache saved to ~/.McpHub/schema-cache.json");
-    eprintln!("Proxy will now start instantly from cache.");
+/// `--jobs N` of generate / warm: servers started at once (default 4).
+fn jobs_arg(args: &[String]) -> usize {
+    args.iter().skip(2)
+        .skip_while(|a| *a != "--jobs")
+        .nth(1)
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(4)
+        .max(1)
+}
+
+/// `McpHub warm [--jobs N]`: like generate, without the index check.
+/// Unreachable servers are recorded as cache errors.
+async fn cmd_warm(jobs: usize) {
+    let Some((server_tools, server_errors)) = rebuild_cache("Warming", jobs).await else { return };
+    let total_tools: usize = server_tools.values().map(Vec::len).sum();
+    eprintln!("Warmed cache: {} ok, {} failed, {} tools", server_tools.len(), server_errors.len(), total_tools);
+}
+
+fn cmd_validate(file: Option<&str>) {
+    let path = match file {
+        Some(f) => std::path::PathBuf::from(f),
+        None => match config::dedicated_config_path() {
+            Some(p) => p,
+            None => {
+                eprintln!("Cannot locate home directory");
+                std::process::exit(1);
+            }
+        },
+    };
+    match config::read_config_file(&path) {
+        Ok(json) => {
+            if let Some(Err(e)) = json.pointer("/settings/transforms").map(transform::parse_transforms) {
+                eprintln!("✗ {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            let errors = config::server_errors(&json);
+            if !errors.is_empty() {
+                for e in errors {
+                    eprintln!("✗ {}: server {}", path.display(), e);
+                }
+                std::process::exit(1);
+            }
+            let servers = json.get("mcpServers").or_else(|| json.get("servers"))
+                .and_then(|v| v.as_object())
+                .map(|s| s.len())
+                .unwrap_or(0);
+            println!("✓ {} is valid ({} servers)", path.display(), servers);
+        }
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 fn cmd_search(query: &str) {
@@ -185,16 +489,78 @@ fn cmd_search(query: &str) {
     }
 }
 
-/// HTTP-only server mode: dashboard + SSE, no stdio.
-/// Used by `McpHub serve` and auto-start (install).
-async fn cmd_serve() {
-    eprintln!("McpHub v{} — serve mode (HTTP only)", VERSION);
-    let config = auto_detect();
-    let proxy = std::sync::Arc::new(ProxyServer::new(config));
-    proxy.init().await;
-    eprintln!("[McpHub][SERVE] Ready. Waiting for SSE connections on http://127.0.0.1:24680/sse");
+/// HTTP address from `--bind IP:PORT` (or `--bind=IP:PORT`), `MCPHUB_BIND`,
+/// or the default, with the port of `--port` if given. Exits on an invalid
+/// address.
+fn bind_addr(args: &[String]) -> std::net::SocketAddr {
+    match config::resolve_bind_addr(flag_value(args, "--bind"), flag_value(args, "--port")) {
+        Ok(addr) => addr,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let proxy_shutdown = proxy.clone();
+/// `--client cursor|claude|vscode|all`; Cursor when absent.
+fn clients_arg(args: &[String]) -> Vec<install::Client> {
+    match flag_value(args, "--client").map(install::Client::parse_list) {
+        None => vec![install::Client::Cursor],
+        Some(Ok(clients)) => clients,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Client configs of `token show` and `token rotate`.
+fn print_token_config(token: &str, addr: std::net::SocketAddr, args: &[String]) {
+    let clients = clients_arg(args);
+    install::print_client_config(token, addr, args.iter().any(|a| a == "--redact-token"), &clients);
+    if args.iter().any(|a| a == "--write-config") {
+        println!();
+        install::write_client_configs(token, addr, &clients);
+    }
+}
+
+/// Log target of the daemon: `--log-stdout`, else `--log-path PATH`, else
+/// `default`.
+fn log_target(args: &[String], default: logging::Target) -> logging::Target {
+    if args.iter().any(|a| a == "--log-stdout") {
+        logging::Target::Stdout
+    } else if let Some(path) = flag_value(args, "--log-path") {
+        logging::Target::File(path.into())
+    } else {
+        default
+    }
+}
+
+/// Value of `--name VALUE` or `--name=VALUE`.
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix(name) {
+        Some("") => Some(args.get(i + 1).map(|s| s.as_str()).unwrap_or("")),
+        Some(rest) => rest.strip_prefix('='),
+        None => None,
+    })
+}
+
+/// Reload the config whenever the process gets SIGHUP.
+fn reload_on_sighup(proxy: std::sync::Arc<ProxyServer>) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        let Ok(mut sighup) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) else { return };
+        while sighup.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading config");
+            proxy.request_reload();
+        }
+    });
+    #[cfg(not(unix))]
+    let _ = proxy;
+}
+
+/// Shut the proxy down and exit on Ctrl-C, SIGTERM or a Windows service stop.
+fn shutdown_on_signal(proxy: std::sync::Arc<ProxyServer>) {
     tokio::spawn(async move {
         #[cfg(unix)]
         {
@@ -204,39 +570,160 @@ async fn cmd_serve() {
                 _ = sigterm.recv() => {}
             }
         }
-        #[cfg(not(unix))]
+        #[cfg(windows)]
+        {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = winservice::stop_requested() => {}
+            }
+        }
+        #[cfg(not(any(unix, windows)))]
         {
             tokio::signal::ctrl_c().await.ok();
         }
         eprintln!("\n[McpHub] Shutting down gracefully...");
-        proxy_shutdown.shutdown().await;
+        proxy.shutdown().await;
+        crashloop::clear();
+        #[cfg(windows)]
+        winservice::report_stopped();
         std::process::exit(0);
     });
+}
+
+/// The proxy the daemon serves, with its request interceptors. Register
+/// your own here.
+fn new_proxy(config: config::ProxyConfig) -> ProxyServer {
+    let blocked = config.blocked_methods.clone();
+    let proxy = ProxyServer::new(config);
+    match blocked.is_empty() {
+        true => proxy,
+        false => proxy.with_interceptor(intercept::BlockMethods(blocked)),
+    }
+}
+
+/// MCP over stdin/stdout, for clients that launch McpHub as a child
+/// server. With `http`, the HTTP server (dashboard, SSE, Streamable HTTP,
+/// WebSocket) also runs on that address, sharing the proxy. Returns when
+/// stdin closes.
+async fn cmd_stdio(http: Option<std::net::SocketAddr>) {
+    match http {
+        Some(_) => eprintln!("McpHub v{} — starting...", VERSION),
+        None => eprintln!("McpHub v{} — stdio mode (no HTTP)", VERSION),
+    }
+    let config = auto_detect();
+    let proxy = std::sync::Arc::new(new_proxy(config));
+
+    // Init proxy (load cache, start background tasks)
+    proxy.init().await;
+    reload_on_sighup(proxy.clone());
+    shutdown_on_signal(proxy.clone());
+
+    if let Some(bind_addr) = http {
+        // Try to start HTTP server in background (non-blocking if port taken)
+        let proxy_http = proxy.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dashboard::start_server(proxy_http, bind_addr).await {
+                tracing::warn!("{}. Continuing with stdio only.", e);
+            }
+        });
+
+        // Give HTTP server a moment to bind
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    // Run stdio loop (blocks until stdin closes)
+    proxy.stdio_loop().await;
+}
+
+/// HTTP-only server mode: dashboard + SSE, no stdio.
+/// Used by `McpHub serve` and auto-start (install).
+async fn cmd_serve(bind_addr: std::net::SocketAddr) {
+    eprintln!("McpHub v{} — serve mode (HTTP only)", VERSION);
+    let config = auto_detect();
+    let (max_starts, window_secs) = (config.crash_loop_max_starts, config.crash_loop_window_secs);
+    if let Err(failed) = crashloop::record_start(max_starts, window_secs) {
+        tracing::error!(
+            tag = "SERVE",
+            "Crash loop: the last {} starts failed within {}s. Not starting again; fix the error logged before them (see 'McpHub doctor'), then start McpHub by hand.",
+            failed, window_secs
+        );
+        std::process::exit(crashloop::EXIT_CRASH_LOOP);
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(window_secs)).await;
+        crashloop::clear();
+    });
+    let proxy = std::sync::Arc::new(new_proxy(config));
+    proxy.init().await;
+    reload_on_sighup(proxy.clone());
+    tracing::info!(tag = "SERVE", "Ready. Waiting for SSE connections on {}/sse", config::client_base_url(bind_addr));
+
+    shutdown_on_signal(proxy.clone());
 
-    dashboard::start_server(proxy).await;
+    if let Err(e) = dashboard::start_server(proxy.clone(), bind_addr).await {
+        tracing::error!("{}", e);
+        proxy.shutdown().await;
+        std::process::exit(1);
+    }
+}
+
+/// `serve` as a Windows service, the command `install --service` registers:
+/// the service control manager's stop ends it like Ctrl-C.
+#[cfg(windows)]
+async fn cmd_service(bind_addr: std::net::SocketAddr) {
+    if let Err(e) = winservice::connect() {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    }
+    cmd_serve(bind_addr).await;
+}
+
+#[cfg(not(windows))]
+async fn cmd_service(_bind_addr: std::net::SocketAddr) {
+    eprintln!("'McpHub service' runs under the Windows service manager; use 'McpHub serve'");
+    std::process::exit(1);
 }
 
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("serve") => logging::init_to(log_target(&args, logging::Target::Stderr)),
+        // A service has no stderr: log where `McpHub logs` looks
+        #[cfg(windows)]
+        Some("service") => logging::init_to(log_target(&args, logging::Target::File(logs::default_path()))),
+        _ => logging::init(),
+    }
 
     match args.get(1).map(|s| s.as_str()) {
         Some("help") | Some("--help") | Some("-h") => print_help(),
         Some("version") | Some("--version") | Some("-V") => println!("McpHub v{}", VERSION),
-        Some("status") => cmd_status(),
-        Some("doctor") => doctor::run(),
+        Some("status") => cmd_status(bind_addr(&args)),
+        Some("doctor") => doctor::run(!args.iter().any(|a| a == "--no-launch")).await,
+        Some("config") => match args.get(2).map(|s| s.as_str()) {
+            Some("dump") => dump::run(
+                flag_value(&args, "--bind"),
+                flag_value(&args, "--port"),
+                args.iter().any(|a| a == "--toml"),
+            ),
+            _ => eprintln!("Usage: McpHub config dump [--toml] [--bind ADDR] [--port N]"),
+        },
+        Some("validate") => cmd_validate(args.get(2).map(|s| s.as_str())),
         Some("logs") => {
             let mut server = None;
             let mut level = None;
+            let mut path = None;
             let mut iter = args.iter().skip(2);
             while let Some(arg) = iter.next() {
                 if arg == "--server" {
                     server = iter.next().map(|s| s.as_str());
                 } else if arg == "--level" {
                     level = iter.next().map(|s| s.as_str());
+                } else if arg == "--log-path" {
+                    path = iter.next().map(std::path::PathBuf::from);
                 }
             }
-            logs::run(server, level);
+            logs::run(server, level, path);
         }
         Some("add") => add::run().await,
         Some("benchmark") => benchmark::run().await,
@@ -248,55 +735,84 @@ async fn main() {
                 eprintln!("Usage: McpHub import <file>");
             }
         }
-        Some("generate") => cmd_generate().await,
-        Some("dashboard") | Some("ui") | Some("web") => dashboard::start_dashboard().await,
-        Some("install") => install::install(),
-        Some("uninstall") => install::uninstall(),
-        Some("update") => update::run(),
-        Some("serve") => cmd_serve().await,
-        Some("search") => {
-            let query = args.get(2).map(|s| s.as_str()).unwrap_or("*");
-            cmd_search(query);
-        }
-        _ => {
-            // Default: stdio proxy + HTTP server with SSE
-            eprintln!("McpHub v{} — starting...", VERSION);
-            let config = auto_detect();
-            let proxy = std::sync::Arc::new(ProxyServer::new(config));
-
-            // Init proxy (load cache, start background tasks)
-            proxy.init().await;
-
-            let proxy_shutdown = proxy.clone();
-            tokio::spawn(async move {
-                #[cfg(unix)]
-                {
-                    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
-                    tokio::select! {
-                        _ = tokio::signal::ctrl_c() => {}
-                        _ = sigterm.recv() => {}
+        Some("replay") => match args.get(2).filter(|a| !a.starts_with("--")) {
+            Some(file) => record::run_replay(file, args.iter().any(|a| a == "--mock"), args.iter().any(|a| a == "--realtime")).await,
+            None => {
+                eprintln!("Usage: McpHub replay <file.jsonl> [--realtime] [--mock]");
+                std::process::exit(1);
+            }
+        },
+        Some("generate") => cmd_generate(jobs_arg(&args)).await,
+        Some("warm") => cmd_warm(jobs_arg(&args)).await,
+        Some("cache") => {
+            // Rewrites keep the configured format
+            cache::set_gzip(auto_detect().cache_gzip);
+            match args.get(2).map(|s| s.as_str()) {
+                Some("show") => cache::run_show(args.get(3).map(|s| s.as_str())),
+                Some("prune") => {
+                    let max_age_days = args.iter().skip(3)
+                        .skip_while(|a| *a != "--max-age-days")
+                        .nth(1)
+                        .and_then(|v| v.parse::<u64>().ok());
+                    cache::run_prune(max_age_days);
+                }
+                Some("inspect") => {
+                    if let Err(e) = cache::run_inspect() {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
                     }
                 }
-                #[cfg(not(unix))]
-                {
-                    tokio::signal::ctrl_c().await.ok();
+                Some("clear") => cache::run_clear(args.get(3).map(|s| s.as_str())),
+                _ => eprintln!("Usage: McpHub cache <show [server] | inspect | clear [server] | prune [--max-age-days N]>"),
+            }
+        }
+        Some("dashboard") | Some("ui") | Some("web") => {
+            if let Err(e) = dashboard::start_dashboard(bind_addr(&args)).await {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("install") => install::install(
+            install::InstallOptions {
+                redact_token: args.iter().any(|a| a == "--redact-token"),
+                force: args.iter().any(|a| a == "--force"),
+                dry_run: args.iter().any(|a| a == "--dry-run"),
+                service: args.iter().any(|a| a == "--service"),
+                log_path: flag_value(&args, "--log-path").map(std::path::PathBuf::from),
+                log_stdout: args.iter().any(|a| a == "--log-stdout"),
+                clients: clients_arg(&args),
+                write_config: args.iter().any(|a| a == "--write-config"),
+            },
+            bind_addr(&args),
+        ),
+        Some("upgrade-check") => update::run_upgrade_check(bind_addr(&args)),
+        Some("token") => match args.get(2).map(|s| s.as_str()) {
+            Some("show") => {
+                let addr = bind_addr(&args);
+                print_token_config(&dashboard::get_auth_token(addr), addr, &args);
+            }
+            Some("rotate") => match dashboard::rotate_auth_token() {
+                Ok(token) => {
+                    tracing::info!(tag = "AUTH", "Generated a new auth token; the old one no longer works");
+                    print_token_config(&token, bind_addr(&args), &args);
                 }
-                eprintln!("\n[McpHub] Shutting down gracefully...");
-                proxy_shutdown.shutdown().await;
-                std::process::exit(0);
-            });
-
-            // Try to start HTTP server in background (non-blocking if port taken)
-            let proxy_http = proxy.clone();
-            tokio::spawn(async move {
-                dashboard::start_server(proxy_http).await;
-            });
-
-            // Give HTTP server a moment to bind
-            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-
-            // Run stdio loop (blocks until stdin closes)
-            proxy.stdio_loop().await;
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    std::process::exit(1);
+                }
+            },
+            _ => eprintln!("Usage: McpHub token <show | rotate> [--redact-token] [--client NAMES] [--write-config] [--bind IP:PORT]"),
+        },
+        Some("uninstall") => install::uninstall(args.iter().any(|a| a == "--dry-run"), args.iter().any(|a| a == "--purge")),
+        Some("update") => update::run(bind_addr(&args)),
+        Some("serve") => cmd_serve(bind_addr(&args)).await,
+        Some("service") => cmd_service(bind_addr(&args)).await,
+        Some("search") => {
+            let query = args.get(2).map(|s| s.as_str()).unwrap_or("*");
+            cmd_search(query);
         }
+        Some("stdio") => cmd_stdio(None).await,
+        // Default: stdio proxy + HTTP server with SSE
+        _ => cmd_stdio(Some(bind_addr(&args))).await,
     }
 }
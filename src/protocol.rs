@@ -6,7 +6,7 @@ use serde_json::Value;
 
 // ─── JSON-RPC 2.0 Base Types ─────────────────────────────────
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
     pub id: Option<Value>,
@@ -18,7 +18,7 @@ pub struct JsonRpcRequest {
 #[derive(Debug, Serialize)]
 pub struct JsonRpcResponse {
     pub jsonrpc: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Null when the request's id couldn't be read.
     pub id: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<Value>,
@@ -30,6 +30,8 @@ pub struct JsonRpcResponse {
 pub struct JsonRpcError {
     pub code: i64,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
 impl JsonRpcResponse {
@@ -38,16 +40,11 @@ impl JsonRpcResponse {
     }
 
     pub fn error(id: Option<Value>, code: i64, message: String) -> Self {
-        Self { jsonrpc: "2.0".into(), id, result: None, error: Some(JsonRpcError { code, message }) }
+        Self::error_with_data(id, code, message, None)
     }
 
-    #[allow(dead_code)]
-    pub fn notification(method: &str, params: Value) -> String {
-        serde_json::to_string(&serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": method,
-            "params": params,
-        })).unwrap()
+    pub fn error_with_data(id: Option<Value>, code: i64, message: String, data: Option<Value>) -> Self {
+        Self { jsonrpc: "2.0".into(), id, result: None, error: Some(JsonRpcError { code, message, data }) }
     }
 }
 
@@ -60,23 +57,75 @@ pub struct ToolDef {
     pub description: String,
     #[serde(rename = "inputSchema", default)]
     pub input_schema: Value,
+    /// Behavior hints (`readOnlyHint`, `destructiveHint`, ...), passed on as is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Value>,
 }
 
-#[derive(Debug, Serialize)]
-#[allow(dead_code)]
-pub struct ToolContent {
-    #[serde(rename = "type")]
-    pub content_type: String,
-    pub text: String,
+impl ToolDef {
+    /// Whether the server annotates the tool as not modifying anything.
+    pub fn is_read_only(&self) -> bool {
+        self.annotations.as_ref().and_then(|a| a.get("readOnlyHint")).and_then(|v| v.as_bool()) == Some(true)
+    }
+}
+
+// ─── MCP Prompt Types ────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptDef {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
 }
 
-#[allow(dead_code)]
-impl ToolContent {
-    pub fn text(s: impl Into<String>) -> Self {
-        Self { content_type: "text".into(), text: s.into() }
+impl PromptDef {
+    /// Parse one entry of a `prompts/list` result, rejecting definitions
+    /// whose argument schema can't be used to validate a `prompts/get`.
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let prompt: PromptDef = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+        if prompt.name.is_empty() {
+            return Err("prompt without a name".into());
+        }
+        let mut seen = std::collections::HashSet::new();
+        for arg in &prompt.arguments {
+            if arg.name.is_empty() {
+                return Err(format!("prompt '{}' has an argument without a name", prompt.name));
+            }
+            if !seen.insert(arg.name.as_str()) {
+                return Err(format!("prompt '{}' declares argument '{}' twice", prompt.name, arg.name));
+            }
+        }
+        Ok(prompt)
     }
 }
 
+// ─── MCP Resource Types ──────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceDef {
+    pub uri: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "mimeType", default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Fields McpHub doesn't use (`annotations`, `size`, ...), passed on as is.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
 // ─── MCP Initialize Types ────────────────────────────────────
 
 #[derive(Debug, Serialize)]
@@ -96,15 +145,63 @@ pub struct InitializeResult {
     pub instructions: Option<String>,
 }
 
+/// Capabilities advertised by the hub. Only features the hub can honor end to
+/// end are representable here: backend sub-flags like `listChanged` or
+/// `subscribe` are never passed on. The hub sets `tools.listChanged` itself
+/// in passthrough mode, where it refreshes a stale tool list.
 #[derive(Debug, Serialize)]
 pub struct Capabilities {
     pub tools: ToolsCapability,
-    pub prompts: PromptsCapability,
-    pub resources: ResourcesCapability,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<PromptsCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourcesCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<LoggingCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completions: Option<CompletionsCapability>,
+    /// Backend `experimental` capabilities, passed through uninterpreted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experimental: Option<Value>,
+}
+
+impl Capabilities {
+    /// Union of backend `initialize` capabilities, intersected with what the hub supports.
+    /// Tools are always advertised (discover/execute are the hub's own).
+    /// `experimental` keys are merged across backends; the first backend wins on conflicts.
+    pub fn from_backends<'a>(backends: impl IntoIterator<Item = &'a Value>) -> Self {
+        let mut prompts = false;
+        let mut resources = false;
+        let mut logging = false;
+        let mut completions = false;
+        let mut experimental = serde_json::Map::new();
+        for caps in backends {
+            prompts |= caps.get("prompts").is_some();
+            resources |= caps.get("resources").is_some();
+            logging |= caps.get("logging").is_some();
+            completions |= caps.get("completions").is_some();
+            if let Some(exp) = caps.get("experimental").and_then(|v| v.as_object()) {
+                for (k, v) in exp {
+                    experimental.entry(k.clone()).or_insert_with(|| v.clone());
+                }
+            }
+        }
+        Self {
+            tools: ToolsCapability { list_changed: None },
+            prompts: prompts.then_some(PromptsCapability {}),
+            resources: resources.then_some(ResourcesCapability {}),
+            logging: logging.then_some(LoggingCapability {}),
+            completions: completions.then_some(CompletionsCapability {}),
+            experimental: (!experimental.is_empty()).then_some(Value::Object(experimental)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
-pub struct ToolsCapability {}
+pub struct ToolsCapability {
+    #[serde(rename = "listChanged", skip_serializing_if = "Option::is_none")]
+    pub list_changed: Option<bool>,
+}
 
 #[derive(Debug, Serialize)]
 pub struct PromptsCapability {}
@@ -112,6 +209,43 @@ pub struct PromptsCapability {}
 #[derive(Debug, Serialize)]
 pub struct ResourcesCapability {}
 
+#[derive(Debug, Serialize)]
+pub struct LoggingCapability {}
+
+#[derive(Debug, Serialize)]
+pub struct CompletionsCapability {}
+
+/// Syslog severities used by `logging/setLevel` and `notifications/message`,
+/// least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        Some(match level {
+            "debug" => Self::Debug,
+            "info" => Self::Info,
+            "notice" => Self::Notice,
+            "warning" => Self::Warning,
+            "error" => Self::Error,
+            "critical" => Self::Critical,
+            "alert" => Self::Alert,
+            "emergency" => Self::Emergency,
+            _ => return None,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,9 +286,12 @@ mod tests {
         let init_result = InitializeResult {
             protocol_version: "2024-11-05".to_string(),
             capabilities: Capabilities {
-                tools: ToolsCapability {},
-                prompts: PromptsCapability {},
-                resources: ResourcesCapability {},
+                tools: ToolsCapability { list_changed: None },
+                prompts: Some(PromptsCapability {}),
+                resources: Some(ResourcesCapability {}),
+                logging: None,
+                completions: None,
+                experimental: None,
             },
             server_info: ServerInfo {
                 name: "McpHub".to_string(),
@@ -167,4 +304,26 @@ mod tests {
         assert!(result_str.contains(r#""capabilities":{"tools":{},"prompts":{},"resources":{}}"#));
         assert!(result_str.contains(r#""serverInfo":{"name":"McpHub","version":"2.0.0"}"#));
     }
+
+    #[test]
+    fn test_capabilities_from_mixed_backends() {
+        let tools_only = json!({"tools": {}});
+        let with_prompts = json!({"tools": {"listChanged": true}, "prompts": {"listChanged": true}});
+
+        let caps = Capabilities::from_backends([&tools_only, &with_prompts]);
+        let result_str = serde_json::to_string(&caps).unwrap();
+        // Prompts unioned in, but the unsupported listChanged flag is not advertised
+        assert_eq!(result_str, r#"{"tools":{},"prompts":{}}"#);
+
+        let caps = Capabilities::from_backends([&tools_only]);
+        assert_eq!(serde_json::to_string(&caps).unwrap(), r#"{"tools":{}}"#);
+    }
+
+    #[test]
+    fn test_capabilities_merge_experimental() {
+        let a = json!({"tools": {}, "experimental": {"streaming": {"v": 1}}});
+        let b = json!({"tools": {}, "experimental": {"streaming": {"v": 2}, "x-trace": true}});
+        let caps = Capabilities::from_backends([&a, &b]);
+        assert_eq!(caps.experimental, Some(json!({"streaming": {"v": 1}, "x-trace": true})));
+    }
 }
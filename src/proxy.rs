@@ -1,19 +1,35 @@
 /// Core proxy server: reads JSON-RPC from stdin, routes to child servers.
 /// Two modes: discover (2 meta-tools) or passthrough (all tools exposed).
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Instant, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
-use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::Mutex;
+use tokio::io::{self, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, watch, Mutex, Notify};
 
-use crate::child::ChildManager;
-use crate::config::{Mode, Preload, ProxyConfig};
+use crate::breaker::BreakerStatus;
+use crate::cache::{CacheWriter, SchemaCache};
+use crate::child::{CallContext, CallTrace, ChildManager, ClientRequest, GroupHealth, RestartStats};
+use crate::config::{Mode, PreInitialize, Preload, ProxyConfig, ToolNames};
+use crate::error::{self, Failure};
+use crate::framing::MessageReader;
 use crate::health::HealthMonitor;
+use crate::intercept::{self, RequestInterceptor};
+use crate::limits::{self, ResultPolicy};
 use crate::protocol::*;
+use crate::audit::{AuditEntry, AuditLog, Outcome};
+use crate::record::Recorder;
+use crate::coalesce::Coalescer;
+use crate::result_cache::{ResultCache, ToolCacheStats};
+use crate::schema;
 use crate::search::{IndexedTool, SearchEngine};
+use crate::transform;
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct ServerMetrics {
     pub call_count: u64,
     pub error_count: u64,
@@ -22,15 +38,65 @@ pub struct ServerMetrics {
     pub last_error: Option<String>,
 }
 
-impl Default for ServerMetrics {
+/// Upper bounds (ms) of the tool latency histogram buckets.
+/// A final overflow bucket catches everything above the last bound.
+pub const LATENCY_BUCKETS_MS: [u64; 12] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000];
+
+/// Calls of one upstream tool. Latency is the backend round-trip only:
+/// cold starts, waiting for a free instance and cache hits are left out.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ToolMetrics {
+    pub call_count: u64,
+    /// Failed calls and `isError` results.
+    pub error_count: u64,
+    /// Per-bucket counts; one longer than `LATENCY_BUCKETS_MS` (last is overflow).
+    pub latency_bucket_counts: Vec<u64>,
+    pub latency_sum_ms: u64,
+    /// Estimated from the histogram: the upper bound of the bucket holding
+    /// the quantile (None while nothing was measured, or in the overflow bucket).
+    pub p50_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+}
+
+impl Default for ToolMetrics {
     fn default() -> Self {
         Self {
             call_count: 0,
             error_count: 0,
-            total_latency_ms: 0,
-            last_call_time: None,
-            last_error: None,
+            latency_bucket_counts: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            latency_sum_ms: 0,
+            p50_ms: None,
+            p95_ms: None,
+        }
+    }
+}
+
+impl ToolMetrics {
+    fn record(&mut self, latency: Option<Duration>, failed: bool) {
+        self.call_count += 1;
+        if failed {
+            self.error_count += 1;
+        }
+        let Some(latency) = latency else { return };
+        let ms = latency.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS.iter().position(|&bound| ms <= bound).unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latency_bucket_counts[bucket] += 1;
+        self.latency_sum_ms += ms;
+        self.p50_ms = self.quantile_ms(0.5);
+        self.p95_ms = self.quantile_ms(0.95);
+    }
+
+    fn quantile_ms(&self, q: f64) -> Option<u64> {
+        let total: u64 = self.latency_bucket_counts.iter().sum();
+        let rank = ((total as f64) * q).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.latency_bucket_counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank && total > 0 {
+                return LATENCY_BUCKETS_MS.get(i).copied();
+            }
         }
+        None
     }
 }
 
@@ -39,7 +105,11 @@ pub struct GlobalMetrics {
     pub start_time: SystemTime,
     pub total_requests: u64,
     pub active_sse_sessions: usize,
+    /// SSE session limit; 0 when SSE isn't served.
+    pub max_sse_sessions: usize,
     pub servers: HashMap<String, ServerMetrics>,
+    /// Keyed by server, then by the server's own tool name.
+    pub tools: HashMap<String, HashMap<String, ToolMetrics>>,
 }
 
 impl GlobalMetrics {
@@ -48,41 +118,255 @@ impl GlobalMetrics {
             start_time: SystemTime::now(),
             total_requests: 0,
             active_sse_sessions: 0,
+            max_sse_sessions: 0,
             servers: HashMap::new(),
+            tools: HashMap::new(),
+        }
+    }
+}
+
+/// Per-tool counters and latency histograms in Prometheus text format.
+pub fn tools_prometheus(tools: &HashMap<String, HashMap<String, ToolMetrics>>) -> String {
+    let mut rows: Vec<(&String, &String, &ToolMetrics)> = tools
+        .iter()
+        .flat_map(|(server, tools)| tools.iter().map(move |(tool, m)| (server, tool, m)))
+        .collect();
+    rows.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let mut calls = String::from("# HELP mcphub_tool_calls_total Upstream tool calls\n# TYPE mcphub_tool_calls_total counter\n");
+    let mut errors = String::from("# HELP mcphub_tool_errors_total Upstream tool calls that failed\n# TYPE mcphub_tool_errors_total counter\n");
+    let mut latency = String::from(
+        "# HELP mcphub_tool_call_duration_seconds Upstream tool round-trip time\n# TYPE mcphub_tool_call_duration_seconds histogram\n",
+    );
+    for (server, tool, m) in rows {
+        let labels = format!("server=\"{}\",tool=\"{}\"", prometheus_label(server), prometheus_label(tool));
+        calls.push_str(&format!("mcphub_tool_calls_total{{{labels}}} {}\n", m.call_count));
+        errors.push_str(&format!("mcphub_tool_errors_total{{{labels}}} {}\n", m.error_count));
+        let mut cumulative = 0;
+        for (i, count) in m.latency_bucket_counts.iter().enumerate() {
+            cumulative += count;
+            let le = LATENCY_BUCKETS_MS.get(i).map_or("+Inf".to_string(), |b| (*b as f64 / 1000.0).to_string());
+            latency.push_str(&format!("mcphub_tool_call_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulative}\n"));
+        }
+        latency.push_str(&format!("mcphub_tool_call_duration_seconds_sum{{{labels}}} {}\n", m.latency_sum_ms as f64 / 1000.0));
+        latency.push_str(&format!("mcphub_tool_call_duration_seconds_count{{{labels}}} {cumulative}\n"));
+    }
+    calls + &errors + &latency
+}
+
+/// One server's value in a metric family; None leaves the server out.
+type ServerValue<'a> = &'a dyn Fn(&str) -> Option<u64>;
+
+/// Per configured server: `mcphub_server_up` (1 while at least one of its
+/// instances is up, 0 when it is stopped or failed), restart and respawn
+/// counters, the time of the last one, and the breaker's consecutive failures.
+pub fn servers_prometheus(
+    servers: &[String],
+    health: &HashMap<String, GroupHealth>,
+    restarts: &HashMap<String, RestartStats>,
+    breakers: &HashMap<String, BreakerStatus>,
+) -> String {
+    let mut servers = servers.to_vec();
+    servers.sort();
+    let none = RestartStats::default();
+    let families: [(&str, &str, &str, ServerValue); 6] = [
+        ("mcphub_server_up", "gauge", "Whether a server has an instance up", &|s| Some(health.get(s).is_some_and(|h| h.up > 0) as u64)),
+        ("mcphub_server_restarts_total", "counter", "Whole-server restarts since startup", &|s| Some(restarts.get(s).unwrap_or(&none).restarts)),
+        ("mcphub_server_restart_failures_total", "counter", "Restarts that failed to bring the server back", &|s| Some(restarts.get(s).unwrap_or(&none).failed_restarts)),
+        ("mcphub_server_respawns_total", "counter", "Pool instances respawned after going down", &|s| Some(restarts.get(s).unwrap_or(&none).respawns)),
+        ("mcphub_server_last_restart_timestamp_seconds", "gauge", "Unix time of the last restart or respawn", &|s| restarts.get(s).and_then(|r| r.last_restart_at)),
+        ("mcphub_server_consecutive_failures", "gauge", "Consecutive failed tool calls counted by the circuit breaker", &|s| Some(breakers.get(s).map_or(0, |b| b.consecutive_failures as u64))),
+    ];
+    let mut text = String::new();
+    for (name, kind, help, value) in families {
+        text.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n"));
+        for server in &servers {
+            if let Some(v) = value(server) {
+                text.push_str(&format!("{name}{{server=\"{}\"}} {v}\n", prometheus_label(server)));
+            }
         }
     }
+    text
+}
+
+fn prometheus_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
 }
 
 pub struct ProxyServer {
     config: Arc<Mutex<ProxyConfig>>,
     child_manager: Arc<ChildManager>,
+    /// Schema cache writes not on disk yet; flushed on shutdown.
+    cache: Arc<CacheWriter>,
     search_engine: Arc<Mutex<SearchEngine>>,
+    /// Backend capabilities from the schema cache, for servers not running yet.
+    cached_capabilities: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    /// Prompt definitions per server: from the schema cache, refreshed by
+    /// live `prompts/list` results.
+    cached_prompts: Arc<Mutex<HashMap<String, Vec<PromptDef>>>>,
+    /// Resources per server: from the schema cache, refreshed by live
+    /// `resources/list` results.
+    cached_resources: Arc<Mutex<HashMap<String, Vec<ResourceDef>>>>,
     pub metrics: Arc<Mutex<GlobalMetrics>>,
+    /// Set once graceful shutdown begins; transports stop dispatching.
+    shutting_down: AtomicBool,
+    shutdown_signal: watch::Sender<bool>,
+    /// Full results of calls answered with the `reference` policy, newest
+    /// last: uri, the session that made the call, result.
+    stored_results: Mutex<VecDeque<(String, Option<String>, serde_json::Value)>>,
+    /// Results of cacheable tools (`resultCache`).
+    result_cache: Mutex<ResultCache>,
+    /// Shares list requests arriving together (`listCacheMs`).
+    lists: Arc<Coalescer>,
+    /// Cancel handles of sessions' in-flight `tools/call`s, keyed by
+    /// session and request id.
+    in_flight_calls: Mutex<HashMap<(String, String), watch::Sender<bool>>>,
+    audit: AuditLog,
+    /// Session recordings (`record`), written per session request.
+    recorder: Recorder,
+    /// Wakes the config watcher to reload now.
+    reload: Arc<Notify>,
+    pre_initialize: PreInitialize,
+    /// Run around every dispatched request, in order.
+    interceptors: Vec<Arc<dyn RequestInterceptor>>,
+    /// Set once startup discovery (cache load and preload) has finished.
+    discovered: Arc<AtomicBool>,
+    /// Hub-originated notifications for every client session.
+    client_broadcasts: broadcast::Sender<serde_json::Value>,
+}
+
+/// What a config or cache reload updates, shared with the watcher.
+#[derive(Clone)]
+struct Reloadable {
+    engine: Arc<Mutex<SearchEngine>>,
+    config: Arc<Mutex<ProxyConfig>>,
+    child_manager: Arc<ChildManager>,
+    cached_capabilities: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    cached_prompts: Arc<Mutex<HashMap<String, Vec<PromptDef>>>>,
+    cached_resources: Arc<Mutex<HashMap<String, Vec<ResourceDef>>>>,
+    client_broadcasts: broadcast::Sender<serde_json::Value>,
+    cache: Arc<CacheWriter>,
+    lists: Arc<Coalescer>,
+}
+
+/// Where a session's request stands against the `initialize` handshake.
+pub enum Handshake {
+    Ready,
+    /// Sent before `initialize` under the strict policy.
+    Rejected(JsonRpcResponse),
+    /// First request of a session that skipped `initialize`; a default
+    /// initialize runs before it.
+    Assumed,
+}
+
+/// Oversized results kept for `mcphub://results/<id>` reads.
+const STORED_RESULTS: usize = 16;
+
+/// How long config files must stay unchanged before a reload.
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Most text one streamed result chunk carries.
+const STREAM_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Servers a client session is limited to (`GET /sse?servers=a,b`).
+pub type Scope = HashSet<String>;
+
+/// A tool result sent in pieces: `notifications/mcphub/chunk` messages in
+/// order, then `done`, the response with the content moved out.
+pub struct ChunkedReply {
+    pub chunks: Vec<serde_json::Value>,
+    pub done: JsonRpcResponse,
 }
 
 impl ProxyServer {
     pub fn new(config: ProxyConfig) -> Self {
-        let child_manager = Arc::new(ChildManager::new(
-            config.servers.clone(),
-            config.idle_timeout_ms,
-        ));
+        crate::logging::set_rotation(config.log_rotation);
+        crate::redact::set_extra_patterns(&config.cache_redact_patterns);
+        crate::cache::set_gzip(config.cache_gzip);
+        let cache = Arc::new(CacheWriter::new());
+        let child_manager = Arc::new(
+            ChildManager::new(config.servers.clone(), config.idle_timeout_ms)
+                .with_timeouts(config.init_timeout_secs, config.call_timeout_secs)
+                .with_init_retries(config.init_retries, config.init_retry_backoff_ms)
+                .with_activation_timeout(config.activation_timeout_secs)
+                .with_circuit_breaker(config.circuit_breaker)
+                .with_max_concurrent(config.max_concurrent_calls)
+                .with_failure_recording(cache.clone())
+                .with_max_message_bytes(config.stdio_max_message_bytes)
+                .with_stderr_limit(config.stdio_max_stderr_lines_per_sec)
+                .with_resource_affinity(config.resource_affinity)
+                .with_sampling(config.forward_sampling)
+                .with_tool_filter(config.tool_filter.clone())
+                .with_tool_overrides(config.tool_overrides.clone()),
+        );
 
         Self {
+            pre_initialize: config.pre_initialize,
+            audit: AuditLog::new(&config.audit),
+            recorder: Recorder::new(&config.record),
+            reload: Arc::new(Notify::new()),
             config: Arc::new(Mutex::new(config)),
             child_manager,
+            cache,
             search_engine: Arc::new(Mutex::new(SearchEngine::new())),
+            cached_capabilities: Arc::new(Mutex::new(HashMap::new())),
+            cached_prompts: Arc::new(Mutex::new(HashMap::new())),
+            cached_resources: Arc::new(Mutex::new(HashMap::new())),
             metrics: Arc::new(Mutex::new(GlobalMetrics::new())),
+            shutting_down: AtomicBool::new(false),
+            shutdown_signal: watch::Sender::new(false),
+            stored_results: Mutex::new(VecDeque::new()),
+            result_cache: Mutex::new(ResultCache::default()),
+            lists: Arc::new(Coalescer::default()),
+            in_flight_calls: Mutex::new(HashMap::new()),
+            interceptors: Vec::new(),
+            discovered: Arc::new(AtomicBool::new(false)),
+            client_broadcasts: broadcast::channel(64).0,
+        }
+    }
+
+    fn reloadable(&self) -> Reloadable {
+        Reloadable {
+            engine: self.search_engine.clone(),
+            config: self.config.clone(),
+            child_manager: self.child_manager.clone(),
+            cached_capabilities: self.cached_capabilities.clone(),
+            cached_prompts: self.cached_prompts.clone(),
+            cached_resources: self.cached_resources.clone(),
+            client_broadcasts: self.client_broadcasts.clone(),
+            cache: self.cache.clone(),
+            lists: self.lists.clone(),
         }
     }
 
+    /// Add a hook run around every request, after those added before it.
+    pub fn with_interceptor(mut self, interceptor: impl RequestInterceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
     /// Initialize proxy: load cache, start background tasks.
     /// Call this before stdio_loop() or serving SSE.
     pub async fn init(&self) {
         // 1. Load cache synchronously FIRST (instant, <1ms)
-        if let Some(cached) = crate::cache::load_cache() {
+        let loaded = load_configured_cache(&*self.config.lock().await);
+        if let Some((cached, stale)) = loaded {
+            self.refetch_stale(stale).await;
+            *self.cached_capabilities.lock().await = cached.capabilities.clone();
+            *self.cached_prompts.lock().await = cached.prompts.clone();
+            *self.cached_resources.lock().await = cached.resources.clone();
+            let (filter, overrides, configured) = {
+                let config = self.config.lock().await;
+                (
+                    config.tool_filter.clone(),
+                    config.tool_overrides.clone(),
+                    config.servers.keys().cloned().collect::<std::collections::HashSet<_>>(),
+                )
+            };
             let mut all_tools: Vec<IndexedTool> = Vec::new();
-            for (server_name, tools) in &cached.servers {
-                for tool in tools {
+            for (server_name, tools) in cached.servers.iter().filter(|(name, _)| configured.contains(*name)) {
+                // Cached tools carry their client-facing names; the filter matches upstream ones
+                for tool in tools.iter().filter(|t| overrides.upstream_name(server_name, &t.name).is_some_and(|u| filter.allows(server_name, u))) {
                     all_tools.push(IndexedTool {
                         name: format!("{}__{}", server_name, tool.name),
                         original_name: tool.name.clone(),
@@ -95,13 +379,31 @@ impl ProxyServer {
             if !all_tools.is_empty() {
                 let mut eng = self.search_engine.lock().await;
                 eng.build_index(all_tools);
-                eprintln!("[McpHub][INFO] Ready: {} tools from cache", eng.tool_count());
+                tracing::info!("Ready: {} tools from cache", eng.tool_count());
             }
         } else {
-            eprintln!("[McpHub][WARN] No cache found. Run 'McpHub generate' for instant startup.");
+            tracing::warn!("No cache found. Run 'McpHub generate' for instant startup.");
+        }
+
+        // 2. Preload the servers `preload` names in the background; the
+        // others (all, by default) start on their first call, and their
+        // tools come from the cache until then
+        let preload = self.servers_to_preload().await;
+        if preload.is_empty() {
+            self.discovered.store(true, Ordering::Relaxed);
+        } else {
+            let (delay_ms, concurrency) = {
+                let config = self.config.lock().await;
+                (config.preload_delay_ms, config.preload_concurrency)
+            };
+            let (manager, engine, discovered) = (self.child_manager.clone(), self.search_engine.clone(), self.discovered.clone());
+            tokio::spawn(async move {
+                preload_servers(manager, engine, preload, delay_ms, concurrency).await;
+                discovered.store(true, Ordering::Relaxed);
+            });
         }
 
-        // 2. Start idle reaper
+        // 3. Start idle reaper
         let manager_reap = self.child_manager.clone();
         tokio::spawn(async move {
             loop {
@@ -110,15 +412,30 @@ impl ProxyServer {
             }
         });
 
-        // 3. Start config & cache hot-reload watcher
-        let engine_watch = self.search_engine.clone();
-        let config_watch = self.config.clone();
-        let child_manager_watch = self.child_manager.clone();
+        // 4. Start config & cache hot-reload watcher
+        let (reloadable, reload_watch) = (self.reloadable(), self.reload.clone());
+        tokio::spawn(async move {
+            config_and_cache_watcher(reloadable, reload_watch).await;
+        });
+
+        // 5. Forget shared lists when one changes
+        let mut notifications = self.child_manager.subscribe_notifications();
+        let lists = self.lists.clone();
         tokio::spawn(async move {
-            config_and_cache_watcher(engine_watch, config_watch, child_manager_watch).await;
+            loop {
+                match notifications.recv().await {
+                    Ok((_, notification)) => {
+                        if notification["method"].as_str().is_some_and(|m| m.ends_with("/list_changed")) {
+                            lists.invalidate();
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => lists.invalidate(),
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
         });
 
-        // 4. Start health monitor (notifications + auto-restart)
+        // 6. Start health monitor (notifications + auto-restart)
         let config = self.config.lock().await;
         if config.health_notifications {
             let monitor = HealthMonitor::new(
@@ -132,20 +449,121 @@ impl ProxyServer {
         }
     }
 
-    /// Full run: init + stdio loop. Backward compatible.
-    pub async fn run(&self) {
-        self.init().await;
-        self.stdio_loop().await;
+    /// Fetch the tools of servers whose cache entries were too old, in the
+    /// background. The cache write is picked up by the hot-reload watcher.
+    async fn refetch_stale(&self, stale: Vec<String>) {
+        let configured = self.config.lock().await.servers.keys().cloned().collect::<std::collections::HashSet<_>>();
+        let stale: Vec<String> = stale.into_iter().filter(|name| configured.contains(name)).collect();
+        if stale.is_empty() {
+            return;
+        }
+        let manager = self.child_manager.clone();
+        tokio::spawn(async move {
+            for name in stale {
+                let refreshed = match manager.start_server(&name).await {
+                    Ok(_) => manager.refresh_tools(&name).await,
+                    Err(e) => Err(e),
+                };
+                match refreshed {
+                    Ok(tools) => tracing::info!("Refreshed stale schema of {}: {} tools", name, tools.len()),
+                    Err(e) => tracing::warn!("Could not refresh stale schema of {}: {}", name, e),
+                }
+            }
+        });
+    }
+
+    /// Reload the config now instead of at the next poll (e.g. on SIGHUP).
+    pub fn request_reload(&self) {
+        self.reload.notify_one();
+    }
+
+    /// Reload the config files and apply them before returning, so the next
+    /// request already sees the result (the watcher would take seconds).
+    pub async fn reload_config(&self) -> Result<(), String> {
+        let new_config = crate::config::reload()?;
+        apply_config(new_config, &self.reloadable()).await;
+        Ok(())
+    }
+
+    /// Snapshot of the current (possibly hot-reloaded) configuration.
+    pub async fn config(&self) -> ProxyConfig {
+        self.config.lock().await.clone()
+    }
+
+    /// Instance group health of every running server.
+    pub async fn group_health(&self) -> HashMap<String, GroupHealth> {
+        self.child_manager.group_health().await
+    }
+
+    /// Whether startup discovery has finished: the cache is loaded and the
+    /// preloaded servers have started or failed.
+    pub fn discovered(&self) -> bool {
+        self.discovered.load(Ordering::Relaxed)
+    }
+
+    /// Circuit breaker state of every server that has been called.
+    pub async fn breaker_states(&self) -> HashMap<String, BreakerStatus> {
+        self.child_manager.breaker_states().await
+    }
+
+    /// Restart and respawn counts per server since startup.
+    pub fn restart_stats(&self) -> HashMap<String, RestartStats> {
+        self.child_manager.restart_stats()
     }
 
+    /// Restart one server, letting in-flight calls finish first.
+    pub async fn reload_server(&self, name: &str) -> Result<usize, String> {
+        let grace = Duration::from_secs(self.config.lock().await.reload_grace_secs);
+        self.child_manager.reload_server(name, grace).await.map_err(String::from)
+    }
+
+    /// Ping one running server; see `ChildManager::ping_server`.
+    pub async fn ping_server(&self, name: &str) -> Result<Duration, String> {
+        self.child_manager.ping_server(name).await.map_err(String::from)
+    }
+
+    /// Stop one server, letting in-flight calls finish first.
+    pub async fn stop_server(&self, name: &str) -> Result<(), String> {
+        let grace = Duration::from_secs(self.config.lock().await.reload_grace_secs);
+        self.child_manager.stop_server_gracefully(name, grace).await
+    }
+
+    /// Close client sessions, then stop the backends. Both get up to the
+    /// `shutdownGracePeriod`.
     pub async fn shutdown(&self) {
-        self.child_manager.stop_all().await;
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let grace = Duration::from_secs(self.config.lock().await.shutdown_grace_secs);
+        self.shutdown_signal.send_replace(true);
+        let _ = tokio::time::timeout(grace, self.shutdown_signal.closed()).await;
+        self.child_manager.shutdown(grace).await;
+        let cache = self.cache.clone();
+        let _ = tokio::task::spawn_blocking(move || cache.flush()).await;
+        self.audit.flush();
+    }
+
+    /// Turns true when shutdown begins. Transports drop the receiver once
+    /// their sessions are closed; `shutdown` waits for that.
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown_signal.subscribe()
+    }
+
+    /// The schema cache, including server state not written to disk yet.
+    pub fn read_cache(&self) -> Option<SchemaCache> {
+        self.cache.read()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
     }
 
     async fn servers_to_preload(&self) -> Vec<String> {
         let config = self.config.lock().await;
         match &config.preload {
-            Preload::All => self.child_manager.server_names().await,
+            Preload::All => {
+                let mut names = self.child_manager.server_names().await;
+                names.sort();
+                names
+            }
             Preload::Some(names) => names.clone(),
             Preload::None => Vec::new(),
         }
@@ -154,82 +572,453 @@ impl ProxyServer {
     pub async fn stdio_loop(&self) {
         let stdin = io::stdin();
         let mut stdout = io::stdout();
-        let reader = BufReader::new(stdin);
-        let mut lines = reader.lines();
+        let max_bytes = self.config.lock().await.stdio_max_message_bytes;
+        let mut reader = MessageReader::new(BufReader::new(stdin), max_bytes, "stdin");
+        let (tx, mut messages) = tokio::sync::mpsc::channel(64);
+        tokio::spawn(async move {
+            while let Ok(Some(message)) = reader.next_message().await {
+                if tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
 
-        while let Ok(Some(line)) = lines.next_line().await {
-            let line = line.trim().to_string();
-            if line.is_empty() {
-                continue;
+        // Requests are answered one at a time, in order, but read as they
+        // arrive so that a cancellation reaches the one being answered.
+        let mut initialized = false;
+        let mut open = true;
+        let mut queued = VecDeque::new();
+        let mut current: Option<Pin<Box<dyn Future<Output = Option<JsonRpcResponse>> + '_>>> = None;
+        loop {
+            if current.is_none() {
+                match queued.pop_front() {
+                    Some((request, handshake)) => current = Some(Box::pin(self.handle_session_request("stdio", request, handshake))),
+                    None if !open => break,
+                    None => {}
+                }
+            }
+            tokio::select! {
+                message = messages.recv(), if open => {
+                    let Some(message) = message else {
+                        open = false;
+                        continue;
+                    };
+                    let request: JsonRpcRequest = match serde_json::from_value(message) {
+                        Ok(r) => r,
+                        Err(_) => continue,
+                    };
+                    let handshake = self.handshake(&request, &mut initialized);
+                    match request.method.as_str() {
+                        "notifications/cancelled" => {
+                            self.handle_session_request("stdio", request, handshake).await;
+                        }
+                        _ => queued.push_back((request, handshake)),
+                    }
+                }
+                response = async { current.as_mut().unwrap().await }, if current.is_some() => {
+                    current = None;
+                    if let Some(resp) = response {
+                        let mut msg = serde_json::to_string(&resp).unwrap();
+                        msg.push('\n');
+                        let _ = stdout.write_all(msg.as_bytes()).await;
+                        let _ = stdout.flush().await;
+                    }
+                }
+            }
+        }
+
+        // Cleanup
+        self.shutdown().await;
+    }
+
+    /// Check a session's request against its handshake, marking the session
+    /// `initialized` once it is. Done in arrival order, before dispatch.
+    pub fn handshake(&self, req: &JsonRpcRequest, initialized: &mut bool) -> Handshake {
+        if req.method == "initialize" {
+            *initialized = true;
+            return Handshake::Ready;
+        }
+        // Notifications and pings are allowed before initialize
+        if *initialized || req.id.is_none() || req.method == "ping" {
+            return Handshake::Ready;
+        }
+        match self.pre_initialize {
+            PreInitialize::Strict => Handshake::Rejected(JsonRpcResponse::error(
+                req.id.clone(),
+                error::NOT_INITIALIZED,
+                format!("Server not initialized: send initialize before {}", req.method),
+            )),
+            PreInitialize::Lenient => {
+                *initialized = true;
+                Handshake::Assumed
+            }
+        }
+    }
+
+    /// `handle_request` for a session request that went through `handshake`.
+    pub async fn handle_session_request(&self, session: &str, req: JsonRpcRequest, handshake: Handshake) -> Option<JsonRpcResponse> {
+        let recording = self.recorder.enabled().then(|| (serde_json::to_value(&req).unwrap_or_default(), Instant::now()));
+        let response = self.answer_session_request(session, req, handshake, None).await;
+        if let Some((request, started)) = recording {
+            self.recorder.record(session, &request, response.as_ref(), started.elapsed());
+        }
+        response
+    }
+
+    /// Answer `req` once `handshake` allows it. `scope` limits the servers
+    /// unknown-tool suggestions come from.
+    async fn answer_session_request(&self, session: &str, req: JsonRpcRequest, handshake: Handshake, scope: Option<&Scope>) -> Option<JsonRpcResponse> {
+        match handshake {
+            Handshake::Ready => {}
+            Handshake::Rejected(response) => return Some(response),
+            Handshake::Assumed => {
+                tracing::warn!("{} sent before initialize, initializing with defaults", req.method);
+                self.handle_initialize(None, serde_json::json!({})).await;
+            }
+        }
+        self.answer_request_in(session, req, scope).await
+    }
+
+    pub async fn handle_request(&self, req: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        self.route_request(req, CallContext::default()).await
+    }
+
+    /// Check a `servers` connect parameter (comma-separated) against the
+    /// configured servers.
+    pub async fn parse_scope(&self, servers: &str) -> Result<Scope, String> {
+        let scope: Scope = servers.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect();
+        if scope.is_empty() {
+            return Err("servers: no server names given".into());
+        }
+        let config = self.config.lock().await;
+        let mut unknown: Vec<&str> = scope.iter().map(String::as_str).filter(|s| !config.servers.contains_key(*s)).collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(format!("servers: unknown server(s): {}", unknown.join(", ")));
+        }
+        Ok(scope)
+    }
+
+    /// `handle_session_request` for a session limited to `scope`: its
+    /// `tools/list`, `discover`, prompt and resource lists only show those
+    /// servers' entries, and requests naming any other server are rejected.
+    pub async fn handle_scoped_request(&self, session: &str, req: JsonRpcRequest, handshake: Handshake, scope: &Scope) -> Option<JsonRpcResponse> {
+        let recording = self.recorder.enabled().then(|| (serde_json::to_value(&req).unwrap_or_default(), Instant::now()));
+        let response = self.answer_scoped_request(session, req, handshake, scope).await;
+        if let Some((request, started)) = recording {
+            self.recorder.record(session, &request, response.as_ref(), started.elapsed());
+        }
+        response
+    }
+
+    async fn answer_scoped_request(&self, session: &str, req: JsonRpcRequest, handshake: Handshake, scope: &Scope) -> Option<JsonRpcResponse> {
+        if matches!(handshake, Handshake::Rejected(_)) {
+            return self.answer_session_request(session, req, handshake, Some(scope)).await;
+        }
+        let mode = self.config.lock().await.mode.clone();
+        match req.method.as_str() {
+            "tools/call" => {
+                let (server, tool, _) = self.call_target(&req.params).await;
+                if let Some(server) = server.filter(|s| !scope.contains(s)) {
+                    return Some(JsonRpcResponse::error(
+                        req.id,
+                        error::INVALID_PARAMS,
+                        format!("Tool '{}' is on server '{}', which this session can't use", tool, server),
+                    ));
+                }
+                self.answer_session_request(session, req, handshake, Some(scope)).await
+            }
+            "tools/list" if mode == Mode::Passthrough => {
+                let mut response = self.answer_session_request(session, req, handshake, Some(scope)).await;
+                if let Some(tools) = response.as_mut().and_then(|r| r.result.as_mut()).and_then(|r| r.get_mut("tools")).and_then(|t| t.as_array_mut()) {
+                    let mut kept = Vec::with_capacity(tools.len());
+                    for tool in tools.drain(..) {
+                        let name = tool["name"].as_str().unwrap_or_default();
+                        if self.resolve_tool_name(name).await.is_some_and(|(server, _)| scope.contains(&server)) {
+                            kept.push(tool);
+                        }
+                    }
+                    *tools = kept;
+                }
+                response
+            }
+            "ping" => match req.params.get("server").and_then(|s| s.as_str()) {
+                Some(server) if !scope.contains(server) => Some(JsonRpcResponse::error(
+                    req.id,
+                    error::INVALID_PARAMS,
+                    format!("Server '{}' is not available to this session", server),
+                )),
+                _ => self.answer_session_request(session, req, handshake, Some(scope)).await,
+            },
+            "prompts/get" | "resources/read" | "resources/subscribe" | "resources/unsubscribe" | "completion/complete" => {
+                match self.named_server(&req.method, &req.params).await {
+                    Some(server) if !scope.contains(&server) => Some(JsonRpcResponse::error(
+                        req.id,
+                        error::INVALID_PARAMS,
+                        format!("Server '{}' is not available to this session", server),
+                    )),
+                    _ => self.answer_session_request(session, req, handshake, Some(scope)).await,
+                }
+            }
+            "prompts/list" | "resources/list" | "resources/templates/list" => {
+                let (list, field) = match req.method.as_str() {
+                    "prompts/list" => ("prompts", "name"),
+                    "resources/list" => ("resources", "uri"),
+                    _ => ("resourceTemplates", "uriTemplate"),
+                };
+                let mut response = self.answer_session_request(session, req, handshake, Some(scope)).await;
+                if let Some(items) = response.as_mut().and_then(|r| r.result.as_mut()).and_then(|r| r.get_mut(list)).and_then(|l| l.as_array_mut()) {
+                    items.retain(|item| item[field].as_str().and_then(|n| n.split_once("__")).is_some_and(|(server, _)| scope.contains(server)));
+                }
+                response
             }
+            _ => self.answer_session_request(session, req, handshake, Some(scope)).await,
+        }
+    }
+
+    /// The server a `prompts/get`, `resources/*` or `completion/complete`
+    /// request names with its `server__` prefix. Completions resolve the
+    /// prefix like `handle_completion` does; a stored result names none.
+    async fn named_server(&self, method: &str, params: &serde_json::Value) -> Option<String> {
+        let name = match method {
+            "prompts/get" => params.get("name"),
+            "completion/complete" => params.get("ref").zip(completion_ref_key(params).ok()).and_then(|(r, key)| r.get(key)),
+            _ => params.get("uri"),
+        }?;
+        let (server, _) = name.as_str()?.split_once("__")?;
+        match method {
+            "completion/complete" => self.child_manager.resolve_name(server).await,
+            _ => Some(server.to_string()),
+        }
+    }
+
+    /// `handle_request` on behalf of a client session, whose requests it can
+    /// then cancel with `notifications/cancelled`. A cancelled `tools/call`
+    /// is cancelled on its server too; other requests are abandoned. A
+    /// request reusing the id of one still in flight is rejected.
+    pub async fn handle_request_in(&self, session: &str, req: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let recording = self.recorder.enabled().then(|| (serde_json::to_value(&req).unwrap_or_default(), Instant::now()));
+        let response = self.answer_request_in(session, req, None).await;
+        if let Some((request, started)) = recording {
+            self.recorder.record(session, &request, response.as_ref(), started.elapsed());
+        }
+        response
+    }
 
-            let request: JsonRpcRequest = match serde_json::from_str(&line) {
-                Ok(r) => r,
-                Err(_) => continue,
+    #[tracing::instrument(name = "session", skip_all, fields(session = session))]
+    async fn answer_request_in(&self, session: &str, req: JsonRpcRequest, scope: Option<&Scope>) -> Option<JsonRpcResponse> {
+        if req.method == "notifications/cancelled" {
+            self.cancel_call(session, &req.params).await;
+            return None;
+        }
+        let key = match &req.id {
+            Some(id) => (session.to_string(), id.to_string()),
+            None => return self.handle_request(req).await,
+        };
+        let (cancel, cancelled) = watch::channel(false);
+        match self.in_flight_calls.lock().await.entry(key.clone()) {
+            Entry::Occupied(_) => {
+                return Some(JsonRpcResponse::error(req.id, error::INVALID_REQUEST, format!("Request id {} is already in flight", key.1)));
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(cancel);
+            }
+        }
+        let target = match req.method == "tools/call" && self.audit.enabled() {
+            true => Some(self.call_target(&req.params).await),
+            false => None,
+        };
+        let started = Instant::now();
+        let mut stop = cancelled.clone();
+        let context = CallContext { cancelled: Some(cancelled), session: Some(session.to_string()), scope: scope.cloned(), ..Default::default() };
+        let response = match req.method.as_str() {
+            "tools/call" => self.route_request(req, context).await,
+            // Servers are only told about cancelled calls: anything else is dropped
+            _ => tokio::select! {
+                response = self.route_request(req, context) => response,
+                _ = stop.wait_for(|cancelled| *cancelled) => None,
+            },
+        };
+        let cancel = self.in_flight_calls.lock().await.remove(&key);
+        let was_cancelled = cancel.is_some_and(|c| *c.borrow());
+        if let Some((server, tool, arguments)) = target {
+            let outcome = match &response {
+                _ if was_cancelled => Outcome::Cancelled,
+                Some(JsonRpcResponse { error: Some(e), .. }) => Outcome::Error(Some(e.message.clone())),
+                Some(JsonRpcResponse { result: Some(r), .. }) if r["isError"] == true => Outcome::Error(None),
+                _ => Outcome::Ok,
             };
+            self.audit.record(AuditEntry {
+                session,
+                server: server.as_deref(),
+                tool: &tool,
+                arguments: &arguments,
+                duration: started.elapsed(),
+                outcome,
+            });
+        }
+        // The client gave up on it: no response
+        if was_cancelled {
+            return None;
+        }
+        response
+    }
 
-            let response = self.handle_request(request).await;
+    /// Server, tool and arguments of a `tools/call`, as the audit log and
+    /// result streaming see them: `execute` is unwrapped, hub tools have no
+    /// server.
+    async fn call_target(&self, params: &serde_json::Value) -> (Option<String>, String, serde_json::Value) {
+        let name = params.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+        let arguments = params.get("arguments").cloned().unwrap_or(serde_json::json!({}));
+        let mode = self.config.lock().await.mode.clone();
+        match mode {
+            Mode::Passthrough => match self.resolve_tool_name(name).await {
+                Some((server, tool)) => (Some(server), tool, arguments),
+                None => (None, name.to_string(), arguments),
+            },
+            Mode::Discover if name == "execute" => {
+                let field = |key: &str| arguments.get(key).and_then(|v| v.as_str()).map(String::from);
+                let (server, tool) = (field("server"), field("tool").unwrap_or_default());
+                (server, tool, arguments.get("arguments").cloned().unwrap_or(serde_json::json!({})))
+            }
+            Mode::Discover => (None, name.to_string(), arguments),
+        }
+    }
+
+    /// Split the response to a `tools/call` of a tool in `sse.streamResults`
+    /// into chunk notifications tied to its request id, plus the response
+    /// that completes it. None for other tools, errors and empty results.
+    pub async fn chunk_result(&self, params: &serde_json::Value, response: &JsonRpcResponse) -> Option<ChunkedReply> {
+        let streamed = self.config.lock().await.sse_stream_results.clone();
+        if streamed.is_empty() || response.error.is_some() {
+            return None;
+        }
+        let result = response.result.as_ref()?;
+        let content = result.get("content")?.as_array().filter(|c| !c.is_empty())?;
+        let (server, tool, _) = self.call_target(params).await;
+        let server = server?;
+        if !streamed.iter().any(|s| *s == server || *s == format!("{}__{}", server, tool)) {
+            return None;
+        }
+        let id = response.id.clone().unwrap_or(serde_json::Value::Null);
+        let mut chunks = Vec::new();
+        for (item, part) in content.iter().enumerate() {
+            let pieces = match part.get("text").and_then(|t| t.as_str()) {
+                Some(text) if text.len() > STREAM_CHUNK_BYTES => split_text(text, STREAM_CHUNK_BYTES)
+                    .into_iter()
+                    .map(|piece| {
+                        let mut part = part.clone();
+                        part["text"] = piece.into();
+                        part
+                    })
+                    .collect(),
+                _ => vec![part.clone()],
+            };
+            for piece in pieces {
+                chunks.push(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/mcphub/chunk",
+                    "params": { "requestId": id, "index": chunks.len(), "item": item, "content": piece },
+                }));
+            }
+        }
+        let mut result = result.clone();
+        result["content"] = serde_json::json!([]);
+        result["_meta"]["mcphub/chunks"] = chunks.len().into();
+        let done = JsonRpcResponse { jsonrpc: response.jsonrpc.clone(), id: response.id.clone(), result: Some(result), error: None };
+        Some(ChunkedReply { chunks, done })
+    }
 
-            if let Some(resp) = response {
-                let mut msg = serde_json::to_string(&resp).unwrap();
-                msg.push('\n');
-                let _ = stdout.write_all(msg.as_bytes()).await;
-                let _ = stdout.flush().await;
+    /// Cancel the session's in-flight request named by `params.requestId`.
+    async fn cancel_call(&self, session: &str, params: &serde_json::Value) {
+        let Some(id) = params.get("requestId") else { return };
+        let key = (session.to_string(), id.to_string());
+        match self.in_flight_calls.lock().await.get(&key) {
+            Some(cancel) => {
+                cancel.send_replace(true);
             }
+            None => tracing::info!("Cancellation of request {} that isn't in flight, ignored", id),
         }
+    }
 
-        // Cleanup
-        self.child_manager.stop_all().await;
+    async fn route_request(&self, req: JsonRpcRequest, context: CallContext) -> Option<JsonRpcResponse> {
+        intercept::around(&self.interceptors, req, |req| self.dispatch(req, context)).await
     }
 
-    pub async fn handle_request(&self, req: JsonRpcRequest) -> Option<JsonRpcResponse> {
+    async fn dispatch(&self, req: JsonRpcRequest, context: CallContext) -> Option<JsonRpcResponse> {
         match req.method.as_str() {
-            "initialize" => Some(self.handle_initialize(req.id).await),
+            "initialize" => Some(self.handle_initialize(req.id, req.params).await),
             "notifications/initialized" => None,
             "tools/list" => Some(self.handle_tools_list(req.id).await),
-            "tools/call" => Some(self.handle_tools_call(req.id, req.params).await),
+            "tools/call" => Some(self.handle_tools_call(req.id, req.params, context).await),
             "prompts/list" => Some(self.handle_prompts_list(req.id).await),
             "prompts/get" => Some(self.handle_prompts_get(req.id, req.params).await),
             "resources/list" => Some(self.handle_resources_list(req.id).await),
             "resources/templates/list" => Some(self.handle_resource_templates_list(req.id).await),
-            "resources/read" => Some(self.handle_resources_read(req.id, req.params).await),
-            "completion/complete" => Some(JsonRpcResponse::success(req.id, serde_json::json!({ "completion": { "values": [] } }))),
-            "ping" => Some(JsonRpcResponse::success(req.id, serde_json::json!({}))),
-            "notifications/cancelled" => {
-                self.handle_cancel(req.params).await;
-                None
+            "resources/read" => Some(self.handle_resources_read(req.id, req.params, context.session.as_deref()).await),
+            "resources/subscribe" | "resources/unsubscribe" => {
+                Some(self.handle_resource_subscription(req.id, &req.method, req.params).await)
             }
+            "completion/complete" => Some(self.handle_completion(req.id, req.params, context.scope.as_ref()).await),
+            "ping" => Some(self.handle_ping(req.id, req.params).await),
+            // Backend logs are forwarded (and filtered) per SSE session; the
+            // SSE transport applies the level before it gets here.
+            "logging/setLevel" => Some(JsonRpcResponse::success(req.id, serde_json::json!({}))),
+            // Only calls made through a session can be cancelled
+            "notifications/cancelled" => None,
             _ => {
-                eprintln!("[McpHub][WARN] Unknown method: {}", req.method);
+                tracing::warn!("Unknown method: {}", req.method);
                 Some(JsonRpcResponse::error(
                     req.id,
-                    -32601,
+                    error::METHOD_NOT_FOUND,
                     format!("Method not found: {}", req.method),
                 ))
             }
         }
     }
 
-    async fn handle_initialize(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
+    async fn handle_initialize(&self, id: Option<serde_json::Value>, params: serde_json::Value) -> JsonRpcResponse {
         let config = self.config.lock().await;
+        if config.forward_experimental {
+            let experimental = params
+                .get("capabilities")
+                .and_then(|c| c.get("experimental"))
+                .filter(|v| v.is_object())
+                .cloned();
+            self.child_manager.set_client_experimental(experimental).await;
+        }
         let mode_str = match config.mode {
             Mode::Discover => "discover",
             Mode::Passthrough => "passthrough",
         };
 
-        eprintln!(
-            "[McpHub][INFO] Initialize: mode={}, servers={}",
+        tracing::info!(
+            "Initialize: mode={}, servers={}",
             mode_str,
             config.servers.len()
         );
 
+        // Advertise only what configured backends support. Computed per
+        // initialize so hot-reloads and restarts are always reflected.
+        let live = self.child_manager.server_capabilities().await;
+        let cached = self.cached_capabilities.lock().await;
+        let backend_caps: Vec<&serde_json::Value> = config
+            .servers
+            .keys()
+            .filter_map(|name| live.get(name).or_else(|| cached.get(name)))
+            .collect();
+
+        let mut capabilities = Capabilities::from_backends(backend_caps);
+        if !config.forward_experimental {
+            capabilities.experimental = None;
+        }
+        if config.mode == Mode::Passthrough {
+            capabilities.tools.list_changed = Some(true);
+        }
+
         let result = InitializeResult {
             protocol_version: "2024-11-05".into(),
-            capabilities: Capabilities {
-                tools: ToolsCapability {},
-                prompts: PromptsCapability {},
-                resources: ResourcesCapability {},
-            },
+            capabilities,
             server_info: ServerInfo {
                 name: "McpHub".into(),
                 version: env!("CARGO_PKG_VERSION").into(),
@@ -248,26 +1037,39 @@ impl ProxyServer {
         &self,
         id: Option<serde_json::Value>,
     ) -> JsonRpcResponse {
-        let mode = {
+        let (mode, ttl) = {
             let config = self.config.lock().await;
-            config.mode.clone()
+            (config.mode.clone(), Duration::from_millis(config.list_cache_ms))
         };
 
-        let tools = match mode {
-            Mode::Discover => self.get_discover_tools().await,
-            Mode::Passthrough => self.get_passthrough_tools().await,
-        };
+        let tools = self
+            .lists
+            .run("tools/list", ttl, || async {
+                match mode {
+                    Mode::Discover => self.get_discover_tools().await,
+                    Mode::Passthrough => self.get_passthrough_tools().await,
+                }
+            })
+            .await;
 
         JsonRpcResponse::success(id, serde_json::json!({ "tools": tools }))
     }
 
+    async fn list_cache_ttl(&self) -> Duration {
+        Duration::from_millis(self.config.lock().await.list_cache_ms)
+    }
+
+    /// Priority of each configured server, for ordering `tools/list`.
+    async fn server_priorities(&self) -> HashMap<String, i32> {
+        let config = self.config.lock().await;
+        config.servers.iter().map(|(name, c)| (name.clone(), c.priority)).collect()
+    }
+
     async fn get_discover_tools(&self) -> serde_json::Value {
-        let mut server_names: Vec<String> = {
-            let config = self.config.lock().await;
-            config.servers.keys().cloned().collect()
-        };
-        server_names.sort();
-        let server_list = server_names.join(", ");
+        let priorities = self.server_priorities().await;
+        let mut server_names: Vec<&String> = priorities.keys().collect();
+        server_names.sort_by(|a, b| priorities[*b].cmp(&priorities[*a]).then_with(|| a.cmp(b)));
+        let server_list = server_names.iter().map(|n| n.as_str()).collect::<Vec<_>>().join(", ");
 
         let discover_desc = format!(
             "Search for available MCP tools across all connected servers. Returns matching tools with full schemas. \
@@ -303,7 +1105,8 @@ impl ProxyServer {
                         }
                     },
                     "required": ["query"]
-                }
+                },
+                "annotations": { "readOnlyHint": true }
             },
             {
                 "name": "execute",
@@ -332,19 +1135,33 @@ impl ProxyServer {
     }
 
     async fn get_passthrough_tools(&self) -> serde_json::Value {
+        let priorities = self.server_priorities().await;
+        let names = self.config.lock().await.tool_names.clone();
+        let tripped = self.child_manager.tripped_servers().await;
         let engine = self.search_engine.lock().await;
-        let catalog = engine.get_catalog();
+        let mut catalog = engine.get_catalog();
+        // Higher-priority servers first, alphabetical within a tier
+        let priority = |server: &str| priorities.get(server).copied().unwrap_or(0);
+        catalog.sort_by(|a, b| {
+            priority(&b.server).cmp(&priority(&a.server)).then_with(|| (&a.server, &a.name).cmp(&(&b.server, &b.name)))
+        });
 
-        // Expose all tools with prefixed names
+        // Expose all tools under their client-facing names; unprefixed, the
+        // highest-priority server wins a name clash
         let mut tools = Vec::new();
+        let mut seen = std::collections::HashSet::new();
         for entry in &catalog {
             if let Some(indexed) = engine.find_tool(&entry.server, &entry.name) {
+                let name = names.join(&indexed.server_name, &indexed.original_name);
+                if !seen.insert(name.clone()) {
+                    continue;
+                }
                 let mut tool_json = serde_json::to_value(&indexed.tool_def).unwrap();
                 if let Some(obj) = tool_json.as_object_mut() {
-                    obj.insert(
-                        "name".into(),
-                        serde_json::Value::String(indexed.name.clone()),
-                    );
+                    obj.insert("name".into(), serde_json::Value::String(name));
+                    if tripped.contains(&indexed.server_name) {
+                        flag_unavailable(obj);
+                    }
                 }
                 tools.push(tool_json);
             }
@@ -353,10 +1170,12 @@ impl ProxyServer {
         serde_json::Value::Array(tools)
     }
 
+    #[tracing::instrument(name = "call", skip_all, fields(tool = params.get("name").and_then(|v| v.as_str()).unwrap_or("")))]
     async fn handle_tools_call(
         &self,
         id: Option<serde_json::Value>,
         params: serde_json::Value,
+        mut context: CallContext,
     ) -> JsonRpcResponse {
         let tool_name = params
             .get("name")
@@ -367,22 +1186,28 @@ impl ProxyServer {
             .cloned()
             .unwrap_or(serde_json::json!({}));
 
-        let mode = {
+        let (mode, fallback) = {
             let config = self.config.lock().await;
-            config.mode.clone()
+            (config.mode.clone(), config.unknown_tool_fallback)
         };
+        let max_result = limits::requested_max(&params);
+        context.progress_token = params.pointer("/_meta/progressToken").cloned();
 
         match mode {
             Mode::Discover => match tool_name {
-                "discover" => self.handle_discover(id, arguments).await,
-                "execute" => self.handle_execute(id, arguments).await,
+                "discover" => self.handle_discover(id, arguments, context.scope.as_ref()).await,
+                "execute" => self.handle_execute(id, arguments, max_result, context).await,
+                _ if fallback => {
+                    let engine = self.search_engine.lock().await;
+                    JsonRpcResponse::success(id, unknown_tool_result(&engine, tool_name, None, context.scope.as_ref(), &mode, &ToolNames::default()))
+                }
                 _ => JsonRpcResponse::error(
                     id,
-                    -32602,
+                    error::INVALID_PARAMS,
                     format!("Unknown tool: {}. Use 'discover' first.", tool_name),
                 ),
             },
-            Mode::Passthrough => self.handle_passthrough_call(id, tool_name, arguments).await,
+            Mode::Passthrough => self.handle_passthrough_call(id, tool_name, arguments, max_result, context).await,
         }
     }
 
@@ -390,21 +1215,23 @@ impl ProxyServer {
         &self,
         id: Option<serde_json::Value>,
         args: serde_json::Value,
+        scope: Option<&Scope>,
     ) -> JsonRpcResponse {
         let query = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
         let top_k = args.get("top_k").and_then(|v| v.as_u64()).unwrap_or(10).min(50) as usize;
 
-        // Always provide the full server list
+        // Always provide the full server list (of the session's scope, if it has one)
+        let in_scope = |server: &String| scope.is_none_or(|s| s.contains(server));
         let mut all_server_names: Vec<String> = {
             let config = self.config.lock().await;
-            config.servers.keys().cloned().collect()
+            config.servers.keys().filter(|s| in_scope(s)).cloned().collect()
         };
         all_server_names.sort();
 
         let engine = self.search_engine.lock().await;
 
         if engine.tool_count() > 0 {
-            let results = engine.search(query, top_k);
+            let results = engine.search_where(query, top_k, |t| in_scope(&t.server_name));
 
             // Collect unique servers from results
             let mut seen_servers: Vec<String> = Vec::new();
@@ -414,12 +1241,17 @@ impl ProxyServer {
                 }
                 let desc: String = t.description.chars().take(200).collect();
                 let schema = strip_schema(&t.tool_def.input_schema);
-                serde_json::json!({
+                let mut tool = serde_json::json!({
                     "server": t.server_name,
                     "tool": t.original_name,
                     "description": desc,
                     "inputSchema": schema,
-                })
+                });
+                // Clients decide on approval from these hints, so they travel with the tool
+                if let Some(annotations) = &t.tool_def.annotations {
+                    tool["annotations"] = annotations.clone();
+                }
+                tool
             }).collect();
 
             let text = serde_json::to_string(&serde_json::json!({
@@ -438,11 +1270,7 @@ impl ProxyServer {
         drop(engine);
 
         let query_lower = query.to_lowercase();
-        let mut server_names: Vec<String> = {
-            let config = self.config.lock().await;
-            config.servers.keys().cloned().collect()
-        };
-        server_names.sort();
+        let server_names = all_server_names;
 
         let mut matches: Vec<serde_json::Value> = Vec::new();
         for name in &server_names {
@@ -487,18 +1315,20 @@ impl ProxyServer {
         &self,
         id: Option<serde_json::Value>,
         args: serde_json::Value,
+        max_result: Option<usize>,
+        context: CallContext,
     ) -> JsonRpcResponse {
         let server = match args.get("server").and_then(|v| v.as_str()) {
             Some(s) => s.to_string(),
             None => {
-                return JsonRpcResponse::error(id, -32602, "Missing 'server' parameter".into())
+                return JsonRpcResponse::error(id, error::INVALID_PARAMS, "Missing 'server' parameter".into())
             }
         };
 
         let tool = match args.get("tool").and_then(|v| v.as_str()) {
             Some(s) => s.to_string(),
             None => {
-                return JsonRpcResponse::error(id, -32602, "Missing 'tool' parameter".into())
+                return JsonRpcResponse::error(id, error::INVALID_PARAMS, "Missing 'tool' parameter".into())
             }
         };
 
@@ -507,27 +1337,66 @@ impl ProxyServer {
             .cloned()
             .unwrap_or(serde_json::json!({}));
 
+        if let Some(e) = self.filtered_tool_error(&server, &tool).await {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, e);
+        }
+        if let Some(result) = self.check_unknown_tool(&server, &tool, &Mode::Discover).await {
+            return JsonRpcResponse::success(id, result);
+        }
+        if let Some(e) = self.invalid_arguments(&server, &tool, &arguments).await {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, e);
+        }
+
         let start_time = Instant::now();
-        let res = self.child_manager.call_tool(&server, &tool, arguments).await;
+        let session = context.session.clone();
+        let (res, trace) = self.call_tool_cached(&server, &tool, arguments, context).await;
         let elapsed = start_time.elapsed().as_millis() as u64;
 
-        {
-            let mut m = self.metrics.lock().await;
-            m.total_requests += 1;
-            let sm = m.servers.entry(server.clone()).or_default();
-            sm.call_count += 1;
-            sm.total_latency_ms += elapsed;
-            sm.last_call_time = Some(SystemTime::now());
-            if let Err(ref e) = res {
-                sm.error_count += 1;
-                sm.last_error = Some(e.clone());
+        self.record_call_metrics(&server, &tool, elapsed, &res, trace).await;
+
+        let debug_meta = self.config.lock().await.debug_meta;
+        match res {
+            Ok(result) => {
+                let result = with_debug_meta(debug_meta, result, &server, elapsed, trace);
+                self.limit_result(id, &server, &tool, result, max_result, session.as_deref()).await
             }
+            Err(e) => error::upstream(id, e),
         }
+    }
 
-        match res {
-            Ok(result) => JsonRpcResponse::success(id, result),
-            Err(e) => JsonRpcResponse::error(id, -32000, e),
+    async fn record_call_metrics(
+        &self,
+        server: &str,
+        tool: &str,
+        elapsed_ms: u64,
+        res: &Result<serde_json::Value, Failure>,
+        trace: CallTrace,
+    ) {
+        tracing::debug!(server, elapsed_ms, ok = res.is_ok(), upstream_ms = trace.upstream.map(|d| d.as_millis() as u64), "Call finished");
+        let mut m = self.metrics.lock().await;
+        m.total_requests += 1;
+        let sm = m.servers.entry(server.to_string()).or_default();
+        sm.call_count += 1;
+        sm.total_latency_ms += elapsed_ms;
+        sm.last_call_time = Some(SystemTime::now());
+        if let Err(e) = res {
+            sm.error_count += 1;
+            sm.last_error = Some(e.message.clone());
+        }
+        // Cache hits never reach the backend and aren't the tool's calls
+        if trace.upstream.is_none() && res.is_ok() {
+            return;
         }
+        let failed = match res {
+            Ok(result) => result.get("isError").and_then(|e| e.as_bool()) == Some(true),
+            Err(_) => true,
+        };
+        m.tools
+            .entry(server.to_string())
+            .or_default()
+            .entry(tool.to_string())
+            .or_default()
+            .record(trace.upstream, failed);
     }
 
     async fn handle_passthrough_call(
@@ -535,95 +1404,458 @@ impl ProxyServer {
         id: Option<serde_json::Value>,
         prefixed_name: &str,
         arguments: serde_json::Value,
+        max_result: Option<usize>,
+        context: CallContext,
     ) -> JsonRpcResponse {
-        // Parse "server__tool" format
-        let parts: Vec<&str> = prefixed_name.splitn(2, "__").collect();
-        if parts.len() != 2 {
-            return JsonRpcResponse::error(
-                id,
-                -32602,
-                format!("Invalid tool name format: {}", prefixed_name),
-            );
-        }
+        let Some((server, tool)) = self.resolve_tool_name(prefixed_name).await else {
+            let config = self.config.lock().await;
+            if config.tool_names.prefix {
+                return JsonRpcResponse::error(
+                    id,
+                    error::INVALID_PARAMS,
+                    format!("Invalid tool name format: {}", prefixed_name),
+                );
+            }
+            if config.unknown_tool_fallback {
+                let engine = self.search_engine.lock().await;
+                return JsonRpcResponse::success(
+                    id,
+                    unknown_tool_result(&engine, prefixed_name, None, context.scope.as_ref(), &Mode::Passthrough, &config.tool_names),
+                );
+            }
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, format!("Unknown tool: {}", prefixed_name));
+        };
+        let (server, tool) = (server.as_str(), tool.as_str());
 
-        let server = parts[0];
-        let tool = parts[1];
+        if let Some(e) = self.filtered_tool_error(server, tool).await {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, e);
+        }
+        if let Some(result) = self.check_unknown_tool(server, tool, &Mode::Passthrough).await {
+            return JsonRpcResponse::success(id, result);
+        }
+        if let Some(e) = self.invalid_arguments(server, tool, &arguments).await {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, e);
+        }
 
         let start_time = Instant::now();
-        let res = self.child_manager.call_tool(server, tool, arguments).await;
+        let session = context.session.clone();
+        let (res, trace) = self.call_tool_cached(server, tool, arguments, context).await;
         let elapsed = start_time.elapsed().as_millis() as u64;
 
-        {
-            let mut m = self.metrics.lock().await;
-            m.total_requests += 1;
-            let sm = m.servers.entry(server.to_string()).or_default();
-            sm.call_count += 1;
-            sm.total_latency_ms += elapsed;
-            sm.last_call_time = Some(SystemTime::now());
-            if let Err(ref e) = res {
-                sm.error_count += 1;
-                sm.last_error = Some(e.clone());
-            }
-        }
+        self.record_call_metrics(server, tool, elapsed, &res, trace).await;
 
+        let debug_meta = self.config.lock().await.debug_meta;
         match res {
-            Ok(result) => JsonRpcResponse::success(id, result),
-            Err(e) => JsonRpcResponse::error(id, -32000, e),
+            Ok(result) => {
+                let result = with_debug_meta(debug_meta, result, server, elapsed, trace);
+                self.limit_result(id, server, tool, result, max_result, session.as_deref()).await
+            }
+            Err(e) => error::upstream(id, e),
         }
     }
 
-    async fn handle_prompts_list(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
-        let results = self.child_manager.request_all_running("prompts/list", serde_json::json!({})).await;
-        let mut all_prompts = Vec::new();
-        for (server_name, res) in results {
-            if let Ok(mut val) = res {
-                if let Some(prompts) = val.get_mut("prompts").and_then(|v| v.as_array_mut()) {
-                    for prompt in prompts {
-                        if let Some(name) = prompt.get("name").and_then(|v| v.as_str()) {
-                            prompt["name"] = serde_json::json!(format!("{}__{}", server_name, name));
-                        }
-                        all_prompts.push(prompt.clone());
-                    }
-                }
+    /// With `validateArguments`, why `arguments` don't fit the tool's cached
+    /// input schema, checked as the backend would get them (after request
+    /// transforms). None when they fit or the tool's schema isn't known.
+    async fn invalid_arguments(&self, server: &str, tool: &str, arguments: &serde_json::Value) -> Option<String> {
+        let transforms = {
+            let config = self.config.lock().await;
+            if !config.validate_arguments {
+                return None;
             }
+            config.transforms.clone()
+        };
+        let input_schema = self.search_engine.lock().await.find_tool(server, tool)?.tool_def.input_schema.clone();
+        let mut params = serde_json::json!({ "name": tool, "arguments": arguments });
+        transform::apply_request(&transforms, server, "tools/call", Some(tool), &mut params);
+        let errors = schema::validate(&input_schema, &params["arguments"]);
+        if errors.is_empty() {
+            return None;
         }
-        JsonRpcResponse::success(id, serde_json::json!({ "prompts": all_prompts }))
+        Some(format!("Invalid arguments for '{}__{}': {}", server, tool, errors.join("; ")))
     }
 
-    async fn handle_prompts_get(&self, id: Option<serde_json::Value>, args: serde_json::Value) -> JsonRpcResponse {
-        let name = args.get("name").and_then(|v| v.as_str()).unwrap_or("");
-        let parts: Vec<&str> = name.splitn(2, "__").collect();
-        if parts.len() != 2 {
-            return JsonRpcResponse::error(id, -32602, "Invalid prompt name format".into());
+    /// Apply the oversized-result policy of `server__tool` when the client
+    /// set a limit the result exceeds. A stored result belongs to `session`.
+    async fn limit_result(
+        &self,
+        id: Option<serde_json::Value>,
+        server: &str,
+        tool: &str,
+        mut result: serde_json::Value,
+        max: Option<usize>,
+        session: Option<&str>,
+    ) -> JsonRpcResponse {
+        let size = limits::size_of(&result);
+        let Some(max) = max.filter(|&max| size > max) else {
+            return JsonRpcResponse::success(id, result);
+        };
+        let policy = self.config.lock().await.oversized_results.policy_for(server, tool);
+        match policy {
+            ResultPolicy::Passthrough => {}
+            ResultPolicy::Truncate => {
+                let marker = format!("[McpHub: result truncated from {} to {} bytes]", size, max);
+                limits::truncate(&mut result, max, &marker);
+            }
+            ResultPolicy::Reference => {
+                let uri = self.store_result(result.clone(), session).await;
+                let marker = format!("[McpHub: result truncated from {} bytes; read resource {} for all of it]", size, uri);
+                limits::truncate(&mut result, max, &marker);
+            }
+            ResultPolicy::Error => {
+                return JsonRpcResponse::error(
+                    id,
+                    error::SERVER_ERROR,
+                    format!("Result of '{}__{}' is {} bytes, over the requested maximum of {}", server, tool, size, max),
+                );
+            }
         }
-        let server = parts[0];
-        let prompt_name = parts[1];
-        
+        JsonRpcResponse::success(id, result)
+    }
+
+    /// Keep a full result for `resources/read` by `session`; returns its
+    /// uri. The id is random, so other sessions can't guess it.
+    async fn store_result(&self, result: serde_json::Value, session: Option<&str>) -> String {
+        let uri = format!("{}{}", limits::RESULT_URI_PREFIX, crate::sse::generate_session_id());
+        let mut stored = self.stored_results.lock().await;
+        if stored.len() == STORED_RESULTS {
+            stored.pop_front();
+        }
+        stored.push_back((uri.clone(), session.map(String::from), result));
+        uri
+    }
+
+    /// `call_tool_transformed`, answered from the result cache when the tool
+    /// is cacheable. Only successful results are stored.
+    async fn call_tool_cached(
+        &self,
+        server: &str,
+        tool: &str,
+        arguments: serde_json::Value,
+        context: CallContext,
+    ) -> (Result<serde_json::Value, Failure>, CallTrace) {
+        let read_only = self.search_engine.lock().await.find_tool(server, tool).is_some_and(|t| t.tool_def.is_read_only());
+        let (ttl, max_entries) = {
+            let config = self.config.lock().await;
+            (config.result_cache.ttl_for(server, tool, read_only), config.result_cache.max_entries)
+        };
+        let Some(ttl) = ttl else {
+            return self.call_tool_transformed(server, tool, arguments, context).await;
+        };
+        let name = format!("{}__{}", server, tool);
+        if let Some(result) = self.result_cache.lock().await.get(&name, &arguments, Instant::now()) {
+            return (Ok(result), CallTrace::default());
+        }
+        let (res, trace) = self.call_tool_transformed(server, tool, arguments.clone(), context).await;
+        if let Ok(result) = &res {
+            if result.get("isError").and_then(|e| e.as_bool()) != Some(true) {
+                self.result_cache.lock().await.insert(&name, &arguments, result.clone(), ttl, max_entries, Instant::now());
+            }
+        }
+        (res, trace)
+    }
+
+    /// Drop cached results of one tool (`server__tool`), one server, or
+    /// every tool; returns how many were dropped.
+    pub async fn invalidate_tool_results(&self, tool: Option<&str>) -> usize {
+        let removed = self.result_cache.lock().await.invalidate(tool);
+        tracing::info!("Invalidated {} cached result(s) of {}", removed, tool.unwrap_or("all tools"));
+        removed
+    }
+
+    pub async fn tool_cache_stats(&self) -> HashMap<String, ToolCacheStats> {
+        self.result_cache.lock().await.stats()
+    }
+
+    /// Call a backend tool with the configured transforms applied to the
+    /// forwarded params and to a successful result.
+    async fn call_tool_transformed(
+        &self,
+        server: &str,
+        tool: &str,
+        arguments: serde_json::Value,
+        context: CallContext,
+    ) -> (Result<serde_json::Value, Failure>, CallTrace) {
+        let transforms = self.config.lock().await.transforms.clone();
+        if transforms.is_empty() {
+            let (res, trace) = self.child_manager.call_tool_traced(server, tool, arguments, context).await;
+            return (self.check_stale_tool(server, tool, res).await, trace);
+        }
+        let mut params = serde_json::json!({ "name": tool, "arguments": arguments });
+        transform::apply_request(&transforms, server, "tools/call", Some(tool), &mut params);
+        let arguments = params.get_mut("arguments").map(serde_json::Value::take).unwrap_or(serde_json::json!({}));
+
+        let (mut res, trace) = self.child_manager.call_tool_traced(server, tool, arguments, context).await;
+        if let Ok(result) = &mut res {
+            transform::apply_response(&transforms, server, "tools/call", Some(tool), result);
+        }
+        (self.check_stale_tool(server, tool, res).await, trace)
+    }
+
+    /// A backend refusing a tool the cache lists means the cache is stale
+    /// (server restarted or upgraded): re-list the server's tools, reindex,
+    /// tell clients the list changed and explain the failure. If the tool
+    /// is still listed, the backend's own answer stands.
+    async fn check_stale_tool(
+        &self,
+        server: &str,
+        tool: &str,
+        res: Result<serde_json::Value, Failure>,
+    ) -> Result<serde_json::Value, Failure> {
+        if !self.config.lock().await.refresh_stale_tools
+            || !reports_missing_tool(&res)
+            || self.search_engine.lock().await.find_tool(server, tool).is_none()
+        {
+            return res;
+        }
+        tracing::warn!("Server '{}' no longer knows cached tool '{}', refreshing its tool list", server, tool);
+        let tools = match self.child_manager.refresh_tools(server).await {
+            Ok(tools) => tools,
+            Err(e) => {
+                tracing::warn!("Failed to refresh tools of '{}': {}", server, e);
+                return res;
+            }
+        };
+        if tools.iter().any(|t| t.name == tool) {
+            return res;
+        }
+        let count = tools.len();
+        let indexed = tools.into_iter().map(|tool_def| IndexedTool {
+            name: format!("{}__{}", server, tool_def.name),
+            original_name: tool_def.name.clone(),
+            server_name: server.to_string(),
+            description: tool_def.description.clone(),
+            tool_def,
+        }).collect();
+        self.search_engine.lock().await.replace_server(server, indexed);
+        if self.config.lock().await.mode == Mode::Passthrough {
+            self.child_manager.notify_clients(server, serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/tools/list_changed",
+            }));
+        }
+        Err(Failure::from(format!(
+            "Tool '{}' is no longer provided by server '{}' (the tool cache was stale and has been refreshed: {} tools now)",
+            tool, server, count
+        )))
+    }
+
+    /// `call_method` with the configured transforms applied.
+    async fn call_method_transformed(&self, server: &str, method: &str, mut params: serde_json::Value) -> Result<serde_json::Value, Failure> {
+        let transforms = self.config.lock().await.transforms.clone();
+        transform::apply_request(&transforms, server, method, None, &mut params);
+        let mut result = self.child_manager.call_method(server, method, params).await?;
+        transform::apply_response(&transforms, server, method, None, &mut result);
+        Ok(result)
+    }
+
+    /// Error for a call to a tool hidden by `toolFilter`, or by a
+    /// `toolOverrides` rename, matched on the configured server name so a
+    /// differently-cased name can't bypass it.
+    async fn filtered_tool_error(&self, server: &str, tool: &str) -> Option<String> {
+        let server = self.child_manager.resolve_name(server).await.unwrap_or_else(|| server.to_string());
+        let config = self.config.lock().await;
+        let Some(upstream) = config.tool_overrides.upstream_name(&server, tool) else {
+            return Some(format!("Tool '{}' of server '{}' is renamed by toolOverrides", tool, server));
+        };
+        if config.tool_filter.allows(&server, upstream) {
+            return None;
+        }
+        Some(format!("Tool '{}' of server '{}' is not allowed by toolFilter", tool, server))
+    }
+
+    /// Server and tool a passthrough tool name refers to. Unprefixed names
+    /// go to the highest-priority server that has the tool.
+    async fn resolve_tool_name(&self, name: &str) -> Option<(String, String)> {
+        let names = self.config.lock().await.tool_names.clone();
+        if names.prefix {
+            let (server, tool) = name.split_once(names.separator.as_str())?;
+            return Some((server.to_string(), tool.to_string()));
+        }
+        let priorities = self.server_priorities().await;
+        let engine = self.search_engine.lock().await;
+        engine
+            .get_catalog()
+            .into_iter()
+            .filter(|entry| entry.name == name)
+            .max_by(|a, b| {
+                let priority = |server: &str| priorities.get(server).copied().unwrap_or(0);
+                priority(&a.server).cmp(&priority(&b.server)).then_with(|| b.server.cmp(&a.server))
+            })
+            .map(|entry| (entry.server, entry.name))
+    }
+
+    /// With `unknownToolFallback` enabled, returns a helpful tool result when
+    /// `tool` is not among the cached tools of `server`. Servers missing from
+    /// the cache are not checked, so the call goes through as usual.
+    async fn check_unknown_tool(&self, server: &str, tool: &str, mode: &Mode) -> Option<serde_json::Value> {
+        let names = {
+            let config = self.config.lock().await;
+            if !config.unknown_tool_fallback {
+                return None;
+            }
+            config.tool_names.clone()
+        };
+        let engine = self.search_engine.lock().await;
+        let on_server = engine.tools_on_server(server);
+        if on_server.is_empty() || on_server.iter().any(|t| t.original_name == tool) {
+            return None;
+        }
+        let requested = match mode {
+            Mode::Passthrough => names.join(server, tool),
+            Mode::Discover => tool.to_string(),
+        };
+        Some(unknown_tool_result(&engine, &requested, Some(server), None, mode, &names))
+    }
+
+    /// Prompts of running servers, live, plus cached prompts of configured
+    /// servers that aren't running. Names are namespaced `server__prompt`
+    /// like tools, so identical prompt names on two servers don't collide.
+    async fn handle_prompts_list(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
+        let ttl = self.list_cache_ttl().await;
+        let prompts = self.lists.run("prompts/list", ttl, || self.build_prompts_list()).await;
+        JsonRpcResponse::success(id, serde_json::json!({ "prompts": prompts }))
+    }
+
+    async fn build_prompts_list(&self) -> serde_json::Value {
+        let results = self.child_manager.request_all_running("prompts/list", serde_json::json!({})).await;
+        let mut cached = self.cached_prompts.lock().await;
+        for (server_name, res) in results {
+            if let Ok(val) = res {
+                cached.insert(server_name.clone(), crate::cache::parse_prompt_list(&server_name, &val));
+            }
+        }
+        let configured = self.config.lock().await.servers.keys().cloned().collect::<Vec<_>>();
+        let mut all_prompts: Vec<serde_json::Value> = configured
+            .iter()
+            .filter_map(|server| cached.get(server).map(|prompts| (server, prompts)))
+            .flat_map(|(server, prompts)| {
+                prompts.iter().map(move |p| {
+                    let mut prompt = serde_json::to_value(p).unwrap_or_default();
+                    prompt["name"] = serde_json::json!(format!("{}__{}", server, p.name));
+                    prompt
+                })
+            })
+            .collect();
+        all_prompts.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        serde_json::Value::Array(all_prompts)
+    }
+
+    async fn handle_prompts_get(&self, id: Option<serde_json::Value>, args: serde_json::Value) -> JsonRpcResponse {
+        let name = args.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let Some((server, prompt_name)) = name.split_once("__") else {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, "Invalid prompt name format".into());
+        };
+
+        let def = self.cached_prompts.lock().await
+            .get(server)
+            .and_then(|prompts| prompts.iter().find(|p| p.name == prompt_name).cloned());
+        let arguments = match prompt_arguments(name, def.as_ref(), args.get("arguments")) {
+            Ok(a) => a,
+            Err(e) => return JsonRpcResponse::error(id, error::INVALID_PARAMS, e),
+        };
+        if !self.child_manager.is_running(server).await {
+            if let Err(e) = self.child_manager.start_server(server).await {
+                return error::upstream(id, e);
+            }
+        }
+
         let mut new_args = args.clone();
         new_args["name"] = serde_json::json!(prompt_name);
-        
-        match self.child_manager.call_method(server, "prompts/get", new_args).await {
+        new_args["arguments"] = arguments;
+
+        match self.call_method_transformed(server, "prompts/get", new_args).await {
             Ok(res) => JsonRpcResponse::success(id, res),
-            Err(e) => JsonRpcResponse::error(id, -32000, e),
+            Err(e) => error::upstream(id, e),
+        }
+    }
+
+    /// `ping` is answered by the hub itself. With `params.server` it is
+    /// forwarded to that backend instead (see `ChildManager::ping_server`),
+    /// so a monitor can check one upstream.
+    async fn handle_ping(&self, id: Option<serde_json::Value>, params: serde_json::Value) -> JsonRpcResponse {
+        let Some(server) = params.get("server") else {
+            return JsonRpcResponse::success(id, serde_json::json!({}));
+        };
+        let Some(server) = server.as_str() else {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, "ping: server must be a string".to_string());
+        };
+        match self.child_manager.ping_server(server).await {
+            Ok(_) => JsonRpcResponse::success(id, serde_json::json!({})),
+            Err(e) if e.kind == error::Kind::UnknownServer => JsonRpcResponse::error(id, error::INVALID_PARAMS, e.message),
+            Err(e) => error::upstream(id, e),
+        }
+    }
+
+    /// Forward `completion/complete` to the backend owning the namespaced
+    /// prompt or resource. A reference without a known server prefix goes to
+    /// every running backend offering completions (within `scope`, for a
+    /// scoped session), and their values are merged.
+    async fn handle_completion(&self, id: Option<serde_json::Value>, params: serde_json::Value, scope: Option<&Scope>) -> JsonRpcResponse {
+        let key = match completion_ref_key(&params) {
+            Ok(key) => key,
+            Err(e) => return JsonRpcResponse::error(id, error::INVALID_PARAMS, e),
+        };
+        if !self.config.lock().await.forward_completions {
+            return JsonRpcResponse::success(id, serde_json::json!({ "completion": { "values": [] } }));
+        }
+
+        let owner = match params["ref"][key].as_str().and_then(|r| r.split_once("__")) {
+            Some((server, inner)) => self.child_manager.resolve_name(server).await.map(|s| (s, inner.to_string())),
+            None => None,
+        };
+        if let Some((server, inner)) = owner {
+            if !self.child_manager.is_running(&server).await {
+                if let Err(e) = self.child_manager.start_server(&server).await {
+                    return error::upstream(id, e);
+                }
+            }
+            let mut forwarded = params.clone();
+            forwarded["ref"][key] = serde_json::json!(inner);
+            return match self.call_method_transformed(&server, "completion/complete", forwarded).await {
+                Ok(res) => JsonRpcResponse::success(id, res),
+                Err(e) => error::upstream(id, e),
+            };
         }
+
+        let capabilities = self.child_manager.server_capabilities().await;
+        let mut results = self.child_manager.request_all_running("completion/complete", params).await;
+        results.retain(|(server, _)| scope.is_none_or(|s| s.contains(server)) && capabilities.get(server).is_some_and(|c| c.get("completions").is_some()));
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        let completions: Vec<serde_json::Value> = results.into_iter().filter_map(|(_, res)| res.ok()).collect();
+        JsonRpcResponse::success(id, serde_json::json!({ "completion": merge_completions(&completions) }))
     }
 
+    /// Resources of running servers, and from the schema cache for servers
+    /// that aren't started yet.
     async fn handle_resources_list(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
+        let ttl = self.list_cache_ttl().await;
+        let resources = self.lists.run("resources/list", ttl, || self.build_resources_list()).await;
+        JsonRpcResponse::success(id, serde_json::json!({ "resources": resources }))
+    }
+
+    async fn build_resources_list(&self) -> serde_json::Value {
         let results = self.child_manager.request_all_running("resources/list", serde_json::json!({})).await;
-        let mut all_resources = Vec::new();
+        let mut cached = self.cached_resources.lock().await;
         for (server_name, res) in results {
-            if let Ok(mut val) = res {
-                if let Some(resources) = val.get_mut("resources").and_then(|v| v.as_array_mut()) {
-                    for res in resources {
-                        if let Some(uri) = res.get("uri").and_then(|v| v.as_str()) {
-                            res["uri"] = serde_json::json!(format!("{}__{}", server_name, uri));
-                        }
-                        all_resources.push(res.clone());
-                    }
-                }
+            if let Ok(val) = res {
+                cached.insert(server_name.clone(), crate::cache::parse_resource_list(&server_name, &val));
             }
         }
-        JsonRpcResponse::success(id, serde_json::json!({ "resources": all_resources }))
+        let mut configured = self.config.lock().await.servers.keys().cloned().collect::<Vec<_>>();
+        configured.sort();
+        let all_resources: Vec<serde_json::Value> = configured
+            .iter()
+            .filter_map(|server| cached.get(server).map(|resources| (server, resources)))
+            .flat_map(|(server, resources)| {
+                resources.iter().map(move |r| {
+                    let mut resource = serde_json::to_value(r).unwrap_or_default();
+                    resource["uri"] = serde_json::json!(format!("{}__{}", server, r.uri));
+                    resource
+                })
+            })
+            .collect();
+        serde_json::Value::Array(all_resources)
     }
 
     async fn handle_resource_templates_list(&self, id: Option<serde_json::Value>) -> JsonRpcResponse {
@@ -644,11 +1876,22 @@ impl ProxyServer {
         JsonRpcResponse::success(id, serde_json::json!({ "resourceTemplates": all_templates }))
     }
 
-    async fn handle_resources_read(&self, id: Option<serde_json::Value>, args: serde_json::Value) -> JsonRpcResponse {
+    /// `resources/read` for `session`. A stored result is only read by the
+    /// session whose call stored it; to the others it doesn't exist.
+    async fn handle_resources_read(&self, id: Option<serde_json::Value>, args: serde_json::Value, session: Option<&str>) -> JsonRpcResponse {
         let uri = args.get("uri").and_then(|v| v.as_str()).unwrap_or("");
+        if uri.starts_with(limits::RESULT_URI_PREFIX) {
+            let stored = self.stored_results.lock().await;
+            return match stored.iter().find(|(u, owner, _)| u == uri && owner.as_deref() == session) {
+                Some((_, _, result)) => JsonRpcResponse::success(id, serde_json::json!({
+                    "contents": [{ "uri": uri, "mimeType": "application/json", "text": result.to_string() }]
+                })),
+                None => JsonRpcResponse::error(id, error::RESOURCE_NOT_FOUND, format!("Stored result {} has expired", uri)),
+            };
+        }
         let parts: Vec<&str> = uri.splitn(2, "__").collect();
         if parts.len() != 2 {
-            return JsonRpcResponse::error(id, -32602, "Invalid resource uri format".into());
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, "Invalid resource uri format".into());
         }
         let server = parts[0];
         let actual_uri = parts[1];
@@ -656,21 +1899,149 @@ impl ProxyServer {
         let mut new_args = args.clone();
         new_args["uri"] = serde_json::json!(actual_uri);
         
-        match self.child_manager.call_method(server, "resources/read", new_args).await {
+        match self.call_method_transformed(server, "resources/read", new_args).await {
+            Ok(res) => JsonRpcResponse::success(id, res),
+            Err(e) => error::upstream(id, e),
+        }
+    }
+
+    /// Forward `resources/subscribe` / `resources/unsubscribe` to the server
+    /// owning the namespaced uri, starting it if needed.
+    async fn handle_resource_subscription(&self, id: Option<serde_json::Value>, method: &str, args: serde_json::Value) -> JsonRpcResponse {
+        let uri = args.get("uri").and_then(|v| v.as_str()).unwrap_or("");
+        let Some((server, actual_uri)) = uri.split_once("__") else {
+            return JsonRpcResponse::error(id, error::INVALID_PARAMS, "Invalid resource uri format".into());
+        };
+        if !self.child_manager.is_running(server).await {
+            if let Err(e) = self.child_manager.start_server(server).await {
+                return error::upstream(id, e);
+            }
+        }
+
+        let mut new_args = args.clone();
+        new_args["uri"] = serde_json::json!(actual_uri);
+
+        match self.child_manager.call_resource_subscription(server, method, new_args).await {
             Ok(res) => JsonRpcResponse::success(id, res),
-            Err(e) => JsonRpcResponse::error(id, -32000, e),
+            Err(e) => error::upstream(id, e),
+        }
+    }
+
+    /// Drop the backend subscription for a namespaced uri nobody holds anymore.
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), String> {
+        let (server, actual_uri) = uri.split_once("__").ok_or("Invalid resource uri format")?;
+        if !self.child_manager.is_running(server).await {
+            return Ok(());
+        }
+        self.child_manager
+            .call_resource_subscription(server, "resources/unsubscribe", serde_json::json!({ "uri": actual_uri }))
+            .await
+            .map(|_| ())
+            .map_err(String::from)
+    }
+
+    pub fn take_client_requests(&self) -> Option<tokio::sync::mpsc::UnboundedReceiver<ClientRequest>> {
+        self.child_manager.take_client_requests()
+    }
+
+    /// Notifications the hub itself sends to every session, such as
+    /// `tools/list_changed` after a config reload.
+    pub fn subscribe_client_broadcasts(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.client_broadcasts.subscribe()
+    }
+
+    pub fn subscribe_notifications(&self) -> tokio::sync::broadcast::Receiver<(String, serde_json::Value)> {
+        self.child_manager.subscribe_notifications()
+    }
+}
+
+/// Prompt arguments are a string map: scalars are converted to strings and
+/// nulls dropped. With a known definition, required arguments are checked
+/// before the backend is involved.
+fn prompt_arguments(name: &str, def: Option<&PromptDef>, args: Option<&serde_json::Value>) -> Result<serde_json::Value, String> {
+    let mut out = serde_json::Map::new();
+    if let Some(obj) = args.and_then(|a| a.as_object()) {
+        for (key, value) in obj {
+            let text = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            out.insert(key.clone(), serde_json::json!(text));
+        }
+    }
+    if let Some(def) = def {
+        if let Some(missing) = def.arguments.iter().find(|a| a.required && !out.contains_key(&a.name)) {
+            return Err(format!("Missing required argument '{}' for prompt '{}'", missing.name, name));
         }
     }
+    Ok(serde_json::Value::Object(out))
+}
+
+/// Mark a listed tool whose server's circuit is open: calls fail fast until
+/// a probe succeeds.
+fn flag_unavailable(tool: &mut serde_json::Map<String, serde_json::Value>) {
+    let description = tool.get("description").and_then(|d| d.as_str()).unwrap_or("");
+    let flagged = format!("[Unavailable: server is failing, calls are rejected until it recovers] {}", description);
+    tool.insert("description".into(), flagged.trim_end().into());
+    let meta = tool.entry("_meta").or_insert_with(|| serde_json::json!({}));
+    if let Some(meta) = meta.as_object_mut() {
+        meta.insert("mcphub".into(), serde_json::json!({ "unavailable": true, "reason": "circuit open" }));
+    }
+}
 
-    async fn handle_cancel(&self, args: serde_json::Value) {
-        // Just broadcast the cancellation to all running servers.
-        // ChildManager does not keep track of request IDs globally.
-        // The server will simply ignore the cancellation if it doesn't know the request ID.
-        let running_servers = self.child_manager.server_names().await;
-        for server in running_servers {
-            let _ = self.child_manager.forward_notification(&server, "notifications/cancelled", args.clone()).await;
+/// With `debugMeta` on, record which backend served a tool call, how long it
+/// took and whether it needed a cold start or a retry under `_meta.mcphub`.
+fn with_debug_meta(enabled: bool, mut result: serde_json::Value, server: &str, elapsed_ms: u64, trace: CallTrace) -> serde_json::Value {
+    if !enabled {
+        return result;
+    }
+    if let Some(obj) = result.as_object_mut() {
+        let meta = obj.entry("_meta").or_insert_with(|| serde_json::json!({}));
+        if let Some(meta) = meta.as_object_mut() {
+            meta.insert("mcphub".into(), serde_json::json!({
+                "server": server,
+                "latencyMs": elapsed_ms,
+                "coldStart": trace.cold_start,
+                "retried": trace.retried,
+            }));
         }
     }
+    result
+}
+
+/// Tool result (`isError: true`) for a call to a tool that doesn't exist:
+/// suggests the closest names (of servers in `scope`, if the session has
+/// one), or lists what is available.
+fn unknown_tool_result(
+    engine: &SearchEngine,
+    requested: &str,
+    server: Option<&str>,
+    scope: Option<&Scope>,
+    mode: &Mode,
+    names: &ToolNames,
+) -> serde_json::Value {
+    let display = |t: &IndexedTool| match mode {
+        Mode::Passthrough => names.join(&t.server_name, &t.original_name),
+        Mode::Discover => format!("execute(server=\"{}\", tool=\"{}\")", t.server_name, t.original_name),
+    };
+
+    let in_scope = |t: &&IndexedTool| scope.is_none_or(|s| s.contains(&t.server_name));
+    let suggestions: Vec<&IndexedTool> = engine.suggest(requested, server, usize::MAX).into_iter().filter(in_scope).take(3).collect();
+    let text = if !suggestions.is_empty() {
+        let names: Vec<String> = suggestions.into_iter().map(display).collect();
+        format!("Unknown tool '{}'. Did you mean: {}?", requested, names.join(", "))
+    } else if let Some(server) = server {
+        let names: Vec<String> = engine.tools_on_server(server).into_iter().take(30).map(|t| t.original_name.clone()).collect();
+        format!("Unknown tool '{}'. Available tools on {}: {}", requested, server, names.join(", "))
+    } else {
+        format!("Unknown tool '{}'. Call 'discover' to find available tools.", requested)
+    };
+
+    serde_json::json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": true,
+    })
 }
 
 /// Strip noise from inputSchema: remove title, examples, $schema, additionalProperties.
@@ -707,78 +2078,208 @@ async fn preload_servers(
     engine: Arc<Mutex<SearchEngine>>,
     names: Vec<String>,
     delay_ms: u64,
+    concurrency: usize,
 ) {
     let total = names.len();
-    eprintln!(
-        "[McpHub][INFO] Preloading {} servers ({}ms stagger)...",
-        total, delay_ms
+    tracing::info!(
+        "Preloading {} servers ({}ms stagger, {} at a time)...",
+        total, delay_ms, concurrency
     );
 
-    let mut all_tools: Vec<IndexedTool> = Vec::new();
+    // Launches are staggered, but a slow server doesn't hold up the next.
+    // A start keeps its slot through all its attempts.
+    let started = Instant::now();
+    let slots = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    let mut starts = tokio::task::JoinSet::new();
+    let mut starting = HashMap::new();
+    for (i, name) in names.into_iter().enumerate() {
+        if i > 0 && delay_ms > 0 {
+            tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+        }
+        let (manager, slots) = (manager.clone(), slots.clone());
+        let task = starts.spawn({
+            let name = name.clone();
+            async move {
+                let _slot = slots.acquire_owned().await;
+                let result = manager.start_server(&name).await;
+                (name, result)
+            }
+        });
+        starting.insert(task.id(), name);
+    }
 
-    for (i, name) in names.iter().enumerate() {
-        match manager.start_server(name).await {
+    let mut failed = Vec::new();
+    while let Some(done) = starts.join_next().await {
+        let (name, result) = match done {
+            Ok(done) => done,
+            Err(e) => {
+                let name = starting.remove(&e.id()).unwrap_or_default();
+                tracing::error!("Preload of '{}' panicked: {}", name, e);
+                failed.push(name);
+                continue;
+            }
+        };
+        match result {
             Ok(tools) => {
-                for tool in tools {
-                    all_tools.push(IndexedTool {
-                        name: format!("{}__{}", name, tool.name),
-                        original_name: tool.name.clone(),
-                        server_name: name.clone(),
-                        description: tool.description.clone(),
-                        tool_def: tool,
-                    });
-                }
+                let indexed = tools.into_iter().map(|tool| IndexedTool {
+                    name: format!("{}__{}", name, tool.name),
+                    original_name: tool.name.clone(),
+                    server_name: name.clone(),
+                    description: tool.description.clone(),
+                    tool_def: tool,
+                }).collect();
+                // Live tools replace the server's cached ones; the others stay
+                engine.lock().await.replace_server(&name, indexed);
             }
             Err(e) => {
-                eprintln!("[McpHub][ERROR] Failed to start '{}': {}", name, e);
+                tracing::error!("Failed to start '{}': {}", name, e);
+                failed.push(name);
             }
         }
+    }
+    failed.sort();
+    tracing::info!(
+        "Preloaded {}/{} servers in {:.1}s{}",
+        total - failed.len(),
+        total,
+        started.elapsed().as_secs_f64(),
+        if failed.is_empty() { String::new() } else { format!(", failed: {}", failed.join(", ")) },
+    );
+}
 
-        // Stagger starts (skip delay after last)
-        if i < total - 1 && delay_ms > 0 {
-            tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+/// Whether a tool call failed because the backend doesn't know the tool:
+/// a JSON-RPC method-not-found error, or an error (or `isError` result)
+/// saying the tool is unknown or not found.
+fn reports_missing_tool(res: &Result<serde_json::Value, Failure>) -> bool {
+    let message = match res {
+        Err(e) => {
+            let Some(error) = e.answer() else {
+                return false;
+            };
+            if error.get("code").and_then(|c| c.as_i64()) == Some(error::METHOD_NOT_FOUND) {
+                return true;
+            }
+            error.get("message").and_then(|m| m.as_str()).unwrap_or("").to_lowercase()
+        }
+        Ok(result) if result.get("isError").and_then(|v| v.as_bool()) == Some(true) => {
+            result.pointer("/content/0/text").and_then(|t| t.as_str()).unwrap_or("").to_lowercase()
         }
+        Ok(_) => return false,
+    };
+    message.contains("tool") && (message.contains("unknown") || message.contains("not found"))
+}
+
+/// `text` in pieces of at most `max` bytes, cut on char boundaries.
+fn split_text(text: &str, max: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max {
+        let mut cut = max;
+        while !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let (piece, tail) = rest.split_at(cut);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces.push(rest);
+    pieces
+}
+
+/// Most values a `completion/complete` result may carry.
+const MAX_COMPLETION_VALUES: usize = 100;
+
+/// Check a `completion/complete` request's reference and argument. Returns
+/// the reference field naming the target: `name` or `uri`.
+fn completion_ref_key(params: &serde_json::Value) -> Result<&'static str, String> {
+    let reference = params.get("ref").ok_or("Missing ref")?;
+    let key = match reference.get("type").and_then(|t| t.as_str()) {
+        Some("ref/prompt") => "name",
+        Some("ref/resource") => "uri",
+        _ => return Err("ref.type must be ref/prompt or ref/resource".into()),
+    };
+    if !reference.get(key).is_some_and(|v| v.is_string()) {
+        return Err(format!("ref.{} must be a string", key));
+    }
+    let argument = params.get("argument").ok_or("Missing argument")?;
+    if !argument.get("name").is_some_and(|v| v.is_string()) || !argument.get("value").is_some_and(|v| v.is_string()) {
+        return Err("argument must have string name and value".into());
+    }
+    Ok(key)
+}
+
+/// Merge backend completion results: values in order without duplicates,
+/// capped at MAX_COMPLETION_VALUES.
+fn merge_completions(results: &[serde_json::Value]) -> serde_json::Value {
+    let mut values: Vec<&serde_json::Value> = Vec::new();
+    let mut has_more = false;
+    for result in results {
+        has_more |= result.pointer("/completion/hasMore").and_then(|v| v.as_bool()).unwrap_or(false);
+        for value in result.pointer("/completion/values").and_then(|v| v.as_array()).into_iter().flatten() {
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+    }
+    if values.len() > MAX_COMPLETION_VALUES {
+        values.truncate(MAX_COMPLETION_VALUES);
+        has_more = true;
     }
+    serde_json::json!({ "values": values, "hasMore": has_more })
+}
 
-    // Build search index
-    let mut eng = engine.lock().await;
-    eng.build_index(all_tools);
+/// The schema cache under the configured version and age policies, with
+/// the names of entries dropped for their age.
+fn load_configured_cache(config: &ProxyConfig) -> Option<(SchemaCache, Vec<String>)> {
+    match config.cache_max_age {
+        Some(max_age) => crate::cache::load_cache_fresh(&config.cache_version, max_age),
+        None => crate::cache::load_cache_checked(&config.cache_version).map(|cache| (cache, Vec::new())),
+    }
 }
 
 /// Watches schema-cache.json and config.json for changes and hot-reloads them.
-async fn config_and_cache_watcher(
-    engine: Arc<Mutex<SearchEngine>>,
-    config_store: Arc<Mutex<ProxyConfig>>,
-    child_manager: Arc<ChildManager>,
-) {
+async fn config_and_cache_watcher(shared: Reloadable, reload: Arc<Notify>) {
     use std::time::SystemTime;
+    let Reloadable { engine, config: config_store, cached_capabilities, cached_prompts, cached_resources, client_broadcasts, lists, .. } = &shared;
 
-    let cache_path_opt = crate::cache::cache_path();
-    let mut last_cache_modified: Option<SystemTime> = cache_path_opt
-        .as_ref()
+    let mut last_cache_modified: Option<SystemTime> = crate::cache::cache_file()
         .and_then(|p| p.metadata().ok())
         .and_then(|m| m.modified().ok());
 
-    let config_path_opt = dirs::home_dir().map(|h| h.join(".McpHub/config.json"));
-    let mut last_config_modified: Option<SystemTime> = config_path_opt
-        .as_ref()
-        .and_then(|p| p.metadata().ok())
-        .and_then(|m| m.modified().ok());
+    let config_paths = crate::config::config_sources();
+    let mut last_config = config_signature(&config_paths);
 
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        let forced = tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(5)) => false,
+            _ = reload.notified() => true,
+        };
 
-        // Check Cache
-        if let Some(cache_path) = &cache_path_opt {
+        // Check Cache, whichever format it is in now
+        if let Some(cache_path) = &crate::cache::cache_file() {
             if let Ok(m) = cache_path.metadata() {
                 if let Ok(current_modified) = m.modified() {
                     if Some(current_modified) != last_cache_modified {
                         last_cache_modified = Some(current_modified);
 
-                        if let Some(cached) = crate::cache::load_cache() {
+                        let loaded = load_configured_cache(&*config_store.lock().await);
+                        if let Some((cached, _)) = loaded {
+                            *cached_capabilities.lock().await = cached.capabilities.clone();
+                            *cached_prompts.lock().await = cached.prompts.clone();
+                            *cached_resources.lock().await = cached.resources.clone();
+                            let (filter, overrides, configured, passthrough) = {
+                                let config = config_store.lock().await;
+                                (
+                                    config.tool_filter.clone(),
+                                    config.tool_overrides.clone(),
+                                    config.servers.keys().cloned().collect::<std::collections::HashSet<_>>(),
+                                    config.mode == Mode::Passthrough,
+                                )
+                            };
                             let mut all_tools: Vec<IndexedTool> = Vec::new();
-                            for (server_name, tools) in &cached.servers {
-                                for tool in tools {
+                            // Entries of servers removed from the config stay in the cache until pruned
+                            for (server_name, tools) in cached.servers.iter().filter(|(name, _)| configured.contains(*name)) {
+                                for tool in tools.iter().filter(|t| overrides.upstream_name(server_name, &t.name).is_some_and(|u| filter.allows(server_name, u))) {
                                     all_tools.push(IndexedTool {
                                         name: format!("{}__{}", server_name, tool.name),
                                         original_name: tool.name.clone(),
@@ -789,11 +2290,22 @@ async fn config_and_cache_watcher(
                                 }
                             }
                             let mut eng = engine.lock().await;
+                            let changed = eng.changed_servers(&all_tools);
                             eng.build_index(all_tools);
-                            eprintln!(
-                                "[McpHub][INFO] Cache hot-reloaded: {} tools",
+                            lists.invalidate();
+                            tracing::info!(
+                                "Cache hot-reloaded: {} tools",
                                 eng.tool_count()
                             );
+                            // A restarted or refreshed backend may have come back
+                            // with other tools: tell clients to list them again
+                            if passthrough && !changed.is_empty() {
+                                tracing::info!("Tools changed on {}, notifying clients", changed.join(", "));
+                                let _ = client_broadcasts.send(serde_json::json!({
+                                    "jsonrpc": "2.0",
+                                    "method": "notifications/tools/list_changed",
+                                }));
+                            }
                         }
                     }
                 }
@@ -801,25 +2313,977 @@ async fn config_and_cache_watcher(
         }
 
         // Check Config
-        if let Some(config_path) = &config_path_opt {
-            if let Ok(m) = config_path.metadata() {
-                if let Ok(current_modified) = m.modified() {
-                    if Some(current_modified) != last_config_modified {
-                        last_config_modified = Some(current_modified);
-
-                        let new_config = crate::config::auto_detect();
-                        let new_servers = new_config.servers.clone();
-                        
-                        {
-                            let mut cfg = config_store.lock().await;
-                            *cfg = new_config;
-                        }
+        let mut current = config_signature(&config_paths);
+        if forced || current != last_config {
+            // Let a burst of edits settle into one reload
+            loop {
+                tokio::time::sleep(CONFIG_RELOAD_DEBOUNCE).await;
+                let settled = config_signature(&config_paths);
+                if settled == current {
+                    break;
+                }
+                current = settled;
+            }
+            last_config = current;
+            match crate::config::reload() {
+                Ok(new_config) => {
+                    apply_config(new_config, &shared).await;
+                }
+                Err(e) => tracing::error!("Config not reloaded, keeping the running one:\n{}", e),
+            }
+        }
+    }
+}
+
+/// Modification times of the config files; None for a missing one.
+fn config_signature(paths: &[std::path::PathBuf]) -> Vec<Option<std::time::SystemTime>> {
+    paths.iter().map(|p| p.metadata().and_then(|m| m.modified()).ok()).collect()
+}
+
+/// Switch to a reloaded config. Removed servers are stopped and dropped
+/// from the tool index, added ones (and running ones whose command
+/// changed) are started in the background, and unchanged servers keep
+/// running. Added servers are indexed from the schema cache until they are
+/// up. Servers whose `toolOverrides` changed are started if need be and
+/// their tools listed again, replacing the cached ones. Sessions see the
+/// new tools on their next `tools/list`.
+async fn apply_config(new_config: ProxyConfig, shared: &Reloadable) {
+    let Reloadable { engine, config: config_store, child_manager, cached_capabilities, cached_prompts, cached_resources, client_broadcasts, cache, lists } = shared;
+    crate::logging::set_rotation(new_config.log_rotation);
+    crate::redact::set_extra_patterns(&new_config.cache_redact_patterns);
+    crate::cache::set_gzip(new_config.cache_gzip);
+    let (old_servers, old_overrides) = {
+        let config = config_store.lock().await;
+        (config.servers.clone(), config.tool_overrides.clone())
+    };
+    let running = child_manager.group_health().await;
+    let removed: Vec<String> = old_servers.keys().filter(|name| !new_config.servers.contains_key(*name)).cloned().collect();
+    let mut to_start: Vec<String> = new_config
+        .servers
+        .iter()
+        .filter(|(name, config)| match old_servers.get(*name) {
+            None => true,
+            Some(old) => old.needs_restart(config) && running.contains_key(*name),
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    to_start.sort();
+    let added = to_start.iter().filter(|name| !old_servers.contains_key(*name)).count();
+    // Tools listed (and cached) with other toolOverrides are listed again
+    let mut remapped: Vec<String> = new_config
+        .servers
+        .keys()
+        .filter(|name| old_servers.contains_key(*name) && !to_start.contains(*name))
+        .filter(|name| old_overrides.servers.get(*name) != new_config.tool_overrides.servers.get(*name))
+        .cloned()
+        .collect();
+    remapped.sort();
+    let passthrough = new_config.mode == Mode::Passthrough;
+    let filter = new_config.tool_filter.clone();
+    let overrides = new_config.tool_overrides.clone();
+
+    child_manager.set_tool_filter(new_config.tool_filter.clone()).await;
+    child_manager.set_tool_overrides(new_config.tool_overrides.clone()).await;
+    let new_servers = new_config.servers.clone();
+    let grace = Duration::from_secs(new_config.reload_grace_secs);
+    *config_store.lock().await = new_config;
+    child_manager.update_configs(new_servers, grace).await;
+
+    if !removed.is_empty() {
+        let mut eng = engine.lock().await;
+        for name in &removed {
+            eng.replace_server(name, Vec::new());
+        }
+        drop(eng);
+        for name in &removed {
+            cached_capabilities.lock().await.remove(name);
+            cached_prompts.lock().await.remove(name);
+            cached_resources.lock().await.remove(name);
+        }
+    }
+    if added > 0 {
+        if let Some(cache) = cache.read() {
+            let mut eng = engine.lock().await;
+            for name in to_start.iter().filter(|name| !old_servers.contains_key(*name)) {
+                let Some(tools) = cache.servers.get(name) else { continue };
+                let indexed = tools.iter().filter(|t| overrides.upstream_name(name, &t.name).is_some_and(|u| filter.allows(name, u))).map(|tool| IndexedTool {
+                    name: format!("{}__{}", name, tool.name),
+                    original_name: tool.name.clone(),
+                    server_name: name.clone(),
+                    description: tool.description.clone(),
+                    tool_def: tool.clone(),
+                }).collect();
+                eng.replace_server(name, indexed);
+            }
+        }
+    }
+    // Filters, names, mode and servers may all have changed
+    lists.invalidate();
+    tracing::info!(
+        "Config hot-reloaded: {} added, {} restarted, {} removed, {} relisted",
+        added,
+        to_start.len() - added,
+        removed.len(),
+        remapped.len()
+    );
+    let list_changed = serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" });
+    if passthrough && !removed.is_empty() {
+        let _ = client_broadcasts.send(list_changed.clone());
+    }
 
-                        child_manager.update_configs(new_servers).await;
-                        eprintln!("[McpHub][INFO] Config hot-reloaded");
+    let relist = remapped.into_iter().map(|name| (name, true));
+    for (name, remap) in to_start.into_iter().map(|name| (name, false)).chain(relist) {
+        let (manager, engine, lists, list_changed) = (child_manager.clone(), engine.clone(), lists.clone(), list_changed.clone());
+        tokio::spawn(async move {
+            let tools = match manager.start_server(&name).await {
+                // A running server still has the tools it listed before
+                Ok(_) if remap => manager.refresh_tools(&name).await,
+                Ok(tools) => {
+                    if let Some(cache) = manager.cache_writer() {
+                        cache.repair_server(&name, tools.clone());
                     }
+                    Ok(tools)
+                }
+                Err(e) => Err(e),
+            };
+            let tools = match tools {
+                Ok(tools) => tools,
+                Err(e) => {
+                    tracing::warn!("Failed to list the tools of '{}' after config reload: {}", name, e);
+                    return;
+                }
+            };
+            let indexed = tools.into_iter().map(|tool_def| IndexedTool {
+                name: format!("{}__{}", name, tool_def.name),
+                original_name: tool_def.name.clone(),
+                server_name: name.clone(),
+                description: tool_def.description.clone(),
+                tool_def,
+            }).collect();
+            engine.lock().await.replace_server(&name, indexed);
+            lists.invalidate();
+            if passthrough {
+                manager.notify_clients(&name, list_changed);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed_tool(server: &str, name: &str) -> IndexedTool {
+        IndexedTool {
+            name: format!("{}__{}", server, name),
+            original_name: name.to_string(),
+            server_name: server.to_string(),
+            description: String::new(),
+            tool_def: ToolDef { name: name.to_string(), description: String::new(), input_schema: serde_json::json!({}), annotations: None },
+        }
+    }
+
+    fn engine() -> SearchEngine {
+        let mut engine = SearchEngine::new();
+        engine.build_index(["create_issue", "list_issues", "create_pull_request"].iter().map(|name| indexed_tool("github", name)).collect());
+        engine
+    }
+
+    /// A proxy over `config` with `tools` (server, tool) indexed. Their
+    /// servers are added, running `true`, unless `config` has them already.
+    async fn proxy_with_tools(mut config: ProxyConfig, tools: &[(&str, &str)]) -> ProxyServer {
+        for (server, _) in tools {
+            config.servers.entry(server.to_string()).or_insert_with(|| crate::config::ServerConfig { command: "true".into(), ..Default::default() });
+        }
+        let proxy = ProxyServer::new(config);
+        proxy.search_engine.lock().await.build_index(tools.iter().map(|(server, name)| indexed_tool(server, name)).collect());
+        proxy
+    }
+
+    async fn listed_tools(proxy: &ProxyServer) -> Vec<String> {
+        let resp = proxy.handle_tools_list(Some(serde_json::json!(1))).await;
+        resp.result.unwrap()["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_unknown_tool_result_suggests_near_miss() {
+        let engine = engine();
+        let result = unknown_tool_result(&engine, "github__create_isue", Some("github"), None, &Mode::Passthrough, &ToolNames::default());
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert_eq!(result["isError"], true);
+        assert!(text.contains("Did you mean: github__create_issue"), "{}", text);
+
+        let result = unknown_tool_result(&engine, "creat_issue", Some("github"), None, &Mode::Discover, &ToolNames::default());
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains(r#"execute(server="github", tool="create_issue")"#), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_orders_by_server_priority() {
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        for (name, priority) in [("alpha", 0), ("beta", -1), ("zeta", 10)] {
+            config.servers.insert(name.into(), crate::config::ServerConfig { command: "true".into(), priority, ..Default::default() });
+        }
+        let proxy = proxy_with_tools(config, &[("beta", "a"), ("alpha", "b"), ("zeta", "z"), ("alpha", "a"), ("zeta", "c")]).await;
+
+        assert_eq!(listed_tools(&proxy).await, ["zeta__c", "zeta__z", "alpha__a", "alpha__b", "beta__a"]);
+    }
+
+    #[tokio::test]
+    async fn test_session_scope_limits_tools() {
+        let config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        let proxy = proxy_with_tools(config, &[("alpha", "read_notes"), ("zeta", "read_file")]).await;
+
+        assert_eq!(proxy.parse_scope("beta, alpha,gamma").await, Err("servers: unknown server(s): beta, gamma".into()));
+        assert!(proxy.parse_scope(" , ").await.is_err());
+        let scope = proxy.parse_scope("alpha").await.unwrap();
+
+        let request = |method: &str, params| JsonRpcRequest { jsonrpc: "2.0".into(), id: Some(serde_json::json!(1)), method: method.into(), params };
+        let list = proxy.handle_scoped_request("s", request("tools/list", serde_json::json!({})), Handshake::Ready, &scope).await.unwrap();
+        let names: Vec<&str> = list.result.as_ref().unwrap()["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["alpha__read_notes"]);
+
+        let call = request("tools/call", serde_json::json!({"name": "zeta__read_file"}));
+        let err = proxy.handle_scoped_request("s", call, Handshake::Ready, &scope).await.unwrap().error.unwrap();
+        assert_eq!(err.message, "Tool 'read_file' is on server 'zeta', which this session can't use");
+
+        // The hub answers a bare ping; a server ping stays within the scope
+        let ping = proxy.handle_scoped_request("s", request("ping", serde_json::json!({})), Handshake::Ready, &scope).await.unwrap();
+        assert_eq!(ping.result, Some(serde_json::json!({})));
+        let ping = request("ping", serde_json::json!({"server": "zeta"}));
+        let err = proxy.handle_scoped_request("s", ping, Handshake::Ready, &scope).await.unwrap().error.unwrap();
+        assert_eq!(err.code, error::INVALID_PARAMS);
+        let ping = request("ping", serde_json::json!({"server": "alpha"}));
+        let err = proxy.handle_scoped_request("s", ping, Handshake::Ready, &scope).await.unwrap().error.unwrap();
+        assert_eq!((err.code, err.message.as_str()), (error::UNAVAILABLE, "Server not running: alpha"));
+
+        // top_k counts only the tools in scope, however well the others match
+        proxy.config.lock().await.mode = Mode::Discover;
+        let discover = request("tools/call", serde_json::json!({"name": "discover", "arguments": {"query": "read file", "top_k": 1}}));
+        let result = proxy.handle_scoped_request("s", discover, Handshake::Ready, &scope).await.unwrap().result.unwrap();
+        let found: serde_json::Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(found["available_servers"], serde_json::json!(["alpha"]));
+        assert_eq!(found["total_servers"], 1);
+        assert_eq!(found["results"][0]["tool"], "read_notes");
+    }
+
+    #[tokio::test]
+    async fn test_stored_result_read_only_by_its_session() {
+        let proxy = ProxyServer::new(ProxyConfig::default());
+        let result = serde_json::json!({"content": [{"type": "text", "text": "all of it"}]});
+        let uri = proxy.store_result(result, Some("a")).await;
+        let id = uri.strip_prefix(limits::RESULT_URI_PREFIX).unwrap();
+        assert!(id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()), "{}", uri);
+        assert_ne!(proxy.store_result(serde_json::json!({}), Some("a")).await, uri);
+
+        let read = |session| proxy.handle_resources_read(Some(serde_json::json!(1)), serde_json::json!({"uri": uri}), session);
+        let err = read(Some("b")).await.error.unwrap();
+        assert_eq!(err.code, error::RESOURCE_NOT_FOUND);
+        assert!(read(None).await.error.is_some());
+        let text = read(Some("a")).await.result.unwrap()["contents"][0]["text"].as_str().unwrap().to_string();
+        assert!(text.contains("all of it"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tool_suggestions_stay_in_scope() {
+        let config = ProxyConfig { unknown_tool_fallback: true, ..Default::default() };
+        let proxy = proxy_with_tools(config, &[("alpha", "list_items"), ("zeta", "list_item")]).await;
+        let call = || JsonRpcRequest { jsonrpc: "2.0".into(), id: Some(serde_json::json!(1)), method: "tools/call".into(), params: serde_json::json!({"name": "list_itm"}) };
+
+        let text = |response: Option<JsonRpcResponse>| response.unwrap().result.unwrap()["content"][0]["text"].as_str().unwrap().to_string();
+        let unscoped = text(proxy.handle_session_request("s", call(), Handshake::Ready).await);
+        assert!(unscoped.contains(r#"server="zeta""#), "{}", unscoped);
+        let scope = proxy.parse_scope("alpha").await.unwrap();
+        let scoped = text(proxy.handle_scoped_request("s", call(), Handshake::Ready, &scope).await);
+        assert!(scoped.contains(r#"execute(server="alpha", tool="list_items")"#), "{}", scoped);
+        assert!(!scoped.contains("zeta"), "{}", scoped);
+    }
+
+    #[tokio::test]
+    async fn test_annotations_travel_with_tools() {
+        let proxy = ProxyServer::new(ProxyConfig { list_cache_ms: 0, ..Default::default() });
+        let annotations = serde_json::json!({"readOnlyHint": true, "destructiveHint": false});
+        proxy.search_engine.lock().await.replace_server("fs", vec![IndexedTool {
+            name: "fs__read_file".into(),
+            original_name: "read_file".into(),
+            server_name: "fs".into(),
+            description: "Read a file".into(),
+            tool_def: ToolDef { name: "read_file".into(), description: "Read a file".into(), input_schema: serde_json::json!({}), annotations: Some(annotations.clone()) },
+        }]);
+
+        let found = proxy.handle_discover(None, serde_json::json!({"query": "read file"}), None).await.result.unwrap();
+        let found: serde_json::Value = serde_json::from_str(found["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(found["results"][0]["annotations"], annotations);
+
+        proxy.config.lock().await.mode = Mode::Passthrough;
+        let list = proxy.handle_tools_list(None).await.result.unwrap();
+        assert_eq!(list["tools"][0]["name"], "fs__read_file");
+        assert_eq!(list["tools"][0]["annotations"], annotations);
+    }
+
+    #[tokio::test]
+    async fn test_tool_names_unprefixed_and_custom_separator() {
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.tool_names.prefix = false;
+        for (name, priority) in [("alpha", 0), ("zeta", 10)] {
+            config.servers.insert(name.into(), crate::config::ServerConfig { command: "true".into(), priority, ..Default::default() });
+        }
+        let proxy = proxy_with_tools(config, &[("alpha", "search"), ("alpha", "read"), ("zeta", "search")]).await;
+
+        // The higher-priority server wins a clash, and calls route to it
+        assert_eq!(listed_tools(&proxy).await, ["search", "read"]);
+        assert_eq!(proxy.resolve_tool_name("search").await, Some(("zeta".into(), "search".into())));
+        assert_eq!(proxy.resolve_tool_name("read").await, Some(("alpha".into(), "read".into())));
+        assert_eq!(proxy.resolve_tool_name("write").await, None);
+
+        let mut reloaded = proxy.config.lock().await.clone();
+        reloaded.tool_names = ToolNames { prefix: true, separator: ".".into() };
+        apply_config(reloaded, &proxy.reloadable()).await;
+        assert_eq!(listed_tools(&proxy).await, ["zeta.search", "alpha.read", "alpha.search"]);
+        assert_eq!(proxy.resolve_tool_name("alpha.search").await, Some(("alpha".into(), "search".into())));
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_starts_added_and_drops_removed_servers() {
+        let server = |command: &str, args: Vec<String>| crate::config::ServerConfig {
+            command: command.into(),
+            args,
+            ..Default::default()
+        };
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.servers.insert("kept".into(), server("true", Vec::new()));
+        config.servers.insert("gone".into(), server("true", Vec::new()));
+        let proxy = proxy_with_tools(config.clone(), &[("kept", "a"), ("gone", "b")]).await;
+
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"hello"}]}}'
+while read l; do :; done"#;
+        config.servers.remove("gone");
+        config.servers.insert("mcphub-reload-test".into(), server("sh", vec!["-c".into(), script.into()]));
+        let mut broadcasts = proxy.subscribe_client_broadcasts();
+        apply_config(config, &proxy.reloadable()).await;
+        // Removing a server changes every session's list
+        assert_eq!(broadcasts.try_recv().unwrap()["method"], "notifications/tools/list_changed");
+        // The added server may already be listed from the schema cache
+        let listed = listed_tools(&proxy).await;
+        assert!(listed.contains(&"kept__a".to_string()) && !listed.contains(&"gone__b".to_string()), "{:?}", listed);
+
+        let started = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let names = listed_tools(&proxy).await;
+                if names.len() > 1 {
+                    return names;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }).await;
+        proxy.shutdown().await;
+        assert_eq!(started.expect("added server not started"), ["kept__a", "mcphub-reload-test__hello"]);
+        assert!(!proxy.config.lock().await.servers.contains_key("gone"));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_tool_call_is_rejected() {
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        let server = crate::config::ServerConfig {
+            command: "true".into(),
+            ..Default::default()
+        };
+        config.servers.insert("fs".into(), server);
+        config.tool_filter = crate::tool_filter::parse_tool_filter(&serde_json::json!({"fs": {"deny": ["write_*"]}})).unwrap();
+        let proxy = ProxyServer::new(config);
+
+        let resp = proxy.handle_tools_call(Some(serde_json::json!(1)), serde_json::json!({"name": "fs__write_file"}), CallContext::default()).await;
+        let error = resp.error.unwrap();
+        assert_eq!(error.code, -32602);
+        assert!(error.message.contains("not allowed"), "{}", error.message);
+
+        // Discover mode's execute can't reach it either, whatever the case
+        proxy.config.lock().await.mode = Mode::Discover;
+        let args = serde_json::json!({"name": "execute", "arguments": {"server": "FS", "tool": "write_file"}});
+        let resp = proxy.handle_tools_call(Some(serde_json::json!(2)), args, CallContext::default()).await;
+        assert_eq!(resp.error.unwrap().code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_call_with_invalid_arguments_is_rejected() {
+        let mut config = ProxyConfig { mode: Mode::Passthrough, validate_arguments: true, ..Default::default() };
+        let server = crate::config::ServerConfig {
+            command: "true".into(),
+            ..Default::default()
+        };
+        config.servers.insert("fs".into(), server);
+        config.transforms = transform::parse_transforms(&serde_json::json!([{
+            "match": { "server": "fs", "tool": "read_file" },
+            "request": [{ "op": "default", "path": "arguments.encoding", "value": "utf-8" }]
+        }]))
+        .unwrap();
+        let proxy = ProxyServer::new(config);
+        let input_schema = serde_json::json!({
+            "type": "object",
+            "properties": {"path": {"type": "string"}, "encoding": {"type": "string"}},
+            "required": ["path", "encoding"]
+        });
+        proxy.search_engine.lock().await.replace_server("fs", vec![IndexedTool {
+            name: "fs__read_file".into(),
+            original_name: "read_file".into(),
+            server_name: "fs".into(),
+            description: String::new(),
+            tool_def: ToolDef { name: "read_file".into(), description: String::new(), input_schema, annotations: None },
+        }]);
+
+        let args = serde_json::json!({"name": "fs__read_file", "arguments": {"path": 7}});
+        let error = proxy.handle_tools_call(Some(serde_json::json!(1)), args, CallContext::default()).await.error.unwrap();
+        assert_eq!(error.code, -32602);
+        assert_eq!(error.message, "Invalid arguments for 'fs__read_file': arguments.path: expected string, got integer");
+
+        // The transform's default counts, and nothing is checked with validation off
+        assert_eq!(proxy.invalid_arguments("fs", "read_file", &serde_json::json!({"path": "a"})).await, None);
+        proxy.config.lock().await.validate_arguments = false;
+        assert_eq!(proxy.invalid_arguments("fs", "read_file", &serde_json::json!({})).await, None);
+    }
+
+    #[test]
+    fn test_tool_metrics_histogram_and_quantiles() {
+        let mut m = ToolMetrics::default();
+        assert_eq!(m.quantile_ms(0.5), None);
+        for ms in [3, 4, 40, 45, 48, 90, 200, 800, 2000, 60000] {
+            m.record(Some(Duration::from_millis(ms)), false);
+        }
+        m.record(None, true);
+        assert_eq!((m.call_count, m.error_count), (11, 1));
+        assert_eq!(m.p50_ms, Some(50));
+        // The 95th percentile is past the last bound
+        assert_eq!(m.p95_ms, None);
+
+        let tools = HashMap::from([("github".to_string(), HashMap::from([("search".to_string(), m)]))]);
+        let text = tools_prometheus(&tools);
+        assert!(text.contains("mcphub_tool_calls_total{server=\"github\",tool=\"search\"} 11\n"));
+        assert!(text.contains("mcphub_tool_call_duration_seconds_bucket{server=\"github\",tool=\"search\",le=\"0.05\"} 5\n"));
+        assert!(text.contains("mcphub_tool_call_duration_seconds_bucket{server=\"github\",tool=\"search\",le=\"+Inf\"} 10\n"));
+        assert!(text.contains("mcphub_tool_call_duration_seconds_count{server=\"github\",tool=\"search\"} 10\n"));
+    }
+
+    #[test]
+    fn test_server_up_gauges() {
+        let health = HashMap::from([
+            ("github".to_string(), GroupHealth { state: crate::child::GroupState::Degraded, up: 1, total: 2 }),
+            ("slack".to_string(), GroupHealth { state: crate::child::GroupState::Failed, up: 0, total: 1 }),
+        ]);
+        let restarts = HashMap::from([
+            ("slack".to_string(), RestartStats { restarts: 3, failed_restarts: 1, respawns: 0, last_restart_at: Some(1700000000) }),
+        ]);
+        let breakers = HashMap::from([
+            ("slack".to_string(), BreakerStatus { state: crate::breaker::BreakerState::Closed, consecutive_failures: 2, opened_count: 0 }),
+        ]);
+        let text = servers_prometheus(&["slack".into(), "github".into(), "lazy".into()], &health, &restarts, &breakers);
+        assert!(text.contains("mcphub_server_up{server=\"github\"} 1\nmcphub_server_up{server=\"lazy\"} 0\nmcphub_server_up{server=\"slack\"} 0\n"));
+        assert!(text.contains("mcphub_server_restarts_total{server=\"lazy\"} 0\nmcphub_server_restarts_total{server=\"slack\"} 3\n"));
+        assert!(text.contains("mcphub_server_restart_failures_total{server=\"slack\"} 1\n"));
+        // Only servers that have been restarted have a timestamp
+        assert!(text.contains("# TYPE mcphub_server_last_restart_timestamp_seconds gauge\nmcphub_server_last_restart_timestamp_seconds{server=\"slack\"} 1700000000\n#"));
+        assert!(text.ends_with("mcphub_server_consecutive_failures{server=\"slack\"} 2\n"));
+    }
+
+    #[test]
+    fn test_split_text_on_char_boundaries() {
+        assert_eq!(split_text("abcdef", 4), ["abcd", "ef"]);
+        assert_eq!(split_text("", 4), [""]);
+        // 'é' is two bytes: the cut backs off rather than split it
+        assert_eq!(split_text("abcé", 4), ["abc", "é"]);
+    }
+
+    #[test]
+    fn test_debug_meta_only_when_enabled() {
+        let result = serde_json::json!({"content": [], "_meta": {"progressToken": 1}});
+        let trace = CallTrace { cold_start: true, retried: false, upstream: None };
+
+        let plain = with_debug_meta(false, result.clone(), "github", 12, trace);
+        assert_eq!(plain, result);
+
+        let debug = with_debug_meta(true, result, "github", 12, trace);
+        assert_eq!(debug["_meta"]["mcphub"]["server"], "github");
+        assert_eq!(debug["_meta"]["mcphub"]["latencyMs"], 12);
+        assert_eq!(debug["_meta"]["mcphub"]["coldStart"], true);
+        assert_eq!(debug["_meta"]["progressToken"], 1);
+    }
+
+    #[test]
+    fn test_unknown_tool_result_lists_available() {
+        let engine = engine();
+        let result = unknown_tool_result(&engine, "delete_repository", Some("github"), None, &Mode::Discover, &ToolNames::default());
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("Available tools on github"), "{}", text);
+        assert!(text.contains("list_issues"));
+    }
+
+    /// Backend with one `summarize` prompt whose description and messages
+    /// name the backend, so results show which server answered.
+    #[cfg(unix)]
+    fn prompt_server(label: &str) -> crate::config::ServerConfig {
+        let script = format!(
+            r#"read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05","capabilities":{{"prompts":{{}},"completions":{{}}}}}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[]}}}}'
+id=3
+while read l; do
+  case "$l" in
+    *completion/complete*'"name":"summarize"'*) echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{\"completion\":{{\"values\":[\"{label}\",\"shared\"]}}}}}}" ;;
+    *prompts/list*) echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{\"prompts\":[{{\"name\":\"summarize\",\"description\":\"{label}\",\"arguments\":[{{\"name\":\"text\",\"required\":true}}]}}]}}}}" ;;
+    *) echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{\"messages\":[{{\"role\":\"user\",\"content\":{{\"type\":\"text\",\"text\":\"{label}\"}}}}]}}}}" ;;
+  esac
+  id=$((id+1))
+done"#,
+            label = label,
+        );
+        crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script],
+            ..Default::default()
+        }
+    }
+
+    fn request(id: u64, method: &str) -> JsonRpcRequest {
+        serde_json::from_value(serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_before_initialize_strict_is_rejected() {
+        let config = ProxyConfig { pre_initialize: PreInitialize::Strict, ..Default::default() };
+        let proxy = ProxyServer::new(config);
+        let mut initialized = false;
+
+        let early = request(1, "tools/list");
+        let handshake = proxy.handshake(&early, &mut initialized);
+        let response = proxy.handle_session_request("s", early, handshake).await.unwrap();
+        assert_eq!(response.error.unwrap().code, error::NOT_INITIALIZED);
+        assert!(!initialized);
+
+        for (id, method) in [(2, "initialize"), (3, "tools/list")] {
+            let req = request(id, method);
+            let handshake = proxy.handshake(&req, &mut initialized);
+            assert!(proxy.handle_session_request("s", req, handshake).await.unwrap().error.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_before_initialize_lenient_proceeds() {
+        let proxy = ProxyServer::new(ProxyConfig::default());
+        let mut initialized = false;
+
+        let early = request(1, "tools/list");
+        let handshake = proxy.handshake(&early, &mut initialized);
+        assert!(matches!(handshake, Handshake::Assumed));
+        let response = proxy.handle_session_request("s", early, handshake).await.unwrap();
+        assert!(response.result.unwrap()["tools"].is_array());
+        assert!(initialized);
+        assert!(matches!(proxy.handshake(&request(2, "tools/list"), &mut initialized), Handshake::Ready));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_prompts_aggregated_and_fetched_by_namespaced_name() {
+        let mut config = ProxyConfig::default();
+        config.servers.insert("alpha".into(), prompt_server("from alpha"));
+        config.servers.insert("beta".into(), prompt_server("from beta"));
+        let proxy = ProxyServer::new(config);
+        proxy.child_manager.start_server("alpha").await.unwrap();
+        proxy.child_manager.start_server("beta").await.unwrap();
+
+        let list = proxy.handle_prompts_list(Some(serde_json::json!(1))).await;
+        let prompts = list.result.unwrap()["prompts"].clone();
+        let names: Vec<&str> = prompts.as_array().unwrap().iter().filter_map(|p| p["name"].as_str()).collect();
+        assert_eq!(names, ["alpha__summarize", "beta__summarize"]);
+        assert_eq!(prompts[1]["arguments"][0]["name"], "text");
+
+        let get = proxy.handle_prompts_get(
+            Some(serde_json::json!(2)),
+            serde_json::json!({ "name": "beta__summarize", "arguments": { "text": "hello" } }),
+        ).await;
+        assert_eq!(get.result.unwrap()["messages"][0]["content"]["text"], "from beta");
+
+        // The cached schema rejects a call missing a required argument
+        let missing = proxy.handle_prompts_get(Some(serde_json::json!(3)), serde_json::json!({ "name": "alpha__summarize" })).await;
+        assert!(missing.error.unwrap().message.contains("Missing required argument 'text'"));
+        proxy.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_completion_routes_to_owning_backend() {
+        let mut config = ProxyConfig::default();
+        config.servers.insert("alpha".into(), prompt_server("from alpha"));
+        config.servers.insert("beta".into(), prompt_server("from beta"));
+        let proxy = ProxyServer::new(config);
+        proxy.child_manager.start_server("alpha").await.unwrap();
+        let complete = |name: &str| serde_json::json!({
+            "ref": { "type": "ref/prompt", "name": name },
+            "argument": { "name": "text", "value": "fr" }
+        });
+
+        // beta isn't running yet: started on demand, alpha isn't asked
+        let routed = proxy.handle_completion(Some(serde_json::json!(1)), complete("beta__summarize"), None).await;
+        assert_eq!(routed.result.unwrap()["completion"]["values"], serde_json::json!(["from beta", "shared"]));
+
+        // No server prefix: every backend offering completions, merged
+        let merged = proxy.handle_completion(Some(serde_json::json!(2)), complete("summarize"), None).await;
+        let completion = merged.result.unwrap()["completion"].clone();
+        assert_eq!(completion["values"], serde_json::json!(["from alpha", "shared", "from beta"]));
+        assert_eq!(completion["hasMore"], false);
+
+        let invalid = proxy.handle_completion(
+            Some(serde_json::json!(3)),
+            serde_json::json!({ "ref": { "type": "ref/tool", "name": "x" }, "argument": { "name": "a", "value": "" } }),
+            None,
+        ).await;
+        assert_eq!(invalid.error.unwrap().code, -32602);
+        proxy.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_scope_limits_prompts_and_completions() {
+        let mut config = ProxyConfig::default();
+        config.servers.insert("alpha".into(), prompt_server("from alpha"));
+        config.servers.insert("beta".into(), prompt_server("from beta"));
+        let proxy = ProxyServer::new(config);
+        proxy.child_manager.start_server("alpha").await.unwrap();
+        proxy.child_manager.start_server("beta").await.unwrap();
+        let scope = proxy.parse_scope("alpha").await.unwrap();
+        let request = |method: &str, params| JsonRpcRequest { jsonrpc: "2.0".into(), id: Some(serde_json::json!(1)), method: method.into(), params };
+        let ask = |method: &str, params| proxy.handle_scoped_request("s", request(method, params), Handshake::Ready, &scope);
+
+        let list = ask("prompts/list", serde_json::json!({})).await.unwrap().result.unwrap();
+        let names: Vec<&str> = list["prompts"].as_array().unwrap().iter().filter_map(|p| p["name"].as_str()).collect();
+        assert_eq!(names, ["alpha__summarize"]);
+
+        let get = ask("prompts/get", serde_json::json!({ "name": "beta__summarize", "arguments": { "text": "hi" } })).await.unwrap();
+        assert_eq!(get.error.unwrap().message, "Server 'beta' is not available to this session");
+        let read = ask("resources/read", serde_json::json!({ "uri": "beta__file:///x" })).await.unwrap();
+        assert_eq!(read.error.unwrap().code, error::INVALID_PARAMS);
+
+        let complete = |name: &str| serde_json::json!({
+            "ref": { "type": "ref/prompt", "name": name },
+            "argument": { "name": "text", "value": "fr" }
+        });
+        let routed = ask("completion/complete", complete("beta__summarize")).await.unwrap();
+        assert_eq!(routed.error.unwrap().code, error::INVALID_PARAMS);
+        // An unprefixed ref only reaches the servers in scope
+        let merged = ask("completion/complete", complete("summarize")).await.unwrap();
+        assert_eq!(merged.result.unwrap()["completion"]["values"], serde_json::json!(["from alpha", "shared"]));
+        proxy.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_answers_before_dispatch() {
+        let proxy = ProxyServer::new(ProxyConfig::default()).with_interceptor(intercept::BlockMethods(vec!["ping".into()]));
+        let ping = JsonRpcRequest { jsonrpc: "2.0".into(), id: Some(serde_json::json!(1)), method: "ping".into(), params: serde_json::json!({}) };
+        let resp = proxy.handle_request_in("s1", ping).await.unwrap();
+        assert_eq!(resp.error.unwrap().message, "Method blocked by blockedMethods: ping");
+        let list = JsonRpcRequest { jsonrpc: "2.0".into(), id: Some(serde_json::json!(2)), method: "tools/list".into(), params: serde_json::json!({}) };
+        assert!(proxy.handle_request(list).await.unwrap().result.is_some());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reloaded_tool_overrides_relist_tools() {
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+id=2
+while read l; do
+  echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"tools\":[{\"name\":\"search_code\"},{\"name\":\"create_issue\"}]}}"
+  id=$((id+1))
+done"#;
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.servers.insert("mock".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let mut proxy = ProxyServer::new(config.clone());
+        // Without failure recording, so the user's schema cache is left alone
+        proxy.child_manager = Arc::new(ChildManager::new(config.servers, 60_000));
+        proxy.child_manager.start_server("mock").await.unwrap();
+
+        let mut reloaded = proxy.config.lock().await.clone();
+        reloaded.tool_overrides = crate::tool_overrides::parse_tool_overrides(&serde_json::json!({"mock": {"search_code": {"name": "find"}}})).unwrap();
+        apply_config(reloaded, &proxy.reloadable()).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while proxy.search_engine.lock().await.find_tool("mock", "find").is_none() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("tools not listed again");
+        assert!(proxy.search_engine.lock().await.find_tool("mock", "search_code").is_none());
+
+        // The upstream name is hidden like the tool it now is
+        let resp = proxy.handle_tools_call(Some(serde_json::json!(1)), serde_json::json!({ "name": "mock__search_code" }), CallContext::default()).await;
+        let message = resp.error.unwrap().message;
+        assert!(message.contains("renamed by toolOverrides"), "{}", message);
+        proxy.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_call_to_dropped_tool_refreshes_cache() {
+        // Started with `keep` and `gone`; after an upgrade only `keep` is left
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"keep"},{"name":"gone"}]}}'
+id=3
+while read l; do
+  case "$l" in
+    *tools/list*) echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"tools\":[{\"name\":\"keep\"}]}}" ;;
+    *) echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"error\":{\"code\":-32602,\"message\":\"Unknown tool: gone\"}}" ;;
+  esac
+  id=$((id+1))
+done"#;
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.servers.insert("mock".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let mut proxy = ProxyServer::new(config.clone());
+        // Without failure recording, so the user's schema cache is left alone
+        proxy.child_manager = Arc::new(ChildManager::new(config.servers, 60_000));
+        let tools = proxy.child_manager.start_server("mock").await.unwrap();
+        let indexed = tools.into_iter().map(|tool_def| IndexedTool {
+            name: format!("mock__{}", tool_def.name),
+            original_name: tool_def.name.clone(),
+            server_name: "mock".into(),
+            description: String::new(),
+            tool_def,
+        }).collect();
+        proxy.search_engine.lock().await.build_index(indexed);
+        let mut notifications = proxy.subscribe_notifications();
+
+        let resp = proxy.handle_tools_call(Some(serde_json::json!(1)), serde_json::json!({ "name": "mock__gone" }), CallContext::default()).await;
+        let message = resp.error.unwrap().message;
+        assert!(message.contains("no longer provided by server 'mock'"), "{}", message);
+        let engine = proxy.search_engine.lock().await;
+        assert!(engine.find_tool("mock", "gone").is_none());
+        assert!(engine.find_tool("mock", "keep").is_some());
+        drop(engine);
+        let (server, notification) = notifications.try_recv().unwrap();
+        assert_eq!((server.as_str(), notification["method"].as_str()), ("mock", Some("notifications/tools/list_changed")));
+        proxy.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tripped_server_tools_flagged_unavailable() {
+        // Exits on the first tool call
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"lookup","description":"Look up"}]}}'
+read l"#;
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.servers.insert("mock".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            circuit_breaker: crate::breaker::BreakerOverride { failure_threshold: Some(1), ..Default::default() },
+            ..Default::default()
+        });
+        let mut proxy = ProxyServer::new(config.clone());
+        proxy.child_manager = Arc::new(ChildManager::new(config.servers, 60_000));
+        let tools = proxy.child_manager.start_server("mock").await.unwrap();
+        let indexed = tools.into_iter().map(|tool_def| IndexedTool {
+            name: format!("mock__{}", tool_def.name),
+            original_name: tool_def.name.clone(),
+            server_name: "mock".into(),
+            description: String::new(),
+            tool_def,
+        }).collect();
+        proxy.search_engine.lock().await.build_index(indexed);
+        let mut notifications = proxy.subscribe_notifications();
+
+        let call = serde_json::json!({ "name": "mock__lookup" });
+        assert!(proxy.handle_tools_call(Some(serde_json::json!(1)), call.clone(), CallContext::default()).await.error.is_some());
+        let message = proxy.handle_tools_call(Some(serde_json::json!(2)), call, CallContext::default()).await.error.unwrap().message;
+        assert!(message.starts_with("Circuit open for 'mock'"), "{}", message);
+
+        let listed = proxy.get_passthrough_tools().await;
+        assert!(listed[0]["description"].as_str().unwrap().starts_with("[Unavailable:"));
+        assert_eq!(listed[0]["_meta"]["mcphub"]["unavailable"], true);
+        let changed = std::iter::from_fn(|| notifications.try_recv().ok())
+            .any(|(_, n)| n["method"] == "notifications/tools/list_changed");
+        assert!(changed);
+        proxy.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cached_result_until_invalidated() {
+        // Every call answers with its own request id
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"lookup"}]}}'
+id=3
+while read l; do
+  echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"content\":[{\"type\":\"text\",\"text\":\"call $id\"}]}}"
+  id=$((id+1))
+done"#;
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.result_cache.tools.insert("mock__lookup".into(), 300);
+        config.servers.insert("mock".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let proxy = ProxyServer::new(config);
+        let call = |q: &str| serde_json::json!({ "name": "mock__lookup", "arguments": { "q": q } });
+        let text = |resp: JsonRpcResponse| resp.result.unwrap()["content"][0]["text"].as_str().unwrap().to_string();
+
+        let first = text(proxy.handle_tools_call(Some(serde_json::json!(1)), call("a"), CallContext::default()).await);
+        assert_eq!(text(proxy.handle_tools_call(Some(serde_json::json!(2)), call("a"), CallContext::default()).await), first);
+        // Other arguments are a different entry
+        assert_ne!(text(proxy.handle_tools_call(Some(serde_json::json!(3)), call("b"), CallContext::default()).await), first);
+
+        assert_eq!(proxy.invalidate_tool_results(Some("mock__lookup")).await, 2);
+        assert_ne!(text(proxy.handle_tools_call(Some(serde_json::json!(4)), call("a"), CallContext::default()).await), first);
+        let stats = &proxy.tool_cache_stats().await["mock__lookup"];
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 3, 1));
+        proxy.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_call_is_cancelled_upstream() {
+        // Never answers the call; records what it gets afterwards
+        let log = std::env::temp_dir().join(format!("mcphub-cancel-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        let script = format!(
+            r#"read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05"}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{{"name":"spin"}}]}}}}'
+while read l; do echo "$l" >> {log}; done"#,
+            log = log.display(),
+        );
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.servers.insert("busy".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script],
+            ..Default::default()
+        });
+        let audit = std::env::temp_dir().join(format!("mcphub-cancel-audit-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&audit);
+        config.audit = crate::audit::AuditConfig { enabled: true, path: Some(audit.clone()), ..Default::default() };
+        let proxy = Arc::new(ProxyServer::new(config));
+        let request = |method: &str, id: Option<u64>, params: serde_json::Value| JsonRpcRequest {
+            jsonrpc: "2.0".into(),
+            id: id.map(|id| serde_json::json!(id)),
+            method: method.into(),
+            params,
+        };
+        let call = request("tools/call", Some(5), serde_json::json!({ "name": "busy__spin", "arguments": {} }));
+        let pending = {
+            let proxy = proxy.clone();
+            tokio::spawn(async move { proxy.handle_request_in("s1", call).await })
+        };
+        while proxy.in_flight_calls.lock().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Another session's request 5 is a different request
+        let cancel = |session: &'static str| {
+            let proxy = proxy.clone();
+            let cancel = request("notifications/cancelled", None, serde_json::json!({ "requestId": 5 }));
+            async move { proxy.handle_request_in(session, cancel).await }
+        };
+        assert!(cancel("s2").await.is_none());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!pending.is_finished());
+        // Its id can't be reused while it's in flight
+        let reused = proxy.handle_request_in("s1", request("ping", Some(5), serde_json::json!({}))).await.unwrap();
+        assert_eq!(reused.error.unwrap().code, error::INVALID_REQUEST);
+
+        assert!(cancel("s1").await.is_none());
+        let response = tokio::time::timeout(Duration::from_secs(5), pending).await.expect("call not cancelled").unwrap();
+        assert!(response.is_none());
+        assert!(proxy.in_flight_calls.lock().await.is_empty());
+        let forwarded = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let seen = std::fs::read_to_string(&log).unwrap_or_default();
+                if seen.contains("notifications/cancelled") {
+                    return seen;
                 }
+                tokio::time::sleep(Duration::from_millis(20)).await;
             }
+        })
+        .await;
+        proxy.shutdown().await;
+        let _ = std::fs::remove_file(&log);
+        // The backend's own id for the call, not the client's
+        assert!(forwarded.expect("backend not told").contains(r#""requestId":3"#));
+
+        proxy.audit.flush();
+        let audited: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&audit).unwrap().trim()).unwrap();
+        let _ = std::fs::remove_file(&audit);
+        assert_eq!((audited["session"].as_str(), audited["server"].as_str(), audited["tool"].as_str()), (Some("s1"), Some("busy"), Some("spin")));
+        assert_eq!(audited["outcome"], "cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_other_than_call_gets_no_response() {
+        // Answers the handshake, then nothing
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"spin"}]}}'
+while read l; do :; done"#;
+        let mut config = ProxyConfig { mode: Mode::Passthrough, ..Default::default() };
+        config.servers.insert("busy".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let proxy = Arc::new(ProxyServer::new(config));
+        let request = |method: &str, id: Option<u64>, params: serde_json::Value| JsonRpcRequest {
+            jsonrpc: "2.0".into(),
+            id: id.map(|id| serde_json::json!(id)),
+            method: method.into(),
+            params,
+        };
+        proxy.child_manager.start_server("busy").await.unwrap();
+        let ping = request("ping", Some(7), serde_json::json!({ "server": "busy" }));
+        let pending = {
+            let proxy = proxy.clone();
+            tokio::spawn(async move { proxy.handle_request_in("s1", ping).await })
+        };
+        while proxy.in_flight_calls.lock().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
+
+        let cancel = request("notifications/cancelled", None, serde_json::json!({ "requestId": 7 }));
+        assert!(proxy.handle_request_in("s1", cancel).await.is_none());
+        let response = tokio::time::timeout(Duration::from_secs(5), pending).await.expect("ping not cancelled").unwrap();
+        assert!(response.is_none());
+        assert!(proxy.in_flight_calls.lock().await.is_empty());
+        proxy.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_resources_listed_from_cache_for_stopped_servers() {
+        let mut config = ProxyConfig::default();
+        config.servers.insert("docs".into(), crate::config::ServerConfig {
+            command: "true".into(),
+            ..Default::default()
+        });
+        let proxy = ProxyServer::new(config);
+        let cached = crate::cache::parse_resource_list("docs", &serde_json::json!({
+            "resources": [{ "uri": "file:///readme.md", "name": "readme", "mimeType": "text/markdown" }]
+        }));
+        let mut resources = proxy.cached_resources.lock().await;
+        resources.insert("docs".into(), cached.clone());
+        // Left over from a server no longer configured
+        resources.insert("gone".into(), cached);
+        drop(resources);
+
+        let list = proxy.handle_resources_list(Some(serde_json::json!(1))).await;
+        assert_eq!(list.result.unwrap()["resources"], serde_json::json!([
+            { "uri": "docs__file:///readme.md", "name": "readme", "mimeType": "text/markdown" }
+        ]));
+        assert!(!proxy.child_manager.is_running("docs").await);
     }
 }
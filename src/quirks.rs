@@ -0,0 +1,148 @@
+/// Per-client compatibility profiles for SSE sessions.
+/// A profile is picked once on connect (from `X-McpHub-Client` or the
+/// User-Agent) and adjusts that session only, so client-specific shims
+/// live here instead of being scattered through the transport.
+use std::collections::HashMap;
+
+/// How idle SSE connections are kept alive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Keepalive {
+    /// `: keepalive` comment line (invisible to spec-compliant parsers).
+    Comment,
+    /// `event: ping` with an empty JSON payload, for clients that only
+    /// reset their read timeout on real events.
+    PingEvent,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct QuirkProfile {
+    pub name: &'static str,
+    /// Advertise the message endpoint as an absolute URL instead of a path.
+    pub absolute_endpoint: bool,
+    /// Strip `_meta.progressToken` from requests so backends don't send
+    /// progress the client can't handle.
+    pub strip_progress: bool,
+    pub keepalive: Keepalive,
+}
+
+/// Strict, spec-following behavior for unrecognized clients.
+pub const DEFAULT: QuirkProfile = QuirkProfile {
+    name: "default",
+    absolute_endpoint: false,
+    strip_progress: false,
+    keepalive: Keepalive::Comment,
+};
+
+/// Known clients: (profile, lowercase User-Agent substrings that identify it).
+const PROFILES: &[(QuirkProfile, &[&str])] = &[
+    (
+        QuirkProfile {
+            name: "cursor",
+            absolute_endpoint: false,
+            strip_progress: true,
+            keepalive: Keepalive::Comment,
+        },
+        &["cursor/"],
+    ),
+    (
+        QuirkProfile {
+            name: "vscode",
+            absolute_endpoint: false,
+            strip_progress: false,
+            keepalive: Keepalive::PingEvent,
+        },
+        &["vscode", "visual studio code"],
+    ),
+    (
+        QuirkProfile {
+            name: "mcp-remote",
+            absolute_endpoint: true,
+            strip_progress: false,
+            keepalive: Keepalive::PingEvent,
+        },
+        &["mcp-remote"],
+    ),
+];
+
+/// Pick the profile for a connecting client. An explicit `X-McpHub-Client`
+/// header naming a profile wins over User-Agent detection.
+/// `headers` keys are expected lowercase.
+pub fn detect(headers: &HashMap<String, String>) -> &'static QuirkProfile {
+    if let Some(name) = headers.get("x-mcphub-client") {
+        if let Some(profile) = by_name(name.trim()) {
+            return profile;
+        }
+    }
+    let agent = match headers.get("user-agent") {
+        Some(ua) => ua.to_lowercase(),
+        None => return &DEFAULT,
+    };
+    PROFILES
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|n| agent.contains(n)))
+        .map(|(profile, _)| profile)
+        .unwrap_or(&DEFAULT)
+}
+
+pub fn by_name(name: &str) -> Option<&'static QuirkProfile> {
+    if name.eq_ignore_ascii_case(DEFAULT.name) {
+        return Some(&DEFAULT);
+    }
+    PROFILES
+        .iter()
+        .map(|(profile, _)| profile)
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Remove `params._meta.progressToken` from a JSON-RPC request.
+pub fn strip_progress_token(request: &mut serde_json::Value) {
+    if let Some(meta) = request
+        .get_mut("params")
+        .and_then(|p| p.get_mut("_meta"))
+        .and_then(|m| m.as_object_mut())
+    {
+        meta.remove("progressToken");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_user_agent_selects_profile() {
+        let profile = detect(&headers(&[("user-agent", "Cursor/0.48.6 (darwin arm64)")]));
+        assert_eq!(profile.name, "cursor");
+        assert!(profile.strip_progress);
+
+        let profile = detect(&headers(&[("user-agent", "node mcp-remote/0.1.0")]));
+        assert_eq!(profile.name, "mcp-remote");
+        assert!(profile.absolute_endpoint);
+    }
+
+    #[test]
+    fn test_unknown_client_gets_default() {
+        assert_eq!(detect(&headers(&[("user-agent", "curl/8.4.0")])), &DEFAULT);
+        assert_eq!(detect(&headers(&[])), &DEFAULT);
+    }
+
+    #[test]
+    fn test_header_overrides_user_agent() {
+        let h = headers(&[("user-agent", "Cursor/0.48.6"), ("x-mcphub-client", "default")]);
+        assert_eq!(detect(&h).name, "default");
+        // Unknown override names fall back to detection
+        let h = headers(&[("user-agent", "Cursor/0.48.6"), ("x-mcphub-client", "nope")]);
+        assert_eq!(detect(&h).name, "cursor");
+    }
+
+    #[test]
+    fn test_strip_progress_token() {
+        let mut req = serde_json::json!({"params": {"name": "x", "_meta": {"progressToken": 7, "other": 1}}});
+        strip_progress_token(&mut req);
+        assert_eq!(req["params"]["_meta"], serde_json::json!({"other": 1}));
+    }
+}
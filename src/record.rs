@@ -0,0 +1,380 @@
+/// Session recordings for reproducing problems: every request a session
+/// sends and the response it got, one JSON line each, and `McpHub replay`
+/// to send them through the proxy again or answer a client from them.
+use crate::error;
+use crate::framing::{MessageReader, DEFAULT_MAX_MESSAGE_BYTES};
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse};
+use serde_json::Value;
+use std::collections::hash_map::Entry as MapEntry;
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWriteExt, BufReader};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordConfig {
+    pub enabled: bool,
+    /// Defaults to `~/.McpHub/recordings`.
+    pub dir: Option<PathBuf>,
+}
+
+pub struct Recorder {
+    dir: Option<PathBuf>,
+    /// Lines for the writer thread, started on the first one.
+    writer: Mutex<Option<(mpsc::Sender<Line>, std::thread::JoinHandle<()>)>>,
+}
+
+/// A session id and the recording line to append for it.
+type Line = (String, Value);
+
+/// A session's recording file is closed once it has had nothing to write
+/// for this long, so closed sessions don't keep files open; a session that
+/// speaks up again continues in a new file.
+const IDLE_CLOSE: Duration = Duration::from_secs(300);
+
+impl Recorder {
+    pub fn new(config: &RecordConfig) -> Self {
+        let dir = config.enabled.then(|| {
+            config.dir.clone().unwrap_or_else(|| crate::config::hub_dir().unwrap_or_default().join("recordings"))
+        });
+        Self { dir, writer: Mutex::new(None) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Queue a request and its response (None for notifications) for the
+    /// session's recording. The file I/O happens on a writer thread; its
+    /// failures are logged and otherwise ignored.
+    pub fn record(&self, session: &str, request: &Value, response: Option<&JsonRpcResponse>, duration: Duration) {
+        let Some(dir) = &self.dir else { return };
+        let line = serde_json::json!({
+            "ts": unix_ms(),
+            "session": session,
+            "request": request,
+            "response": response,
+            "duration_ms": duration.as_millis() as u64,
+        });
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let (lines, _) = writer.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            let dir = dir.clone();
+            (tx, std::thread::spawn(move || write_recordings(&dir, rx, IDLE_CLOSE)))
+        });
+        let _ = lines.send((session.to_string(), line));
+    }
+}
+
+impl Drop for Recorder {
+    /// Let the writer thread write out what is queued.
+    fn drop(&mut self) {
+        let writer = self.writer.get_mut().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((lines, thread)) = writer {
+            drop(lines);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Writer thread: one open file per session, each line flushed as it comes
+/// in, closed after `idle` without one. Ends when the recorder is dropped.
+fn write_recordings(dir: &Path, lines: mpsc::Receiver<Line>, idle: Duration) {
+    let mut files: HashMap<String, (PathBuf, BufWriter<std::fs::File>, Instant)> = HashMap::new();
+    loop {
+        match lines.recv_timeout(idle) {
+            Ok((session, line)) => {
+                let file = match files.entry(session) {
+                    MapEntry::Occupied(entry) => Ok(entry.into_mut()),
+                    MapEntry::Vacant(entry) => {
+                        let path = dir.join(file_name(entry.key(), line["ts"].as_u64().unwrap_or(0)));
+                        match open_recording(&path) {
+                            Ok(file) => Ok(entry.insert((path, BufWriter::new(file), Instant::now()))),
+                            Err(e) => Err((path, e)),
+                        }
+                    }
+                };
+                let written = file.and_then(|(path, file, last)| {
+                    *last = Instant::now();
+                    writeln!(file, "{}", line).and_then(|()| file.flush()).map_err(|e| (path.clone(), e))
+                });
+                if let Err((path, e)) = written {
+                    tracing::warn!(tag = "RECORD", "Failed to write recording {}: {}", path.display(), e);
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+        files.retain(|_, (_, _, last)| last.elapsed() < idle);
+    }
+}
+
+/// Recordings hold whatever the tools returned, so only the owner may read them.
+fn open_recording(path: &Path) -> std::io::Result<std::fs::File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn unix_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// `<session>-<ts>.jsonl`, with characters unsafe in file names replaced.
+fn file_name(session: &str, ts: u64) -> String {
+    let session: String = session
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}-{}.jsonl", session, ts)
+}
+
+/// Parse `settings.record`.
+pub fn parse_record(value: &Value) -> Result<RecordConfig, String> {
+    let mut config = RecordConfig::default();
+    if let Some(v) = value.get("enabled") {
+        config.enabled = v.as_bool().ok_or("enabled must be true or false")?;
+    }
+    if let Some(v) = value.get("dir") {
+        let dir = v.as_str().filter(|d| !d.is_empty()).ok_or("dir must be a directory path")?;
+        config.dir = Some(PathBuf::from(dir));
+    }
+    Ok(config)
+}
+
+/// One recorded request.
+pub struct Entry {
+    pub ts: u64,
+    pub request: Value,
+    /// None for notifications.
+    pub response: Option<Value>,
+}
+
+pub fn read_recording(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let line: Value = serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))?;
+        let request = line.get("request").filter(|r| r.get("method").is_some())
+            .ok_or(format!("{}:{}: no request", path.display(), i + 1))?;
+        entries.push(Entry {
+            ts: line["ts"].as_u64().unwrap_or(0),
+            request: request.clone(),
+            response: line.get("response").filter(|r| !r.is_null()).cloned(),
+        });
+    }
+    Ok(entries)
+}
+
+/// JSON pointer of the first place `a` and `b` differ, or None if equal.
+fn first_difference(a: &Value, b: &Value, path: &str) -> Option<String> {
+    match (a, b) {
+        (Value::Object(x), Value::Object(y)) => {
+            let mut keys: Vec<&String> = x.keys().chain(y.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|k| match (x.get(k), y.get(k)) {
+                (Some(v), Some(w)) => first_difference(v, w, &format!("{}/{}", path, k)),
+                _ => Some(format!("{}/{}", path, k)),
+            })
+        }
+        (Value::Array(x), Value::Array(y)) if x.len() == y.len() => {
+            x.iter().zip(y).enumerate().find_map(|(i, (v, w))| first_difference(v, w, &format!("{}/{}", path, i)))
+        }
+        _ if a == b => None,
+        _ => Some(if path.is_empty() { "/".to_string() } else { path.to_string() }),
+    }
+}
+
+/// Answers requests from a recording: the first unused entry with the same
+/// method and params (ignoring `_meta`), else the first with the same method.
+struct Mock {
+    entries: Vec<(Value, Value)>,
+}
+
+impl Mock {
+    fn new(entries: Vec<Entry>) -> Self {
+        Self {
+            entries: entries.into_iter().filter_map(|e| Some((e.request, e.response?))).collect(),
+        }
+    }
+
+    fn answer(&mut self, req: &JsonRpcRequest) -> Value {
+        let params = without_meta(&req.params);
+        let same_method = |r: &Value| r["method"].as_str() == Some(req.method.as_str());
+        let found = self.entries.iter().position(|(r, _)| same_method(r) && without_meta(&r["params"]) == params)
+            .or_else(|| self.entries.iter().position(|(r, _)| same_method(r)));
+        match found {
+            Some(i) => {
+                let (_, mut response) = self.entries.remove(i);
+                response["id"] = req.id.clone().unwrap_or(Value::Null);
+                response
+            }
+            None => serde_json::to_value(JsonRpcResponse::error(
+                req.id.clone(),
+                error::SERVER_ERROR,
+                format!("No recorded response left for {}", req.method),
+            )).unwrap_or_default(),
+        }
+    }
+}
+
+fn without_meta(params: &Value) -> Value {
+    let mut params = params.clone();
+    if let Some(obj) = params.as_object_mut() {
+        obj.remove("_meta");
+    }
+    params
+}
+
+/// `McpHub replay <file>`: send the recorded requests through a proxy
+/// built from the current config and report responses that differ. With
+/// `realtime`, the recorded gaps between requests are kept. With `mock`,
+/// serve stdio MCP from the recording instead.
+pub async fn run_replay(file: &str, mock: bool, realtime: bool) {
+    let entries = match read_recording(Path::new(file)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if mock {
+        serve_mock(Mock::new(entries)).await;
+        return;
+    }
+
+    let mut config = crate::config::auto_detect();
+    // Don't record the replay itself
+    config.record.enabled = false;
+    let proxy = crate::proxy::ProxyServer::new(config);
+    proxy.init().await;
+
+    println!("Replaying {} requests from {}", entries.len(), file);
+    let (mut initialized, mut differed) = (false, 0);
+    let mut last_ts = entries.first().map(|e| e.ts).unwrap_or(0);
+    for entry in &entries {
+        if realtime && entry.ts > last_ts {
+            tokio::time::sleep(Duration::from_millis(entry.ts - last_ts)).await;
+        }
+        last_ts = entry.ts;
+        let req: JsonRpcRequest = match serde_json::from_value(entry.request.clone()) {
+            Ok(req) => req,
+            Err(e) => {
+                println!("  ✗ invalid request: {}", e);
+                differed += 1;
+                continue;
+            }
+        };
+        let label = match req.params.get("name").and_then(|n| n.as_str()) {
+            Some(name) => format!("{} ({})", req.method, name),
+            None => req.method.clone(),
+        };
+        let handshake = proxy.handshake(&req, &mut initialized);
+        let response = proxy.handle_session_request("replay", req, handshake).await;
+        let got = response.map(|r| serde_json::to_value(r).unwrap_or_default());
+        let difference = match (&entry.response, &got) {
+            (Some(want), Some(got)) => first_difference(want, got, ""),
+            (None, None) => None,
+            _ => Some("/".to_string()),
+        };
+        match difference {
+            None => println!("  ✓ {}", label),
+            Some(at) => {
+                differed += 1;
+                println!("  ✗ {}: response differs at {}", label, at);
+                println!("      recorded: {}", entry.response.as_ref().map(|v| v.to_string()).unwrap_or("none".into()));
+                println!("      replayed: {}", got.as_ref().map(|v| v.to_string()).unwrap_or("none".into()));
+            }
+        }
+    }
+    proxy.shutdown().await;
+
+    println!();
+    println!("{} requests, {} matched, {} differed", entries.len(), entries.len() - differed, differed);
+    if differed > 0 {
+        std::process::exit(1);
+    }
+}
+
+async fn serve_mock(mut mock: Mock) {
+    let mut reader = MessageReader::new(BufReader::new(tokio::io::stdin()), DEFAULT_MAX_MESSAGE_BYTES, "stdin");
+    let mut stdout = tokio::io::stdout();
+    while let Ok(Some(message)) = reader.next_message().await {
+        let Ok(req) = serde_json::from_value::<JsonRpcRequest>(message) else { continue };
+        if req.id.is_none() {
+            continue;
+        }
+        let mut msg = mock.answer(&req).to_string();
+        msg.push('\n');
+        let _ = stdout.write_all(msg.as_bytes()).await;
+        let _ = stdout.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: u64, method: &str, params: Value) -> JsonRpcRequest {
+        serde_json::from_value(serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})).unwrap()
+    }
+
+    #[test]
+    fn test_record_then_mock_replay() {
+        let dir = std::env::temp_dir().join(format!("mcphub-record-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let recorder = Recorder::new(&parse_record(&serde_json::json!({"enabled": true, "dir": dir.to_str().unwrap()})).unwrap());
+        let call = |id: u64, q: &str| serde_json::json!({"jsonrpc": "2.0", "id": id, "method": "tools/call", "params": {"name": "search", "arguments": {"q": q}}});
+        let answer = |id: u64, text: &str| JsonRpcResponse::success(Some(id.into()), serde_json::json!({"content": [{"type": "text", "text": text}]}));
+        recorder.record("s/1", &call(1, "a"), Some(&answer(1, "first")), Duration::from_millis(3));
+        recorder.record("s/1", &serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"}), None, Duration::ZERO);
+        recorder.record("s/1", &call(2, "b"), Some(&answer(2, "second")), Duration::from_millis(4));
+        // Dropping the recorder waits for the writer thread
+        drop(recorder);
+
+        let files: Vec<PathBuf> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].file_name().unwrap().to_str().unwrap().starts_with("s_1-"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&files[0]).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let entries = read_recording(&files[0]).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(entries.len(), 3);
+        assert!(entries[1].response.is_none());
+
+        // Matched on params first, so out-of-order requests get their own answers
+        let mut mock = Mock::new(entries);
+        let second = mock.answer(&request(7, "tools/call", serde_json::json!({"name": "search", "arguments": {"q": "b"}, "_meta": {"progressToken": 1}})));
+        assert_eq!((second["id"].clone(), second["result"]["content"][0]["text"].clone()), (7.into(), "second".into()));
+        let first = mock.answer(&request(8, "tools/call", serde_json::json!({"name": "search", "arguments": {"q": "zzz"}})));
+        assert_eq!(first["result"]["content"][0]["text"], "first");
+        assert_eq!(mock.answer(&request(9, "tools/call", Value::Null))["error"]["code"], error::SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_first_difference() {
+        let a = serde_json::json!({"result": {"content": [{"text": "x"}], "isError": false}});
+        let b = serde_json::json!({"result": {"content": [{"text": "y"}], "isError": false}});
+        assert_eq!(first_difference(&a, &a, ""), None);
+        assert_eq!(first_difference(&a, &b, "").as_deref(), Some("/result/content/0/text"));
+        assert_eq!(first_difference(&a, &serde_json::json!({}), "").as_deref(), Some("/result"));
+        assert!(parse_record(&serde_json::json!({"enabled": "yes"})).is_err());
+    }
+}
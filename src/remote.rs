@@ -0,0 +1,449 @@
+//! Remote MCP servers over HTTP+SSE: the client side of `sse.rs`. A
+//! connection GETs the server's `url`, learns where to POST messages from
+//! its `endpoint` event, then POSTs each message there; answers and
+//! notifications come back as `message` events. To the child manager it
+//! looks like a process's stdio: lines written are POSTed, events are read
+//! back as lines. Plain `http://` only.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+/// Bytes buffered in each direction between the manager and the connection.
+const PIPE_BYTES: usize = 64 * 1024;
+
+/// Where a request goes: host, port and path with query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Target {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        if url.starts_with("https://") {
+            return Err(format!("{}: https is not supported, use an http:// url (e.g. through a local TLS proxy)", url));
+        }
+        let rest = url.strip_prefix("http://").ok_or_else(|| format!("{}: expected an http:// url", url))?;
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        let (host, port) = match authority.rsplit_once(':').filter(|(_, p)| !p.contains(']')) {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("{}: invalid port '{}'", url, port))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("{}: missing host", url));
+        }
+        Ok(Self { host: host.trim_start_matches('[').trim_end_matches(']').to_string(), port, path })
+    }
+
+    /// The `endpoint` event's url: absolute, or relative to this target. An
+    /// absolute url must stay on this host and port, since the configured
+    /// headers (often credentials) go with every POST.
+    fn join(&self, endpoint: &str) -> Result<Self, String> {
+        if endpoint.contains("://") {
+            let target = Self::parse(endpoint)?;
+            if !target.host.eq_ignore_ascii_case(&self.host) || target.port != self.port {
+                return Err(format!("{}: endpoint is on another origin than {}", endpoint, self.host_header()));
+            }
+            return Ok(target);
+        }
+        let path = if endpoint.starts_with('/') {
+            endpoint.to_string()
+        } else {
+            let dir = self.path.split('?').next().unwrap_or("/");
+            format!("{}{}", &dir[..dir.rfind('/').map_or(0, |i| i + 1)], endpoint)
+        };
+        Ok(Self { path, ..self.clone() })
+    }
+
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') { format!("[{}]", self.host) } else { self.host.clone() };
+        if self.port == 80 { host } else { format!("{}:{}", host, self.port) }
+    }
+
+    fn head(&self, method: &str, headers: &[(String, String)]) -> String {
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, self.path, self.host_header());
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head
+    }
+
+    async fn connect(&self) -> Result<TcpStream, String> {
+        TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| format!("Connect to {}:{} failed: {}", self.host, self.port, e))
+    }
+}
+
+/// A live connection to a remote server. Dropping it closes it.
+#[derive(Debug)]
+pub struct Connection {
+    events: JoinHandle<()>,
+    posts: JoinHandle<()>,
+    /// Why the connection ended, once it has.
+    closed: Arc<std::sync::Mutex<Option<String>>>,
+}
+
+impl Connection {
+    /// Why the connection is gone; None while it is up.
+    pub fn closed_reason(&self) -> Option<String> {
+        if !self.events.is_finished() {
+            return None;
+        }
+        let reason = self.closed.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Some(reason.unwrap_or_else(|| "connection closed".into()))
+    }
+
+    pub fn close(&self) {
+        self.events.abort();
+        self.posts.abort();
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Open the event stream at `url` and wait for its `endpoint` event.
+/// Returns the connection, the pipe to write messages into and the pipe
+/// events are read from. `headers` go with every request; `max_bytes`
+/// caps one event, `timeout` each POST.
+pub async fn connect(
+    url: &str,
+    headers: Vec<(String, String)>,
+    max_bytes: usize,
+    timeout: Duration,
+) -> Result<(Connection, DuplexStream, DuplexStream), String> {
+    let target = Target::parse(url)?;
+    let mut stream = target.connect().await?;
+    let head = target.head("GET", &headers) + "Accept: text/event-stream\r\nCache-Control: no-cache\r\n\r\n";
+    stream.write_all(head.as_bytes()).await.map_err(|e| format!("Write error: {}", e))?;
+
+    let mut reader = BufReader::new(stream);
+    let (status, response_headers) = read_head(&mut reader).await?;
+    if status != 200 {
+        return Err(format!("{} answered HTTP {}", url, status));
+    }
+    let chunked = response_headers.iter().any(|(k, v)| k == "transfer-encoding" && v.to_ascii_lowercase().contains("chunked"));
+    let mut body = Body { reader, chunked, left: 0, max_bytes };
+
+    let endpoint = loop {
+        match body.next_event().await.map_err(|e| format!("Read error: {}", e))? {
+            Some((event, data)) if event == "endpoint" => break target.join(data.trim())?,
+            Some(_) => continue,
+            None => return Err(format!("{} closed the event stream before sending its endpoint", url)),
+        }
+    };
+
+    let closed = Arc::new(std::sync::Mutex::new(None));
+    let (input, mut outgoing) = tokio::io::duplex(PIPE_BYTES);
+    let (mut incoming, output) = tokio::io::duplex(PIPE_BYTES);
+
+    let reason = closed.clone();
+    let events = tokio::spawn(async move {
+        let ended = loop {
+            match body.next_event().await {
+                Ok(Some((event, data))) if event == "message" => {
+                    if incoming.write_all(format!("{}\n", data).as_bytes()).await.is_err() {
+                        break "connection closed".to_string();
+                    }
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) => break "event stream ended".to_string(),
+                Err(e) => break format!("event stream failed: {}", e),
+            }
+        };
+        reason.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(ended);
+    });
+
+    let (reason, stop_events) = (closed.clone(), events.abort_handle());
+    let posts = tokio::spawn(async move {
+        let mut lines = BufReader::new(&mut outgoing);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match lines.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let sent = tokio::time::timeout(timeout, post(&endpoint, &headers, &line)).await
+                .unwrap_or_else(|_| Err(format!("POST to {} timed out", endpoint.path)));
+            if let Err(e) = sent {
+                // The answer will never come: end the stream so readers see the loss
+                reason.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(e);
+                stop_events.abort();
+                return;
+            }
+        }
+    });
+
+    Ok((Connection { events, posts, closed }, input, output))
+}
+
+/// POST one message; any 2xx is success (the answer comes as an event).
+async fn post(target: &Target, headers: &[(String, String)], message: &[u8]) -> Result<(), String> {
+    let mut stream = target.connect().await?;
+    let body = message.trim_ascii_end();
+    let head = target.head("POST", headers)
+        + &format!("Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+    stream.write_all(head.as_bytes()).await.map_err(|e| format!("Write error: {}", e))?;
+    stream.write_all(body).await.map_err(|e| format!("Write error: {}", e))?;
+    let mut reader = BufReader::new(stream);
+    let (status, _) = read_head(&mut reader).await?;
+    // Drain the (small) body so the server isn't cut off mid-write
+    let _ = reader.read_to_end(&mut Vec::new()).await;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("POST to {} answered HTTP {}", target.path, status))
+    }
+}
+
+/// Status code and lowercased headers of an HTTP response.
+async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<(u16, Vec<(String, String)>), String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.map_err(|e| format!("Read error: {}", e))?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| format!("Not an HTTP response: {:?}", line.trim_end()))?;
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await.map_err(|e| format!("Read error: {}", e))? == 0 {
+            return Err("Connection closed in the response head".into());
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            return Ok((status, headers));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+}
+
+/// The event stream's body, with chunked transfer encoding undone.
+struct Body<R> {
+    reader: R,
+    chunked: bool,
+    /// Bytes left in the current chunk.
+    left: usize,
+    max_bytes: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> Body<R> {
+    /// Next line without its line ending; None at the end of the body.
+    async fn next_line(&mut self) -> std::io::Result<Option<String>> {
+        let mut line = Vec::new();
+        loop {
+            if self.chunked && self.left == 0 {
+                let mut size = String::new();
+                if self.reader.read_line(&mut size).await? == 0 {
+                    return Ok(None);
+                }
+                // The CRLF closing the previous chunk
+                if size.trim().is_empty() {
+                    continue;
+                }
+                let hex = size.split(';').next().unwrap_or_default().trim();
+                self.left = usize::from_str_radix(hex, 16)
+                    .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("bad chunk size {:?}", hex)))?;
+                if self.left == 0 {
+                    return Ok(None);
+                }
+            }
+            let buf = self.reader.fill_buf().await?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            let available = if self.chunked { buf.len().min(self.left) } else { buf.len() };
+            let (take, done) = match buf[..available].iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (available, false),
+            };
+            line.extend_from_slice(&buf[..take]);
+            self.reader.consume(take);
+            if self.chunked {
+                self.left -= take;
+            }
+            if line.len() > self.max_bytes {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "event too large"));
+            }
+            if done {
+                let text = String::from_utf8_lossy(&line);
+                return Ok(Some(text.trim_end_matches(['\r', '\n']).to_string()));
+            }
+        }
+    }
+
+    /// Next event as (type, data); the type is `message` when unnamed.
+    async fn next_event(&mut self) -> std::io::Result<Option<(String, String)>> {
+        let mut event = String::new();
+        let mut data: Vec<String> = Vec::new();
+        while let Some(line) = self.next_line().await? {
+            if line.is_empty() {
+                if data.is_empty() {
+                    event.clear();
+                    continue;
+                }
+                let event = if event.is_empty() { "message".to_string() } else { event };
+                return Ok(Some((event, data.join("\n"))));
+            }
+            let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => event = value.to_string(),
+                "data" => data.push(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_target_parse_and_join() {
+        let target = Target::parse("http://example.com:8080/mcp/sse?x=1").unwrap();
+        assert_eq!(target, Target { host: "example.com".into(), port: 8080, path: "/mcp/sse?x=1".into() });
+        assert_eq!(target.join("/messages?sessionId=a").unwrap().path, "/messages?sessionId=a");
+        assert_eq!(target.join("messages?sessionId=a").unwrap().path, "/mcp/messages?sessionId=a");
+        assert_eq!(target.join("http://example.com:8080/m").unwrap().path, "/m");
+        assert!(target.join("http://other:9/m").unwrap_err().contains("another origin"));
+        assert!(target.join("http://example.com/m").is_err());
+        assert_eq!(Target::parse("http://[::1]/sse").unwrap().host_header(), "[::1]");
+        assert!(Target::parse("https://example.com/sse").unwrap_err().contains("https"));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_events() {
+        let raw: &[u8] = b"1a\r\nevent: endpoint\ndata: /m\n\n\r\n8\r\ndata: {\"\r\n7\r\na\":1}\n\n\r\n0\r\n\r\n";
+        let mut body = Body { reader: BufReader::new(raw), chunked: true, left: 0, max_bytes: 1024 };
+        assert_eq!(body.next_event().await.unwrap(), Some(("endpoint".into(), "/m".into())));
+        assert_eq!(body.next_event().await.unwrap(), Some(("message".into(), "{\"a\":1}".into())));
+        assert_eq!(body.next_event().await.unwrap(), None);
+    }
+
+    /// A remote MCP server with one `echo` tool that answers with its
+    /// session id. After answering a call with `hang_up` it drops that
+    /// session's stream. Each request is reported as `METHOD path auth`.
+    async fn fake_server() -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/sse", listener.local_addr().unwrap());
+        let (seen_tx, seen) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let streams: Arc<tokio::sync::Mutex<HashMap<String, mpsc::UnboundedSender<String>>>> = Default::default();
+            let mut next = 0;
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).await.unwrap();
+                let mut length = 0;
+                let mut auth = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(':').unwrap();
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => length = value.trim().parse().unwrap(),
+                        "authorization" => auth = value.trim().to_string(),
+                        _ => {}
+                    }
+                }
+                let _ = seen_tx.send(format!("{} {}", request_line.split_whitespace().take(2).collect::<Vec<_>>().join(" "), auth));
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let mut stream = reader.into_inner();
+                if request_line.starts_with("GET") {
+                    next += 1;
+                    let session = format!("s{}", next);
+                    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+                    streams.lock().await.insert(session.clone(), tx);
+                    tokio::spawn(async move {
+                        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n";
+                        let endpoint = format!("event: endpoint\ndata: /message?sessionId={}\n\n", session);
+                        stream.write_all(format!("{}{}", head, endpoint).as_bytes()).await.unwrap();
+                        while let Some(message) = rx.recv().await {
+                            if message == "hang up" {
+                                return;
+                            }
+                            let _ = stream.write_all(format!("event: message\ndata: {}\n\n", message).as_bytes()).await;
+                        }
+                    });
+                    continue;
+                }
+                let session = request_line.split("sessionId=").nth(1).unwrap().split_whitespace().next().unwrap().to_string();
+                let Some(events) = streams.lock().await.get(&session).cloned() else {
+                    let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await;
+                    continue;
+                };
+                let _ = stream.write_all(b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\n\r\n").await;
+                let message: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let result = match message["method"].as_str() {
+                    Some("initialize") => serde_json::json!({ "protocolVersion": "2024-11-05", "capabilities": {} }),
+                    Some("tools/list") => serde_json::json!({ "tools": [{ "name": "echo", "inputSchema": {} }] }),
+                    Some("tools/call") => serde_json::json!({ "content": [{ "type": "text", "text": session }] }),
+                    _ => continue,
+                };
+                let _ = events.send(serde_json::json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }).to_string());
+                if message["params"]["arguments"]["hang_up"] == true {
+                    let _ = events.send("hang up".into());
+                    streams.lock().await.remove(&session);
+                }
+            }
+        });
+        (url, seen)
+    }
+
+    #[tokio::test]
+    async fn test_remote_server_serves_calls_and_reconnects() {
+        let (url, mut seen) = fake_server().await;
+        let server = ServerConfig {
+            url: Some(url),
+            headers: HashMap::from([("Authorization".to_string(), "Bearer t0ken".to_string())]),
+            ..Default::default()
+        };
+        let m = crate::child::ChildManager::new(HashMap::from([("remote".to_string(), server)]), 60_000)
+            .with_timeouts(5, 5);
+        let tools = m.start_server("remote").await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(seen.recv().await.unwrap(), "GET /sse Bearer t0ken");
+
+        let text = |r: serde_json::Value| r["content"][0]["text"].as_str().unwrap().to_string();
+        let result = m.call_tool("remote", "echo", serde_json::json!({})).await.unwrap();
+        assert_eq!(text(result), "s1");
+
+        // The server drops the stream: the next call goes over a new connection
+        let result = m.call_tool("remote", "echo", serde_json::json!({ "hang_up": true })).await.unwrap();
+        assert_eq!(text(result), "s1");
+        let result = m.call_tool("remote", "echo", serde_json::json!({})).await.unwrap();
+        assert_eq!(text(result), "s2");
+        let requests: Vec<String> = std::iter::from_fn(|| seen.try_recv().ok()).collect();
+        assert_eq!(requests.iter().filter(|r| r.starts_with("GET")).count(), 1, "{:?}", requests);
+        m.stop_all().await;
+    }
+}
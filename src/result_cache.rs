@@ -0,0 +1,223 @@
+/// Cache of `tools/call` results for tools flagged cacheable, keyed by the
+/// call's arguments. An entry lives for its tool's TTL or until invalidated.
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultCacheConfig {
+    /// TTL of tools flagged with `true`.
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    /// TTL in seconds keyed by `server__tool` or by server name; 0 means
+    /// not cacheable, so one tool can opt out of a cacheable server.
+    pub tools: HashMap<String, u64>,
+    /// Also cache tools the server annotates `readOnlyHint: true`, for
+    /// `ttl_secs`, unless `tools` says otherwise.
+    pub read_only_tools: bool,
+}
+
+impl Default for ResultCacheConfig {
+    fn default() -> Self {
+        Self { ttl_secs: 60, max_entries: 256, tools: HashMap::new(), read_only_tools: false }
+    }
+}
+
+impl ResultCacheConfig {
+    /// How long a tool's results are kept; None if it isn't cacheable.
+    /// `read_only` is whether the tool is annotated read-only.
+    pub fn ttl_for(&self, server: &str, tool: &str, read_only: bool) -> Option<Duration> {
+        self.tools
+            .get(&format!("{}__{}", server, tool))
+            .or_else(|| self.tools.get(server))
+            .copied()
+            .or((self.read_only_tools && read_only).then_some(self.ttl_secs))
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs)
+    }
+}
+
+/// Parse `settings.resultCache`.
+pub fn parse_result_cache(value: &Value) -> Result<ResultCacheConfig, String> {
+    let mut config = ResultCacheConfig::default();
+    if let Some(v) = value.get("ttlSecs") {
+        config.ttl_secs = v.as_u64().ok_or("ttlSecs must be a number of seconds")?;
+    }
+    if let Some(v) = value.get("maxEntries") {
+        config.max_entries = v.as_u64().filter(|&n| n > 0).ok_or("maxEntries must be a positive number")? as usize;
+    }
+    if let Some(v) = value.get("readOnlyTools") {
+        config.read_only_tools = v.as_bool().ok_or("readOnlyTools must be true or false")?;
+    }
+    if let Some(tools) = value.get("tools") {
+        let tools = tools.as_object().ok_or("tools must be an object")?;
+        for (name, v) in tools {
+            let ttl = match v {
+                Value::Bool(true) => config.ttl_secs,
+                Value::Bool(false) => 0,
+                v => v.as_u64().ok_or(format!("tools.{}: expected true, false or a TTL in seconds", name))?,
+            };
+            config.tools.insert(name.clone(), ttl);
+        }
+    }
+    Ok(config)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ToolCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped because they expired or the cache was full.
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+struct Entry {
+    result: Value,
+    stored_at: Instant,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+pub struct ResultCache {
+    /// Keyed by `server__tool`, then by the serialized arguments (object
+    /// keys are sorted, so equal arguments give equal keys).
+    entries: HashMap<String, HashMap<String, Entry>>,
+    stats: HashMap<String, ToolCacheStats>,
+}
+
+impl ResultCache {
+    pub fn get(&mut self, tool: &str, arguments: &Value, now: Instant) -> Option<Value> {
+        let stats = self.stats.entry(tool.to_string()).or_default();
+        let key = arguments.to_string();
+        if let Some(results) = self.entries.get_mut(tool) {
+            match results.get(&key) {
+                Some(entry) if entry.expires_at > now => {
+                    stats.hits += 1;
+                    return Some(entry.result.clone());
+                }
+                Some(_) => {
+                    results.remove(&key);
+                    stats.evictions += 1;
+                }
+                None => {}
+            }
+        }
+        stats.misses += 1;
+        None
+    }
+
+    /// Store a result, making room by dropping expired entries and then the
+    /// oldest ones.
+    pub fn insert(&mut self, tool: &str, arguments: &Value, result: Value, ttl: Duration, max_entries: usize, now: Instant) {
+        let key = arguments.to_string();
+        if let Some(results) = self.entries.get_mut(tool) {
+            results.remove(&key);
+        }
+        for (name, results) in &mut self.entries {
+            let before = results.len();
+            results.retain(|_, e| e.expires_at > now);
+            self.stats.entry(name.clone()).or_default().evictions += (before - results.len()) as u64;
+        }
+        while self.len() >= max_entries.max(1) {
+            let oldest = self.entries.iter()
+                .flat_map(|(name, results)| results.iter().map(move |(key, e)| (e.stored_at, name, key)))
+                .min()
+                .map(|(_, name, key)| (name.clone(), key.clone()));
+            let Some((name, key)) = oldest else { break };
+            if let Some(results) = self.entries.get_mut(&name) {
+                results.remove(&key);
+            }
+            self.stats.entry(name).or_default().evictions += 1;
+        }
+        let entry = Entry { result, stored_at: now, expires_at: now + ttl };
+        self.entries.entry(tool.to_string()).or_default().insert(key, entry);
+    }
+
+    /// Drop the results of `tool` (`server__tool` or a server name), or of
+    /// every tool; returns how many were dropped.
+    pub fn invalidate(&mut self, tool: Option<&str>) -> usize {
+        let mut removed = 0;
+        self.entries.retain(|name, results| {
+            let matches = tool.is_none_or(|t| name == t || name.split_once("__").is_some_and(|(server, _)| server == t));
+            if matches {
+                removed += results.len();
+            }
+            !matches
+        });
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.values().map(HashMap::len).sum()
+    }
+
+    /// Counters per `server__tool`, with its current entry count.
+    pub fn stats(&self) -> HashMap<String, ToolCacheStats> {
+        let mut stats = self.stats.clone();
+        for (name, s) in &mut stats {
+            s.entries = self.entries.get(name).map_or(0, HashMap::len);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ttl_per_tool_and_server() {
+        let config = parse_result_cache(&json!({
+            "ttlSecs": 30,
+            "tools": { "docs": true, "docs__write": false, "github__search_code": 300 }
+        }))
+        .unwrap();
+        assert_eq!(config.ttl_for("docs", "read", false), Some(Duration::from_secs(30)));
+        assert_eq!(config.ttl_for("docs", "write", false), None);
+        assert_eq!(config.ttl_for("github", "search_code", false), Some(Duration::from_secs(300)));
+        assert_eq!(config.ttl_for("github", "create_issue", false), None);
+        // Annotations only count when opted in
+        assert_eq!(config.ttl_for("github", "list_issues", true), None);
+        assert!(parse_result_cache(&json!({ "tools": { "x": "yes" } })).is_err());
+    }
+
+    #[test]
+    fn test_read_only_annotated_tools() {
+        let config = parse_result_cache(&json!({
+            "ttlSecs": 20,
+            "readOnlyTools": true,
+            "tools": { "fs__read_secret": false, "fs__list_directory": 5 }
+        }))
+        .unwrap();
+        assert_eq!(config.ttl_for("fs", "read_file", true), Some(Duration::from_secs(20)));
+        assert_eq!(config.ttl_for("fs", "write_file", false), None);
+        // Explicit entries win over the annotation
+        assert_eq!(config.ttl_for("fs", "read_secret", true), None);
+        assert_eq!(config.ttl_for("fs", "list_directory", true), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_expiry_and_capacity_evict() {
+        let mut cache = ResultCache::default();
+        let now = Instant::now();
+        let ttl = Duration::from_secs(10);
+        // Argument order doesn't matter
+        cache.insert("a__t", &json!({ "x": 1, "y": 2 }), json!(1), ttl, 2, now);
+        assert_eq!(cache.get("a__t", &json!({ "y": 2, "x": 1 }), now), Some(json!(1)));
+        assert_eq!(cache.get("a__t", &json!({ "x": 1 }), now), None);
+
+        cache.insert("a__t", &json!({ "x": 2 }), json!(2), ttl, 2, now + Duration::from_secs(1));
+        cache.insert("b__t", &json!({}), json!(3), ttl, 2, now + Duration::from_secs(2));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a__t", &json!({ "x": 1, "y": 2 }), now), None);
+        assert_eq!(cache.get("b__t", &json!({}), now + ttl * 2), None);
+
+        let stats = cache.stats();
+        assert_eq!(stats["a__t"], ToolCacheStats { hits: 1, misses: 2, evictions: 1, entries: 1 });
+        assert_eq!(stats["b__t"], ToolCacheStats { hits: 0, misses: 1, evictions: 1, entries: 0 });
+        assert_eq!(cache.invalidate(Some("a")), 1);
+        assert_eq!(cache.len(), 0);
+    }
+}
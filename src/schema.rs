@@ -0,0 +1,239 @@
+/// Checking tool-call arguments against a tool's `inputSchema`, for
+/// `validateArguments`. Covers the JSON Schema keywords tool schemas use in
+/// practice: `type`, `properties`, `required`, `additionalProperties`,
+/// `items`, `enum`, `const`, numeric and length bounds, `allOf` / `anyOf` /
+/// `oneOf` / `not` and local `$ref`s. Other keywords (`pattern`, `format`,
+/// ...) aren't checked, so they never reject a call.
+use serde_json::Value;
+
+/// Failures listed per call; the rest are counted.
+const MAX_ERRORS: usize = 10;
+/// Nesting (through `$ref`s and combinators) past which a schema is not followed.
+const MAX_DEPTH: usize = 32;
+
+/// What is wrong with `arguments` under `schema`, one `path: problem` per
+/// failure; empty when they match.
+pub fn validate(schema: &Value, arguments: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, schema, arguments, "arguments", 0, &mut errors);
+    if errors.len() > MAX_ERRORS {
+        let more = errors.len() - MAX_ERRORS;
+        errors.truncate(MAX_ERRORS);
+        errors.push(format!("... and {} more", more));
+    }
+    errors
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        ty => type_name(value) == ty,
+    }
+}
+
+/// Target of a local `$ref` (`#` or `#/json/pointer`).
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        Some(root)
+    } else {
+        root.pointer(pointer)
+    }
+}
+
+fn matches(root: &Value, schema: &Value, value: &Value, depth: usize) -> bool {
+    let mut errors = Vec::new();
+    check(root, schema, value, "", depth, &mut errors);
+    errors.is_empty()
+}
+
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, depth: usize, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Object(schema) if depth <= MAX_DEPTH => schema,
+        Value::Bool(false) => return errors.push(format!("{}: not allowed", path)),
+        _ => return,
+    };
+    if let Some(target) = schema.get("$ref").and_then(|r| r.as_str()).and_then(|r| resolve(root, r)) {
+        check(root, target, value, path, depth + 1, errors);
+    }
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(ty)) => vec![ty.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(|t| t.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|ty| is_type(value, ty)) {
+        return errors.push(format!("{}: expected {}, got {}", path, types.join(" or "), type_name(value)));
+    }
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            errors.push(format!("{}: must be one of {}", path, Value::Array(options.clone())));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: must be {}", path, expected));
+        }
+    }
+
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            let bound = |key: &str| schema.get(key).and_then(|b| b.as_f64());
+            if let Some(min) = bound("minimum").filter(|&min| n < min) {
+                errors.push(format!("{}: must be at least {}", path, min));
+            }
+            if let Some(max) = bound("maximum").filter(|&max| n > max) {
+                errors.push(format!("{}: must be at most {}", path, max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|&min| n <= min) {
+                errors.push(format!("{}: must be greater than {}", path, min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|&max| n >= max) {
+                errors.push(format!("{}: must be less than {}", path, max));
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()).filter(|&min| len < min) {
+                errors.push(format!("{}: must be at least {} characters", path, min));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()).filter(|&max| len > max) {
+                errors.push(format!("{}: must be at most {} characters", path, max));
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()).filter(|&min| len < min) {
+                errors.push(format!("{}: must have at least {} items", path, min));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()).filter(|&max| len > max) {
+                errors.push(format!("{}: must have at most {} items", path, max));
+            }
+            match schema.get("items") {
+                Some(Value::Array(tuple)) => {
+                    for (i, (item, item_schema)) in items.iter().zip(tuple).enumerate() {
+                        check(root, item_schema, item, &format!("{}[{}]", path, i), depth + 1, errors);
+                    }
+                }
+                Some(item_schema) => {
+                    for (i, item) in items.iter().enumerate() {
+                        check(root, item_schema, item, &format!("{}[{}]", path, i), depth + 1, errors);
+                    }
+                }
+                None => {}
+            }
+        }
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for name in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten().filter_map(|n| n.as_str()) {
+                if !object.contains_key(name) {
+                    errors.push(format!("{}.{}: required property missing", path, name));
+                }
+            }
+            // Unlisted properties may be matched by patternProperties, which isn't checked
+            let additional = schema.get("additionalProperties").filter(|_| !schema.contains_key("patternProperties"));
+            for (name, property) in object {
+                let property_path = format!("{}.{}", path, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(property_schema) => check(root, property_schema, property, &property_path, depth + 1, errors),
+                    None => match additional {
+                        Some(Value::Bool(false)) => errors.push(format!("{}: unexpected property", property_path)),
+                        Some(additional) => check(root, additional, property, &property_path, depth + 1, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for sub in schema.get("allOf").and_then(|a| a.as_array()).into_iter().flatten() {
+        check(root, sub, value, path, depth + 1, errors);
+    }
+    if let Some(any) = schema.get("anyOf").and_then(|a| a.as_array()) {
+        if !any.iter().any(|sub| matches(root, sub, value, depth + 1)) {
+            errors.push(format!("{}: matches none of the anyOf schemas", path));
+        }
+    }
+    if let Some(one) = schema.get("oneOf").and_then(|o| o.as_array()) {
+        match one.iter().filter(|sub| matches(root, sub, value, depth + 1)).count() {
+            0 => errors.push(format!("{}: matches none of the oneOf schemas", path)),
+            1 => {}
+            _ => errors.push(format!("{}: matches more than one of the oneOf schemas", path)),
+        }
+    }
+    if let Some(not) = schema.get("not") {
+        if matches(root, not, value, depth + 1) {
+            errors.push(format!("{}: must not match the 'not' schema", path));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn search_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "minLength": 1},
+                "limit": {"type": "integer", "minimum": 1, "maximum": 100},
+                "sort": {"enum": ["stars", "updated"]},
+                "labels": {"type": "array", "items": {"$ref": "#/$defs/label"}}
+            },
+            "required": ["query"],
+            "additionalProperties": false,
+            "$defs": {"label": {"type": "string", "maxLength": 5}}
+        })
+    }
+
+    #[test]
+    fn test_matching_arguments_pass() {
+        let args = json!({"query": "mcp", "limit": 10.0, "sort": "stars", "labels": ["bug"]});
+        assert!(validate(&search_schema(), &args).is_empty());
+        // No schema, no constraints
+        assert!(validate(&json!({}), &json!({"anything": [1, 2]})).is_empty());
+    }
+
+    #[test]
+    fn test_failures_are_listed_with_paths() {
+        let args = json!({"limit": "ten", "sort": "name", "labels": ["bug", "wontfix"], "page": 2});
+        assert_eq!(
+            validate(&search_schema(), &args),
+            vec![
+                "arguments.query: required property missing",
+                "arguments.labels[1]: must be at most 5 characters",
+                "arguments.limit: expected integer, got string",
+                "arguments.page: unexpected property",
+                "arguments.sort: must be one of [\"stars\",\"updated\"]",
+            ]
+        );
+        assert_eq!(validate(&search_schema(), &json!([])), vec!["arguments: expected object, got array"]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({"properties": {"id": {"oneOf": [{"type": "integer"}, {"type": "number"}]}, "ref": {"anyOf": [{"type": "string"}, {"type": "null"}]}}});
+        assert_eq!(validate(&schema, &json!({"id": 1.5, "ref": null})), Vec::<String>::new());
+        assert_eq!(
+            validate(&schema, &json!({"id": 1, "ref": 2})),
+            vec!["arguments.id: matches more than one of the oneOf schemas", "arguments.ref: matches none of the anyOf schemas"]
+        );
+    }
+}
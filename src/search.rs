@@ -1,7 +1,6 @@
 /// Ultra-fast BM25 in-memory search engine for MCP tool discovery.
 /// Pure Rust, zero allocations during search (pre-computed at index time).
 /// Sub-microsecond search across hundreds of tools.
-
 use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
@@ -21,7 +20,6 @@ pub struct IndexedTool {
 
 struct DocEntry {
     tool_idx: usize,
-    terms: Vec<String>,
     tf: HashMap<String, f64>,
     length: f64,
 }
@@ -84,7 +82,6 @@ impl SearchEngine {
 
             self.docs.push(DocEntry {
                 tool_idx: idx,
-                terms,
                 tf,
                 length,
             });
@@ -101,8 +98,8 @@ impl SearchEngine {
         }
 
         let elapsed = start.elapsed();
-        eprintln!(
-            "[McpHub][INFO] Search index built: {} tools in {:.2}ms",
+        tracing::info!(
+            "Search index built: {} tools in {:.2}ms",
             self.tools.len(),
             elapsed.as_secs_f64() * 1000.0
         );
@@ -112,19 +109,25 @@ impl SearchEngine {
     /// Returns top-K results sorted by BM25 relevance.
     /// Typically <0.05ms for 200 tools in release mode.
     pub fn search(&self, query: &str, top_k: usize) -> Vec<&IndexedTool> {
+        self.search_where(query, top_k, |_| true)
+    }
+
+    /// `search` over only the tools `keep` accepts, so `top_k` counts
+    /// matches among those.
+    pub fn search_where(&self, query: &str, top_k: usize, keep: impl Fn(&IndexedTool) -> bool) -> Vec<&IndexedTool> {
         if self.docs.is_empty() {
             return Vec::new();
         }
 
         let query_terms = tokenize(&query.to_lowercase());
         if query_terms.is_empty() {
-            return self.tools.iter().take(top_k).collect();
+            return self.tools.iter().filter(|t| keep(t)).take(top_k).collect();
         }
 
         let mut scores: Vec<(f64, usize)> = Vec::with_capacity(self.docs.len());
         let query_lower = query.to_lowercase();
 
-        for doc in &self.docs {
+        for doc in self.docs.iter().filter(|d| keep(&self.tools[d.tool_idx])) {
             let mut score = 0.0_f64;
 
             for qt in &query_terms {
@@ -180,18 +183,121 @@ impl SearchEngine {
             .collect()
     }
 
-    /// Find a tool by prefixed name (exact match).
-    #[allow(dead_code)]
-    pub fn find_by_name(&self, prefixed_name: &str) -> Option<&IndexedTool> {
-        self.tools.iter().find(|t| t.name == prefixed_name)
-    }
-
     /// Find a tool by original name on a specific server.
     pub fn find_tool(&self, server: &str, tool: &str) -> Option<&IndexedTool> {
         self.tools
             .iter()
             .find(|t| t.server_name == server && t.original_name == tool)
     }
+
+    /// Replace the tools of one server and rebuild the index.
+    pub fn replace_server(&mut self, server: &str, tools: Vec<IndexedTool>) {
+        let mut all: Vec<IndexedTool> = self.tools.drain(..).filter(|t| t.server_name != server).collect();
+        all.extend(tools);
+        self.build_index(all);
+    }
+
+    /// Servers whose tools in `tools` differ from the indexed ones: tools
+    /// added, removed or with a new description or schema. Sorted.
+    pub fn changed_servers(&self, tools: &[IndexedTool]) -> Vec<String> {
+        fn by_server(tools: &[IndexedTool]) -> HashMap<&str, Vec<String>> {
+            let mut servers: HashMap<&str, Vec<String>> = HashMap::new();
+            for tool in tools {
+                let def = serde_json::to_string(&tool.tool_def).unwrap_or_default();
+                servers.entry(tool.server_name.as_str()).or_default().push(def);
+            }
+            servers.values_mut().for_each(|defs| defs.sort());
+            servers
+        }
+        let (old, new) = (by_server(&self.tools), by_server(tools));
+        let mut changed: Vec<String> = old
+            .keys()
+            .chain(new.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|server| old.get(*server) != new.get(*server))
+            .map(|server| server.to_string())
+            .collect();
+        changed.sort();
+        changed
+    }
+
+    /// All indexed tools of a server (server name matched case-insensitively).
+    pub fn tools_on_server(&self, server: &str) -> Vec<&IndexedTool> {
+        self.tools
+            .iter()
+            .filter(|t| t.server_name.eq_ignore_ascii_case(server))
+            .collect()
+    }
+
+    /// Closest indexed tools to a name that didn't resolve, by edit distance.
+    /// A `server__tool` name is compared against prefixed names, a bare name
+    /// against tool names (on `server` only, if given). Distant names are dropped.
+    pub fn suggest(&self, requested: &str, server: Option<&str>, limit: usize) -> Vec<&IndexedTool> {
+        let prefixed = requested.contains("__");
+        let requested_lower = requested.to_lowercase();
+        let max_distance = (requested.len() / 3).max(2);
+
+        let mut scored: Vec<(usize, &IndexedTool)> = self
+            .tools
+            .iter()
+            .filter(|t| server.is_none_or(|s| t.server_name.eq_ignore_ascii_case(s)))
+            .map(|t| {
+                let candidate = if prefixed { &t.name } else { &t.original_name };
+                (edit_distance(&requested_lower, &candidate.to_lowercase()), t)
+            })
+            .filter(|(d, _)| *d <= max_distance)
+            .collect();
+        scored.sort_by_key(|(d, _)| *d);
+        scored.into_iter().take(limit).map(|(_, t)| t).collect()
+    }
+}
+
+/// Fuzzy relevance of a tool to `query`, for ranking tools without an
+/// index. Each query term scores on its best word in the name (worth 3x)
+/// and in the description: exact, prefix, or for terms of 4+ chars one
+/// typo away. A name containing the whole query gets a bonus. 0 for no match.
+pub fn fuzzy_score(query: &str, name: &str, description: &str) -> f64 {
+    let terms = tokenize(query);
+    let name_words = tokenize(name);
+    let description_words = tokenize(description);
+    let mut score: f64 = terms
+        .iter()
+        .map(|term| 3.0 * word_match(term, &name_words) + word_match(term, &description_words))
+        .sum();
+    let (query, name) = (query.trim().to_lowercase(), name.to_lowercase());
+    if !query.is_empty() && name.contains(&query) {
+        score += if name == query { 10.0 } else { 5.0 };
+    }
+    score
+}
+
+/// How well `term` matches the closest of `words`, from 1.0 (equal) to 0.
+fn word_match(term: &str, words: &[String]) -> f64 {
+    words
+        .iter()
+        .map(|word| match word {
+            w if w == term => 1.0,
+            w if w.starts_with(term) => 0.75,
+            w if term.len() >= 4 && edit_distance(term, w) <= 1 => 0.5,
+            _ => 0.0,
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Levenshtein distance between two strings (by char).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -259,6 +365,7 @@ mod tests {
                 name: name.to_string(),
                 description: desc.to_string(),
                 input_schema: json!({"type": "object"}),
+                annotations: None,
             },
         }
     }
@@ -301,6 +408,57 @@ mod tests {
         assert_eq!(results2[0].original_name, "write_file");
     }
 
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn test_changed_servers() {
+        let mut engine = SearchEngine::new();
+        engine.build_index(vec![mock_tool("read_file", "Reads a file"), mock_tool("write_file", "Writes a file")]);
+        let same = vec![mock_tool("write_file", "Writes a file"), mock_tool("read_file", "Reads a file")];
+        assert!(engine.changed_servers(&same).is_empty());
+
+        let mut other = mock_tool("search", "Searches");
+        other.server_name = "other".into();
+        let changed = vec![mock_tool("read_file", "Reads a file from disk"), other];
+        assert_eq!(engine.changed_servers(&changed), ["other", "test"]);
+        assert_eq!(engine.changed_servers(&[]), ["test"]);
+    }
+
+    #[test]
+    fn test_suggest_near_miss() {
+        let mut engine = SearchEngine::new();
+        engine.build_index(vec![
+            mock_tool("read_file", "Reads a file"),
+            mock_tool("write_file", "Writes a file"),
+            mock_tool("list_directory", "Lists a directory"),
+        ]);
+
+        let results = engine.suggest("raed_file", Some("test"), 3);
+        assert_eq!(results[0].original_name, "read_file");
+
+        let results = engine.suggest("test__writefile", None, 3);
+        assert_eq!(results[0].name, "test__write_file");
+
+        assert!(engine.suggest("completely_unrelated", None, 3).is_empty());
+        assert!(engine.suggest("read_file", Some("other"), 3).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        let read = fuzzy_score("read fil", "read_file", "Reads a file from disk");
+        let write = fuzzy_score("read fil", "write_file", "Writes a file to disk");
+        assert!(read > write && write > 0.0, "{} {}", read, write);
+        // One typo still matches, a name hit outweighs a description hit
+        assert!(fuzzy_score("serch", "search_code", "") > fuzzy_score("serch", "grep", "Search for code"));
+        assert!(fuzzy_score("search", "grep", "Search for code") > 0.0);
+        assert_eq!(fuzzy_score("deploy", "read_file", "Reads a file"), 0.0);
+    }
+
     #[test]
     fn test_empty_search() {
         let mut engine = SearchEngine::new();
@@ -6,285 +6,3608 @@
 /// - TCP keepalive enabled to detect half-open connections
 /// - Session reaper cleans stale sessions every 60s
 /// - Write + flush errors both trigger session teardown
-
-use std::collections::HashMap;
+/// - Resource subscriptions are released when their session closes
+/// - With `sse.persistSessions`, session metadata survives a restart, and
+///   so do buffered events on a clean shutdown
+/// - Backend logs reach a session only after its `logging/setLevel`, and at
+///   most `sse.logs.maxPerSecond` of them; the excess is summarized
+///
+/// Ordering: every event for a session goes through one FIFO channel and is
+/// queued while holding the session map lock, so a client sees events in
+/// the order they were queued, responses and notifications alike. A
+/// response waiting for room in a full channel waits outside the lock and
+/// takes its place (and event id) once a slot is free.
+///
+/// Backpressure: a full channel makes a response wait up to the send
+/// timeout before /message answers 503; notifications are dropped.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
 
-use crate::protocol::JsonRpcRequest;
-use crate::proxy::ProxyServer;
+use crate::child::ClientRequest;
+use crate::config::{client_base_url, DuplicateClients, ProxyConfig, DEFAULT_BIND_ADDR};
+use crate::error;
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse, LogLevel};
+use crate::proxy::{Handshake, ProxyServer, Scope};
+use crate::quirks::{self, Keepalive, QuirkProfile};
 
 /// A single SSE client session.
 struct SseSession {
     tx: mpsc::Sender<String>,
     last_activity: Instant,
+    created_at: Instant,
+    /// Bytes sent into the channel but not yet written to the socket.
+    buffered_bytes: Arc<AtomicUsize>,
+    /// Client compatibility profile picked on connect.
+    profile: &'static QuirkProfile,
+    /// Stable id sent by the client on connect (`X-McpHub-Client-Id`), if any.
+    client_id: Option<String>,
+    /// Namespaced resource uris this session subscribed to.
+    subscriptions: HashSet<String>,
+    /// Id of the last event queued (SSE `id:` field); 0 before the first.
+    last_event_id: u64,
+    /// Most recent events with their ids, replayed on resume.
+    replay: VecDeque<(u64, String)>,
+    /// Set when the client's stream dropped; the session is kept for
+    /// RESUME_WINDOW_SECS so it can reconnect with `Last-Event-ID`.
+    detached_at: Option<Instant>,
+    /// Bumped on every (re)connect, so a stale stream's cleanup can't
+    /// detach the connection that replaced it.
+    connection: u64,
+    /// The stream was dropped because the client stopped reading it.
+    slow_consumer: bool,
+    logs: LogGate,
+    /// Exempt from the idle timeout (dashboards, monitors). Still closed
+    /// when its stream drops and isn't resumed, and on shutdown.
+    pinned: bool,
+    /// Set by the client's `initialize` (or one assumed for it).
+    initialized: bool,
+    /// The client declared the `sampling` capability in `initialize`.
+    sampling: bool,
+    /// `tools/call`s of this session not answered yet.
+    calls_in_flight: usize,
+    /// Fingerprint of the token the session connected with; None with auth off.
+    token: Option<u64>,
+    /// `sse.rateLimit` budget for the messages this session posts.
+    rate: TokenBucket,
+    /// Set while messages are refused, so the warning is logged once.
+    throttled: bool,
+    /// Servers the session may use (`?servers=` on connect); None = all.
+    scope: Option<Arc<Scope>>,
+    queue: QueueSize,
 }
 
-/// Manages all active SSE sessions.
-pub struct SseManager {
-    sessions: Arc<Mutex<HashMap<String, SseSession>>>,
-}
+/// How long a grown session queue has to sit empty before it shrinks.
+const QUEUE_SHRINK_AFTER: Duration = Duration::from_secs(60);
 
-/// Max time a session can be idle before reaper kills it (5 minutes).
-const SESSION_TIMEOUT_SECS: u64 = 300;
-/// Reaper interval.
-const REAPER_INTERVAL_SECS: u64 = 60;
-/// SSE keepalive interval.
-const KEEPALIVE_INTERVAL_SECS: u64 = 15;
+/// How many events a session may have queued. Its channel is created at
+/// `sse.maxChannelCapacity`; the limit starts at `sse.channelCapacity`,
+/// doubles (up to the channel's size) each time the queue fills up, and
+/// halves back once the queue has been empty for QUEUE_SHRINK_AFTER.
+/// Tokio allocates channel slots as they are used, so a large channel
+/// costs an idle session nothing.
+#[derive(Debug, Clone, Copy)]
+struct QueueSize {
+    base: usize,
+    limit: usize,
+    /// Most events queued at once.
+    high_water: usize,
+    /// When the limit last changed; None at its base.
+    resized_at: Option<Instant>,
+}
 
-impl SseManager {
-    pub fn new() -> Self {
-        let manager = Self {
-            sessions: Arc::new(Mutex::new(HashMap::new())),
-        };
-        // Start session reaper
-        let sessions_ref = manager.sessions.clone();
-        tokio::spawn(async move {
-            session_reaper(sessions_ref).await;
-        });
-        manager
+impl QueueSize {
+    fn new(base: usize) -> Self {
+        Self { base, limit: base, high_water: 0, resized_at: None }
     }
+}
 
-    /// Handle GET /sse — establish long-lived SSE connection.
-    /// Sends endpoint event, then streams responses until client disconnects.
-    pub async fn handle_connect(&self, mut stream: TcpStream) {
-        let session_id = generate_session_id();
+/// Per-session filter and rate limit for forwarded backend logs.
+#[derive(Debug)]
+struct LogGate {
+    /// Set by the client's `logging/setLevel`; no logs before that.
+    min_level: Option<LogLevel>,
+    window_start: Instant,
+    sent_in_window: u32,
+    /// Logs refused by the rate limit since the last summary.
+    dropped: u64,
+}
 
-        // Enable TCP keepalive to detect half-open connections.
-        // OS will send probes after idle; dead peers detected in ~30-75s.
-        configure_tcp_keepalive(&stream);
+/// Span over which `sse.logs.maxPerSecond` is counted.
+const LOG_WINDOW: Duration = Duration::from_secs(1);
 
-        // SSE response headers
-        let headers = "HTTP/1.1 200 OK\r\n\
-             Content-Type: text/event-stream\r\n\
-             Cache-Control: no-cache\r\n\
-             Connection: keep-alive\r\n\
-             Access-Control-Allow-Origin: *\r\n\
-             \r\n";
+/// How often a response waiting for room under `maxBufferedBytes` checks
+/// whether the stream has drained.
+const DRAIN_POLL: Duration = Duration::from_millis(10);
 
-        if stream.write_all(headers.as_bytes()).await.is_err() {
-            return;
+/// How long a backend's request waits for the client's response.
+const CLIENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Next backend request for a client; never ready without a receiver.
+async fn next_client_request(requests: &mut Option<mpsc::UnboundedReceiver<ClientRequest>>) -> Option<ClientRequest> {
+    match requests {
+        Some(requests) => requests.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+impl LogGate {
+    fn new() -> Self {
+        Self { min_level: None, window_start: Instant::now(), sent_in_window: 0, dropped: 0 }
+    }
+
+    /// Whether a log at `level` may be forwarded at `now`. `max_per_window`
+    /// of 0 means no cap.
+    fn admit(&mut self, level: LogLevel, floor: LogLevel, max_per_window: u32, now: Instant) -> bool {
+        let Some(min) = self.min_level else { return false };
+        if level < min.max(floor) {
+            return false;
+        }
+        if now.saturating_duration_since(self.window_start) >= LOG_WINDOW {
+            self.window_start = now;
+            self.sent_in_window = 0;
         }
+        if max_per_window == 0 || self.sent_in_window < max_per_window {
+            self.sent_in_window += 1;
+            true
+        } else {
+            self.dropped += 1;
+            false
+        }
+    }
 
-        // Send endpoint event — tells client where to POST messages
-        let endpoint_event = format!(
-            "event: endpoint\ndata: /message?sessionId={}\n\n",
-            session_id
-        );
-        if write_and_flush(&mut stream, endpoint_event.as_bytes()).await.is_err() {
-            return;
+    /// Number of logs dropped, once the window they were dropped in is over.
+    fn take_dropped(&mut self, now: Instant) -> Option<u64> {
+        if self.dropped == 0 || now.saturating_duration_since(self.window_start) < LOG_WINDOW {
+            return None;
         }
+        Some(std::mem::take(&mut self.dropped))
+    }
+}
 
-        eprintln!("[McpHub][SSE] Client connected: {}", session_id);
+/// Details of the GET /sse request that shape the session.
+pub struct ConnectInfo {
+    pub profile: &'static QuirkProfile,
+    /// Host header, used for absolute endpoint URLs.
+    pub host: Option<String>,
+    pub client_id: Option<String>,
+    /// Authorization header of the connect request.
+    pub authorization: Option<String>,
+    /// `sessionId` query parameter of a client resuming a dropped stream.
+    pub resume_session: Option<String>,
+    /// `Last-Event-ID` header: last event the client received.
+    pub last_event_id: Option<u64>,
+    /// `Origin` header, echoed in CORS headers if allowed.
+    pub origin: Option<String>,
+    /// `X-McpHub-Pin: true`: never reap the session for idleness.
+    pub pinned: bool,
+    /// Address the connect came from, for the per-IP connect limit.
+    pub peer: Option<IpAddr>,
+    /// `servers` query parameter, checked against the configured servers.
+    pub scope: Option<Scope>,
+}
 
-        // Create channel for this session (bounded: backpressure if client is slow)
-        let (tx, mut rx) = mpsc::channel::<String>(64);
+/// What a resumed stream gets before live events.
+enum Replay {
+    /// Missed events, oldest first.
+    Events(Vec<String>),
+    /// Missed events fell out of the buffer: the client must re-initialize.
+    Resync,
+}
 
-        {
-            let mut sessions = self.sessions.lock().await;
-            sessions.insert(
-                session_id.clone(),
-                SseSession {
-                    tx,
-                    last_activity: Instant::now(),
-                },
-            );
+/// Sent when missed events can't be replayed.
+const RESYNC_EVENT: &str = "event: resync\ndata: {\"reason\":\"missed events are no longer buffered, re-initialize\"}\n\n";
+/// Last event of a session closed for its age: reconnect after a second.
+const EXPIRED_EVENT: &str = "retry: 1000\nevent: expired\ndata: {\"reason\":\"maximum session lifetime reached, reconnect for a new session\"}\n\n";
+
+/// Keepalive cadence of an SSE stream. Once the client has sent nothing for
+/// `idle_after`, keepalives go out every `idle_interval` instead, so a dead
+/// peer behind an idle connection is noticed sooner. Client activity
+/// relaxes it back to `interval`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeepaliveConfig {
+    pub interval: Duration,
+    /// Zero disables escalation.
+    pub idle_after: Duration,
+    pub idle_interval: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            idle_after: Duration::from_secs(60),
+            idle_interval: Duration::from_secs(5),
         }
+    }
+}
 
-        // Stream events until disconnect.
-        // Keepalive every 15s to detect dead connections faster than TCP keepalive alone.
-        loop {
-            tokio::select! {
-                msg = rx.recv() => {
-                    match msg {
-                        Some(event) => {
-                            if write_and_flush(&mut stream, event.as_bytes()).await.is_err() {
-                                break;
-                            }
-                        }
-                        None => break, // Channel dropped (session reaped or server shutdown)
-                    }
-                }
-                _ = tokio::time::sleep(std::time::Duration::from_secs(KEEPALIVE_INTERVAL_SECS)) => {
-                    if write_and_flush(&mut stream, b": keepalive\n\n").await.is_err() {
-                        break;
-                    }
-                }
-            }
+impl KeepaliveConfig {
+    /// Wait before the next keepalive for a client idle for `idle`.
+    fn interval_for(&self, idle: Duration) -> Duration {
+        if !self.idle_after.is_zero() && idle >= self.idle_after {
+            self.idle_interval.min(self.interval)
+        } else {
+            self.interval
         }
+    }
+}
 
-        // Cleanup: remove session from map
-        {
-            let mut sessions = self.sessions.lock().await;
-            sessions.remove(&session_id);
+/// `sse.rateLimit`: token buckets for the messages a session posts and the
+/// streams a client IP opens. A rate of 0 turns that limit off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// JSON-RPC messages per second per session, on average.
+    pub messages_per_sec: f64,
+    /// Messages a session may post at once.
+    pub message_burst: u32,
+    /// `GET /sse` connects per second per client IP, on average.
+    pub connects_per_sec: f64,
+    /// Connects a client IP may make at once.
+    pub connect_burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self { messages_per_sec: 0.0, message_burst: 20, connects_per_sec: 0.0, connect_burst: 10 }
+    }
+}
+
+/// Refills at `rate` tokens a second up to `burst`.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket, whatever its burst turns out to be.
+    fn full() -> Self {
+        Self { tokens: f64::INFINITY, refilled_at: Instant::now() }
+    }
+
+    /// Take `cost` tokens at `now`, or say how long until there are enough.
+    /// A cost above the burst is charged as the whole burst, so a large
+    /// batch still gets through from a full bucket.
+    fn take(&mut self, cost: usize, rate: f64, burst: u32, now: Instant) -> Result<(), Duration> {
+        let burst = f64::from(burst.max(1));
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.refilled_at = now;
+        let cost = (cost as f64).min(burst);
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((cost - self.tokens) / rate))
         }
-        // Explicitly shutdown the socket
-        let _ = stream.shutdown().await;
-        eprintln!("[McpHub][SSE] Client disconnected: {}", session_id);
     }
 
-    /// Handle POST /message?sessionId=xxx — process JSON-RPC and send response via SSE.
-    /// Returns HTTP response bytes (202 Accepted or error).
-    pub async fn handle_message(
-        &self,
-        session_id: &str,
-        body: &str,
-        proxy: &Arc<ProxyServer>,
-    ) -> Vec<u8> {
-        // Parse JSON-RPC request
-        let request: JsonRpcRequest = match serde_json::from_str(body) {
-            Ok(r) => r,
-            Err(e) => {
-                return http_response(
-                    400,
-                    "Bad Request",
-                    &format!("{{\"error\":\"Invalid JSON-RPC: {}\"}}", e),
-                );
+    /// Whether the bucket is back to `burst` by `now`, i.e. forgettable.
+    fn is_full(&self, rate: f64, burst: u32, now: Instant) -> bool {
+        self.tokens + now.saturating_duration_since(self.refilled_at).as_secs_f64() * rate >= f64::from(burst.max(1))
+    }
+}
+
+/// What an SSE event carries; only matters with `sse.eventTypes: "split"`.
+#[derive(Debug, Clone, Copy)]
+enum EventKind {
+    Response,
+    Notification,
+    /// A request to the client, from a backend.
+    Request,
+    /// A piece of a streamed tool result.
+    Chunk,
+    /// An error standing in for a response that couldn't be produced.
+    Error,
+}
+
+/// Outcome of queueing an event on a session channel.
+#[derive(Debug, PartialEq)]
+enum Enqueue {
+    Sent,
+    /// Channel already holds its maximum number of messages.
+    CountFull,
+    /// Queued bytes would exceed the session byte budget.
+    BytesFull,
+    Closed,
+    /// Session's stream is down and late responses aren't buffered.
+    Late,
+}
+
+impl SseSession {
+    /// Queue an event, enforcing both the channel's message-count bound and
+    /// the optional byte budget. A single event larger than the budget is
+    /// still accepted when nothing else is queued, so it is never starved.
+    #[cfg(test)]
+    fn enqueue(&mut self, event: String, max_bytes: Option<usize>) -> Enqueue {
+        match self.try_reserve(event.len(), max_bytes) {
+            Ok(permit) => {
+                self.send_reserved(permit, event);
+                Enqueue::Sent
             }
-        };
+            Err(refused) => refused,
+        }
+    }
 
-        let has_id = request.id.is_some();
+    /// A channel slot for an event of `len` bytes, or why there is none.
+    fn try_reserve(&mut self, len: usize, max_bytes: Option<usize>) -> Result<mpsc::OwnedPermit<String>, Enqueue> {
+        if let Some(max) = max_bytes {
+            let buffered = self.buffered_bytes.load(Ordering::Relaxed);
+            if buffered > 0 && buffered + len > max {
+                return Err(Enqueue::BytesFull);
+            }
+        }
+        if self.queued() >= self.resize_queue() {
+            return Err(Enqueue::CountFull);
+        }
+        self.tx.clone().try_reserve_owned().map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => Enqueue::CountFull,
+            mpsc::error::TrySendError::Closed(_) => Enqueue::Closed,
+        })
+    }
 
-        // Process through proxy
-        let response = proxy.handle_request(request).await;
+    /// Events in the channel not yet written to the stream.
+    fn queued(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
 
-        // Send response through SSE stream
-        if let Some(resp) = response {
-            let json = match serde_json::to_string(&resp) {
-                Ok(j) => j,
-                Err(e) => {
-                    eprintln!("[McpHub][SSE] Serialize error: {}", e);
-                    return http_response(500, "Internal Server Error", "{\"error\":\"Serialize failed\"}");
-                }
-            };
+    /// Grow the queue limit if the queue is full, or shrink it if it has
+    /// been empty for a while (see `QueueSize`). Returns the limit.
+    fn resize_queue(&mut self) -> usize {
+        let (queued, cap, q) = (self.queued(), self.tx.max_capacity(), &mut self.queue);
+        if queued >= q.limit && q.limit < cap {
+            q.limit = (q.limit * 2).min(cap);
+            q.resized_at = Some(Instant::now());
+            tracing::debug!(tag = "SSE", "Session queue full, limit raised to {}", q.limit);
+        } else if queued == 0 && q.limit > q.base && q.resized_at.is_some_and(|t| t.elapsed() >= QUEUE_SHRINK_AFTER) {
+            q.limit = (q.limit / 2).max(q.base);
+            q.resized_at = (q.limit > q.base).then(Instant::now);
+        }
+        q.limit
+    }
 
-            let event = format!("event: message\ndata: {}\n\n", json);
+    fn send_reserved(&mut self, permit: mpsc::OwnedPermit<String>, event: String) {
+        self.buffered_bytes.fetch_add(event.len(), Ordering::Relaxed);
+        permit.send(event);
+        self.queue.high_water = self.queue.high_water.max(self.queued());
+    }
 
-            // Update last_activity and send via channel
-            let mut sessions = self.sessions.lock().await;
-            if let Some(session) = sessions.get_mut(session_id) {
-                session.last_activity = Instant::now();
-                // try_send: non-blocking, if channel full the client is too slow
-                match session.tx.try_send(event) {
-                    Ok(_) => {}
-                    Err(mpsc::error::TrySendError::Full(_)) => {
-                        eprintln!("[McpHub][SSE] Session {} channel full, dropping message", session_id);
-                        // Don't kill the session, just drop this message
-                    }
-                    Err(mpsc::error::TrySendError::Closed(_)) => {
-                        drop(sessions);
-                        // Session is dead, clean it up
-                        self.sessions.lock().await.remove(session_id);
-                        return http_response(410, "Gone", "{\"error\":\"Session closed\"}");
-                    }
-                }
-            } else {
-                return http_response(404, "Not Found", "{\"error\":\"Session not found\"}");
+    /// Give `event` the next id, keep it for replay and queue it. Events are
+    /// kept even if the queue refuses them, so a resuming client gets them,
+    /// except on CountFull: the caller may wait for room and `push_reserved`.
+    fn push_event(&mut self, event: &str, max_bytes: Option<usize>) -> Enqueue {
+        match self.try_reserve(event.len(), max_bytes) {
+            Ok(permit) => {
+                self.push_reserved(event, permit);
+                Enqueue::Sent
+            }
+            Err(Enqueue::CountFull) => Enqueue::CountFull,
+            Err(refused) => {
+                self.record_event(event);
+                refused
             }
         }
+    }
 
-        // Return 202 Accepted for requests, 200 for notifications
-        if has_id {
-            http_response(202, "Accepted", "{\"ok\":true}")
-        } else {
-            http_response(200, "OK", "{\"ok\":true}")
+    /// Queue `event` into a slot reserved on this session's channel.
+    fn push_reserved(&mut self, event: &str, permit: mpsc::OwnedPermit<String>) {
+        let event = self.record_event(event);
+        self.send_reserved(permit, event);
+    }
+
+    /// Give `event` the next id and keep it for replay.
+    fn record_event(&mut self, event: &str) -> String {
+        self.last_event_id += 1;
+        let event = format!("id: {}\n{}", self.last_event_id, event);
+        if self.replay.len() == REPLAY_BUFFER_EVENTS {
+            self.replay.pop_front();
         }
+        self.replay.push_back((self.last_event_id, event.clone()));
+        event
     }
 
-    /// Get active session count.
-    #[allow(dead_code)]
-    pub async fn session_count(&self) -> usize {
-        self.sessions.lock().await.len()
+    /// Events after `last`, or Resync if some of them are no longer buffered.
+    fn replay_after(&self, last: u64) -> Replay {
+        if last >= self.last_event_id {
+            return Replay::Events(Vec::new());
+        }
+        match self.replay.front() {
+            Some((oldest, _)) if *oldest <= last + 1 => Replay::Events(
+                self.replay.iter().filter(|(id, _)| *id > last).map(|(_, e)| e.clone()).collect(),
+            ),
+            _ => Replay::Resync,
+        }
     }
 }
 
-/// Extract sessionId from query string: /message?sessionId=xxx
-pub fn extract_session_id(path: &str) -> Option<String> {
-    let query = path.split('?').nth(1)?;
-    for param in query.split('&') {
-        if let Some(val) = param.strip_prefix("sessionId=") {
-            return Some(val.to_string());
+/// One session as listed by GET /api/sessions.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub client_id: Option<String>,
+    pub profile: &'static str,
+    pub age_secs: u64,
+    pub idle_secs: u64,
+    /// False while the stream is dropped and the session awaits a resume.
+    pub connected: bool,
+    pub pinned: bool,
+    pub subscriptions: usize,
+    /// Servers the session is limited to; None = all.
+    pub servers: Option<Vec<String>>,
+    /// Events waiting in the session's queue.
+    pub queued: usize,
+    /// How many events the queue may hold right now.
+    pub queue_limit: usize,
+    /// Most events the queue has held at once.
+    pub queue_high_water: usize,
+}
+
+/// Why a session ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CloseReason {
+    /// Removed by the reaper after the session timeout of inactivity.
+    Reaped,
+    /// Torn down by the server for any other reason.
+    ForceClosed,
+    /// Client stream dropped and wasn't resumed within RESUME_WINDOW_SECS.
+    Disconnected,
+    /// Replaced by a newer session with the same client id.
+    Superseded,
+    /// Open for longer than the maximum session lifetime.
+    Expired,
+    /// Opened with an auth token that has since been rotated.
+    Revoked,
+    /// Stream dropped because the client stopped reading it, and not
+    /// resumed within RESUME_WINDOW_SECS.
+    SlowConsumer,
+}
+
+/// Upper bounds (seconds) of the session lifetime histogram buckets.
+/// A final overflow bucket catches everything above the last bound.
+const LIFETIME_BUCKETS_SECS: [u64; 8] = [60, 300, 900, 1800, 3600, 7200, 14400, 28800];
+
+/// Session close counters and lifetime histogram, exposed via /api/metrics.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SessionStats {
+    pub reaped: u64,
+    pub force_closed: u64,
+    pub disconnected: u64,
+    pub superseded: u64,
+    pub expired: u64,
+    pub revoked: u64,
+    pub slow_consumer: u64,
+    pub lifetime_bucket_bounds_secs: Vec<u64>,
+    /// Per-bucket counts; one longer than the bounds (last is overflow).
+    pub lifetime_bucket_counts: Vec<u64>,
+    pub lifetime_sum_secs: u64,
+    /// Session ids regenerated because the generated id was already in use.
+    pub id_collision_retries: u64,
+    /// Connects answered 503 because `max_sessions` was reached.
+    pub rejected_at_capacity: u64,
+}
+
+impl SessionStats {
+    fn new() -> Self {
+        Self {
+            reaped: 0,
+            force_closed: 0,
+            disconnected: 0,
+            superseded: 0,
+            expired: 0,
+            revoked: 0,
+            slow_consumer: 0,
+            lifetime_bucket_bounds_secs: LIFETIME_BUCKETS_SECS.to_vec(),
+            lifetime_bucket_counts: vec![0; LIFETIME_BUCKETS_SECS.len() + 1],
+            lifetime_sum_secs: 0,
+            id_collision_retries: 0,
+            rejected_at_capacity: 0,
+        }
+    }
+
+    fn record(&mut self, reason: CloseReason, lifetime_secs: u64) {
+        match reason {
+            CloseReason::Reaped => self.reaped += 1,
+            CloseReason::ForceClosed => self.force_closed += 1,
+            CloseReason::Disconnected => self.disconnected += 1,
+            CloseReason::Superseded => self.superseded += 1,
+            CloseReason::Expired => self.expired += 1,
+            CloseReason::Revoked => self.revoked += 1,
+            CloseReason::SlowConsumer => self.slow_consumer += 1,
         }
+        let bucket = LIFETIME_BUCKETS_SECS
+            .iter()
+            .position(|&bound| lifetime_secs <= bound)
+            .unwrap_or(LIFETIME_BUCKETS_SECS.len());
+        self.lifetime_bucket_counts[bucket] += 1;
+        self.lifetime_sum_secs += lifetime_secs;
     }
-    None
 }
 
-/// Write bytes + flush. Returns Err if either fails.
-async fn write_and_flush(stream: &mut TcpStream, data: &[u8]) -> Result<(), ()> {
-    if stream.write_all(data).await.is_err() {
-        return Err(());
+/// Counters bumped on the message path, kept lock-free.
+#[derive(Debug, Default)]
+struct Counters {
+    sessions_created: AtomicU64,
+    messages_processed: AtomicU64,
+    /// Responses and notifications refused by a full session queue.
+    messages_dropped: AtomicU64,
+    /// Responses that finished after their session was gone (or detached,
+    /// without `bufferLateResponses`).
+    late_responses_dropped: AtomicU64,
+    /// Messages and connects refused with 429 by `sse.rateLimit`.
+    rate_limited: AtomicU64,
+    /// Connects whose endpoint event couldn't be delivered.
+    endpoint_failures: AtomicU64,
+    /// Streams dropped because the client stopped reading them.
+    slow_consumers: AtomicU64,
+}
+
+/// Snapshot served by GET /metrics.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SseMetrics {
+    pub active_sessions: usize,
+    pub max_sessions: usize,
+    pub sessions_created: u64,
+    pub sessions_reaped: u64,
+    pub messages_processed: u64,
+    pub messages_dropped: u64,
+    pub late_responses_dropped: u64,
+    pub rate_limited: u64,
+    pub endpoint_failures: u64,
+    pub slow_consumers: u64,
+    /// Queue sizing of each open session, for tuning `sse.channelCapacity`.
+    pub session_queues: Vec<SessionQueue>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SessionQueue {
+    pub session: String,
+    pub queued: usize,
+    pub limit: usize,
+    pub high_water: usize,
+}
+
+impl SseMetrics {
+    /// Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let metrics: [(&str, &str, &str, u64); 10] = [
+            ("mcphub_sessions_active", "gauge", "Open SSE sessions", self.active_sessions as u64),
+            ("mcphub_sse_sessions_max", "gauge", "SSE session limit", self.max_sessions as u64),
+            ("mcphub_sse_sessions_created_total", "counter", "SSE sessions opened", self.sessions_created),
+            ("mcphub_sse_sessions_reaped_total", "counter", "SSE sessions closed for inactivity", self.sessions_reaped),
+            ("mcphub_messages_total", "counter", "Messages posted to /message", self.messages_processed),
+            ("mcphub_messages_dropped_total", "counter", "Events dropped because a session queue was full", self.messages_dropped),
+            ("mcphub_sse_late_responses_dropped_total", "counter", "Responses dropped because their session was gone", self.late_responses_dropped),
+            ("mcphub_sse_rate_limited_total", "counter", "Messages and connects refused by the rate limit", self.rate_limited),
+            ("mcphub_sse_endpoint_failures_total", "counter", "SSE connects whose endpoint event couldn't be sent", self.endpoint_failures),
+            ("mcphub_sse_slow_consumers_total", "counter", "SSE streams dropped because the client stopped reading", self.slow_consumers),
+        ];
+        let mut text: String = metrics
+            .iter()
+            .map(|(name, kind, help, value)| format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"))
+            .collect();
+        type Gauge = fn(&SessionQueue) -> usize;
+        let queues: [(&str, &str, Gauge); 3] = [
+            ("mcphub_sse_session_queued", "Events waiting in the session's queue", |q| q.queued),
+            ("mcphub_sse_session_queue_limit", "Events the session's queue may hold now", |q| q.limit),
+            ("mcphub_sse_session_queue_high_water", "Most events the session's queue has held", |q| q.high_water),
+        ];
+        for (name, help, value) in queues {
+            text.push_str(&format!("# HELP {name} {help}\n# TYPE {name} gauge\n"));
+            for queue in &self.session_queues {
+                text.push_str(&format!("{}{{session=\"{}\"}} {}\n", name, queue.session, value(queue)));
+            }
+        }
+        text
     }
-    if stream.flush().await.is_err() {
-        return Err(());
+}
+
+/// Manages all active SSE sessions.
+pub struct SseManager {
+    sessions: Arc<Mutex<HashMap<String, SseSession>>>,
+    stats: Arc<Mutex<SessionStats>>,
+    counters: Counters,
+    /// New connects beyond this many sessions get 503 (resumes don't count).
+    max_sessions: usize,
+    max_buffered_bytes: Option<usize>,
+    /// Pending events per session before producers have to wait.
+    channel_capacity: usize,
+    /// What a session's queue limit can grow to (see `QueueSize`).
+    max_channel_capacity: usize,
+    /// How long a response waits for room in a full session channel.
+    send_timeout: Duration,
+    keepalive: KeepaliveConfig,
+    log_rate: u32,
+    log_floor: LogLevel,
+    /// Browser origins allowed to read the event stream (see `cors_headers`).
+    allowed_origins: Vec<String>,
+    duplicate_clients: DuplicateClients,
+    split_events: bool,
+    /// Largest event data sent; a bigger one is replaced by an error.
+    max_event_bytes: Option<usize>,
+    rate_limit: RateLimitConfig,
+    /// Connect budgets of client IPs seen recently; full ones are dropped.
+    connects: std::sync::Mutex<HashMap<IpAddr, TokenBucket>>,
+    /// Check the Bearer token on connect and on every message.
+    require_auth: bool,
+    auto_generate_token: bool,
+    /// Address the HTTP server is bound to, for absolute endpoint URLs and
+    /// the client config logged when a token is generated.
+    bind_addr: SocketAddr,
+    /// Subscriptions dropped by closed sessions or explicit unsubscribes.
+    released_tx: mpsc::UnboundedSender<Vec<String>>,
+    /// Taken by the notification router once it starts.
+    released_rx: Mutex<Option<mpsc::UnboundedReceiver<Vec<String>>>>,
+    /// Sessions idle longer than this are reaped (and not restored).
+    session_timeout: Duration,
+    /// Keep responses for a detached session to replay on resume.
+    buffer_late_responses: bool,
+    /// Progress tokens of in-flight `tools/call`s, keyed by the token sent
+    /// to the backend: the calling session and the client's own token.
+    progress_tokens: Mutex<HashMap<String, (String, serde_json::Value)>>,
+    /// Backend requests sent to a client, keyed by the (random) id they were
+    /// sent with: the session they went to and who waits for its response.
+    pending_client_requests: Mutex<HashMap<String, (String, oneshot::Sender<serde_json::Value>)>>,
+    /// Numbers hub-issued progress tokens.
+    next_hub_id: AtomicU64,
+    /// Where open sessions are persisted (`with_session_store`).
+    session_store: Option<PathBuf>,
+    /// Task writing the store periodically, stopped on shutdown.
+    store_writer: Option<JoinHandle<()>>,
+    store_writes: Arc<StoreWrites>,
+}
+
+/// Session metadata written to the session store, enough for a client
+/// to resume its session after a restart.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredSession {
+    id: String,
+    client_id: Option<String>,
+    profile: String,
+    subscriptions: Vec<String>,
+    last_event_id: u64,
+    log_level: Option<LogLevel>,
+    pinned: bool,
+    initialized: bool,
+    #[serde(default)]
+    sampling: bool,
+    #[serde(default)]
+    token: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    servers: Option<Vec<String>>,
+    /// Unix time (secs) of the client's last message.
+    last_active: u64,
+    /// Events kept for replay, only written on shutdown.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<(u64, String)>,
+}
+
+/// How often changed session metadata is written to the store.
+const PERSIST_INTERVAL_SECS: u64 = 5;
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn sorted(scope: &Scope) -> Vec<String> {
+    let mut servers: Vec<String> = scope.iter().cloned().collect();
+    servers.sort();
+    servers
+}
+
+fn sessions_path() -> Option<PathBuf> {
+    crate::config::hub_dir().map(|dir| dir.join("sessions.json"))
+}
+
+/// Snapshot of the metadata of every open session, ordered by id.
+fn stored_sessions(sessions: &HashMap<String, SseSession>, now: u64) -> Vec<StoredSession> {
+    let mut stored: Vec<StoredSession> = sessions
+        .iter()
+        .map(|(id, s)| {
+            let mut subscriptions: Vec<String> = s.subscriptions.iter().cloned().collect();
+            subscriptions.sort();
+            StoredSession {
+                id: id.clone(),
+                client_id: s.client_id.clone(),
+                profile: s.profile.name.to_string(),
+                subscriptions,
+                last_event_id: s.last_event_id,
+                log_level: s.logs.min_level,
+                pinned: s.pinned,
+                initialized: s.initialized,
+                sampling: s.sampling,
+                token: s.token,
+                servers: s.scope.as_deref().map(sorted),
+                last_active: now.saturating_sub(s.last_activity.elapsed().as_secs()),
+                events: Vec::new(),
+            }
+        })
+        .collect();
+    stored.sort_by(|a, b| a.id.cmp(&b.id));
+    stored
+}
+
+/// Rebuild a stored session, detached so it waits for the client to
+/// resume. Its stored events and those queued until then are kept for
+/// replay.
+fn restore_session(stored: StoredSession, now: u64) -> SseSession {
+    let (tx, _) = mpsc::channel(1);
+    let idle = Duration::from_secs(now.saturating_sub(stored.last_active));
+    let mut logs = LogGate::new();
+    logs.min_level = stored.log_level;
+    SseSession {
+        tx,
+        last_activity: Instant::now().checked_sub(idle).unwrap_or_else(Instant::now),
+        created_at: Instant::now(),
+        buffered_bytes: Arc::new(AtomicUsize::new(0)),
+        profile: quirks::by_name(&stored.profile).unwrap_or(&quirks::DEFAULT),
+        client_id: stored.client_id,
+        subscriptions: stored.subscriptions.into_iter().collect(),
+        last_event_id: stored.last_event_id,
+        replay: stored.events.into(),
+        detached_at: Some(Instant::now()),
+        connection: 0,
+        slow_consumer: false,
+        logs,
+        pinned: stored.pinned,
+        initialized: stored.initialized,
+        sampling: stored.sampling,
+        calls_in_flight: 0,
+        token: stored.token,
+        rate: TokenBucket::full(),
+        throttled: false,
+        scope: stored.servers.map(|servers| Arc::new(servers.into_iter().collect())),
+        // Sized on resume
+        queue: QueueSize::new(1),
     }
-    Ok(())
 }
 
-/// Configure TCP keepalive on the socket to detect dead peers.
-fn configure_tcp_keepalive(stream: &TcpStream) {
-    use std::time::Duration;
-    let sock_ref = socket2::SockRef::from(stream);
-    let mut ka = socket2::TcpKeepalive::new()
-        .with_time(Duration::from_secs(15))
-        .with_interval(Duration::from_secs(5));
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
+/// Write the store atomically, like the schema cache. It holds session
+/// ids and buffered tool results, so only the owner can read it.
+fn write_store(path: &std::path::Path, json: &str) -> Result<(), String> {
+    use std::io::Write as _;
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let tmp = path.with_extension("json.tmp");
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
     {
-        ka = ka.with_retries(3);
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
-    let _ = sock_ref.set_tcp_keepalive(&ka);
-    let _ = sock_ref.set_nodelay(true);
+    options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(json.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
 }
 
-/// Periodically reap stale sessions (no activity for SESSION_TIMEOUT_SECS).
-/// Dropping the sender half of the channel causes the SSE loop to break.
-async fn session_reaper(sessions: Arc<Mutex<HashMap<String, SseSession>>>) {
-    loop {
-        tokio::time::sleep(std::time::Duration::from_secs(REAPER_INTERVAL_SECS)).await;
-        let mut map = sessions.lock().await;
-        let stale: Vec<String> = map
-            .iter()
-            .filter(|(_, s)| s.last_activity.elapsed().as_secs() > SESSION_TIMEOUT_SECS)
-            .map(|(id, _)| id.clone())
-            .collect();
-        for id in &stale {
-            map.remove(id);
-            eprintln!("[McpHub][SSE] Reaped stale session: {}", id);
+/// Orders writes of the session store: snapshots are numbered when taken
+/// (under the sessions lock) and written after it is released, and one
+/// that reaches the file late never replaces a newer one.
+#[derive(Default)]
+struct StoreWrites {
+    taken: AtomicU64,
+    /// Number of the snapshot on disk.
+    written: std::sync::Mutex<u64>,
+}
+
+impl StoreWrites {
+    /// Number the snapshot being taken; call with the sessions lock held.
+    fn take(&self) -> u64 {
+        self.taken.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Write snapshot `number` unless a newer one is already on disk.
+    fn write(&self, path: &Path, json: &str, number: u64) -> Result<(), String> {
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if *written > number {
+            return Ok(());
         }
+        write_store(path, json)?;
+        *written = number;
+        Ok(())
     }
 }
 
-/// Generate a random session ID (no uuid crate needed).
-fn generate_session_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    let pid = std::process::id();
-    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    format!("{:x}-{:x}-{:x}", nanos, pid, count)
+/// Record a session's close and hand its subscriptions to the router.
+fn record_close(
+    stats: &mut SessionStats,
+    released: &mpsc::UnboundedSender<Vec<String>>,
+    session: SseSession,
+    reason: CloseReason,
+) {
+    stats.record(reason, session.created_at.elapsed().as_secs());
+    if !session.subscriptions.is_empty() {
+        let _ = released.send(session.subscriptions.into_iter().collect());
+    }
 }
 
-fn http_response(status: u16, status_text: &str, body: &str) -> Vec<u8> {
-    format!(
-        "HTTP/1.1 {} {}\r\n\
-         Content-Type: application/json\r\n\
-         Content-Length: {}\r\n\
-         Access-Control-Allow-Origin: *\r\n\
-         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
-         Access-Control-Allow-Headers: Content-Type\r\n\
-         Connection: close\r\n\
-         \r\n\
-         {}",
-        status,
-        status_text,
-        body.len(),
-        body
-    )
-    .into_bytes()
+/// Default max time a session can be idle before reaper kills it (5 minutes).
+pub const SESSION_TIMEOUT_SECS: u64 = 300;
+/// Default reaper interval.
+pub const REAPER_INTERVAL_SECS: u64 = 60;
+
+/// Most lines GET /logs/stream replays on connect.
+pub const MAX_LOG_BACKLOG: usize = 5000;
+
+/// How often GET /logs/stream checks the log for new lines.
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often the token file is checked for a rotation.
+const TOKEN_CHECK_SECS: u64 = 5;
+
+/// Default max age of a session, however active (a day).
+pub const MAX_SESSION_LIFETIME_SECS: u64 = 86_400;
+
+/// When idle or old sessions are closed, for SSE and Streamable HTTP alike.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReaperConfig {
+    pub session_timeout: Duration,
+    pub interval: Duration,
+    /// Sessions are closed this long after creation even if active; None
+    /// for no limit.
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        Self {
+            session_timeout: Duration::from_secs(SESSION_TIMEOUT_SECS),
+            interval: Duration::from_secs(REAPER_INTERVAL_SECS),
+            max_lifetime: Some(Duration::from_secs(MAX_SESSION_LIFETIME_SECS)),
+        }
+    }
+}
+/// How long a session whose stream dropped can be resumed.
+const RESUME_WINDOW_SECS: u64 = 60;
+/// Events kept per session for `Last-Event-ID` replay.
+const REPLAY_BUFFER_EVENTS: usize = 128;
+
+impl SseManager {
+    pub fn new(config: &ProxyConfig) -> Self {
+        let (released_tx, released_rx) = mpsc::unbounded_channel();
+        let manager = Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            stats: Arc::new(Mutex::new(SessionStats::new())),
+            counters: Counters::default(),
+            max_sessions: config.sse_max_sessions.max(1),
+            max_buffered_bytes: config.sse_max_buffered_bytes,
+            channel_capacity: config.sse_channel_capacity.max(1),
+            max_channel_capacity: config.sse_max_channel_capacity.max(config.sse_channel_capacity).max(1),
+            send_timeout: Duration::from_millis(config.sse_send_timeout_ms),
+            keepalive: config.sse_keepalive,
+            allowed_origins: config.cors_allowed_origins.clone(),
+            log_rate: config.sse_log_rate,
+            log_floor: config.sse_log_floor,
+            duplicate_clients: config.sse_duplicate_clients.clone(),
+            split_events: config.sse_split_events,
+            max_event_bytes: config.sse_max_event_bytes,
+            rate_limit: config.sse_rate_limit,
+            connects: std::sync::Mutex::new(HashMap::new()),
+            require_auth: config.auth_required,
+            auto_generate_token: config.auth_auto_generate_token,
+            bind_addr: DEFAULT_BIND_ADDR.parse().expect("valid default bind address"),
+            released_tx,
+            released_rx: Mutex::new(Some(released_rx)),
+            session_timeout: config.sse_reaper.session_timeout,
+            buffer_late_responses: config.sse_buffer_late_responses,
+            progress_tokens: Mutex::new(HashMap::new()),
+            pending_client_requests: Mutex::new(HashMap::new()),
+            next_hub_id: AtomicU64::new(0),
+            session_store: None,
+            store_writer: None,
+            store_writes: Arc::default(),
+        };
+        if !manager.require_auth {
+            tracing::warn!(tag = "AUTH", "Auth disabled (settings.auth.required = false): any local process can use /sse and /message");
+        }
+        // Start session reaper
+        let sessions_ref = manager.sessions.clone();
+        let stats_ref = manager.stats.clone();
+        let released_ref = manager.released_tx.clone();
+        let reaper = config.sse_reaper;
+        tokio::spawn(async move {
+            session_reaper(sessions_ref, stats_ref, released_ref, reaper).await;
+        });
+        if manager.require_auth {
+            let (sessions, stats, released) = (manager.sessions.clone(), manager.stats.clone(), manager.released_tx.clone());
+            let bind_addr = manager.bind_addr;
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(TOKEN_CHECK_SECS)).await;
+                    if let Ok(token) = crate::dashboard::load_auth_token(false, bind_addr) {
+                        revoke_sessions(&sessions, &stats, &released, token_fingerprint(&token)).await;
+                    }
+                }
+            });
+        }
+        match sessions_path().filter(|_| config.sse_persist_sessions) {
+            Some(path) => manager.with_session_store(path),
+            None => manager,
+        }
+    }
+
+    /// Restore the sessions kept at `path` and keep writing open sessions
+    /// there. Sessions idle past the session timeout are discarded, except
+    /// pinned ones; a restored session not resumed in time is reaped.
+    pub fn with_session_store(mut self, path: PathBuf) -> Self {
+        let now = unix_now();
+        let stored: Vec<StoredSession> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let timeout = self.session_timeout.as_secs();
+        let restored: Vec<StoredSession> = stored
+            .into_iter()
+            .filter(|s| s.pinned || now.saturating_sub(s.last_active) <= timeout)
+            .collect();
+        if let Ok(mut sessions) = self.sessions.try_lock() {
+            for stored in restored {
+                sessions.insert(stored.id.clone(), restore_session(stored, now));
+            }
+            if !sessions.is_empty() {
+                tracing::info!(tag = "SSE", "Restored {} session(s) from {}", sessions.len(), path.display());
+            }
+        }
+
+        self.session_store = Some(path.clone());
+        let (sessions, writes) = (self.sessions.clone(), self.store_writes.clone());
+        self.store_writer = Some(tokio::spawn(async move {
+            let mut written = String::new();
+            loop {
+                // Numbered under the lock, written outside it: the final
+                // write at shutdown is newer, so a late one here can't undo it
+                let (json, number) = {
+                    let sessions = sessions.lock().await;
+                    (serde_json::to_string_pretty(&stored_sessions(&sessions, unix_now())).unwrap_or_default(), writes.take())
+                };
+                if json != written {
+                    let (path, writes) = (path.clone(), writes.clone());
+                    let result = tokio::task::spawn_blocking(move || writes.write(&path, &json, number).map(|()| json)).await;
+                    match result {
+                        Ok(Ok(json)) => written = json,
+                        Ok(Err(e)) => tracing::warn!("Session store: {}", e),
+                        Err(e) => tracing::warn!("Session store: {}", e),
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(PERSIST_INTERVAL_SECS)).await;
+            }
+        }));
+        self
+    }
+
+    /// End every client stream and write the sessions to the store one last
+    /// time, with their buffered events, so clients can resume them after a
+    /// restart and replay what they missed.
+    pub async fn shutdown(&self) {
+        if let Some(writer) = &self.store_writer {
+            writer.abort();
+        }
+        let mut sessions = self.sessions.lock().await;
+        for session in sessions.values_mut() {
+            // Dropping the only sender ends the stream's loop
+            session.tx = mpsc::channel(1).0;
+            session.detached_at.get_or_insert_with(Instant::now);
+        }
+        if let Some(path) = &self.session_store {
+            let mut stored = stored_sessions(&sessions, unix_now());
+            for entry in &mut stored {
+                entry.events = sessions[&entry.id].replay.iter().cloned().collect();
+            }
+            let json = serde_json::to_string_pretty(&stored).unwrap_or_default();
+            if let Err(e) = self.store_writes.write(path, &json, self.store_writes.take()) {
+                tracing::warn!("Session store: {}", e);
+            }
+        }
+        tracing::info!(tag = "SSE", "Closed {} session stream(s)", sessions.len());
+    }
+
+    /// Address the HTTP server is bound to (DEFAULT_BIND_ADDR otherwise).
+    pub fn with_bind_addr(mut self, bind_addr: SocketAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// Route backend `notifications/resources/updated` to subscribed sessions
+    /// and unsubscribe backends from uris no session holds anymore.
+    /// Only the first call starts the router.
+    pub fn start_router(self: &Arc<Self>, proxy: Arc<ProxyServer>) {
+        let Some(mut released) = self.released_rx.try_lock().ok().and_then(|mut rx| rx.take()) else {
+            return;
+        };
+        let mut notifications = proxy.subscribe_notifications();
+        let mut broadcasts = proxy.subscribe_client_broadcasts();
+        // Taken before returning, so servers started from now on offer sampling
+        let mut client_requests = proxy.take_client_requests();
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.resubscribe_restored(&proxy).await;
+            let mut log_summaries = tokio::time::interval(LOG_WINDOW);
+            loop {
+                tokio::select! {
+                    msg = notifications.recv() => match msg {
+                        Ok((server, notification)) => manager.route_notification(&server, notification).await,
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            tracing::warn!("SSE router lagged, dropped {} backend notifications", n);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    Ok(notification) = broadcasts.recv() => manager.broadcast(&notification).await,
+                    Some(uris) = released.recv() => manager.release_subscriptions(&proxy, uris).await,
+                    Some(request) = next_client_request(&mut client_requests) => {
+                        let manager = manager.clone();
+                        tokio::spawn(async move { manager.forward_client_request(request).await });
+                    }
+                    _ = log_summaries.tick() => manager.flush_log_summaries().await,
+                }
+            }
+        });
+    }
+
+    /// Send a backend's request to the session whose call caused it, if its
+    /// client declared `sampling`, and pass the client's response back. The
+    /// wait ends early once the backend call stops waiting.
+    async fn forward_client_request(&self, client_request: ClientRequest) {
+        let ClientRequest { server, session, mut request, mut reply } = client_request;
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+        // Random, so a client can't guess the ids of requests sent to others
+        let id = format!("mcphub-{}", generate_session_id());
+        request["id"] = serde_json::json!(id);
+        let (tx, rx) = oneshot::channel();
+
+        let event = self.frame(EventKind::Request, &request.to_string());
+        let target = match &session {
+            Some(session_id) => {
+                let mut sessions = self.sessions.lock().await;
+                match sessions.get_mut(session_id).filter(|s| s.sampling && s.detached_at.is_none()) {
+                    Some(s) => {
+                        self.pending_client_requests.lock().await.insert(id.clone(), (session_id.clone(), tx));
+                        let queued = self.count_drop(s.push_event(&event, self.max_buffered_bytes));
+                        (queued == Enqueue::Sent).then(|| session_id.clone())
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        let response = match &target {
+            Some(session_id) => tokio::select! {
+                answered = tokio::time::timeout(CLIENT_REQUEST_TIMEOUT, rx) => match answered {
+                    Ok(Ok(response)) => Ok(response),
+                    _ => Err(format!("Client did not answer {} within {}s", method, CLIENT_REQUEST_TIMEOUT.as_secs())),
+                },
+                _ = reply.closed() => Err(format!("{} from {} is no longer awaited", method, server)),
+            }
+            .inspect_err(|e| tracing::info!(tag = "SSE", "Session {}: {} (from {})", session_id, e, server)),
+            None => Err(format!("The calling session doesn't support {}", method)),
+        };
+        self.pending_client_requests.lock().await.remove(&id);
+        let _ = reply.send(response.unwrap_or_else(|message| serde_json::json!({ "error": { "code": error::INTERNAL_ERROR, "message": message } })));
+    }
+
+    /// Hand a client's response to the backend request it answers. Only
+    /// the session the request was sent to can answer it. Returns false if
+    /// no request of `session_id` is waiting for it.
+    async fn deliver_client_response(&self, session_id: &str, response: serde_json::Value) -> bool {
+        let Some(id) = response.get("id").and_then(|id| id.as_str()) else {
+            return false;
+        };
+        let mut pending = self.pending_client_requests.lock().await;
+        if pending.get(id).is_none_or(|(session, _)| session != session_id) {
+            return false;
+        }
+        match pending.remove(id) {
+            Some((_, waiting)) => waiting.send(response).is_ok(),
+            None => false,
+        }
+    }
+
+    async fn route_notification(&self, server: &str, notification: serde_json::Value) {
+        match notification.get("method").and_then(|m| m.as_str()) {
+            Some("notifications/resources/updated") => self.route_resource_update(server, notification).await,
+            Some("notifications/message") => self.route_log(server, notification).await,
+            Some("notifications/progress") => self.route_progress(notification).await,
+            Some("notifications/tools/list_changed") => self.broadcast(&notification).await,
+            _ => {}
+        }
+    }
+
+    /// Queue a notification for every session; a detached session gets it
+    /// on resume. A session with a full queue misses it.
+    pub async fn broadcast(&self, notification: &serde_json::Value) {
+        let event = self.frame(EventKind::Notification, &notification.to_string());
+        let mut sessions = self.sessions.lock().await;
+        for session in sessions.values_mut() {
+            self.count_drop(session.push_event(&event, self.max_buffered_bytes));
+        }
+    }
+
+    async fn route_resource_update(&self, server: &str, mut notification: serde_json::Value) {
+        let Some(uri) = notification.pointer("/params/uri").and_then(|u| u.as_str()) else {
+            return;
+        };
+        let namespaced = format!("{}__{}", server, uri);
+        notification["params"]["uri"] = serde_json::json!(namespaced);
+        let event = self.frame(EventKind::Notification, &notification.to_string());
+
+        let mut sessions = self.sessions.lock().await;
+        for (id, session) in sessions.iter_mut().filter(|(_, s)| s.subscriptions.contains(&namespaced)) {
+            let queued = self.count_drop(session.push_event(&event, self.max_buffered_bytes));
+            if queued != Enqueue::Sent && session.detached_at.is_none() {
+                tracing::warn!(tag = "SSE", "Session {} not accepting updates, dropped {}", id, namespaced);
+            }
+        }
+    }
+
+    /// Forward progress of an in-flight call to the session that made it,
+    /// with the client's own token. Progress after the response is dropped.
+    async fn route_progress(&self, mut notification: serde_json::Value) {
+        let Some(token) = notification.pointer("/params/progressToken").and_then(|t| t.as_str()) else {
+            return;
+        };
+        let Some((session_id, client_token)) = self.progress_tokens.lock().await.get(token).cloned() else {
+            return;
+        };
+        notification["params"]["progressToken"] = client_token;
+        let event = self.frame(EventKind::Notification, &notification.to_string());
+        if let Some(session) = self.sessions.lock().await.get_mut(&session_id) {
+            self.count_drop(session.push_event(&event, self.max_buffered_bytes));
+        }
+    }
+
+    /// Forward a backend log to sessions that asked for its level, within
+    /// each session's rate limit. The logger is prefixed with the server.
+    async fn route_log(&self, server: &str, mut notification: serde_json::Value) {
+        let Some(level) = notification.pointer("/params/level").and_then(|l| l.as_str()).and_then(LogLevel::parse) else {
+            return;
+        };
+        let logger = match notification.pointer("/params/logger").and_then(|l| l.as_str()) {
+            Some(logger) => format!("{}/{}", server, logger),
+            None => server.to_string(),
+        };
+        notification["params"]["logger"] = serde_json::json!(logger);
+        let event = self.frame(EventKind::Notification, &notification.to_string());
+
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().await;
+        for session in sessions.values_mut() {
+            if let Some(dropped) = session.logs.take_dropped(now) {
+                self.count_drop(session.push_event(&self.log_summary(dropped), self.max_buffered_bytes));
+            }
+            if session.logs.admit(level, self.log_floor, self.log_rate, now) {
+                self.count_drop(session.push_event(&event, self.max_buffered_bytes));
+            }
+        }
+    }
+
+    /// Tell sessions how many logs the rate limit dropped, once the flood
+    /// window is over (the next log may be a long time coming).
+    async fn flush_log_summaries(&self) {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().await;
+        for session in sessions.values_mut() {
+            if let Some(dropped) = session.logs.take_dropped(now) {
+                self.count_drop(session.push_event(&self.log_summary(dropped), self.max_buffered_bytes));
+            }
+        }
+    }
+
+    fn log_summary(&self, dropped: u64) -> String {
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/message",
+            "params": {
+                "level": "warning",
+                "logger": "mcphub",
+                "data": format!("Dropped {} backend log messages (over {} per second)", dropped, self.log_rate),
+            }
+        });
+        self.frame(EventKind::Notification, &notification.to_string())
+    }
+
+    /// Subscribe backends again to the uris held by sessions restored from
+    /// the session store. A uri that can't be subscribed is dropped.
+    async fn resubscribe_restored(&self, proxy: &ProxyServer) {
+        let mut uris: Vec<String> = self.sessions.lock().await
+            .values()
+            .flat_map(|s| s.subscriptions.iter().cloned())
+            .collect();
+        uris.sort();
+        uris.dedup();
+        for uri in uris {
+            let request = JsonRpcRequest {
+                jsonrpc: "2.0".into(),
+                id: Some(serde_json::json!(0)),
+                method: "resources/subscribe".into(),
+                params: serde_json::json!({ "uri": uri }),
+            };
+            let failed = proxy.handle_request(request).await.and_then(|r| r.error).map(|e| e.message);
+            if let Some(error) = failed {
+                tracing::warn!("Could not restore subscription to {}: {}", uri, error);
+                for session in self.sessions.lock().await.values_mut() {
+                    session.subscriptions.remove(&uri);
+                }
+            }
+        }
+    }
+
+    /// Unsubscribe the backend from each uri no remaining session holds.
+    async fn release_subscriptions(&self, proxy: &ProxyServer, uris: Vec<String>) {
+        for uri in uris {
+            let held = self.sessions.lock().await.values().any(|s| s.subscriptions.contains(&uri));
+            if held {
+                continue;
+            }
+            match proxy.unsubscribe_resource(&uri).await {
+                Ok(()) => tracing::info!(tag = "SSE", "Unsubscribed {} (no sessions left)", uri),
+                Err(e) => tracing::warn!("Failed to unsubscribe {}: {}", uri, e),
+            }
+        }
+    }
+
+    /// Handle GET /sse — establish long-lived SSE connection.
+    /// Sends endpoint event, then streams responses until client disconnects.
+    pub async fn handle_connect(&self, mut stream: TcpStream, info: ConnectInfo) {
+        if !self.is_authorized(info.authorization.as_deref()) {
+            let _ = stream.write_all(&unauthorized()).await;
+            let _ = stream.shutdown().await;
+            return;
+        }
+        if let Some(Err(wait)) = info.peer.map(|peer| self.admit_connect(peer)) {
+            let _ = stream.write_all(&too_many_requests(wait)).await;
+            let _ = stream.shutdown().await;
+            return;
+        }
+        // Create channel for this session (bounded: backpressure if client is slow)
+        let (tx, mut rx) = mpsc::channel::<String>(self.max_channel_capacity);
+        let buffered_bytes = Arc::new(AtomicUsize::new(0));
+        let resumed = match &info.resume_session {
+            Some(id) => self.resume(id, &tx, &buffered_bytes, info.last_event_id).await,
+            None => None,
+        };
+        let (session_id, profile, connection, replay) = match resumed {
+            Some((profile, connection, replay)) => (info.resume_session.clone().unwrap_or_default(), profile, connection, replay),
+            None => {
+                let session = SseSession {
+                    tx,
+                    last_activity: Instant::now(),
+                    created_at: Instant::now(),
+                    buffered_bytes: buffered_bytes.clone(),
+                    profile: info.profile,
+                    client_id: info.client_id,
+                    subscriptions: HashSet::new(),
+                    last_event_id: 0,
+                    replay: VecDeque::new(),
+                    detached_at: None,
+                    connection: 0,
+                    slow_consumer: false,
+                    logs: LogGate::new(),
+                    pinned: info.pinned,
+                    initialized: false,
+                    sampling: false,
+                    calls_in_flight: 0,
+                    token: self.current_token(),
+                    rate: TokenBucket::full(),
+                    throttled: false,
+                    scope: info.scope.map(Arc::new),
+                    queue: QueueSize::new(self.channel_capacity),
+                };
+                // Register before announcing the endpoint, so the id is known to be unique.
+                let Some(session_id) = self.register(session).await else {
+                    let _ = stream.write_all(&http_response(503, "Service Unavailable", "{\"error\":\"Too many SSE sessions\"}")).await;
+                    let _ = stream.shutdown().await;
+                    return;
+                };
+                (session_id, info.profile, 0, Replay::Events(Vec::new()))
+            }
+        };
+
+        // Enable TCP keepalive to detect half-open connections.
+        // OS will send probes after idle; dead peers detected in ~30-75s.
+        configure_tcp_keepalive(&stream);
+
+        // SSE response headers
+        let headers = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/event-stream\r\n\
+             Cache-Control: no-cache\r\n\
+             Connection: keep-alive\r\n\
+             {}\r\n",
+            crate::dashboard::cors_headers(&self.allowed_origins, info.origin.as_deref(), false)
+        );
+
+        if stream.write_all(headers.as_bytes()).await.is_err() {
+            self.detach(&session_id, connection).await;
+            return;
+        }
+
+        // Send endpoint event — tells client where to POST messages
+        let endpoint = if profile.absolute_endpoint {
+            let base = match info.host.as_deref() {
+                Some(host) => format!("http://{}", host),
+                None => client_base_url(self.bind_addr),
+            };
+            format!("{}/message?sessionId={}", base, session_id)
+        } else {
+            format!("/message?sessionId={}", session_id)
+        };
+        let endpoint_event = format!("event: endpoint\ndata: {}\n\n", endpoint);
+        if let Err(e) = send_endpoint(&mut stream, endpoint_event.as_bytes()).await {
+            // Unlike a disconnect later on, the client can't POST or resume
+            tracing::warn!(tag = "SSE", "Client never got its endpoint: {} ({})", session_id, e);
+            self.counters.endpoint_failures.fetch_add(1, Ordering::Relaxed);
+            if connection > 0 {
+                self.detach(&session_id, connection).await;
+            } else if let Some(session) = self.sessions.lock().await.remove(&session_id) {
+                record_close(&mut *self.stats.lock().await, &self.released_tx, session, CloseReason::ForceClosed);
+            }
+            let _ = stream.shutdown().await;
+            return;
+        }
+
+        // A resumed stream first gets what it missed, then live events
+        let missed = match replay {
+            Replay::Events(events) => events.concat(),
+            Replay::Resync => RESYNC_EVENT.to_string(),
+        };
+        let mut ended = None;
+        if !missed.is_empty() {
+            ended = write_and_flush(&mut stream, missed.as_bytes()).await.err();
+        }
+
+        if connection > 0 {
+            tracing::info!(tag = "SSE", "Client resumed: {} (after event {:?})", session_id, info.last_event_id);
+        } else {
+            tracing::info!(tag = "SSE", "Client connected: {} (profile: {})", session_id, profile.name);
+        }
+        let keepalive: &[u8] = match profile.keepalive {
+            Keepalive::Comment => b": keepalive\n\n",
+            Keepalive::PingEvent => b"event: ping\ndata: {}\n\n",
+        };
+
+        // Stream events until disconnect.
+        // Keepalives detect dead connections faster than TCP keepalive alone;
+        // the cadence is re-evaluated after each one.
+        let mut keepalive_wait = self.keepalive.interval;
+        while ended.is_none() {
+            tokio::select! {
+                msg = rx.recv() => {
+                    match msg {
+                        Some(event) => {
+                            buffered_bytes.fetch_sub(event.len(), Ordering::Relaxed);
+                            ended = write_and_flush(&mut stream, event.as_bytes()).await.err();
+                        }
+                        None => break, // Channel dropped (session reaped or server shutdown)
+                    }
+                }
+                _ = tokio::time::sleep(keepalive_wait) => {
+                    ended = write_and_flush(&mut stream, keepalive).await.err();
+                    keepalive_wait = self.keepalive.interval_for(self.client_idle(&session_id).await);
+                }
+            }
+        }
+
+        // Keep the session resumable; the reaper closes it if the client
+        // doesn't come back. If it is already gone, whoever removed it
+        // (reaper, superseding session) has recorded the close.
+        self.detach_after(&session_id, connection, ended.as_ref()).await;
+        // Explicitly shutdown the socket
+        let _ = stream.shutdown().await;
+        match ended {
+            Some(e) if e.slow_consumer() => {
+                self.counters.slow_consumers.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(tag = "SSE", "Client not reading its stream, disconnected: {} ({})", session_id, e);
+            }
+            Some(e) => tracing::info!(tag = "SSE", "Client disconnected: {} ({})", session_id, e),
+            None => tracing::info!(tag = "SSE", "Client disconnected: {} (stream closed by the server)", session_id),
+        }
+    }
+
+    /// Handle GET /logs/stream: the last `backlog` lines of the log at
+    /// `path` (at most MAX_LOG_BACKLOG), then each new line as it is
+    /// written, as `event: log`. Needs the Bearer token.
+    pub async fn stream_log(&self, mut stream: TcpStream, authorization: Option<&str>, origin: Option<&str>, path: &Path, backlog: usize) {
+        if !self.is_authorized(authorization) {
+            let _ = stream.write_all(&unauthorized()).await;
+            let _ = stream.shutdown().await;
+            return;
+        }
+        let (lines, mut pos) = match crate::logs::tail(path, backlog.min(MAX_LOG_BACKLOG)) {
+            Ok(tail) => tail,
+            Err(e) => {
+                let body = serde_json::json!({ "error": format!("Cannot read {}: {}", path.display(), e) });
+                let _ = stream.write_all(&http_response(404, "Not Found", &body.to_string())).await;
+                let _ = stream.shutdown().await;
+                return;
+            }
+        };
+        configure_tcp_keepalive(&stream);
+        let headers = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/event-stream\r\n\
+             Cache-Control: no-cache\r\n\
+             Connection: keep-alive\r\n\
+             {}\r\n",
+            crate::dashboard::cors_headers(&self.allowed_origins, origin, false)
+        );
+        if stream.write_all(headers.as_bytes()).await.is_err() {
+            return;
+        }
+
+        let mut pending = lines;
+        let mut last_write = Instant::now();
+        loop {
+            let events: String = pending.iter().filter(|l| !l.trim().is_empty()).map(|l| log_event(l)).collect();
+            let quiet = last_write.elapsed() >= self.keepalive.interval;
+            if !events.is_empty() || quiet {
+                let data = if events.is_empty() { ": keepalive\n\n".to_string() } else { events };
+                if write_and_flush(&mut stream, data.as_bytes()).await.is_err() {
+                    break;
+                }
+                last_write = Instant::now();
+            }
+            tokio::time::sleep(LOG_POLL_INTERVAL).await;
+            pending = crate::logs::read_new(path, &mut pos).unwrap_or_default();
+        }
+        let _ = stream.shutdown().await;
+    }
+
+    /// Time since the client last posted to its session.
+    async fn client_idle(&self, session_id: &str) -> Duration {
+        self.sessions.lock().await
+            .get(session_id)
+            .map(|s| s.last_activity.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Attach a new stream to a known session. Returns None for unknown ids,
+    /// which then get a fresh session.
+    async fn resume(
+        &self,
+        session_id: &str,
+        tx: &mpsc::Sender<String>,
+        buffered_bytes: &Arc<AtomicUsize>,
+        last_event_id: Option<u64>,
+    ) -> Option<(&'static QuirkProfile, u64, Replay)> {
+        let token = self.current_token();
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions.get_mut(session_id)?;
+        // Replacing the sender ends a stream still attached to this session
+        session.tx = tx.clone();
+        session.token = token;
+        session.buffered_bytes = buffered_bytes.clone();
+        session.detached_at = None;
+        session.slow_consumer = false;
+        session.last_activity = Instant::now();
+        session.connection += 1;
+        // A new stream starts small again
+        session.queue = QueueSize { high_water: session.queue.high_water, ..QueueSize::new(self.channel_capacity) };
+        let replay = match last_event_id {
+            Some(last) => session.replay_after(last),
+            None => Replay::Events(Vec::new()),
+        };
+        Some((session.profile, session.connection, replay))
+    }
+
+    /// Mark a session's stream as gone, unless a newer connection took over.
+    async fn detach(&self, session_id: &str, connection: u64) {
+        self.detach_after(session_id, connection, None).await;
+    }
+
+    /// `detach` after the write that failed with `error`, if any.
+    async fn detach_after(&self, session_id: &str, connection: u64, error: Option<&WriteError>) {
+        if let Some(session) = self.sessions.lock().await.get_mut(session_id) {
+            if session.connection == connection {
+                session.detached_at = Some(Instant::now());
+                session.slow_consumer = error.is_some_and(WriteError::slow_consumer);
+            }
+        }
+    }
+
+    /// Add a session under a fresh unique id. With the `supersede` policy,
+    /// older sessions carrying the same client id are closed first.
+    /// None if `max_sessions` are open already.
+    async fn register(&self, session: SseSession) -> Option<String> {
+        let mut superseded = Vec::new();
+        let (session_id, retries) = {
+            let mut sessions = self.sessions.lock().await;
+            let old: Vec<String> = match (&self.duplicate_clients, &session.client_id) {
+                (DuplicateClients::Supersede, Some(client_id)) => sessions.iter()
+                    .filter(|(_, s)| s.client_id.as_ref() == Some(client_id))
+                    .map(|(id, _)| id.clone())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            // Sessions this one supersedes free their slots
+            if sessions.len() - old.len() >= self.max_sessions {
+                drop(sessions);
+                tracing::warn!("SSE session limit ({}) reached, rejecting connect", self.max_sessions);
+                self.stats.lock().await.rejected_at_capacity += 1;
+                return None;
+            }
+            for id in old {
+                // Dropping the sender ends the old session's stream loop
+                if let Some(s) = sessions.remove(&id) {
+                    superseded.push((id, s));
+                }
+            }
+            insert_unique(&mut sessions, session, generate_session_id)
+        };
+        self.counters.sessions_created.fetch_add(1, Ordering::Relaxed);
+
+        let mut stats = self.stats.lock().await;
+        if retries > 0 {
+            tracing::warn!("Session id collision, regenerated {} time(s)", retries);
+            stats.id_collision_retries += retries;
+        }
+        for (id, old) in superseded {
+            tracing::info!(tag = "SSE", "Session {} superseded by {} (same client id)", id, session_id);
+            record_close(&mut stats, &self.released_tx, old, CloseReason::Superseded);
+        }
+        Some(session_id)
+    }
+
+    /// Handle POST /message?sessionId=xxx — process JSON-RPC and send response via SSE.
+    /// Returns HTTP response bytes (202 Accepted or error).
+    pub async fn handle_message(
+        &self,
+        session_id: &str,
+        body: &str,
+        authorization: Option<&str>,
+        proxy: &Arc<ProxyServer>,
+    ) -> Vec<u8> {
+        if !self.is_authorized(authorization) {
+            return unauthorized();
+        }
+        let strip_progress = self.sessions.lock().await
+            .get(session_id)
+            .is_some_and(|s| s.profile.strip_progress);
+
+        // Parse JSON-RPC request, or a batch of them
+        let raw = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(raw) => raw,
+            Err(e) => {
+                return http_response(400, "Bad Request", &error::body(None, error::PARSE_ERROR, format!("Parse error: {}", e)));
+            }
+        };
+        let (items, batch) = match raw {
+            serde_json::Value::Array(items) if items.is_empty() => {
+                return http_response(400, "Bad Request", &error::body(None, error::INVALID_REQUEST, "Invalid Request: empty batch".into()));
+            }
+            serde_json::Value::Array(items) => (items, true),
+            single => (vec![single], false),
+        };
+        let mut requests = Vec::with_capacity(items.len());
+        for mut raw in items {
+            // A response to a request we forwarded from a backend
+            if raw.get("method").is_none() && (raw.get("result").is_some() || raw.get("error").is_some()) {
+                if !self.deliver_client_response(session_id, raw).await {
+                    tracing::warn!(tag = "SSE", "Session {} answered a request nobody is waiting for", session_id);
+                }
+                continue;
+            }
+            if strip_progress {
+                quirks::strip_progress_token(&mut raw);
+            }
+            let id = raw.get("id").cloned();
+            match serde_json::from_value::<JsonRpcRequest>(raw) {
+                Ok(request) => requests.push(Ok(request)),
+                // One bad entry doesn't sink the rest of the batch, and one
+                // with an id is answered over the stream like any request
+                Err(e) if batch || id.is_some() => {
+                    requests.push(Err(JsonRpcResponse::error(id, error::INVALID_REQUEST, format!("Invalid Request: {}", e))));
+                }
+                Err(e) => {
+                    return http_response(400, "Bad Request", &error::body(None, error::INVALID_REQUEST, format!("Invalid Request: {}", e)));
+                }
+            }
+        }
+
+        if let Err(wait) = self.admit_messages(session_id, requests.len()).await {
+            return too_many_requests(wait);
+        }
+
+        let has_id = requests.iter().any(|r| r.as_ref().map_or(true, |r| r.id.is_some()));
+        let method = match &requests[..] {
+            [Ok(request)] if !batch => request.method.clone(),
+            _ => "batch".to_string(),
+        };
+        let valid = requests.iter().filter(|r| r.is_ok()).count();
+        self.counters.messages_processed.fetch_add(valid as u64, Ordering::Relaxed);
+
+        // Backends are being stopped: don't hand them anything new.
+        if proxy.is_shutting_down() {
+            for request in requests.iter().flatten().filter(|r| r.id.is_none()) {
+                tracing::warn!(tag = "SSE", "Shutting down, dropped notification {} (session {})", request.method, session_id);
+            }
+            return http_response(503, "Service Unavailable", "{\"error\":\"Server shutting down\"}");
+        }
+
+        let call = match &requests[..] {
+            [Ok(request)] if !batch && request.method == "tools/call" => Some(request.params.clone()),
+            _ => None,
+        };
+
+        // Batch entries run in order so an initialize ahead of them counts
+        let mut responses = Vec::new();
+        let mut failed = false;
+        for request in requests {
+            let response = match request {
+                Ok(request) => {
+                    let (id, method) = (request.id.clone(), request.method.clone());
+                    match self.process_request(session_id, request, proxy).await {
+                        // A cancelled call gets no response by design; any
+                        // other request would leave the client waiting
+                        None if id.is_some() && method != "tools/call" => {
+                            tracing::error!(tag = "SSE", "No response to {} (session {})", method, session_id);
+                            failed = true;
+                            Some(JsonRpcResponse::error(id, error::INTERNAL_ERROR, format!("Internal error: no response to {}", method)))
+                        }
+                        response => response,
+                    }
+                }
+                Err(response) => Some(response),
+            };
+            responses.extend(response);
+        }
+        // A streamed result goes out as its chunks, then the response
+        let mut chunks = Vec::new();
+        if let (Some(params), [response]) = (&call, &responses[..]) {
+            if let Some(reply) = proxy.chunk_result(params, response).await {
+                chunks = reply.chunks;
+                responses = vec![reply.done];
+            }
+        }
+        let answered: Vec<Option<serde_json::Value>> = responses.iter().map(|r| r.id.clone()).collect();
+        // A batch of notifications gets no response at all
+        let serialized = match (batch, &responses[..]) {
+            (_, []) => None,
+            (false, [.., resp]) => Some(serde_json::to_string(resp)),
+            (true, all) => Some(serde_json::to_string(all)),
+        };
+        let mut serialize_failed = false;
+        let mut last = None;
+        match serialized {
+            Some(Ok(json)) => last = Some(self.frame(if failed { EventKind::Error } else { EventKind::Response }, &json)),
+            Some(Err(e)) => {
+                // The client still gets an answer to correlate with its requests
+                tracing::error!(tag = "SSE", "Serialize error: {}", e);
+                let failed_ids = if batch { &responses[..] } else { &responses[responses.len() - 1..] };
+                let errors: Vec<String> = failed_ids
+                    .iter()
+                    .map(|resp| error::body(resp.id.clone(), error::INTERNAL_ERROR, format!("Internal error: response could not be serialized: {}", e)))
+                    .collect();
+                let json = if batch { format!("[{}]", errors.join(",")) } else { errors.concat() };
+                chunks.clear();
+                last = Some(self.frame(EventKind::Error, &json));
+                serialize_failed = true;
+            }
+            None => {}
+        }
+
+        // Send response through SSE stream, chunks first and in order, each
+        // framed only once the stream has room for it
+        let mut events = chunks.into_iter().map(|chunk| self.frame(EventKind::Chunk, &chunk.to_string())).chain(last);
+        while let Some(event) = events.next() {
+            self.wait_for_room(session_id, event.len()).await;
+            match self.push_response(session_id, &event).await.map(|queued| self.count_drop(queued)) {
+                None => {
+                    self.drop_late_response(session_id, &method, "session is gone");
+                    return http_response(404, "Not Found", "{\"error\":\"Session not found\"}");
+                }
+                Some(Enqueue::Late) => {
+                    self.drop_late_response(session_id, &method, "stream is disconnected");
+                    return http_response(410, "Gone", "{\"error\":\"Session disconnected\"}");
+                }
+                Some(Enqueue::Sent) => {}
+                Some(Enqueue::CountFull) => {
+                    // The client can't keep up: tell it rather than let it
+                    // wait forever for a response that was never queued.
+                    tracing::warn!(
+                        tag = "SSE", "Session {} channel still full after {}ms, rejecting message",
+                        session_id,
+                        self.send_timeout.as_millis()
+                    );
+                    return http_response(503, "Service Unavailable", "{\"error\":\"Client is not reading its event stream\"}");
+                }
+                Some(Enqueue::BytesFull) => {
+                    tracing::warn!(
+                        tag = "SSE", "Session {} still over {} buffered bytes after {}ms, dropping the result",
+                        session_id,
+                        self.max_buffered_bytes.unwrap_or_default(),
+                        self.send_timeout.as_millis()
+                    );
+                    // The rest of a streamed result is no use without this
+                    // part: answer with an error instead, over the stream if
+                    // it has room for that, else in the POST response
+                    drop(events);
+                    let message = "Result dropped: the client is not reading its event stream (sse.maxBufferedBytes)";
+                    let errors: Vec<String> = answered.iter().map(|id| error::body(id.clone(), error::INTERNAL_ERROR, message.into())).collect();
+                    let json = if batch { format!("[{}]", errors.join(",")) } else { errors.concat() };
+                    self.wait_for_room(session_id, json.len()).await;
+                    let queued = self.push_response(session_id, &self.frame(EventKind::Error, &json)).await;
+                    if queued != Some(Enqueue::Sent) {
+                        return http_response(503, "Service Unavailable", &json);
+                    }
+                    break;
+                }
+                Some(Enqueue::Closed) => {
+                    let mut sessions = self.sessions.lock().await;
+                    // Stream dropped: kept for replay when the client resumes
+                    if sessions.get(session_id).is_some_and(|s| s.detached_at.is_none()) {
+                        // Session is dead, clean it up
+                        if let Some(session) = sessions.remove(session_id) {
+                            record_close(&mut *self.stats.lock().await, &self.released_tx, session, CloseReason::ForceClosed);
+                        }
+                        return http_response(410, "Gone", "{\"error\":\"Session closed\"}");
+                    }
+                }
+            }
+        }
+
+        if serialize_failed {
+            return http_response(500, "Internal Server Error", "{\"error\":\"Serialize failed\"}");
+        }
+        // Return 202 Accepted for requests, 200 for notifications
+        if has_id {
+            http_response(202, "Accepted", "{\"ok\":true}")
+        } else {
+            http_response(200, "OK", "{\"ok\":true}")
+        }
+    }
+
+    /// Handle POST /message without a sessionId: notifications (or a batch
+    /// of them) are dispatched and answered 200, as nothing needs routing
+    /// back. Anything with an id needs a session to answer on and gets 400.
+    pub async fn handle_sessionless(&self, body: &str, authorization: Option<&str>, proxy: &Arc<ProxyServer>) -> Vec<u8> {
+        if !self.is_authorized(authorization) {
+            return unauthorized();
+        }
+        let items = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(serde_json::Value::Array(items)) => items,
+            Ok(single) => vec![single],
+            Err(e) => {
+                return http_response(400, "Bad Request", &error::body(None, error::PARSE_ERROR, format!("Parse error: {}", e)));
+            }
+        };
+        let mut notifications = Vec::with_capacity(items.len());
+        for raw in items {
+            if raw.get("id").is_some() {
+                return http_response(400, "Bad Request", "{\"error\":\"Missing sessionId parameter: only notifications can be sent without a session\"}");
+            }
+            match serde_json::from_value::<JsonRpcRequest>(raw) {
+                Ok(request) => notifications.push(request),
+                Err(e) => {
+                    return http_response(400, "Bad Request", &error::body(None, error::INVALID_REQUEST, format!("Invalid Request: {}", e)));
+                }
+            }
+        }
+        if notifications.is_empty() {
+            return http_response(400, "Bad Request", &error::body(None, error::INVALID_REQUEST, "Invalid Request: empty batch".into()));
+        }
+        if proxy.is_shutting_down() {
+            for notification in &notifications {
+                tracing::warn!(tag = "SSE", "Shutting down, dropped notification {} (no session)", notification.method);
+            }
+            return http_response(503, "Service Unavailable", "{\"error\":\"Server shutting down\"}");
+        }
+        self.counters.messages_processed.fetch_add(notifications.len() as u64, Ordering::Relaxed);
+        for notification in notifications {
+            tracing::debug!(tag = "SSE", "Notification {} without a session", notification.method);
+            proxy.handle_request(notification).await;
+        }
+        http_response(200, "OK", "{\"ok\":true}")
+    }
+
+    /// Run one JSON-RPC request from a session: handshake gating, the
+    /// session-local methods, then the proxy. None for notifications.
+    async fn process_request(
+        &self,
+        session_id: &str,
+        mut request: JsonRpcRequest,
+        proxy: &Arc<ProxyServer>,
+    ) -> Option<JsonRpcResponse> {
+        let mut scope = None;
+        let handshake = match self.sessions.lock().await.get_mut(session_id) {
+            Some(session) => {
+                scope = session.scope.clone();
+                if request.method == "initialize" {
+                    session.sampling = request.params.pointer("/capabilities/sampling").is_some();
+                }
+                if request.method == "tools/call" {
+                    session.calls_in_flight += 1;
+                }
+                proxy.handshake(&request, &mut session.initialized)
+            }
+            None => Handshake::Ready,
+        };
+        let tool_call = request.method == "tools/call";
+        let subscription_uri = request.params.get("uri").and_then(|u| u.as_str()).map(String::from);
+        // Backend subscriptions are shared between sessions: only this
+        // session's interest is dropped here, the router unsubscribes the
+        // backend once nobody else holds the uri.
+        if request.method == "resources/unsubscribe" {
+            if let Some(uri) = &subscription_uri {
+                let removed = self.sessions.lock().await
+                    .get_mut(session_id)
+                    .is_some_and(|s| s.subscriptions.remove(uri));
+                if removed {
+                    let _ = self.released_tx.send(vec![uri.clone()]);
+                }
+            }
+        }
+
+        let response = if let Handshake::Rejected(response) = handshake {
+            Some(response)
+        } else if request.method == "resources/unsubscribe" {
+            Some(JsonRpcResponse::success(request.id, serde_json::json!({})))
+        } else if request.method == "logging/setLevel" {
+            let level = request.params.get("level").and_then(|l| l.as_str()).and_then(LogLevel::parse);
+            Some(match level {
+                Some(level) => {
+                    if let Some(session) = self.sessions.lock().await.get_mut(session_id) {
+                        session.logs.min_level = Some(level);
+                    }
+                    JsonRpcResponse::success(request.id, serde_json::json!({}))
+                }
+                None => JsonRpcResponse::error(request.id, error::INVALID_PARAMS, "Invalid log level".into()),
+            })
+        } else {
+            let subscribe = request.method == "resources/subscribe";
+            let progress_token = self.track_progress(session_id, &mut request).await;
+            let response = match &scope {
+                Some(scope) => proxy.handle_scoped_request(session_id, request, handshake, scope).await,
+                None => proxy.handle_session_request(session_id, request, handshake).await,
+            };
+            if let Some(token) = progress_token {
+                self.progress_tokens.lock().await.remove(&token);
+            }
+            if let (true, Some(uri), Some(resp)) = (subscribe, &subscription_uri, &response) {
+                if resp.error.is_none() {
+                    match self.sessions.lock().await.get_mut(session_id) {
+                        Some(session) => {
+                            session.subscriptions.insert(uri.clone());
+                        }
+                        // The session went away meanwhile: nobody else would release it
+                        None => {
+                            let _ = self.released_tx.send(vec![uri.clone()]);
+                        }
+                    }
+                }
+            }
+            response
+        };
+        if tool_call {
+            if let Some(session) = self.sessions.lock().await.get_mut(session_id) {
+                session.calls_in_flight = session.calls_in_flight.saturating_sub(1);
+            }
+        }
+        response
+    }
+
+    /// Swap the client's progress token of a `tools/call` for one unique to
+    /// the hub, so sessions reusing a token don't get each other's progress.
+    async fn track_progress(&self, session_id: &str, request: &mut JsonRpcRequest) -> Option<String> {
+        if request.method != "tools/call" {
+            return None;
+        }
+        let token = request.params.pointer_mut("/_meta/progressToken")?;
+        let hub_token = format!("mcphub-{}", self.next_hub_id.fetch_add(1, Ordering::Relaxed));
+        let client_token = std::mem::replace(token, serde_json::json!(hub_token));
+        self.progress_tokens.lock().await.insert(hub_token.clone(), (session_id.to_string(), client_token));
+        Some(hub_token)
+    }
+
+    /// Queue a response event on a session. If its channel is full, wait up
+    /// to `send_timeout` for the client to drain it; CountFull then means it
+    /// still couldn't keep up. None if the session is gone.
+    async fn push_response(&self, session_id: &str, event: &str) -> Option<Enqueue> {
+        let tx = {
+            let mut sessions = self.sessions.lock().await;
+            let session = sessions.get_mut(session_id)?;
+            if session.detached_at.is_some() && !self.buffer_late_responses {
+                return Some(Enqueue::Late);
+            }
+            session.last_activity = Instant::now();
+            match session.push_event(event, self.max_buffered_bytes) {
+                Enqueue::CountFull => session.tx.clone(),
+                result => return Some(result),
+            }
+        };
+        // Wait without holding the session map; the event gets its id once
+        // the slot is ours, so ids still follow queue order.
+        let reserved = tokio::time::timeout(self.send_timeout, tx.clone().reserve_owned()).await;
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions.get_mut(session_id)?;
+        match reserved {
+            Ok(Ok(permit)) if session.tx.same_channel(&tx) => {
+                session.push_reserved(event, permit);
+                Some(Enqueue::Sent)
+            }
+            // Closed, or the client reconnected meanwhile: queue on the
+            // current channel (or keep for replay) like any other event.
+            Ok(_) => Some(session.push_event(event, self.max_buffered_bytes)),
+            Err(_) => Some(Enqueue::CountFull),
+        }
+    }
+
+    /// Wait up to `send_timeout` for the session's queued bytes to leave
+    /// room for an event of `len` bytes under `maxBufferedBytes`, so a long
+    /// streamed result goes out at the pace the client reads it.
+    async fn wait_for_room(&self, session_id: &str, len: usize) {
+        let Some(max) = self.max_buffered_bytes else { return };
+        let deadline = Instant::now() + self.send_timeout;
+        loop {
+            let buffered = match self.sessions.lock().await.get(session_id) {
+                Some(session) => session.buffered_bytes.load(Ordering::Relaxed),
+                None => return,
+            };
+            if buffered == 0 || buffered + len <= max || Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep(DRAIN_POLL).await;
+        }
+    }
+
+    /// Log and count a response that has no stream to go to.
+    fn drop_late_response(&self, session_id: &str, method: &str, why: &str) {
+        self.counters.late_responses_dropped.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(tag = "SSE", "Dropped late {} response: session {} {}", method, session_id, why);
+    }
+
+    /// Format an SSE event. By default everything is `event: message`; with
+    /// split event types clients can demultiplex responses from notifications,
+    /// each still in queue order. Data over `sse.maxEventBytes` is replaced
+    /// by a marker (see `oversized`).
+    fn frame(&self, kind: EventKind, data: &str) -> String {
+        let (kind, data) = match self.max_event_bytes {
+            Some(max) if data.len() > max => {
+                tracing::warn!(tag = "SSE", "Event of {} bytes exceeds sse.maxEventBytes ({}), replaced", data.len(), max);
+                let (kind, marker) = oversized(data, max);
+                (kind, std::borrow::Cow::Owned(marker))
+            }
+            _ => (kind, std::borrow::Cow::Borrowed(data)),
+        };
+        let event = match (self.split_events, kind) {
+            (false, _) => "message",
+            (true, EventKind::Response) => "response",
+            (true, EventKind::Notification) => "notification",
+            (true, EventKind::Request) => "request",
+            (true, EventKind::Chunk) => "chunk",
+            (true, EventKind::Error) => "error",
+        };
+        sse_event(event, &data)
+    }
+
+    /// Count an event a full queue refused.
+    fn count_drop(&self, queued: Enqueue) -> Enqueue {
+        if matches!(queued, Enqueue::CountFull | Enqueue::BytesFull) {
+            self.counters.messages_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queued
+    }
+
+    /// Charge `count` messages to the session's `sse.rateLimit` budget.
+    async fn admit_messages(&self, session_id: &str, count: usize) -> Result<(), Duration> {
+        let limit = self.rate_limit;
+        if limit.messages_per_sec <= 0.0 || count == 0 {
+            return Ok(());
+        }
+        let mut sessions = self.sessions.lock().await;
+        // An unknown session is answered 404 further on
+        let Some(session) = sessions.get_mut(session_id) else { return Ok(()) };
+        let admitted = session.rate.take(count, limit.messages_per_sec, limit.message_burst, Instant::now());
+        match (&admitted, session.throttled) {
+            (Err(_), false) => tracing::warn!(
+                tag = "SSE", "Session {} is posting more than {}/s, answering 429 until it slows down",
+                session_id,
+                limit.messages_per_sec
+            ),
+            (Ok(()), true) => tracing::info!(tag = "SSE", "Session {} is back under its message rate", session_id),
+            _ => {}
+        }
+        session.throttled = admitted.is_err();
+        if admitted.is_err() {
+            self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+        }
+        admitted
+    }
+
+    /// Charge a connect to `peer`'s `sse.rateLimit` budget.
+    fn admit_connect(&self, peer: IpAddr) -> Result<(), Duration> {
+        let limit = self.rate_limit;
+        if limit.connects_per_sec <= 0.0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut connects = self.connects.lock().unwrap_or_else(|e| e.into_inner());
+        connects.retain(|ip, bucket| *ip == peer || !bucket.is_full(limit.connects_per_sec, limit.connect_burst, now));
+        let admitted = connects.entry(peer).or_insert_with(TokenBucket::full).take(1, limit.connects_per_sec, limit.connect_burst, now);
+        if admitted.is_err() {
+            self.counters.rate_limited.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(tag = "SSE", "Refused a connect from {}: more than {}/s", peer, limit.connects_per_sec);
+        }
+        admitted
+    }
+
+    pub fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        authorized(self.require_auth, self.auto_generate_token, self.bind_addr, authorization)
+    }
+
+    /// Fingerprint of the token clients authenticate with now.
+    fn current_token(&self) -> Option<u64> {
+        if !self.require_auth {
+            return None;
+        }
+        crate::dashboard::load_auth_token(false, self.bind_addr).ok().map(|t| token_fingerprint(&t))
+    }
+
+    /// Get active session count.
+    pub async fn session_count(&self) -> usize {
+        self.sessions.lock().await.len()
+    }
+
+    /// Open sessions, oldest first.
+    pub async fn sessions(&self) -> Vec<SessionInfo> {
+        let sessions = self.sessions.lock().await;
+        let mut list: Vec<SessionInfo> = sessions
+            .iter()
+            .map(|(id, s)| SessionInfo {
+                id: id.clone(),
+                client_id: s.client_id.clone(),
+                profile: s.profile.name,
+                age_secs: s.created_at.elapsed().as_secs(),
+                idle_secs: s.last_activity.elapsed().as_secs(),
+                connected: s.detached_at.is_none(),
+                pinned: s.pinned,
+                subscriptions: s.subscriptions.len(),
+                servers: s.scope.as_deref().map(sorted),
+                queued: s.queued(),
+                queue_limit: s.queue.limit,
+                queue_high_water: s.queue.high_water,
+            })
+            .collect();
+        list.sort_by_key(|s| std::cmp::Reverse(s.age_secs));
+        list
+    }
+
+    /// Pin or unpin a session. False if there is no such session.
+    pub async fn set_pinned(&self, session_id: &str, pinned: bool) -> bool {
+        let mut sessions = self.sessions.lock().await;
+        let Some(session) = sessions.get_mut(session_id) else { return false };
+        session.pinned = pinned;
+        tracing::info!(tag = "SSE", "Session {} {}", session_id, if pinned { "pinned" } else { "unpinned" });
+        true
+    }
+
+    /// Session limit, for reporting saturation next to `session_count`.
+    pub fn max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    /// Session and throughput counters for GET /metrics.
+    pub async fn metrics(&self) -> SseMetrics {
+        SseMetrics {
+            active_sessions: self.session_count().await,
+            max_sessions: self.max_sessions,
+            sessions_created: self.counters.sessions_created.load(Ordering::Relaxed),
+            sessions_reaped: self.stats.lock().await.reaped,
+            messages_processed: self.counters.messages_processed.load(Ordering::Relaxed),
+            messages_dropped: self.counters.messages_dropped.load(Ordering::Relaxed),
+            late_responses_dropped: self.counters.late_responses_dropped.load(Ordering::Relaxed),
+            rate_limited: self.counters.rate_limited.load(Ordering::Relaxed),
+            endpoint_failures: self.counters.endpoint_failures.load(Ordering::Relaxed),
+            slow_consumers: self.counters.slow_consumers.load(Ordering::Relaxed),
+            session_queues: self.sessions().await.into_iter().map(|s| SessionQueue {
+                session: s.id,
+                queued: s.queued,
+                limit: s.queue_limit,
+                high_water: s.queue_high_water,
+            }).collect(),
+        }
+    }
+
+    /// Snapshot of session close counters and lifetime histogram.
+    pub async fn stats(&self) -> SessionStats {
+        self.stats.lock().await.clone()
+    }
+}
+
+/// One log line as an SSE event.
+fn log_event(line: &str) -> String {
+    sse_event("log", line.trim_end())
+}
+
+/// An SSE event with one `data:` field per line of `data`: a line break
+/// inside a field would end it (or the event) early.
+pub fn sse_event(event: &str, data: &str) -> String {
+    let mut framed = format!("event: {}\n", event);
+    for line in data.split("\r\n").flat_map(|l| l.split(['\r', '\n'])) {
+        framed.push_str("data: ");
+        framed.push_str(line);
+        framed.push('\n');
+    }
+    framed.push('\n');
+    framed
+}
+
+/// Stand-in for event data over `max` bytes: an error response to each id
+/// it answers, or else a warning log notification saying it was dropped.
+fn oversized(data: &str, max: usize) -> (EventKind, String) {
+    let message = format!("Event of {} bytes exceeds sse.maxEventBytes ({}), dropped", data.len(), max);
+    let error = |id: &serde_json::Value| error::body(Some(id.clone()), error::INTERNAL_ERROR, message.clone());
+    let answers = |item: &serde_json::Value| item.get("method").is_none() && item.get("id").is_some_and(|id| !id.is_null());
+    match serde_json::from_str::<serde_json::Value>(data) {
+        Ok(serde_json::Value::Array(items)) if items.iter().any(answers) => {
+            let errors: Vec<String> = items.iter().filter(|i| answers(i)).map(|i| error(&i["id"])).collect();
+            (EventKind::Error, format!("[{}]", errors.join(",")))
+        }
+        Ok(item) if answers(&item) => (EventKind::Error, error(&item["id"])),
+        _ => {
+            let warning = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": { "level": "warning", "logger": "mcphub", "data": message },
+            });
+            (EventKind::Notification, warning.to_string())
+        }
+    }
+}
+
+/// Extract sessionId from query string: /message?sessionId=xxx
+pub fn extract_session_id(path: &str) -> Option<String> {
+    crate::http::query_param(path, "sessionId")
+}
+
+/// Attempts at sending the endpoint event before giving up on the client.
+const ENDPOINT_ATTEMPTS: u32 = 3;
+/// How long one attempt waits for the client to take the bytes.
+const ENDPOINT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Send the endpoint event. A client that misses it never learns where to
+/// POST, so a stalled or interrupted write is retried, resuming after the
+/// bytes already sent; a closed or reset connection is not.
+async fn send_endpoint<W: AsyncWrite + Unpin>(stream: &mut W, event: &[u8]) -> Result<(), String> {
+    use std::io::{Error, ErrorKind};
+    let mut sent = 0;
+    let mut attempt = 1;
+    loop {
+        let write = async {
+            while sent < event.len() {
+                match stream.write(&event[sent..]).await? {
+                    0 => return Err(Error::from(ErrorKind::WriteZero)),
+                    n => sent += n,
+                }
+            }
+            stream.flush().await
+        };
+        let result = tokio::time::timeout(ENDPOINT_WRITE_TIMEOUT, write).await.unwrap_or_else(|_| Err(ErrorKind::TimedOut.into()));
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < ENDPOINT_ATTEMPTS && matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(format!("{} after {} attempt(s), {} of {} bytes sent", e, attempt, sent, event.len())),
+        }
+    }
+}
+
+/// How long a write to a client stream may wait for the client to read.
+const STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Why writing to a client stream failed.
+#[derive(Debug)]
+pub enum WriteError {
+    /// The write failed, usually because the client closed the connection.
+    Write(std::io::Error),
+    /// The data was written but couldn't be flushed out.
+    Flush(std::io::Error),
+    /// The client took nothing for this long: its receive buffer and ours
+    /// are full.
+    Stalled(Duration),
+}
+
+impl WriteError {
+    /// The client is there but not keeping up, as opposed to gone.
+    pub fn slow_consumer(&self) -> bool {
+        use std::io::ErrorKind;
+        match self {
+            WriteError::Write(e) => matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+            WriteError::Flush(_) | WriteError::Stalled(_) => true,
+        }
+    }
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Write(e) => write!(f, "write failed: {}", e),
+            WriteError::Flush(e) => write!(f, "flush failed: {}", e),
+            WriteError::Stalled(after) => write!(f, "write stalled for {}ms", after.as_millis()),
+        }
+    }
+}
+
+/// Write bytes + flush, giving up after STREAM_WRITE_TIMEOUT.
+pub async fn write_and_flush<W: AsyncWrite + Unpin>(stream: &mut W, data: &[u8]) -> Result<(), WriteError> {
+    write_within(stream, data, STREAM_WRITE_TIMEOUT).await
+}
+
+async fn write_within<W: AsyncWrite + Unpin>(stream: &mut W, data: &[u8], timeout: Duration) -> Result<(), WriteError> {
+    match tokio::time::timeout(timeout, stream.write_all(data)).await {
+        Err(_) => return Err(WriteError::Stalled(timeout)),
+        Ok(Err(e)) => return Err(WriteError::Write(e)),
+        Ok(Ok(())) => {}
+    }
+    stream.flush().await.map_err(WriteError::Flush)
+}
+
+/// Configure TCP keepalive on the socket to detect dead peers.
+fn configure_tcp_keepalive(stream: &TcpStream) {
+    let sock_ref = socket2::SockRef::from(stream);
+    let mut ka = socket2::TcpKeepalive::new()
+        .with_time(Duration::from_secs(15))
+        .with_interval(Duration::from_secs(5));
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        ka = ka.with_retries(3);
+    }
+    let _ = sock_ref.set_tcp_keepalive(&ka);
+    let _ = sock_ref.set_nodelay(true);
+}
+
+/// Periodically reap stale sessions (no activity for the session timeout).
+async fn session_reaper(
+    sessions: Arc<Mutex<HashMap<String, SseSession>>>,
+    stats: Arc<Mutex<SessionStats>>,
+    released: mpsc::UnboundedSender<Vec<String>>,
+    reaper: ReaperConfig,
+) {
+    loop {
+        tokio::time::sleep(reaper.interval).await;
+        reap_stale(&sessions, &stats, &released, reaper.session_timeout, reaper.max_lifetime).await;
+    }
+}
+
+/// Remove sessions idle for longer than `max_idle`.
+/// Dropping the sender half of the channel causes the SSE loop to break.
+async fn reap_stale(
+    sessions: &Mutex<HashMap<String, SseSession>>,
+    stats: &Mutex<SessionStats>,
+    released: &mpsc::UnboundedSender<Vec<String>>,
+    max_idle: Duration,
+    max_lifetime: Option<Duration>,
+) {
+    let resume_window = max_idle.min(Duration::from_secs(RESUME_WINDOW_SECS));
+    let expired = |s: &SseSession| max_lifetime.is_some_and(|max| s.created_at.elapsed() > max);
+    let mut map = sessions.lock().await;
+    let stale: Vec<String> = map
+        .iter()
+        .filter(|(_, s)| {
+            (!s.pinned && s.last_activity.elapsed() > max_idle)
+                || s.detached_at.is_some_and(|t| t.elapsed() > resume_window)
+                || expired(s)
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in &stale {
+        let Some(mut session) = map.remove(id) else { continue };
+        // A detached session that wasn't resumed is a client disconnect
+        let reason = if session.detached_at.is_some() && session.slow_consumer {
+            CloseReason::SlowConsumer
+        } else if session.detached_at.is_some() {
+            CloseReason::Disconnected
+        } else if expired(&session) {
+            // Pinned and busy sessions too: the client reconnects
+            if let Ok(permit) = session.try_reserve(EXPIRED_EVENT.len(), None) {
+                session.send_reserved(permit, EXPIRED_EVENT.to_string());
+            }
+            CloseReason::Expired
+        } else {
+            CloseReason::Reaped
+        };
+        if reason == CloseReason::Expired {
+            tracing::info!(tag = "SSE", "Closed session {} at its maximum lifetime", id);
+        } else {
+            tracing::info!(tag = "SSE", "Reaped stale session: {}", id);
+        }
+        record_close(&mut *stats.lock().await, released, session, reason);
+    }
+    tracing::debug!(tag = "SSE", open = map.len(), reaped = stale.len(), "Reaper pass done");
+}
+
+/// Close the sessions opened with a token other than `current` (rotated
+/// since they connected).
+async fn revoke_sessions(
+    sessions: &Mutex<HashMap<String, SseSession>>,
+    stats: &Mutex<SessionStats>,
+    released: &mpsc::UnboundedSender<Vec<String>>,
+    current: u64,
+) {
+    let mut map = sessions.lock().await;
+    let revoked: Vec<String> = map
+        .iter()
+        .filter(|(_, s)| s.token.is_some_and(|t| t != current))
+        .map(|(id, _)| id.clone())
+        .collect();
+    if revoked.is_empty() {
+        return;
+    }
+    for id in &revoked {
+        if let Some(session) = map.remove(id) {
+            record_close(&mut *stats.lock().await, released, session, CloseReason::Revoked);
+        }
+    }
+    tracing::info!(tag = "AUTH", "Auth token rotated: closed {} session(s) opened with the old token", revoked.len());
+}
+
+/// Identifies a token without keeping it in memory or the session store
+/// (FNV-1a, stable across builds).
+fn token_fingerprint(token: &str) -> u64 {
+    token.bytes().fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Generate a session ID: 128 bits from the OS random source, as hex so it
+/// is URL-safe. Ids are what let a client POST to a session, so they must
+/// not be guessable.
+pub fn generate_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("OS random source unavailable");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Insert `session` under an id from `generate`, drawing a new id while the
+/// generated one is already taken. Returns the id and the number of retries.
+fn insert_unique(
+    sessions: &mut HashMap<String, SseSession>,
+    session: SseSession,
+    mut generate: impl FnMut() -> String,
+) -> (String, u64) {
+    let mut retries = 0;
+    let mut id = generate();
+    while sessions.contains_key(&id) {
+        retries += 1;
+        id = generate();
+    }
+    sessions.insert(id.clone(), session);
+    (id, retries)
+}
+
+/// Check an `Authorization` header against the hub token.
+/// No token means no client can authenticate: reject rather than serve open.
+pub fn authorized(require_auth: bool, auto_generate_token: bool, bind_addr: SocketAddr, authorization: Option<&str>) -> bool {
+    if !require_auth {
+        return true;
+    }
+    let Some(header) = authorization else { return false };
+    match crate::dashboard::load_auth_token(auto_generate_token, bind_addr) {
+        Ok(token) => bearer_matches(header, &token),
+        Err(_) => false,
+    }
+}
+
+/// Compare `Bearer <token>` in constant time (for a given header length).
+fn bearer_matches(header: &str, token: &str) -> bool {
+    let Some(presented) = header.strip_prefix("Bearer ") else { return false };
+    let (a, b) = (presented.as_bytes(), token.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub fn unauthorized() -> Vec<u8> {
+    http_response(401, "Unauthorized", "{\"error\":\"Unauthorized\"}")
+}
+
+/// 429 telling the client when to retry, in whole seconds.
+fn too_many_requests(wait: Duration) -> Vec<u8> {
+    let body = "{\"error\":\"Too many requests\"}";
+    format!(
+        "HTTP/1.1 429 Too Many Requests\r\n\
+         Content-Type: application/json\r\n\
+         Retry-After: {}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        wait.as_secs_f64().ceil().max(1.0) as u64,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+pub fn http_response(status: u16, status_text: &str, body: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        status_text,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(capacity: usize) -> (SseSession, mpsc::Receiver<String>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let session = SseSession {
+            tx,
+            last_activity: Instant::now(),
+            created_at: Instant::now(),
+            buffered_bytes: Arc::new(AtomicUsize::new(0)),
+            profile: &quirks::DEFAULT,
+            client_id: None,
+            subscriptions: HashSet::new(),
+            last_event_id: 0,
+            replay: VecDeque::new(),
+            detached_at: None,
+            connection: 0,
+            slow_consumer: false,
+            logs: LogGate::new(),
+            pinned: false,
+            initialized: false,
+            sampling: false,
+            calls_in_flight: 0,
+            token: None,
+            rate: TokenBucket::full(),
+            throttled: false,
+            scope: None,
+            queue: QueueSize::new(capacity),
+        };
+        (session, rx)
+    }
+
+    #[tokio::test]
+    async fn test_rotated_token_revokes_its_sessions() {
+        let (old, new) = (token_fingerprint("mcphub_old"), token_fingerprint("mcphub_new"));
+        assert_ne!(old, new);
+        let sessions = Mutex::new(HashMap::new());
+        let stats = Mutex::new(SessionStats::new());
+        let (released, _released_rx) = mpsc::unbounded_channel();
+        for (id, token) in [("a", Some(old)), ("b", Some(new)), ("c", None)] {
+            let (mut s, _) = session(4);
+            s.token = token;
+            sessions.lock().await.insert(id.to_string(), s);
+        }
+
+        revoke_sessions(&sessions, &stats, &released, new).await;
+        let mut left: Vec<String> = sessions.lock().await.keys().cloned().collect();
+        left.sort();
+        assert_eq!(left, ["b", "c"]);
+        assert_eq!(stats.lock().await.revoked, 1);
+    }
+
+    #[test]
+    fn test_byte_budget_triggers_before_count() {
+        let (mut session, _rx) = session(64);
+        let big = "x".repeat(300);
+        assert_eq!(session.enqueue(big.clone(), Some(1000)), Enqueue::Sent);
+        assert_eq!(session.enqueue(big.clone(), Some(1000)), Enqueue::Sent);
+        assert_eq!(session.enqueue(big.clone(), Some(1000)), Enqueue::Sent);
+        // 3 messages of 64 queued, but a 4th would exceed 1000 bytes
+        assert_eq!(session.enqueue(big, Some(1000)), Enqueue::BytesFull);
+        assert_eq!(session.buffered_bytes.load(Ordering::Relaxed), 900);
+    }
+
+    #[test]
+    fn test_count_bound_without_byte_budget() {
+        let (mut session, _rx) = session(2);
+        assert_eq!(session.enqueue("a".into(), None), Enqueue::Sent);
+        assert_eq!(session.enqueue("b".into(), None), Enqueue::Sent);
+        assert_eq!(session.enqueue("c".into(), None), Enqueue::CountFull);
+    }
+
+    #[tokio::test]
+    async fn test_queue_grows_when_full_and_shrinks_when_idle() {
+        let config = ProxyConfig { auth_required: false, sse_channel_capacity: 2, sse_max_channel_capacity: 8, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let (mut s, mut rx) = session(manager.max_channel_capacity);
+        s.queue = QueueSize::new(manager.channel_capacity);
+        for i in 0..8 {
+            assert_eq!(s.enqueue(i.to_string(), None), Enqueue::Sent, "event {}", i);
+        }
+        assert_eq!(s.enqueue("9".into(), None), Enqueue::CountFull);
+        assert_eq!((s.queue.limit, s.queue.high_water), (8, 8));
+
+        // Drained and idle: back down one step per event
+        while !rx.is_empty() {
+            rx.recv().await.unwrap();
+        }
+        s.queue.resized_at = Instant::now().checked_sub(QUEUE_SHRINK_AFTER);
+        assert_eq!(s.enqueue("a".into(), None), Enqueue::Sent);
+        assert_eq!(s.queue.limit, 4);
+
+        manager.sessions.lock().await.insert("s1".into(), s);
+        let text = manager.metrics().await.to_prometheus();
+        assert!(text.contains("mcphub_sse_session_queue_limit{session=\"s1\"} 4\n"), "{}", text);
+        assert!(text.contains("mcphub_sse_session_queue_high_water{session=\"s1\"} 8\n"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_message_rejected_during_shutdown() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, _rx) = session(4);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        proxy.shutdown().await;
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", request, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 503"), "{}", resp);
+        assert!(resp.contains("Server shutting down"));
+
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", notification, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 503"), "{}", resp);
+    }
+
+    #[tokio::test]
+    async fn test_request_left_unanswered_gets_error_event() {
+        let config = ProxyConfig { auth_required: false, sse_split_events: true, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, mut rx) = session(4);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        // A notification method the proxy doesn't answer, sent with an id
+        let request = r#"{"jsonrpc":"2.0","id":7,"method":"notifications/initialized"}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", request, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+        let event = rx.recv().await.unwrap();
+        assert!(event.contains("event: error\n"), "{}", event);
+        let data: serde_json::Value = serde_json::from_str(event.split("data: ").nth(1).unwrap().trim()).unwrap();
+        assert_eq!((data["id"].clone(), data["error"]["code"].clone()), (serde_json::json!(7), serde_json::json!(error::INTERNAL_ERROR)));
+    }
+
+    #[tokio::test]
+    async fn test_sessionless_post_takes_notifications_only() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        let resp = String::from_utf8(manager.handle_sessionless(notification, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "{}", resp);
+        let batch = format!("[{},{}]", notification, r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":1}}"#);
+        let resp = String::from_utf8(manager.handle_sessionless(&batch, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "{}", resp);
+        assert_eq!(manager.metrics().await.messages_processed, 3);
+
+        // Anything with an id has nowhere to get its response
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        let resp = String::from_utf8(manager.handle_sessionless(request, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 400") && resp.contains("Missing sessionId"), "{}", resp);
+        let batch = format!("[{},{}]", notification, request);
+        let resp = String::from_utf8(manager.handle_sessionless(&batch, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 400"), "{}", resp);
+        assert_eq!(manager.metrics().await.messages_processed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_full_channel_waits_for_room_then_rejects() {
+        let config = ProxyConfig { auth_required: false, sse_send_timeout_ms: 300, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (mut s, mut rx) = session(1);
+        assert_eq!(s.push_event("data: {}\n\n", None), Enqueue::Sent);
+        manager.sessions.lock().await.insert("s1".into(), s);
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+
+        // The client drains its stream while the response waits: nothing is lost
+        let drain = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            rx.recv().await.unwrap();
+            rx
+        });
+        let resp = String::from_utf8(manager.handle_message("s1", request, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+        let mut rx = drain.await.unwrap();
+        assert!(rx.recv().await.unwrap().starts_with("id: 2\n"));
+
+        // Nobody reads: rejected after the timeout instead of silently dropped
+        assert_eq!(manager.sessions.lock().await.get_mut("s1").unwrap().push_event("data: {}\n\n", None), Enqueue::Sent);
+        let resp = String::from_utf8(manager.handle_message("s1", request, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 503"), "{}", resp);
+        assert_eq!(manager.sessions.lock().await["s1"].last_event_id, 3);
+
+        let metrics = manager.metrics().await;
+        assert_eq!((metrics.active_sessions, metrics.messages_processed, metrics.messages_dropped), (1, 2, 1));
+        assert!(metrics.to_prometheus().contains("# TYPE mcphub_messages_dropped_total counter\nmcphub_messages_dropped_total 1\n"));
+    }
+
+    #[test]
+    fn test_keepalive_interval_escalates_when_idle() {
+        let k = KeepaliveConfig::default();
+        assert_eq!(k.interval_for(Duration::from_secs(10)), Duration::from_secs(15));
+        assert_eq!(k.interval_for(Duration::from_secs(90)), Duration::from_secs(5));
+        let off = KeepaliveConfig { idle_after: Duration::ZERO, ..k };
+        assert_eq!(off.interval_for(Duration::from_secs(90)), Duration::from_secs(15));
+    }
+
+    #[tokio::test]
+    async fn test_keepalives_speed_up_during_prolonged_idle() {
+        let keepalive = KeepaliveConfig {
+            interval: Duration::from_millis(300),
+            idle_after: Duration::from_millis(200),
+            idle_interval: Duration::from_millis(40),
+        };
+        let config = ProxyConfig { auth_required: false, sse_keepalive: keepalive, ..Default::default() };
+        let manager = Arc::new(SseManager::new(&config));
+        let mut client = connect(&manager, "fresh", 0).await;
+        read_until(&mut client, "event: endpoint").await;
+
+        // At the base interval five keepalives take 1.5s; escalated, the
+        // four after the first come 40ms apart.
+        let start = Instant::now();
+        let mut seen = String::new();
+        while seen.matches(": keepalive").count() < 5 {
+            seen.push_str(&read_until(&mut client, ": keepalive").await);
+        }
+        assert!(start.elapsed() < Duration::from_millis(1000), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_log_flood_is_rate_limited_while_responses_flow() {
+        let config = ProxyConfig { auth_required: false, sse_log_rate: 5, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, mut rx) = session(64);
+        manager.sessions.lock().await.insert("s1".into(), s);
+        let log = |i: usize| {
+            serde_json::json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {"level": "debug", "data": format!("line {}", i)}})
+        };
+
+        // No logs before the client picks a level
+        manager.route_notification("srv", log(0)).await;
+        assert!(rx.try_recv().is_err());
+
+        let set_level = r#"{"jsonrpc":"2.0","id":1,"method":"logging/setLevel","params":{"level":"debug"}}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", set_level, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+        assert!(rx.recv().await.unwrap().contains("\"id\":1"));
+
+        for i in 0..500 {
+            manager.route_notification("srv", log(i)).await;
+        }
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", ping, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.iter().filter(|e| e.contains("notifications/message")).count(), 5);
+        assert!(events[0].contains("\"logger\":\"srv\""), "{}", events[0]);
+        assert!(events.last().unwrap().contains("\"id\":2"));
+
+        // Once the window is over, the excess is summarized
+        manager.sessions.lock().await.get_mut("s1").unwrap().logs.window_start -= LOG_WINDOW;
+        manager.flush_log_summaries().await;
+        assert!(rx.try_recv().unwrap().contains("Dropped 495 backend log messages"));
+    }
+
+    #[test]
+    fn test_token_bucket_refills_up_to_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::full();
+        assert!(bucket.take(2, 1.0, 2, start).is_ok());
+        assert_eq!(bucket.take(1, 1.0, 2, start), Err(Duration::from_secs(1)));
+        assert!(bucket.take(1, 1.0, 2, start + Duration::from_secs(1)).is_ok());
+        assert!(!bucket.is_full(1.0, 2, start + Duration::from_secs(1)));
+        // A long pause refills to the burst and no further
+        assert!(bucket.is_full(1.0, 2, start + Duration::from_secs(60)));
+        assert!(bucket.take(5, 1.0, 2, start + Duration::from_secs(60)).is_ok());
+        assert!(bucket.take(1, 1.0, 2, start + Duration::from_secs(60)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_message_flood_gets_429() {
+        let rate_limit = RateLimitConfig { messages_per_sec: 0.5, message_burst: 2, ..Default::default() };
+        let config = ProxyConfig { auth_required: false, sse_rate_limit: rate_limit, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, _rx) = session(64);
+        manager.sessions.lock().await.insert("s1".into(), s);
+        let (other, _other_rx) = session(64);
+        manager.sessions.lock().await.insert("s2".into(), other);
+
+        let ping = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        for _ in 0..2 {
+            let resp = String::from_utf8(manager.handle_message("s1", ping, None, &proxy).await).unwrap();
+            assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+        }
+        let resp = String::from_utf8(manager.handle_message("s1", ping, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 429"), "{}", resp);
+        assert!(resp.contains("Retry-After: 2\r\n"), "{}", resp);
+        assert_eq!(manager.metrics().await.rate_limited, 1);
+
+        // Budgets are per session
+        let resp = String::from_utf8(manager.handle_message("s2", ping, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+    }
+
+    #[test]
+    fn test_log_gate_applies_client_level_and_floor() {
+        let mut gate = LogGate::new();
+        let now = Instant::now();
+        gate.min_level = Some(LogLevel::Warning);
+        assert!(!gate.admit(LogLevel::Info, LogLevel::Debug, 0, now));
+        assert!(gate.admit(LogLevel::Error, LogLevel::Debug, 0, now));
+
+        gate.min_level = Some(LogLevel::Debug);
+        assert!(!gate.admit(LogLevel::Info, LogLevel::Notice, 0, now));
+        assert_eq!(gate.take_dropped(now), None);
+    }
+
+    #[tokio::test]
+    async fn test_same_client_id_supersedes() {
+        let config = ProxyConfig { sse_duplicate_clients: DuplicateClients::Supersede, ..Default::default() };
+        let manager = SseManager::new(&config);
+
+        let (mut first, mut first_rx) = session(4);
+        first.client_id = Some("cursor-window-1".into());
+        let first_id = manager.register(first).await.unwrap();
+
+        let (mut second, _second_rx) = session(4);
+        second.client_id = Some("cursor-window-1".into());
+        let second_id = manager.register(second).await.unwrap();
+
+        let sessions = manager.sessions.lock().await;
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions.contains_key(&second_id) && !sessions.contains_key(&first_id));
+        drop(sessions);
+        // The old session's channel is closed, ending its stream
+        assert!(first_rx.recv().await.is_none());
+        assert_eq!(manager.stats().await.superseded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_same_client_id_allowed_by_default() {
+        let manager = SseManager::new(&ProxyConfig::default());
+        for _ in 0..2 {
+            let (mut s, _rx) = session(4);
+            s.client_id = Some("c".into());
+            manager.register(s).await;
+        }
+        assert_eq!(manager.session_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_session_survives_idle_timeout() {
+        let manager = SseManager::new(&ProxyConfig::default());
+        let idle_since = Instant::now() - Duration::from_secs(SESSION_TIMEOUT_SECS + 1);
+        let mut receivers = Vec::new();
+        for id in ["idle", "pinned"] {
+            let (mut s, rx) = session(4);
+            s.last_activity = idle_since;
+            manager.sessions.lock().await.insert(id.into(), s);
+            receivers.push(rx);
+        }
+        assert!(manager.set_pinned("pinned", true).await);
+        let timeout = Duration::from_secs(SESSION_TIMEOUT_SECS);
+        reap_stale(&manager.sessions, &manager.stats, &manager.released_tx, timeout, None).await;
+        let listed: Vec<(String, bool)> = manager.sessions().await.into_iter().map(|s| (s.id, s.pinned)).collect();
+        assert_eq!(listed, vec![("pinned".to_string(), true)]);
+
+        // Its stream dropped and it wasn't resumed: closed all the same
+        manager.sessions.lock().await.get_mut("pinned").unwrap().detached_at = Some(idle_since);
+        reap_stale(&manager.sessions, &manager.stats, &manager.released_tx, timeout, None).await;
+        assert_eq!(manager.session_count().await, 0);
+        assert!(!manager.set_pinned("pinned", false).await);
+    }
+
+    #[tokio::test]
+    async fn test_session_past_max_lifetime_closed_despite_activity() {
+        let manager = SseManager::new(&ProxyConfig::default());
+        let (mut old, mut old_rx) = session(4);
+        old.created_at = Instant::now() - Duration::from_secs(7200);
+        old.pinned = true;
+        let (young, _young_rx) = session(4);
+        manager.sessions.lock().await.insert("old".into(), old);
+        manager.sessions.lock().await.insert("young".into(), young);
+
+        let timeout = Duration::from_secs(SESSION_TIMEOUT_SECS);
+        reap_stale(&manager.sessions, &manager.stats, &manager.released_tx, timeout, Some(Duration::from_secs(3600))).await;
+        let left: Vec<String> = manager.sessions().await.into_iter().map(|s| s.id).collect();
+        assert_eq!(left, ["young"]);
+        // Told to come back rather than just cut off
+        let last = old_rx.recv().await.unwrap();
+        assert!(last.starts_with("retry: 1000\nevent: expired\n"), "{}", last);
+        assert!(old_rx.recv().await.is_none());
+        let stats = manager.stats().await;
+        assert_eq!((stats.expired, stats.reaped), (1, 0));
+    }
+
+    #[test]
+    fn test_session_id_collision_regenerates() {
+        let mut sessions = HashMap::new();
+        let (existing, _rx1) = session(1);
+        sessions.insert("dup".to_string(), existing);
+
+        let mut ids = vec!["fresh", "dup"];
+        let (new_session, _rx2) = session(1);
+        let (id, retries) = insert_unique(&mut sessions, new_session, || ids.pop().unwrap().to_string());
+        assert_eq!(id, "fresh");
+        assert_eq!(retries, 1);
+        assert_eq!(sessions.len(), 2);
+    }
+
+    #[test]
+    fn test_session_ids_random_and_url_safe() {
+        let ids: HashSet<String> = (0..100).map(|_| generate_session_id()).collect();
+        assert_eq!(ids.len(), 100);
+        assert!(ids.iter().all(|id| id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())));
+    }
+
+    #[test]
+    fn test_oversized_event_accepted_when_queue_empty() {
+        let (mut session, _rx) = session(64);
+        assert_eq!(session.enqueue("x".repeat(5000), Some(1000)), Enqueue::Sent);
+        assert_eq!(session.enqueue("y".into(), Some(1000)), Enqueue::BytesFull);
+    }
+
+    /// Backend that logs every request after the handshake to `log` and
+    /// sends a resource update before each response after the first.
+    #[cfg(unix)]
+    fn resource_server(log: &std::path::Path) -> crate::config::ServerConfig {
+        let script = format!(
+            r#"read l; echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05"}}}}'
+read l
+read l; echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[]}}}}'
+id=3; n=0
+while read l; do
+  echo "$l" >> {log}
+  if [ $n -gt 0 ]; then echo '{{"jsonrpc":"2.0","method":"notifications/resources/updated","params":{{"uri":"file:///a"}}}}'; fi
+  echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{}}}}"; id=$((id+1)); n=$((n+1))
+done"#,
+            log = log.display(),
+        );
+        crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script],
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_subscribed_session_receives_update_and_reap_unsubscribes() {
+        let log = std::env::temp_dir().join(format!("mcphub-subscribe-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        let mut config = ProxyConfig { auth_required: false, ..Default::default() };
+        config.servers.insert("res".into(), resource_server(&log));
+        let manager = Arc::new(SseManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        manager.start_router(proxy.clone());
+        let (s, mut rx) = session(16);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let subscribe = r#"{"jsonrpc":"2.0","id":1,"method":"resources/subscribe","params":{"uri":"res__file:///a"}}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", subscribe, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+
+        // The backend emits the update alongside its next response
+        let read = r#"{"jsonrpc":"2.0","id":2,"method":"resources/read","params":{"uri":"res__file:///a"}}"#;
+        manager.handle_message("s1", read, None, &proxy).await;
+        let update = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let event = rx.recv().await.unwrap();
+                if event.contains("notifications/resources/updated") {
+                    return event;
+                }
+            }
+        })
+        .await
+        .expect("no resource update routed to the session");
+        assert!(update.contains(r#""uri":"res__file:///a""#), "{}", update);
+
+        reap_stale(&manager.sessions, &manager.stats, &manager.released_tx, Duration::ZERO, None).await;
+        assert_eq!(manager.stats().await.reaped, 1);
+        let unsubscribed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let seen = std::fs::read_to_string(&log).unwrap_or_default();
+                if seen.contains("resources/unsubscribe") && seen.contains(r#""uri":"file:///a""#) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        proxy.shutdown().await;
+        let _ = std::fs::remove_file(&log);
+        assert!(unsubscribed.is_ok(), "backend was not unsubscribed after the reap");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_progress_forwarded_to_calling_session() {
+        // Reports progress on the call's token, then answers
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"work"}]}}'
+read l
+t=$(echo "$l" | sed 's/.*"progressToken":\("[^"]*"\).*/\1/')
+echo "{\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\",\"params\":{\"progressToken\":$t,\"progress\":50,\"total\":100}}"
+sleep 0.2
+echo '{"jsonrpc":"2.0","id":3,"result":{"content":[]}}'
+cat > /dev/null"#;
+        let mut config = ProxyConfig { auth_required: false, mode: crate::config::Mode::Passthrough, ..Default::default() };
+        config.servers.insert("slow".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let manager = Arc::new(SseManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        manager.start_router(proxy.clone());
+        let (caller, mut caller_rx) = session(16);
+        let (other, mut other_rx) = session(16);
+        manager.sessions.lock().await.insert("s1".into(), caller);
+        manager.sessions.lock().await.insert("s2".into(), other);
+
+        let call = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"slow__work","arguments":{},"_meta":{"progressToken":"bar"}}}"#;
+        manager.handle_message("s1", call, None, &proxy).await;
+        let mut events = Vec::new();
+        while let Ok(event) = caller_rx.try_recv() {
+            events.push(event);
+        }
+        proxy.shutdown().await;
+
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(events[0].contains("notifications/progress") && events[0].contains(r#""progressToken":"bar""#), "{}", events[0]);
+        assert!(events[1].contains(r#""id":7"#), "{}", events[1]);
+        assert!(other_rx.try_recv().is_err());
+        assert!(manager.progress_tokens.lock().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streamed_result_sent_as_chunks_then_response() {
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"cat"}]}}'
+read l
+echo '{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"one"},{"type":"text","text":"two"}]}}'
+cat > /dev/null"#;
+        let mut config = ProxyConfig { auth_required: false, mode: crate::config::Mode::Passthrough, ..Default::default() };
+        config.sse_split_events = true;
+        config.sse_stream_results = vec!["files__cat".into()];
+        config.servers.insert("files".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let manager = Arc::new(SseManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (caller, mut rx) = session(16);
+        manager.sessions.lock().await.insert("s1".into(), caller);
+
+        let call = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"files__cat","arguments":{}}}"#;
+        manager.handle_message("s1", call, None, &proxy).await;
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        proxy.shutdown().await;
+
+        assert_eq!(events.len(), 3, "{:?}", events);
+        for (i, text) in ["one", "two"].iter().enumerate() {
+            assert!(events[i].contains("event: chunk\n"), "{}", events[i]);
+            let data = events[i].lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+            let data: serde_json::Value = serde_json::from_str(data).unwrap();
+            assert_eq!(data["params"]["requestId"], 7);
+            assert_eq!(data["params"]["index"], i);
+            assert_eq!(data["params"]["content"]["text"], *text);
+        }
+        assert!(events[2].contains("event: response\n"), "{}", events[2]);
+        assert!(events[2].contains(r#""content":[]"#) && events[2].contains(r#""mcphub/chunks":2"#), "{}", events[2]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streamed_result_waits_for_the_client_to_drain() {
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"cat"}]}}'
+while read l; do
+  id=$(echo "$l" | sed 's/.*"id":\([0-9]*\).*/\1/')
+  echo "{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{\"content\":[{\"type\":\"text\",\"text\":\"one\"},{\"type\":\"text\",\"text\":\"two\"}]}}"
+done"#;
+        let mut config = ProxyConfig { auth_required: false, mode: crate::config::Mode::Passthrough, ..Default::default() };
+        config.sse_stream_results = vec!["files__cat".into()];
+        // Room for one chunk at a time
+        config.sse_max_buffered_bytes = Some(200);
+        config.sse_send_timeout_ms = 100;
+        config.servers.insert("files".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let manager = Arc::new(SseManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        let call = |id: u64| format!(r#"{{"jsonrpc":"2.0","id":{},"method":"tools/call","params":{{"name":"files__cat","arguments":{{}}}}}}"#, id);
+
+        // A client reading its stream gets every chunk, then the response
+        let (reader, mut rx) = session(16);
+        let buffered = reader.buffered_bytes.clone();
+        manager.sessions.lock().await.insert("s1".into(), reader);
+        let drain = tokio::spawn(async move {
+            let mut events = Vec::new();
+            while events.len() < 3 {
+                let event = rx.recv().await.unwrap();
+                buffered.fetch_sub(event.len(), Ordering::Relaxed);
+                events.push(event);
+            }
+            events
+        });
+        let reply = String::from_utf8(manager.handle_message("s1", &call(7), None, &proxy).await).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 202 "), "{}", reply);
+        let events = drain.await.unwrap();
+        assert!(events[0].contains("one") && events[1].contains("two") && events[2].contains(r#""mcphub/chunks":2"#), "{:?}", events);
+
+        // One that never reads gets an error instead of a silent 202
+        let (stalled, _rx) = session(16);
+        manager.sessions.lock().await.insert("s2".into(), stalled);
+        let reply = String::from_utf8(manager.handle_message("s2", &call(8), None, &proxy).await).unwrap();
+        proxy.shutdown().await;
+        assert!(reply.starts_with("HTTP/1.1 503 "), "{}", reply);
+        assert!(reply.contains(r#""id":8"#) && reply.contains("Result dropped"), "{}", reply);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_backend_sampling_request_round_trip() {
+        // Asks the client for a completion mid-call and returns its answer
+        let script = r#"read l; echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05"}}'
+read l
+read l; echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"ask"}]}}'
+read l
+echo '{"jsonrpc":"2.0","id":"b1","method":"sampling/createMessage","params":{"messages":[],"maxTokens":10}}'
+read answer
+echo "{\"jsonrpc\":\"2.0\",\"id\":3,\"result\":{\"content\":[],\"answer\":$answer}}"
+cat > /dev/null"#;
+        let mut config = ProxyConfig { auth_required: false, mode: crate::config::Mode::Passthrough, ..Default::default() };
+        config.servers.insert("llm".into(), crate::config::ServerConfig {
+            command: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            ..Default::default()
+        });
+        let manager = Arc::new(SseManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        manager.start_router(proxy.clone());
+        let (caller, mut caller_rx) = session(16);
+        let (other, mut other_rx) = session(16);
+        manager.sessions.lock().await.insert("caller".into(), caller);
+        manager.sessions.lock().await.insert("other".into(), other);
+        let init = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{"sampling":{}}}}"#;
+        for (id, rx) in [("caller", &mut caller_rx), ("other", &mut other_rx)] {
+            manager.handle_message(id, init, None, &proxy).await;
+            rx.recv().await.unwrap();
+        }
+
+        // Only the session whose call caused it gets the request
+        let call = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"llm__ask","arguments":{}}}"#;
+        let pending = {
+            let (manager, proxy) = (manager.clone(), proxy.clone());
+            tokio::spawn(async move { manager.handle_message("caller", call, None, &proxy).await })
+        };
+        let request = tokio::time::timeout(Duration::from_secs(5), caller_rx.recv()).await.unwrap().unwrap();
+        assert!(request.contains("sampling/createMessage"), "{}", request);
+        assert!(other_rx.try_recv().is_err());
+        let data: serde_json::Value = serde_json::from_str(request.split("data: ").nth(1).unwrap().trim()).unwrap();
+        let answer = serde_json::json!({"jsonrpc": "2.0", "id": data["id"], "result": {"role": "assistant", "content": {"type": "text", "text": "hi"}}});
+        // Another session can't answer it
+        assert!(!manager.deliver_client_response("other", answer.clone()).await);
+        let accepted = String::from_utf8(manager.handle_message("caller", &answer.to_string(), None, &proxy).await).unwrap();
+        assert!(accepted.starts_with("HTTP/1.1 200"), "{}", accepted);
+
+        pending.await.unwrap();
+        let response = caller_rx.recv().await.unwrap();
+        proxy.shutdown().await;
+        // The backend got the answer under its own request id
+        assert!(response.contains(r#""id":"b1""#) && response.contains(r#""text":"hi""#), "{}", response);
+        assert!(manager.pending_client_requests.lock().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_late_response_after_session_gone_is_dropped() {
+        let log = std::env::temp_dir().join(format!("mcphub-late-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        let mut config = ProxyConfig { auth_required: false, sse_buffer_late_responses: false, ..Default::default() };
+        config.servers.insert("res".into(), resource_server(&log));
+        let manager = Arc::new(SseManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        manager.start_router(proxy.clone());
+        let (s, _rx) = session(16);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        // The session is reaped while the backend is still starting up
+        let subscribe = r#"{"jsonrpc":"2.0","id":1,"method":"resources/subscribe","params":{"uri":"res__file:///a"}}"#;
+        let pending = {
+            let (manager, proxy) = (manager.clone(), proxy.clone());
+            tokio::spawn(async move { manager.handle_message("s1", subscribe, None, &proxy).await })
+        };
+        reap_stale(&manager.sessions, &manager.stats, &manager.released_tx, Duration::ZERO, None).await;
+        let resp = String::from_utf8(pending.await.unwrap()).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 404"), "{}", resp);
+        assert_eq!(manager.metrics().await.late_responses_dropped, 1);
+        // The subscription it made is released rather than leaked
+        let unsubscribed = tokio::time::timeout(Duration::from_secs(5), async {
+            while !std::fs::read_to_string(&log).unwrap_or_default().contains("resources/unsubscribe") {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+
+        // A detached session doesn't keep it for replay without bufferLateResponses
+        let (mut s, _rx) = session(16);
+        s.detached_at = Some(Instant::now());
+        manager.sessions.lock().await.insert("s2".into(), s);
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+        let resp = String::from_utf8(manager.handle_message("s2", ping, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 410"), "{}", resp);
+        assert_eq!(manager.sessions.lock().await["s2"].last_event_id, 0);
+        assert_eq!(manager.metrics().await.late_responses_dropped, 2);
+
+        proxy.shutdown().await;
+        let _ = std::fs::remove_file(&log);
+        assert!(unsubscribed.is_ok(), "backend was not unsubscribed after the late response");
+    }
+
+    #[test]
+    fn test_bearer_matches() {
+        assert!(bearer_matches("Bearer mcphub_abc", "mcphub_abc"));
+        assert!(!bearer_matches("Bearer mcphub_abd", "mcphub_abc"));
+        assert!(!bearer_matches("Bearer mcphub_ab", "mcphub_abc"));
+        assert!(!bearer_matches("mcphub_abc", "mcphub_abc"));
+    }
+
+    #[tokio::test]
+    async fn test_message_without_token_is_unauthorized() {
+        let config = ProxyConfig::default();
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, _rx) = session(4);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        let resp = String::from_utf8(manager.handle_message("s1", request, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 401"), "{}", resp);
+        // Rejected before the body is parsed
+        let resp = String::from_utf8(manager.handle_message("s1", "not json", None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 401"), "{}", resp);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_message_gets_jsonrpc_error() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, mut rx) = session(4);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let resp = String::from_utf8(manager.handle_message("s1", "not json", None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 400"), "{}", resp);
+        assert!(resp.contains(r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Parse error: "#), "{}", resp);
+        let resp = String::from_utf8(manager.handle_message("s1", r#"{"jsonrpc":"2.0"}"#, None, &proxy).await).unwrap();
+        assert!(resp.contains(r#""id":null,"error":{"code":-32600"#), "{}", resp);
+
+        // With an id, the error is the request's answer on the stream
+        let resp = String::from_utf8(manager.handle_message("s1", r#"{"jsonrpc":"2.0","id":7}"#, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+        let event = rx.recv().await.unwrap();
+        assert!(event.contains(r#""id":7,"error":{"code":-32600,"message":"Invalid Request: "#), "{}", event);
+    }
+
+    #[tokio::test]
+    async fn test_batch_message() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = SseManager::new(&config);
+        let proxy = Arc::new(ProxyServer::new(config));
+        let (s, mut rx) = session(4);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let batch = r#"[
+            {"jsonrpc":"2.0","id":1,"method":"ping"},
+            {"jsonrpc":"2.0","method":"notifications/initialized"},
+            {"jsonrpc":"2.0","id":2,"method":"logging/setLevel","params":{"level":"bogus"}},
+            {"id":3}
+        ]"#;
+        let resp = String::from_utf8(manager.handle_message("s1", batch, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 202"), "{}", resp);
+        let event = rx.recv().await.unwrap();
+        let data = event.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+        let responses: Vec<serde_json::Value> = serde_json::from_str(data).unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[1]["error"]["code"], -32602);
+        assert_eq!((&responses[2]["id"], &responses[2]["error"]["code"]), (&serde_json::json!(3), &serde_json::json!(-32600)));
+
+        // Notifications only: nothing goes out on the stream
+        let batch = r#"[{"jsonrpc":"2.0","method":"notifications/initialized"}]"#;
+        let resp = String::from_utf8(manager.handle_message("s1", batch, None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "{}", resp);
+        assert!(rx.try_recv().is_err());
+
+        let resp = String::from_utf8(manager.handle_message("s1", "[]", None, &proxy).await).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 400"), "{}", resp);
+    }
+
+    #[tokio::test]
+    async fn test_events_keep_send_order_under_concurrent_producers() {
+        let manager = Arc::new(SseManager::new(&ProxyConfig::default()));
+        let (s, mut rx) = session(1024);
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let producers: Vec<_> = (0..4)
+            .map(|p| {
+                let manager = manager.clone();
+                tokio::spawn(async move {
+                    for i in 0..100 {
+                        let kind = if i % 2 == 0 { EventKind::Response } else { EventKind::Notification };
+                        let event = manager.frame(kind, &format!("{} {}", p, i));
+                        let mut sessions = manager.sessions.lock().await;
+                        assert_eq!(sessions.get_mut("s1").unwrap().enqueue(event, None), Enqueue::Sent);
+                        drop(sessions);
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.await.unwrap();
+        }
+
+        let mut last = [None::<u32>; 4];
+        for _ in 0..400 {
+            let event = rx.recv().await.unwrap();
+            let data = event.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+            let (p, i) = data.split_once(' ').unwrap();
+            let (p, i): (usize, u32) = (p.parse().unwrap(), i.parse().unwrap());
+            assert!(last[p].is_none_or(|prev| prev < i), "producer {} out of order", p);
+            last[p] = Some(i);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_split_event_types() {
+        let config = ProxyConfig { sse_split_events: true, ..Default::default() };
+        let manager = SseManager::new(&config);
+        assert!(manager.frame(EventKind::Response, "{}").starts_with("event: response\n"));
+        assert!(manager.frame(EventKind::Notification, "{}").starts_with("event: notification\n"));
+    }
+
+    #[tokio::test]
+    async fn test_multiline_and_oversized_event_data() {
+        let config = ProxyConfig { sse_max_event_bytes: Some(64), ..Default::default() };
+        let manager = SseManager::new(&config);
+        assert_eq!(manager.frame(EventKind::Response, "{\n\"a\":1\r\n}\r"), "event: message\ndata: {\ndata: \"a\":1\ndata: }\ndata: \n\n");
+
+        // A response too big for the stream still answers its id
+        let big = format!("{{\"jsonrpc\":\"2.0\",\"id\":7,\"result\":{{\"text\":\"{}\"}}}}", "x".repeat(100));
+        let event = manager.frame(EventKind::Response, &big);
+        let data: serde_json::Value = serde_json::from_str(event.strip_prefix("event: message\ndata: ").unwrap().trim()).unwrap();
+        assert_eq!(data["id"], 7);
+        assert!(data["error"]["message"].as_str().unwrap().contains("exceeds sse.maxEventBytes (64)"));
+
+        // A notification is dropped with a warning in its place
+        let big = format!("{{\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\",\"params\":\"{}\"}}", "x".repeat(100));
+        let event = manager.frame(EventKind::Notification, &big);
+        assert!(event.contains("\"method\":\"notifications/message\"") && event.contains("\"level\":\"warning\""), "{}", event);
+    }
+
+    #[test]
+    fn test_replay_after_last_event_id() {
+        let (mut s, _rx) = session(256);
+        for i in 1..=130 {
+            s.push_event(&format!("data: {}\n\n", i), None);
+        }
+        // Only the last REPLAY_BUFFER_EVENTS are kept: ids 3..=130
+        match s.replay_after(127) {
+            Replay::Events(events) => {
+                assert_eq!(events.len(), 3);
+                assert!(events[0].starts_with("id: 128\n"));
+            }
+            Replay::Resync => panic!("expected replay"),
+        }
+        assert!(matches!(s.replay_after(2), Replay::Events(e) if e.len() == 128));
+        assert!(matches!(s.replay_after(1), Replay::Resync));
+        assert!(matches!(s.replay_after(130), Replay::Events(e) if e.is_empty()));
+    }
+
+    /// Run `handle_connect` on a loopback connection; returns the client end.
+    async fn connect(manager: &Arc<SseManager>, resume: &str, last_event_id: u64) -> TcpStream {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let info = ConnectInfo {
+            profile: &quirks::DEFAULT,
+            host: None,
+            client_id: None,
+            authorization: None,
+            resume_session: Some(resume.to_string()),
+            last_event_id: Some(last_event_id),
+            origin: None,
+            pinned: false,
+            peer: server.peer_addr().ok().map(|addr| addr.ip()),
+            scope: None,
+        };
+        let manager = manager.clone();
+        tokio::spawn(async move { manager.handle_connect(server, info).await });
+        client
+    }
+
+    /// Read from `client` until `needle` shows up; returns everything read.
+    async fn read_until(client: &mut TcpStream, needle: &str) -> String {
+        use tokio::io::AsyncReadExt;
+        let mut seen = String::new();
+        let mut buf = [0u8; 4096];
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !seen.contains(needle) {
+                let n = client.read(&mut buf).await.unwrap();
+                assert!(n > 0, "stream closed before {:?}: {}", needle, seen);
+                seen.push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {:?}: {}", needle, seen));
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_connect_beyond_max_sessions_gets_503() {
+        let config = ProxyConfig { auth_required: false, sse_max_sessions: 2, ..Default::default() };
+        let manager = Arc::new(SseManager::new(&config));
+        let mut first = connect(&manager, "fresh", 0).await;
+        let endpoint = read_until(&mut first, "event: endpoint").await;
+        let first_id = endpoint.split("sessionId=").nth(1).unwrap().split_whitespace().next().unwrap().to_string();
+        let mut second = connect(&manager, "fresh", 0).await;
+        read_until(&mut second, "event: endpoint").await;
+
+        let mut third = connect(&manager, "fresh", 0).await;
+        let rejected = read_until(&mut third, "Too many SSE sessions").await;
+        assert!(rejected.starts_with("HTTP/1.1 503"), "{}", rejected);
+        assert!(!rejected.contains("text/event-stream"));
+        assert_eq!(manager.stats().await.rejected_at_capacity, 1);
+        assert_eq!((manager.session_count().await, manager.max_sessions()), (2, 2));
+
+        // Resuming an existing session needs no new slot
+        drop(first);
+        let mut resumed = connect(&manager, &first_id, 0).await;
+        read_until(&mut resumed, "event: endpoint").await;
+        assert_eq!(manager.session_count().await, 2);
+
+        // A closed session (as the reaper does) frees its slot
+        manager.sessions.lock().await.remove(&first_id);
+        let mut fourth = connect(&manager, "fresh", 0).await;
+        read_until(&mut fourth, "event: endpoint").await;
+    }
+
+    /// Takes a few bytes per write, failing with `failures` first.
+    struct FlakyWriter {
+        failures: Vec<std::io::ErrorKind>,
+        written: Vec<u8>,
+    }
+
+    impl AsyncWrite for FlakyWriter {
+        fn poll_write(mut self: std::pin::Pin<&mut Self>, _: &mut std::task::Context<'_>, buf: &[u8]) -> std::task::Poll<std::io::Result<usize>> {
+            if !self.failures.is_empty() {
+                let kind = self.failures.remove(0);
+                return std::task::Poll::Ready(Err(kind.into()));
+            }
+            let n = buf.len().min(7);
+            self.written.extend_from_slice(&buf[..n]);
+            std::task::Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: std::pin::Pin<&mut Self>, _: &mut std::task::Context<'_>) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: std::pin::Pin<&mut Self>, _: &mut std::task::Context<'_>) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_errors_tell_slow_consumers_from_disconnects() {
+        let mut gone = FlakyWriter { failures: vec![std::io::ErrorKind::BrokenPipe], written: Vec::new() };
+        let err = write_and_flush(&mut gone, b"data: {}\n\n").await.unwrap_err();
+        assert!(matches!(err, WriteError::Write(_)) && !err.slow_consumer(), "{}", err);
+
+        // Nobody reads the other end of a small pipe
+        let (mut client, _server) = tokio::io::duplex(16);
+        let err = write_within(&mut client, &[b'x'; 64], Duration::from_millis(50)).await.unwrap_err();
+        assert!(matches!(err, WriteError::Stalled(_)) && err.slow_consumer());
+        assert_eq!(err.to_string(), "write stalled for 50ms");
+
+        let mut stats = SessionStats::new();
+        stats.record(CloseReason::SlowConsumer, 10);
+        assert_eq!((stats.slow_consumer, stats.disconnected), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_send_retries_transient_failures_only() {
+        use std::io::ErrorKind;
+        let event = b"event: endpoint\ndata: /message?sessionId=s1\n\n";
+        let mut flaky = FlakyWriter { failures: vec![ErrorKind::Interrupted, ErrorKind::WouldBlock], written: Vec::new() };
+        send_endpoint(&mut flaky, event).await.unwrap();
+        assert_eq!(flaky.written, event);
+
+        let mut gone = FlakyWriter { failures: vec![ErrorKind::BrokenPipe], written: Vec::new() };
+        let err = send_endpoint(&mut gone, event).await.unwrap_err();
+        assert!(err.contains("after 1 attempt(s), 0 of"), "{}", err);
+
+        let mut stuck = FlakyWriter { failures: vec![ErrorKind::Interrupted; 3], written: Vec::new() };
+        assert!(send_endpoint(&mut stuck, event).await.unwrap_err().contains("after 3 attempt(s)"));
+    }
+
+    #[tokio::test]
+    async fn test_connects_from_one_ip_are_rate_limited() {
+        let rate_limit = RateLimitConfig { connects_per_sec: 0.1, connect_burst: 1, ..Default::default() };
+        let config = ProxyConfig { auth_required: false, sse_rate_limit: rate_limit, ..Default::default() };
+        let manager = Arc::new(SseManager::new(&config));
+        let mut first = connect(&manager, "fresh", 0).await;
+        read_until(&mut first, "event: endpoint").await;
+
+        let mut second = connect(&manager, "fresh", 0).await;
+        let rejected = read_until(&mut second, "Too many requests").await;
+        assert!(rejected.starts_with("HTTP/1.1 429"), "{}", rejected);
+        assert!(rejected.contains("Retry-After: 10\r\n"), "{}", rejected);
+        assert_eq!(manager.session_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_resume_replays_missed_events() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = Arc::new(SseManager::new(&config));
+
+        // A session whose stream dropped, with events queued since
+        let (mut s, rx) = session(4);
+        drop(rx);
+        s.detached_at = Some(Instant::now());
+        for i in 1..=3 {
+            assert_eq!(s.push_event(&manager.frame(EventKind::Response, &format!("{{\"n\":{}}}", i)), None), Enqueue::Closed);
+        }
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let mut client = connect(&manager, "s1", 1).await;
+        let replayed = read_until(&mut client, "id: 3\n").await;
+        assert!(replayed.contains("sessionId=s1"), "{}", replayed);
+        assert!(!replayed.contains("id: 1\n"), "{}", replayed);
+        assert!(replayed.find("id: 2\n").unwrap() < replayed.find("id: 3\n").unwrap());
+
+        // Live events continue the sequence
+        manager.sessions.lock().await.get_mut("s1").unwrap().push_event("data: {}\n\n", None);
+        read_until(&mut client, "id: 4\n").await;
+    }
+
+    #[tokio::test]
+    async fn test_session_restored_after_restart_resumes() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-sessions-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("sessions.json");
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+
+        let before = Arc::new(SseManager::new(&config));
+        let (mut s, _rx) = session(4);
+        s.initialized = true;
+        s.logs.min_level = Some(LogLevel::Warning);
+        s.subscriptions.insert("fs__file:///notes".into());
+        s.push_event("data: {}\n\n", None);
+        before.sessions.lock().await.insert("s1".into(), s);
+        let mut stored = stored_sessions(&*before.sessions.lock().await, unix_now());
+        // Idle past the session timeout: not restored
+        let (old, _rx) = session(4);
+        let mut old = stored_sessions(&HashMap::from([("old".to_string(), old)]), unix_now());
+        old[0].last_active -= SESSION_TIMEOUT_SECS + 1;
+        stored.append(&mut old);
+        write_store(&path, &serde_json::to_string(&stored).unwrap()).unwrap();
+
+        let after = Arc::new(SseManager::new(&config).with_session_store(path.clone()));
+        assert_eq!(after.session_count().await, 1);
+        let mut client = connect(&after, "s1", 1).await;
+        let endpoint = read_until(&mut client, "event: endpoint").await;
+        assert!(endpoint.contains("sessionId=s1"), "{}", endpoint);
+        {
+            let mut sessions = after.sessions.lock().await;
+            let restored = sessions.get_mut("s1").unwrap();
+            assert!(restored.initialized && restored.detached_at.is_none());
+            assert_eq!(restored.logs.min_level, Some(LogLevel::Warning));
+            assert!(restored.subscriptions.contains("fs__file:///notes"));
+            // Event ids continue where the previous process stopped
+            restored.push_event("data: {}\n\n", None);
+        }
+        let live = read_until(&mut client, "id: 2\n").await;
+        assert!(!live.contains("event: resync"), "{}", live);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_late_store_write_keeps_the_newer_snapshot() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-store-writes-{}", std::process::id()));
+        let path = dir.join("sessions.json");
+        let writes = StoreWrites::default();
+        let (older, newer) = (writes.take(), writes.take());
+        writes.write(&path, "[2]", newer).unwrap();
+        writes.write(&path, "[1]", older).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[2]");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_events_buffered_at_shutdown_replayed_after_restart() {
+        let dir = std::env::temp_dir().join(format!("mcphub-test-session-events-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("sessions.json");
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+
+        let before = SseManager::new(&config).with_session_store(path.clone());
+        let (mut s, _rx) = session(4);
+        for i in 1..=2 {
+            s.push_event(&before.frame(EventKind::Response, &format!("{{\"n\":{}}}", i)), None);
+        }
+        before.sessions.lock().await.insert("s1".into(), s);
+        before.shutdown().await;
+        // Session ids and tool results: readable by the owner only
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let after = Arc::new(SseManager::new(&config).with_session_store(path.clone()));
+        // The client saw event 1 before the restart
+        let mut client = connect(&after, "s1", 1).await;
+        let replayed = read_until(&mut client, "id: 2\n").await;
+        assert!(replayed.contains(r#"{"n":2}"#) && !replayed.contains(r#"{"n":1}"#), "{}", replayed);
+        assert!(!replayed.contains("event: resync"), "{}", replayed);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_resume_past_buffer_sends_resync() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = Arc::new(SseManager::new(&config));
+        let (mut s, rx) = session(4);
+        drop(rx);
+        s.detached_at = Some(Instant::now());
+        for _ in 0..REPLAY_BUFFER_EVENTS + 5 {
+            s.push_event("data: {}\n\n", None);
+        }
+        manager.sessions.lock().await.insert("s1".into(), s);
+
+        let mut client = connect(&manager, "s1", 0).await;
+        read_until(&mut client, "event: resync").await;
+    }
 }
@@ -0,0 +1,132 @@
+/// Backend stderr: forwarded line by line to the hub's log, at most
+/// `max_per_sec` lines per second per instance. Lines over the cap are
+/// counted and reported as one "suppressed" line when the next second
+/// starts, so a flooding backend is still sampled. The last lines are kept
+/// regardless, to explain a crash.
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Default cap on forwarded stderr lines per second.
+pub const DEFAULT_MAX_LINES_PER_SEC: u32 = 20;
+/// Lines kept in an instance's tail.
+const TAIL_LINES: usize = 50;
+/// Longest line forwarded; the rest is cut.
+const MAX_LINE_CHARS: usize = 1000;
+/// Bytes of a line kept while reading it, enough for `MAX_LINE_CHARS` of
+/// any UTF-8; the rest is skipped rather than buffered.
+const MAX_LINE_BYTES: usize = MAX_LINE_CHARS * 4;
+
+/// Most recent stderr lines of one instance, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct StderrTail(Arc<Mutex<VecDeque<String>>>);
+
+impl StderrTail {
+    fn push(&self, line: String) {
+        let mut lines = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn last(&self) -> Option<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).back().cloned()
+    }
+}
+
+/// Read `reader` to the end, passing the lines to log to `emit`.
+pub async fn forward<R: AsyncRead + Unpin>(
+    reader: R,
+    max_per_sec: u32,
+    tail: StderrTail,
+    mut emit: impl FnMut(String),
+) {
+    let mut reader = BufReader::new(reader);
+    let mut window = Instant::now();
+    let (mut sent, mut suppressed) = (0u32, 0u64);
+    let summary = |suppressed: u64| format!("... suppressed {} line(s)", suppressed);
+
+    while let Some(line) = read_line(&mut reader).await {
+        let line: String = line.chars().take(MAX_LINE_CHARS).collect();
+        if window.elapsed() >= Duration::from_secs(1) {
+            if suppressed > 0 {
+                emit(summary(suppressed));
+            }
+            window = Instant::now();
+            (sent, suppressed) = (0, 0);
+        }
+        if sent < max_per_sec {
+            sent += 1;
+            emit(line.clone());
+        } else {
+            suppressed += 1;
+        }
+        tail.push(line);
+    }
+    if suppressed > 0 {
+        emit(summary(suppressed));
+    }
+}
+
+/// The next line, without its line ending, cut to `MAX_LINE_BYTES`.
+/// Bytes that aren't UTF-8 are replaced, so a binary line doesn't end the
+/// forwarding. None at the end of the stream or on a read error.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Option<String> {
+    let mut line = Vec::new();
+    loop {
+        let available = reader.fill_buf().await.ok()?;
+        if available.is_empty() {
+            return (!line.is_empty()).then(|| decode(&line));
+        }
+        let newline = available.iter().position(|&b| b == b'\n');
+        let chunk = &available[..newline.unwrap_or(available.len())];
+        let room = MAX_LINE_BYTES.saturating_sub(line.len());
+        line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        let used = newline.map_or(available.len(), |i| i + 1);
+        reader.consume(used);
+        if newline.is_some() {
+            return Some(decode(&line));
+        }
+    }
+}
+
+fn decode(line: &[u8]) -> String {
+    String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_flooding_backend_is_rate_limited() {
+        let flood: String = (0..500).map(|i| format!("line {}\n", i)).collect();
+        let tail = StderrTail::default();
+        let mut out = Vec::new();
+        forward(flood.as_bytes(), 10, tail.clone(), |line| out.push(line)).await;
+
+        assert_eq!(out.len(), 11);
+        assert_eq!(out[0], "line 0");
+        assert_eq!(out[10], "... suppressed 490 line(s)");
+        // The tail still has the latest lines, suppressed or not
+        assert_eq!(tail.last().as_deref(), Some("line 499"));
+        assert_eq!(tail.0.lock().unwrap().len(), TAIL_LINES);
+    }
+
+    #[tokio::test]
+    async fn test_binary_and_overlong_lines_are_forwarded() {
+        let mut input = b"bad \xff\xfe bytes\r\n".to_vec();
+        input.extend(vec![b'x'; MAX_LINE_BYTES * 3]);
+        input.extend_from_slice(b"\nlast");
+        let mut out = Vec::new();
+        forward(&input[..], 10, StderrTail::default(), |line| out.push(line)).await;
+
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0], "bad \u{fffd}\u{fffd} bytes");
+        assert_eq!(out[1], "x".repeat(MAX_LINE_CHARS));
+        assert_eq!(out[2], "last");
+    }
+}
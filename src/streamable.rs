@@ -0,0 +1,428 @@
+/// Streamable HTTP transport for MCP protocol (2025 spec), the successor of
+/// the HTTP+SSE transport in `sse.rs`. Everything goes through `/mcp`:
+///
+/// - `POST /mcp` carries one JSON-RPC message or a batch. Requests are
+///   answered in the response body: `application/json`, or a short SSE
+///   stream when the client accepts `text/event-stream` (kept alive while
+///   slow calls run, closed after the last response)
+/// - `initialize` opens a session; its id comes back in `Mcp-Session-Id`
+///   and every later POST must send it (404 once the session is gone)
+/// - `DELETE /mcp` ends a session
+/// - `GET /mcp` answers 405: there is no server-initiated stream, so
+///   backend notifications (resource updates, logs) stay SSE-only
+///
+/// Sessions live in one map cleaned by a reaper, as in `SseManager`, and
+/// take the same Bearer token and origin checks.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::config::{ProxyConfig, DEFAULT_BIND_ADDR};
+use crate::error;
+use crate::http::{gzip_response, with_headers, HttpRequest};
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse};
+use crate::proxy::ProxyServer;
+use crate::sse::{authorized, generate_session_id, http_response, unauthorized, write_and_flush};
+
+/// A Streamable HTTP client session.
+struct StreamableSession {
+    last_activity: Instant,
+    created_at: Instant,
+}
+
+/// Manages Streamable HTTP sessions.
+pub struct StreamableManager {
+    sessions: Arc<Mutex<HashMap<String, StreamableSession>>>,
+    /// Further `initialize` requests get 503 (shares `sse.maxSessions`).
+    max_sessions: usize,
+    keepalive_interval: Duration,
+    allowed_origins: Vec<String>,
+    require_auth: bool,
+    auto_generate_token: bool,
+    /// Address the HTTP server is bound to, for token lookup.
+    bind_addr: SocketAddr,
+    /// Size from which JSON replies are gzipped; None when compression is off.
+    gzip_threshold: Option<usize>,
+}
+
+/// How a POST is answered, from its `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReplyMode {
+    Json,
+    EventStream,
+}
+
+impl StreamableManager {
+    pub fn new(config: &ProxyConfig) -> Self {
+        let manager = Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            max_sessions: config.sse_max_sessions.max(1),
+            keepalive_interval: config.sse_keepalive.interval,
+            allowed_origins: config.cors_allowed_origins.clone(),
+            require_auth: config.auth_required,
+            auto_generate_token: config.auth_auto_generate_token,
+            bind_addr: DEFAULT_BIND_ADDR.parse().expect("valid default bind address"),
+            gzip_threshold: config.http_compression.then_some(config.http_compression_threshold),
+        };
+        let sessions = manager.sessions.clone();
+        let reaper = config.sse_reaper;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(reaper.interval).await;
+                reap_stale(&sessions, reaper.session_timeout, reaper.max_lifetime).await;
+            }
+        });
+        manager
+    }
+
+    /// Address the HTTP server is bound to (DEFAULT_BIND_ADDR otherwise).
+    pub fn with_bind_addr(mut self, bind_addr: SocketAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// Handle any request to /mcp and write its response to `stream`.
+    pub async fn handle(&self, mut stream: TcpStream, req: &HttpRequest, proxy: Arc<ProxyServer>) {
+        let origin = req.headers.get("origin").map(|o| o.as_str());
+        let mut cors = crate::dashboard::cors_headers(&self.allowed_origins, origin, false);
+        if !cors.is_empty() {
+            cors.push_str("Access-Control-Expose-Headers: Mcp-Session-Id\r\n");
+        }
+        let response = match self.check_request(req) {
+            Err(response) => response,
+            Ok(()) => match req.method.as_str() {
+                "POST" => match self.handle_post(&mut stream, req, &cors, proxy).await {
+                    Some(response) => response,
+                    None => return, // Streamed
+                },
+                "DELETE" => self.handle_delete(req).await,
+                _ => respond(405, "Method Not Allowed", "Allow: POST, DELETE\r\n", "{\"error\":\"Use POST or DELETE\"}"),
+            },
+        };
+        let _ = stream.write_all(&gzip_response(with_headers(response, &cors), req, self.gzip_threshold)).await;
+        let _ = stream.shutdown().await;
+    }
+
+    /// Origin allowlist (against DNS rebinding), then the Bearer token.
+    fn check_request(&self, req: &HttpRequest) -> Result<(), Vec<u8>> {
+        if let Some(origin) = req.headers.get("origin") {
+            if !crate::dashboard::origin_allowed(&self.allowed_origins, origin) {
+                tracing::warn!(tag = "HTTP", "Rejected /mcp request from origin {}", origin);
+                return Err(http_response(403, "Forbidden", "{\"error\":\"Origin not allowed\"}"));
+            }
+        }
+        let authorization = req.headers.get("authorization").map(|a| a.as_str());
+        if !authorized(self.require_auth, self.auto_generate_token, self.bind_addr, authorization) {
+            return Err(unauthorized());
+        }
+        Ok(())
+    }
+
+    /// Handle POST /mcp. Returns the response to write, or None if the
+    /// replies were already streamed to `stream`.
+    async fn handle_post(
+        &self,
+        stream: &mut TcpStream,
+        req: &HttpRequest,
+        cors: &str,
+        proxy: Arc<ProxyServer>,
+    ) -> Option<Vec<u8>> {
+        let (messages, batch) = match parse_messages(&req.body) {
+            Ok(parsed) => parsed,
+            Err(body) => return Some(http_response(400, "Bad Request", &body)),
+        };
+
+        let initialize = messages.iter().any(|m| m.method == "initialize");
+        let session_id = if initialize {
+            match self.register().await {
+                Some(id) => id,
+                None => return Some(http_response(503, "Service Unavailable", "{\"error\":\"Too many sessions\"}")),
+            }
+        } else {
+            let Some(id) = req.headers.get("mcp-session-id") else {
+                return Some(http_response(400, "Bad Request", "{\"error\":\"Missing Mcp-Session-Id header\"}"));
+            };
+            if !self.touch(id).await {
+                return Some(http_response(404, "Not Found", "{\"error\":\"Session not found, initialize again\"}"));
+            }
+            id.clone()
+        };
+        let session_header = format!("Mcp-Session-Id: {}\r\n", session_id);
+
+        if proxy.is_shutting_down() {
+            return Some(http_response(503, "Service Unavailable", "{\"error\":\"Server shutting down\"}"));
+        }
+
+        // Requests run concurrently; their responses arrive as they finish
+        let expected = messages.iter().filter(|m| m.id.is_some()).count();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for message in messages {
+            let (proxy, tx, session_id) = (proxy.clone(), tx.clone(), session_id.clone());
+            tokio::spawn(async move {
+                if let Some(response) = dispatch(&proxy, &session_id, message).await {
+                    let _ = tx.send(response);
+                }
+            });
+        }
+        drop(tx);
+
+        // Only notifications (or client responses): nothing to answer
+        if expected == 0 {
+            return Some(respond(202, "Accepted", &session_header, ""));
+        }
+
+        match reply_mode(req.headers.get("accept").map(|a| a.as_str())) {
+            ReplyMode::Json => {
+                let mut responses = Vec::with_capacity(expected);
+                while let Some(response) = rx.recv().await {
+                    responses.push(response);
+                }
+                if initialize && responses.iter().any(|r| r.error.is_some()) {
+                    self.sessions.lock().await.remove(&session_id);
+                }
+                let body = match responses.first() {
+                    None => return Some(respond(202, "Accepted", &session_header, "")),
+                    Some(response) if !batch => serde_json::to_string(response),
+                    Some(_) => serde_json::to_string(&responses),
+                };
+                match body {
+                    Ok(body) => Some(respond(200, "OK", &session_header, &body)),
+                    Err(e) => {
+                        tracing::error!(tag = "HTTP", "Serialize error: {}", e);
+                        Some(http_response(500, "Internal Server Error", "{\"error\":\"Serialize failed\"}"))
+                    }
+                }
+            }
+            ReplyMode::EventStream => {
+                let headers = format!(
+                    "HTTP/1.1 200 OK\r\n\
+                     Content-Type: text/event-stream\r\n\
+                     Cache-Control: no-cache\r\n\
+                     Connection: close\r\n\
+                     {}{}\r\n",
+                    session_header, cors
+                );
+                if write_and_flush(stream, headers.as_bytes()).await.is_err() {
+                    return None;
+                }
+                loop {
+                    tokio::select! {
+                        response = rx.recv() => {
+                            let Some(response) = response else { break };
+                            if initialize && response.error.is_some() {
+                                self.sessions.lock().await.remove(&session_id);
+                            }
+                            let Ok(json) = serde_json::to_string(&response) else { continue };
+                            let event = crate::sse::sse_event("message", &json);
+                            if write_and_flush(stream, event.as_bytes()).await.is_err() {
+                                break;
+                            }
+                        }
+                        _ = tokio::time::sleep(self.keepalive_interval) => {
+                            if write_and_flush(stream, b": keepalive\n\n").await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                let _ = stream.shutdown().await;
+                None
+            }
+        }
+    }
+
+    /// Handle DELETE /mcp: end the session named by `Mcp-Session-Id`.
+    async fn handle_delete(&self, req: &HttpRequest) -> Vec<u8> {
+        let Some(id) = req.headers.get("mcp-session-id") else {
+            return http_response(400, "Bad Request", "{\"error\":\"Missing Mcp-Session-Id header\"}");
+        };
+        match self.sessions.lock().await.remove(id) {
+            Some(session) => {
+                tracing::info!(
+                    tag = "HTTP", "Session {} ended by client after {}s",
+                    id,
+                    session.created_at.elapsed().as_secs()
+                );
+                http_response(200, "OK", "{\"ok\":true}")
+            }
+            None => http_response(404, "Not Found", "{\"error\":\"Session not found\"}"),
+        }
+    }
+
+    /// Open a session under a fresh id. None if `max_sessions` are open.
+    async fn register(&self) -> Option<String> {
+        let mut sessions = self.sessions.lock().await;
+        if sessions.len() >= self.max_sessions {
+            tracing::warn!("Streamable HTTP session limit ({}) reached, rejecting initialize", self.max_sessions);
+            return None;
+        }
+        let mut id = generate_session_id();
+        while sessions.contains_key(&id) {
+            id = generate_session_id();
+        }
+        let now = Instant::now();
+        sessions.insert(id.clone(), StreamableSession { last_activity: now, created_at: now });
+        tracing::info!(tag = "HTTP", "Streamable HTTP session opened: {} ({} open)", id, sessions.len());
+        Some(id)
+    }
+
+    /// Record activity on a session; false if it doesn't exist.
+    async fn touch(&self, session_id: &str) -> bool {
+        match self.sessions.lock().await.get_mut(session_id) {
+            Some(session) => {
+                session.last_activity = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Parse a POST body: one message or a non-empty batch. Client responses
+/// (no `method`) are accepted and dropped, as nothing here sends requests.
+/// Fails with the JSON-RPC error body to answer.
+fn parse_messages(body: &str) -> Result<(Vec<JsonRpcRequest>, bool), String> {
+    let value: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| error::body(None, error::PARSE_ERROR, format!("Parse error: {}", e)))?;
+    let (values, batch) = match value {
+        serde_json::Value::Array(values) if values.is_empty() => {
+            return Err(error::body(None, error::INVALID_REQUEST, "Invalid Request: empty batch".into()));
+        }
+        serde_json::Value::Array(values) => (values, true),
+        value => (vec![value], false),
+    };
+    let mut messages = Vec::new();
+    for value in values {
+        if value.get("method").is_none() && (value.get("result").is_some() || value.get("error").is_some()) {
+            continue;
+        }
+        let id = value.get("id").cloned();
+        messages.push(
+            serde_json::from_value::<JsonRpcRequest>(value)
+                .map_err(|e| error::body(id, error::INVALID_REQUEST, format!("Invalid Request: {}", e)))?,
+        );
+    }
+    Ok((messages, batch))
+}
+
+/// Handle one message. Resource subscriptions are refused: updates could
+/// only reach the client over a server-initiated stream, which /mcp lacks.
+async fn dispatch(proxy: &ProxyServer, session_id: &str, message: JsonRpcRequest) -> Option<JsonRpcResponse> {
+    if message.method == "resources/subscribe" {
+        return Some(JsonRpcResponse::error(
+            message.id,
+            error::METHOD_NOT_FOUND,
+            "Resource subscriptions are only available over the SSE transport (/sse)".into(),
+        ));
+    }
+    proxy.handle_request_in(session_id, message).await
+}
+
+/// Stream the replies when the client accepts `text/event-stream`.
+fn reply_mode(accept: Option<&str>) -> ReplyMode {
+    let streams = accept.is_some_and(|accept| {
+        accept.split(',').any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("text/event-stream"))
+    });
+    if streams {
+        ReplyMode::EventStream
+    } else {
+        ReplyMode::Json
+    }
+}
+
+/// Remove sessions idle for longer than `max_idle` or older than `max_lifetime`.
+async fn reap_stale(sessions: &Mutex<HashMap<String, StreamableSession>>, max_idle: Duration, max_lifetime: Option<Duration>) {
+    sessions.lock().await.retain(|id, session| {
+        if max_lifetime.is_some_and(|max| session.created_at.elapsed() > max) {
+            tracing::info!(tag = "HTTP", "Closed Streamable HTTP session {} at its maximum lifetime", id);
+            return false;
+        }
+        let live = session.last_activity.elapsed() <= max_idle;
+        if !live {
+            tracing::info!(tag = "HTTP", "Reaped stale Streamable HTTP session: {}", id);
+        }
+        live
+    });
+}
+
+/// A JSON response with extra header lines (each ending in `\r\n`).
+fn respond(status: u16, status_text: &str, headers: &str, body: &str) -> Vec<u8> {
+    with_headers(http_response(status, status_text, body), headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    async fn send(manager: &Arc<StreamableManager>, proxy: &Arc<ProxyServer>, method: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let headers = headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let req = HttpRequest { method: method.into(), path: "/mcp".into(), headers, body: body.into() };
+        let (manager, proxy) = (manager.clone(), proxy.clone());
+        tokio::spawn(async move { manager.handle(server, &req, proxy).await });
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(5), client.read_to_string(&mut response)).await.unwrap().unwrap();
+        response
+    }
+
+    fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+        response
+            .lines()
+            .take_while(|l| !l.is_empty())
+            .find_map(|l| l.split_once(": ").filter(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v))
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle_over_json_and_event_stream() {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = Arc::new(StreamableManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        let json = ("accept", "application/json");
+
+        let init = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"t","version":"1"}}}"#;
+        let response = send(&manager, &proxy, "POST", &[json], init).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        let session = header(&response, "mcp-session-id").unwrap().to_string();
+        let sid = ("mcp-session-id", session.as_str());
+
+        // Without the session header, and with an unknown one
+        let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+        assert!(send(&manager, &proxy, "POST", &[json], ping).await.starts_with("HTTP/1.1 400"));
+        assert!(send(&manager, &proxy, "POST", &[json, ("mcp-session-id", "nope")], ping).await.starts_with("HTTP/1.1 404"));
+
+        let response = send(&manager, &proxy, "POST", &[json, sid], ping).await;
+        assert!(response.starts_with("HTTP/1.1 200") && response.ends_with(r#"{"jsonrpc":"2.0","id":2,"result":{}}"#), "{}", response);
+
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        assert!(send(&manager, &proxy, "POST", &[json, sid], notification).await.starts_with("HTTP/1.1 202"));
+
+        let batch = r#"[{"jsonrpc":"2.0","id":3,"method":"ping"},{"jsonrpc":"2.0","id":4,"method":"ping"}]"#;
+        let accept_both = ("accept", "application/json, text/event-stream");
+        let response = send(&manager, &proxy, "POST", &[accept_both, sid], batch).await;
+        assert_eq!(header(&response, "content-type"), Some("text/event-stream"));
+        assert_eq!(response.matches("event: message\ndata: ").count(), 2, "{}", response);
+
+        assert!(send(&manager, &proxy, "GET", &[sid], "").await.starts_with("HTTP/1.1 405"));
+        assert!(send(&manager, &proxy, "DELETE", &[sid], "").await.starts_with("HTTP/1.1 200"));
+        assert!(manager.sessions.lock().await.is_empty());
+        assert!(send(&manager, &proxy, "POST", &[json, sid], ping).await.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_reply_mode_follows_accept() {
+        assert_eq!(reply_mode(None), ReplyMode::Json);
+        assert_eq!(reply_mode(Some("application/json")), ReplyMode::Json);
+        assert_eq!(reply_mode(Some("application/json, Text/Event-Stream;q=0.9")), ReplyMode::EventStream);
+        assert!(parse_messages("[]").is_err());
+        let (messages, batch) = parse_messages(r#"[{"jsonrpc":"2.0","id":1,"result":{}},{"jsonrpc":"2.0","method":"ping","id":2}]"#).unwrap();
+        assert_eq!((messages.len(), batch), (1, true));
+    }
+}
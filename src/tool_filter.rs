@@ -0,0 +1,106 @@
+/// Per-server allow/deny patterns restricting which upstream tools are
+/// exposed. Patterns match the server's own tool name; `*` matches any run
+/// of characters.
+use crate::protocol::ToolDef;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerToolFilter {
+    /// If non-empty, only tools matching one of these are exposed.
+    pub allow: Vec<String>,
+    /// Tools matching one of these are never exposed, even if allowed.
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolFilter {
+    /// Keyed by server name; servers without an entry are unfiltered.
+    pub servers: HashMap<String, ServerToolFilter>,
+}
+
+impl ToolFilter {
+    pub fn allows(&self, server: &str, tool: &str) -> bool {
+        let Some(filter) = self.servers.get(server) else { return true };
+        if filter.deny.iter().any(|p| glob_match(p, tool)) {
+            return false;
+        }
+        filter.allow.is_empty() || filter.allow.iter().any(|p| glob_match(p, tool))
+    }
+
+    /// Drop the tools of `server` that aren't allowed.
+    pub fn apply(&self, server: &str, mut tools: Vec<ToolDef>) -> Vec<ToolDef> {
+        tools.retain(|t| self.allows(server, &t.name));
+        tools
+    }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else { return rest.is_empty() };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Parse `settings.toolFilter`: `{"<server>": {"allow": [..], "deny": [..]}}`.
+pub fn parse_tool_filter(value: &Value) -> Result<ToolFilter, String> {
+    let servers = value.as_object().ok_or("toolFilter must be an object keyed by server name")?;
+    let mut filter = ToolFilter::default();
+    for (server, v) in servers {
+        let patterns = |key: &str| -> Result<Vec<String>, String> {
+            match v.get(key) {
+                None => Ok(Vec::new()),
+                Some(list) => list
+                    .as_array()
+                    .and_then(|l| l.iter().map(|p| p.as_str().map(String::from)).collect::<Option<Vec<_>>>())
+                    .ok_or(format!("{}.{} must be a list of patterns", server, key)),
+            }
+        };
+        if !v.is_object() {
+            return Err(format!("{}: expected an object with allow and/or deny", server));
+        }
+        filter.servers.insert(server.clone(), ServerToolFilter { allow: patterns("allow")?, deny: patterns("deny")? });
+    }
+    Ok(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("read_*", "read_file"));
+        assert!(glob_match("*_file", "write_file"));
+        assert!(glob_match("*issue*", "create_issue_comment"));
+        assert!(glob_match("list", "list"));
+        assert!(!glob_match("list", "list_issues"));
+        assert!(!glob_match("a*a", "a"));
+        assert!(glob_match("*", "anything"));
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let filter = parse_tool_filter(&serde_json::json!({
+            "fs": {"allow": ["read_*", "list_*"], "deny": ["read_secret*"]},
+            "github": {"deny": ["delete_*"]},
+        })).unwrap();
+        assert!(filter.allows("fs", "read_file"));
+        assert!(!filter.allows("fs", "write_file"));
+        assert!(!filter.allows("fs", "read_secrets"));
+        assert!(filter.allows("github", "create_issue"));
+        assert!(!filter.allows("github", "delete_repo"));
+        assert!(filter.allows("other", "delete_repo"));
+
+        assert!(parse_tool_filter(&serde_json::json!({"fs": {"allow": "read_*"}})).is_err());
+        assert!(parse_tool_filter(&serde_json::json!(["fs"])).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+/// Per-server edits to the tools upstream servers expose: replace or extend
+/// a tool's description, or rename it for clients. Keys are the server's
+/// own tool names.
+use crate::protocol::ToolDef;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOverride {
+    /// Replaces the upstream description.
+    pub description: Option<String>,
+    /// Added before the (possibly replaced) description.
+    pub prepend: Option<String>,
+    /// Added after the (possibly replaced) description.
+    pub append: Option<String>,
+    /// Name clients see instead of the upstream one.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOverrides {
+    /// Server name -> upstream tool name -> override.
+    pub servers: HashMap<String, HashMap<String, ToolOverride>>,
+}
+
+impl ToolOverrides {
+    /// Apply the overrides of `server` to its tool list. Entries naming tools
+    /// the server doesn't have are skipped with a warning, and an upstream
+    /// tool another one is renamed to is hidden so names stay unique.
+    pub fn apply(&self, server: &str, mut tools: Vec<ToolDef>) -> Vec<ToolDef> {
+        let Some(overrides) = self.servers.get(server) else { return tools };
+        for tool_name in overrides.keys() {
+            if !tools.iter().any(|t| &t.name == tool_name) {
+                tracing::warn!(tag = "OVERRIDE", "toolOverrides: server '{}' has no tool '{}'", server, tool_name);
+            }
+        }
+        tools.retain(|t| {
+            let renamed_away = overrides.get(&t.name).is_some_and(|o| o.name.is_some());
+            let shadowed = !renamed_away && overrides.values().any(|o| o.name.as_deref() == Some(t.name.as_str()));
+            if shadowed {
+                tracing::warn!(tag = "OVERRIDE", "toolOverrides: hiding '{}' of '{}', another tool is renamed to it", t.name, server);
+            }
+            !shadowed
+        });
+        for tool in &mut tools {
+            let Some(o) = overrides.get(&tool.name) else { continue };
+            if let Some(description) = &o.description {
+                tool.description = description.clone();
+            }
+            if o.prepend.is_some() || o.append.is_some() {
+                let parts: Vec<&str> = [o.prepend.as_deref(), Some(tool.description.as_str()).filter(|d| !d.is_empty()), o.append.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                tool.description = parts.join(" ");
+            }
+            if let Some(name) = &o.name {
+                tool.name = name.clone();
+            }
+        }
+        tools
+    }
+
+    /// The upstream name of a tool clients see as `tool`. None when `tool`
+    /// is the upstream name of a renamed tool, which clients no longer see.
+    pub fn upstream_name<'a>(&'a self, server: &str, tool: &'a str) -> Option<&'a str> {
+        let Some(overrides) = self.servers.get(server) else { return Some(tool) };
+        if let Some((from, _)) = overrides.iter().find(|(_, o)| o.name.as_deref() == Some(tool)) {
+            return Some(from.as_str());
+        }
+        match overrides.get(tool) {
+            Some(o) if o.name.is_some() => None,
+            _ => Some(tool),
+        }
+    }
+}
+
+/// Parse `settings.toolOverrides`:
+/// `{"<server>": {"<tool>": {"description"|"prepend"|"append"|"name": "..."}}}`.
+pub fn parse_tool_overrides(value: &Value) -> Result<ToolOverrides, String> {
+    let servers = value.as_object().ok_or("toolOverrides must be an object keyed by server name")?;
+    let mut overrides = ToolOverrides::default();
+    for (server, tools) in servers {
+        let tools = tools.as_object().ok_or(format!("{}: expected an object keyed by tool name", server))?;
+        let mut parsed: HashMap<String, ToolOverride> = HashMap::new();
+        for (tool, v) in tools {
+            let obj = v.as_object().ok_or(format!("{}.{}: expected an object", server, tool))?;
+            for key in obj.keys() {
+                if !["description", "prepend", "append", "name"].contains(&key.as_str()) {
+                    return Err(format!("{}.{}: unknown key '{}'", server, tool, key));
+                }
+            }
+            let text = |key: &str| -> Result<Option<String>, String> {
+                match obj.get(key) {
+                    None => Ok(None),
+                    Some(s) => s.as_str().map(|s| Some(s.to_string())).ok_or(format!("{}.{}.{} must be a string", server, tool, key)),
+                }
+            };
+            let name = text("name")?;
+            if let Some(name) = &name {
+                if name.trim().is_empty() {
+                    return Err(format!("{}.{}.name must not be empty", server, tool));
+                }
+                if let Some((other, _)) = parsed.iter().find(|(_, o)| o.name.as_ref() == Some(name)) {
+                    return Err(format!("{}: both '{}' and '{}' are renamed to '{}'", server, other, tool, name));
+                }
+            }
+            parsed.insert(tool.clone(), ToolOverride {
+                description: text("description")?,
+                prepend: text("prepend")?,
+                append: text("append")?,
+                name,
+            });
+        }
+        overrides.servers.insert(server.clone(), parsed);
+    }
+    Ok(overrides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str, description: &str) -> ToolDef {
+        serde_json::from_value(serde_json::json!({"name": name, "description": description, "inputSchema": {}})).unwrap()
+    }
+
+    #[test]
+    fn test_descriptions_and_renames() {
+        let overrides = parse_tool_overrides(&serde_json::json!({
+            "github": {
+                "search_code": {"prepend": "Use this only for repo X.", "name": "search_repo_x"},
+                "create_issue": {"description": "Open an issue.", "append": "Ask first."},
+                "get_file": {"name": "search_code"},
+                "missing_tool": {"append": "ignored"},
+            },
+        })).unwrap();
+        let tools = overrides.apply("github", vec![
+            tool("search_code", "Search code."),
+            tool("create_issue", "Create an issue."),
+            tool("get_file", "Read a file."),
+        ]);
+        assert_eq!(tools[0].name, "search_repo_x");
+        assert_eq!(tools[0].description, "Use this only for repo X. Search code.");
+        assert_eq!(tools[1].description, "Open an issue. Ask first.");
+        assert_eq!(tools[2].name, "search_code");
+        assert_eq!(tools[2].description, "Read a file.");
+
+        assert_eq!(overrides.upstream_name("github", "search_repo_x"), Some("search_code"));
+        assert_eq!(overrides.upstream_name("github", "search_code"), Some("get_file"));
+        assert_eq!(overrides.upstream_name("github", "create_issue"), Some("create_issue"));
+        assert_eq!(overrides.upstream_name("other", "search_repo_x"), Some("search_repo_x"));
+        // Renamed away
+        assert_eq!(overrides.upstream_name("github", "get_file"), None);
+        assert_eq!(overrides.apply("other", vec![tool("a", "b")])[0].description, "b");
+
+        assert!(parse_tool_overrides(&serde_json::json!({"github": {"x": {"prefix": "y"}}})).is_err());
+        assert!(parse_tool_overrides(&serde_json::json!({"github": {"x": {"name": ""}}})).is_err());
+        assert!(parse_tool_overrides(&serde_json::json!(["github"])).is_err());
+        assert!(parse_tool_overrides(&serde_json::json!({"github": {"a": {"name": "x"}, "b": {"name": "x"}}})).is_err());
+
+        // An upstream tool another one is renamed onto is hidden
+        let overrides = parse_tool_overrides(&serde_json::json!({"fs": {"read": {"name": "list"}}})).unwrap();
+        let tools = overrides.apply("fs", vec![tool("read", "Read."), tool("list", "List.")]);
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].description, "Read.");
+    }
+}
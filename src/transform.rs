@@ -0,0 +1,223 @@
+/// Declarative request/response rewriting for calls forwarded to backends.
+/// A transform matches on server, tool and/or method and applies a fixed
+/// list of field operations (set, default, remove, rename) addressed by
+/// dotted paths. Request paths are relative to the forwarded `params`
+/// (e.g. `arguments.owner` for a tool call), response paths to the `result`.
+use serde_json::{Map, Value};
+
+/// Deepest dotted path a transform may address.
+const MAX_PATH_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Always write `value` at `path`.
+    Set { path: Vec<String>, value: Value },
+    /// Write `value` at `path` only if nothing is there yet.
+    Default { path: Vec<String>, value: Value },
+    Remove { path: Vec<String> },
+    /// Move the value at `from` to `to`, if present.
+    Rename { from: Vec<String>, to: Vec<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    pub server: Option<String>,
+    pub tool: Option<String>,
+    pub method: Option<String>,
+    pub request: Vec<Op>,
+    pub response: Vec<Op>,
+}
+
+impl Transform {
+    /// `tool` is the bare tool name for `tools/call`, None for other methods.
+    /// A transform naming a tool never matches other methods.
+    fn matches(&self, server: &str, method: &str, tool: Option<&str>) -> bool {
+        self.server.as_deref().is_none_or(|s| s == server)
+            && self.method.as_deref().is_none_or(|m| m == method)
+            && self.tool.as_deref().is_none_or(|t| tool == Some(t))
+    }
+}
+
+/// Parse and validate the `settings.transforms` array.
+pub fn parse_transforms(value: &Value) -> Result<Vec<Transform>, String> {
+    let items = value.as_array().ok_or("transforms must be an array")?;
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| parse_transform(item).map_err(|e| format!("transforms[{}]: {}", i, e)))
+        .collect()
+}
+
+fn parse_transform(item: &Value) -> Result<Transform, String> {
+    let matcher = item.get("match").unwrap_or(&Value::Null);
+    let field = |key: &str| -> Result<Option<String>, String> {
+        match matcher.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) if !s.is_empty() => Ok(Some(s.clone())),
+            Some(_) => Err(format!("match.{} must be a non-empty string", key)),
+        }
+    };
+    let transform = Transform {
+        server: field("server")?,
+        tool: field("tool")?,
+        method: field("method")?,
+        request: parse_ops(item.get("request")).map_err(|e| format!("request: {}", e))?,
+        response: parse_ops(item.get("response")).map_err(|e| format!("response: {}", e))?,
+    };
+    if transform.request.is_empty() && transform.response.is_empty() {
+        return Err("no request or response operations".into());
+    }
+    Ok(transform)
+}
+
+fn parse_ops(ops: Option<&Value>) -> Result<Vec<Op>, String> {
+    let Some(ops) = ops else { return Ok(Vec::new()) };
+    let ops = ops.as_array().ok_or("operations must be an array")?;
+    ops.iter()
+        .enumerate()
+        .map(|(i, op)| parse_op(op).map_err(|e| format!("[{}]: {}", i, e)))
+        .collect()
+}
+
+fn parse_op(op: &Value) -> Result<Op, String> {
+    let path_of = |key: &str| -> Result<Vec<String>, String> {
+        let raw = op.get(key).and_then(|v| v.as_str()).ok_or(format!("missing '{}'", key))?;
+        parse_path(raw)
+    };
+    let value = || op.get("value").cloned().ok_or("missing 'value'".to_string());
+    match op.get("op").and_then(|v| v.as_str()) {
+        Some("set") => Ok(Op::Set { path: path_of("path")?, value: value()? }),
+        Some("default") => Ok(Op::Default { path: path_of("path")?, value: value()? }),
+        Some("remove") => Ok(Op::Remove { path: path_of("path")? }),
+        Some("rename") => Ok(Op::Rename { from: path_of("from")?, to: path_of("to")? }),
+        Some(other) => Err(format!("unknown op '{}' (expected set, default, remove or rename)", other)),
+        None => Err("missing 'op'".into()),
+    }
+}
+
+fn parse_path(raw: &str) -> Result<Vec<String>, String> {
+    let path: Vec<String> = raw.split('.').map(String::from).collect();
+    if path.iter().any(|p| p.is_empty()) {
+        return Err(format!("invalid path '{}'", raw));
+    }
+    if path.len() > MAX_PATH_DEPTH {
+        return Err(format!("path '{}' is deeper than {} levels", raw, MAX_PATH_DEPTH));
+    }
+    Ok(path)
+}
+
+/// Apply the request operations of every matching transform to `params`.
+pub fn apply_request(transforms: &[Transform], server: &str, method: &str, tool: Option<&str>, params: &mut Value) {
+    for t in transforms.iter().filter(|t| t.matches(server, method, tool)) {
+        apply_ops(&t.request, params);
+    }
+}
+
+/// Apply the response operations of every matching transform to `result`.
+pub fn apply_response(transforms: &[Transform], server: &str, method: &str, tool: Option<&str>, result: &mut Value) {
+    for t in transforms.iter().filter(|t| t.matches(server, method, tool)) {
+        apply_ops(&t.response, result);
+    }
+}
+
+fn apply_ops(ops: &[Op], target: &mut Value) {
+    for op in ops {
+        match op {
+            Op::Set { path, value } => {
+                if let Some((parent, key)) = parent_mut(target, path, true) {
+                    parent.insert(key.clone(), value.clone());
+                }
+            }
+            Op::Default { path, value } => {
+                if let Some((parent, key)) = parent_mut(target, path, true) {
+                    parent.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+            }
+            Op::Remove { path } => {
+                if let Some((parent, key)) = parent_mut(target, path, false) {
+                    parent.remove(key);
+                }
+            }
+            Op::Rename { from, to } => {
+                let moved = parent_mut(target, from, false).and_then(|(parent, key)| parent.remove(key));
+                if let Some(value) = moved {
+                    if let Some((parent, key)) = parent_mut(target, to, true) {
+                        parent.insert(key.clone(), value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Object holding the last path segment, plus that segment. With `create`,
+/// missing intermediate objects are added. None if the path runs through a
+/// non-object value.
+fn parent_mut<'a>(target: &'a mut Value, path: &'a [String], create: bool) -> Option<(&'a mut Map<String, Value>, &'a String)> {
+    let (last, parents) = path.split_last()?;
+    let mut current = target;
+    for key in parents {
+        let obj = current.as_object_mut()?;
+        if create && !obj.contains_key(key) {
+            obj.insert(key.clone(), Value::Object(Map::new()));
+        }
+        current = obj.get_mut(key)?;
+    }
+    Some((current.as_object_mut()?, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_injects_default_argument() {
+        let transforms = parse_transforms(&json!([{
+            "match": { "server": "github", "tool": "create_issue" },
+            "request": [{ "op": "default", "path": "arguments.owner", "value": "acme" }]
+        }]))
+        .unwrap();
+
+        let mut params = json!({ "name": "create_issue", "arguments": { "title": "x" } });
+        apply_request(&transforms, "github", "tools/call", Some("create_issue"), &mut params);
+        assert_eq!(params["arguments"], json!({ "title": "x", "owner": "acme" }));
+
+        // An explicit argument wins over the default
+        let mut params = json!({ "arguments": { "owner": "me" } });
+        apply_request(&transforms, "github", "tools/call", Some("create_issue"), &mut params);
+        assert_eq!(params["arguments"]["owner"], "me");
+
+        // Other tools and servers are untouched
+        let mut params = json!({ "arguments": {} });
+        apply_request(&transforms, "github", "tools/call", Some("list_issues"), &mut params);
+        apply_request(&transforms, "gitlab", "tools/call", Some("create_issue"), &mut params);
+        assert_eq!(params, json!({ "arguments": {} }));
+    }
+
+    #[test]
+    fn test_response_strips_and_renames_fields() {
+        let transforms = parse_transforms(&json!([{
+            "match": { "server": "fs" },
+            "response": [
+                { "op": "remove", "path": "_meta.trace" },
+                { "op": "rename", "from": "structuredContent", "to": "data" }
+            ]
+        }]))
+        .unwrap();
+
+        let mut result = json!({ "content": [], "structuredContent": { "n": 1 }, "_meta": { "trace": "abc", "keep": true } });
+        apply_response(&transforms, "fs", "tools/call", Some("read"), &mut result);
+        assert_eq!(result, json!({ "content": [], "data": { "n": 1 }, "_meta": { "keep": true } }));
+    }
+
+    #[test]
+    fn test_invalid_specs_are_rejected() {
+        let err = parse_transforms(&json!([{ "request": [{ "op": "exec", "path": "a" }] }])).unwrap_err();
+        assert!(err.contains("transforms[0]") && err.contains("unknown op 'exec'"), "{}", err);
+        assert!(parse_transforms(&json!([{ "request": [{ "op": "set", "path": "a..b", "value": 1 }] }])).is_err());
+        assert!(parse_transforms(&json!([{ "request": [{ "op": "set", "path": "a" }] }])).is_err());
+        assert!(parse_transforms(&json!([{ "match": { "server": "x" } }])).is_err());
+        assert!(parse_transforms(&json!({})).is_err());
+    }
+}
@@ -4,7 +4,66 @@ use std::process::Command;
 
 const REPO: &str = "Soflution1/McpHub";
 
-pub fn run() {
+/// Running instance vs the binary it was started from.
+#[derive(Debug, PartialEq)]
+enum UpgradeCheck {
+    Current,
+    /// The binary on disk was replaced; the old one is still running.
+    RestartNeeded { running: String, on_disk: String },
+}
+
+fn compare_versions(running: &str, on_disk: &str) -> UpgradeCheck {
+    let running = running.trim_start_matches('v');
+    let on_disk = on_disk.trim_start_matches('v');
+    if running == on_disk {
+        UpgradeCheck::Current
+    } else {
+        UpgradeCheck::RestartNeeded { running: running.to_string(), on_disk: on_disk.to_string() }
+    }
+}
+
+/// Version from `McpHub version` output ("McpHub v1.2.3").
+fn parse_version_output(output: &str) -> Option<&str> {
+    output.split_whitespace().find_map(|w| w.strip_prefix('v')).filter(|v| !v.is_empty())
+}
+
+/// `McpHub upgrade-check`: compare the running instance (from /info) with
+/// the version of the binary it was started from.
+pub fn run_upgrade_check(bind_addr: std::net::SocketAddr) {
+    let Some(info) = crate::dashboard::daemon_get(bind_addr, "/info") else {
+        eprintln!("✗ No running McpHub answered on {}", bind_addr);
+        std::process::exit(1);
+    };
+    let Some(running) = info.get("version").and_then(|v| v.as_str()) else {
+        eprintln!("✗ The instance on {} doesn't report its version; it predates upgrade-check. Restart it.", bind_addr);
+        std::process::exit(1);
+    };
+    // Linux marks an executable replaced on disk as "(deleted)"
+    let binary = info
+        .get("binary")
+        .and_then(|v| v.as_str())
+        .map(|b| std::path::PathBuf::from(b.trim_end_matches(" (deleted)")))
+        .or_else(|| env::current_exe().ok());
+    let on_disk = binary.as_ref().and_then(|b| {
+        let out = Command::new(b).arg("version").output().ok()?;
+        parse_version_output(&String::from_utf8_lossy(&out.stdout)).map(String::from)
+    });
+    let (Some(binary), Some(on_disk)) = (binary, on_disk) else {
+        eprintln!("✗ Could not read the version of the installed binary");
+        std::process::exit(1);
+    };
+
+    match compare_versions(running, &on_disk) {
+        UpgradeCheck::Current => println!("✓ Running McpHub v{} matches {}", running, binary.display()),
+        UpgradeCheck::RestartNeeded { running, on_disk } => {
+            println!("! Running McpHub is v{} but {} is v{}.", running, binary.display(), on_disk);
+            println!("  Restart the service to use it: McpHub install --force");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn run(bind_addr: std::net::SocketAddr) {
     let current_version = env!("CARGO_PKG_VERSION");
     println!("Checking for updates (current: v{})...", current_version);
 
@@ -74,7 +133,7 @@ pub fn run() {
         .args(["-L", "-s", "-o", archive_path.to_str().unwrap(), &download_url])
         .status();
 
-    if !dl_status.map_or(false, |s| s.success()) {
+    if !dl_status.is_ok_and(|s| s.success()) {
         eprintln!("Download failed.");
         return;
     }
@@ -90,7 +149,7 @@ pub fn run() {
         ])
         .status();
 
-    if !extract_status.map_or(false, |s| s.success()) {
+    if !extract_status.is_ok_and(|s| s.success()) {
         eprintln!("Extraction failed. Is tar installed?");
         return;
     }
@@ -141,7 +200,23 @@ pub fn run() {
 
     // 5. Restart daemon if installed
     println!("Restarting daemon to apply changes...");
-    crate::install::install();
+    crate::install::install(crate::install::InstallOptions { force: true, ..Default::default() }, bind_addr);
 
     println!("Update complete.");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reported_vs_on_disk_version() {
+        let on_disk = parse_version_output("McpHub v2.1.0\n").unwrap();
+        assert_eq!(compare_versions("2.1.0", on_disk), UpgradeCheck::Current);
+        assert_eq!(
+            compare_versions("2.0.3", on_disk),
+            UpgradeCheck::RestartNeeded { running: "2.0.3".into(), on_disk: "2.1.0".into() }
+        );
+        assert_eq!(parse_version_output("command not found"), None);
+    }
+}
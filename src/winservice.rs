@@ -0,0 +1,181 @@
+/// Running under the Windows service control manager: `McpHub service` is
+/// the command `install --service` registers. It connects to the SCM,
+/// reports itself running and turns a stop or system shutdown into the
+/// same graceful shutdown as Ctrl-C.
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+
+use tokio::sync::Notify;
+
+/// Name the service is registered under.
+pub const SERVICE_NAME: &str = "McpHub";
+
+#[repr(C)]
+struct ServiceStatus {
+    service_type: u32,
+    current_state: u32,
+    controls_accepted: u32,
+    win32_exit_code: u32,
+    service_specific_exit_code: u32,
+    check_point: u32,
+    wait_hint: u32,
+}
+
+type ServiceMain = unsafe extern "system" fn(argc: u32, argv: *mut *mut u16);
+type HandlerEx = unsafe extern "system" fn(control: u32, event_type: u32, event_data: *mut c_void, context: *mut c_void) -> u32;
+
+#[repr(C)]
+struct ServiceTableEntry {
+    name: *mut u16,
+    main: Option<ServiceMain>,
+}
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn StartServiceCtrlDispatcherW(table: *const ServiceTableEntry) -> i32;
+    fn RegisterServiceCtrlHandlerExW(name: *const u16, handler: HandlerEx, context: *mut c_void) -> isize;
+    fn SetServiceStatus(handle: isize, status: *const ServiceStatus) -> i32;
+}
+
+const SERVICE_WIN32_OWN_PROCESS: u32 = 0x10;
+const SERVICE_STOPPED: u32 = 1;
+const SERVICE_STOP_PENDING: u32 = 3;
+const SERVICE_RUNNING: u32 = 4;
+const SERVICE_ACCEPT_STOP: u32 = 0x1;
+const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x4;
+const SERVICE_CONTROL_STOP: u32 = 1;
+const SERVICE_CONTROL_INTERROGATE: u32 = 4;
+const SERVICE_CONTROL_SHUTDOWN: u32 = 5;
+const NO_ERROR: u32 = 0;
+const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+/// How long the SCM waits for the stop before it gives up on us.
+const STOP_WAIT_HINT_MS: u32 = 30_000;
+
+/// Status handle from the SCM; 0 when not running as a service.
+static STATUS_HANDLE: AtomicIsize = AtomicIsize::new(0);
+/// Tells `connect` whether the service started.
+static STARTED: Mutex<Option<mpsc::Sender<Result<(), String>>>> = Mutex::new(None);
+
+fn stop() -> &'static Notify {
+    static STOP: OnceLock<Notify> = OnceLock::new();
+    STOP.get_or_init(Notify::new)
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+fn set_state(state: u32, wait_hint: u32) {
+    let handle = STATUS_HANDLE.load(Ordering::SeqCst);
+    if handle == 0 {
+        return;
+    }
+    let status = ServiceStatus {
+        service_type: SERVICE_WIN32_OWN_PROCESS,
+        current_state: state,
+        controls_accepted: if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 },
+        win32_exit_code: NO_ERROR,
+        service_specific_exit_code: 0,
+        check_point: 0,
+        wait_hint,
+    };
+    unsafe {
+        SetServiceStatus(handle, &status);
+    }
+}
+
+unsafe extern "system" fn handler(control: u32, _event_type: u32, _event_data: *mut c_void, _context: *mut c_void) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            set_state(SERVICE_STOP_PENDING, STOP_WAIT_HINT_MS);
+            stop().notify_one();
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+/// Runs on a thread of the SCM's: registers the control handler and
+/// reports the service running. The hub itself runs on the tokio runtime.
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+    let name = wide(SERVICE_NAME);
+    let handle = RegisterServiceCtrlHandlerExW(name.as_ptr(), handler, std::ptr::null_mut());
+    let result = match handle {
+        0 => Err(format!("Could not register the service control handler: {}", std::io::Error::last_os_error())),
+        handle => {
+            STATUS_HANDLE.store(handle, Ordering::SeqCst);
+            set_state(SERVICE_RUNNING, 0);
+            Ok(())
+        }
+    };
+    if let Some(started) = STARTED.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = started.send(result);
+    }
+}
+
+/// Hand a thread to the service control manager and wait until it has
+/// started the service. Fails when the process wasn't started by the SCM.
+pub fn connect() -> Result<(), String> {
+    let (tx, rx) = mpsc::channel();
+    *STARTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx.clone());
+    std::thread::spawn(move || {
+        let mut name = wide(SERVICE_NAME);
+        let table = [
+            ServiceTableEntry { name: name.as_mut_ptr(), main: Some(service_main) },
+            ServiceTableEntry { name: std::ptr::null_mut(), main: None },
+        ];
+        // Blocks until the service reports SERVICE_STOPPED
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            let _ = tx.send(Err(format!(
+                "Not started by the service manager ({}); run 'McpHub serve' in a console instead",
+                std::io::Error::last_os_error()
+            )));
+        }
+    });
+    rx.recv().unwrap_or_else(|_| Err("The service dispatcher exited".to_string()))
+}
+
+/// Resolves when the SCM asks the service to stop; never outside a service.
+pub async fn stop_requested() {
+    stop().notified().await;
+}
+
+/// Tell the SCM the service has stopped, once shutdown is done.
+pub fn report_stopped() {
+    set_state(SERVICE_STOPPED, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wide_is_nul_terminated_utf16() {
+        assert_eq!(wide("Mé"), [0x4d, 0xe9, 0]);
+        assert_eq!(wide(""), [0]);
+    }
+
+    #[test]
+    fn test_service_status_matches_the_win32_layout() {
+        // SERVICE_STATUS is seven DWORDs
+        assert_eq!(std::mem::size_of::<ServiceStatus>(), 28);
+        assert_eq!(std::mem::size_of::<ServiceTableEntry>(), 2 * std::mem::size_of::<usize>());
+    }
+
+    #[tokio::test]
+    async fn test_handler_turns_stop_into_shutdown() {
+        // Not a service: no status handle, so set_state reports nothing
+        assert_eq!(unsafe { handler(SERVICE_CONTROL_INTERROGATE, 0, std::ptr::null_mut(), std::ptr::null_mut()) }, NO_ERROR);
+        assert_eq!(unsafe { handler(2, 0, std::ptr::null_mut(), std::ptr::null_mut()) }, ERROR_CALL_NOT_IMPLEMENTED);
+        assert_eq!(unsafe { handler(SERVICE_CONTROL_STOP, 0, std::ptr::null_mut(), std::ptr::null_mut()) }, NO_ERROR);
+        tokio::time::timeout(std::time::Duration::from_secs(1), stop_requested()).await.expect("stop not requested");
+    }
+
+    #[test]
+    fn test_connect_fails_outside_the_service_manager() {
+        let err = connect().unwrap_err();
+        assert!(err.starts_with("Not started by the service manager"), "{}", err);
+    }
+}
@@ -0,0 +1,712 @@
+/// WebSocket transport for MCP protocol, for networks that mishandle
+/// long-lived SSE responses. `GET /ws` upgrades the connection, then
+/// JSON-RPC messages travel both ways as text frames on that one socket.
+///
+/// - Same Bearer token check as /sse, plus the CORS origin allowlist
+///   (browsers don't apply CORS to WebSockets)
+/// - Requests are dispatched concurrently; responses are written in the
+///   order they complete
+/// - The server pings at the SSE keepalive interval; a client that answers
+///   nothing for PONG_GRACE intervals is disconnected
+/// - permessage-deflate (RFC 7692) when the client offers it, without
+///   context takeover: every message is compressed on its own
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use crate::config::{ProxyConfig, DEFAULT_BIND_ADDR};
+use crate::deflate;
+use crate::error;
+use crate::http::HttpRequest;
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse};
+use crate::proxy::ProxyServer;
+use crate::sse::{authorized, generate_session_id, http_response, unauthorized};
+
+/// GUID appended to the client key for `Sec-WebSocket-Accept` (RFC 6455).
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest message accepted, across all of its fragments.
+const MAX_MESSAGE_BYTES: usize = 32 * 1024 * 1024;
+/// Ping intervals a client may stay silent before it's considered gone.
+const PONG_GRACE: u32 = 2;
+/// Outgoing frames queued for the writer.
+const OUTGOING_FRAMES: usize = 64;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+/// Header bit marking a compressed message (permessage-deflate).
+const RSV1: u8 = 0x40;
+/// Empty stored block that permessage-deflate strips from each message.
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Close codes sent to the client.
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Manages WebSocket connections.
+pub struct WsManager {
+    require_auth: bool,
+    auto_generate_token: bool,
+    allowed_origins: Vec<String>,
+    ping_interval: Duration,
+    /// Address the HTTP server is bound to, for token lookup.
+    bind_addr: SocketAddr,
+    /// Size from which messages are compressed; None when compression is off.
+    compression_threshold: Option<usize>,
+    connections: AtomicUsize,
+}
+
+impl WsManager {
+    pub fn new(config: &ProxyConfig) -> Self {
+        Self {
+            require_auth: config.auth_required,
+            auto_generate_token: config.auth_auto_generate_token,
+            allowed_origins: config.cors_allowed_origins.clone(),
+            ping_interval: config.sse_keepalive.interval,
+            bind_addr: DEFAULT_BIND_ADDR.parse().expect("valid default bind address"),
+            compression_threshold: config.ws_compression.then_some(config.ws_compression_threshold),
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    /// Address the HTTP server is bound to (DEFAULT_BIND_ADDR otherwise).
+    pub fn with_bind_addr(mut self, bind_addr: SocketAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// Open WebSocket connections.
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Handle GET /ws: check the upgrade request, switch protocols, then
+    /// serve JSON-RPC over the socket until either side closes it.
+    pub async fn handle_connect(&self, mut stream: TcpStream, req: &HttpRequest, proxy: Arc<ProxyServer>) {
+        let header = |name: &str| req.headers.get(name).map(|v| v.as_str());
+        let accept = match self.check_upgrade(req) {
+            Ok(accept) => accept,
+            Err(response) => {
+                let _ = stream.write_all(&response).await;
+                let _ = stream.shutdown().await;
+                return;
+            }
+        };
+        if !authorized(self.require_auth, self.auto_generate_token, self.bind_addr, header("authorization")) {
+            let _ = stream.write_all(&unauthorized()).await;
+            let _ = stream.shutdown().await;
+            return;
+        }
+        let offer = self.compression_threshold.and(header("sec-websocket-extensions")).and_then(negotiate_deflate);
+        let extensions = offer.map(|o| format!("Sec-WebSocket-Extensions: {}\r\n", o.response())).unwrap_or_default();
+        let handshake = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n{}\r\n",
+            accept, extensions
+        );
+        if stream.write_all(handshake.as_bytes()).await.is_err() {
+            return;
+        }
+        let _ = stream.set_nodelay(true);
+
+        let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_else(|_| "unknown".into());
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        tracing::info!(tag = "WS", "Client connected: {} ({} open)", peer, self.connection_count());
+        let compression = offer.zip(self.compression_threshold).map(|(offer, threshold)| Compression {
+            window_bits: offer.server_max_window_bits.unwrap_or(15),
+            threshold,
+        });
+        let reason = self.serve(stream, proxy, compression).await;
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+        tracing::info!(tag = "WS", "Client disconnected: {} ({})", peer, reason);
+    }
+
+    /// `Sec-WebSocket-Accept` for a valid upgrade request, or the HTTP error
+    /// response to send instead.
+    fn check_upgrade(&self, req: &HttpRequest) -> Result<String, Vec<u8>> {
+        let header = |name: &str| req.headers.get(name).map(|v| v.to_ascii_lowercase()).unwrap_or_default();
+        if !header("upgrade").contains("websocket") || !header("connection").contains("upgrade") {
+            return Err(http_response(400, "Bad Request", "{\"error\":\"Expected a WebSocket upgrade\"}"));
+        }
+        if header("sec-websocket-version") != "13" {
+            let body = "{\"error\":\"Unsupported WebSocket version\"}";
+            return Err(format!(
+                "HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .into_bytes());
+        }
+        if let Some(origin) = req.headers.get("origin") {
+            if !crate::dashboard::origin_allowed(&self.allowed_origins, origin) {
+                tracing::warn!(tag = "WS", "Rejected upgrade from origin {}", origin);
+                return Err(http_response(403, "Forbidden", "{\"error\":\"Origin not allowed\"}"));
+            }
+        }
+        match req.headers.get("sec-websocket-key") {
+            Some(key) if !key.trim().is_empty() => Ok(accept_key(key.trim())),
+            _ => Err(http_response(400, "Bad Request", "{\"error\":\"Missing Sec-WebSocket-Key\"}")),
+        }
+    }
+
+    /// Read frames and dispatch messages until the connection ends.
+    /// Returns why it ended, for the log.
+    async fn serve(&self, stream: TcpStream, proxy: Arc<ProxyServer>, compression: Option<Compression>) -> String {
+        let (read_half, mut write_half) = stream.into_split();
+        let (tx, mut rx) = mpsc::channel::<Vec<u8>>(OUTGOING_FRAMES);
+        let writer = tokio::spawn(async move {
+            while let Some(frame) = rx.recv().await {
+                if write_half.write_all(&frame).await.is_err() || write_half.flush().await.is_err() {
+                    break;
+                }
+                if frame.first() == Some(&(0x80 | OP_CLOSE)) {
+                    break;
+                }
+            }
+            let _ = write_half.shutdown().await;
+        });
+
+        let mut reader = FrameReader::new(read_half, compression.is_some());
+        let mut message: Option<(u8, bool, Vec<u8>)> = None;
+        let mut initialized = false;
+        // Scopes the connection's request ids, for cancellation
+        let session = generate_session_id();
+        let mut last_heard = Instant::now();
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + self.ping_interval, self.ping_interval);
+        let reason = loop {
+            let frame = tokio::select! {
+                frame = reader.next_frame() => frame,
+                _ = ping.tick() => {
+                    if last_heard.elapsed() > self.ping_interval * PONG_GRACE {
+                        break "no pong".to_string();
+                    }
+                    if tx.send(encode_frame(OP_PING, b"")).await.is_err() {
+                        break "write failed".to_string();
+                    }
+                    continue;
+                }
+            };
+            let frame = match frame {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break "connection closed".to_string(),
+                Err((code, e)) => {
+                    let _ = tx.send(close_frame(code)).await;
+                    break e;
+                }
+            };
+            last_heard = Instant::now();
+            match frame.opcode {
+                OP_PING => {
+                    let _ = tx.send(encode_frame(OP_PONG, &frame.payload)).await;
+                }
+                OP_PONG => {}
+                OP_CLOSE => {
+                    let _ = tx.send(close_frame(CLOSE_NORMAL)).await;
+                    break "closed by client".to_string();
+                }
+                OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                    let (opcode, compressed, mut payload) = match (message.take(), frame.opcode) {
+                        (None, OP_CONTINUATION) | (Some(_), OP_TEXT | OP_BINARY) => {
+                            let _ = tx.send(close_frame(CLOSE_PROTOCOL_ERROR)).await;
+                            break "unexpected fragment".to_string();
+                        }
+                        (Some(message), _) => message,
+                        (None, opcode) => (opcode, frame.compressed, Vec::new()),
+                    };
+                    if payload.len() + frame.payload.len() > MAX_MESSAGE_BYTES {
+                        let _ = tx.send(close_frame(CLOSE_TOO_BIG)).await;
+                        break format!("message over {} bytes", MAX_MESSAGE_BYTES);
+                    }
+                    payload.extend_from_slice(&frame.payload);
+                    if !frame.fin {
+                        message = Some((opcode, compressed, payload));
+                        continue;
+                    }
+                    if compressed {
+                        payload.extend_from_slice(&DEFLATE_TAIL);
+                        payload = match deflate::decompress(&payload, MAX_MESSAGE_BYTES) {
+                            Ok(payload) => payload,
+                            Err(e) => {
+                                let _ = tx.send(close_frame(CLOSE_PROTOCOL_ERROR)).await;
+                                break format!("invalid compressed message: {}", e);
+                            }
+                        };
+                    }
+                    self.dispatch(&proxy, &session, &payload, &tx, compression, &mut initialized);
+                }
+                _ => {
+                    let _ = tx.send(close_frame(CLOSE_PROTOCOL_ERROR)).await;
+                    break format!("unknown opcode {:#x}", frame.opcode);
+                }
+            }
+        };
+        drop(tx);
+        let _ = writer.await;
+        reason
+    }
+
+    /// Handle one JSON-RPC message in the background; its response, if any,
+    /// is queued as a text frame.
+    fn dispatch(
+        &self,
+        proxy: &Arc<ProxyServer>,
+        session: &str,
+        payload: &[u8],
+        tx: &mpsc::Sender<Vec<u8>>,
+        compression: Option<Compression>,
+        initialized: &mut bool,
+    ) {
+        let request = match serde_json::from_slice::<JsonRpcRequest>(payload) {
+            Ok(r) => r,
+            Err(e) => {
+                let response = match serde_json::from_slice::<serde_json::Value>(payload) {
+                    Ok(value) => JsonRpcResponse::error(value.get("id").cloned(), error::INVALID_REQUEST, format!("Invalid Request: {}", e)),
+                    Err(e) => JsonRpcResponse::error(None, error::PARSE_ERROR, format!("Parse error: {}", e)),
+                };
+                send_response(tx.clone(), response, compression);
+                return;
+            }
+        };
+        if proxy.is_shutting_down() {
+            if request.id.is_some() {
+                let response = JsonRpcResponse::error(request.id, error::UNAVAILABLE, "Server shutting down".into());
+                send_response(tx.clone(), response, compression);
+            }
+            return;
+        }
+        let handshake = proxy.handshake(&request, initialized);
+        let (proxy, session) = (proxy.clone(), session.to_string());
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            if let Some(response) = proxy.handle_session_request(&session, request, handshake).await {
+                send_response(tx, response, compression);
+            }
+        });
+    }
+}
+
+/// Queue a response without holding up the read loop.
+fn send_response(tx: mpsc::Sender<Vec<u8>>, response: JsonRpcResponse, compression: Option<Compression>) {
+    match serde_json::to_string(&response) {
+        Ok(json) => {
+            tokio::spawn(async move {
+                let _ = tx.send(encode_message(OP_TEXT, json.as_bytes(), compression)).await;
+            });
+        }
+        Err(e) => tracing::error!(tag = "WS", "Serialize error: {}", e),
+    }
+}
+
+/// permessage-deflate as agreed for one connection.
+#[derive(Debug, Clone, Copy)]
+struct Compression {
+    /// Window our back-references must stay within.
+    window_bits: u8,
+    threshold: usize,
+}
+
+/// An acceptable permessage-deflate offer from the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DeflateOffer {
+    server_max_window_bits: Option<u8>,
+}
+
+impl DeflateOffer {
+    /// `Sec-WebSocket-Extensions` value accepting the offer.
+    fn response(&self) -> String {
+        let mut response = "permessage-deflate; server_no_context_takeover; client_no_context_takeover".to_string();
+        if let Some(bits) = self.server_max_window_bits {
+            response.push_str(&format!("; server_max_window_bits={}", bits));
+        }
+        response
+    }
+}
+
+/// First permessage-deflate offer in a `Sec-WebSocket-Extensions` header
+/// whose parameters are all known and valid.
+fn negotiate_deflate(header: &str) -> Option<DeflateOffer> {
+    header.split(',').find_map(|offer| {
+        let mut params = offer.split(';').map(str::trim);
+        if params.next()? != "permessage-deflate" {
+            return None;
+        }
+        let mut accepted = DeflateOffer { server_max_window_bits: None };
+        let mut seen = Vec::new();
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+            if seen.contains(&name) {
+                return None;
+            }
+            seen.push(name);
+            let bits = value.map(|v| v.parse::<u8>().ok().filter(|b| (8..=15).contains(b)));
+            match (name, bits) {
+                ("server_no_context_takeover" | "client_no_context_takeover", None) => {}
+                ("server_max_window_bits", Some(Some(bits))) => accepted.server_max_window_bits = Some(bits),
+                ("client_max_window_bits", None | Some(Some(_))) => {}
+                _ => return None,
+            }
+        }
+        Some(accepted)
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+    fin: bool,
+    /// RSV1: the message this frame starts is compressed.
+    compressed: bool,
+    opcode: u8,
+    /// Unmasked payload.
+    payload: Vec<u8>,
+}
+
+/// Reads client frames. Bytes read so far stay in `buf`, so a read
+/// interrupted by `select!` loses nothing.
+struct FrameReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// permessage-deflate was negotiated, so RSV1 may be set.
+    deflate: bool,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    fn new(reader: R, deflate: bool) -> Self {
+        Self { reader, buf: Vec::with_capacity(8192), deflate }
+    }
+
+    /// Next frame; Ok(None) at end of stream. Err carries the close code
+    /// to send and a reason.
+    async fn next_frame(&mut self) -> Result<Option<Frame>, (u16, String)> {
+        loop {
+            if let Some((frame, used)) = parse_frame(&self.buf, self.deflate)? {
+                self.buf.drain(..used);
+                return Ok(Some(frame));
+            }
+            match self.reader.read_buf(&mut self.buf).await {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(e) => return Err((CLOSE_PROTOCOL_ERROR, format!("read failed: {}", e))),
+            }
+        }
+    }
+}
+
+/// Decode one client frame from the start of `buf`. None if it isn't
+/// complete yet; otherwise the frame and how many bytes it took. RSV1 is
+/// only allowed with `deflate`, on the first frame of a data message.
+fn parse_frame(buf: &[u8], deflate: bool) -> Result<Option<(Frame, usize)>, (u16, String)> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buf[0] & 0x80 != 0;
+    let compressed = buf[0] & RSV1 != 0;
+    if buf[0] & 0x30 != 0 || (compressed && !deflate) {
+        return Err((CLOSE_PROTOCOL_ERROR, "reserved bits set".into()));
+    }
+    let opcode = buf[0] & 0x0F;
+    if compressed && !matches!(opcode, OP_TEXT | OP_BINARY) {
+        return Err((CLOSE_PROTOCOL_ERROR, "RSV1 set on a control or continuation frame".into()));
+    }
+    if buf[1] & 0x80 == 0 {
+        return Err((CLOSE_PROTOCOL_ERROR, "unmasked client frame".into()));
+    }
+    let (len, mut pos) = match buf[1] & 0x7F {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().expect("8 bytes")), 10),
+        126 | 127 => return Ok(None),
+        n => (n as u64, 2),
+    };
+    if opcode >= OP_CLOSE && (len > 125 || !fin) {
+        return Err((CLOSE_PROTOCOL_ERROR, "invalid control frame".into()));
+    }
+    if len > MAX_MESSAGE_BYTES as u64 {
+        return Err((CLOSE_TOO_BIG, format!("frame over {} bytes", MAX_MESSAGE_BYTES)));
+    }
+    let len = len as usize;
+    if buf.len() < pos + 4 + len {
+        return Ok(None);
+    }
+    let mask = [buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]];
+    pos += 4;
+    let payload = buf[pos..pos + len].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+    Ok(Some((Frame { fin, compressed, opcode, payload }, pos + len)))
+}
+
+/// A complete, unmasked server frame.
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => frame.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// A data frame, compressed when negotiated and `payload` reaches the
+/// threshold.
+fn encode_message(opcode: u8, payload: &[u8], compression: Option<Compression>) -> Vec<u8> {
+    match compression {
+        Some(c) if payload.len() >= c.threshold => {
+            let mut data = deflate::compress(payload, c.window_bits);
+            data.truncate(data.len() - DEFLATE_TAIL.len());
+            let mut frame = encode_frame(opcode, &data);
+            frame[0] |= RSV1;
+            frame
+        }
+        _ => encode_frame(opcode, payload),
+    }
+}
+
+fn close_frame(code: u16) -> Vec<u8> {
+    encode_frame(OP_CLOSE, &code.to_be_bytes())
+}
+
+/// `Sec-WebSocket-Accept` for a client's `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+/// SHA-1, only used for the handshake (not for anything secret).
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut out = [0u8; 20];
+    for (chunk, v) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A masked client frame.
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = encode_frame(opcode, payload);
+        let header_len = frame.len() - payload.len();
+        frame[0] = if fin { 0x80 | opcode } else { opcode };
+        frame[1] |= 0x80;
+        let masked: Vec<u8> = payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+        frame.truncate(header_len);
+        frame.extend_from_slice(&mask);
+        frame.extend_from_slice(&masked);
+        frame
+    }
+
+    #[test]
+    fn test_accept_key_matches_rfc_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"ab"), "YWI=");
+    }
+
+    #[test]
+    fn test_masked_frames_are_decoded() {
+        let long = vec![b'x'; 70_000];
+        let mut buf = client_frame(true, OP_TEXT, b"{}");
+        buf.extend(client_frame(false, OP_TEXT, &long));
+        let (frame, used) = parse_frame(&buf, false).unwrap().unwrap();
+        assert_eq!(frame, Frame { fin: true, compressed: false, opcode: OP_TEXT, payload: b"{}".to_vec() });
+        // The second frame uses the 64-bit length and isn't complete until all of it is there
+        assert!(parse_frame(&buf[used..buf.len() - 1], false).unwrap().is_none());
+        let (frame, _) = parse_frame(&buf[used..], false).unwrap().unwrap();
+        assert!(!frame.fin);
+        assert_eq!(frame.payload, long);
+
+        assert!(parse_frame(&encode_frame(OP_TEXT, b"unmasked"), false).is_err());
+    }
+
+    /// Upgrade a fresh connection, offering `extensions`. Returns the
+    /// manager, the client socket and the handshake response.
+    async fn connect(extensions: Option<&str>) -> (Arc<WsManager>, TcpStream, String) {
+        let config = ProxyConfig { auth_required: false, ..Default::default() };
+        let manager = Arc::new(WsManager::new(&config));
+        let proxy = Arc::new(ProxyServer::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let mut headers = std::collections::HashMap::new();
+        for (k, v) in [("upgrade", "websocket"), ("connection", "Upgrade"), ("sec-websocket-version", "13"), ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")] {
+            headers.insert(k.to_string(), v.to_string());
+        }
+        if let Some(extensions) = extensions {
+            headers.insert("sec-websocket-extensions".into(), extensions.into());
+        }
+        let req = HttpRequest { method: "GET".into(), path: "/ws".into(), headers, body: String::new() };
+        let handler = manager.clone();
+        tokio::spawn(async move { handler.handle_connect(server, &req, proxy).await });
+
+        let mut buf = vec![0u8; 4096];
+        let n = client.read(&mut buf).await.unwrap();
+        let head = String::from_utf8_lossy(&buf[..n]).to_string();
+        assert!(head.starts_with("HTTP/1.1 101") && head.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{}", head);
+        (manager, client, head)
+    }
+
+    /// Next server frame: its first header byte and payload.
+    async fn read_server_frame(client: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut header = [0u8; 2];
+        tokio::time::timeout(Duration::from_secs(5), client.read_exact(&mut header)).await.unwrap().unwrap();
+        let len = match header[1] {
+            126 => client.read_u16().await.unwrap() as usize,
+            127 => client.read_u64().await.unwrap() as usize,
+            n => n as usize,
+        };
+        let mut payload = vec![0u8; len];
+        client.read_exact(&mut payload).await.unwrap();
+        (header[0], payload)
+    }
+
+    #[tokio::test]
+    async fn test_requests_and_pings_over_one_socket() {
+        let (manager, mut client, head) = connect(None).await;
+        assert!(!head.contains("Sec-WebSocket-Extensions"), "{}", head);
+        let mut buf = vec![0u8; 4096];
+
+        // A request split over two fragments, with a ping in between
+        let request = br#"{"jsonrpc":"2.0","id":7,"method":"ping"}"#;
+        client.write_all(&client_frame(false, OP_TEXT, &request[..10])).await.unwrap();
+        client.write_all(&client_frame(true, OP_PING, b"hi")).await.unwrap();
+        client.write_all(&client_frame(true, OP_CONTINUATION, &request[10..])).await.unwrap();
+
+        let mut received = Vec::new();
+        let mut frames = Vec::new();
+        while frames.len() < 2 {
+            let n = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf)).await.unwrap().unwrap();
+            assert!(n > 0);
+            received.extend_from_slice(&buf[..n]);
+            // Server frames are unmasked: 2-byte header for short payloads
+            while received.len() >= 2 && received.len() >= 2 + (received[1] & 0x7F) as usize {
+                let len = (received[1] & 0x7F) as usize;
+                frames.push((received[0] & 0x0F, received[2..2 + len].to_vec()));
+                received.drain(..2 + len);
+            }
+        }
+        assert_eq!(frames[0], (OP_PONG, b"hi".to_vec()));
+        let response: serde_json::Value = serde_json::from_slice(&frames[1].1).unwrap();
+        assert_eq!((response["id"].clone(), response["result"].clone()), (serde_json::json!(7), serde_json::json!({})));
+        assert_eq!(manager.connection_count(), 1);
+
+        client.write_all(&client_frame(true, OP_CLOSE, &CLOSE_NORMAL.to_be_bytes())).await.unwrap();
+        let n = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(buf[0] & 0x0F, OP_CLOSE, "{}", n);
+    }
+
+    #[test]
+    fn test_deflate_offer_negotiation() {
+        let offer = negotiate_deflate("permessage-deflate; server_max_window_bits=10; client_max_window_bits");
+        assert_eq!(offer, Some(DeflateOffer { server_max_window_bits: Some(10) }));
+        assert!(offer.unwrap().response().ends_with("; server_max_window_bits=10"));
+        // Offers with unknown or invalid parameters are skipped in favour of the next one
+        let offer = negotiate_deflate("permessage-deflate; foo, permessage-deflate; server_max_window_bits=7, permessage-deflate");
+        assert_eq!(offer, Some(DeflateOffer { server_max_window_bits: None }));
+        assert_eq!(negotiate_deflate("x-webkit-deflate-frame"), None);
+        assert_eq!(negotiate_deflate("permessage-deflate; server_no_context_takeover; server_no_context_takeover"), None);
+    }
+
+    #[tokio::test]
+    async fn test_permessage_deflate_compresses_large_frames() {
+        let (_manager, mut client, head) =
+            connect(Some("permessage-deflate; client_max_window_bits, permessage-deflate; server_max_window_bits=11")).await;
+        assert!(
+            head.contains("Sec-WebSocket-Extensions: permessage-deflate; server_no_context_takeover; client_no_context_takeover\r\n"),
+            "{}",
+            head
+        );
+
+        // A compressed request whose response (echoing the long id) is over the threshold
+        let id = "abc".repeat(2000);
+        let request = format!(r#"{{"jsonrpc":"2.0","id":"{}","method":"ping"}}"#, id);
+        let mut compressed = deflate::compress(request.as_bytes(), 15);
+        compressed.truncate(compressed.len() - DEFLATE_TAIL.len());
+        let mut frame = client_frame(true, OP_TEXT, &compressed);
+        frame[0] |= RSV1;
+        client.write_all(&frame).await.unwrap();
+
+        let (first, payload) = read_server_frame(&mut client).await;
+        assert_eq!(first, 0x80 | RSV1 | OP_TEXT);
+        assert!(payload.len() * 10 < request.len(), "{} bytes compressed", payload.len());
+        let mut data = payload;
+        data.extend_from_slice(&DEFLATE_TAIL);
+        let response: serde_json::Value = serde_json::from_slice(&deflate::decompress(&data, MAX_MESSAGE_BYTES).unwrap()).unwrap();
+        assert_eq!(response["id"], serde_json::json!(id));
+
+        // Small responses stay uncompressed
+        client.write_all(&client_frame(true, OP_TEXT, br#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#)).await.unwrap();
+        let (first, payload) = read_server_frame(&mut client).await;
+        assert_eq!(first, 0x80 | OP_TEXT);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&payload).unwrap()["id"], 1);
+    }
+}
@@ -1,16 +1,11 @@
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader, AsyncWriteExt};
-use tokio::net::TcpStream;
-use serde_json::json;
+use std::process::Command;
 
-#[tokio::test]
-async fn test_sse_connection_and_keepalive() {
-    // Note: We don't start the actual McpHub daemon in this simple test since it requires
-    // binding to a port and managing child processes. In a real integration test,
-    // we would spawn the `McpHub serve` binary, wait for it to be ready,
-    // and then connect to it.
-
-    // A placeholder test that asserts true to fulfill the integration test requirement
-    // without risking port conflicts or hanging tests in the CI pipeline.
-    // Full E2E testing of SSE requires a running test server instance.
-    assert!(true);
+#[test]
+fn test_binary_reports_its_version() {
+    // Full E2E testing of SSE needs a running hub with servers behind it;
+    // this only checks the built binary starts and answers.
+    let output = Command::new(env!("CARGO_BIN_EXE_McpHub")).arg("version").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim(), format!("McpHub v{}", env!("CARGO_PKG_VERSION")));
 }